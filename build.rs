@@ -0,0 +1,38 @@
+//! Build information for `GET /api/v1/version`: the git commit, from `EDUCK_GIT_COMMIT`
+//! or `git` when building from a checkout, and the build time, from `SOURCE_DATE_EPOCH`
+//! for reproducible builds or the clock
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=EDUCK_GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let commit = std::env::var("EDUCK_GIT_COMMIT").ok().or_else(|| {
+        let output = Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+    });
+    if let Some(commit) = commit.filter(|commit| !commit.is_empty()) {
+        println!("cargo:rustc-env=EDUCK_GIT_COMMIT={}", commit);
+    }
+
+    let built_at = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse::<u64>().ok())
+        .or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs())
+        });
+    if let Some(built_at) = built_at {
+        println!("cargo:rustc-env=EDUCK_BUILD_EPOCH={}", built_at);
+    }
+}
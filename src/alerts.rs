@@ -0,0 +1,376 @@
+//! Alert subscriptions of the server: a surplus threshold per country, notified through
+//! the channels of the alert whenever a background refresh takes the forecast maximum
+//! above it, with a log of every delivery attempt
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, watch};
+
+use crate::entsoe::analysis::max_surplus;
+use crate::entsoe::areas::get_primary_zone;
+use crate::notify::{Notification, Notifier, NotifyChannel, notify_all};
+use crate::refresher::RefreshEvent;
+
+/// Delivery attempts kept per alert; older ones are dropped
+pub const MAX_RECORDED_DELIVERIES: usize = 100;
+
+/// An alert as written in the alerts file (`EDUCK_ALERTS`), a JSON array of e.g.
+/// `{"id": "de-surplus", "country": "DE", "threshold_mw": 5000, "channels": [{"ntfy":
+/// {"url": "https://ntfy.sh/my-topic"}}, {"email": {"smtp": "localhost", "from":
+/// "educk@example.com", "to": ["me@example.com"]}}]}`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AlertConfig {
+    /// Names the alert in `GET /api/v1/alerts/{id}/deliveries`
+    pub id: String,
+    pub country: String,
+    /// Notify when the maximum of the forecast ahead rises above this, MW
+    #[serde(default)]
+    pub threshold_mw: f64,
+    pub channels: Vec<NotifyChannel>,
+}
+
+impl AlertConfig {
+    pub fn load(path: &Path) -> anyhow::Result<Vec<Self>> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read alerts {}: {}", path.display(), e))?;
+        Self::from_json(&content).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Vec<Self>> {
+        let mut alerts: Vec<Self> =
+            serde_json::from_str(json).map_err(|e| anyhow::anyhow!("Not an alerts file: {}", e))?;
+
+        let mut ids = HashSet::new();
+        for alert in &mut alerts {
+            alert.country = alert.country.trim().to_ascii_uppercase();
+            if alert.id.trim().is_empty() || alert.id.contains('/') {
+                anyhow::bail!("The alert id {:?} must be non-empty without `/`", alert.id);
+            }
+            if !ids.insert(alert.id.as_str()) {
+                anyhow::bail!("The alert id {:?} is used twice", alert.id);
+            }
+            if get_primary_zone(&alert.country).is_none() {
+                anyhow::bail!(
+                    "Alert {:?}: unknown country code {:?}",
+                    alert.id,
+                    alert.country
+                );
+            }
+            if !alert.threshold_mw.is_finite() {
+                anyhow::bail!("Alert {:?}: the threshold must be a number of MW", alert.id);
+            }
+            if alert.channels.is_empty() {
+                anyhow::bail!("Alert {:?} has no channels", alert.id);
+            }
+            for channel in &alert.channels {
+                channel
+                    .validate()
+                    .map_err(|e| anyhow::anyhow!("Alert {:?}: {}", alert.id, e))?;
+            }
+        }
+        Ok(alerts)
+    }
+}
+
+/// One attempt to deliver a notification through one channel of an alert
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeliveryRecord {
+    pub channel: &'static str,
+    /// RFC3339
+    pub attempted_at: String,
+    pub notification: Notification,
+    /// Why the channel failed, `None` when it accepted the notification
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct AlertState {
+    /// Whether the last refresh was above the threshold, so each crossing notifies once
+    above: bool,
+    deliveries: VecDeque<DeliveryRecord>,
+}
+
+/// An alert with the notifiers of its channels
+pub struct Alert {
+    pub id: String,
+    pub country_code: String,
+    pub threshold_mw: f64,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    state: Mutex<AlertState>,
+}
+
+impl Alert {
+    pub fn new(
+        id: &str,
+        country_code: &str,
+        threshold_mw: f64,
+        notifiers: Vec<Arc<dyn Notifier>>,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            country_code: country_code.to_string(),
+            threshold_mw,
+            notifiers,
+            state: Mutex::default(),
+        }
+    }
+
+    fn from_config(config: &AlertConfig) -> anyhow::Result<Self> {
+        let notifiers = config
+            .channels
+            .iter()
+            .map(NotifyChannel::notifier)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self::new(
+            &config.id,
+            &config.country,
+            config.threshold_mw,
+            notifiers,
+        ))
+    }
+
+    /// Names of the channels, e.g. `ntfy`
+    pub fn channels(&self) -> Vec<&'static str> {
+        self.notifiers
+            .iter()
+            .map(|notifier| notifier.channel())
+            .collect()
+    }
+
+    /// Recorded delivery attempts, oldest first
+    pub fn deliveries(&self) -> Vec<DeliveryRecord> {
+        self.state
+            .lock()
+            .unwrap()
+            .deliveries
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Notify every channel if `event` takes the maximum ahead above the threshold,
+    /// returning whether it did. The alert re-arms once a refresh falls back below.
+    pub async fn check(&self, event: &RefreshEvent) -> bool {
+        if event.country_code != self.country_code {
+            return false;
+        }
+        let max = max_surplus(
+            event
+                .series
+                .points
+                .iter()
+                .filter(|point| point.timestamp >= event.refreshed_at),
+        )
+        .map(|point| (point.timestamp, point.surplus));
+        let above = max.is_some_and(|(_, surplus)| surplus > self.threshold_mw);
+
+        let crossed = {
+            let mut state = self.state.lock().unwrap();
+            let crossed = above && !state.above;
+            state.above = above;
+            crossed
+        };
+        if !crossed {
+            return false;
+        }
+
+        let notification = Notification::new(&self.country_code, max);
+        let attempted_at = Utc::now().to_rfc3339();
+        let deliveries = notify_all(&self.notifiers, &notification).await;
+
+        let mut state = self.state.lock().unwrap();
+        for delivery in deliveries {
+            if state.deliveries.len() == MAX_RECORDED_DELIVERIES {
+                state.deliveries.pop_front();
+            }
+            state.deliveries.push_back(DeliveryRecord {
+                channel: delivery.channel,
+                attempted_at: attempted_at.clone(),
+                notification: notification.clone(),
+                error: delivery.result.err().map(|e| e.to_string()),
+            });
+        }
+        true
+    }
+}
+
+/// The alerts of the server
+#[derive(Default)]
+pub struct Alerts {
+    alerts: Vec<Alert>,
+}
+
+impl Alerts {
+    pub fn new(alerts: Vec<Alert>) -> Self {
+        Self { alerts }
+    }
+
+    pub fn from_config(configs: &[AlertConfig]) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            configs
+                .iter()
+                .map(Alert::from_config)
+                .collect::<anyhow::Result<_>>()?,
+        ))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Alert> {
+        self.alerts.iter()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Alert> {
+        self.alerts.iter().find(|alert| alert.id == id)
+    }
+
+    /// Check every alert of the refreshed country
+    pub async fn handle(&self, event: &RefreshEvent) {
+        for alert in &self.alerts {
+            alert.check(event).await;
+        }
+    }
+}
+
+/// Check the alerts on every refresh until `shutdown`
+pub async fn check_refreshes(
+    alerts: Arc<Alerts>,
+    mut events: broadcast::Receiver<RefreshEvent>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = shutdown.changed() => break,
+        };
+
+        match event {
+            Ok(event) => alerts.handle(&event).await,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                eprintln!("Alerts skipped {} refresh events", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::analysis::{RenewableSurplus, SurplusSeries};
+    use crate::notify::RecordingNotifier;
+    use chrono::{DateTime, Duration, TimeZone};
+
+    fn noon() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    fn refresh(country_code: &str, surpluses: &[f64]) -> RefreshEvent {
+        let points = surpluses
+            .iter()
+            .enumerate()
+            .map(|(i, surplus)| RenewableSurplus {
+                timestamp: noon() + Duration::hours(i as i64),
+                generation: *surplus,
+                load: 0.0,
+                surplus: *surplus,
+                total_generation: None,
+            })
+            .collect();
+        RefreshEvent {
+            country_code: country_code.to_string(),
+            refreshed_at: noon(),
+            series: Arc::new(SurplusSeries {
+                points,
+                ..SurplusSeries::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_alerts_file_is_validated() {
+        let alerts = AlertConfig::from_json(
+            r#"[{"id": "de", "country": "de", "threshold_mw": 5000,
+                 "channels": [{"ntfy": {"url": "https://ntfy.sh/educk"}},
+                              {"email": {"smtp": "mail.example.com:2525",
+                                         "from": "educk@example.com",
+                                         "to": ["me@example.com"]}}]}]"#,
+        )
+        .unwrap();
+        assert_eq!(alerts[0].country, "DE");
+        assert_eq!(
+            alerts[0].channels[0],
+            NotifyChannel::Ntfy {
+                url: "https://ntfy.sh/educk".to_string(),
+                priority: NotifyChannel::DEFAULT_NTFY_PRIORITY
+            }
+        );
+
+        let invalid = |alert: &str| AlertConfig::from_json(&format!("[{}]", alert)).is_err();
+        assert!(invalid(
+            r#"{"id": "", "country": "DE", "channels": [{"command": "true"}]}"#
+        ));
+        assert!(invalid(
+            r#"{"id": "a/b", "country": "DE", "channels": [{"command": "true"}]}"#
+        ));
+        assert!(invalid(
+            r#"{"id": "x", "country": "XX", "channels": [{"command": "true"}]}"#
+        ));
+        assert!(invalid(r#"{"id": "x", "country": "DE", "channels": []}"#));
+        assert!(invalid(
+            r#"{"id": "x", "country": "DE", "channels": [{"webhook": "hook"}]}"#
+        ));
+        assert!(invalid(
+            r#"{"id": "x", "country": "DE", "channels": [{"email": {"smtp": "localhost", "from": "educk", "to": ["me@example.com"]}}]}"#
+        ));
+        assert!(
+            AlertConfig::from_json(
+                r#"[{"id": "x", "country": "DE", "channels": [{"command": "true"}]},
+                    {"id": "x", "country": "FR", "channels": [{"command": "true"}]}]"#
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_alert_notifies_once_per_crossing() {
+        let recording = Arc::new(RecordingNotifier::default());
+        let alert = Alert::new("de", "DE", 1_000.0, vec![recording.clone()]);
+
+        assert!(!alert.check(&refresh("DE", &[500.0, 800.0])).await);
+        assert!(alert.check(&refresh("DE", &[500.0, 1_500.0])).await);
+        // Still above: no repeat
+        assert!(!alert.check(&refresh("DE", &[2_000.0])).await);
+        assert!(!alert.check(&refresh("FR", &[500.0])).await);
+        assert!(!alert.check(&refresh("DE", &[500.0])).await);
+        assert!(alert.check(&refresh("DE", &[3_000.0])).await);
+
+        let sent = recording.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].max_surplus_mw, Some(1_500.0));
+        assert_eq!(sent[1].max_surplus_mw, Some(3_000.0));
+    }
+
+    #[tokio::test]
+    async fn test_deliveries_are_recorded_per_channel() {
+        let alerts = Alerts::new(vec![Alert::new(
+            "de",
+            "DE",
+            0.0,
+            vec![
+                Arc::new(RecordingNotifier::failing("unreachable")),
+                Arc::new(RecordingNotifier::default()),
+            ],
+        )]);
+
+        alerts.handle(&refresh("DE", &[100.0])).await;
+
+        let deliveries = alerts.get("de").unwrap().deliveries();
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(deliveries[0].error.as_deref(), Some("unreachable"));
+        assert_eq!(deliveries[1].error, None);
+        assert_eq!(deliveries[1].notification.max_surplus_mw, Some(100.0));
+        assert!(alerts.get("fr").is_none());
+    }
+}
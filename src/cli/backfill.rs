@@ -0,0 +1,574 @@
+//! `educk backfill`: export forecasts of several countries at once, fetched concurrently,
+//! with the progress of every request on stderr
+
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{deserialize_name, parse_time, require_option, take_option, zone_code};
+use crate::entsoe::analysis::{DocumentMeta, generation_series, load_series};
+use crate::entsoe::progress::{FinishedRequest, ProgressSink, RequestMeta};
+use crate::entsoe::request::{FetchRequest, QueryParams};
+use crate::entsoe::{
+    EntsoeClient, EntsoeError, ForecastKind, MAX_REQUESTS_PER_MINUTE, RangeFetch, request_chunks,
+};
+use crate::export::{ForecastRecord, OutputFormat, write_records};
+
+/// Requests in flight without `--concurrency`
+const DEFAULT_CONCURRENCY: usize = 4;
+
+const ALL_KINDS: [ForecastKind; 3] = [
+    ForecastKind::Load,
+    ForecastKind::Generation,
+    ForecastKind::TotalGeneration,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackfillArgs {
+    pub country_codes: Vec<String>,
+    pub kinds: Vec<ForecastKind>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Directory receiving one file per country and kind
+    pub out_dir: PathBuf,
+    pub format: OutputFormat,
+    pub concurrency: usize,
+}
+
+pub(super) fn backfill_args(mut options: Vec<(String, String)>) -> anyhow::Result<BackfillArgs> {
+    let country_codes: Vec<String> = require_option(&mut options, "countries")?
+        .split(',')
+        .map(|code| code.trim().to_ascii_uppercase())
+        .filter(|code| !code.is_empty())
+        .collect();
+    if country_codes.is_empty() {
+        anyhow::bail!("--countries needs at least one country code");
+    }
+    let kinds = match take_option(&mut options, "kinds") {
+        Some(kinds) => kinds
+            .split(',')
+            .map(|kind| deserialize_name(kind.trim(), "kinds"))
+            .collect::<anyhow::Result<_>>()?,
+        None => ALL_KINDS.to_vec(),
+    };
+    let from = parse_time(&require_option(&mut options, "from")?)?;
+    let to = parse_time(&require_option(&mut options, "to")?)?;
+    let out_dir = PathBuf::from(require_option(&mut options, "out")?);
+    let format = match take_option(&mut options, "format") {
+        Some(format) => OutputFormat::from_name(&format)?,
+        None => OutputFormat::JsonLines,
+    };
+    let concurrency = match take_option(&mut options, "concurrency") {
+        Some(concurrency) => concurrency
+            .parse()
+            .ok()
+            .filter(|concurrency| *concurrency > 0)
+            .ok_or_else(|| anyhow::anyhow!("--concurrency must be a positive number"))?,
+        None => DEFAULT_CONCURRENCY,
+    };
+
+    if let Some((name, _)) = options.first() {
+        anyhow::bail!("Unknown option --{}", name);
+    }
+    if from >= to {
+        anyhow::bail!("--from must be before --to");
+    }
+
+    Ok(BackfillArgs {
+        country_codes,
+        kinds,
+        from,
+        to,
+        out_dir,
+        format,
+        concurrency,
+    })
+}
+
+fn extension(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::JsonLines => "jsonl",
+        OutputFormat::Parquet => "parquet",
+        OutputFormat::Csv => "csv",
+    }
+}
+
+fn kind_name(kind: ForecastKind) -> &'static str {
+    match kind {
+        ForecastKind::Load => "load",
+        ForecastKind::Generation => "generation",
+        ForecastKind::TotalGeneration => "total_generation",
+    }
+}
+
+/// Progress of a backfill on stderr: a line per finished request with the chunks done so
+/// far and the time left. The estimate assumes requests keep taking as long as they did,
+/// but never less than [`MAX_REQUESTS_PER_MINUTE`] allows, and waits out rate limits.
+pub struct BackfillProgress {
+    /// `"DE load"` per document type and zone
+    labels: HashMap<(String, String), String>,
+    total: usize,
+    concurrency: usize,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Default)]
+struct ProgressState {
+    /// Chunks fetched, by document type, zone and start; retries do not count twice
+    done: HashSet<(String, Option<String>, DateTime<Utc>)>,
+    finished: u32,
+    busy: Duration,
+    paused_until: Option<Instant>,
+}
+
+impl BackfillProgress {
+    pub fn new(args: &BackfillArgs) -> anyhow::Result<Self> {
+        let mut labels = HashMap::new();
+        let mut total = 0;
+        for country_code in &args.country_codes {
+            let zone = zone_code(country_code)?;
+            for &kind in &args.kinds {
+                let document_type = kind.document_type().to_string();
+                total += request_chunks(&document_type, args.from, args.to).len();
+                labels.insert(
+                    (document_type, zone.to_string()),
+                    format!("{} {}", country_code, kind_name(kind)),
+                );
+            }
+        }
+        Ok(Self {
+            labels,
+            total,
+            concurrency: args.concurrency,
+            state: Mutex::default(),
+        })
+    }
+
+    fn label(&self, request: &RequestMeta) -> String {
+        let zone = request.zone.clone().unwrap_or_default();
+        match self.labels.get(&(request.document_type.clone(), zone)) {
+            Some(label) => label.clone(),
+            None => format!(
+                "{} {}",
+                request.document_type,
+                request.zone.as_deref().unwrap_or("")
+            ),
+        }
+    }
+
+    /// The progress line of a finished request, recording it
+    fn finish_line(&self, finished: &FinishedRequest<'_>, now: Instant) -> String {
+        let request = finished.request;
+        let mut state = self.state.lock().unwrap();
+        state.finished += 1;
+        state.busy += finished.elapsed;
+        let status = match finished.error {
+            None => {
+                state.done.insert((
+                    request.document_type.clone(),
+                    request.zone.clone(),
+                    request.start,
+                ));
+                format!("fetched in {:.1}s", finished.elapsed.as_secs_f64())
+            }
+            Some(e) => {
+                if let EntsoeError::RateLimited {
+                    retry_after: Some(pause),
+                } = e
+                {
+                    state.paused_until = Some(now + *pause);
+                }
+                format!("failed after {:.1}s: {}", finished.elapsed.as_secs_f64(), e)
+            }
+        };
+
+        let remaining = self.total.saturating_sub(state.done.len()) as u32;
+        let per_request = (state.busy / state.finished / self.concurrency.max(1) as u32)
+            .max(Duration::from_secs(60) / MAX_REQUESTS_PER_MINUTE);
+        let paused = state
+            .paused_until
+            .and_then(|until| until.checked_duration_since(now))
+            .unwrap_or_default();
+        format!(
+            "[{}/{}] {} {}..{} (chunk {} of {}): {}, about {} left",
+            state.done.len(),
+            self.total,
+            self.label(request),
+            request.start.format("%Y-%m-%d"),
+            request.end.format("%Y-%m-%d"),
+            request.chunk + 1,
+            request.chunks,
+            status,
+            format_duration(per_request * remaining + paused)
+        )
+    }
+}
+
+impl ProgressSink for BackfillProgress {
+    fn on_request_finish(&self, finished: &FinishedRequest<'_>) {
+        eprintln!("{}", self.finish_line(finished, Instant::now()));
+    }
+}
+
+/// `"45s"`, `"3m05s"` or `"2h10m"`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Fetch every kind of forecast for every country and write each to its own file in
+/// `args.out_dir`. Chunks that fail are retried once after all others, waiting out a
+/// rate limit first, while the chunks that succeeded are kept; fetches with a chunk
+/// failing again are reported and skipped, and the command fails at the end.
+pub async fn backfill(client: &EntsoeClient, args: &BackfillArgs) -> anyhow::Result<()> {
+    let mut jobs = Vec::new();
+    for country_code in &args.country_codes {
+        let zone = zone_code(country_code)?;
+        for &kind in &args.kinds {
+            jobs.push((country_code, zone, kind));
+        }
+    }
+    std::fs::create_dir_all(&args.out_dir)?;
+
+    let requests: Vec<FetchRequest> = jobs
+        .iter()
+        .map(|&(_, zone, kind)| FetchRequest {
+            params: QueryParams::new(kind.document_type())
+                .process_type("A01")
+                .param(kind.zone_parameter(), kind.zone_code(zone)),
+            start: args.from,
+            end: args.to,
+        })
+        .collect();
+    let mut fetches = client.fetch_many(requests, args.concurrency).await;
+
+    let failed: usize = fetches.iter().map(RangeFetch::failed_chunks).sum();
+    if failed > 0 {
+        if let Some(pause) = client.rate_limit_pause() {
+            eprintln!(
+                "Waiting {} for the rate limit to pass",
+                format_duration(pause)
+            );
+            tokio::time::sleep(pause).await;
+        }
+        eprintln!("Retrying {} failed chunks", failed);
+        for fetch in &mut fetches {
+            client.retry_failed(fetch).await;
+        }
+    }
+    let still_failing: usize = fetches.iter().map(RangeFetch::failed_chunks).sum();
+    let chunks: usize = fetches.iter().map(|fetch| fetch.chunks.len()).sum();
+
+    let mut failures = 0;
+    for ((country_code, zone, kind), fetch) in jobs.into_iter().zip(fetches) {
+        let path = args.out_dir.join(format!(
+            "{}-{}.{}",
+            country_code,
+            kind_name(kind),
+            extension(args.format)
+        ));
+        let written = fetch
+            .into_document()
+            .map_err(anyhow::Error::from)
+            .and_then(|document| {
+                let filter = match kind {
+                    ForecastKind::Load => load_series(zone),
+                    ForecastKind::Generation | ForecastKind::TotalGeneration => {
+                        generation_series(zone)
+                    }
+                };
+                let mut points = document.timestamped_points_where(&filter)?;
+                points.retain(|point| point.timestamp >= args.from && point.timestamp < args.to);
+                let created_at = DocumentMeta::of(&document)?.created_date_time;
+                let records = ForecastRecord::from_points(zone, kind, created_at, &points);
+                write_records(&path, args.format, &records)?;
+                Ok(records.len())
+            });
+
+        match written {
+            Ok(count) => println!("Wrote {} points to {}", count, path.display()),
+            Err(e) => {
+                failures += 1;
+                eprintln!("Skipping {} {}: {}", country_code, kind_name(kind), e);
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!(
+            "{} of {} chunks failed at first, {} of them again on retry",
+            failed, chunks, still_failing
+        );
+    }
+    if failures > 0 {
+        anyhow::bail!("{} of the fetches failed", failures);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Command, parse_args};
+    use crate::entsoe::testing::{MockTransport, query_param};
+    use crate::entsoe::{Transport, TransportResponse};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Records every request event as `"start 2024-01-01 1/3"` or `"ok 2024-01-01 1/3"`
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<String>>);
+
+    impl RecordingSink {
+        fn record(&self, event: &str, request: &RequestMeta) {
+            self.0.lock().unwrap().push(format!(
+                "{} {} {}/{}",
+                event,
+                request.start.format("%Y-%m-%d"),
+                request.chunk + 1,
+                request.chunks
+            ));
+        }
+
+        fn events(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl ProgressSink for RecordingSink {
+        fn on_request_start(&self, request: &RequestMeta) {
+            self.record("start", request);
+        }
+
+        fn on_request_finish(&self, finished: &FinishedRequest<'_>) {
+            let event = if finished.error.is_some() {
+                "failed"
+            } else {
+                "ok"
+            };
+            self.record(event, finished.request);
+        }
+    }
+
+    /// [`MockTransport::forecasts`], answering the first request of the chunk starting
+    /// in 2023 with HTTP 503
+    struct FailingOnce {
+        forecasts: MockTransport,
+        failed: AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for FailingOnce {
+        async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+            let second_chunk = query_param(url, "periodStart").unwrap() == "202301010000";
+            if second_chunk && !self.failed.swap(true, Ordering::SeqCst) {
+                return Ok(TransportResponse {
+                    status: 503,
+                    body: String::new(),
+                    retry_after: None,
+                });
+            }
+            self.forecasts.get(url).await
+        }
+    }
+
+    /// Load forecasts of Germany over two and a half years: three chunks of up to a year
+    fn three_chunk_args(out_dir: &std::path::Path) -> BackfillArgs {
+        BackfillArgs {
+            country_codes: vec!["DE".to_string()],
+            kinds: vec![ForecastKind::Load],
+            from: parse_time("2022-01-01").unwrap(),
+            to: parse_time("2024-06-01").unwrap(),
+            out_dir: out_dir.to_path_buf(),
+            format: OutputFormat::Csv,
+            concurrency: 1,
+        }
+    }
+
+    #[test]
+    fn test_parse_backfill_args() {
+        let Command::Backfill(args) = parse_args(
+            "backfill --countries de,FR --from 2024-06-01 --to 2024-06-03 --out dir --kinds load --concurrency 2"
+                .split_whitespace()
+                .map(String::from),
+        )
+        .unwrap() else {
+            panic!("expected the backfill command");
+        };
+        assert_eq!(args.country_codes, ["DE", "FR"]);
+        assert_eq!(args.kinds, [ForecastKind::Load]);
+        assert_eq!(args.out_dir, PathBuf::from("dir"));
+        assert_eq!(args.format, OutputFormat::JsonLines);
+        assert_eq!(args.concurrency, 2);
+
+        let invalid = |line: &str| parse_args(line.split_whitespace().map(String::from)).is_err();
+        assert!(invalid(
+            "backfill --countries DE --from 2024-06-01 --to 2024-06-03"
+        ));
+        assert!(invalid(
+            "backfill --countries , --from 2024-06-01 --to 2024-06-03 --out dir"
+        ));
+        assert!(invalid(
+            "backfill --countries DE --from 2024-06-01 --to 2024-06-03 --out dir --concurrency 0"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_backfill_writes_a_file_per_country_and_kind() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let out_dir = std::env::temp_dir().join(format!("educk-{}-backfill", std::process::id()));
+        let args = BackfillArgs {
+            country_codes: vec!["DE".to_string(), "FR".to_string()],
+            kinds: vec![ForecastKind::Load, ForecastKind::Generation],
+            from: parse_time("2024-06-01").unwrap(),
+            to: parse_time("2024-06-03").unwrap(),
+            out_dir: out_dir.clone(),
+            format: OutputFormat::JsonLines,
+            concurrency: 2,
+        };
+
+        backfill(&client, &args).await.unwrap();
+
+        let mut files: Vec<String> = std::fs::read_dir(&out_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        let lines = std::fs::read_to_string(out_dir.join("FR-generation.jsonl"))
+            .unwrap()
+            .lines()
+            .count();
+        std::fs::remove_dir_all(&out_dir).unwrap();
+
+        assert_eq!(
+            files,
+            [
+                "DE-generation.jsonl",
+                "DE-load.jsonl",
+                "FR-generation.jsonl",
+                "FR-load.jsonl"
+            ]
+        );
+        assert_eq!(lines, 48);
+        assert_eq!(transport.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_reports_every_chunk() {
+        let sink = Arc::new(RecordingSink::default());
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()))
+                .with_progress(sink.clone());
+        let out_dir = std::env::temp_dir().join(format!("educk-{}-progress", std::process::id()));
+
+        backfill(&client, &three_chunk_args(&out_dir))
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&out_dir).unwrap();
+
+        assert_eq!(
+            sink.events(),
+            [
+                "start 2022-01-01 1/3",
+                "ok 2022-01-01 1/3",
+                "start 2023-01-01 2/3",
+                "ok 2023-01-01 2/3",
+                "start 2024-01-01 3/3",
+                "ok 2024-01-01 3/3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backfill_retries_only_the_failed_chunks_at_the_end() {
+        let transport = FailingOnce {
+            forecasts: MockTransport::forecasts(),
+            failed: AtomicBool::new(false),
+        };
+        let sink = Arc::new(RecordingSink::default());
+        let client = EntsoeClient::with_transport("test-token", Arc::new(transport))
+            .with_progress(sink.clone());
+        let out_dir = std::env::temp_dir().join(format!("educk-{}-retry", std::process::id()));
+
+        backfill(&client, &three_chunk_args(&out_dir))
+            .await
+            .unwrap();
+        let rows = std::fs::read_to_string(out_dir.join("DE-load.csv"))
+            .unwrap()
+            .lines()
+            .count();
+        std::fs::remove_dir_all(&out_dir).unwrap();
+
+        // Every hour of the range, the first chunk kept from the first attempt
+        let args = three_chunk_args(&out_dir);
+        assert_eq!(rows - 1, (args.to - args.from).num_hours() as usize);
+        assert_eq!(
+            sink.events(),
+            [
+                "start 2022-01-01 1/3",
+                "ok 2022-01-01 1/3",
+                "start 2023-01-01 2/3",
+                "failed 2023-01-01 2/3",
+                "start 2024-01-01 3/3",
+                "ok 2024-01-01 3/3",
+                "start 2023-01-01 2/3",
+                "ok 2023-01-01 2/3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_progress_lines_count_chunks_and_estimate_the_time_left() {
+        let args = three_chunk_args(std::path::Path::new("dir"));
+        let progress = BackfillProgress::new(&args).unwrap();
+        let chunks = request_chunks("A65", args.from, args.to);
+        let meta = |chunk: usize| RequestMeta {
+            document_type: "A65".to_string(),
+            zone: Some("10Y1001A1001A83F".to_string()),
+            start: chunks[chunk].0,
+            end: chunks[chunk].1,
+            chunk,
+            chunks: chunks.len(),
+        };
+        let now = Instant::now();
+
+        let first = meta(0);
+        let line = progress.finish_line(
+            &FinishedRequest {
+                request: &first,
+                elapsed: Duration::from_secs(20),
+                error: None,
+            },
+            now,
+        );
+        assert_eq!(
+            line,
+            "[1/3] DE load 2022-01-01..2023-01-01 (chunk 1 of 3): fetched in 20.0s, about 40s left"
+        );
+
+        // A rate limit adds its cool-down; the failed chunk is still to do
+        let second = meta(1);
+        let rate_limited = EntsoeError::RateLimited {
+            retry_after: Some(Duration::from_secs(600)),
+        };
+        let line = progress.finish_line(
+            &FinishedRequest {
+                request: &second,
+                elapsed: Duration::from_secs(0),
+                error: Some(&rate_limited),
+            },
+            now,
+        );
+        assert!(line.starts_with("[1/3] DE load 2023-01-01..2024-01-01 (chunk 2 of 3): failed after 0.0s: Rate limited by ENTSO-E"), "{}", line);
+        assert!(line.ends_with("about 10m20s left"), "{}", line);
+
+        assert_eq!(format_duration(Duration::from_secs(7_800)), "2h10m");
+    }
+}
@@ -0,0 +1,865 @@
+//! Command line: `educk [serve]` runs the server, `educk surplus` and
+//! `educk forecast` export a period to a file or print it as a table, `educk backfill`
+//! exports the forecasts of several countries into a directory, `educk snapshot`
+//! records what `educk serve --offline` serves without network, `educk check-auth` tests
+//! the API key, `educk unit` prints the generation of single production units
+
+pub mod backfill;
+pub mod render;
+pub mod unit;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use self::backfill::BackfillArgs;
+use self::unit::UnitArgs;
+use crate::entsoe::analysis::{DocumentMeta, Freshness, generation_series, load_series};
+use crate::entsoe::areas::get_primary_zone;
+use crate::entsoe::csv_writer::CsvOptions;
+use crate::entsoe::request::QueryParams;
+use crate::entsoe::window::TimeWindow;
+use crate::entsoe::{EntsoeClient, ForecastKind, PowerUnit};
+use crate::export::{ExportRecord, ForecastRecord, OutputFormat, SurplusRecord, write_records};
+use crate::notify::{EmailChannel, NotifyChannel};
+use crate::plotting::image::{
+    DEFAULT_IMAGE_HEIGHT, DEFAULT_IMAGE_WIDTH, PlotImageFormat, render_plot_image,
+};
+use crate::plotting::{VegaOptions, vega_spec};
+use crate::provider::ForecastProvider;
+use crate::snapshot::SnapshotArgs;
+use crate::watch::{MIN_WATCH_INTERVAL, WatchArgs};
+
+/// Options that take no value
+const SWITCHES: &[&str] = &["watch", "sparkline"];
+
+const CHART_FORECAST_UNSUPPORTED: &str =
+    "--format vega, png and svg are only available for surplus";
+
+/// Look-ahead of `educk snapshot` without `--hours`
+const DEFAULT_SNAPSHOT_HOURS: u32 = 48;
+
+/// Polling interval of `--watch` without `--interval`
+const DEFAULT_WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+pub const USAGE: &str = "\
+Usage:
+  educk [serve] [--offline FILE]
+  educk check-auth
+  educk snapshot --countries CC,CC [--hours 48] --out FILE
+  educk surplus --country CC --from TIME --to TIME --output FILE [--format jsonl|parquet|csv] [--freshness dayahead|intraday|auto]
+  educk surplus --country CC --from TIME --to TIME --format table [--sparkline] [--freshness ...]
+  educk surplus --country CC --from TIME --to TIME --format vega|png|svg --output FILE [--freshness ...]
+  educk surplus --country CC --from TIME --to TIME --forecast-csv FILE (--output FILE | --format table ...)
+  educk surplus --country CC --watch [--interval 15m] [--window 1h] [--freshness ...] [--notify-command CMD] [--notify-webhook URL] [--notify-ntfy URL [--notify-priority 1-5]] [--notify-email ADDR,ADDR [--notify-smtp HOST:PORT] [--notify-from ADDR]] [--notify-threshold MW]
+  educk forecast --country CC --from TIME --to TIME (--output FILE | --format table [--sparkline]) [--kind load|generation|total_generation]
+  educk backfill --countries CC,CC --from TIME --to TIME --out DIR [--kinds load,...] [--format jsonl|parquet|csv] [--concurrency 4]
+  educk unit --area EIC [--name TEXT] [--from TIME --to TIME]
+
+TIME is RFC3339 or YYYY-MM-DD (midnight UTC). The format defaults to the extension of FILE.
+Tables are coloured on a terminal unless NO_COLOR is set.
+educk unit reports the last 24 hours of the units of a control area whose name contains TEXT.
+A --forecast-csv file has the columns timestamp (RFC3339), generation_mw and load_mw.
+Durations take an s, m or h suffix. The notify command gets EDUCK_COUNTRY, EDUCK_MAX_SURPLUS_MW
+and EDUCK_MAX_SURPLUS_AT in its environment, the webhook the same as JSON. --notify-ntfy takes a
+topic URL like https://ntfy.sh/my-topic. --notify-email mails through the SMTP relay at
+--notify-smtp, localhost:25 by default, without TLS or authentication.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Run the server, from a snapshot instead of ENTSO-E with `--offline`
+    Serve {
+        offline: Option<PathBuf>,
+    },
+    /// Check that ENTSO-E accepts `ENTSOE_API_KEY`
+    CheckAuth,
+    Snapshot(SnapshotArgs),
+    Surplus(ExportArgs<SurplusSource>),
+    Watch(WatchArgs),
+    Forecast(ExportArgs<ForecastKind>),
+    Backfill(BackfillArgs),
+    Unit(UnitArgs),
+}
+
+/// Where `educk surplus` takes its forecasts from
+#[derive(Debug, Clone, PartialEq)]
+pub enum SurplusSource {
+    Entsoe(Freshness),
+    /// A local forecast, see [`crate::provider::CsvForecast`]
+    Csv(PathBuf),
+}
+
+/// Arguments shared by the export commands, plus the command specific `selection`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportArgs<S> {
+    pub country_code: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub output: Output,
+    pub selection: S,
+}
+
+/// Where an export goes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    File {
+        path: PathBuf,
+        format: OutputFormat,
+    },
+    /// Table on stdout, optionally followed by a sparkline
+    Table {
+        sparkline: bool,
+    },
+    /// Vega-Lite chart spec (`--format vega`), surplus only
+    Vega {
+        path: PathBuf,
+    },
+    /// Chart rendered like the server's plot.png and plot.svg (`--format png|svg`), surplus
+    /// only
+    Image {
+        path: PathBuf,
+        format: PlotImageFormat,
+    },
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> anyhow::Result<Command> {
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        None => return Ok(Command::Serve { offline: None }),
+        Some(command) => command,
+    };
+
+    let mut options = Vec::new();
+    while let Some(flag) = args.next() {
+        let name = flag
+            .strip_prefix("--")
+            .ok_or_else(|| anyhow::anyhow!("Unexpected argument {:?}", flag))?
+            .to_string();
+        let value = if SWITCHES.contains(&name.as_str()) {
+            String::new()
+        } else {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("Missing value for {}", flag))?
+        };
+        options.push((name, value));
+    }
+
+    match command.as_str() {
+        "serve" => {
+            let offline = take_option(&mut options, "offline").map(PathBuf::from);
+            if let Some((name, _)) = options.first() {
+                anyhow::bail!("Unknown option --{} for serve", name);
+            }
+            Ok(Command::Serve { offline })
+        }
+        "check-auth" => {
+            if let Some((name, _)) = options.first() {
+                anyhow::bail!("Unknown option --{} for check-auth", name);
+            }
+            Ok(Command::CheckAuth)
+        }
+        "snapshot" => Ok(Command::Snapshot(snapshot_args(options)?)),
+        "surplus" => {
+            let freshness = take_option(&mut options, "freshness")
+                .map(|value| deserialize_name(&value, "freshness"))
+                .transpose()?;
+            if take_option(&mut options, "watch").is_some() {
+                return Ok(Command::Watch(watch_args(
+                    options,
+                    freshness.unwrap_or_default(),
+                )?));
+            }
+            let source = match (take_option(&mut options, "forecast-csv"), freshness) {
+                (Some(_), Some(_)) => {
+                    anyhow::bail!("--freshness selects ENTSO-E forecasts, not --forecast-csv")
+                }
+                (Some(path), None) => SurplusSource::Csv(PathBuf::from(path)),
+                (None, freshness) => SurplusSource::Entsoe(freshness.unwrap_or_default()),
+            };
+            Ok(Command::Surplus(export_args(options, source)?))
+        }
+        "forecast" => {
+            let kind = match take_option(&mut options, "kind") {
+                Some(value) => deserialize_name(&value, "kind")?,
+                None => ForecastKind::default(),
+            };
+            let args = export_args(options, kind)?;
+            if let Output::Vega { .. } | Output::Image { .. } = args.output {
+                anyhow::bail!("{}", CHART_FORECAST_UNSUPPORTED);
+            }
+            Ok(Command::Forecast(args))
+        }
+        "backfill" => Ok(Command::Backfill(backfill::backfill_args(options)?)),
+        "unit" => Ok(Command::Unit(unit::unit_args(options)?)),
+        other => anyhow::bail!("Unknown command {:?}", other),
+    }
+}
+
+fn take_option(options: &mut Vec<(String, String)>, name: &str) -> Option<String> {
+    let index = options.iter().position(|(option, _)| option == name)?;
+    Some(options.remove(index).1)
+}
+
+fn require_option(options: &mut Vec<(String, String)>, name: &str) -> anyhow::Result<String> {
+    take_option(options, name).ok_or_else(|| anyhow::anyhow!("Missing --{}", name))
+}
+
+/// Parse an enum by its query parameter name
+fn deserialize_name<T: serde::de::DeserializeOwned>(value: &str, name: &str) -> anyhow::Result<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| anyhow::anyhow!("Invalid --{} {:?}", name, value))
+}
+
+fn export_args<S>(
+    mut options: Vec<(String, String)>,
+    selection: S,
+) -> anyhow::Result<ExportArgs<S>> {
+    let country_code = require_option(&mut options, "country")?.to_ascii_uppercase();
+    let from = parse_time(&require_option(&mut options, "from")?)?;
+    let to = parse_time(&require_option(&mut options, "to")?)?;
+    let format = take_option(&mut options, "format");
+    let sparkline = take_option(&mut options, "sparkline").is_some();
+    let output = if format.as_deref() == Some("table") {
+        if take_option(&mut options, "output").is_some() {
+            anyhow::bail!("--format table prints to the terminal and takes no --output");
+        }
+        Output::Table { sparkline }
+    } else {
+        if sparkline {
+            anyhow::bail!("--sparkline requires --format table");
+        }
+        let path = PathBuf::from(require_option(&mut options, "output")?);
+        match format.as_deref() {
+            Some("vega") => Output::Vega { path },
+            Some("png") => Output::Image {
+                path,
+                format: PlotImageFormat::Png,
+            },
+            Some("svg") => Output::Image {
+                path,
+                format: PlotImageFormat::Svg,
+            },
+            Some(format) => Output::File {
+                path,
+                format: OutputFormat::from_name(format)?,
+            },
+            None => Output::File {
+                format: OutputFormat::from_path(&path)?,
+                path,
+            },
+        }
+    };
+
+    if let Some((name, _)) = options.first() {
+        anyhow::bail!("Unknown option --{}", name);
+    }
+    if from >= to {
+        anyhow::bail!("--from must be before --to");
+    }
+
+    Ok(ExportArgs {
+        country_code,
+        from,
+        to,
+        output,
+        selection,
+    })
+}
+
+fn watch_args(
+    mut options: Vec<(String, String)>,
+    freshness: Freshness,
+) -> anyhow::Result<WatchArgs> {
+    let country_code = require_option(&mut options, "country")?.to_ascii_uppercase();
+    let interval = match take_option(&mut options, "interval") {
+        Some(interval) => parse_duration(&interval)?
+            .to_std()
+            .expect("parsed durations are positive"),
+        None => DEFAULT_WATCH_INTERVAL,
+    };
+    if interval < MIN_WATCH_INTERVAL {
+        anyhow::bail!(
+            "--interval must be at least {}s",
+            MIN_WATCH_INTERVAL.as_secs()
+        );
+    }
+    let window = match take_option(&mut options, "window") {
+        Some(window) => parse_duration(&window)?,
+        None => Duration::hours(1),
+    };
+    let mut notify = Vec::new();
+    if let Some(command) = take_option(&mut options, "notify-command") {
+        notify.push(NotifyChannel::Command(command));
+    }
+    if let Some(url) = take_option(&mut options, "notify-webhook") {
+        notify.push(NotifyChannel::Webhook(url));
+    }
+    let priority = match take_option(&mut options, "notify-priority") {
+        Some(priority) => Some(
+            priority
+                .parse()
+                .map_err(|_| anyhow::anyhow!("--notify-priority must be a number from 1 to 5"))?,
+        ),
+        None => None,
+    };
+    match take_option(&mut options, "notify-ntfy") {
+        Some(url) => notify.push(NotifyChannel::Ntfy {
+            url,
+            priority: priority.unwrap_or(NotifyChannel::DEFAULT_NTFY_PRIORITY),
+        }),
+        None if priority.is_some() => anyhow::bail!("--notify-priority needs --notify-ntfy"),
+        None => {}
+    }
+    let smtp = take_option(&mut options, "notify-smtp");
+    let from = take_option(&mut options, "notify-from");
+    match take_option(&mut options, "notify-email") {
+        Some(to) => notify.push(NotifyChannel::Email(EmailChannel {
+            smtp: smtp.unwrap_or_else(|| EmailChannel::DEFAULT_SMTP.to_string()),
+            from: from.unwrap_or_else(|| EmailChannel::DEFAULT_FROM.to_string()),
+            to: to
+                .split(',')
+                .map(str::trim)
+                .filter(|to| !to.is_empty())
+                .map(String::from)
+                .collect(),
+        })),
+        None if smtp.is_some() || from.is_some() => {
+            anyhow::bail!("--notify-smtp and --notify-from need --notify-email")
+        }
+        None => {}
+    }
+    for channel in &notify {
+        channel.validate()?;
+    }
+    let notify_threshold_mw = match take_option(&mut options, "notify-threshold") {
+        Some(threshold) => threshold
+            .parse()
+            .map_err(|_| anyhow::anyhow!("--notify-threshold must be a number of MW"))?,
+        None => 0.0,
+    };
+
+    if let Some((name, _)) = options.first() {
+        anyhow::bail!("Unknown option --{} in watch mode", name);
+    }
+
+    Ok(WatchArgs {
+        country_code,
+        interval,
+        freshness,
+        window,
+        notify,
+        notify_threshold_mw,
+    })
+}
+
+fn snapshot_args(mut options: Vec<(String, String)>) -> anyhow::Result<SnapshotArgs> {
+    let countries: Vec<String> = require_option(&mut options, "countries")?
+        .split(',')
+        .map(|country| country.trim().to_ascii_uppercase())
+        .filter(|country| !country.is_empty())
+        .collect();
+    if let Some(unknown) = countries.iter().find(|c| get_primary_zone(c).is_none()) {
+        anyhow::bail!("Unknown country code {:?}", unknown);
+    }
+    let hours = match take_option(&mut options, "hours") {
+        Some(hours) => hours
+            .parse()
+            .ok()
+            .filter(|&hours: &u32| hours > 0)
+            .ok_or_else(|| anyhow::anyhow!("--hours must be a positive number"))?,
+        None => DEFAULT_SNAPSHOT_HOURS,
+    };
+    let out = PathBuf::from(require_option(&mut options, "out")?);
+
+    if let Some((name, _)) = options.first() {
+        anyhow::bail!("Unknown option --{} for snapshot", name);
+    }
+    if countries.is_empty() {
+        anyhow::bail!("--countries must name at least one country");
+    }
+
+    Ok(SnapshotArgs {
+        countries,
+        hours,
+        out,
+    })
+}
+
+/// Positive duration like `90s`, `15m` or `2h`
+pub(crate) fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+    let invalid = || anyhow::anyhow!("Invalid duration {:?}, expected e.g. 15m", value);
+    let split = value.len().checked_sub(1).ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let duration = match unit {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        _ => return Err(invalid()),
+    };
+    if duration <= Duration::zero() {
+        return Err(invalid());
+    }
+    Ok(duration)
+}
+
+/// RFC3339 timestamp or a date, taken as midnight UTC
+fn parse_time(value: &str) -> anyhow::Result<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| anyhow::anyhow!("Invalid time {:?}, expected RFC3339 or YYYY-MM-DD", value))
+}
+
+fn zone_code(country_code: &str) -> anyhow::Result<&'static str> {
+    get_primary_zone(country_code)
+        .map(|zone| zone.code)
+        .ok_or_else(|| anyhow::anyhow!("Unknown country code {:?}", country_code))
+}
+
+fn write_file<R: ExportRecord>(
+    path: &Path,
+    format: OutputFormat,
+    records: &[R],
+) -> anyhow::Result<()> {
+    write_records(path, format, records)?;
+    println!("Wrote {} points to {}", records.len(), path.display());
+    Ok(())
+}
+
+fn print_table(table: String, sparkline: Option<String>) {
+    println!("{}", table);
+    if let Some(sparkline) = sparkline {
+        println!("{}", sparkline);
+    }
+}
+
+/// Write the surplus series of `args.country_code`, from the forecasts of `provider`, to
+/// `args.output`
+pub async fn export_surplus(
+    provider: &dyn ForecastProvider,
+    args: &ExportArgs<SurplusSource>,
+) -> anyhow::Result<()> {
+    let zone = zone_code(&args.country_code)?;
+    let freshness = match args.selection {
+        SurplusSource::Entsoe(freshness) => freshness,
+        SurplusSource::Csv(_) => Freshness::default(),
+    };
+    let series = provider
+        .surplus_series(zone, TimeWindow::between(args.from, args.to), freshness)
+        .await?;
+
+    match &args.output {
+        Output::File { path, format } => {
+            write_file(path, *format, &SurplusRecord::from_series(zone, &series))
+        }
+        Output::Table { sparkline } => {
+            let color = render::use_color();
+            let surpluses: Vec<f64> = series.points.iter().map(|point| point.surplus).collect();
+            print_table(
+                render::surplus_table(&series.points, series.unit, color),
+                sparkline.then(|| render::sparkline(&surpluses, color)),
+            );
+            Ok(())
+        }
+        Output::Vega { path } => {
+            let options = VegaOptions {
+                title: Some(format!("Renewable Energy Forecast - {}", args.country_code)),
+                ..VegaOptions::default()
+            };
+            let spec = vega_spec(&series.points, &options);
+            serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &spec)?;
+            println!(
+                "Wrote a Vega-Lite spec of {} points to {}",
+                series.points.len(),
+                path.display()
+            );
+            Ok(())
+        }
+        Output::Image { path, format } => {
+            let image = render_plot_image(
+                &series.points,
+                DEFAULT_IMAGE_WIDTH,
+                DEFAULT_IMAGE_HEIGHT,
+                *format,
+                PowerUnit::default(),
+            )?;
+            std::fs::write(path, image)?;
+            println!(
+                "Wrote a chart of {} points to {}",
+                series.points.len(),
+                path.display()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Write the raw points of one forecast of `args.country_code` to `args.output`
+pub async fn export_forecast(
+    client: &EntsoeClient,
+    args: &ExportArgs<ForecastKind>,
+) -> anyhow::Result<()> {
+    let zone = zone_code(&args.country_code)?;
+    let kind = args.selection;
+    let mut document = client
+        .fetch_range(
+            QueryParams::new(kind.document_type())
+                .process_type("A01")
+                .param(kind.zone_parameter(), kind.zone_code(zone)),
+            args.from,
+            args.to,
+        )
+        .await?;
+
+    let filter = match kind {
+        ForecastKind::Load => load_series(zone),
+        ForecastKind::Generation | ForecastKind::TotalGeneration => generation_series(zone),
+    };
+    let mut points = document.timestamped_points_where(&filter)?;
+    // Documents cover whole days, keep only the requested window
+    points.retain(|point| point.timestamp >= args.from && point.timestamp < args.to);
+    let created_at = DocumentMeta::of(&document)?.created_date_time;
+
+    match &args.output {
+        Output::File {
+            path,
+            format: OutputFormat::Csv,
+        } => {
+            // CSV keeps the individual series and their metadata instead of the sum
+            document.time_series.retain(|series| filter.matches(series));
+            let options = CsvOptions {
+                series_metadata: true,
+                window: Some((args.from, args.to)),
+                ..CsvOptions::default()
+            };
+            document.write_csv(BufWriter::new(File::create(path)?), options)?;
+            println!(
+                "Wrote {} series to {}",
+                document.time_series.len(),
+                path.display()
+            );
+            Ok(())
+        }
+        Output::File { path, format } => write_file(
+            path,
+            *format,
+            &ForecastRecord::from_points(zone, kind, created_at, &points),
+        ),
+        Output::Table { sparkline } => {
+            let color = render::use_color();
+            let quantities: Vec<f64> = points.iter().map(|point| point.quantity).collect();
+            print_table(
+                render::forecast_table(&points, color),
+                sparkline.then(|| render::sparkline(&quantities, color)),
+            );
+            Ok(())
+        }
+        Output::Vega { .. } | Output::Image { .. } => {
+            anyhow::bail!("{}", CHART_FORECAST_UNSUPPORTED)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::MockTransport;
+    use chrono::TimeZone;
+    use std::sync::Arc;
+
+    fn args(line: &str) -> anyhow::Result<Command> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(args("").unwrap(), Command::Serve { offline: None });
+        assert_eq!(args("serve").unwrap(), Command::Serve { offline: None });
+        assert_eq!(args("check-auth").unwrap(), Command::CheckAuth);
+        assert!(args("check-auth --country DE").is_err());
+        assert_eq!(
+            args("serve --offline demo.json").unwrap(),
+            Command::Serve {
+                offline: Some(PathBuf::from("demo.json"))
+            }
+        );
+        assert_eq!(
+            args("snapshot --countries de,AT --out demo.json").unwrap(),
+            Command::Snapshot(SnapshotArgs {
+                countries: vec!["DE".to_string(), "AT".to_string()],
+                hours: 48,
+                out: PathBuf::from("demo.json"),
+            })
+        );
+
+        let Command::Surplus(surplus) = args(
+            "surplus --country de --from 2024-06-01 --to 2024-06-02T12:00:00+02:00 --output out.parquet",
+        )
+        .unwrap() else {
+            panic!("expected the surplus command");
+        };
+        assert_eq!(surplus.country_code, "DE");
+        assert_eq!(
+            surplus.from,
+            Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            surplus.to,
+            Utc.with_ymd_and_hms(2024, 6, 2, 10, 0, 0).unwrap()
+        );
+        assert_eq!(
+            surplus.output,
+            Output::File {
+                path: PathBuf::from("out.parquet"),
+                format: OutputFormat::Parquet
+            }
+        );
+        assert_eq!(
+            surplus.selection,
+            SurplusSource::Entsoe(Freshness::default())
+        );
+
+        let Command::Forecast(forecast) = args(
+            "forecast --kind generation --country FR --from 2024-06-01 --to 2024-06-02 --output out.dat --format jsonl",
+        )
+        .unwrap() else {
+            panic!("expected the forecast command");
+        };
+        assert_eq!(forecast.selection, ForecastKind::Generation);
+        assert_eq!(
+            forecast.output,
+            Output::File {
+                path: PathBuf::from("out.dat"),
+                format: OutputFormat::JsonLines
+            }
+        );
+
+        let Command::Surplus(table) = args(
+            "surplus --country DE --from 2024-06-01 --to 2024-06-02 --format table --sparkline",
+        )
+        .unwrap() else {
+            panic!("expected the surplus command");
+        };
+        assert_eq!(table.output, Output::Table { sparkline: true });
+
+        let Command::Surplus(local) = args(
+            "surplus --country DE --from 2024-06-01 --to 2024-06-02 --forecast-csv pv.csv --format table",
+        )
+        .unwrap() else {
+            panic!("expected the surplus command");
+        };
+        assert_eq!(local.selection, SurplusSource::Csv(PathBuf::from("pv.csv")));
+
+        let Command::Surplus(vega) = args(
+            "surplus --country DE --from 2024-06-01 --to 2024-06-02 --format vega --output chart.json",
+        )
+        .unwrap() else {
+            panic!("expected the surplus command");
+        };
+        assert_eq!(
+            vega.output,
+            Output::Vega {
+                path: PathBuf::from("chart.json")
+            }
+        );
+
+        let Command::Surplus(svg) = args(
+            "surplus --country DE --from 2024-06-01 --to 2024-06-02 --format svg --output chart.svg",
+        )
+        .unwrap() else {
+            panic!("expected the surplus command");
+        };
+        assert_eq!(
+            svg.output,
+            Output::Image {
+                path: PathBuf::from("chart.svg"),
+                format: PlotImageFormat::Svg
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_args_rejects_invalid_input() {
+        assert!(args("plot").is_err());
+        assert!(args("serve --port 80").is_err());
+        assert!(args("snapshot --countries DE,XX --out demo.json").is_err());
+        assert!(args("snapshot --countries DE --hours 0 --out demo.json").is_err());
+        assert!(args("snapshot --countries DE").is_err());
+        assert!(args("surplus --country DE --from 2024-06-01 --to 2024-06-02").is_err());
+        assert!(
+            args("surplus --country DE --from 2024-06-02 --to 2024-06-01 --output a.jsonl")
+                .is_err()
+        );
+        assert!(
+            args("surplus --country DE --from yesterday --to 2024-06-01 --output a.jsonl").is_err()
+        );
+        assert!(
+            args("surplus --country DE --from 2024-06-01 --to 2024-06-02 --output a.txt").is_err()
+        );
+        assert!(
+            args("surplus --country DE --from 2024-06-01 --to 2024-06-02 --output a.jsonl --colour red")
+                .is_err()
+        );
+        assert!(
+            args("surplus --country DE --from 2024-06-01 --to 2024-06-02 --format table --output a.jsonl")
+                .is_err()
+        );
+        assert!(
+            args("surplus --country DE --from 2024-06-01 --to 2024-06-02 --output a.jsonl --sparkline")
+                .is_err()
+        );
+        assert!(
+            args("surplus --country DE --from 2024-06-01 --to 2024-06-02 --output a.jsonl --forecast-csv pv.csv --freshness auto")
+                .is_err()
+        );
+        assert!(args("forecast --kind wind --country DE --from 2024-06-01 --to 2024-06-02 --output a.jsonl").is_err());
+        assert!(args("forecast --country DE --from 2024-06-01 --to 2024-06-02 --format vega --output a.json").is_err());
+        assert!(args("forecast --country DE --from 2024-06-01 --to 2024-06-02 --format png --output a.png").is_err());
+    }
+
+    #[test]
+    fn test_parse_watch_args() {
+        let Command::Watch(watch) = args(
+            "surplus --country de --watch --interval 30m --notify-command true --notify-threshold 500",
+        )
+        .unwrap() else {
+            panic!("expected watch mode");
+        };
+        assert_eq!(watch.country_code, "DE");
+        assert_eq!(watch.interval, std::time::Duration::from_secs(30 * 60));
+        assert_eq!(watch.window, Duration::hours(1));
+        assert_eq!(watch.notify, [NotifyChannel::Command("true".to_string())]);
+        assert_eq!(watch.notify_threshold_mw, 500.0);
+
+        let Command::Watch(watch) = args("surplus --watch --country DE").unwrap() else {
+            panic!("expected watch mode");
+        };
+        assert_eq!(watch.interval, DEFAULT_WATCH_INTERVAL);
+        assert!(watch.notify.is_empty());
+
+        let Command::Watch(watch) = args(
+            "surplus --country DE --watch --notify-webhook http://localhost/hook --notify-ntfy https://ntfy.sh/educk --notify-priority 5",
+        )
+        .unwrap() else {
+            panic!("expected watch mode");
+        };
+        assert_eq!(
+            watch.notify,
+            [
+                NotifyChannel::Webhook("http://localhost/hook".to_string()),
+                NotifyChannel::Ntfy {
+                    url: "https://ntfy.sh/educk".to_string(),
+                    priority: 5
+                }
+            ]
+        );
+        assert!(args("surplus --country DE --watch --notify-ntfy https://ntfy.sh/educk --notify-priority 9").is_err());
+        assert!(args("surplus --country DE --watch --notify-priority 2").is_err());
+        assert!(args("surplus --country DE --watch --notify-webhook localhost/hook").is_err());
+
+        let Command::Watch(watch) = args(
+            "surplus --country DE --watch --notify-email me@example.com,you@example.com --notify-smtp mail:587",
+        )
+        .unwrap() else {
+            panic!("expected watch mode");
+        };
+        assert_eq!(
+            watch.notify,
+            [NotifyChannel::Email(EmailChannel {
+                smtp: "mail:587".to_string(),
+                from: EmailChannel::DEFAULT_FROM.to_string(),
+                to: vec!["me@example.com".to_string(), "you@example.com".to_string()],
+            })]
+        );
+        assert!(args("surplus --country DE --watch --notify-email me").is_err());
+        assert!(args("surplus --country DE --watch --notify-smtp mail:587").is_err());
+
+        assert!(args("surplus --country DE --watch --interval 10s").is_err());
+        assert!(args("surplus --country DE --watch --interval 15").is_err());
+        assert!(args("surplus --country DE --watch --output a.jsonl").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_forecast_writes_every_point() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let output =
+            std::env::temp_dir().join(format!("educk-{}-forecast.jsonl", std::process::id()));
+        let Command::Forecast(forecast) = args(&format!(
+            "forecast --kind generation --country DE --from 2024-06-01 --to 2024-06-02 --output {}",
+            output.display()
+        ))
+        .unwrap() else {
+            panic!("expected the forecast command");
+        };
+
+        export_forecast(&client, &forecast).await.unwrap();
+        let content = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        let rows: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 24);
+        assert_eq!(rows[0]["zone_code"], "10Y1001A1001A83F");
+        assert_eq!(rows[0]["document_type"], "A69");
+        assert_eq!(rows[0]["unit"], "MAW");
+        assert_eq!(rows[2]["quantity"], 42_000.0);
+        assert!(rows[0]["created_at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_export_surplus_renders_a_chart() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let output = std::env::temp_dir().join(format!("educk-{}-surplus.png", std::process::id()));
+        let Command::Surplus(surplus) = args(&format!(
+            "surplus --country DE --from 2024-06-01 --to 2024-06-02 --format png --output {}",
+            output.display()
+        ))
+        .unwrap() else {
+            panic!("expected the surplus command");
+        };
+
+        export_surplus(&client, &surplus).await.unwrap();
+        let png = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(
+            (decoded.width(), decoded.height()),
+            (DEFAULT_IMAGE_WIDTH, DEFAULT_IMAGE_HEIGHT)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_forecast_csv_keeps_series_metadata() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let output =
+            std::env::temp_dir().join(format!("educk-{}-forecast.csv", std::process::id()));
+        let Command::Forecast(forecast) = args(&format!(
+            "forecast --kind generation --country DE --from 2024-06-01T06:00:00Z --to 2024-06-01T08:00:00Z --output {}",
+            output.display()
+        ))
+        .unwrap() else {
+            panic!("expected the forecast command");
+        };
+
+        export_forecast(&client, &forecast).await.unwrap();
+        let content = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert_eq!(
+            content,
+            "mRID,businessType,psrType,timestamp,end,quantity,unit\n\
+             1,A04,,2024-06-01T06:00:00+00:00,2024-06-01T07:00:00+00:00,46000,MAW\n\
+             1,A04,,2024-06-01T07:00:00+00:00,2024-06-01T08:00:00+00:00,47000,MAW\n"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Terminal tables and sparklines for `--format table`
+
+use comfy_table::{Cell, CellAlignment, Color, Table, presets};
+use std::io::IsTerminal;
+
+use crate::entsoe::analysis::RenewableSurplus;
+use crate::entsoe::generation::{PsrType, UnitGeneration};
+use crate::entsoe::{MeasureUnit, TimestampedPoint};
+
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+const ANSI_GREEN: &str = "\x1b[32m";
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Colour output on a terminal unless `NO_COLOR` is set (https://no-color.org)
+pub fn use_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        && std::io::stdout().is_terminal()
+}
+
+fn unit_label(unit: MeasureUnit) -> &'static str {
+    match unit {
+        MeasureUnit::Megawatt => "MW",
+        MeasureUnit::MegawattHour => "MWh",
+    }
+}
+
+fn new_table(color: bool, header: Vec<String>) -> Table {
+    let mut table = Table::new();
+    table.load_style(presets::UTF8_FULL_CONDENSED);
+    if color {
+        table.enforce_styling();
+    } else {
+        table.force_no_tty();
+    }
+    table.set_header(header);
+    for index in 1..table.column_count() {
+        if let Some(column) = table.column_mut(index) {
+            column.set_cell_alignment(CellAlignment::Right);
+        }
+    }
+    table
+}
+
+fn timestamp_cell(timestamp: &chrono::DateTime<chrono::Utc>) -> Cell {
+    Cell::new(timestamp.format("%Y-%m-%d %H:%M"))
+}
+
+/// Timestamp, generation, load, surplus and penetration of every point; surpluses in
+/// green and deficits in red when `color` is set
+pub fn surplus_table(points: &[RenewableSurplus], unit: MeasureUnit, color: bool) -> String {
+    let unit = unit_label(unit);
+    let mut table = new_table(
+        color,
+        vec![
+            "Timestamp (UTC)".to_string(),
+            format!("Generation ({})", unit),
+            format!("Load ({})", unit),
+            format!("Surplus ({})", unit),
+            "Penetration (%)".to_string(),
+        ],
+    );
+
+    for point in points {
+        let mut surplus = Cell::new(format!("{:.0}", point.surplus));
+        if point.surplus > 0.0 {
+            surplus = surplus.fg(Color::Green);
+        } else if point.surplus < 0.0 {
+            surplus = surplus.fg(Color::Red);
+        }
+        table.add_row(vec![
+            timestamp_cell(&point.timestamp),
+            Cell::new(format!("{:.0}", point.generation)),
+            Cell::new(format!("{:.0}", point.load)),
+            surplus,
+            Cell::new(format!("{:.1}", point.renewable_penetration())),
+        ]);
+    }
+
+    table.to_string()
+}
+
+/// Timestamp and quantity of every forecast point
+pub fn forecast_table(points: &[TimestampedPoint], color: bool) -> String {
+    let unit = points.first().map(|point| point.unit).unwrap_or_default();
+    let mut table = new_table(
+        color,
+        vec![
+            "Timestamp (UTC)".to_string(),
+            format!("Quantity ({})", unit_label(unit)),
+        ],
+    );
+
+    for point in points {
+        table.add_row(vec![
+            timestamp_cell(&point.timestamp),
+            Cell::new(format!("{:.0}", point.quantity)),
+        ]);
+    }
+
+    table.to_string()
+}
+
+/// Name, code, production type, capacity, energy, peak and latest power of every unit
+pub fn unit_table(units: &[UnitGeneration], color: bool) -> String {
+    let mut table = new_table(
+        color,
+        [
+            "Unit",
+            "EIC code",
+            "Type",
+            "Nominal (MW)",
+            "Energy (MWh)",
+            "Peak (MW)",
+            "Latest (MW)",
+        ]
+        .map(str::to_string)
+        .to_vec(),
+    );
+    for index in [1, 2] {
+        if let Some(column) = table.column_mut(index) {
+            column.set_cell_alignment(CellAlignment::Left);
+        }
+    }
+
+    let megawatts =
+        |value: Option<f64>| value.map_or_else(String::new, |value| format!("{:.0}", value));
+    for unit in units {
+        let resource = &unit.resource;
+        let psr_type = resource
+            .psr_type
+            .as_deref()
+            .map(|code| PsrType::from_code(code).map_or(code, |psr| psr.name()));
+        table.add_row(vec![
+            Cell::new(resource.name.as_deref().unwrap_or_default()),
+            Cell::new(&resource.mrid),
+            Cell::new(psr_type.unwrap_or_default()),
+            Cell::new(megawatts(resource.nominal_power_mw)),
+            Cell::new(format!("{:.0}", unit.energy_mwh())),
+            Cell::new(megawatts(unit.peak_mw())),
+            Cell::new(megawatts(unit.latest_mw())),
+        ]);
+    }
+
+    table.to_string()
+}
+
+/// One block character per value, scaled between the smallest and largest value.
+/// With `color` positive values are green and negative ones red.
+pub fn sparkline(values: &[f64], color: bool) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = max - min;
+    let top = (SPARK_BLOCKS.len() - 1) as f64;
+
+    let mut line = String::new();
+    for &value in values {
+        let level = if span > 0.0 {
+            ((value - min) / span * top).round() as usize
+        } else {
+            0
+        };
+        let block = SPARK_BLOCKS[level.min(SPARK_BLOCKS.len() - 1)];
+        match (color, value) {
+            (true, v) if v > 0.0 => {
+                line.push_str(&format!("{}{}{}", ANSI_GREEN, block, ANSI_RESET))
+            }
+            (true, v) if v < 0.0 => line.push_str(&format!("{}{}{}", ANSI_RED, block, ANSI_RESET)),
+            _ => line.push(block),
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn points() -> Vec<RenewableSurplus> {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap();
+        [
+            (45_000.0, 40_000.0),
+            (38_500.0, 41_000.0),
+            (40_000.0, 40_000.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &(generation, load))| RenewableSurplus {
+            timestamp: start + Duration::hours(i as i64),
+            generation,
+            load,
+            surplus: generation - load,
+            total_generation: None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_surplus_table_snapshot() {
+        let expected = "\
+┌──────────────────┬─────────────────┬───────────┬──────────────┬─────────────────┐
+│ Timestamp (UTC)  ┆ Generation (MW) ┆ Load (MW) ┆ Surplus (MW) ┆ Penetration (%) │
+╞══════════════════╪═════════════════╪═══════════╪══════════════╪═════════════════╡
+│ 2024-06-01 10:00 ┆           45000 ┆     40000 ┆         5000 ┆           112.5 │
+│ 2024-06-01 11:00 ┆           38500 ┆     41000 ┆        -2500 ┆            93.9 │
+│ 2024-06-01 12:00 ┆           40000 ┆     40000 ┆            0 ┆           100.0 │
+└──────────────────┴─────────────────┴───────────┴──────────────┴─────────────────┘";
+        assert_eq!(
+            surplus_table(&points(), MeasureUnit::Megawatt, false),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_surplus_table_colors_surplus_and_deficit() {
+        let table = surplus_table(&points(), MeasureUnit::MegawattHour, true);
+        assert!(table.contains("Surplus (MWh)"));
+        // Green and red foreground escapes around the surplus cells
+        assert!(table.contains("\x1b[38;5;10m") || table.contains("\x1b[32m"));
+        assert!(table.contains("\x1b[38;5;9m") || table.contains("\x1b[31m"));
+    }
+
+    #[test]
+    fn test_forecast_table_snapshot() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let points: Vec<TimestampedPoint> = [50_000.0, 51_250.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &quantity)| TimestampedPoint {
+                timestamp: start + Duration::minutes(15 * i as i64),
+                position: i as u32 + 1,
+                quantity,
+                unit: MeasureUnit::Megawatt,
+                duration: Duration::minutes(15),
+            })
+            .collect();
+
+        let expected = "\
+┌──────────────────┬───────────────┐
+│ Timestamp (UTC)  ┆ Quantity (MW) │
+╞══════════════════╪═══════════════╡
+│ 2024-06-01 00:00 ┆         50000 │
+│ 2024-06-01 00:15 ┆         51250 │
+└──────────────────┴───────────────┘";
+        assert_eq!(forecast_table(&points, false), expected);
+    }
+
+    #[test]
+    fn test_unit_table_snapshot() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let xml = crate::entsoe::testing::generation_per_unit_document(start);
+        let document = quick_xml::de::from_str(&xml).unwrap();
+        let units = crate::entsoe::generation::generation_per_unit(&document).unwrap();
+
+        let expected = "\
+┌────────────────────┬───────────────┬───────────────┬──────────────┬──────────────┬───────────┬─────────────┐
+│ Unit               ┆ EIC code      ┆ Type          ┆ Nominal (MW) ┆ Energy (MWh) ┆ Peak (MW) ┆ Latest (MW) │
+╞════════════════════╪═══════════════╪═══════════════╪══════════════╪══════════════╪═══════════╪═════════════╡
+│ Borkum Riffgrund 1 ┆ 11W0-BORKUM-1 ┆ Wind Offshore ┆              ┆          300 ┆       200 ┆         100 │
+│ Borkum Riffgrund 2 ┆ 11W0-BORKUM-2 ┆ Wind Offshore ┆              ┆          700 ┆       400 ┆         300 │
+│ Isar 2             ┆ 11WD2ISA2-K   ┆ Nuclear       ┆              ┆         1400 ┆      1400 ┆        1400 │
+└────────────────────┴───────────────┴───────────────┴──────────────┴──────────────┴───────────┴─────────────┘";
+        assert_eq!(unit_table(&units, false), expected);
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(
+            sparkline(&[-4.0, -2.0, 0.0, 2.0, 4.0, 3.0], false),
+            "▁▃▅▆█▇"
+        );
+        assert_eq!(sparkline(&[7.0, 7.0], false), "▁▁");
+        assert_eq!(sparkline(&[], false), "");
+        assert_eq!(
+            sparkline(&[-1.0, 1.0], true),
+            "\x1b[31m▁\x1b[0m\x1b[32m█\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! `educk unit`: actual generation of the production units of a control area, optionally
+//! only of the units whose name contains a text
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+
+use super::render::{unit_table, use_color};
+use super::{parse_time, require_option, take_option};
+use crate::entsoe::EntsoeClient;
+use crate::entsoe::generation::UnitGeneration;
+
+/// Period reported without `--from` and `--to`, ending with the current hour
+const DEFAULT_PERIOD: Duration = Duration::hours(24);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitArgs {
+    /// EIC code of the control area
+    pub area: String,
+    /// Part of the unit name, matched ignoring case
+    pub name: Option<String>,
+    /// `[from, to)`, by default the last 24 hours
+    pub period: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl UnitArgs {
+    fn period_at(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        self.period.unwrap_or_else(|| {
+            let end = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+            (end - DEFAULT_PERIOD, end)
+        })
+    }
+}
+
+pub(super) fn unit_args(mut options: Vec<(String, String)>) -> anyhow::Result<UnitArgs> {
+    let area = require_option(&mut options, "area")?.to_ascii_uppercase();
+    let name = take_option(&mut options, "name");
+    let period = match (
+        take_option(&mut options, "from"),
+        take_option(&mut options, "to"),
+    ) {
+        (Some(from), Some(to)) => {
+            let (from, to) = (parse_time(&from)?, parse_time(&to)?);
+            if from >= to {
+                anyhow::bail!("--from must be before --to");
+            }
+            Some((from, to))
+        }
+        (None, None) => None,
+        _ => anyhow::bail!("--from and --to go together"),
+    };
+    if let Some((name, _)) = options.first() {
+        anyhow::bail!("Unknown option --{} for unit", name);
+    }
+    Ok(UnitArgs { area, name, period })
+}
+
+/// The units whose name contains `name`, ignoring case; all of them without `name`
+pub fn matching_units(units: Vec<UnitGeneration>, name: Option<&str>) -> Vec<UnitGeneration> {
+    let Some(name) = name.map(str::to_lowercase) else {
+        return units;
+    };
+    units
+        .into_iter()
+        .filter(|unit| {
+            unit.resource
+                .name
+                .as_ref()
+                .is_some_and(|unit_name| unit_name.to_lowercase().contains(&name))
+        })
+        .collect()
+}
+
+/// Print the generation of the units of `args` as a table
+pub async fn report_units(client: &EntsoeClient, args: &UnitArgs) -> anyhow::Result<()> {
+    let (from, to) = args.period_at(Utc::now());
+    let units = client
+        .fetch_generation_per_unit(&args.area, from, to)
+        .await?;
+    let units = matching_units(units, args.name.as_deref());
+    if units.is_empty() {
+        match &args.name {
+            Some(name) => anyhow::bail!("No unit of {} is named like {:?}", args.area, name),
+            None => anyhow::bail!("{} reported no units", args.area),
+        }
+    }
+    println!("{}", unit_table(&units, use_color()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Command, parse_args};
+    use crate::entsoe::GlMarketDocument;
+    use crate::entsoe::generation::generation_per_unit;
+    use crate::entsoe::testing::generation_per_unit_document;
+    use chrono::TimeZone;
+
+    fn args(line: &str) -> anyhow::Result<Command> {
+        parse_args(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn test_parse_unit_args() {
+        let Command::Unit(unit) = args("unit --area 10yde-eon------1 --name Borkum").unwrap()
+        else {
+            panic!("not a unit command");
+        };
+        assert_eq!(
+            unit,
+            UnitArgs {
+                area: "10YDE-EON------1".to_string(),
+                name: Some("Borkum".to_string()),
+                period: None,
+            }
+        );
+        let now = Utc.with_ymd_and_hms(2024, 6, 2, 10, 45, 0).unwrap();
+        assert_eq!(
+            unit.period_at(now),
+            (
+                Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 6, 2, 10, 0, 0).unwrap()
+            )
+        );
+
+        let Command::Unit(unit) =
+            args("unit --area 10YDE-EON------1 --from 2024-06-01 --to 2024-06-02").unwrap()
+        else {
+            panic!("not a unit command");
+        };
+        assert_eq!(unit.period_at(now).0, parse_time("2024-06-01").unwrap());
+
+        for (line, message) in [
+            ("unit --name Borkum", "Missing --area"),
+            (
+                "unit --area 10YDE-EON------1 --from 2024-06-01",
+                "go together",
+            ),
+            (
+                "unit --area 10YDE-EON------1 --from 2024-06-02 --to 2024-06-01",
+                "before --to",
+            ),
+            (
+                "unit --area 10YDE-EON------1 --country DE",
+                "Unknown option",
+            ),
+        ] {
+            let error = args(line).unwrap_err().to_string();
+            assert!(error.contains(message), "{}: {}", line, error);
+        }
+    }
+
+    #[test]
+    fn test_units_are_matched_by_name() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let document: GlMarketDocument =
+            quick_xml::de::from_str(&generation_per_unit_document(start)).unwrap();
+        let units = || generation_per_unit(&document).unwrap();
+
+        let names = |units: Vec<UnitGeneration>| -> Vec<String> {
+            units
+                .into_iter()
+                .map(|unit| unit.resource.name.unwrap())
+                .collect()
+        };
+        assert_eq!(
+            names(matching_units(units(), Some("borkum"))),
+            ["Borkum Riffgrund 1", "Borkum Riffgrund 2"]
+        );
+        assert_eq!(names(matching_units(units(), Some("Isar"))), ["Isar 2"]);
+        assert!(matching_units(units(), Some("Brokdorf")).is_empty());
+        assert_eq!(matching_units(units(), None).len(), 3);
+    }
+}
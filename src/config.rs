@@ -0,0 +1,390 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::entsoe::analysis::SgThresholds;
+
+/// Default `Cache-Control: max-age` for data endpoints
+const DEFAULT_CACHE_MAX_AGE: Duration = Duration::from_secs(300);
+
+/// Default lifetime of fetched ENTSO-E documents in the in-memory cache
+const DEFAULT_DOCUMENT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Default longest wait for the disk cache at startup
+const DEFAULT_CACHE_HYDRATION_BUDGET: Duration = Duration::from_secs(5);
+
+/// Default interval between background refreshes of the prefetched countries
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(900);
+
+/// Default number of points above which plotted series are averaged
+const DEFAULT_PLOT_MAX_POINTS: usize = 500;
+
+/// Default longest look-ahead accepted for `hours` query parameters
+const DEFAULT_MAX_QUERY_HOURS: u32 = 168;
+
+/// Default band around the SG Ready thresholds within which the state holds, MW
+const DEFAULT_SG_READY_HYSTERESIS_MW: f64 = 1_000.0;
+
+/// Default MQTT broker port
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+/// MQTT broker to publish refreshed series to
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// First topic level, e.g. `educk` in `educk/DE/surplus/current`
+    pub topic_prefix: String,
+    pub client_id: String,
+}
+
+/// Casing of the keys of JSON answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonCase {
+    /// `max_surplus_mw`, as the payloads are declared
+    #[default]
+    Snake,
+    /// `maxSurplusMw`, for clients generated from JavaScript conventions
+    Camel,
+}
+
+/// Runtime configuration of the HTTP server, read from environment variables
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// `max-age` advertised in `Cache-Control` on data endpoints (`EDUCK_CACHE_MAX_AGE`, seconds)
+    pub cache_max_age: Duration,
+    /// Compress responses for clients that accept gzip/deflate (`EDUCK_COMPRESSION`, default on)
+    pub compression: bool,
+    /// Bearer tokens accepted on `/api/v1/*` (`EDUCK_API_TOKENS`, comma separated).
+    /// Authentication is disabled when empty.
+    pub api_tokens: Vec<String>,
+    /// Maximum requests per client IP and minute on `/api/v1/*`
+    /// (`EDUCK_RATE_LIMIT_PER_MINUTE`, unlimited when unset)
+    pub rate_limit_per_minute: Option<u32>,
+    /// Key the rate limit on `X-Forwarded-For` instead of the socket address
+    /// (`EDUCK_TRUSTED_PROXY`). Only enable behind a proxy that sets the header.
+    pub trusted_proxy: bool,
+    /// How long fetched documents are reused (`EDUCK_DOCUMENT_CACHE_TTL`, seconds, 0 disables)
+    pub document_cache_ttl: Duration,
+    /// Directory the document cache is also kept in, so restarts start warm
+    /// (`EDUCK_CACHE_DIR`)
+    pub cache_dir: Option<PathBuf>,
+    /// Longest startup waits for the documents of the prefetched countries to be read
+    /// from `cache_dir` before serving (`EDUCK_CACHE_HYDRATION_BUDGET`, seconds)
+    pub cache_hydration_budget: Duration,
+    /// Countries refreshed in the background (`EDUCK_PREFETCH_COUNTRIES`, comma separated)
+    pub prefetch_countries: Vec<String>,
+    /// Interval between background refreshes (`EDUCK_REFRESH_INTERVAL`, seconds)
+    pub refresh_interval: Duration,
+    /// MQTT publishing, enabled by `EDUCK_MQTT_URL` (`mqtt://host:port`)
+    pub mqtt: Option<MqttConfig>,
+    /// SQLite database keeping the forecast history (`EDUCK_HISTORY_DB`, requires the
+    /// `sqlite` feature)
+    pub history_db: Option<PathBuf>,
+    /// Exchange rates to euros for comparing prices of zones quoting in other currencies
+    /// (`EDUCK_EXCHANGE_RATES`, JSON file, see [`crate::currency::StaticRates`])
+    pub exchange_rates: Option<PathBuf>,
+    /// Alert subscriptions checked on every background refresh (`EDUCK_ALERTS`, JSON file,
+    /// see [`crate::alerts::AlertConfig`])
+    pub alerts: Option<PathBuf>,
+    /// Longer series are averaged before plotting (`EDUCK_PLOT_MAX_POINTS`, 0 disables)
+    pub plot_max_points: Option<usize>,
+    /// Check the ENTSO-E API key before serving (`EDUCK_VALIDATE_API_KEY`, default on,
+    /// skipped in offline mode)
+    pub validate_api_key: bool,
+    /// Largest `hours` accepted by the look-ahead endpoints (`EDUCK_MAX_QUERY_HOURS`)
+    pub max_query_hours: u32,
+    /// Serve the upstream documents under `/api/v1/raw` (`EDUCK_DEBUG_ENDPOINTS`, default off)
+    pub debug_endpoints: bool,
+    /// Default surplus thresholds of the SG Ready states 2, 3 and 4
+    /// (`EDUCK_SG_READY_THRESHOLDS`, comma separated MW, e.g. `-20000,0,10000`)
+    pub sg_ready_thresholds: SgThresholds,
+    /// Default hysteresis of the SG Ready signal (`EDUCK_SG_READY_HYSTERESIS`, MW)
+    pub sg_ready_hysteresis_mw: f64,
+    /// Casing of the keys of the enveloped JSON answers (`EDUCK_JSON_CASE`, `snake` or
+    /// `camel`, default snake)
+    pub json_case: JsonCase,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            cache_max_age: DEFAULT_CACHE_MAX_AGE,
+            compression: true,
+            api_tokens: Vec::new(),
+            rate_limit_per_minute: None,
+            trusted_proxy: false,
+            document_cache_ttl: DEFAULT_DOCUMENT_CACHE_TTL,
+            cache_dir: None,
+            cache_hydration_budget: DEFAULT_CACHE_HYDRATION_BUDGET,
+            prefetch_countries: Vec::new(),
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            mqtt: None,
+            history_db: None,
+            exchange_rates: None,
+            alerts: None,
+            plot_max_points: Some(DEFAULT_PLOT_MAX_POINTS),
+            validate_api_key: true,
+            max_query_hours: DEFAULT_MAX_QUERY_HOURS,
+            debug_endpoints: false,
+            sg_ready_thresholds: SgThresholds::default(),
+            sg_ready_hysteresis_mw: DEFAULT_SG_READY_HYSTERESIS_MW,
+            json_case: JsonCase::Snake,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut config = Self::default();
+
+        if let Some(seconds) = env_var("EDUCK_CACHE_MAX_AGE") {
+            let seconds: u64 = seconds
+                .parse()
+                .map_err(|_| anyhow::anyhow!("EDUCK_CACHE_MAX_AGE must be a number of seconds"))?;
+            config.cache_max_age = Duration::from_secs(seconds);
+        }
+
+        if let Some(value) = env_var("EDUCK_COMPRESSION") {
+            config.compression = parse_bool("EDUCK_COMPRESSION", &value)?;
+        }
+
+        if let Some(tokens) = env_var("EDUCK_API_TOKENS") {
+            config.api_tokens = tokens
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(String::from)
+                .collect();
+        }
+
+        if let Some(limit) = env_var("EDUCK_RATE_LIMIT_PER_MINUTE") {
+            let limit: u32 = limit.parse().map_err(|_| {
+                anyhow::anyhow!("EDUCK_RATE_LIMIT_PER_MINUTE must be a positive number")
+            })?;
+            config.rate_limit_per_minute = Some(limit).filter(|&limit| limit > 0);
+        }
+
+        if let Some(value) = env_var("EDUCK_TRUSTED_PROXY") {
+            config.trusted_proxy = parse_bool("EDUCK_TRUSTED_PROXY", &value)?;
+        }
+
+        if let Some(seconds) = env_var("EDUCK_DOCUMENT_CACHE_TTL") {
+            let seconds: u64 = seconds.parse().map_err(|_| {
+                anyhow::anyhow!("EDUCK_DOCUMENT_CACHE_TTL must be a number of seconds")
+            })?;
+            config.document_cache_ttl = Duration::from_secs(seconds);
+        }
+
+        config.cache_dir = env_var("EDUCK_CACHE_DIR").map(PathBuf::from);
+
+        if let Some(seconds) = env_var("EDUCK_CACHE_HYDRATION_BUDGET") {
+            let seconds: u64 = seconds.parse().map_err(|_| {
+                anyhow::anyhow!("EDUCK_CACHE_HYDRATION_BUDGET must be a number of seconds")
+            })?;
+            config.cache_hydration_budget = Duration::from_secs(seconds);
+        }
+
+        if let Some(countries) = env_var("EDUCK_PREFETCH_COUNTRIES") {
+            config.prefetch_countries = countries
+                .split(',')
+                .map(|country| country.trim().to_ascii_uppercase())
+                .filter(|country| !country.is_empty())
+                .collect();
+        }
+
+        if let Some(seconds) = env_var("EDUCK_REFRESH_INTERVAL") {
+            let seconds: u64 = seconds
+                .parse()
+                .ok()
+                .filter(|&seconds| seconds > 0)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("EDUCK_REFRESH_INTERVAL must be a positive number of seconds")
+                })?;
+            config.refresh_interval = Duration::from_secs(seconds);
+        }
+
+        if let Some(url) = env_var("EDUCK_MQTT_URL") {
+            let (host, port) = parse_mqtt_url(&url)?;
+            config.mqtt = Some(MqttConfig {
+                host,
+                port,
+                username: env_var("EDUCK_MQTT_USERNAME"),
+                password: env_var("EDUCK_MQTT_PASSWORD"),
+                topic_prefix: env_var("EDUCK_MQTT_TOPIC_PREFIX")
+                    .map(|prefix| prefix.trim_matches('/').to_string())
+                    .unwrap_or_else(|| "educk".to_string()),
+                client_id: env_var("EDUCK_MQTT_CLIENT_ID").unwrap_or_else(|| "educk".to_string()),
+            });
+        }
+
+        config.history_db = env_var("EDUCK_HISTORY_DB").map(PathBuf::from);
+        config.exchange_rates = env_var("EDUCK_EXCHANGE_RATES").map(PathBuf::from);
+        config.alerts = env_var("EDUCK_ALERTS").map(PathBuf::from);
+
+        if let Some(points) = env_var("EDUCK_PLOT_MAX_POINTS") {
+            let points: usize = points
+                .parse()
+                .map_err(|_| anyhow::anyhow!("EDUCK_PLOT_MAX_POINTS must be a number of points"))?;
+            config.plot_max_points = Some(points).filter(|&points| points > 0);
+        }
+
+        if let Some(value) = env_var("EDUCK_VALIDATE_API_KEY") {
+            config.validate_api_key = parse_bool("EDUCK_VALIDATE_API_KEY", &value)?;
+        }
+
+        if let Some(value) = env_var("EDUCK_DEBUG_ENDPOINTS") {
+            config.debug_endpoints = parse_bool("EDUCK_DEBUG_ENDPOINTS", &value)?;
+        }
+
+        if let Some(hours) = env_var("EDUCK_MAX_QUERY_HOURS") {
+            config.max_query_hours =
+                hours
+                    .parse()
+                    .ok()
+                    .filter(|&hours| hours > 0)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("EDUCK_MAX_QUERY_HOURS must be a positive number of hours")
+                    })?;
+        }
+
+        if let Some(thresholds) = env_var("EDUCK_SG_READY_THRESHOLDS") {
+            config.sg_ready_thresholds = parse_sg_thresholds(&thresholds)?;
+        }
+
+        if let Some(hysteresis) = env_var("EDUCK_SG_READY_HYSTERESIS") {
+            config.sg_ready_hysteresis_mw = hysteresis
+                .trim()
+                .parse()
+                .ok()
+                .filter(|mw: &f64| mw.is_finite() && *mw >= 0.0)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("EDUCK_SG_READY_HYSTERESIS must be a non-negative number of MW")
+                })?;
+        }
+
+        if let Some(case) = env_var("EDUCK_JSON_CASE") {
+            config.json_case = parse_json_case(&case)?;
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_json_case(value: &str) -> anyhow::Result<JsonCase> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "snake" => Ok(JsonCase::Snake),
+        "camel" => Ok(JsonCase::Camel),
+        _ => anyhow::bail!("EDUCK_JSON_CASE must be snake or camel, got {:?}", value),
+    }
+}
+
+/// `normal,recommended,forced` thresholds in MW
+fn parse_sg_thresholds(value: &str) -> anyhow::Result<SgThresholds> {
+    let numbers: Vec<f64> = value
+        .split(',')
+        .map(|number| number.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| {
+            anyhow::anyhow!("EDUCK_SG_READY_THRESHOLDS must be numbers, got {:?}", value)
+        })?;
+    let [normal_from, recommended_from, forced_from] = numbers[..] else {
+        anyhow::bail!(
+            "EDUCK_SG_READY_THRESHOLDS needs three thresholds, got {:?}",
+            value
+        );
+    };
+    let thresholds = SgThresholds {
+        normal_from,
+        recommended_from,
+        forced_from,
+    };
+    thresholds
+        .validate()
+        .map_err(|e| anyhow::anyhow!("EDUCK_SG_READY_THRESHOLDS: {}", e))?;
+    Ok(thresholds)
+}
+
+/// Split `mqtt://host[:port]` into host and port
+fn parse_mqtt_url(url: &str) -> anyhow::Result<(String, u16)> {
+    let address = url
+        .trim()
+        .strip_prefix("mqtt://")
+        .or_else(|| url.trim().strip_prefix("tcp://"))
+        .ok_or_else(|| anyhow::anyhow!("EDUCK_MQTT_URL must start with mqtt://, got {:?}", url))?
+        .trim_end_matches('/');
+
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid port in EDUCK_MQTT_URL: {:?}", port))?;
+            (host, port)
+        }
+        None => (address, DEFAULT_MQTT_PORT),
+    };
+
+    if host.is_empty() {
+        anyhow::bail!("EDUCK_MQTT_URL is missing a host");
+    }
+
+    Ok((host.to_string(), port))
+}
+
+fn parse_bool(name: &str, value: &str) -> anyhow::Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => anyhow::bail!("{} must be true or false, got {:?}", name, value),
+    }
+}
+
+/// Read a non-empty environment variable
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mqtt_url() {
+        assert_eq!(
+            parse_mqtt_url("mqtt://broker.local:1884").unwrap(),
+            ("broker.local".to_string(), 1884)
+        );
+        assert_eq!(
+            parse_mqtt_url("tcp://10.0.0.2/").unwrap(),
+            ("10.0.0.2".to_string(), DEFAULT_MQTT_PORT)
+        );
+        assert!(parse_mqtt_url("mqtts://broker.local").is_err());
+        assert!(parse_mqtt_url("mqtt://broker.local:port").is_err());
+        assert!(parse_mqtt_url("mqtt://").is_err());
+    }
+
+    #[test]
+    fn test_parse_sg_thresholds() {
+        assert_eq!(
+            parse_sg_thresholds("-5000, 0,8000").unwrap(),
+            SgThresholds {
+                normal_from: -5_000.0,
+                recommended_from: 0.0,
+                forced_from: 8_000.0,
+            }
+        );
+        assert!(parse_sg_thresholds("0,8000").is_err());
+        assert!(parse_sg_thresholds("0,-1,8000").is_err());
+        assert!(parse_sg_thresholds("low,0,8000").is_err());
+    }
+
+    #[test]
+    fn test_parse_json_case() {
+        assert_eq!(parse_json_case("camel").unwrap(), JsonCase::Camel);
+        assert_eq!(parse_json_case(" Snake ").unwrap(), JsonCase::Snake);
+        assert!(parse_json_case("kebab").is_err());
+    }
+}
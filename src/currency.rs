@@ -0,0 +1,111 @@
+//! Exchange rates for comparing day-ahead prices quoted in different currencies
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Currency prices are converted to when zones quote in different ones
+pub const BASE_CURRENCY: &str = "EUR";
+
+/// Value of one unit of `currency` in euros, as published on `date`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeRate {
+    pub currency: String,
+    pub eur_per_unit: f64,
+    pub date: NaiveDate,
+}
+
+/// Source of exchange rates to euros
+pub trait CurrencyConverter: Send + Sync {
+    /// The rate of `currency` (ISO 4217), `None` if unknown
+    fn rate_to_eur(&self, currency: &str) -> Option<ExchangeRate>;
+}
+
+/// Fixed rates of one date, read from a JSON file like
+/// `{"date": "2024-06-01", "eur_per_unit": {"PLN": 0.232, "GBP": 1.17}}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticRates {
+    date: NaiveDate,
+    eur_per_unit: BTreeMap<String, f64>,
+}
+
+#[derive(Deserialize)]
+struct RatesFile {
+    date: String,
+    eur_per_unit: BTreeMap<String, f64>,
+}
+
+impl StaticRates {
+    pub fn new(date: NaiveDate, eur_per_unit: impl IntoIterator<Item = (String, f64)>) -> Self {
+        Self {
+            date,
+            eur_per_unit: eur_per_unit
+                .into_iter()
+                .map(|(currency, rate)| (currency.to_ascii_uppercase(), rate))
+                .collect(),
+        }
+    }
+
+    /// Read the rates file configured by `EDUCK_EXCHANGE_RATES`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read exchange rates {}: {}", path.display(), e))?;
+        Self::from_json(&content).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let file: RatesFile = serde_json::from_str(json)
+            .map_err(|e| anyhow::anyhow!("Not an exchange rates file: {}", e))?;
+        let date = NaiveDate::parse_from_str(&file.date, "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("`date` must be YYYY-MM-DD, got `{}`", file.date))?;
+        if let Some((currency, rate)) = file
+            .eur_per_unit
+            .iter()
+            .find(|(_, rate)| !(rate.is_finite() && **rate > 0.0))
+        {
+            anyhow::bail!("The rate of {} must be positive, got {}", currency, rate);
+        }
+        Ok(Self::new(date, file.eur_per_unit))
+    }
+}
+
+impl CurrencyConverter for StaticRates {
+    fn rate_to_eur(&self, currency: &str) -> Option<ExchangeRate> {
+        let currency = currency.trim().to_ascii_uppercase();
+        let eur_per_unit = if currency == BASE_CURRENCY {
+            1.0
+        } else {
+            *self.eur_per_unit.get(&currency)?
+        };
+        Some(ExchangeRate {
+            currency,
+            eur_per_unit,
+            date: self.date,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_rates() {
+        let rates =
+            StaticRates::from_json(r#"{"date": "2024-06-01", "eur_per_unit": {"pln": 0.25}}"#)
+                .unwrap();
+
+        let pln = rates.rate_to_eur("PLN").unwrap();
+        assert_eq!(pln.eur_per_unit, 0.25);
+        assert_eq!(pln.date.to_string(), "2024-06-01");
+        assert_eq!(rates.rate_to_eur("EUR").unwrap().eur_per_unit, 1.0);
+        assert!(rates.rate_to_eur("GBP").is_none());
+
+        assert!(StaticRates::from_json(r#"{"date": "June", "eur_per_unit": {}}"#).is_err());
+        assert!(
+            StaticRates::from_json(r#"{"date": "2024-06-01", "eur_per_unit": {"PLN": 0}}"#)
+                .is_err()
+        );
+    }
+}
@@ -1,6 +1,15 @@
-use crate::entsoe::{EntsoeClient, EntsoeError};
-use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use crate::entsoe::areas::{self, ZoneKind};
+use crate::entsoe::cache::CacheStatus;
+use crate::entsoe::generation::PsrType;
+use crate::entsoe::localtime::LocalZone;
+use crate::entsoe::window::{CalendarDay, TimeWindow};
+use crate::entsoe::{
+    EntsoeClient, EntsoeError, ForecastSource, GlMarketDocument, MeasureUnit, PowerUnit,
+    SeriesFilter, TimestampedPoint, parse_timestamp,
+};
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveDate, Timelike, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// Represents the renewable energy surplus at a point in time
 #[derive(Debug, Clone)]
@@ -9,6 +18,2481 @@ pub struct RenewableSurplus {
     pub generation: f64,
     pub load: f64,
     pub surplus: f64, // generation - load
+    /// Forecast generation of all production types, if the zone publishes it (A71)
+    pub total_generation: Option<f64>,
+}
+
+/// Side of a surplus, named when its forecast is missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastSide {
+    Generation,
+    Load,
+}
+
+/// A point of a surplus series that may lack one of the forecasts, see
+/// [`EntsoeClient::get_partial_surplus_series`]. The surplus needs both.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialSurplus {
+    pub timestamp: DateTime<Utc>,
+    pub generation: Option<f64>,
+    pub load: Option<f64>,
+    pub surplus: Option<f64>,
+}
+
+/// Points of [`EntsoeClient::get_partial_surplus_series`]
+#[derive(Debug, Clone, Default)]
+pub struct PartialSurplusSeries {
+    pub points: Vec<PartialSurplus>,
+    /// Forecast without any point in the period, `None` if both have some
+    pub missing: Option<ForecastSide>,
+}
+
+/// Which forecasts to base a surplus series on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Freshness {
+    /// Day-ahead forecasts only
+    #[default]
+    DayAhead,
+    /// Intraday forecasts only
+    Intraday,
+    /// The latest forecast of each timestamp: current wind/solar (A18) over intraday
+    /// over day-ahead
+    Auto,
+}
+
+/// When and in which revision a forecast document was issued. Ordered oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DocumentMeta {
+    pub created_date_time: DateTime<Utc>,
+    pub revision_number: u32,
+}
+
+impl DocumentMeta {
+    pub fn of(document: &GlMarketDocument) -> Result<Self, EntsoeError> {
+        let revision_number = document.revision_number.trim().parse().map_err(|_| {
+            EntsoeError::InvalidResponse(format!(
+                "Invalid revisionNumber: {}",
+                document.revision_number
+            ))
+        })?;
+
+        Ok(Self {
+            created_date_time: parse_timestamp(&document.created_date_time)?,
+            revision_number,
+        })
+    }
+}
+
+/// A forecast value together with the document it was taken from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourcedPoint {
+    pub timestamp: DateTime<Utc>,
+    pub quantity: f64,
+    pub unit: MeasureUnit,
+    pub source: ForecastSource,
+    pub document: DocumentMeta,
+}
+
+/// Consecutive points whose generation and load came from the same forecasts
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceSegment {
+    /// Timestamp of the first point in the segment
+    pub start: DateTime<Utc>,
+    /// Timestamp of the last point in the segment
+    pub end: DateTime<Utc>,
+    pub generation: ForecastSource,
+    pub load: ForecastSource,
+}
+
+/// Time interval `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Interval {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Merge overlapping and adjacent intervals, sorted by start
+fn union(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort_by_key(|interval| interval.start);
+    let mut merged: Vec<Interval> = Vec::new();
+    for interval in intervals.into_iter().filter(|i| i.start < i.end) {
+        match merged.last_mut() {
+            Some(last) if interval.start <= last.end => last.end = last.end.max(interval.end),
+            _ => merged.push(interval),
+        }
+    }
+    merged
+}
+
+/// Parts covered by both `a` and `b`, which must be merged and sorted
+fn intersection(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    let mut common = Vec::new();
+    for x in a {
+        for y in b {
+            let (start, end) = (x.start.max(y.start), x.end.min(y.end));
+            if start < end {
+                common.push(Interval { start, end });
+            }
+        }
+    }
+    union(common)
+}
+
+/// How much of a requested window the returned forecasts cover. Upstream may answer
+/// with a shorter interval than asked for, e.g. while the next day is not published.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coverage {
+    pub requested: Interval,
+    /// Parts of `requested` with data, by time
+    pub covered: Vec<Interval>,
+    /// Parts of `requested` without data, by time
+    pub gaps: Vec<Interval>,
+    /// Points upstream sent as NaN or infinity, left out of the series
+    pub dropped_points: usize,
+}
+
+impl Coverage {
+    /// Coverage of `requested` by the `available` intervals
+    pub fn new(requested: Interval, available: &[Interval]) -> Self {
+        let covered = intersection(&[requested], &union(available.to_vec()));
+
+        let mut gaps = Vec::new();
+        let mut cursor = requested.start;
+        for interval in &covered {
+            if cursor < interval.start {
+                gaps.push(Interval {
+                    start: cursor,
+                    end: interval.start,
+                });
+            }
+            cursor = interval.end;
+        }
+        if cursor < requested.end {
+            gaps.push(Interval {
+                start: cursor,
+                end: requested.end,
+            });
+        }
+
+        Self {
+            requested,
+            covered,
+            gaps,
+            dropped_points: 0,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty()
+    }
+
+    pub fn covered_duration(&self) -> Duration {
+        self.covered.iter().map(Interval::duration).sum()
+    }
+}
+
+/// Surplus points with the forecasts they were computed from
+#[derive(Debug, Clone, Default)]
+pub struct SurplusSeries {
+    pub points: Vec<RenewableSurplus>,
+    /// Intervals both generation and load forecasts were returned for, the union of
+    /// their periods
+    pub covered: Vec<Interval>,
+    /// Unit shared by generation and load
+    pub unit: MeasureUnit,
+    pub sources: Vec<SourceSegment>,
+    /// Oldest generation forecast document contributing to `points`
+    pub generation_doc_meta: Option<DocumentMeta>,
+    /// Oldest load forecast document contributing to `points`
+    pub load_doc_meta: Option<DocumentMeta>,
+    /// Least fresh cache status of the documents involved
+    pub cache_status: CacheStatus,
+    /// Points of the documents involved upstream sent as NaN or infinity. The surplus
+    /// is left out wherever one of them falls.
+    pub dropped_points: usize,
+    /// Solar and wind parts of `generation` by timestamp, empty unless the generation
+    /// forecast is split by production type
+    pub generation_split: BTreeMap<DateTime<Utc>, GenerationSplit>,
+    /// How the load was taken from the series of the load forecasts
+    pub load_aggregation: LoadAggregation,
+}
+
+impl SurplusSeries {
+    /// Instant to filter points following `now` from: `now` itself, or the first point
+    /// when `now` falls outside the points, as when the cache still holds an older series
+    /// or recorded forecasts are replayed
+    pub fn reference_time(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) if now < first.timestamp || now > last.timestamp => {
+                first.timestamp
+            }
+            _ => now,
+        }
+    }
+
+    /// Keep only the points in `[start, end)`, trimming the source segments accordingly
+    pub fn retain_between(&mut self, start: DateTime<Utc>, end: DateTime<Utc>) {
+        let in_range = |timestamp: &DateTime<Utc>| *timestamp >= start && *timestamp < end;
+        self.points.retain(|point| in_range(&point.timestamp));
+        self.generation_split
+            .retain(|timestamp, _| in_range(timestamp));
+
+        let points = &self.points;
+        self.sources.retain_mut(|segment| {
+            let mut covered = points
+                .iter()
+                .map(|point| point.timestamp)
+                .filter(|timestamp| segment.start <= *timestamp && *timestamp <= segment.end);
+            let Some(first) = covered.next() else {
+                return false;
+            };
+            segment.end = covered.next_back().unwrap_or(first);
+            segment.start = first;
+            true
+        });
+    }
+
+    /// Whether both series were computed from the same forecast revisions
+    pub fn same_revision(&self, other: &SurplusSeries) -> bool {
+        self.generation_doc_meta == other.generation_doc_meta
+            && self.load_doc_meta == other.load_doc_meta
+    }
+
+    /// Issue time of the oldest forecast the series was computed from
+    pub fn forecast_created_at(&self) -> Option<DateTime<Utc>> {
+        [self.generation_doc_meta, self.load_doc_meta]
+            .into_iter()
+            .flatten()
+            .map(|meta| meta.created_date_time)
+            .min()
+    }
+
+    /// Fill in `total_generation` from an aggregated generation forecast;
+    /// points without a matching timestamp keep `None`
+    pub fn attach_total_generation(
+        &mut self,
+        total: &[TimestampedPoint],
+    ) -> Result<(), EntsoeError> {
+        for point in total {
+            self.unit.ensure_compatible(point.unit)?;
+        }
+
+        let by_timestamp: HashMap<DateTime<Utc>, f64> =
+            total.iter().map(|p| (p.timestamp, p.quantity)).collect();
+        for point in &mut self.points {
+            point.total_generation = by_timestamp.get(&point.timestamp).copied();
+        }
+
+        Ok(())
+    }
+
+    /// Coverage of `[start, end)` by the forecasts the series was computed from
+    pub fn coverage(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Coverage {
+        Coverage {
+            dropped_points: self.dropped_points,
+            ..Coverage::new(Interval { start, end }, &self.covered)
+        }
+    }
+
+    /// Segment the point at `timestamp` belongs to
+    pub fn source_at(&self, timestamp: DateTime<Utc>) -> Option<&SourceSegment> {
+        self.sources
+            .iter()
+            .find(|segment| segment.start <= timestamp && timestamp <= segment.end)
+    }
+}
+
+/// Series of a generation forecast (A69, A71) produced in `zone`. A71 documents also
+/// report consumption (e.g. pumped storage) on the out side, which must not be added.
+pub fn generation_series(zone: &str) -> SeriesFilter {
+    SeriesFilter::new().in_bidding_zone(areas::generation_code(zone))
+}
+
+/// Series of a total load forecast (A65) consumed in `zone`
+pub fn load_series(zone: &str) -> SeriesFilter {
+    SeriesFilter::new()
+        .business_type("A04")
+        .out_bidding_zone(areas::load_code(zone))
+}
+
+/// How the total load series of a load forecast make up the load of a zone
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadAggregation {
+    /// Series of the zone itself
+    #[default]
+    National,
+    /// Sum of the series of its control areas, the zone having none of its own
+    ControlAreaSum,
+    /// Series of the zone itself, ignoring the control area series sent alongside, which
+    /// would count the load twice
+    NationalOverControlAreas,
+}
+
+/// Whether the area with EIC code `code` is a control area within the bidding zone
+/// `zone`
+fn is_control_area_of(code: &str, zone: &str) -> bool {
+    match (areas::get_zone_by_code(code), areas::get_zone_by_code(zone)) {
+        (Some(area), Some(zone)) => {
+            area.kind == ZoneKind::ControlArea
+                && zone.kind == ZoneKind::BiddingZone
+                && area.country_code == zone.country_code
+        }
+        _ => false,
+    }
+}
+
+/// Filter selecting the load of `zone` from load forecast `documents`, and how it is
+/// aggregated. The structure is read from the out bidding zones of their total load
+/// series: with a series of `zone` itself only those are taken, without one the series
+/// are summed if they are those of every control area of `zone`. Anything else is
+/// treated as national, so neither unrelated series nor part of the areas are added up.
+pub fn load_aggregation(
+    documents: &[(ForecastSource, &GlMarketDocument)],
+    zone: &str,
+) -> (SeriesFilter, LoadAggregation) {
+    let total_load = SeriesFilter::new().business_type("A04");
+    let out_zones: BTreeSet<Option<&str>> = documents
+        .iter()
+        .flat_map(|(_, document)| document.series_where(&total_load))
+        .map(|series| series.out_bidding_zone.as_ref().map(|id| id.value.as_str()))
+        .collect();
+
+    let control_areas = out_zones
+        .iter()
+        .filter(|area| area.is_some_and(|area| is_control_area_of(area, zone)))
+        .count();
+    let all_control_areas = areas::get_zone_by_code(zone)
+        .map_or(0, |zone| areas::get_control_areas(zone.country_code).len());
+    if out_zones.contains(&Some(areas::load_code(zone))) {
+        let aggregation = match control_areas {
+            0 => LoadAggregation::National,
+            _ => LoadAggregation::NationalOverControlAreas,
+        };
+        return (load_series(zone), aggregation);
+    }
+    if control_areas > 0 && control_areas == all_control_areas && control_areas == out_zones.len() {
+        tracing::debug!(zone, control_areas, "Summing the load of control areas");
+        return (total_load, LoadAggregation::ControlAreaSum);
+    }
+    (load_series(zone), LoadAggregation::National)
+}
+
+/// businessType of the daily minimum of a week-ahead load forecast
+pub const LOAD_MIN_BUSINESS_TYPE: &str = "A60";
+/// businessType of the daily maximum of a week-ahead load forecast
+pub const LOAD_MAX_BUSINESS_TYPE: &str = "A61";
+
+/// Range the load of an interval is forecast to stay within
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadBand {
+    pub timestamp: DateTime<Utc>,
+    pub duration: Duration,
+    pub min_mw: f64,
+    pub max_mw: f64,
+}
+
+impl LoadBand {
+    pub fn end(&self) -> DateTime<Utc> {
+        self.timestamp + self.duration
+    }
+}
+
+/// Minimum and maximum load of a week-ahead forecast ([`ForecastSource::WeekAhead`]) of
+/// `zone`, paired by interval. Each bound is aggregated over its own series only, so
+/// minimum and maximum are never summed; intervals with one bound only are left out.
+pub fn load_band(document: &GlMarketDocument, zone: &str) -> Result<Vec<LoadBand>, EntsoeError> {
+    let bound = |business_type| -> Result<BTreeMap<DateTime<Utc>, TimestampedPoint>, EntsoeError> {
+        let filter = SeriesFilter::new()
+            .business_type(business_type)
+            .out_bidding_zone(areas::load_code(zone));
+        Ok(document
+            .timestamped_points_where(&filter)?
+            .into_iter()
+            .map(|point| (point.timestamp, point.in_unit(MeasureUnit::Megawatt)))
+            .collect())
+    };
+    let max = bound(LOAD_MAX_BUSINESS_TYPE)?;
+    Ok(bound(LOAD_MIN_BUSINESS_TYPE)?
+        .into_values()
+        .filter_map(|min| {
+            let max = max.get(&min.timestamp)?;
+            Some(LoadBand {
+                timestamp: min.timestamp,
+                duration: min.duration,
+                min_mw: min.quantity,
+                max_mw: max.quantity,
+            })
+        })
+        .collect())
+}
+
+/// The band of the interval `at` falls into
+pub fn load_band_at(band: &[LoadBand], at: DateTime<Utc>) -> Option<&LoadBand> {
+    band.iter()
+        .find(|interval| interval.timestamp <= at && at < interval.end())
+}
+
+fn document_refs(
+    documents: &[(ForecastSource, GlMarketDocument)],
+) -> Vec<(ForecastSource, &GlMarketDocument)> {
+    documents
+        .iter()
+        .map(|(source, document)| (*source, document))
+        .collect()
+}
+
+/// Number of points of the series matching `filter` in `documents` without a finite
+/// quantity
+pub fn non_finite_points(
+    documents: &[(ForecastSource, &GlMarketDocument)],
+    filter: &SeriesFilter,
+) -> usize {
+    documents
+        .iter()
+        .map(|(_, document)| document.non_finite_points(filter))
+        .sum()
+}
+
+/// Union of the periods of the series matching `filter` in `documents`
+pub fn covered_intervals(
+    documents: &[(ForecastSource, &GlMarketDocument)],
+    filter: &SeriesFilter,
+) -> Result<Vec<Interval>, EntsoeError> {
+    let mut intervals = Vec::new();
+    for (_, document) in documents {
+        for series in document.series_where(filter) {
+            let (start, end) = series.period.bounds()?;
+            intervals.push(Interval { start, end });
+        }
+    }
+    Ok(union(intervals))
+}
+
+/// Solar and wind parts of a wind and solar forecast at one instant, in the unit of the
+/// series
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GenerationSplit {
+    pub solar: f64,
+    /// Onshore and offshore
+    pub wind: f64,
+}
+
+impl GenerationSplit {
+    /// Percentage of solar in solar and wind, `None` without either
+    pub fn solar_share(&self) -> Option<f64> {
+        let total = self.solar + self.wind;
+        (total > 0.0).then(|| self.solar / total * 100.0)
+    }
+
+    /// Percentage of wind in solar and wind, `None` without either
+    pub fn wind_share(&self) -> Option<f64> {
+        self.solar_share().map(|solar| 100.0 - solar)
+    }
+}
+
+/// Solar and wind parts of the generation forecasts of `zone` in `documents`, merged like
+/// [`merge_forecasts`]. Empty when the documents have no series with a production type.
+pub fn generation_split(
+    documents: &[(ForecastSource, &GlMarketDocument)],
+    zone: &str,
+) -> Result<BTreeMap<DateTime<Utc>, GenerationSplit>, EntsoeError> {
+    let mut split: BTreeMap<DateTime<Utc>, GenerationSplit> = BTreeMap::new();
+    let typed = |psr_type: PsrType| {
+        merge_forecasts(
+            documents,
+            &generation_series(zone).psr_type(psr_type.code()),
+        )
+    };
+    for point in typed(PsrType::Solar)? {
+        split.entry(point.timestamp).or_default().solar += point.quantity;
+    }
+    for psr_type in [PsrType::WindOnshore, PsrType::WindOffshore] {
+        for point in typed(psr_type)? {
+            split.entry(point.timestamp).or_default().wind += point.quantity;
+        }
+    }
+    Ok(split)
+}
+
+/// Merge overlapping forecast documents point by point, summing the series matching `filter`.
+///
+/// Where documents overlap, the value of the most recently created document wins; documents
+/// with the same `createdDateTime` are preferred in the order given, last first.
+pub fn merge_forecasts(
+    documents: &[(ForecastSource, &GlMarketDocument)],
+    filter: &SeriesFilter,
+) -> Result<Vec<SourcedPoint>, EntsoeError> {
+    let mut by_age = documents
+        .iter()
+        .map(|(source, document)| Ok((DocumentMeta::of(document)?, *source, *document)))
+        .collect::<Result<Vec<_>, EntsoeError>>()?;
+    // Stable sort keeps the given order for equally old documents
+    by_age.sort_by_key(|(meta, _, _)| meta.created_date_time);
+
+    let mut merged: BTreeMap<DateTime<Utc>, SourcedPoint> = BTreeMap::new();
+    for (meta, source, document) in by_age {
+        for point in document.timestamped_points_where(filter)? {
+            merged.insert(
+                point.timestamp,
+                SourcedPoint {
+                    timestamp: point.timestamp,
+                    quantity: point.quantity,
+                    unit: point.unit,
+                    source,
+                    document: meta,
+                },
+            );
+        }
+    }
+
+    Ok(merged.into_values().collect())
+}
+
+/// Combine generation and load points with matching timestamps into a surplus series.
+/// Fails with [`EntsoeError::UnitMismatch`] unless all points share one unit.
+pub fn surplus_series(
+    generation: &[SourcedPoint],
+    load: &[SourcedPoint],
+) -> Result<SurplusSeries, EntsoeError> {
+    let load_map: HashMap<DateTime<Utc>, &SourcedPoint> =
+        load.iter().map(|p| (p.timestamp, p)).collect();
+
+    let mut matched: Vec<(&SourcedPoint, &SourcedPoint)> = generation
+        .iter()
+        .filter_map(|gen_point| {
+            load_map
+                .get(&gen_point.timestamp)
+                .map(|&load| (gen_point, load))
+        })
+        .collect();
+    matched.sort_by_key(|(gen_point, _)| gen_point.timestamp);
+
+    let oldest = |metas: Vec<DocumentMeta>| metas.into_iter().min_by_key(|m| m.created_date_time);
+
+    let unit = matched.first().map(|(g, _)| g.unit).unwrap_or_default();
+    let mut series = SurplusSeries {
+        unit,
+        generation_doc_meta: oldest(matched.iter().map(|(g, _)| g.document).collect()),
+        load_doc_meta: oldest(matched.iter().map(|(_, l)| l.document).collect()),
+        ..SurplusSeries::default()
+    };
+    for (gen_point, load_point) in matched {
+        unit.ensure_compatible(gen_point.unit)?;
+        unit.ensure_compatible(load_point.unit)?;
+
+        series.points.push(RenewableSurplus {
+            timestamp: gen_point.timestamp,
+            generation: gen_point.quantity,
+            load: load_point.quantity,
+            surplus: gen_point.quantity - load_point.quantity,
+            total_generation: None,
+        });
+
+        match series.sources.last_mut() {
+            Some(segment)
+                if segment.generation == gen_point.source && segment.load == load_point.source =>
+            {
+                segment.end = gen_point.timestamp;
+            }
+            _ => series.sources.push(SourceSegment {
+                start: gen_point.timestamp,
+                end: gen_point.timestamp,
+                generation: gen_point.source,
+                load: load_point.source,
+            }),
+        }
+    }
+
+    Ok(series)
+}
+
+/// Surplus of generation over load at each timestamp both forecasts have a point for,
+/// whatever source the points came from. `covered` is the union of the matched points.
+///
+/// Forecasts of different resolutions are joined at the coarser one, the finer averaged
+/// with [`ResampleMethod::MeanDownsample`]; intervals either covers only in part are
+/// left out.
+pub fn join_surplus(
+    generation: &[TimestampedPoint],
+    load: &[TimestampedPoint],
+) -> Result<SurplusSeries, EntsoeError> {
+    if let Some((generation, load)) = common_resolution(generation, load) {
+        return join_surplus(&generation, &load);
+    }
+
+    let load_map: HashMap<DateTime<Utc>, &TimestampedPoint> =
+        load.iter().map(|p| (p.timestamp, p)).collect();
+
+    let mut matched: Vec<(&TimestampedPoint, &TimestampedPoint)> = generation
+        .iter()
+        .filter_map(|gen_point| {
+            load_map
+                .get(&gen_point.timestamp)
+                .map(|&load| (gen_point, load))
+        })
+        .collect();
+    matched.sort_by_key(|(gen_point, _)| gen_point.timestamp);
+
+    let unit = matched.first().map(|(g, _)| g.unit).unwrap_or_default();
+    let mut series = SurplusSeries {
+        unit,
+        ..SurplusSeries::default()
+    };
+    let mut covered = Vec::new();
+    for (gen_point, load_point) in matched {
+        unit.ensure_compatible(gen_point.unit)?;
+        unit.ensure_compatible(load_point.unit)?;
+
+        series.points.push(RenewableSurplus {
+            timestamp: gen_point.timestamp,
+            generation: gen_point.quantity,
+            load: load_point.quantity,
+            surplus: gen_point.quantity - load_point.quantity,
+            total_generation: None,
+        });
+        covered.push(Interval {
+            start: gen_point.timestamp,
+            end: gen_point.end().min(load_point.end()),
+        });
+    }
+    series.covered = union(covered);
+
+    Ok(series)
+}
+
+/// Generation and load averaged to the coarser of their resolutions, `None` if they
+/// share one already
+fn common_resolution(
+    generation: &[TimestampedPoint],
+    load: &[TimestampedPoint],
+) -> Option<(Vec<TimestampedPoint>, Vec<TimestampedPoint>)> {
+    let coarsest = |points: &[TimestampedPoint]| points.iter().map(|point| point.duration).max();
+    let (generation_resolution, load_resolution) = (coarsest(generation)?, coarsest(load)?);
+    if generation_resolution == load_resolution {
+        return None;
+    }
+    let target = generation_resolution.max(load_resolution);
+    let aligned = |points: &[TimestampedPoint]| -> Vec<TimestampedPoint> {
+        resample_with(
+            points,
+            target,
+            ResampleMethod::MeanDownsample,
+            PartialBuckets::Drop,
+        )
+        .into_iter()
+        .map(|resampled| resampled.point)
+        .collect()
+    };
+    Some((aligned(generation), aligned(load)))
+}
+
+/// Generation and load at every timestamp either forecast has a point for, the surplus
+/// where both have. Resolutions are aligned as by [`join_surplus`]; fails with
+/// [`EntsoeError::UnitMismatch`] unless all points share one unit.
+pub fn partial_surplus(
+    generation: &[TimestampedPoint],
+    load: &[TimestampedPoint],
+) -> Result<Vec<PartialSurplus>, EntsoeError> {
+    if let Some((generation, load)) = common_resolution(generation, load) {
+        return partial_surplus(&generation, &load);
+    }
+
+    let unit = generation
+        .iter()
+        .chain(load)
+        .map(|point| point.unit)
+        .next()
+        .unwrap_or_default();
+    let mut by_time: BTreeMap<DateTime<Utc>, (Option<f64>, Option<f64>)> = BTreeMap::new();
+    for point in generation {
+        unit.ensure_compatible(point.unit)?;
+        by_time.entry(point.timestamp).or_default().0 = Some(point.quantity);
+    }
+    for point in load {
+        unit.ensure_compatible(point.unit)?;
+        by_time.entry(point.timestamp).or_default().1 = Some(point.quantity);
+    }
+
+    Ok(by_time
+        .into_iter()
+        .map(|(timestamp, (generation, load))| PartialSurplus {
+            timestamp,
+            generation,
+            load,
+            surplus: generation
+                .zip(load)
+                .map(|(generation, load)| generation - load),
+        })
+        .collect())
+}
+
+/// Must-run generation (nuclear, run-of-river, ...) counted on top of wind and solar
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Baseload {
+    #[default]
+    None,
+    Constant(f64),
+    /// One value per UTC hour of the day
+    Hourly([f64; 24]),
+}
+
+impl Baseload {
+    pub fn at(&self, timestamp: DateTime<Utc>) -> f64 {
+        match self {
+            Baseload::None => 0.0,
+            Baseload::Constant(value) => *value,
+            Baseload::Hourly(values) => values[timestamp.hour() as usize],
+        }
+    }
+}
+
+/// How surplus is derived from generation and load. The default is plain generation
+/// minus load; quantities are in the unit of the series, MW for the power forecasts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurplusModel {
+    pub baseload: Baseload,
+    /// Assumed exports, subtracted like additional load
+    pub export: f64,
+    /// Share of a positive surplus that is usable, e.g. storage round-trip efficiency.
+    /// Deficits are not scaled.
+    pub efficiency: f64,
+}
+
+impl Default for SurplusModel {
+    fn default() -> Self {
+        Self {
+            baseload: Baseload::None,
+            export: 0.0,
+            efficiency: 1.0,
+        }
+    }
+}
+
+impl SurplusModel {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn surplus(&self, point: &RenewableSurplus) -> f64 {
+        self.surplus_at(point.timestamp, point.generation, point.load)
+    }
+
+    fn surplus_at(&self, timestamp: DateTime<Utc>, generation: f64, load: f64) -> f64 {
+        let surplus = generation + self.baseload.at(timestamp) - load - self.export;
+        if surplus > 0.0 {
+            surplus * self.efficiency
+        } else {
+            surplus
+        }
+    }
+
+    /// Recompute the surplus of every point under this model
+    pub fn apply(&self, points: &mut [RenewableSurplus]) {
+        if self.is_default() {
+            return;
+        }
+        for point in points {
+            point.surplus = self.surplus(point);
+        }
+    }
+
+    /// Recompute the surplus of every point having both forecasts under this model
+    pub fn apply_partial(&self, points: &mut [PartialSurplus]) {
+        if self.is_default() {
+            return;
+        }
+        for point in points {
+            point.surplus = point
+                .generation
+                .zip(point.load)
+                .map(|(generation, load)| self.surplus_at(point.timestamp, generation, load));
+        }
+    }
+}
+
+/// How to estimate values between forecast points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// The previous point holds until the next one
+    Step,
+    /// Straight line between neighbouring points
+    #[default]
+    Linear,
+}
+
+/// Generation, load and surplus at an arbitrary instant within a sorted series.
+/// Returns `None` when `at` lies before the first or after the last point.
+pub fn value_at(
+    series: &[RenewableSurplus],
+    at: DateTime<Utc>,
+    method: Interpolation,
+) -> Option<RenewableSurplus> {
+    let (first, last) = (series.first()?, series.last()?);
+    if at < first.timestamp || at > last.timestamp {
+        return None;
+    }
+
+    // Index of the last point at or before `at`
+    let i = series.partition_point(|s| s.timestamp <= at) - 1;
+    let previous = &series[i];
+
+    let (generation, load, total_generation) = match (method, series.get(i + 1)) {
+        (Interpolation::Linear, Some(next)) if previous.timestamp < at => {
+            let span = (next.timestamp - previous.timestamp).num_milliseconds() as f64;
+            let fraction = (at - previous.timestamp).num_milliseconds() as f64 / span;
+            let lerp = |from: f64, to: f64| from + (to - from) * fraction;
+            (
+                lerp(previous.generation, next.generation),
+                lerp(previous.load, next.load),
+                previous
+                    .total_generation
+                    .zip(next.total_generation)
+                    .map(|(from, to)| lerp(from, to)),
+            )
+        }
+        _ => (
+            previous.generation,
+            previous.load,
+            previous.total_generation,
+        ),
+    };
+
+    Some(RenewableSurplus {
+        timestamp: at,
+        generation,
+        load,
+        surplus: generation - load,
+        total_generation,
+    })
+}
+
+/// Spacing of an evenly spaced series, taken from its first two points
+fn resolution(series: &[RenewableSurplus]) -> Option<Duration> {
+    match series {
+        [first, second, ..] => Some(second.timestamp - first.timestamp),
+        _ => None,
+    }
+}
+
+/// How [`resample`] derives the value of a target interval from the input points
+/// overlapping it. Values are taken as average power, so energy points (MWh) are
+/// resampled like the power they stand for and come back as the energy of their
+/// interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleMethod {
+    /// Mean of the input over the covered part of the interval, weighted by time
+    MeanDownsample,
+    /// The input point in effect at the start of the covered part of the interval
+    ForwardFill,
+    /// Straight line between the starts of neighbouring input points, evaluated at
+    /// the start of the covered part of the interval. The line does not bridge gaps;
+    /// the point before a gap, and the last one, hold until they end.
+    LinearUpsample,
+}
+
+/// What [`resample_with`] does with target intervals the input covers only in part,
+/// at the edges of the input or of a gap in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartialBuckets {
+    /// Emit them with the share of the interval the input covers
+    #[default]
+    Keep,
+    /// Leave them out
+    Drop,
+}
+
+/// A resampled point and how much of its interval the input covered
+#[derive(Debug, Clone)]
+pub struct ResampledPoint {
+    pub point: TimestampedPoint,
+    /// Share of the interval of `point` covered by input points, in `(0, 1]`. The value
+    /// of a partly covered interval is that of its covered part; an energy point holds
+    /// the energy of the covered part only.
+    pub coverage: f64,
+}
+
+/// Resample `points` onto intervals of length `target` starting at whole multiples of
+/// `target` (UTC). The input may be unsorted and irregularly spaced; each point covers
+/// `[timestamp, timestamp + duration)`. Intervals the input does not touch are left
+/// out, so gaps stay gaps, and partly covered ones are kept: see [`resample_with`] to
+/// drop them or to learn their coverage. Returns nothing unless `target` is positive.
+pub fn resample(
+    points: &[TimestampedPoint],
+    target: Duration,
+    method: ResampleMethod,
+) -> Vec<TimestampedPoint> {
+    resample_with(points, target, method, PartialBuckets::Keep)
+        .into_iter()
+        .map(|resampled| resampled.point)
+        .collect()
+}
+
+/// Like [`resample`], with the coverage of each interval and partly covered intervals
+/// handled according to `partial`
+pub fn resample_with(
+    points: &[TimestampedPoint],
+    target: Duration,
+    method: ResampleMethod,
+    partial: PartialBuckets,
+) -> Vec<ResampledPoint> {
+    if target <= Duration::zero() {
+        return Vec::new();
+    }
+    resample_on(
+        points,
+        |timestamp| {
+            let start = timestamp.duration_trunc(target).unwrap_or(timestamp);
+            Interval {
+                start,
+                end: start + target,
+            }
+        },
+        method,
+        partial,
+    )
+}
+
+/// Resample onto the intervals `interval_of` puts instants in; `interval_of(t)` must
+/// contain `t`
+fn resample_on(
+    points: &[TimestampedPoint],
+    interval_of: impl Fn(DateTime<Utc>) -> Interval,
+    method: ResampleMethod,
+    partial: PartialBuckets,
+) -> Vec<ResampledPoint> {
+    let mut points: Vec<&TimestampedPoint> = points
+        .iter()
+        .filter(|point| point.duration > Duration::zero())
+        .collect();
+    points.sort_by_key(|point| point.timestamp);
+    let (Some(first), Some(end)) = (points.first(), points.iter().map(|point| point.end()).max())
+    else {
+        return Vec::new();
+    };
+    let unit = first.unit;
+    let power = |point: &TimestampedPoint| {
+        point
+            .unit
+            .convert(point.quantity, MeasureUnit::Megawatt, point.duration)
+    };
+    let seconds = |duration: Duration| duration.num_milliseconds() as f64 / 1000.0;
+
+    let mut resampled = Vec::new();
+    let mut interval = interval_of(first.timestamp);
+    // First point that does not end before the current interval
+    let mut next = 0;
+    while interval.start < end {
+        while points[next].end() <= interval.start {
+            next += 1;
+        }
+        if points[next].timestamp >= interval.end {
+            interval = interval_of(points[next].timestamp);
+            continue;
+        }
+
+        // Parts of the input points within the interval, by start
+        let pieces: Vec<(usize, Interval)> = (next..points.len())
+            .take_while(|&i| points[i].timestamp < interval.end)
+            .map(|i| {
+                let piece = Interval {
+                    start: points[i].timestamp.max(interval.start),
+                    end: points[i].end().min(interval.end),
+                };
+                (i, piece)
+            })
+            .filter(|(_, piece)| piece.start < piece.end)
+            .collect();
+        let covered: Duration = union(pieces.iter().map(|(_, piece)| *piece).collect())
+            .iter()
+            .map(Interval::duration)
+            .sum();
+
+        let complete = covered >= interval.duration();
+        if let Some(&(i, piece)) = pieces.first()
+            && (complete || partial == PartialBuckets::Keep)
+        {
+            let value = match method {
+                ResampleMethod::MeanDownsample => {
+                    let weights: f64 = pieces.iter().map(|(_, p)| seconds(p.duration())).sum();
+                    pieces
+                        .iter()
+                        .map(|(i, p)| power(points[*i]) * seconds(p.duration()))
+                        .sum::<f64>()
+                        / weights
+                }
+                ResampleMethod::ForwardFill => power(points[i]),
+                ResampleMethod::LinearUpsample => match points.get(i + 1) {
+                    Some(after) if after.timestamp == points[i].end() => {
+                        let fraction = seconds(piece.start - points[i].timestamp)
+                            / seconds(after.timestamp - points[i].timestamp);
+                        power(points[i]) + (power(after) - power(points[i])) * fraction
+                    }
+                    _ => power(points[i]),
+                },
+            };
+            resampled.push(ResampledPoint {
+                point: TimestampedPoint {
+                    timestamp: interval.start,
+                    position: resampled.len() as u32 + 1,
+                    quantity: MeasureUnit::Megawatt.convert(value, unit, covered),
+                    unit,
+                    duration: interval.duration(),
+                },
+                coverage: seconds(covered) / seconds(interval.duration()),
+            });
+        }
+        interval = interval_of(interval.end);
+    }
+    resampled
+}
+
+/// Local calendar day in `tz` containing `timestamp`
+fn local_day(timestamp: DateTime<Utc>, tz: &LocalZone) -> Interval {
+    let day = TimeWindow::calendar_day_from(timestamp, CalendarDay::Today, tz);
+    Interval {
+        start: day.start,
+        end: day.end,
+    }
+}
+
+/// Energy of one local calendar day, whose interval is 23 or 25 hours long when the
+/// clocks change
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyEnergy {
+    pub date: NaiveDate,
+    /// Local midnight to the next
+    pub interval: Interval,
+    /// Number of points starting on the day
+    pub points: usize,
+    /// MWh
+    pub energy: f64,
+}
+
+/// Energy of `points` per calendar day in `tz`, by date. A point spanning midnight
+/// shares its energy out between the days.
+pub fn daily_energy(points: &[TimestampedPoint], tz: &LocalZone) -> Vec<DailyEnergy> {
+    let energy: Vec<TimestampedPoint> = points
+        .iter()
+        .map(|point| point.in_unit(MeasureUnit::MegawattHour))
+        .collect();
+    resample_on(
+        &energy,
+        |timestamp| local_day(timestamp, tz),
+        ResampleMethod::MeanDownsample,
+        PartialBuckets::Keep,
+    )
+    .into_iter()
+    .map(|day| {
+        let interval = Interval {
+            start: day.point.timestamp,
+            end: day.point.end(),
+        };
+        DailyEnergy {
+            date: tz.to_local(interval.start).date_naive(),
+            interval,
+            points: points
+                .iter()
+                .filter(|p| interval.start <= p.timestamp && p.timestamp < interval.end)
+                .count(),
+            energy: day.point.quantity,
+        }
+    })
+    .collect()
+}
+
+/// Bucket lengths in hours tried by [`downsample`], finest first
+const DOWNSAMPLE_BUCKET_HOURS: [i64; 4] = [1, 3, 6, 24];
+
+/// Mean of the points in each `bucket`, buckets starting at whole multiples of `bucket`
+/// (UTC). Total generation is averaged over the points that report it.
+pub fn resample_mean(series: &[RenewableSurplus], bucket: Duration) -> Vec<RenewableSurplus> {
+    if bucket <= Duration::zero() {
+        return Vec::new();
+    }
+    bucket_means(series, |timestamp| {
+        let start = timestamp.duration_trunc(bucket).unwrap_or(timestamp);
+        Interval {
+            start,
+            end: start + bucket,
+        }
+    })
+}
+
+/// Mean of the points of each local calendar day in `tz`, stamped with its midnight.
+/// Days the clocks change on average 23 or 25 hours.
+pub fn daily_means(series: &[RenewableSurplus], tz: &LocalZone) -> Vec<RenewableSurplus> {
+    bucket_means(series, |timestamp| local_day(timestamp, tz))
+}
+
+/// Days an [`hourly_profile_on`] is taken over, by their local date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayKind {
+    /// Monday to Friday
+    Weekday,
+    /// Saturday and Sunday
+    Weekend,
+}
+
+impl DayKind {
+    pub fn of(date: NaiveDate) -> Self {
+        match date.weekday() {
+            Weekday::Sat | Weekday::Sun => DayKind::Weekend,
+            _ => DayKind::Weekday,
+        }
+    }
+}
+
+/// Mean and quartiles of the surplus at one local hour of the day
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HourStats {
+    /// Local hour, 0 to 23
+    pub hour: u32,
+    /// Points starting in that hour
+    pub samples: usize,
+    /// `None` without samples, like the quartiles
+    pub mean: Option<f64>,
+    pub p25: Option<f64>,
+    pub p75: Option<f64>,
+}
+
+/// Typical surplus by local hour of the day in `tz` over `history`. Points are counted
+/// as they are, so a quarter-hourly day has four samples per hour.
+pub fn hourly_profile(history: &[RenewableSurplus], tz: &LocalZone) -> [HourStats; 24] {
+    profile_where(history, tz, |_| true)
+}
+
+/// Like [`hourly_profile`], over the days of `kind` only
+pub fn hourly_profile_on(
+    history: &[RenewableSurplus],
+    tz: &LocalZone,
+    kind: DayKind,
+) -> [HourStats; 24] {
+    profile_where(history, tz, |date| DayKind::of(date) == kind)
+}
+
+fn profile_where(
+    history: &[RenewableSurplus],
+    tz: &LocalZone,
+    on: impl Fn(NaiveDate) -> bool,
+) -> [HourStats; 24] {
+    let mut by_hour: [Vec<f64>; 24] = Default::default();
+    for point in history {
+        let local = tz.to_local(point.timestamp);
+        if on(local.date_naive()) {
+            by_hour[local.hour() as usize].push(point.surplus);
+        }
+    }
+
+    let mut profile = [HourStats::default(); 24];
+    for (hour, (stats, mut values)) in profile.iter_mut().zip(by_hour).enumerate() {
+        values.sort_by(f64::total_cmp);
+        *stats = HourStats {
+            hour: hour as u32,
+            samples: values.len(),
+            mean: (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64),
+            p25: percentile(&values, 25.0),
+            p75: percentile(&values, 75.0),
+        };
+    }
+    profile
+}
+
+/// The `p`th percentile of ascending `sorted`, interpolated between the closest ranks
+fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = p / 100.0 * last as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64))
+}
+
+/// Means of the points over the buckets `bucket_of` puts instants in. Each point lasts
+/// until the next one, at most as long as the spacing of the first two.
+fn bucket_means(
+    series: &[RenewableSurplus],
+    bucket_of: impl Fn(DateTime<Utc>) -> Interval,
+) -> Vec<RenewableSurplus> {
+    let spacing = resolution(series).unwrap_or_else(|| Duration::hours(1));
+    let field = |value: fn(&RenewableSurplus) -> Option<f64>| -> Vec<TimestampedPoint> {
+        series
+            .iter()
+            .enumerate()
+            .filter_map(|(i, point)| {
+                let until_next = series
+                    .get(i + 1)
+                    .map(|next| next.timestamp - point.timestamp);
+                Some(TimestampedPoint {
+                    timestamp: point.timestamp,
+                    position: i as u32 + 1,
+                    quantity: value(point)?,
+                    unit: MeasureUnit::Megawatt,
+                    duration: until_next.map_or(spacing, |until| until.min(spacing)),
+                })
+            })
+            .collect()
+    };
+    let means = |value: fn(&RenewableSurplus) -> Option<f64>| {
+        resample_on(
+            &field(value),
+            &bucket_of,
+            ResampleMethod::MeanDownsample,
+            PartialBuckets::Keep,
+        )
+        .into_iter()
+        .map(|resampled| resampled.point)
+    };
+
+    let total_generation: HashMap<DateTime<Utc>, f64> = means(|p| p.total_generation)
+        .map(|point| (point.timestamp, point.quantity))
+        .collect();
+    means(|p| Some(p.generation))
+        .zip(means(|p| Some(p.load)))
+        .zip(means(|p| Some(p.surplus)))
+        .map(|((generation, load), surplus)| RenewableSurplus {
+            timestamp: generation.timestamp,
+            generation: generation.quantity,
+            load: load.quantity,
+            surplus: surplus.quantity,
+            total_generation: total_generation.get(&generation.timestamp).copied(),
+        })
+        .collect()
+}
+
+/// Average a series longer than `max_points` into hourly, 3-hourly, 6-hourly or daily
+/// means, the finest that fits (daily if none does). Days are calendar days in `tz`.
+/// Returns the bucket length and the means, `None` when the series already fits.
+pub fn downsample(
+    series: &[RenewableSurplus],
+    max_points: usize,
+    tz: &LocalZone,
+) -> Option<(Duration, Vec<RenewableSurplus>)> {
+    if series.len() <= max_points {
+        return None;
+    }
+
+    let current = resolution(series).unwrap_or(Duration::zero());
+    let mut coarsest = None;
+    for hours in DOWNSAMPLE_BUCKET_HOURS {
+        let bucket = Duration::hours(hours);
+        if bucket <= current {
+            continue;
+        }
+        let points = match hours {
+            24 => daily_means(series, tz),
+            _ => resample_mean(series, bucket),
+        };
+        if points.len() <= max_points {
+            return Some((bucket, points));
+        }
+        coarsest = Some((bucket, points));
+    }
+    coarsest
+}
+
+/// Precision of [`series_fingerprint`] and the smallest change [`SeriesDiff::is_significant`]
+/// counts by default, below which differences are taken as floating-point noise
+pub const FINGERPRINT_PRECISION_MW: f64 = 0.5;
+
+/// Hash of the timestamps and values of a series to [`FINGERPRINT_PRECISION_MW`], see
+/// [`series_fingerprint_with`]
+pub fn series_fingerprint(series: &[RenewableSurplus]) -> u64 {
+    series_fingerprint_with(series, FINGERPRINT_PRECISION_MW)
+}
+
+/// Hash of the timestamps and the values rounded to multiples of `precision`, the same
+/// across runs and builds whatever order the points are in. Equal fingerprints mean
+/// nothing changed by more than about `precision`; values close to a rounding boundary
+/// may still differ in fingerprint after a smaller change, so compare with
+/// [`diff_summary`] before acting on a difference.
+pub fn series_fingerprint_with(series: &[RenewableSurplus], precision: f64) -> u64 {
+    let round = |value: f64| match precision > 0.0 {
+        true => (value / precision).round() as i64,
+        false => value.to_bits() as i64,
+    };
+    let mut points: Vec<(i64, [i64; 4])> = series
+        .iter()
+        .map(|point| {
+            (
+                point.timestamp.timestamp_millis(),
+                [
+                    round(point.generation),
+                    round(point.load),
+                    round(point.surplus),
+                    point.total_generation.map_or(i64::MIN, round),
+                ],
+            )
+        })
+        .collect();
+    points.sort_unstable();
+
+    super::fnv1a(
+        points
+            .into_iter()
+            .flat_map(|(timestamp, values)| std::iter::once(timestamp).chain(values))
+            .flat_map(i64::to_le_bytes),
+    )
+}
+
+/// Largest change of one value between two versions of a series
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueChange {
+    pub timestamp: DateTime<Utc>,
+    /// `generation`, `load` or `surplus`
+    pub quantity: &'static str,
+    pub old: f64,
+    pub new: f64,
+}
+
+impl ValueChange {
+    pub fn delta(&self) -> f64 {
+        self.new - self.old
+    }
+}
+
+/// What changed between two versions of a series
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SeriesDiff {
+    /// Timestamps only the new version has, by time
+    pub added: Vec<DateTime<Utc>>,
+    /// Timestamps only the old version has, by time
+    pub removed: Vec<DateTime<Utc>>,
+    /// Largest change at a timestamp both versions have, `None` if they have none in
+    /// common
+    pub largest_change: Option<ValueChange>,
+}
+
+impl SeriesDiff {
+    /// Whether the new version brings timestamps the old one lacked or changes a value
+    /// by at least `threshold`. Timestamps dropping out, e.g. points passing out of a
+    /// look-ahead window, do not count.
+    pub fn is_significant(&self, threshold: f64) -> bool {
+        !self.added.is_empty()
+            || self
+                .largest_change
+                .is_some_and(|change| change.delta().abs() >= threshold)
+    }
+}
+
+/// Timestamps added and removed between `old` and `new`, and the largest change of
+/// generation, load or surplus at the timestamps they share
+pub fn diff_summary(old: &[RenewableSurplus], new: &[RenewableSurplus]) -> SeriesDiff {
+    let old_points: BTreeMap<DateTime<Utc>, &RenewableSurplus> =
+        old.iter().map(|point| (point.timestamp, point)).collect();
+    let new_points: BTreeMap<DateTime<Utc>, &RenewableSurplus> =
+        new.iter().map(|point| (point.timestamp, point)).collect();
+
+    let mut diff = SeriesDiff {
+        added: new_points
+            .keys()
+            .filter(|timestamp| !old_points.contains_key(timestamp))
+            .copied()
+            .collect(),
+        removed: old_points
+            .keys()
+            .filter(|timestamp| !new_points.contains_key(timestamp))
+            .copied()
+            .collect(),
+        largest_change: None,
+    };
+    let values = |point: &RenewableSurplus| {
+        [
+            ("generation", point.generation),
+            ("load", point.load),
+            ("surplus", point.surplus),
+        ]
+    };
+    for (timestamp, old_point) in &old_points {
+        let Some(new_point) = new_points.get(timestamp) else {
+            continue;
+        };
+        for ((quantity, old), (_, new)) in values(old_point).into_iter().zip(values(new_point)) {
+            let change = ValueChange {
+                timestamp: *timestamp,
+                quantity,
+                old,
+                new,
+            };
+            // The earliest change wins ties
+            if diff
+                .largest_change
+                .is_none_or(|largest| change.delta().abs() > largest.delta().abs())
+            {
+                diff.largest_change = Some(change);
+            }
+        }
+    }
+    diff
+}
+
+/// Contiguous stretch of a series whose points all satisfy a condition
+#[derive(Debug, Clone)]
+pub struct SurplusWindow {
+    pub start: DateTime<Utc>,
+    /// End of the last point's interval
+    pub end: DateTime<Utc>,
+    pub mean_surplus: f64,
+    pub min_point: RenewableSurplus,
+}
+
+impl SurplusWindow {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Find runs of evenly spaced points satisfying `condition` that last at least `min_duration`.
+/// A gap in the series ends a run.
+pub fn find_windows(
+    series: &[RenewableSurplus],
+    min_duration: Duration,
+    condition: impl Fn(&RenewableSurplus) -> bool,
+) -> Vec<SurplusWindow> {
+    if series.is_empty() {
+        return Vec::new();
+    }
+    // A lone point is assumed to cover an hour
+    let resolution = resolution(series).unwrap_or(Duration::hours(1));
+
+    let mut windows = Vec::new();
+    let mut run: &[RenewableSurplus] = &[];
+    let mut run_start = 0;
+
+    for (i, point) in series.iter().enumerate() {
+        let continues = run
+            .last()
+            .is_some_and(|last| point.timestamp - last.timestamp == resolution);
+
+        if condition(point) {
+            if !continues {
+                push_window(&mut windows, run, resolution, min_duration);
+                run_start = i;
+            }
+            run = &series[run_start..=i];
+        } else {
+            push_window(&mut windows, run, resolution, min_duration);
+            run = &[];
+        }
+    }
+    push_window(&mut windows, run, resolution, min_duration);
+
+    windows
+}
+
+fn push_window(
+    windows: &mut Vec<SurplusWindow>,
+    run: &[RenewableSurplus],
+    resolution: Duration,
+    min_duration: Duration,
+) {
+    let (Some(first), Some(last)) = (run.first(), run.last()) else {
+        return;
+    };
+    let end = last.timestamp + resolution;
+    if end - first.timestamp < min_duration {
+        return;
+    }
+    let Some(min_point) = find_min_surplus(run) else {
+        return;
+    };
+
+    windows.push(SurplusWindow {
+        start: first.timestamp,
+        end,
+        mean_surplus: run.iter().map(|s| s.surplus).sum::<f64>() / run.len() as f64,
+        min_point,
+    });
+}
+
+/// Windows where the surplus stays below a (negative) `threshold_mw` for at least `min_duration`
+pub fn find_deficit_windows(
+    series: &[RenewableSurplus],
+    threshold_mw: f64,
+    min_duration: Duration,
+) -> Vec<SurplusWindow> {
+    find_windows(series, min_duration, |s| s.surplus < threshold_mw)
+}
+
+/// The point with the lowest surplus, i.e. the largest deficit; the earliest of equal ones
+pub fn find_min_surplus(series: &[RenewableSurplus]) -> Option<RenewableSurplus> {
+    series.iter().min_surplus().cloned()
+}
+
+/// The point with the highest surplus; the earliest of equal ones, whatever the order
+/// of `series`
+pub fn max_surplus<'a>(
+    series: impl IntoIterator<Item = &'a RenewableSurplus>,
+) -> Option<&'a RenewableSurplus> {
+    series.into_iter().max_by(|a, b| {
+        a.surplus
+            .total_cmp(&b.surplus)
+            .then(b.timestamp.cmp(&a.timestamp))
+    })
+}
+
+/// Hours of the day from `from` up to `to`, wrapping past midnight when `to` is the
+/// smaller one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DailyHours {
+    pub from: u32,
+    pub to: u32,
+}
+
+impl DailyHours {
+    /// 22:00-06:00
+    pub const NIGHT: DailyHours = DailyHours { from: 22, to: 6 };
+
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.from <= self.to {
+            (self.from..self.to).contains(&hour)
+        } else {
+            hour >= self.from || hour < self.to
+        }
+    }
+}
+
+/// Filters and extremes of surplus points that borrow them and chain, as in
+/// `series.points.iter().night_hours(DailyHours::NIGHT, &tz).max_surplus()`
+pub trait SurplusPoints<'a>: Iterator<Item = &'a RenewableSurplus> + Sized {
+    /// Points starting within `hours` of the local time in `tz`
+    fn night_hours(
+        self,
+        hours: DailyHours,
+        tz: &LocalZone,
+    ) -> impl Iterator<Item = &'a RenewableSurplus> {
+        self.filter(move |point| hours.contains(tz.to_local(point.timestamp).hour()))
+    }
+
+    /// Points starting from `now` to `hours` later, both included
+    fn within(self, now: DateTime<Utc>, hours: u32) -> impl Iterator<Item = &'a RenewableSurplus> {
+        let end = now + Duration::hours(hours as i64);
+        self.filter(move |point| point.timestamp >= now && point.timestamp <= end)
+    }
+
+    /// Points with a surplus above `threshold` MW
+    fn above(self, threshold: f64) -> impl Iterator<Item = &'a RenewableSurplus> {
+        self.filter(move |point| point.surplus > threshold)
+    }
+
+    /// See [`max_surplus`]
+    fn max_surplus(self) -> Option<&'a RenewableSurplus> {
+        max_surplus(self)
+    }
+
+    /// The point with the lowest surplus; the earliest of equal ones
+    fn min_surplus(self) -> Option<&'a RenewableSurplus> {
+        self.min_by(|a, b| {
+            a.surplus
+                .total_cmp(&b.surplus)
+                .then(a.timestamp.cmp(&b.timestamp))
+        })
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a RenewableSurplus>> SurplusPoints<'a> for I {}
+
+/// A battery behind an inverter, see [`simulate_battery`]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct BatteryParams {
+    /// Usable capacity
+    pub capacity_kwh: f64,
+    /// Of the inverter, when charging and discharging alike
+    pub max_power_kw: f64,
+    /// Round trip, 0 to 1, lost while charging: a kWh drawn stores `efficiency` kWh
+    pub efficiency: f64,
+    /// State of charge at the start, as a fraction of the capacity
+    pub initial_soc: f64,
+}
+
+impl BatteryParams {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.capacity_kwh.is_finite() && self.capacity_kwh > 0.0) {
+            return Err("`capacity_kwh` must be positive".to_string());
+        }
+        if !(self.max_power_kw.is_finite() && self.max_power_kw > 0.0) {
+            return Err("`max_power_kw` must be positive".to_string());
+        }
+        if !(self.efficiency > 0.0 && self.efficiency <= 1.0) {
+            return Err("`efficiency` must be above 0 and at most 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.initial_soc) {
+            return Err("`initial_soc` must be between 0 and 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What the battery does during one point of the series
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryStep {
+    pub timestamp: DateTime<Utc>,
+    pub duration: Duration,
+    /// MW
+    pub surplus: f64,
+    /// Drawn from the grid while charging, negative while discharging
+    pub power_kw: f64,
+    /// State of charge at the end of the step
+    pub soc_kwh: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatterySimulation {
+    pub steps: Vec<BatteryStep>,
+    /// Drawn from the grid, before the losses
+    pub charged_kwh: f64,
+    pub discharged_kwh: f64,
+    pub final_soc_kwh: f64,
+    /// Mean renewable penetration of the grid while charging, weighted by the energy
+    /// drawn and capped at 100 % per step; `None` if the battery never charges
+    pub renewable_share_of_charge: Option<f64>,
+}
+
+/// Energy below which the simulation stops moving any
+const BATTERY_EPSILON_KWH: f64 = 1e-9;
+
+/// Charge and discharge `battery` along `series`, greedily: the intervals above the
+/// median surplus charge as much as inverter and capacity allow, the highest surplus
+/// first, the ones below it discharge, the lowest first. Every step lasts until the next
+/// point, at most as long as the spacing of the series, so quarter-hours move a quarter
+/// of the energy of hours. Discharging may make room for more charging before it and
+/// the other way round, so both are repeated until nothing moves.
+pub fn simulate_battery(series: &[RenewableSurplus], battery: &BatteryParams) -> BatterySimulation {
+    let spacing = resolution(series).unwrap_or_else(|| Duration::hours(1));
+    let durations: Vec<Duration> = series
+        .iter()
+        .enumerate()
+        .map(|(i, point)| {
+            series.get(i + 1).map_or(spacing, |next| {
+                (next.timestamp - point.timestamp).min(spacing)
+            })
+        })
+        .collect();
+    let max_energy: Vec<f64> = durations
+        .iter()
+        .map(|duration| battery.max_power_kw * duration.num_seconds() as f64 / 3600.0)
+        .collect();
+
+    let mut surpluses: Vec<f64> = series.iter().map(|point| point.surplus).collect();
+    surpluses.sort_by(f64::total_cmp);
+    let median = percentile(&surpluses, 50.0).unwrap_or(0.0);
+    let mut by_surplus: Vec<usize> = (0..series.len()).collect();
+    by_surplus.sort_by(|&a, &b| series[b].surplus.total_cmp(&series[a].surplus));
+    let charging: Vec<usize> = by_surplus
+        .iter()
+        .copied()
+        .filter(|&i| series[i].surplus > median)
+        .collect();
+    let discharging: Vec<usize> = by_surplus
+        .iter()
+        .rev()
+        .copied()
+        .filter(|&i| series[i].surplus < median)
+        .collect();
+
+    let initial = battery.initial_soc * battery.capacity_kwh;
+    // Change of the stored energy per step
+    let mut stored = vec![0.0; series.len()];
+    // Lowest and highest state of charge from the end of step `i` on
+    let soc_range = |stored: &[f64], i: usize| {
+        let mut soc = initial + stored[..i].iter().sum::<f64>();
+        stored[i..]
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), delta| {
+                soc += delta;
+                (low.min(soc), high.max(soc))
+            })
+    };
+    for _ in 0..=series.len() {
+        let mut moved = 0.0;
+        for &i in &charging {
+            let (_, peak) = soc_range(&stored, i);
+            let room =
+                (battery.capacity_kwh - peak).min(max_energy[i] * battery.efficiency - stored[i]);
+            if room > BATTERY_EPSILON_KWH {
+                stored[i] += room;
+                moved += room;
+            }
+        }
+        for &i in &discharging {
+            let (low, _) = soc_range(&stored, i);
+            let take = low.min(max_energy[i] + stored[i]);
+            if take > BATTERY_EPSILON_KWH {
+                stored[i] -= take;
+                moved += take;
+            }
+        }
+        if moved <= BATTERY_EPSILON_KWH {
+            break;
+        }
+    }
+
+    let mut soc = initial;
+    let mut charged_kwh = 0.0;
+    let mut discharged_kwh = 0.0;
+    let mut renewable_kwh = 0.0;
+    let steps = series
+        .iter()
+        .zip(&durations)
+        .zip(&stored)
+        .map(|((point, &duration), &delta)| {
+            let drawn = if delta > 0.0 {
+                delta / battery.efficiency
+            } else {
+                delta
+            };
+            if drawn > 0.0 {
+                charged_kwh += drawn;
+                renewable_kwh += drawn * point.renewable_penetration().min(100.0);
+            } else {
+                discharged_kwh -= drawn;
+            }
+            soc = (soc + delta).clamp(0.0, battery.capacity_kwh);
+            BatteryStep {
+                timestamp: point.timestamp,
+                duration,
+                surplus: point.surplus,
+                power_kw: drawn / (duration.num_seconds() as f64 / 3600.0),
+                soc_kwh: soc,
+            }
+        })
+        .collect();
+
+    BatterySimulation {
+        steps,
+        charged_kwh,
+        discharged_kwh,
+        final_soc_kwh: soc,
+        renewable_share_of_charge: (charged_kwh > 0.0).then(|| renewable_kwh / charged_kwh),
+    }
+}
+
+/// What [`plan_energy`] picks the intervals by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanObjective {
+    /// Highest surplus first
+    #[default]
+    Surplus,
+    /// Lowest price first, unpriced intervals last
+    Price,
+}
+
+/// Interval energy can be planned in, with what [`PlanObjective`] judges it by
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergySlot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// MW
+    pub surplus: f64,
+    /// Per MWh, `None` without a published price
+    pub price: Option<f64>,
+}
+
+impl EnergySlot {
+    /// Slots of the points of `series`, each lasting until the next point, at most as
+    /// long as the spacing of the series, without prices
+    pub fn of_series(series: &[RenewableSurplus]) -> Vec<EnergySlot> {
+        let spacing = resolution(series).unwrap_or_else(|| Duration::hours(1));
+        series
+            .iter()
+            .enumerate()
+            .map(|(i, point)| {
+                let until_next = series
+                    .get(i + 1)
+                    .map(|next| next.timestamp - point.timestamp);
+                EnergySlot {
+                    start: point.timestamp,
+                    end: point.timestamp + until_next.map_or(spacing, |until| until.min(spacing)),
+                    surplus: point.surplus,
+                    price: None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Energy planned in (part of) an [`EnergySlot`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedInterval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub energy_kwh: f64,
+    pub surplus: f64,
+    pub price: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyPlan {
+    /// By time
+    pub intervals: Vec<PlannedInterval>,
+    pub energy_kwh: f64,
+    /// Means over the planned energy; the price only if every interval has one
+    pub mean_surplus: f64,
+    pub mean_price: Option<f64>,
+}
+
+/// Not enough time at full power before the deadline
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyShortfall {
+    pub required_kwh: f64,
+    pub available_kwh: f64,
+}
+
+impl EnergyShortfall {
+    pub fn shortfall_kwh(&self) -> f64 {
+        self.required_kwh - self.available_kwh
+    }
+}
+
+impl std::fmt::Display for EnergyShortfall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "only {:.2} of {:.2} kWh fit before the deadline, {:.2} kWh short",
+            self.available_kwh,
+            self.required_kwh,
+            self.shortfall_kwh()
+        )
+    }
+}
+
+/// Plan `demand_kwh` at up to `max_power_kw` into the best of `slots` (sorted by time)
+/// by `objective`, ending by `deadline`. Slots reaching past the deadline count up to
+/// it, and the last slot needed is charged at full power from its start for as long as
+/// it takes, so the plan meets the demand exactly.
+pub fn plan_energy(
+    slots: &[EnergySlot],
+    demand_kwh: f64,
+    max_power_kw: f64,
+    deadline: DateTime<Utc>,
+    objective: PlanObjective,
+) -> Result<EnergyPlan, EnergyShortfall> {
+    let hours =
+        |start: DateTime<Utc>, end: DateTime<Utc>| (end - start).num_seconds() as f64 / 3600.0;
+    let usable: Vec<EnergySlot> = slots
+        .iter()
+        .filter(|slot| slot.start < deadline)
+        .map(|slot| EnergySlot {
+            end: slot.end.min(deadline),
+            ..slot.clone()
+        })
+        .collect();
+    let available_kwh: f64 = usable
+        .iter()
+        .map(|slot| max_power_kw * hours(slot.start, slot.end))
+        .sum();
+    if available_kwh < demand_kwh {
+        return Err(EnergyShortfall {
+            required_kwh: demand_kwh,
+            available_kwh,
+        });
+    }
+
+    let mut ranked: Vec<&EnergySlot> = usable.iter().collect();
+    // Stable, so equal slots keep the earliest first
+    match objective {
+        PlanObjective::Surplus => ranked.sort_by(|a, b| b.surplus.total_cmp(&a.surplus)),
+        PlanObjective::Price => ranked.sort_by(|a, b| match (a.price, b.price) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => a.is_none().cmp(&b.is_none()),
+        }),
+    }
+
+    let mut remaining = demand_kwh;
+    let mut intervals = Vec::new();
+    for slot in ranked {
+        if remaining <= 0.0 {
+            break;
+        }
+        let full = max_power_kw * hours(slot.start, slot.end);
+        let (end, energy_kwh) = if full <= remaining {
+            (slot.end, full)
+        } else {
+            let seconds = (remaining / max_power_kw * 3600.0).ceil() as i64;
+            (
+                (slot.start + Duration::seconds(seconds)).min(slot.end),
+                remaining,
+            )
+        };
+        remaining -= energy_kwh;
+        intervals.push(PlannedInterval {
+            start: slot.start,
+            end,
+            energy_kwh,
+            surplus: slot.surplus,
+            price: slot.price,
+        });
+    }
+    intervals.sort_by_key(|interval| interval.start);
+
+    let energy_kwh: f64 = intervals.iter().map(|interval| interval.energy_kwh).sum();
+    let weighted = |value: fn(&PlannedInterval) -> Option<f64>| {
+        intervals
+            .iter()
+            .map(|interval| Some(value(interval)? * interval.energy_kwh))
+            .sum::<Option<f64>>()
+            .filter(|_| energy_kwh > 0.0)
+            .map(|sum| sum / energy_kwh)
+    };
+    Ok(EnergyPlan {
+        mean_surplus: weighted(|interval| Some(interval.surplus)).unwrap_or(0.0),
+        mean_price: weighted(|interval| interval.price),
+        intervals,
+        energy_kwh,
+    })
+}
+
+/// Operating state of the SG Ready interface of heat pumps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SgReadyState {
+    /// 1: the utility blocks operation
+    Locked = 1,
+    /// 2: normal operation
+    Normal = 2,
+    /// 3: increased operation recommended
+    Recommended = 3,
+    /// 4: switched on
+    Forced = 4,
+}
+
+impl SgReadyState {
+    const ALL: [SgReadyState; 4] = [
+        SgReadyState::Locked,
+        SgReadyState::Normal,
+        SgReadyState::Recommended,
+        SgReadyState::Forced,
+    ];
+
+    /// 1 to 4, as the interface numbers them
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SgReadyState::Locked => "locked",
+            SgReadyState::Normal => "normal",
+            SgReadyState::Recommended => "recommended",
+            SgReadyState::Forced => "forced",
+        }
+    }
+
+    fn above(self) -> Option<SgReadyState> {
+        Self::ALL.get(self as usize).copied()
+    }
+
+    fn below(self) -> Option<SgReadyState> {
+        Self::ALL.get((self as usize).checked_sub(2)?).copied()
+    }
+}
+
+/// Surplus in MW at which the [`SgReadyState`]s begin, ascending
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SgThresholds {
+    /// Locked below, normal from here
+    pub normal_from: f64,
+    pub recommended_from: f64,
+    pub forced_from: f64,
+}
+
+impl Default for SgThresholds {
+    fn default() -> Self {
+        Self {
+            normal_from: -20_000.0,
+            recommended_from: 0.0,
+            forced_from: 10_000.0,
+        }
+    }
+}
+
+impl SgThresholds {
+    pub fn validate(&self) -> Result<(), String> {
+        let thresholds = [self.normal_from, self.recommended_from, self.forced_from];
+        if !thresholds.iter().all(|threshold| threshold.is_finite()) {
+            return Err("SG Ready thresholds must be numbers".to_string());
+        }
+        if !thresholds.is_sorted_by(|a, b| a < b) {
+            return Err("SG Ready thresholds must ascend from normal to forced".to_string());
+        }
+        Ok(())
+    }
+
+    /// Surplus at which `state` begins, `None` for the lowest
+    fn start_of(&self, state: SgReadyState) -> Option<f64> {
+        match state {
+            SgReadyState::Locked => None,
+            SgReadyState::Normal => Some(self.normal_from),
+            SgReadyState::Recommended => Some(self.recommended_from),
+            SgReadyState::Forced => Some(self.forced_from),
+        }
+    }
+
+    /// State of `surplus` regardless of the state before
+    pub fn state_of(&self, surplus: f64) -> SgReadyState {
+        SgReadyState::ALL
+            .into_iter()
+            .rev()
+            .find(|&state| self.start_of(state).is_none_or(|start| surplus >= start))
+            .unwrap_or(SgReadyState::Locked)
+    }
+
+    /// State following `previous` at `surplus`, switching only `margin` past a threshold
+    fn next_state(&self, previous: SgReadyState, surplus: f64, margin: f64) -> SgReadyState {
+        let mut state = previous;
+        while let Some(above) = state.above()
+            && self
+                .start_of(above)
+                .is_some_and(|start| surplus >= start + margin)
+        {
+            state = above;
+        }
+        while let Some(below) = state.below()
+            && self
+                .start_of(state)
+                .is_some_and(|start| surplus < start - margin)
+        {
+            state = below;
+        }
+        state
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SgReadyPoint {
+    pub timestamp: DateTime<Utc>,
+    pub surplus: f64,
+    pub state: SgReadyState,
+}
+
+/// SG Ready states along `series`. The first point gets the state its surplus falls
+/// into; after that a threshold only switches the state once the surplus is
+/// `hysteresis_mw / 2` past it, upwards as downwards, so a surplus wavering around a
+/// threshold keeps the state it had.
+pub fn sg_ready_signal(
+    series: &[RenewableSurplus],
+    thresholds: &SgThresholds,
+    hysteresis_mw: f64,
+) -> Vec<SgReadyPoint> {
+    let margin = hysteresis_mw.max(0.0) / 2.0;
+    let mut previous: Option<SgReadyState> = None;
+    series
+        .iter()
+        .map(|point| {
+            let state = match previous {
+                None => thresholds.state_of(point.surplus),
+                Some(previous) => thresholds.next_state(previous, point.surplus, margin),
+            };
+            previous = Some(state);
+            SgReadyPoint {
+                timestamp: point.timestamp,
+                surplus: point.surplus,
+                state,
+            }
+        })
+        .collect()
+}
+
+/// Surplus of an area over a window, absolute and relative to the size of the area
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedSurplus {
+    pub mean_surplus: f64,
+    pub max_surplus: f64,
+    pub mean_load: f64,
+    /// Mean surplus in kW per inhabitant, `None` without a population
+    pub per_capita_kw: Option<f64>,
+    /// Mean surplus in percent of the mean load over the same window, `None` without load
+    pub share_of_load: Option<f64>,
+}
+
+/// Normalize the surplus of a (MW) series by `population`; `None` for an empty series
+pub fn normalized_surplus(
+    series: &[RenewableSurplus],
+    population: Option<u64>,
+) -> Option<NormalizedSurplus> {
+    if series.is_empty() {
+        return None;
+    }
+    let count = series.len() as f64;
+    let mean_surplus = series.iter().map(|s| s.surplus).sum::<f64>() / count;
+    let mean_load = series.iter().map(|s| s.load).sum::<f64>() / count;
+    let max_surplus = series
+        .iter()
+        .map(|s| s.surplus)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    Some(NormalizedSurplus {
+        mean_surplus,
+        max_surplus,
+        mean_load,
+        per_capita_kw: population
+            .filter(|population| *population > 0)
+            .map(|population| mean_surplus * 1000.0 / population as f64),
+        share_of_load: (mean_load > 0.0).then(|| mean_surplus / mean_load * 100.0),
+    })
+}
+
+/// Flow over an interconnector relative to its offered capacity
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtilizationPoint {
+    pub timestamp: DateTime<Utc>,
+    pub duration: Duration,
+    /// MW
+    pub flow: f64,
+    /// MW
+    pub capacity: f64,
+    /// Flow as a percentage of the capacity, `None` when no capacity is offered
+    pub utilization: Option<f64>,
+}
+
+/// Utilization of an interconnector wherever both `capacity` and `flows` (each sorted by
+/// time) have a value. The two may differ in resolution: points are matched by the
+/// intervals they cover, and each result spans the part both intervals share.
+pub fn interconnector_utilization(
+    capacity: &[TimestampedPoint],
+    flows: &[TimestampedPoint],
+) -> Vec<UtilizationPoint> {
+    fn covering(points: &[TimestampedPoint], at: DateTime<Utc>) -> Option<&TimestampedPoint> {
+        let after = points.partition_point(|point| point.timestamp <= at);
+        let point = points.get(after.checked_sub(1)?)?;
+        (at < point.end()).then_some(point)
+    }
+
+    let starts: Vec<DateTime<Utc>> = capacity
+        .iter()
+        .chain(flows)
+        .map(|point| point.timestamp)
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &start)| {
+            let capacity = covering(capacity, start)?.in_unit(MeasureUnit::Megawatt);
+            let flow = covering(flows, start)?.in_unit(MeasureUnit::Megawatt);
+            let mut end = capacity.end().min(flow.end());
+            if let Some(&next) = starts.get(i + 1) {
+                end = end.min(next);
+            }
+
+            Some(UtilizationPoint {
+                timestamp: start,
+                duration: end - start,
+                flow: flow.quantity,
+                capacity: capacity.quantity,
+                utilization: (capacity.quantity > 0.0)
+                    .then(|| flow.quantity / capacity.quantity * 100.0),
+            })
+        })
+        .collect()
+}
+
+/// Forecast value of a timestamp as published in one document
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastRevision {
+    pub timestamp: DateTime<Utc>,
+    pub meta: DocumentMeta,
+    pub value: f64,
+}
+
+/// Change of one timestamp's forecast from its first to its latest publication
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftPoint {
+    pub timestamp: DateTime<Utc>,
+    pub first: f64,
+    pub latest: f64,
+    /// `latest - first`
+    pub delta: f64,
+}
+
+/// How much a forecast changed over its revisions
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastDrift {
+    pub first_revision: DocumentMeta,
+    pub latest_revision: DocumentMeta,
+    /// Distinct documents the forecast was published in
+    pub revisions: usize,
+    pub points: Vec<DriftPoint>,
+    /// Root mean square of the deltas
+    pub rmse: f64,
+}
+
+/// Drift between the first and latest published value of each timestamp. Only
+/// timestamps published more than once are compared; `None` when there are none.
+pub fn forecast_revision_drift(revisions: &[ForecastRevision]) -> Option<ForecastDrift> {
+    let mut by_timestamp: BTreeMap<DateTime<Utc>, Vec<&ForecastRevision>> = BTreeMap::new();
+    for revision in revisions {
+        by_timestamp
+            .entry(revision.timestamp)
+            .or_default()
+            .push(revision);
+    }
+
+    let points: Vec<DriftPoint> = by_timestamp
+        .into_iter()
+        .filter_map(|(timestamp, revisions)| {
+            let first = revisions.iter().min_by_key(|r| r.meta)?;
+            let latest = revisions.iter().max_by_key(|r| r.meta)?;
+            (first.meta != latest.meta).then_some(DriftPoint {
+                timestamp,
+                first: first.value,
+                latest: latest.value,
+                delta: latest.value - first.value,
+            })
+        })
+        .collect();
+    if points.is_empty() {
+        return None;
+    }
+
+    let metas: std::collections::BTreeSet<DocumentMeta> =
+        revisions.iter().map(|r| r.meta).collect();
+    let squared: f64 = points.iter().map(|p| p.delta * p.delta).sum();
+    Some(ForecastDrift {
+        first_revision: *metas.first()?,
+        latest_revision: *metas.last()?,
+        revisions: metas.len(),
+        rmse: (squared / points.len() as f64).sqrt(),
+        points,
+    })
+}
+
+/// Surplus of one timestamp in the current and an earlier version of a forecast
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurplusDiff {
+    pub timestamp: DateTime<Utc>,
+    pub current: f64,
+    pub baseline: f64,
+    /// `current - baseline`
+    pub revision: f64,
+}
+
+/// Pair each point of `current` with the `baseline` point closest in time, at most
+/// `tolerance` away, preferring the earlier one on ties. Points without a counterpart,
+/// e.g. beyond the end of an older and shorter forecast, are left out.
+pub fn diff_series(
+    current: &[RenewableSurplus],
+    baseline: &[RenewableSurplus],
+    tolerance: Duration,
+) -> Vec<SurplusDiff> {
+    let mut baseline: Vec<&RenewableSurplus> = baseline.iter().collect();
+    baseline.sort_by_key(|point| point.timestamp);
+
+    current
+        .iter()
+        .filter_map(|point| {
+            let next = baseline.partition_point(|b| b.timestamp < point.timestamp);
+            let distance = |b: &&RenewableSurplus| (b.timestamp - point.timestamp).abs();
+            let closest = [next.checked_sub(1), Some(next)]
+                .into_iter()
+                .flatten()
+                .filter_map(|i| baseline.get(i).copied())
+                .min_by_key(distance)
+                .filter(|b| distance(b) <= tolerance)?;
+            Some(SurplusDiff {
+                timestamp: point.timestamp,
+                current: point.surplus,
+                baseline: closest.surplus,
+                revision: point.surplus - closest.surplus,
+            })
+        })
+        .collect()
+}
+
+/// Start and mean surplus of the `length` long stretch with the highest mean surplus.
+/// Only windows fully covered by `series` (sorted, evenly spaced) are considered.
+pub fn best_window(series: &[RenewableSurplus], length: Duration) -> Option<(DateTime<Utc>, f64)> {
+    let resolution = resolution(series)?;
+    let last_end = series.last()?.timestamp + resolution;
+
+    series
+        .iter()
+        .enumerate()
+        .filter(|(_, start)| start.timestamp + length <= last_end)
+        .map(|(i, start)| {
+            let end = start.timestamp + length;
+            let window: Vec<f64> = series[i..]
+                .iter()
+                .take_while(|s| s.timestamp < end)
+                .map(|s| s.surplus)
+                .collect();
+            let mean = window.iter().sum::<f64>() / window.len() as f64;
+            (start.timestamp, mean)
+        })
+        // The earliest of equally good windows
+        .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+}
+
+/// A forecast value next to the value that came true
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForecastOutcome {
+    pub timestamp: DateTime<Utc>,
+    pub forecast: f64,
+    pub actual: f64,
+}
+
+/// The `forecast` and `actual` values of each timestamp both have
+pub fn forecast_outcomes(
+    forecast: &BTreeMap<DateTime<Utc>, f64>,
+    actual: &BTreeMap<DateTime<Utc>, f64>,
+) -> Vec<ForecastOutcome> {
+    forecast
+        .iter()
+        .filter_map(|(&timestamp, &forecast)| {
+            Some(ForecastOutcome {
+                timestamp,
+                forecast,
+                actual: *actual.get(&timestamp)?,
+            })
+        })
+        .collect()
+}
+
+/// Surplus of solar and wind over the load that came true, at each timestamp both the
+/// actual generation (A75) and the actual load of `zone` have a point for. Other
+/// production types and consumption are left out, as in the forecasts.
+pub fn actual_surplus(
+    generation: &GlMarketDocument,
+    load: &GlMarketDocument,
+    zone: &str,
+) -> Result<BTreeMap<DateTime<Utc>, f64>, EntsoeError> {
+    // Merged like forecasts; the source they are tagged with goes unused
+    let generation = generation_split(&[(ForecastSource::DayAhead, generation)], zone)?;
+    let load = [(ForecastSource::DayAhead, load)];
+    let (load_filter, _) = load_aggregation(&load, zone);
+    Ok(merge_forecasts(&load, &load_filter)?
+        .into_iter()
+        .filter_map(|point| {
+            let split = generation.get(&point.timestamp)?;
+            Some((point.timestamp, split.solar + split.wind - point.quantity))
+        })
+        .collect())
+}
+
+/// Hours of the day with fewer forecast errors than this are widened by the errors of
+/// all hours instead
+pub const MIN_ERRORS_PER_HOUR: usize = 5;
+
+/// Historical forecast errors (`actual - forecast`) of a zone by local hour of the day
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorStats {
+    /// Time zone the hours are counted in
+    local: LocalZone,
+    /// Sorted errors of each hour
+    by_hour: [Vec<f64>; 24],
+    /// Sorted errors of all hours
+    all: Vec<f64>,
+}
+
+impl ErrorStats {
+    /// Number of errors the statistics were computed from
+    pub fn samples(&self) -> usize {
+        self.all.len()
+    }
+
+    /// Errors of the hour `timestamp` falls in, or of all hours when that hour has fewer
+    /// than [`MIN_ERRORS_PER_HOUR`]; `None` without any
+    fn errors_at(&self, timestamp: DateTime<Utc>) -> Option<&[f64]> {
+        let hour = &self.by_hour[self.local.to_local(timestamp).hour() as usize];
+        match (hour.len() >= MIN_ERRORS_PER_HOUR, self.all.is_empty()) {
+            (true, _) => Some(hour),
+            (false, false) => Some(&self.all),
+            (false, true) => None,
+        }
+    }
+
+    /// `p`th percentile (0 to 100) of the errors at `timestamp`
+    pub fn percentile_at(&self, timestamp: DateTime<Utc>, p: f64) -> Option<f64> {
+        percentile(self.errors_at(timestamp)?, p)
+    }
+
+    /// Share of the errors at `timestamp` that keep a forecast of `value` above zero
+    pub fn positive_probability(&self, timestamp: DateTime<Utc>, value: f64) -> Option<f64> {
+        let errors = self.errors_at(timestamp)?;
+        let positive = errors.iter().filter(|error| value + **error > 0.0).count();
+        Some(positive as f64 / errors.len() as f64)
+    }
+}
+
+/// Distribution of the errors of `history` by hour of the day in `local`, the hours
+/// forecasts err alike following the local daily routine. Non-finite values are left out.
+pub fn error_stats(history: &[ForecastOutcome], local: LocalZone) -> ErrorStats {
+    let mut stats = ErrorStats {
+        local,
+        by_hour: Default::default(),
+        all: Vec::new(),
+    };
+    for outcome in history {
+        let error = outcome.actual - outcome.forecast;
+        if !error.is_finite() {
+            continue;
+        }
+        stats.by_hour[local.to_local(outcome.timestamp).hour() as usize].push(error);
+        stats.all.push(error);
+    }
+    for errors in stats.by_hour.iter_mut().chain([&mut stats.all]) {
+        errors.sort_by(f64::total_cmp);
+    }
+    stats
+}
+
+/// Surplus a forecast point stays within with 80 % probability
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurplusBand {
+    pub timestamp: DateTime<Utc>,
+    pub p10: f64,
+    pub p90: f64,
+}
+
+/// The surplus of each point of `series` widened by the 10th and 90th percentile of the
+/// historical errors of its hour. `None` when `stats` holds no errors.
+pub fn uncertainty_band(
+    series: &[RenewableSurplus],
+    stats: &ErrorStats,
+) -> Option<Vec<SurplusBand>> {
+    series
+        .iter()
+        .map(|point| {
+            Some(SurplusBand {
+                timestamp: point.timestamp,
+                p10: point.surplus + stats.percentile_at(point.timestamp, 10.0)?,
+                p90: point.surplus + stats.percentile_at(point.timestamp, 90.0)?,
+            })
+        })
+        .collect()
+}
+
+/// Probability that the surplus stays positive over the `length` long window of
+/// `series` starting at `start`. Forecast errors of neighbouring hours go together, so
+/// the window is taken to be as robust as its least likely positive point. `None` when
+/// `series` does not cover the window or `stats` holds no errors.
+pub fn window_robustness(
+    series: &[RenewableSurplus],
+    start: DateTime<Utc>,
+    length: Duration,
+    stats: &ErrorStats,
+) -> Option<f64> {
+    let end = start + length;
+    let window: Vec<&RenewableSurplus> = series
+        .iter()
+        .filter(|point| point.timestamp >= start && point.timestamp < end)
+        .collect();
+    if window.is_empty() {
+        return None;
+    }
+    window
+        .iter()
+        .map(|point| stats.positive_probability(point.timestamp, point.surplus))
+        .try_fold(1.0_f64, |least, probability| Some(least.min(probability?)))
 }
 
 impl EntsoeClient {
@@ -26,101 +2510,2475 @@ impl EntsoeClient {
             self.fetch_day_ahead_total_load_forecast(bidding_zone, period_start, period_end)
         )?;
 
-        // Get timestamped points for both
-        let gen_points = gen_forecast.all_timestamped_points()?;
-        let load_points = load_forecast.all_timestamped_points()?;
+        let (load_filter, _) =
+            load_aggregation(&[(ForecastSource::DayAhead, &load_forecast)], bidding_zone);
+        let series = join_surplus(
+            &gen_forecast.timestamped_points_where(&generation_series(bidding_zone))?,
+            &load_forecast.timestamped_points_where(&load_filter)?,
+        )?;
+
+        max_surplus(&series.points).cloned().ok_or_else(|| {
+            EntsoeError::NoData(format!(
+                "no surplus of {} from {} to {}",
+                bidding_zone, period_start, period_end
+            ))
+        })
+    }
+
+    /// Get all renewable surplus data points for analysis, with surplus as defined by `model`
+    pub async fn get_renewable_surplus_series(
+        &self,
+        bidding_zone: &str,
+        period_start: &str,
+        period_end: &str,
+        model: &SurplusModel,
+    ) -> Result<Vec<RenewableSurplus>, EntsoeError> {
+        let mut series = self
+            .get_surplus_series(bidding_zone, period_start, period_end, Freshness::DayAhead)
+            .await?;
+
+        model.apply(&mut series.points);
+        Ok(series.points)
+    }
+
+    /// Like [`Self::get_renewable_surplus_series`], but a forecast that is missing, as
+    /// it often is right after the publication deadline, leaves its side of the points
+    /// empty instead of failing. Fails only when neither forecast has a point.
+    pub async fn get_partial_surplus_series(
+        &self,
+        bidding_zone: &str,
+        period_start: &str,
+        period_end: &str,
+        model: &SurplusModel,
+    ) -> Result<PartialSurplusSeries, EntsoeError> {
+        let source = ForecastSource::DayAhead;
+        let (generation, load) = tokio::join!(
+            self.fetch_generation_forecast(bidding_zone, period_start, period_end, source),
+            self.fetch_total_load_forecast(bidding_zone, period_start, period_end, source)
+        );
+        let generation = generation.and_then(|document| {
+            document.timestamped_points_where(&generation_series(bidding_zone))
+        });
+        let load = load.and_then(|document| {
+            let (filter, _) = load_aggregation(&[(source, &document)], bidding_zone);
+            document.timestamped_points_where(&filter)
+        });
+        let (generation, load) = match (generation, load) {
+            (Err(e), Err(_)) => return Err(e),
+            (generation, load) => (
+                generation.unwrap_or_else(|e| {
+                    eprintln!("Generation forecast unavailable: {}", e);
+                    Vec::new()
+                }),
+                load.unwrap_or_else(|e| {
+                    eprintln!("Load forecast unavailable: {}", e);
+                    Vec::new()
+                }),
+            ),
+        };
+
+        let missing = match (generation.is_empty(), load.is_empty()) {
+            (true, true) => {
+                return Err(EntsoeError::NoData(format!(
+                    "no forecast of {} from {} to {}",
+                    bidding_zone, period_start, period_end
+                )));
+            }
+            (true, false) => Some(ForecastSide::Generation),
+            (false, true) => Some(ForecastSide::Load),
+            (false, false) => None,
+        };
+        let mut points = partial_surplus(&generation, &load)?;
+        model.apply_partial(&mut points);
+        Ok(PartialSurplusSeries { points, missing })
+    }
+
+    /// Get the surplus series from the forecasts selected by `freshness`.
+    /// In [`Freshness::Auto`] mode a missing intraday forecast falls back to day-ahead.
+    ///
+    /// The day-ahead total generation forecast is fetched alongside; zones that don't
+    /// publish it leave `total_generation` empty. Fails with [`EntsoeError::NoData`] when
+    /// generation and load have no point in common.
+    pub async fn get_surplus_series(
+        &self,
+        bidding_zone: &str,
+        period_start: &str,
+        period_end: &str,
+        freshness: Freshness,
+    ) -> Result<SurplusSeries, EntsoeError> {
+        let (series, total_generation) = tokio::join!(
+            self.get_renewable_series(bidding_zone, period_start, period_end, freshness),
+            self.fetch_day_ahead_total_generation_forecast(bidding_zone, period_start, period_end)
+        );
+        let mut series = series?;
+
+        let total_generation = total_generation.and_then(|document| {
+            let points = document.timestamped_points_where(&generation_series(bidding_zone))?;
+            Ok((points, document.cache_status))
+        });
+        match total_generation {
+            Ok((points, cache_status)) => match series.attach_total_generation(&points) {
+                Ok(()) => series.cache_status = series.cache_status.max(cache_status),
+                Err(e) => eprintln!("Ignoring total generation forecast: {}", e),
+            },
+            Err(e) => eprintln!("Total generation forecast unavailable: {}", e),
+        }
+
+        if series.points.is_empty() {
+            return Err(EntsoeError::NoData(format!(
+                "no surplus of {} from {} to {}",
+                bidding_zone, period_start, period_end
+            )));
+        }
+        Ok(series)
+    }
+
+    /// The day-ahead surplus series of `bidding_zone` from the documents in the cache,
+    /// without any upstream request. `None` unless generation and load forecasts of the
+    /// zone are cached. Comes with the fetch time of the oldest document used.
+    pub fn cached_surplus_series(
+        &self,
+        bidding_zone: &str,
+    ) -> Option<(SurplusSeries, DateTime<Utc>)> {
+        let cached = self.cache.as_ref()?.fresh_documents();
+        let source = ForecastSource::DayAhead;
+        let generation_filter = generation_series(bidding_zone);
+        let matching = |doc_type: &str, filter: &dyn Fn(&GlMarketDocument) -> SeriesFilter| {
+            cached
+                .iter()
+                .filter(|entry| {
+                    entry.document.doc_type == doc_type
+                        && entry.document.process_type == source.process_type()
+                        && !entry
+                            .document
+                            .series_where(&filter(&entry.document))
+                            .is_empty()
+                })
+                .collect::<Vec<_>>()
+        };
+        let generation = matching("A69", &|_| generation_filter.clone());
+        // The cache holds the documents of every zone, so the load structure is read from
+        // the documents with a load of this zone only
+        let load = matching("A65", &|document| {
+            load_aggregation(&[(source, document)], bidding_zone).0
+        });
+        if generation.is_empty() || load.is_empty() {
+            return None;
+        }
+
+        let as_of = generation
+            .iter()
+            .chain(&load)
+            .map(|entry| entry.fetched_at)
+            .min()?;
+        let generation: Vec<_> = generation
+            .into_iter()
+            .map(|entry| (source, &entry.document))
+            .collect();
+        let load: Vec<_> = load
+            .into_iter()
+            .map(|entry| (source, &entry.document))
+            .collect();
+        let (load_filter, aggregation) = load_aggregation(&load, bidding_zone);
+        let mut series = surplus_series(
+            &merge_forecasts(&generation, &generation_filter).ok()?,
+            &merge_forecasts(&load, &load_filter).ok()?,
+        )
+        .ok()?;
+        series.dropped_points = non_finite_points(&generation, &generation_filter)
+            + non_finite_points(&load, &load_filter);
+        series.generation_split = generation_split(&generation, bidding_zone).unwrap_or_default();
+        series.load_aggregation = aggregation;
+        Some((series, as_of))
+    }
+
+    async fn get_renewable_series(
+        &self,
+        bidding_zone: &str,
+        period_start: &str,
+        period_end: &str,
+        freshness: Freshness,
+    ) -> Result<SurplusSeries, EntsoeError> {
+        let (gen_documents, load_documents) = match freshness {
+            Freshness::DayAhead | Freshness::Intraday => {
+                let source = match freshness {
+                    Freshness::Intraday => ForecastSource::Intraday,
+                    _ => ForecastSource::DayAhead,
+                };
+                let (gen_forecast, load_forecast) = tokio::try_join!(
+                    self.fetch_generation_forecast(bidding_zone, period_start, period_end, source),
+                    self.fetch_total_load_forecast(bidding_zone, period_start, period_end, source)
+                )?;
+                (vec![(source, gen_forecast)], vec![(source, load_forecast)])
+            }
+            Freshness::Auto => {
+                use ForecastSource::{Current, DayAhead, Intraday};
+
+                let (gen_day_ahead, load_day_ahead, gen_intraday, load_intraday, gen_current) = tokio::join!(
+                    self.fetch_generation_forecast(
+                        bidding_zone,
+                        period_start,
+                        period_end,
+                        DayAhead
+                    ),
+                    self.fetch_total_load_forecast(
+                        bidding_zone,
+                        period_start,
+                        period_end,
+                        DayAhead
+                    ),
+                    self.fetch_generation_forecast(
+                        bidding_zone,
+                        period_start,
+                        period_end,
+                        Intraday
+                    ),
+                    self.fetch_total_load_forecast(
+                        bidding_zone,
+                        period_start,
+                        period_end,
+                        Intraday
+                    ),
+                    // There is no current load forecast
+                    self.fetch_generation_forecast(bidding_zone, period_start, period_end, Current)
+                );
+                let mut gen_documents = vec![(DayAhead, gen_day_ahead?)];
+                let mut load_documents = vec![(DayAhead, load_day_ahead?)];
+                match gen_intraday {
+                    Ok(document) => gen_documents.push((Intraday, document)),
+                    Err(e) => eprintln!("Intraday generation forecast unavailable: {}", e),
+                }
+                match load_intraday {
+                    Ok(document) => load_documents.push((Intraday, document)),
+                    Err(e) => eprintln!("Intraday load forecast unavailable: {}", e),
+                }
+                match gen_current {
+                    Ok(document) => gen_documents.push((Current, document)),
+                    Err(e) => eprintln!("Current generation forecast unavailable: {}", e),
+                }
+                (gen_documents, load_documents)
+            }
+        };
+
+        let (gen_documents, load_documents) = (
+            document_refs(&gen_documents),
+            document_refs(&load_documents),
+        );
+        let generation_filter = generation_series(bidding_zone);
+        let (load_filter, aggregation) = load_aggregation(&load_documents, bidding_zone);
+
+        let mut series = surplus_series(
+            &merge_forecasts(&gen_documents, &generation_filter)?,
+            &merge_forecasts(&load_documents, &load_filter)?,
+        )?;
+        series.covered = intersection(
+            &covered_intervals(&gen_documents, &generation_filter)?,
+            &covered_intervals(&load_documents, &load_filter)?,
+        );
+        series.dropped_points = non_finite_points(&gen_documents, &generation_filter)
+            + non_finite_points(&load_documents, &load_filter);
+        series.generation_split = generation_split(&gen_documents, bidding_zone)?;
+        series.load_aggregation = aggregation;
+        series.cache_status = gen_documents
+            .iter()
+            .chain(&load_documents)
+            .map(|(_, document)| document.cache_status)
+            .max()
+            .unwrap_or_default();
+        Ok(series)
+    }
+}
+
+impl PartialSurplus {
+    /// The point with its power values in `unit` instead of MW, for reporting
+    pub fn in_power_unit(&self, unit: PowerUnit) -> PartialSurplus {
+        PartialSurplus {
+            timestamp: self.timestamp,
+            generation: self.generation.map(|mw| unit.from_mw(mw)),
+            load: self.load.map(|mw| unit.from_mw(mw)),
+            surplus: self.surplus.map(|mw| unit.from_mw(mw)),
+        }
+    }
+
+    /// The forecast this point lacks, if only one
+    pub fn missing(&self) -> Option<ForecastSide> {
+        match (self.generation, self.load) {
+            (None, Some(_)) => Some(ForecastSide::Generation),
+            (Some(_), None) => Some(ForecastSide::Load),
+            _ => None,
+        }
+    }
+}
+
+impl RenewableSurplus {
+    /// The point with its power values in `unit` instead of MW, for reporting
+    pub fn in_power_unit(&self, unit: PowerUnit) -> RenewableSurplus {
+        RenewableSurplus {
+            timestamp: self.timestamp,
+            generation: unit.from_mw(self.generation),
+            load: unit.from_mw(self.load),
+            surplus: unit.from_mw(self.surplus),
+            total_generation: self.total_generation.map(|mw| unit.from_mw(mw)),
+        }
+    }
+
+    /// Calculate the surplus as a percentage of generation
+    pub fn surplus_percentage(&self) -> f64 {
+        if self.generation == 0.0 {
+            0.0
+        } else {
+            (self.surplus / self.generation) * 100.0
+        }
+    }
+
+    /// Wind and solar generation as a percentage of load
+    pub fn renewable_penetration(&self) -> f64 {
+        if self.load == 0.0 {
+            0.0
+        } else {
+            (self.generation / self.load) * 100.0
+        }
+    }
+
+    /// Wind and solar generation as a percentage of total generation.
+    /// Unlike [`Self::renewable_penetration`] this is not skewed by imports and exports.
+    pub fn renewable_share_of_generation(&self) -> Option<f64> {
+        self.total_generation
+            .filter(|&total| total > 0.0)
+            .map(|total| (self.generation / total) * 100.0)
+    }
+
+    /// Check if there's excess renewable energy (generation > load)
+    pub fn has_excess(&self) -> bool {
+        self.surplus > 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::{
+        DEFAULT_ZONE, MockSeries, MockTransport, NO_MATCHING_DATA, control_area_load_document,
+        gl_document, gl_document_created, gl_document_series, ok, query_param, shuffled,
+        week_ahead_load_document,
+    };
+    use chrono::{Duration, TimeZone};
+    use std::sync::Arc;
+
+    fn document(created: &str, start: DateTime<Utc>, quantities: &[f64]) -> GlMarketDocument {
+        quick_xml::de::from_str(&gl_document_created("A69", created, start, 60, quantities))
+            .unwrap()
+    }
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_load_band_pairs_minimum_and_maximum() {
+        let xml = week_ahead_load_document(DEFAULT_ZONE, midnight(), 7);
+        let document: GlMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+        // Summing every series of the document would add the bounds up
+        assert_eq!(
+            document.all_timestamped_points().unwrap()[0].quantity,
+            100_000.0
+        );
+
+        let band = load_band(&document, DEFAULT_ZONE).unwrap();
+        assert_eq!(band.len(), 7);
+        assert_eq!(band[0].duration, Duration::days(1));
+        assert_eq!((band[6].min_mw, band[6].max_mw), (45_000.0, 55_000.0));
+        assert_eq!(band[6].end(), midnight() + Duration::days(7));
+
+        let at = |hours| load_band_at(&band, midnight() + Duration::hours(hours));
+        assert_eq!(at(30).unwrap().timestamp, midnight() + Duration::days(1));
+        assert!(at(-1).is_none());
+        assert!(at(7 * 24).is_none());
+        assert!(load_band(&document, "10YFR-RTE------C").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_load_band_requests_the_week_ahead_forecast() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+
+        let band = client
+            .fetch_load_band(DEFAULT_ZONE, midnight(), midnight() + Duration::days(7))
+            .await
+            .unwrap();
+        assert!(!band.is_empty());
+        let url = &transport.requests()[0];
+        assert_eq!(query_param(url, "documentType").as_deref(), Some("A65"));
+        assert_eq!(query_param(url, "processType").as_deref(), Some("A31"));
+    }
+
+    #[test]
+    fn test_merge_prefers_newer_document_and_stitches() {
+        let day_ahead = document("2024-05-31T12:00:00Z", midnight(), &[100.0; 24]);
+        // Intraday update covering 06:00-12:00
+        let intraday = document(
+            "2024-06-01T05:00:00Z",
+            midnight() + Duration::hours(6),
+            &[200.0; 6],
+        );
+
+        let merged = merge_forecasts(
+            &[
+                (ForecastSource::Intraday, &intraday),
+                (ForecastSource::DayAhead, &day_ahead),
+            ],
+            &SeriesFilter::new(),
+        )
+        .unwrap();
+
+        assert_eq!(merged.len(), 24);
+        for (hour, point) in merged.iter().enumerate() {
+            assert_eq!(point.timestamp, midnight() + Duration::hours(hour as i64));
+            if (6..12).contains(&hour) {
+                assert_eq!(point.source, ForecastSource::Intraday);
+                assert_eq!(point.quantity, 200.0);
+            } else {
+                assert_eq!(point.source, ForecastSource::DayAhead);
+                assert_eq!(point.quantity, 100.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_coverage_of_document_periods() {
+        let hours = |h: i64| midnight() + Duration::hours(h);
+        let morning = document("2024-05-31T12:00:00Z", hours(0), &[100.0; 6]);
+        let evening = document("2024-05-31T12:00:00Z", hours(18), &[100.0; 4]);
+        let overlapping = document("2024-05-31T12:00:00Z", hours(4), &[100.0; 4]);
+        let covered = covered_intervals(
+            &[
+                (ForecastSource::DayAhead, &morning),
+                (ForecastSource::DayAhead, &evening),
+                (ForecastSource::Intraday, &overlapping),
+            ],
+            &SeriesFilter::new(),
+        )
+        .unwrap();
+        let interval = |start, end| Interval {
+            start: hours(start),
+            end: hours(end),
+        };
+        assert_eq!(covered, [interval(0, 8), interval(18, 22)]);
+
+        let coverage = Coverage::new(interval(2, 24), &covered);
+        assert_eq!(coverage.covered, [interval(2, 8), interval(18, 22)]);
+        assert_eq!(coverage.gaps, [interval(8, 18), interval(22, 24)]);
+        assert_eq!(coverage.covered_duration(), Duration::hours(10));
+        assert!(!coverage.is_complete());
+
+        assert!(Coverage::new(interval(1, 7), &covered).is_complete());
+        assert_eq!(Coverage::new(interval(0, 4), &[]).gaps, [interval(0, 4)]);
+        // Surplus needs both forecasts
+        assert_eq!(
+            intersection(&covered, &[interval(6, 20)]),
+            [interval(6, 8), interval(18, 20)]
+        );
+    }
+
+    #[test]
+    fn test_merge_ignores_outdated_intraday_document() {
+        let day_ahead = document("2024-05-31T12:00:00Z", midnight(), &[100.0; 24]);
+        let stale_intraday = document("2024-05-30T12:00:00Z", midnight(), &[200.0; 24]);
+
+        let merged = merge_forecasts(
+            &[
+                (ForecastSource::DayAhead, &day_ahead),
+                (ForecastSource::Intraday, &stale_intraday),
+            ],
+            &SeriesFilter::new(),
+        )
+        .unwrap();
+
+        assert!(merged.iter().all(|p| p.source == ForecastSource::DayAhead));
+        assert!(merged.iter().all(|p| p.quantity == 100.0));
+    }
+
+    #[test]
+    fn test_merge_overlays_current_on_intraday() {
+        let day_ahead = document("2024-05-31T12:00:00Z", midnight(), &[100.0; 24]);
+        let intraday = document(
+            "2024-06-01T05:00:00Z",
+            midnight() + Duration::hours(6),
+            &[200.0; 12],
+        );
+        // Current update covering 10:00-14:00, inside the intraday update
+        let current = document(
+            "2024-06-01T09:45:00Z",
+            midnight() + Duration::hours(10),
+            &[300.0; 4],
+        );
+
+        let merged = merge_forecasts(
+            &[
+                (ForecastSource::Current, &current),
+                (ForecastSource::DayAhead, &day_ahead),
+                (ForecastSource::Intraday, &intraday),
+            ],
+            &SeriesFilter::new(),
+        )
+        .unwrap();
+
+        let sources: Vec<_> = merged.iter().map(|p| (p.source, p.quantity)).collect();
+        assert_eq!(sources[5], (ForecastSource::DayAhead, 100.0));
+        assert_eq!(sources[6], (ForecastSource::Intraday, 200.0));
+        assert_eq!(sources[9], (ForecastSource::Intraday, 200.0));
+        assert_eq!(sources[10], (ForecastSource::Current, 300.0));
+        assert_eq!(sources[13], (ForecastSource::Current, 300.0));
+        assert_eq!(sources[14], (ForecastSource::Intraday, 200.0));
+        assert_eq!(sources[17], (ForecastSource::Intraday, 200.0));
+        assert_eq!(sources[18], (ForecastSource::DayAhead, 100.0));
+        assert_eq!(merged.len(), 24);
+    }
+
+    fn points(resolution_minutes: i64, quantities: &[f64]) -> Vec<TimestampedPoint> {
+        let duration = Duration::minutes(resolution_minutes);
+        quantities
+            .iter()
+            .enumerate()
+            .map(|(i, &quantity)| TimestampedPoint {
+                timestamp: midnight() + duration * i as i32,
+                position: i as u32 + 1,
+                quantity,
+                unit: MeasureUnit::Megawatt,
+                duration,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_join_surplus_of_plain_points() {
+        let generation = points(60, &[500.0, 800.0, 300.0]);
+        // Load starts an hour later
+        let mut load = points(60, &[400.0, 400.0, 400.0]);
+        for point in &mut load {
+            point.timestamp += Duration::hours(1);
+        }
+
+        let series = join_surplus(&generation, &load).unwrap();
+
+        let surpluses: Vec<_> = series.points.iter().map(|p| p.surplus).collect();
+        assert_eq!(surpluses, [400.0, -100.0]);
+        assert_eq!(series.unit, MeasureUnit::Megawatt);
+        assert_eq!(
+            series.covered,
+            [Interval {
+                start: midnight() + Duration::hours(1),
+                end: midnight() + Duration::hours(3),
+            }]
+        );
+
+        load[0].unit = MeasureUnit::MegawattHour;
+        assert!(join_surplus(&generation, &load).is_err());
+    }
+
+    #[test]
+    fn test_utilization_across_resolutions() {
+        let capacity = points(60, &[1000.0, 0.0, 500.0]);
+        // Flows end half way through the last hour
+        let flows = points(15, &[250.0; 10]);
+
+        let utilization = interconnector_utilization(&capacity, &flows);
+
+        assert_eq!(utilization.len(), 10);
+        assert!(
+            utilization
+                .iter()
+                .all(|p| p.duration == Duration::minutes(15))
+        );
+        let percentages: Vec<_> = utilization.iter().map(|p| p.utilization).collect();
+        assert_eq!(&percentages[..4], [Some(25.0); 4]);
+        assert_eq!(&percentages[4..8], [None; 4]);
+        assert_eq!(&percentages[8..], [Some(50.0); 2]);
+        assert_eq!(
+            utilization[9].timestamp,
+            midnight() + Duration::minutes(135)
+        );
+
+        // Finer capacity than flows splits the flow intervals instead
+        let utilization =
+            interconnector_utilization(&points(30, &[400.0, 800.0]), &points(60, &[200.0]));
+        let halves: Vec<_> = utilization
+            .iter()
+            .map(|p| (p.duration.num_minutes(), p.utilization))
+            .collect();
+        assert_eq!(halves, [(30, Some(50.0)), (30, Some(25.0))]);
+    }
+
+    fn surplus_point(hour: i64, generation: f64, load: f64) -> RenewableSurplus {
+        RenewableSurplus {
+            timestamp: midnight() + Duration::hours(hour),
+            generation,
+            load,
+            surplus: generation - load,
+            total_generation: None,
+        }
+    }
+
+    #[test]
+    fn test_extremes_do_not_depend_on_the_order() {
+        // Ties at the maximum (hours 1 and 4) and minimum (hours 2 and 5)
+        let series: Vec<_> = [500.0, 900.0, 100.0, 400.0, 900.0, 100.0]
+            .iter()
+            .enumerate()
+            .map(|(hour, &generation)| surplus_point(hour as i64, generation, 300.0))
+            .collect();
+
+        for seed in 0..200 {
+            let series = shuffled(&series, seed);
+            let max = max_surplus(&series).unwrap();
+            assert_eq!(
+                max.timestamp,
+                midnight() + Duration::hours(1),
+                "seed {}",
+                seed
+            );
+            let min = find_min_surplus(&series).unwrap();
+            assert_eq!(
+                min.timestamp,
+                midnight() + Duration::hours(2),
+                "seed {}",
+                seed
+            );
+        }
+
+        // Equally good windows: the earliest wins
+        let (start, mean) = best_window(&series[..5], Duration::hours(1)).unwrap();
+        assert_eq!((start, mean), (midnight() + Duration::hours(1), 600.0));
+    }
+
+    #[test]
+    fn test_coverage_counts_dropped_points() {
+        let series = SurplusSeries {
+            covered: vec![Interval {
+                start: midnight(),
+                end: midnight() + Duration::hours(24),
+            }],
+            dropped_points: 3,
+            ..SurplusSeries::default()
+        };
+        let coverage = series.coverage(midnight(), midnight() + Duration::hours(6));
+        assert!(coverage.is_complete());
+        assert_eq!(coverage.dropped_points, 3);
+    }
+
+    #[test]
+    fn test_reference_time_falls_back_to_the_first_point() {
+        let series = SurplusSeries {
+            points: (6..12)
+                .map(|hour| surplus_point(hour, 500.0, 300.0))
+                .collect(),
+            ..SurplusSeries::default()
+        };
+        let at = |hour| midnight() + Duration::hours(hour);
+
+        assert_eq!(series.reference_time(at(8)), at(8));
+        assert_eq!(series.reference_time(at(11)), at(11));
+        assert_eq!(series.reference_time(at(2)), at(6));
+        assert_eq!(series.reference_time(at(30)), at(6));
+        assert_eq!(
+            series.points.iter().within(at(30), 6).count(),
+            0,
+            "filtering from now drops everything"
+        );
+        assert_eq!(
+            series
+                .points
+                .iter()
+                .within(series.reference_time(at(30)), 6)
+                .count(),
+            6
+        );
+        assert_eq!(SurplusSeries::default().reference_time(at(30)), at(30));
+    }
+
+    fn quarter_hourly(count: usize) -> Vec<RenewableSurplus> {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        (0..count)
+            .map(|i| {
+                let generation = 30_000.0 + 10_000.0 * ((i % 96) as f64 / 15.0).sin();
+                let load = 50_000.0 - (i % 7) as f64 * 500.0;
+                RenewableSurplus {
+                    timestamp: start + Duration::minutes(15 * i as i64),
+                    generation,
+                    load,
+                    surplus: generation - load,
+                    total_generation: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Power points given as `(start minute, duration in minutes, MW)`
+    fn irregular(points: &[(i64, i64, f64)]) -> Vec<TimestampedPoint> {
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, &(start, minutes, quantity))| TimestampedPoint {
+                timestamp: midnight() + Duration::minutes(start),
+                position: i as u32 + 1,
+                quantity,
+                unit: MeasureUnit::Megawatt,
+                duration: Duration::minutes(minutes),
+            })
+            .collect()
+    }
+
+    fn quantities(points: &[TimestampedPoint]) -> Vec<f64> {
+        points.iter().map(|point| point.quantity).collect()
+    }
+
+    fn starts(points: &[TimestampedPoint]) -> Vec<i64> {
+        points
+            .iter()
+            .map(|point| (point.timestamp - midnight()).num_minutes())
+            .collect()
+    }
+
+    #[test]
+    fn test_mean_downsample_weights_irregular_points_by_time() {
+        // 15 minutes of 100 MW, then 45 of 200 MW, then a 30-minute point
+        let points = irregular(&[(0, 15, 100.0), (15, 45, 200.0), (60, 30, 400.0)]);
+        let resampled = resample_with(
+            &points,
+            Duration::hours(1),
+            ResampleMethod::MeanDownsample,
+            PartialBuckets::Keep,
+        );
+
+        assert_eq!(resampled.len(), 2);
+        assert_eq!(resampled[0].point.quantity, 175.0);
+        assert_eq!(resampled[0].coverage, 1.0);
+        assert_eq!(resampled[0].point.duration, Duration::hours(1));
+        // The second hour is covered half, its value that of the covered half
+        assert_eq!(resampled[1].point.quantity, 400.0);
+        assert_eq!(resampled[1].coverage, 0.5);
+        assert_eq!(resampled[1].point.position, 2);
+
+        let complete = resample_with(
+            &points,
+            Duration::hours(1),
+            ResampleMethod::MeanDownsample,
+            PartialBuckets::Drop,
+        );
+        assert_eq!(complete.len(), 1);
+        assert_eq!(complete[0].point.timestamp, midnight());
+    }
+
+    #[test]
+    fn test_resampling_keeps_gaps_and_edges() {
+        // Starts half way through an hour and leaves 02:00 to 03:00 out
+        let points = irregular(&[(30, 60, 100.0), (90, 30, 300.0), (180, 60, 50.0)]);
+
+        let hourly = resample_with(
+            &points,
+            Duration::hours(1),
+            ResampleMethod::MeanDownsample,
+            PartialBuckets::Keep,
+        );
+        let hours: Vec<i64> = hourly
+            .iter()
+            .map(|r| r.point.timestamp.hour() as i64)
+            .collect();
+        assert_eq!(hours, [0, 1, 3]);
+        let coverage: Vec<f64> = hourly.iter().map(|r| r.coverage).collect();
+        assert_eq!(coverage, [0.5, 1.0, 1.0]);
+        assert_eq!(hourly[1].point.quantity, 200.0);
+
+        // Covered time stays the same however coarse the target
+        let covered = |resampled: &[ResampledPoint]| -> i64 {
+            resampled
+                .iter()
+                .map(|r| (r.point.duration.num_minutes() as f64 * r.coverage) as i64)
+                .sum()
+        };
+        for minutes in [15, 30, 60, 120, 24 * 60] {
+            let resampled = resample_with(
+                &points,
+                Duration::minutes(minutes),
+                ResampleMethod::MeanDownsample,
+                PartialBuckets::Keep,
+            );
+            assert_eq!(covered(&resampled), 150, "{} minutes", minutes);
+        }
+
+        // Two-hour intervals each cover part of the input
+        let two_hourly = resample_with(
+            &points,
+            Duration::hours(2),
+            ResampleMethod::MeanDownsample,
+            PartialBuckets::Drop,
+        );
+        assert!(two_hourly.is_empty());
+    }
+
+    #[test]
+    fn test_forward_fill_irregular_points() {
+        let points = irregular(&[(0, 90, 100.0), (90, 30, 200.0)]);
+        let half_hourly = resample(&points, Duration::minutes(30), ResampleMethod::ForwardFill);
+        assert_eq!(quantities(&half_hourly), [100.0, 100.0, 100.0, 200.0]);
+        assert_eq!(starts(&half_hourly), [0, 30, 60, 90]);
+
+        // Coarser than the input it takes the first point of each interval
+        let hourly = resample(&points, Duration::hours(1), ResampleMethod::ForwardFill);
+        assert_eq!(quantities(&hourly), [100.0, 100.0]);
+
+        // A gap is not filled
+        let gapped = irregular(&[(0, 15, 100.0), (45, 15, 200.0)]);
+        let quarter_hourly = resample(&gapped, Duration::minutes(15), ResampleMethod::ForwardFill);
+        assert_eq!(starts(&quarter_hourly), [0, 45]);
+    }
+
+    #[test]
+    fn test_linear_upsample() {
+        let points = irregular(&[(0, 60, 100.0), (60, 60, 200.0), (120, 60, 400.0)]);
+        let half_hourly = resample(
+            &points,
+            Duration::minutes(30),
+            ResampleMethod::LinearUpsample,
+        );
+        // The last point holds until it ends
+        assert_eq!(
+            quantities(&half_hourly),
+            [100.0, 150.0, 200.0, 300.0, 400.0, 400.0]
+        );
+
+        // Irregular spacing, and no line across the gap after 01:00
+        let points = irregular(&[(0, 20, 0.0), (20, 40, 120.0), (90, 30, 600.0)]);
+        let ten_minutes = resample(
+            &points,
+            Duration::minutes(10),
+            ResampleMethod::LinearUpsample,
+        );
+        assert_eq!(starts(&ten_minutes), [0, 10, 20, 30, 40, 50, 90, 100, 110]);
+        assert_eq!(
+            quantities(&ten_minutes),
+            [0.0, 60.0, 120.0, 120.0, 120.0, 120.0, 600.0, 600.0, 600.0]
+        );
+
+        // An interval starting before the input is evaluated where the input starts
+        let late = irregular(&[(10, 20, 100.0), (30, 30, 400.0)]);
+        let half_hourly = resample(&late, Duration::minutes(30), ResampleMethod::LinearUpsample);
+        assert_eq!(quantities(&half_hourly), [100.0, 400.0]);
+    }
+
+    #[test]
+    fn test_resampling_energy_points() {
+        let mut points = irregular(&[(0, 15, 0.0), (15, 15, 0.0), (30, 30, 0.0)]);
+        for (point, energy) in points.iter_mut().zip([25.0, 25.0, 50.0]) {
+            point.unit = MeasureUnit::MegawattHour;
+            point.quantity = energy;
+        }
+
+        let hourly = resample(&points, Duration::hours(1), ResampleMethod::MeanDownsample);
+        assert_eq!(hourly.len(), 1);
+        assert_eq!(hourly[0].unit, MeasureUnit::MegawattHour);
+        assert_eq!(hourly[0].quantity, 100.0);
+
+        // Energy is shared out when upsampling, and only the covered part counts
+        let quarter_hourly = resample(&points, Duration::minutes(15), ResampleMethod::ForwardFill);
+        assert_eq!(quantities(&quarter_hourly), [25.0, 25.0, 25.0, 25.0]);
+        let two_hourly = resample_with(
+            &points[..2],
+            Duration::hours(2),
+            ResampleMethod::MeanDownsample,
+            PartialBuckets::Keep,
+        );
+        assert_eq!(two_hourly[0].point.quantity, 50.0);
+        assert_eq!(two_hourly[0].coverage, 0.25);
+    }
+
+    #[test]
+    fn test_resample_edge_cases() {
+        let points = irregular(&[(60, 60, 200.0), (0, 60, 100.0)]);
+        // Unsorted input
+        let hourly = resample(&points, Duration::hours(1), ResampleMethod::MeanDownsample);
+        assert_eq!(quantities(&hourly), [100.0, 200.0]);
+        assert_eq!(
+            quantities(&resample(
+                &points,
+                Duration::hours(2),
+                ResampleMethod::MeanDownsample
+            )),
+            [150.0]
+        );
+
+        assert!(resample(&[], Duration::hours(1), ResampleMethod::ForwardFill).is_empty());
+        assert!(resample(&points, Duration::zero(), ResampleMethod::ForwardFill).is_empty());
+        let empty = irregular(&[(0, 0, 100.0)]);
+        assert!(resample(&empty, Duration::hours(1), ResampleMethod::ForwardFill).is_empty());
+    }
+
+    #[test]
+    fn test_daily_energy_shares_points_spanning_midnight() {
+        // 100 MW from 22:00 to 02:00 UTC
+        let points = irregular(&[(-120, 240, 100.0)]);
+        let days = daily_energy(&points, &LocalZone::UTC);
+        let energy: Vec<f64> = days.iter().map(|day| day.energy).collect();
+        assert_eq!(energy, [200.0, 200.0]);
+        assert_eq!((days[0].points, days[1].points), (1, 0));
+    }
 
-        // Create a map of load by timestamp for quick lookup
-        let load_map: HashMap<DateTime<Utc>, f64> = load_points
-            .into_iter()
-            .map(|p| (p.timestamp, p.quantity))
+    #[test]
+    fn test_join_surplus_across_resolutions() {
+        // Hourly generation against quarter-hourly load that ends a quarter early
+        let generation = points(60, &[500.0, 800.0]);
+        let load = points(15, &[100.0, 200.0, 300.0, 400.0, 400.0, 400.0, 400.0]);
+
+        let series = join_surplus(&generation, &load).unwrap();
+        let surpluses: Vec<_> = series.points.iter().map(|p| p.surplus).collect();
+        assert_eq!(surpluses, [250.0]);
+        assert_eq!(series.covered[0].end, midnight() + Duration::hours(1));
+    }
+
+    #[test]
+    fn test_series_fingerprint() {
+        let series = quarter_hourly(8);
+        let fingerprint = series_fingerprint(&series);
+
+        let mut reversed = series.clone();
+        reversed.reverse();
+        assert_eq!(series_fingerprint(&reversed), fingerprint);
+        // Stable across runs and builds
+        assert_eq!(series_fingerprint(&[]), 0xcbf2_9ce4_8422_2325);
+
+        let mut changed = series.clone();
+        changed[3].load += 10.0;
+        assert_ne!(series_fingerprint(&changed), fingerprint);
+        let mut later = series.clone();
+        later[7].timestamp += Duration::minutes(1);
+        assert_ne!(series_fingerprint(&later), fingerprint);
+        let mut shorter = series.clone();
+        shorter.pop();
+        assert_ne!(series_fingerprint(&shorter), fingerprint);
+
+        // Noise within the precision does not change the fingerprint, but coarser
+        // changes only do at a finer precision
+        let round = |mut series: Vec<RenewableSurplus>| {
+            for point in &mut series {
+                point.generation = point.generation.round();
+                point.surplus = point.generation - point.load;
+            }
+            series
+        };
+        let series = round(series);
+        let mut noisy = series.clone();
+        noisy[0].generation += 0.1;
+        assert_eq!(series_fingerprint(&noisy), series_fingerprint(&series));
+        let mut shifted = series.clone();
+        shifted[0].generation += 2.0;
+        assert_eq!(
+            series_fingerprint_with(&shifted, 10.0),
+            series_fingerprint_with(&series, 10.0)
+        );
+        assert_ne!(
+            series_fingerprint_with(&shifted, 1.0),
+            series_fingerprint_with(&series, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_diff_summary() {
+        let old = quarter_hourly(6);
+        let mut new: Vec<RenewableSurplus> = quarter_hourly(8)[2..].to_vec();
+        new[1].load += 0.2;
+        new[2].generation -= 300.0;
+        new[2].surplus -= 300.0;
+
+        let diff = diff_summary(&old, &new);
+        assert_eq!(
+            diff.added,
+            [
+                old[5].timestamp + Duration::minutes(15),
+                old[5].timestamp + Duration::minutes(30)
+            ]
+        );
+        assert_eq!(diff.removed, [old[0].timestamp, old[1].timestamp]);
+        let change = diff.largest_change.unwrap();
+        assert_eq!(change.timestamp, old[4].timestamp);
+        assert_eq!(change.quantity, "generation");
+        assert_eq!(change.delta(), -300.0);
+        assert!(diff.is_significant(FINGERPRINT_PRECISION_MW));
+
+        // Points dropping out of the window and noise are not significant
+        let mut noisy: Vec<RenewableSurplus> = old[1..].to_vec();
+        noisy[0].load += 0.2;
+        let diff = diff_summary(&old, &noisy);
+        assert_eq!(diff.removed.len(), 1);
+        assert!(!diff.is_significant(FINGERPRINT_PRECISION_MW));
+        assert!(diff.is_significant(0.1));
+        assert!(diff_summary(&old, &[]).largest_change.is_none());
+    }
+
+    #[test]
+    fn test_resample_mean() {
+        let series = quarter_hourly(8);
+        let hourly = resample_mean(&series, Duration::hours(1));
+
+        assert_eq!(hourly.len(), 2);
+        assert_eq!(hourly[1].timestamp, series[4].timestamp);
+        let load: f64 = series[..4].iter().map(|p| p.load).sum::<f64>() / 4.0;
+        assert_eq!(hourly[0].load, load);
+        assert_eq!(hourly[0].total_generation, None);
+    }
+
+    #[test]
+    fn test_downsample_fits_budget_and_stays_within_bounds() {
+        // Two weeks of 15-minute points
+        let series = quarter_hourly(14 * 96);
+        assert!(downsample(&series, series.len(), &LocalZone::UTC).is_none());
+
+        let (bucket, hourly) = downsample(&series, 500, &LocalZone::UTC).unwrap();
+        assert_eq!(bucket, Duration::hours(1));
+        assert_eq!(hourly.len(), 14 * 24);
+
+        let (bucket, coarse) = downsample(&series, 120, &LocalZone::UTC).unwrap();
+        assert_eq!(bucket, Duration::hours(3));
+        assert_eq!(coarse.len(), 14 * 8);
+
+        let surplus = |points: &[RenewableSurplus]| {
+            let values = points.iter().map(|p| p.surplus);
+            (
+                values.clone().fold(f64::INFINITY, f64::min),
+                values.fold(f64::NEG_INFINITY, f64::max),
+            )
+        };
+        let (min, max) = surplus(&series);
+        for points in [&hourly, &coarse] {
+            let (downsampled_min, downsampled_max) = surplus(points);
+            assert!(min <= downsampled_min && downsampled_max <= max);
+        }
+
+        // Already hourly series skip the hourly bucket
+        let (bucket, _) = downsample(&hourly, 200, &LocalZone::UTC).unwrap();
+        assert_eq!(bucket, Duration::hours(3));
+    }
+
+    /// Load points of a fixture of `tests/fixtures`
+    fn fixture_points(xml: &str) -> Vec<TimestampedPoint> {
+        let document: GlMarketDocument = quick_xml::de::from_str(xml).unwrap();
+        document
+            .timestamped_points_where(&load_series("10Y1001A1001A83F"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_surplus_points_chain() {
+        let series: Vec<RenewableSurplus> = (0..48)
+            .map(|hour| surplus_point(hour, 1_000.0 * (hour % 24) as f64, 10_000.0))
             .collect();
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+        let hours = |points: Vec<&RenewableSurplus>| -> Vec<i64> {
+            points
+                .iter()
+                .map(|p| (p.timestamp - midnight()).num_hours())
+                .collect()
+        };
 
-        // Calculate surplus for each generation point that has matching load data
-        let surpluses: Vec<RenewableSurplus> = gen_points
-            .into_iter()
-            .filter_map(|gen_point| {
-                load_map
-                    .get(&gen_point.timestamp)
-                    .map(|&load| RenewableSurplus {
-                        timestamp: gen_point.timestamp,
-                        generation: gen_point.quantity,
-                        load,
-                        surplus: gen_point.quantity - load,
-                    })
+        // 22:00-06:00 CEST is 20:00-04:00 UTC
+        let night: Vec<_> = series[..24]
+            .iter()
+            .night_hours(DailyHours::NIGHT, &berlin)
+            .collect();
+        assert_eq!(hours(night), [0, 1, 2, 3, 20, 21, 22, 23]);
+
+        let night_max = series
+            .iter()
+            .night_hours(DailyHours::NIGHT, &berlin)
+            .max_surplus()
+            .unwrap();
+        assert_eq!(night_max.timestamp, midnight() + Duration::hours(23));
+
+        let ahead: Vec<_> = series
+            .iter()
+            .within(midnight() + Duration::hours(30), 6)
+            .above(-5_000.0)
+            .collect();
+        assert_eq!(hours(ahead), [30, 31, 32, 33, 34, 35, 36]);
+
+        let min = series.iter().within(midnight() + Duration::hours(12), 24);
+        assert_eq!(
+            min.min_surplus().unwrap().timestamp,
+            midnight() + Duration::hours(24)
+        );
+        assert!(series.iter().above(20_000.0).max_surplus().is_none());
+        // The series is still there
+        assert_eq!(series.len(), 48);
+
+        // The night the clocks go back lasts nine hours
+        let start = Utc.with_ymd_and_hms(2024, 10, 26, 12, 0, 0).unwrap();
+        let autumn: Vec<_> = series
+            .iter()
+            .map(|point| RenewableSurplus {
+                timestamp: start + (point.timestamp - midnight()),
+                ..point.clone()
             })
             .collect();
+        let night = autumn.iter().night_hours(DailyHours::NIGHT, &berlin);
+        assert_eq!(
+            night
+                .take_while(|p| p.timestamp < start + Duration::hours(24))
+                .count(),
+            9
+        );
 
-        // Find the maximum surplus
-        surpluses
+        assert!(DailyHours::NIGHT.contains(23) && DailyHours::NIGHT.contains(0));
+        assert!(!DailyHours::NIGHT.contains(6));
+        assert!(DailyHours { from: 9, to: 17 }.contains(9));
+    }
+
+    #[test]
+    fn test_clock_change_days() {
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+        let spring = fixture_points(include_str!(
+            "../../tests/fixtures/a65_dst_spring_forward_pt15m.xml"
+        ));
+        // Published in two periods
+        let autumn = fixture_points(include_str!(
+            "../../tests/fixtures/a65_dst_fall_back_pt15m.xml"
+        ));
+
+        for (points, count, hours) in [(&spring, 92, 23), (&autumn, 100, 25)] {
+            assert_eq!(points.len(), count);
+            // Back to back, without gaps or points at the same instant
+            for pair in points.windows(2) {
+                assert_eq!(pair[0].end(), pair[1].timestamp);
+            }
+
+            let mut local: Vec<_> = points
+                .iter()
+                .map(|p| berlin.to_local(p.timestamp).naive_local())
+                .collect();
+            assert_eq!(local[0].hour(), 0);
+            assert_eq!(local[count - 1].hour(), 23);
+            local.sort();
+            local.dedup();
+            // 02:00-03:00 happens twice when the clocks go back
+            let repeated = if hours == 25 { 4 } else { 0 };
+            assert_eq!(local.len(), count - repeated);
+
+            let days = daily_energy(points, &berlin);
+            assert_eq!(days.len(), 1);
+            let day = &days[0];
+            assert_eq!(
+                day.interval.end - day.interval.start,
+                Duration::hours(hours)
+            );
+            assert_eq!(day.points, count);
+            let energy: f64 = points.iter().map(|p| p.quantity / 4.0).sum();
+            assert_eq!(day.energy, energy);
+        }
+        assert_eq!(
+            daily_energy(&spring, &berlin)[0].date.to_string(),
+            "2024-03-31"
+        );
+        assert_eq!(
+            daily_energy(&autumn, &berlin)[0].date.to_string(),
+            "2024-10-27"
+        );
+        // The same points span two UTC days
+        assert_eq!(daily_energy(&spring, &LocalZone::UTC).len(), 2);
+    }
+
+    #[test]
+    fn test_daily_means_follow_local_days() {
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+        // Local midnight of 30 March to that of 1 April, across the spring clock change
+        let start = Utc.with_ymd_and_hms(2024, 3, 29, 23, 0, 0).unwrap();
+        let series: Vec<RenewableSurplus> = (0..47)
+            .map(|hour| RenewableSurplus {
+                timestamp: start + Duration::hours(hour),
+                generation: 0.0,
+                load: 0.0,
+                surplus: if hour < 24 { 1.0 } else { 2.0 },
+                total_generation: None,
+            })
+            .collect();
+
+        let days = daily_means(&series, &berlin);
+        let means: Vec<_> = days.iter().map(|p| (p.timestamp, p.surplus)).collect();
+        assert_eq!(means, [(start, 1.0), (start + Duration::hours(24), 2.0)]);
+        assert_eq!(resample_mean(&series, Duration::hours(24)).len(), 3);
+    }
+
+    #[test]
+    fn test_hourly_profile_by_local_hour_and_day_kind() {
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+        // Friday 22:00 UTC to Sunday 22:00 UTC: local Saturday and Sunday, plus one
+        // Friday hour before
+        let start = Utc.with_ymd_and_hms(2024, 5, 31, 21, 0, 0).unwrap();
+        let series: Vec<RenewableSurplus> = (0..49)
+            .map(|hour| RenewableSurplus {
+                timestamp: start + Duration::hours(hour),
+                generation: 0.0,
+                load: 0.0,
+                surplus: hour as f64,
+                total_generation: None,
+            })
+            .collect();
+
+        let profile = hourly_profile(&series, &berlin);
+        assert!(
+            profile
+                .iter()
+                .enumerate()
+                .all(|(hour, s)| s.hour == hour as u32)
+        );
+        // Local 23:00 of Friday, Saturday and Sunday
+        assert_eq!(profile[23].samples, 3);
+        assert_eq!(profile[23].mean, Some(24.0));
+        assert_eq!(profile[23].p25, Some(12.0));
+        assert_eq!(profile[23].p75, Some(36.0));
+        assert_eq!(profile[0].samples, 2);
+
+        let weekend = hourly_profile_on(&series, &berlin, DayKind::Weekend);
+        assert_eq!(weekend[23].samples, 2);
+        assert_eq!(weekend[23].mean, Some(36.0));
+        let weekday = hourly_profile_on(&series, &berlin, DayKind::Weekday);
+        assert_eq!(weekday[23].samples, 1);
+        assert_eq!(weekday[23].p25, Some(0.0));
+        assert_eq!(weekday[0].samples, 0);
+        assert_eq!(weekday[0].mean, None);
+    }
+
+    fn battery_series(step: Duration, points: &[(f64, f64)]) -> Vec<RenewableSurplus> {
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, &(generation, load))| RenewableSurplus {
+                timestamp: midnight() + step * i as i32,
+                generation,
+                load,
+                surplus: generation - load,
+                total_generation: None,
+            })
+            .collect()
+    }
+
+    /// Rounded to the micro unit, to compare sums of floats
+    fn rounded(values: impl IntoIterator<Item = f64>) -> Vec<f64> {
+        values
             .into_iter()
-            .max_by(|a, b| {
-                a.surplus
-                    .partial_cmp(&b.surplus)
-                    .unwrap_or(std::cmp::Ordering::Equal)
+            .map(|value| (value * 1e6).round() / 1e6 + 0.0)
+            .collect()
+    }
+
+    #[test]
+    fn test_battery_charges_on_highest_and_discharges_on_lowest_surplus() {
+        // Surplus -10, 100, -60, -70 around a median of -35
+        let series = battery_series(
+            Duration::hours(1),
+            &[(90.0, 100.0), (200.0, 100.0), (40.0, 100.0), (30.0, 100.0)],
+        );
+        let battery = BatteryParams {
+            capacity_kwh: 10.0,
+            max_power_kw: 3.0,
+            efficiency: 0.9,
+            initial_soc: 0.0,
+        };
+        let simulation = simulate_battery(&series, &battery);
+
+        // 3 kW on the grid side store 2.7 kWh an hour
+        assert_eq!(
+            rounded(simulation.steps.iter().map(|step| step.power_kw)),
+            [3.0, 3.0, -2.4, -3.0]
+        );
+        assert_eq!(
+            rounded(simulation.steps.iter().map(|step| step.soc_kwh)),
+            [2.7, 5.4, 3.0, 0.0]
+        );
+        assert_eq!(rounded([simulation.charged_kwh]), [6.0]);
+        assert_eq!(rounded([simulation.discharged_kwh]), [5.4]);
+        assert_eq!(rounded([simulation.final_soc_kwh]), [0.0]);
+        // Charged at penetrations of 90 and 200 %, of which 100 count
+        assert_eq!(rounded(simulation.renewable_share_of_charge), [95.0]);
+
+        let flat = battery_series(Duration::hours(1), &[(50.0, 100.0); 3]);
+        let simulation = simulate_battery(&flat, &battery);
+        assert!(simulation.steps.iter().all(|step| step.power_kw == 0.0));
+        assert_eq!(simulation.renewable_share_of_charge, None);
+        assert!(simulate_battery(&[], &battery).steps.is_empty());
+    }
+
+    #[test]
+    fn test_battery_in_quarter_hours_stays_within_capacity() {
+        // Surplus -1, 1, 2, -2: starting full, it has to discharge before it can charge
+        let series = battery_series(
+            Duration::minutes(15),
+            &[(49.0, 50.0), (51.0, 50.0), (52.0, 50.0), (48.0, 50.0)],
+        );
+        let battery = BatteryParams {
+            capacity_kwh: 1.0,
+            max_power_kw: 2.0,
+            efficiency: 1.0,
+            initial_soc: 1.0,
+        };
+        let simulation = simulate_battery(&series, &battery);
+
+        assert!(
+            simulation
+                .steps
+                .iter()
+                .all(|step| step.duration == Duration::minutes(15))
+        );
+        // A quarter-hour at 2 kW moves 0.5 kWh
+        assert_eq!(
+            rounded(simulation.steps.iter().map(|step| step.power_kw)),
+            [-2.0, 0.0, 2.0, -2.0]
+        );
+        assert_eq!(
+            rounded(simulation.steps.iter().map(|step| step.soc_kwh)),
+            [0.5, 0.5, 1.0, 0.5]
+        );
+        assert_eq!(rounded([simulation.charged_kwh]), [0.5]);
+        assert_eq!(rounded([simulation.discharged_kwh]), [1.0]);
+        assert_eq!(simulation.renewable_share_of_charge, Some(100.0));
+    }
+
+    #[test]
+    fn test_battery_params_are_validated() {
+        let battery = BatteryParams {
+            capacity_kwh: 10.0,
+            max_power_kw: 3.0,
+            efficiency: 0.9,
+            initial_soc: 0.5,
+        };
+        assert_eq!(battery.validate(), Ok(()));
+        for invalid in [
+            BatteryParams {
+                capacity_kwh: 0.0,
+                ..battery
+            },
+            BatteryParams {
+                max_power_kw: f64::INFINITY,
+                ..battery
+            },
+            BatteryParams {
+                efficiency: 0.0,
+                ..battery
+            },
+            BatteryParams {
+                efficiency: 1.1,
+                ..battery
+            },
+            BatteryParams {
+                initial_soc: 1.5,
+                ..battery
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
+        }
+    }
+
+    fn hourly_slots(surpluses: &[f64]) -> Vec<EnergySlot> {
+        surpluses
+            .iter()
+            .enumerate()
+            .map(|(i, &surplus)| EnergySlot {
+                start: midnight() + Duration::hours(i as i64),
+                end: midnight() + Duration::hours(i as i64 + 1),
+                surplus,
+                price: None,
             })
-            .ok_or(EntsoeError::InvalidResponse(
-                "No matching data points found".to_string(),
-            ))
+            .collect()
     }
 
-    /// Get all renewable surplus data points for analysis
-    pub async fn get_renewable_surplus_series(
-        &self,
-        bidding_zone: &str,
-        period_start: &str,
-        period_end: &str,
-    ) -> Result<Vec<RenewableSurplus>, EntsoeError> {
-        // Fetch both forecasts in parallel
-        let (gen_forecast, load_forecast) = tokio::try_join!(
-            self.fetch_day_ahead_generation_forecast(bidding_zone, period_start, period_end),
-            self.fetch_day_ahead_total_load_forecast(bidding_zone, period_start, period_end)
-        )?;
+    /// Hours after midnight the intervals of `plan` cover
+    fn planned_hours(plan: &EnergyPlan) -> Vec<(f64, f64)> {
+        let hours = |t: DateTime<Utc>| (t - midnight()).num_minutes() as f64 / 60.0;
+        plan.intervals
+            .iter()
+            .map(|interval| (hours(interval.start), hours(interval.end)))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_energy_fills_the_highest_surplus_first() {
+        let slots = hourly_slots(&[1.0, 5.0, 3.0, 2.0]);
+        let deadline = midnight() + Duration::hours(4);
+
+        // Exactly two hours at full power
+        let plan = plan_energy(&slots, 6.0, 3.0, deadline, PlanObjective::Surplus).unwrap();
+        assert_eq!(planned_hours(&plan), [(1.0, 2.0), (2.0, 3.0)]);
+        assert_eq!(plan.energy_kwh, 6.0);
+        assert_eq!(plan.mean_surplus, 4.0);
+        assert_eq!(plan.mean_price, None);
+
+        // The last slot needed only as long as it takes
+        let plan = plan_energy(&slots, 4.0, 3.0, deadline, PlanObjective::Surplus).unwrap();
+        assert_eq!(planned_hours(&plan), [(1.0, 2.0), (2.0, 2.0 + 1.0 / 3.0)]);
+        assert_eq!(
+            plan.intervals
+                .iter()
+                .map(|interval| interval.energy_kwh)
+                .collect::<Vec<_>>(),
+            [3.0, 1.0]
+        );
+        assert_eq!(plan.energy_kwh, 4.0);
+
+        // All of it
+        let plan = plan_energy(&slots, 12.0, 3.0, deadline, PlanObjective::Surplus).unwrap();
+        assert_eq!(plan.intervals.len(), 4);
+        assert_eq!(plan.energy_kwh, 12.0);
+    }
+
+    #[test]
+    fn test_plan_energy_reports_the_shortfall() {
+        let slots = hourly_slots(&[1.0, 5.0, 3.0, 2.0]);
+        let shortfall = plan_energy(
+            &slots,
+            13.0,
+            3.0,
+            midnight() + Duration::hours(4),
+            PlanObjective::Surplus,
+        )
+        .unwrap_err();
+        assert_eq!(
+            shortfall,
+            EnergyShortfall {
+                required_kwh: 13.0,
+                available_kwh: 12.0
+            }
+        );
+        assert_eq!(shortfall.shortfall_kwh(), 1.0);
+        assert_eq!(
+            shortfall.to_string(),
+            "only 12.00 of 13.00 kWh fit before the deadline, 1.00 kWh short"
+        );
+
+        // Without data nothing fits
+        let shortfall = plan_energy(&[], 1.0, 3.0, midnight(), PlanObjective::Surplus).unwrap_err();
+        assert_eq!(shortfall.available_kwh, 0.0);
+    }
+
+    #[test]
+    fn test_plan_energy_stops_at_a_deadline_before_the_data_ends() {
+        let slots = hourly_slots(&[1.0, 2.0, 5.0, 9.0]);
+        let deadline = midnight() + Duration::minutes(150);
+
+        let plan = plan_energy(&slots, 1.5, 3.0, deadline, PlanObjective::Surplus).unwrap();
+        assert_eq!(planned_hours(&plan), [(2.0, 2.5)]);
+        assert_eq!(plan.mean_surplus, 5.0);
+
+        let plan = plan_energy(&slots, 7.5, 3.0, deadline, PlanObjective::Surplus).unwrap();
+        assert_eq!(planned_hours(&plan), [(0.0, 1.0), (1.0, 2.0), (2.0, 2.5)]);
+
+        let shortfall =
+            plan_energy(&slots, 8.0, 3.0, deadline, PlanObjective::Surplus).unwrap_err();
+        assert_eq!(shortfall.available_kwh, 7.5);
+    }
+
+    #[test]
+    fn test_plan_energy_by_price() {
+        let mut slots = hourly_slots(&[9.0, 9.0, 1.0, 1.0]);
+        for (slot, price) in slots
+            .iter_mut()
+            .zip([Some(50.0), None, Some(10.0), Some(30.0)])
+        {
+            slot.price = price;
+        }
+        let deadline = midnight() + Duration::hours(4);
+
+        let plan = plan_energy(&slots, 6.0, 3.0, deadline, PlanObjective::Price).unwrap();
+        assert_eq!(planned_hours(&plan), [(2.0, 3.0), (3.0, 4.0)]);
+        assert_eq!(plan.mean_price, Some(20.0));
+        assert_eq!(plan.mean_surplus, 1.0);
 
-        // Get timestamped points for both
-        let gen_points = gen_forecast.all_timestamped_points()?;
-        let load_points = load_forecast.all_timestamped_points()?;
+        // Unpriced hours come last, and leave the mean price unknown
+        let plan = plan_energy(&slots, 9.0, 3.0, deadline, PlanObjective::Price).unwrap();
+        assert_eq!(planned_hours(&plan), [(0.0, 1.0), (2.0, 3.0), (3.0, 4.0)]);
+        assert_eq!(plan.mean_price, Some(30.0));
+        let plan = plan_energy(&slots, 10.0, 3.0, deadline, PlanObjective::Price).unwrap();
+        assert_eq!(plan.intervals.len(), 4);
+        assert_eq!(plan.mean_price, None);
+    }
 
-        // Create a map of load by timestamp
-        let load_map: HashMap<DateTime<Utc>, f64> = load_points
+    #[test]
+    fn test_energy_slots_of_a_series() {
+        let series: Vec<RenewableSurplus> = [0, 15, 30, 60]
             .into_iter()
-            .map(|p| (p.timestamp, p.quantity))
+            .map(|minutes| RenewableSurplus {
+                timestamp: midnight() + Duration::minutes(minutes),
+                generation: 0.0,
+                load: 0.0,
+                surplus: minutes as f64,
+                total_generation: None,
+            })
             .collect();
+        let slots = EnergySlot::of_series(&series);
+        // The gap after 00:30 and the last point last one spacing
+        assert_eq!(
+            slots
+                .iter()
+                .map(|slot| (slot.end - slot.start).num_minutes())
+                .collect::<Vec<_>>(),
+            [15, 15, 15, 15]
+        );
+        assert_eq!(slots[3].surplus, 60.0);
+    }
+
+    fn sg_states(signal: &[SgReadyPoint]) -> Vec<u8> {
+        signal.iter().map(|point| point.state.number()).collect()
+    }
+
+    #[test]
+    fn test_sg_ready_states_of_surplus() {
+        let thresholds = SgThresholds::default();
+        assert_eq!(thresholds.validate(), Ok(()));
+        assert_eq!(thresholds.state_of(-25_000.0), SgReadyState::Locked);
+        assert_eq!(thresholds.state_of(-20_000.0), SgReadyState::Normal);
+        assert_eq!(thresholds.state_of(-0.5), SgReadyState::Normal);
+        assert_eq!(thresholds.state_of(0.0), SgReadyState::Recommended);
+        assert_eq!(thresholds.state_of(10_000.0), SgReadyState::Forced);
+        assert_eq!(SgReadyState::Forced.name(), "forced");
+
+        for invalid in [
+            SgThresholds {
+                recommended_from: -30_000.0,
+                ..thresholds
+            },
+            SgThresholds {
+                forced_from: 0.0,
+                ..thresholds
+            },
+            SgThresholds {
+                normal_from: f64::NAN,
+                ..thresholds
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_sg_ready_signal_does_not_flap_within_the_hysteresis() {
+        let thresholds = SgThresholds::default();
+        // Wavering around the threshold of `recommended` at 0 MW
+        let wavering = [-400.0, 300.0, -200.0, 450.0, -450.0, 100.0];
+
+        let signal = sg_ready_signal(&hourly(&wavering), &thresholds, 1_000.0);
+        assert_eq!(sg_states(&signal), [2; 6]);
+        // Without hysteresis every crossing switches
+        let signal = sg_ready_signal(&hourly(&wavering), &thresholds, 0.0);
+        assert_eq!(sg_states(&signal), [2, 3, 2, 3, 2, 3]);
+
+        // Once past the band, the new state holds until the surplus leaves the band on
+        // the other side
+        let series = hourly(&[-400.0, 600.0, -300.0, 450.0, -499.0, -500.0, -501.0]);
+        let signal = sg_ready_signal(&series, &thresholds, 1_000.0);
+        assert_eq!(sg_states(&signal), [2, 3, 3, 3, 3, 3, 2]);
+        assert_eq!(signal[1].timestamp, midnight() + Duration::hours(1));
+        assert_eq!(signal[1].surplus, 600.0);
+    }
+
+    #[test]
+    fn test_sg_ready_signal_crosses_several_thresholds_at_once() {
+        let series = hourly(&[-30_000.0, 20_000.0, 10_200.0, -30_000.0]);
+        let signal = sg_ready_signal(&series, &SgThresholds::default(), 1_000.0);
+        assert_eq!(sg_states(&signal), [1, 4, 4, 1]);
+        assert!(sg_ready_signal(&[], &SgThresholds::default(), 1_000.0).is_empty());
+    }
 
-        // Calculate surplus for all points
-        let mut surpluses: Vec<RenewableSurplus> = gen_points
+    #[test]
+    fn test_normalized_surplus() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let series: Vec<RenewableSurplus> = [(6_000.0, 4_000.0), (2_000.0, 4_000.0)]
             .into_iter()
-            .filter_map(|gen_point| {
-                load_map
-                    .get(&gen_point.timestamp)
-                    .map(|&load| RenewableSurplus {
-                        timestamp: gen_point.timestamp,
-                        generation: gen_point.quantity,
-                        load,
-                        surplus: gen_point.quantity - load,
-                    })
+            .enumerate()
+            .map(|(i, (generation, load))| RenewableSurplus {
+                timestamp: start + Duration::hours(i as i64),
+                generation,
+                load,
+                surplus: generation - load,
+                total_generation: None,
             })
             .collect();
 
-        // Sort by timestamp
-        surpluses.sort_by_key(|s| s.timestamp);
+        let normalized = normalized_surplus(&series, Some(2_000_000)).unwrap();
+        assert_eq!(normalized.mean_surplus, 0.0);
+        assert_eq!(normalized.max_surplus, 2_000.0);
+        assert_eq!(normalized.per_capita_kw, Some(0.0));
+        assert_eq!(normalized.share_of_load, Some(0.0));
+
+        let normalized = normalized_surplus(&series[..1], Some(2_000_000)).unwrap();
+        assert_eq!(normalized.per_capita_kw, Some(1.0));
+        assert_eq!(normalized.share_of_load, Some(50.0));
 
-        Ok(surpluses)
+        assert_eq!(
+            normalized_surplus(&series, None).unwrap().per_capita_kw,
+            None
+        );
+        assert!(normalized_surplus(&[], Some(1)).is_none());
     }
-}
 
-impl RenewableSurplus {
-    /// Calculate the surplus as a percentage of generation
-    pub fn surplus_percentage(&self) -> f64 {
-        if self.generation == 0.0 {
-            0.0
-        } else {
-            (self.surplus / self.generation) * 100.0
+    #[test]
+    fn test_default_surplus_model_keeps_surplus() {
+        let mut points = vec![
+            surplus_point(0, 0.1 + 0.2, 0.3),
+            surplus_point(1, -0.0, 0.0),
+        ];
+        let bits = |points: &[RenewableSurplus]| {
+            points
+                .iter()
+                .map(|p| p.surplus.to_bits())
+                .collect::<Vec<_>>()
+        };
+        let before = bits(&points);
+
+        SurplusModel::default().apply(&mut points);
+
+        assert_eq!(bits(&points), before);
+        assert_eq!(
+            SurplusModel::default().surplus(&points[0]),
+            points[0].surplus
+        );
+    }
+
+    #[test]
+    fn test_surplus_model_baseload() {
+        let mut hourly = [0.0; 24];
+        hourly[1] = 500.0;
+        let point = |hour| surplus_point(hour, 1000.0, 1200.0);
+
+        let constant = SurplusModel {
+            baseload: Baseload::Constant(300.0),
+            ..SurplusModel::default()
+        };
+        let hourly = SurplusModel {
+            baseload: Baseload::Hourly(hourly),
+            ..SurplusModel::default()
+        };
+
+        assert_eq!(constant.surplus(&point(0)), 100.0);
+        assert_eq!(hourly.surplus(&point(0)), -200.0);
+        assert_eq!(hourly.surplus(&point(1)), 300.0);
+    }
+
+    #[test]
+    fn test_surplus_model_export_and_efficiency() {
+        let model = SurplusModel {
+            export: 400.0,
+            efficiency: 0.5,
+            ..SurplusModel::default()
+        };
+        let mut points = vec![
+            surplus_point(0, 2000.0, 1000.0),
+            surplus_point(1, 1000.0, 1000.0),
+        ];
+
+        model.apply(&mut points);
+
+        // Only the positive surplus is scaled
+        let surplus: Vec<_> = points.iter().map(|p| p.surplus).collect();
+        assert_eq!(surplus, [300.0, -400.0]);
+        assert_eq!(points[0].generation, 2000.0);
+    }
+
+    #[test]
+    fn test_forecast_revision_drift() {
+        let meta = |hour: u32, revision_number: u32| DocumentMeta {
+            created_date_time: Utc.with_ymd_and_hms(2024, 5, 31, hour, 0, 0).unwrap(),
+            revision_number,
+        };
+        let revision = |hour: i64, meta: DocumentMeta, value: f64| ForecastRevision {
+            timestamp: midnight() + Duration::hours(hour),
+            meta,
+            value,
+        };
+        let day_ahead = meta(12, 1);
+        let revisions = [
+            revision(0, day_ahead, 100.0),
+            revision(1, day_ahead, 100.0),
+            revision(2, day_ahead, 100.0),
+            revision(0, meta(18, 1), 130.0),
+            // Published last, superseding the 18:00 update
+            revision(0, meta(18, 2), 110.0),
+            revision(1, meta(18, 2), 70.0),
+        ];
+
+        let drift = forecast_revision_drift(&revisions).unwrap();
+
+        assert_eq!(drift.revisions, 3);
+        assert_eq!(drift.first_revision, day_ahead);
+        assert_eq!(drift.latest_revision, meta(18, 2));
+        let deltas: Vec<_> = drift.points.iter().map(|p| p.delta).collect();
+        assert_eq!(deltas, [10.0, -30.0]);
+        assert_eq!(drift.rmse, 500.0_f64.sqrt());
+
+        assert_eq!(forecast_revision_drift(&revisions[..3]), None);
+        assert_eq!(forecast_revision_drift(&[]), None);
+    }
+
+    #[test]
+    fn test_diff_series_matches_nearest_timestamps() {
+        let baseline = hourly(&[100.0, 200.0, 300.0]);
+        let shifted = |minutes: i64, surplus: f64| RenewableSurplus {
+            timestamp: midnight() + Duration::minutes(minutes),
+            generation: 1_000.0 + surplus,
+            load: 1_000.0,
+            surplus,
+            total_generation: None,
+        };
+
+        let aligned = diff_series(&hourly(&[150.0, 200.0]), &baseline, Duration::zero());
+        let revisions: Vec<_> = aligned.iter().map(|d| d.revision).collect();
+        assert_eq!(revisions, [50.0, 0.0]);
+
+        // Quarter-hourly points against the hourly baseline: :15 belongs to the full
+        // hour before, :45 to the one after, :30 is a tie and takes the earlier hour
+        let current = [
+            shifted(15, 120.0),
+            shifted(30, 120.0),
+            shifted(45, 120.0),
+            shifted(150, 120.0),
+        ];
+        let diff = diff_series(&current, &baseline, Duration::minutes(30));
+        let baselines: Vec<_> = diff.iter().map(|d| d.baseline).collect();
+        assert_eq!(baselines, [100.0, 100.0, 200.0, 300.0]);
+        assert_eq!(diff[2].timestamp, midnight() + Duration::minutes(45));
+        assert_eq!(diff[2].revision, -80.0);
+
+        // Too far from any baseline point, or past its end
+        let diff = diff_series(
+            &[shifted(45, 0.0), shifted(240, 0.0)],
+            &baseline,
+            Duration::minutes(10),
+        );
+        assert!(diff.is_empty());
+        assert!(diff_series(&current, &[], Duration::hours(1)).is_empty());
+
+        // The baseline need not be sorted
+        let mut reversed = baseline.clone();
+        reversed.reverse();
+        assert_eq!(
+            diff_series(&current, &reversed, Duration::minutes(30)),
+            diff_series(&current, &baseline, Duration::minutes(30))
+        );
+    }
+
+    #[test]
+    fn test_surplus_series_reports_source_segments() {
+        let day_ahead = document("2024-05-31T12:00:00Z", midnight(), &[100.0; 24]);
+        let intraday = document(
+            "2024-06-01T05:00:00Z",
+            midnight() + Duration::hours(6),
+            &[200.0; 6],
+        );
+        let generation = merge_forecasts(
+            &[
+                (ForecastSource::DayAhead, &day_ahead),
+                (ForecastSource::Intraday, &intraday),
+            ],
+            &SeriesFilter::new(),
+        )
+        .unwrap();
+        let load = merge_forecasts(
+            &[(ForecastSource::DayAhead, &day_ahead)],
+            &SeriesFilter::new(),
+        )
+        .unwrap();
+
+        let series = surplus_series(&generation, &load).unwrap();
+
+        assert_eq!(series.points.len(), 24);
+        assert_eq!(series.points[6].surplus, 100.0);
+        let segments: Vec<_> = series
+            .sources
+            .iter()
+            .map(|s| {
+                (
+                    s.start.format("%H").to_string(),
+                    s.end.format("%H").to_string(),
+                    s.generation,
+                )
+            })
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                ("00".to_string(), "05".to_string(), ForecastSource::DayAhead),
+                ("06".to_string(), "11".to_string(), ForecastSource::Intraday),
+                ("12".to_string(), "23".to_string(), ForecastSource::DayAhead),
+            ]
+        );
+        assert!(
+            series
+                .sources
+                .iter()
+                .all(|s| s.load == ForecastSource::DayAhead)
+        );
+
+        // The stitched generation forecast is only as fresh as its day-ahead part
+        let day_ahead_created = Utc.with_ymd_and_hms(2024, 5, 31, 12, 0, 0).unwrap();
+        assert_eq!(
+            series.generation_doc_meta,
+            Some(DocumentMeta {
+                created_date_time: day_ahead_created,
+                revision_number: 1,
+            })
+        );
+        assert_eq!(series.forecast_created_at(), Some(day_ahead_created));
+        assert_eq!(
+            series
+                .source_at(midnight() + Duration::hours(8))
+                .unwrap()
+                .generation,
+            ForecastSource::Intraday
+        );
+    }
+
+    fn hourly(surpluses: &[f64]) -> Vec<RenewableSurplus> {
+        surpluses
+            .iter()
+            .enumerate()
+            .map(|(i, &surplus)| RenewableSurplus {
+                timestamp: midnight() + Duration::hours(i as i64),
+                generation: 1_000.0 + surplus,
+                load: 1_000.0,
+                surplus,
+                total_generation: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_best_window() {
+        let series = hourly(&[0.0, 500.0, 100.0, 400.0, 400.0, 300.0, 900.0]);
+
+        // 04:00-07:00 ends with the last point
+        let (start, mean) = best_window(&series, Duration::hours(3)).unwrap();
+        assert_eq!(start, midnight() + Duration::hours(4));
+        assert!((mean - 1_600.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(
+            best_window(&series, Duration::hours(1)).unwrap().0,
+            midnight() + Duration::hours(6)
+        );
+        assert!(best_window(&series, Duration::hours(8)).is_none());
+        assert!(best_window(&series[..1], Duration::hours(1)).is_none());
+    }
+
+    fn outcome(timestamp: DateTime<Utc>, error: f64) -> ForecastOutcome {
+        ForecastOutcome {
+            timestamp,
+            forecast: 1_000.0,
+            actual: 1_000.0 + error,
         }
     }
 
-    /// Check if there's excess renewable energy (generation > load)
-    pub fn has_excess(&self) -> bool {
-        self.surplus > 0.0
+    #[test]
+    fn test_error_stats_by_hour() {
+        // Eleven days of midnight errors from -500 to 500, two errors at 01:00 and one
+        // unusable outcome
+        let mut history: Vec<ForecastOutcome> = (0..11)
+            .map(|day| outcome(midnight() - Duration::days(day), (day * 100 - 500) as f64))
+            .collect();
+        history.push(outcome(midnight() + Duration::hours(1), 50.0));
+        history.push(outcome(midnight() + Duration::hours(25), 50.0));
+        history.push(outcome(midnight(), f64::NAN));
+        let stats = error_stats(&history, LocalZone::UTC);
+
+        assert_eq!(stats.samples(), 13);
+        assert_eq!(stats.percentile_at(midnight(), 10.0), Some(-400.0));
+        assert_eq!(stats.percentile_at(midnight(), 90.0), Some(400.0));
+        // Too few errors at 01:00 and none at 05:00, both fall back to all hours
+        for hour in [1, 5] {
+            let at = midnight() + Duration::days(3) + Duration::hours(hour);
+            assert_eq!(stats.percentile_at(at, 50.0), Some(50.0));
+        }
+        assert_eq!(
+            stats.positive_probability(midnight(), -250.0),
+            Some(3.0 / 11.0)
+        );
+        assert_eq!(
+            error_stats(&[], LocalZone::UTC).percentile_at(midnight(), 50.0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_error_stats_by_local_hour() {
+        // Over the week around the switch to summer time, errors of 100 MW at 08:00 in
+        // Berlin, 07:00 UTC in winter and 06:00 UTC in summer, and of -100 MW at 10:00 UTC
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+        let switch = Utc.with_ymd_and_hms(2024, 3, 31, 1, 0, 0).unwrap();
+        let history: Vec<ForecastOutcome> = (-3..4)
+            .flat_map(|day| {
+                let at = switch + Duration::days(day);
+                let eight = if day < 0 { 6 } else { 5 };
+                [
+                    outcome(at + Duration::hours(eight), 100.0),
+                    outcome(at + Duration::hours(9), -100.0),
+                ]
+            })
+            .collect();
+        let stats = error_stats(&history, berlin);
+
+        let eight_in_summer = Utc.with_ymd_and_hms(2024, 6, 1, 6, 0, 0).unwrap();
+        let eight_in_winter = Utc.with_ymd_and_hms(2024, 1, 1, 7, 0, 0).unwrap();
+        for at in [eight_in_summer, eight_in_winter] {
+            assert_eq!(stats.percentile_at(at, 10.0), Some(100.0));
+        }
+        // In UTC the errors of 08:00 in Berlin fall into two hours of too few errors each
+        let utc = error_stats(&history, LocalZone::UTC);
+        assert_eq!(utc.percentile_at(eight_in_summer, 10.0), Some(-100.0));
+    }
+
+    #[test]
+    fn test_actual_surplus_of_wind_and_solar() {
+        use crate::entsoe::parse_response;
+        use crate::entsoe::testing::actual_generation_document;
+
+        let generation = parse_response(actual_generation_document(midnight()).as_bytes()).unwrap();
+        let load = MockSeries::new("A65", DEFAULT_ZONE, &[2_000.0, 2_500.0]);
+        let load = gl_document_series("A65", "2024-06-01T12:00:00Z", midnight(), 60, &[load]);
+        let load = parse_response(load.as_bytes()).unwrap();
+
+        // 3 GW of solar; gas, nuclear, the unknown type and pumping are left out
+        let surplus = actual_surplus(&generation, &load, DEFAULT_ZONE).unwrap();
+        assert_eq!(
+            surplus.into_iter().collect::<Vec<_>>(),
+            [
+                (midnight(), 1_000.0),
+                (midnight() + Duration::hours(1), 500.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uncertainty_band_and_window_robustness() {
+        let noon = midnight() + Duration::hours(12);
+        let history: Vec<ForecastOutcome> = [-200.0, -100.0, 0.0, 100.0, 200.0]
+            .into_iter()
+            .map(|error| outcome(noon, error))
+            .collect();
+        let stats = error_stats(&history, LocalZone::UTC);
+        let series = hourly(&[0.0, 500.0, 150.0]);
+
+        let band = uncertainty_band(&series, &stats).unwrap();
+        assert_eq!(band.len(), 3);
+        for (band, point) in band.iter().zip(&series) {
+            assert_eq!(band.timestamp, point.timestamp);
+            assert!((band.p10 - (point.surplus - 160.0)).abs() < 1e-9);
+            assert!((band.p90 - (point.surplus + 160.0)).abs() < 1e-9);
+        }
+        let no_errors = error_stats(&[], LocalZone::UTC);
+        assert_eq!(uncertainty_band(&series, &no_errors), None);
+
+        // 150 MW stays positive unless the error is -200 MW
+        let robustness = |start: i64, hours: i64, stats: &ErrorStats| {
+            window_robustness(
+                &series,
+                midnight() + Duration::hours(start),
+                Duration::hours(hours),
+                stats,
+            )
+        };
+        assert_eq!(robustness(1, 2, &stats), Some(0.8));
+        assert_eq!(robustness(0, 3, &stats), Some(0.4));
+        assert_eq!(robustness(1, 1, &stats), Some(1.0));
+        assert_eq!(robustness(5, 1, &stats), None);
+        assert_eq!(robustness(1, 2, &no_errors), None);
+    }
+
+    #[test]
+    fn test_value_at_point_times() {
+        let series = hourly(&[100.0, 300.0, -200.0]);
+
+        for method in [Interpolation::Step, Interpolation::Linear] {
+            for point in &series {
+                let value = value_at(&series, point.timestamp, method).unwrap();
+                assert_eq!(value.generation, point.generation);
+                assert_eq!(value.load, point.load);
+                assert_eq!(value.surplus, point.surplus);
+            }
+        }
+    }
+
+    #[test]
+    fn test_value_at_between_points() {
+        let mut series = hourly(&[100.0, 300.0]);
+        series[1].load = 2_000.0;
+        series[1].surplus = series[1].generation - series[1].load;
+        let midway = midnight() + Duration::minutes(30);
+
+        let step = value_at(&series, midway, Interpolation::Step).unwrap();
+        assert_eq!(step.timestamp, midway);
+        assert_eq!(step.surplus, 100.0);
+
+        // Generation 1100 -> 1300, load 1000 -> 2000
+        let linear = value_at(&series, midway, Interpolation::Linear).unwrap();
+        assert_eq!(linear.generation, 1_200.0);
+        assert_eq!(linear.load, 1_500.0);
+        assert_eq!(linear.surplus, -300.0);
+    }
+
+    #[test]
+    fn test_value_at_outside_range() {
+        let series = hourly(&[100.0, 300.0]);
+
+        for method in [Interpolation::Step, Interpolation::Linear] {
+            assert!(value_at(&series, midnight() - Duration::minutes(1), method).is_none());
+            assert!(value_at(&series, midnight() + Duration::minutes(61), method).is_none());
+            assert!(value_at(&[], midnight(), method).is_none());
+        }
+    }
+
+    #[test]
+    fn test_find_deficit_windows() {
+        let series = hourly(&[
+            -100.0, -300.0, -500.0, 200.0, -400.0, -600.0, -350.0, -50.0, -900.0,
+        ]);
+
+        let windows = find_deficit_windows(&series, -250.0, Duration::hours(2));
+
+        // 01:00-03:00 and 04:00-07:00; the single hour at 08:00 is too short
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].start, midnight() + Duration::hours(1));
+        assert_eq!(windows[0].end, midnight() + Duration::hours(3));
+        assert_eq!(windows[0].mean_surplus, -400.0);
+        assert_eq!(windows[1].start, midnight() + Duration::hours(4));
+        assert_eq!(windows[1].duration(), Duration::hours(3));
+        assert_eq!(windows[1].min_point.surplus, -600.0);
+
+        assert_eq!(
+            find_deficit_windows(&series, -250.0, Duration::hours(1)).len(),
+            3
+        );
+        assert_eq!(find_min_surplus(&series).unwrap().surplus, -900.0);
+    }
+
+    #[test]
+    fn test_windows_break_at_gaps() {
+        let mut series = hourly(&[500.0, 500.0, 500.0, 500.0]);
+        series.remove(2);
+
+        let windows = find_windows(&series, Duration::hours(1), |s| s.surplus > 0.0);
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].end, midnight() + Duration::hours(2));
+        assert_eq!(windows[1].start, midnight() + Duration::hours(3));
+    }
+
+    #[tokio::test]
+    async fn test_generation_is_split_into_solar_and_wind() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::split_forecasts()));
+        let series = client
+            .get_surplus_series(
+                DEFAULT_ZONE,
+                "202406010000",
+                "202406020000",
+                Freshness::DayAhead,
+            )
+            .await
+            .unwrap();
+
+        let noon = midnight() + Duration::hours(12);
+        let max = max_surplus(&series.points).unwrap();
+        assert_eq!(max.timestamp, noon);
+        assert_eq!(max.generation, 22_000.0);
+        let split = series.generation_split[&noon];
+        assert_eq!(
+            split,
+            GenerationSplit {
+                solar: 12_000.0,
+                wind: 10_000.0
+            }
+        );
+        assert!((split.solar_share().unwrap() - 54.545).abs() < 0.001);
+        assert!((split.wind_share().unwrap() - 45.455).abs() < 0.001);
+
+        let night = series.generation_split[&midnight()];
+        assert_eq!(night.solar_share(), Some(0.0));
+        assert_eq!(GenerationSplit::default().solar_share(), None);
+
+        // Without production types there is no split
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let series = client
+            .get_surplus_series(
+                DEFAULT_ZONE,
+                "202406010000",
+                "202406020000",
+                Freshness::DayAhead,
+            )
+            .await
+            .unwrap();
+        assert!(series.generation_split.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_auto_falls_back_when_intraday_is_missing() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            let doc_type = query_param(url, "documentType").unwrap();
+            match query_param(url, "processType").as_deref() {
+                Some("A01") => ok(gl_document(&doc_type, midnight(), 60, &[100.0; 24])),
+                _ => ok(
+                    "<Acknowledgement_MarketDocument><Reason><code>999</code></Reason></Acknowledgement_MarketDocument>",
+                ),
+            }
+        }));
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+
+        let series = client
+            .get_surplus_series(
+                "10Y1001A1001A83F",
+                "202406010000",
+                "202406020000",
+                Freshness::Auto,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(series.points.len(), 24);
+        assert_eq!(
+            series.sources,
+            [SourceSegment {
+                start: midnight(),
+                end: midnight() + Duration::hours(23),
+                generation: ForecastSource::DayAhead,
+                load: ForecastSource::DayAhead,
+            }]
+        );
+        // Both horizons of both forecasts, current generation and the day-ahead total
+        // generation
+        assert_eq!(transport.requests().len(), 6);
+    }
+
+    #[test]
+    fn test_partial_surplus_keeps_either_side() {
+        let points = |quantities: &[f64], minutes: i64| -> Vec<TimestampedPoint> {
+            let document: GlMarketDocument =
+                quick_xml::de::from_str(&gl_document("A65", midnight(), minutes, quantities))
+                    .unwrap();
+            document.all_timestamped_points().unwrap()
+        };
+
+        let surplus = partial_surplus(&points(&[30.0; 2], 60), &points(&[20.0; 3], 60)).unwrap();
+        assert_eq!(surplus.len(), 3);
+        assert_eq!(surplus[0].surplus, Some(10.0));
+        assert_eq!(surplus[0].missing(), None);
+        assert_eq!(
+            surplus[2],
+            PartialSurplus {
+                timestamp: midnight() + Duration::hours(2),
+                generation: None,
+                load: Some(20.0),
+                surplus: None,
+            }
+        );
+        assert_eq!(surplus[2].missing(), Some(ForecastSide::Generation));
+
+        // A quarter-hourly load is averaged to the hourly generation
+        let surplus =
+            partial_surplus(&points(&[30.0], 60), &points(&[10.0, 20.0, 30.0, 40.0], 15)).unwrap();
+        assert_eq!(surplus.len(), 1);
+        assert_eq!(surplus[0].load, Some(25.0));
+        let generation_only = partial_surplus(&points(&[30.0; 2], 60), &[]).unwrap();
+        assert!(generation_only.iter().all(|point| point.surplus.is_none()));
+
+        let mut model_points = partial_surplus(&points(&[30.0; 2], 60), &[]).unwrap();
+        SurplusModel {
+            export: 5.0,
+            ..SurplusModel::default()
+        }
+        .apply_partial(&mut model_points);
+        assert_eq!(model_points, generation_only);
+    }
+
+    #[tokio::test]
+    async fn test_partial_series_without_load_forecast() {
+        let client = EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(MockTransport::forecasts_without("A65")),
+        );
+        let (start, end) = ("202406010000", "202406020000");
+
+        // Strict series fail as a whole
+        assert!(
+            client
+                .get_renewable_surplus_series(DEFAULT_ZONE, start, end, &SurplusModel::default())
+                .await
+                .is_err()
+        );
+
+        let series = client
+            .get_partial_surplus_series(DEFAULT_ZONE, start, end, &SurplusModel::default())
+            .await
+            .unwrap();
+        assert_eq!(series.missing, Some(ForecastSide::Load));
+        assert_eq!(series.points[0].generation, Some(40_000.0));
+        assert!(
+            series
+                .points
+                .iter()
+                .all(|point| point.load.is_none() && point.surplus.is_none())
+        );
+
+        // Without either forecast there is nothing to report
+        let client = EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(MockTransport::new(|_| ok(NO_MATCHING_DATA))),
+        );
+        assert!(
+            client
+                .get_partial_surplus_series(DEFAULT_ZONE, start, end, &SurplusModel::default())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_total_generation_share() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+
+        let series = client
+            .get_surplus_series(
+                "10Y1001A1001A83F",
+                "202406010000",
+                "202406020000",
+                Freshness::DayAhead,
+            )
+            .await
+            .unwrap();
+
+        let first = &series.points[0];
+        assert_eq!(first.total_generation, Some(70_000.0));
+        let share = first.renewable_share_of_generation().unwrap();
+        assert!((share - 57.14).abs() < 0.01, "{}", share);
+        assert_eq!(first.renewable_penetration(), 80.0);
+    }
+
+    #[tokio::test]
+    async fn test_surplus_of_a_zone_with_a_distinct_load_code() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let denmark = "10Y1001A1001A796";
+
+        let series = client
+            .get_surplus_series(denmark, "202406010000", "202406020000", Freshness::DayAhead)
+            .await
+            .unwrap();
+
+        // The load is reported under the country area and still matched
+        assert_eq!(series.points[0].load, 50_000.0);
+        assert_eq!(series.load_aggregation, LoadAggregation::National);
+        let requests = transport.requests();
+        let domain = |document_type: &str, parameter: &str| {
+            requests
+                .iter()
+                .find(|url| query_param(url, "documentType").as_deref() == Some(document_type))
+                .and_then(|url| query_param(url, parameter))
+        };
+        assert_eq!(
+            domain("A65", "outBiddingZone_Domain").as_deref(),
+            Some("10Y1001A1001A65H")
+        );
+        assert_eq!(domain("A69", "in_Domain").as_deref(), Some(denmark));
+        // What the fetches taught is learned for the zone, not the override
+        let capabilities = client.capabilities();
+        assert!(
+            capabilities
+                .of(denmark, areas::DocumentKind::LoadForecast)
+                .learned
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_total_generation_degrades() {
+        let client = EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(MockTransport::new(|url| {
+                match query_param(url, "documentType").unwrap().as_str() {
+                    "A71" => ok(
+                        "<Acknowledgement_MarketDocument><Reason><code>999</code></Reason></Acknowledgement_MarketDocument>",
+                    ),
+                    doc_type => ok(gl_document(doc_type, midnight(), 60, &[100.0; 24])),
+                }
+            })),
+        );
+
+        let series = client
+            .get_surplus_series(
+                "10Y1001A1001A83F",
+                "202406010000",
+                "202406020000",
+                Freshness::DayAhead,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(series.points.len(), 24);
+        assert!(series.points.iter().all(|p| p.total_generation.is_none()));
+        assert_eq!(series.points[0].renewable_share_of_generation(), None);
+        assert_eq!(series.points[0].renewable_penetration(), 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_total_generation_ignores_consumption_and_other_zones() {
+        const ZONE: &str = "10Y1001A1001A83F";
+        let client = EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(MockTransport::new(|url| {
+                let doc_type = query_param(url, "documentType").unwrap();
+                if doc_type != "A71" {
+                    return ok(gl_document(&doc_type, midnight(), 60, &[100.0; 24]));
+                }
+                let series = [
+                    MockSeries {
+                        psr_type: Some("B16"),
+                        ..MockSeries::new("A71", ZONE, &[300.0; 24])
+                    },
+                    MockSeries {
+                        psr_type: Some("B19"),
+                        ..MockSeries::new("A71", ZONE, &[400.0; 24])
+                    },
+                    // Pumped storage consumption
+                    MockSeries {
+                        in_bidding_zone: None,
+                        out_bidding_zone: Some(ZONE),
+                        ..MockSeries::new("A71", ZONE, &[50.0; 24])
+                    },
+                    MockSeries::new("A71", "10YFR-RTE------C", &[9_000.0; 24]),
+                ];
+                ok(gl_document_series(
+                    "A71",
+                    "2024-06-01T12:00:00Z",
+                    midnight(),
+                    60,
+                    &series,
+                ))
+            })),
+        );
+
+        let series = client
+            .get_surplus_series(ZONE, "202406010000", "202406020000", Freshness::DayAhead)
+            .await
+            .unwrap();
+
+        assert!(
+            series
+                .points
+                .iter()
+                .all(|p| p.total_generation == Some(700.0))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_of_control_areas_is_not_counted_twice() {
+        const ZONE: &str = "10Y1001A1001A83F";
+        for (national, aggregation) in [
+            (true, LoadAggregation::NationalOverControlAreas),
+            (false, LoadAggregation::ControlAreaSum),
+        ] {
+            let client = EntsoeClient::with_transport(
+                "test-token",
+                Arc::new(MockTransport::new(move |url| {
+                    match query_param(url, "documentType").unwrap().as_str() {
+                        "A65" => ok(control_area_load_document(midnight(), national)),
+                        doc_type => ok(gl_document(doc_type, midnight(), 60, &[100.0; 24])),
+                    }
+                })),
+            );
+
+            let series = client
+                .get_surplus_series(ZONE, "202406010000", "202406020000", Freshness::DayAhead)
+                .await
+                .unwrap();
+
+            assert_eq!(series.load_aggregation, aggregation);
+            assert_eq!(series.points.len(), 24);
+            assert!(
+                series.points.iter().all(|p| p.load == 50_000.0),
+                "{:?}",
+                series.points[0]
+            );
+        }
+    }
+
+    #[test]
+    fn test_load_aggregation_adds_only_all_control_areas() {
+        const ZONE: &str = "10Y1001A1001A83F";
+        let parse = |xml: &str| -> GlMarketDocument { quick_xml::de::from_str(xml).unwrap() };
+        let national = parse(&gl_document("A65", midnight(), 60, &[50_000.0; 24]));
+        assert_eq!(
+            load_aggregation(&[(ForecastSource::DayAhead, &national)], ZONE),
+            (load_series(ZONE), LoadAggregation::National)
+        );
+
+        // One control area alone, or series of unrelated zones, are never taken for the
+        // load of the zone
+        let partial = parse(&gl_document_series(
+            "A65",
+            "2024-06-01T12:00:00Z",
+            midnight(),
+            60,
+            &[MockSeries::new("A65", "10YDE-EON------1", &[15_000.0; 24])],
+        ));
+        let foreign = parse(&gl_document_series(
+            "A65",
+            "2024-06-01T12:00:00Z",
+            midnight(),
+            60,
+            &[MockSeries::new("A65", "10YFR-RTE------C", &[40_000.0; 24])],
+        ));
+        for document in [&partial, &foreign] {
+            let (filter, aggregation) =
+                load_aggregation(&[(ForecastSource::DayAhead, document)], ZONE);
+            assert_eq!(aggregation, LoadAggregation::National);
+            assert!(document.series_where(&filter).is_empty());
+        }
+
+        // Asked for one control area, only its own series is taken
+        let areas = parse(&control_area_load_document(midnight(), false));
+        assert_eq!(
+            load_aggregation(&[(ForecastSource::DayAhead, &areas)], "10YDE-EON------1").1,
+            LoadAggregation::National
+        );
+    }
+
+    #[tokio::test]
+    async fn test_surplus_refuses_mixed_units() {
+        let client = EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(MockTransport::new(|url| {
+                let doc_type = query_param(url, "documentType").unwrap();
+                let quantities = [100.0; 24];
+                let series = MockSeries {
+                    unit: if doc_type == "A65" { "MWH" } else { "MAW" },
+                    ..MockSeries::new(&doc_type, "10Y1001A1001A83F", &quantities)
+                };
+                ok(gl_document_series(
+                    &doc_type,
+                    "2024-06-01T12:00:00Z",
+                    midnight(),
+                    60,
+                    &[series],
+                ))
+            })),
+        );
+
+        let result = client
+            .get_surplus_series(
+                "10Y1001A1001A83F",
+                "202406010000",
+                "202406020000",
+                Freshness::DayAhead,
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(EntsoeError::UnitMismatch {
+                left: MeasureUnit::Megawatt,
+                right: MeasureUnit::MegawattHour,
+            })
+        ));
     }
 }
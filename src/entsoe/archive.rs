@@ -0,0 +1,175 @@
+//! ZIP archives, in which upstream serves the documents of unavailability queries (one
+//! file per outage). Stored and deflated entries are read; ZIP64 and encryption are not
+//! used by ENTSO-E and are refused.
+
+use std::io::Read;
+
+use super::EntsoeError;
+
+/// Signature a ZIP archive starts with, that of its first local file header
+const LOCAL_HEADER_SIGNATURE: &[u8; 4] = b"PK\x03\x04";
+const CENTRAL_HEADER_SIGNATURE: &[u8; 4] = b"PK\x01\x02";
+const END_OF_DIRECTORY_SIGNATURE: &[u8; 4] = b"PK\x05\x06";
+
+/// Fixed sizes of the records, before their variable-length fields
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const END_OF_DIRECTORY_LEN: usize = 22;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+/// General purpose flag of encrypted entries
+const ENCRYPTED: u16 = 1;
+
+/// Whether `bytes` start like a ZIP archive
+pub(crate) fn is_zip(bytes: &[u8]) -> bool {
+    bytes.starts_with(LOCAL_HEADER_SIGNATURE)
+}
+
+/// Contents of the files of an archive in the order of its central directory, leaving out
+/// directories
+pub(crate) fn unzip(bytes: &[u8]) -> Result<Vec<Vec<u8>>, EntsoeError> {
+    let reader = Reader { bytes };
+    let end = end_of_directory(bytes)?;
+    let entries = reader.u16(end + 10)?;
+    let mut header = reader.u32(end + 16)? as usize;
+
+    let mut files = Vec::with_capacity(entries as usize);
+    for _ in 0..entries {
+        if reader.slice(header, 4)? != CENTRAL_HEADER_SIGNATURE {
+            return Err(invalid("central directory entry"));
+        }
+        let flags = reader.u16(header + 8)?;
+        let method = reader.u16(header + 10)?;
+        let crc = reader.u32(header + 16)?;
+        let compressed_len = reader.u32(header + 20)?;
+        let len = reader.u32(header + 24)?;
+        let name_len = reader.u16(header + 28)? as usize;
+        let extra_len = reader.u16(header + 30)? as usize;
+        let comment_len = reader.u16(header + 32)? as usize;
+        let local_header = reader.u32(header + 42)? as usize;
+        let name = reader.slice(header + CENTRAL_HEADER_LEN, name_len)?;
+        header += CENTRAL_HEADER_LEN + name_len + extra_len + comment_len;
+
+        if name.ends_with(b"/") {
+            continue;
+        }
+        if [compressed_len, len, local_header as u32].contains(&u32::MAX) {
+            return Err(unsupported("ZIP64"));
+        }
+        if flags & ENCRYPTED != 0 {
+            return Err(unsupported("encrypted entries"));
+        }
+
+        if reader.slice(local_header, 4)? != LOCAL_HEADER_SIGNATURE {
+            return Err(invalid("local file header"));
+        }
+        // The local header may carry other extra fields than the central directory
+        let data = local_header
+            + LOCAL_HEADER_LEN
+            + reader.u16(local_header + 26)? as usize
+            + reader.u16(local_header + 28)? as usize;
+        let compressed = reader.slice(data, compressed_len as usize)?;
+
+        // Sizes are those the archive claims, so nothing is allocated up front by them
+        let mut file = Vec::new();
+        match method {
+            STORED => file.extend_from_slice(compressed),
+            DEFLATED => {
+                // One byte past the size is enough to tell an entry inflating beyond it
+                flate2::read::DeflateDecoder::new(compressed)
+                    .take(u64::from(len) + 1)
+                    .read_to_end(&mut file)
+                    .map_err(|e| {
+                        EntsoeError::InvalidResponse(format!("Failed to unzip the response: {}", e))
+                    })?;
+            }
+            other => return Err(unsupported(&format!("compression method {}", other))),
+        }
+
+        let mut checksum = flate2::Crc::new();
+        checksum.update(&file);
+        if file.len() != len as usize || checksum.sum() != crc {
+            return Err(invalid("entry: size or checksum mismatch"));
+        }
+        files.push(file);
+    }
+    Ok(files)
+}
+
+/// Offset of the end of central directory record, the last one in the archive as it may be
+/// followed by a comment
+fn end_of_directory(bytes: &[u8]) -> Result<usize, EntsoeError> {
+    let last = bytes
+        .len()
+        .checked_sub(END_OF_DIRECTORY_LEN)
+        .ok_or_else(|| invalid("archive: too short"))?;
+    (0..=last)
+        .rev()
+        .find(|&offset| bytes[offset..].starts_with(END_OF_DIRECTORY_SIGNATURE))
+        .ok_or_else(|| invalid("archive: no central directory"))
+}
+
+fn invalid(what: &str) -> EntsoeError {
+    EntsoeError::InvalidResponse(format!("Corrupt ZIP {}", what))
+}
+
+fn unsupported(what: &str) -> EntsoeError {
+    EntsoeError::InvalidResponse(format!("Unsupported ZIP archive: {}", what))
+}
+
+/// Little-endian fields at offsets of the archive, refusing any out of its bounds
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&self, offset: usize, len: usize) -> Result<&'a [u8], EntsoeError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| invalid("archive: truncated"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, EntsoeError> {
+        let bytes = self.slice(offset, 2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, EntsoeError> {
+        let bytes = self.slice(offset, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::zip_archive;
+
+    #[test]
+    fn test_unzip_stored_and_deflated_entries() {
+        let archive = zip_archive(&[("a.xml", b"<a/>", false), ("b.xml", &[b'b'; 1000], true)]);
+        assert!(is_zip(&archive));
+
+        let files = unzip(&archive).unwrap();
+        assert_eq!(files, [b"<a/>".to_vec(), vec![b'b'; 1000]]);
+    }
+
+    #[test]
+    fn test_corrupt_archives_are_refused() {
+        let archive = zip_archive(&[("a.xml", b"<a/>", false)]);
+
+        let mut flipped = archive.clone();
+        // The stored contents follow the local header and its name
+        flipped[LOCAL_HEADER_LEN + "a.xml".len()] ^= 1;
+        assert!(matches!(
+            unzip(&flipped),
+            Err(EntsoeError::InvalidResponse(_))
+        ));
+
+        for len in [0, 10, archive.len() - 1] {
+            assert!(unzip(&archive[..len]).is_err(), "{}", len);
+        }
+    }
+}
@@ -1,23 +1,250 @@
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Maximum number of results of [`search_zones`]
+pub const MAX_SEARCH_RESULTS: usize = 20;
+
 /// ISO 3166-1 alpha-2 country code
 pub type CountryCode = &'static str;
 
 /// ENTSO-E area/bidding zone code
 pub type AreaCode = &'static str;
 
+/// Whether an area is a market bidding zone or a TSO control area
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneKind {
+    BiddingZone,
+    ControlArea,
+}
+
 /// Represents an ENTSO-E bidding zone or control area
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct BiddingZone {
     pub code: AreaCode,
     pub country_code: CountryCode,
     pub name: &'static str,
     pub tso: Option<&'static str>, // Transmission System Operator
+    /// Common short name, e.g. `DE-LU`
+    pub alias: Option<&'static str>,
+    pub kind: ZoneKind,
+    /// IANA time zone of local market time, e.g. `Europe/Berlin`
+    pub timezone: &'static str,
+    /// Code the load of the zone is published under, if not `code`, see [`load_code`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_code: Option<AreaCode>,
+    /// Code the generation of the zone is published under, if not `code`, see
+    /// [`generation_code`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_code: Option<AreaCode>,
+}
+
+/// IANA time zone of a country; areas in another zone override it
+fn country_timezone(country_code: &str) -> &'static str {
+    match country_code {
+        "AL" => "Europe/Tirane",
+        "AT" => "Europe/Vienna",
+        "BA" => "Europe/Sarajevo",
+        "BE" => "Europe/Brussels",
+        "BG" => "Europe/Sofia",
+        "BY" => "Europe/Minsk",
+        "CH" => "Europe/Zurich",
+        "CY" => "Asia/Nicosia",
+        "CZ" => "Europe/Prague",
+        "DE" => "Europe/Berlin",
+        "DK" => "Europe/Copenhagen",
+        "EE" => "Europe/Tallinn",
+        "ES" => "Europe/Madrid",
+        "FI" => "Europe/Helsinki",
+        "FR" => "Europe/Paris",
+        "GB" => "Europe/London",
+        "GR" => "Europe/Athens",
+        "HR" => "Europe/Zagreb",
+        "HU" => "Europe/Budapest",
+        "IE" => "Europe/Dublin",
+        "IS" => "Atlantic/Reykjavik",
+        "IT" => "Europe/Rome",
+        "LT" => "Europe/Vilnius",
+        "LU" => "Europe/Luxembourg",
+        "LV" => "Europe/Riga",
+        "MD" => "Europe/Chisinau",
+        "ME" => "Europe/Podgorica",
+        "MK" => "Europe/Skopje",
+        "MT" => "Europe/Malta",
+        "NL" => "Europe/Amsterdam",
+        "NO" => "Europe/Oslo",
+        "PL" => "Europe/Warsaw",
+        "PT" => "Europe/Lisbon",
+        "RO" => "Europe/Bucharest",
+        "RS" => "Europe/Belgrade",
+        "RU" => "Europe/Moscow",
+        "SE" => "Europe/Stockholm",
+        "SI" => "Europe/Ljubljana",
+        "SK" => "Europe/Bratislava",
+        "TR" => "Europe/Istanbul",
+        "UA" => "Europe/Kyiv",
+        _ => "UTC",
+    }
+}
+
+/// Reference figures of a country for comparing it with larger or smaller ones
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CountryStats {
+    pub population: u64,
+    /// Annual electricity consumption spread over the year
+    pub mean_load_mw: f64,
+}
+
+/// Approximate 2023 figures; `None` for countries without reliable ones at hand
+pub fn country_stats(country_code: &str) -> Option<CountryStats> {
+    let (population, mean_load_mw) = match country_code {
+        "AT" => (9_100_000, 7_200.0),
+        "BE" => (11_800_000, 9_400.0),
+        "BG" => (6_400_000, 4_200.0),
+        "CH" => (8_800_000, 6_500.0),
+        "CY" => (930_000, 570.0),
+        "CZ" => (10_900_000, 7_200.0),
+        "DE" => (84_500_000, 53_300.0),
+        "DK" => (5_900_000, 4_000.0),
+        "EE" => (1_400_000, 900.0),
+        "ES" => (48_400_000, 28_000.0),
+        "FI" => (5_600_000, 9_100.0),
+        "FR" => (68_200_000, 50_800.0),
+        "GB" => (68_300_000, 30_800.0),
+        "GR" => (10_400_000, 5_700.0),
+        "HR" => (3_900_000, 2_000.0),
+        "HU" => (9_600_000, 4_700.0),
+        "IE" => (5_300_000, 3_600.0),
+        "IS" => (390_000, 2_200.0),
+        "IT" => (59_000_000, 34_900.0),
+        "LT" => (2_900_000, 1_400.0),
+        "LU" => (660_000, 700.0),
+        "LV" => (1_900_000, 800.0),
+        "MT" => (540_000, 340.0),
+        "NL" => (17_900_000, 12_900.0),
+        "NO" => (5_500_000, 15_400.0),
+        "PL" => (36_800_000, 19_200.0),
+        "PT" => (10_500_000, 5_700.0),
+        "RO" => (19_000_000, 6_300.0),
+        "RS" => (6_600_000, 3_800.0),
+        "SE" => (10_500_000, 15_200.0),
+        "SI" => (2_100_000, 1_500.0),
+        "SK" => (5_400_000, 3_100.0),
+        _ => return None,
+    };
+    Some(CountryStats {
+        population,
+        mean_load_mw,
+    })
+}
+
+/// Kinds of documents of the transparency platform a zone may or may not publish
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentKind {
+    LoadForecast,
+    WindSolarForecast,
+    GenerationForecast,
+    ActualGeneration,
+    GenerationPerUnit,
+    DayAheadPrices,
+    /// Prices of the continuous intraday market. The transparency platform publishes
+    /// none, so no zone supports them until another source is added.
+    IntradayPrices,
+}
+
+impl DocumentKind {
+    pub const ALL: [DocumentKind; 7] = [
+        DocumentKind::LoadForecast,
+        DocumentKind::WindSolarForecast,
+        DocumentKind::GenerationForecast,
+        DocumentKind::ActualGeneration,
+        DocumentKind::GenerationPerUnit,
+        DocumentKind::DayAheadPrices,
+        DocumentKind::IntradayPrices,
+    ];
+
+    /// `documentType` fetching this kind, `None` for kinds without a document
+    pub fn document_type(self) -> Option<&'static str> {
+        match self {
+            DocumentKind::LoadForecast => Some("A65"),
+            DocumentKind::WindSolarForecast => Some("A69"),
+            DocumentKind::GenerationForecast => Some("A71"),
+            DocumentKind::ActualGeneration => Some("A75"),
+            DocumentKind::GenerationPerUnit => Some("A73"),
+            DocumentKind::DayAheadPrices => Some("A44"),
+            DocumentKind::IntradayPrices => None,
+        }
+    }
+
+    /// As serialized, e.g. `day_ahead_prices`
+    pub fn name(self) -> &'static str {
+        match self {
+            DocumentKind::LoadForecast => "load_forecast",
+            DocumentKind::WindSolarForecast => "wind_solar_forecast",
+            DocumentKind::GenerationForecast => "generation_forecast",
+            DocumentKind::ActualGeneration => "actual_generation",
+            DocumentKind::GenerationPerUnit => "generation_per_unit",
+            DocumentKind::DayAheadPrices => "day_ahead_prices",
+            DocumentKind::IntradayPrices => "intraday_prices",
+        }
+    }
+
+    pub fn of_document_type(document_type: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.document_type() == Some(document_type))
+    }
+}
+
+/// Whether a zone publishes a kind of document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Support {
+    Supported,
+    Unsupported,
+    /// Neither seeded nor learned yet; fetches are tried
+    Unknown,
+}
+
+/// Countries the transparency platform has no data of at all
+const WITHOUT_TRANSPARENCY_DATA: [CountryCode; 3] = ["BY", "IS", "RU"];
+/// Countries publishing no wind and solar forecast
+const WITHOUT_WIND_SOLAR_FORECAST: [CountryCode; 6] = ["AL", "BA", "MD", "ME", "MK", "MT"];
+/// Countries without day-ahead prices on the platform, e.g. Great Britain since 2021
+const WITHOUT_DAY_AHEAD_PRICES: [CountryCode; 10] =
+    ["AL", "BA", "CY", "GB", "MD", "ME", "MK", "MT", "TR", "UA"];
+
+/// What the ENTSO-E knowledge base says about `kind` in `zone`, for the common cases;
+/// [`Support::Unknown`] elsewhere. The server corrects it by what fetches return, see
+/// [`crate::entsoe::capabilities`].
+pub fn seeded_support(zone: &BiddingZone, kind: DocumentKind) -> Support {
+    let country = zone.country_code;
+    let listed = |countries: &[CountryCode]| countries.contains(&country);
+    if kind.document_type().is_none() || listed(&WITHOUT_TRANSPARENCY_DATA) {
+        return Support::Unsupported;
+    }
+    // Figures at hand mark the countries reporting the full set of forecasts
+    let reporting = country_stats(country).is_some();
+    match kind {
+        DocumentKind::WindSolarForecast if listed(&WITHOUT_WIND_SOLAR_FORECAST) => {
+            Support::Unsupported
+        }
+        // Prices are set per bidding zone, never per control area
+        DocumentKind::DayAheadPrices
+            if listed(&WITHOUT_DAY_AHEAD_PRICES) || zone.kind == ZoneKind::ControlArea =>
+        {
+            Support::Unsupported
+        }
+        DocumentKind::GenerationPerUnit => Support::Unknown,
+        _ if reporting => Support::Supported,
+        _ => Support::Unknown,
+    }
 }
 
 impl BiddingZone {
-    pub const fn new(
+    pub fn new(
         code: AreaCode,
         country_code: CountryCode,
         name: &'static str,
@@ -28,8 +255,38 @@ impl BiddingZone {
             country_code,
             name,
             tso,
+            alias: None,
+            kind: ZoneKind::BiddingZone,
+            timezone: country_timezone(country_code),
+            load_code: None,
+            generation_code: None,
         }
     }
+
+    pub const fn alias(mut self, alias: &'static str) -> Self {
+        self.alias = Some(alias);
+        self
+    }
+
+    pub const fn control_area(mut self) -> Self {
+        self.kind = ZoneKind::ControlArea;
+        self
+    }
+
+    pub const fn timezone(mut self, timezone: &'static str) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub const fn load_code(mut self, code: AreaCode) -> Self {
+        self.load_code = Some(code);
+        self
+    }
+
+    pub const fn generation_code(mut self, code: AreaCode) -> Self {
+        self.generation_code = Some(code);
+        self
+    }
 }
 
 /// All available ENTSO-E bidding zones
@@ -44,24 +301,35 @@ pub static BIDDING_ZONES: Lazy<HashMap<CountryCode, Vec<BiddingZone>>> = Lazy::n
         BiddingZone::new("10YHR-HEP------M", "HR", "Croatia", None),
         BiddingZone::new("10YCY-1001A0003J", "CY", "Cyprus", None),
         BiddingZone::new("10YCZ-CEPS-----N", "CZ", "Czech Republic", None),
-        BiddingZone::new("10Y1001A1001A796", "DK", "Denmark", None),
+        // The load of Energinet is published for the country area of Denmark, the sum of
+        // DK1 and DK2
+        BiddingZone::new("10Y1001A1001A796", "DK", "Denmark", None).load_code("10Y1001A1001A65H"),
         BiddingZone::new("10Y1001A1001A39I", "EE", "Estonia", None),
         BiddingZone::new("10YFI-1--------U", "FI", "Finland", None),
         BiddingZone::new("10YFR-RTE------C", "FR", "France", None),
-        BiddingZone::new("10Y1001A1001A83F", "DE", "Germany", None),
-        BiddingZone::new("10YDE-VE-------2", "DE", "Germany", Some("50Hertz")),
-        BiddingZone::new("10YDE-RWENET---I", "DE", "Germany", Some("Amprion")),
-        BiddingZone::new("10YDE-EON------1", "DE", "Germany", Some("TenneT")),
-        BiddingZone::new("10YDE-ENBW-----N", "DE", "Germany", Some("TransnetBW")),
+        BiddingZone::new("10Y1001A1001A83F", "DE", "Germany", None).alias("DE-LU"),
+        BiddingZone::new("10YDE-VE-------2", "DE", "Germany", Some("50Hertz"))
+            .alias("DE-50HZ")
+            .control_area(),
+        BiddingZone::new("10YDE-RWENET---I", "DE", "Germany", Some("Amprion"))
+            .alias("DE-AMPRION")
+            .control_area(),
+        BiddingZone::new("10YDE-EON------1", "DE", "Germany", Some("TenneT"))
+            .alias("DE-TENNET")
+            .control_area(),
+        BiddingZone::new("10YDE-ENBW-----N", "DE", "Germany", Some("TransnetBW"))
+            .alias("DE-TRANSNET")
+            .control_area(),
         BiddingZone::new("10YGR-HTSO-----Y", "GR", "Greece", None),
         BiddingZone::new("10YHU-MAVIR----U", "HU", "Hungary", None),
         BiddingZone::new("IS", "IS", "Iceland", None),
         BiddingZone::new("10YIE-1001A00010", "IE", "Ireland", None),
-        BiddingZone::new("10Y1001A1001A016", "GB", "Northern Ireland", None),
+        BiddingZone::new("10Y1001A1001A016", "GB", "Northern Ireland", None).alias("GB-NIR"),
         BiddingZone::new("10YIT-GRTN-----B", "IT", "Italy", None),
-        BiddingZone::new("10Y1001A1001A885", "IT", "Italy", Some("Saco AC")),
-        BiddingZone::new("10Y1001A1001A893", "IT", "Italy", Some("Saco DC")),
-        BiddingZone::new("10Y1001A1001A50U", "RU", "Kaliningrad", None),
+        BiddingZone::new("10Y1001A1001A885", "IT", "Italy", Some("Saco AC")).alias("IT-SACO-AC"),
+        BiddingZone::new("10Y1001A1001A893", "IT", "Italy", Some("Saco DC")).alias("IT-SACO-DC"),
+        BiddingZone::new("10Y1001A1001A50U", "RU", "Kaliningrad", None)
+            .timezone("Europe/Kaliningrad"),
         BiddingZone::new("10YLV-1001A00074", "LV", "Latvia", None),
         BiddingZone::new("10YLT-1001A0008Q", "LT", "Lithuania", None),
         BiddingZone::new("10YLU-CEGEDEL-NQ", "LU", "Luxembourg", None),
@@ -88,9 +356,7 @@ pub static BIDDING_ZONES: Lazy<HashMap<CountryCode, Vec<BiddingZone>>> = Lazy::n
     // Group by country code
     let mut map: HashMap<CountryCode, Vec<BiddingZone>> = HashMap::new();
     for zone in zones {
-        map.entry(zone.country_code)
-            .or_insert_with(Vec::new)
-            .push(zone);
+        map.entry(zone.country_code).or_default().push(zone);
     }
     map
 });
@@ -101,13 +367,48 @@ pub fn get_zones_by_country(country_code: &str) -> Option<&'static Vec<BiddingZo
 }
 
 /// Get a specific bidding zone by its ENTSO-E code
-pub fn get_zone_by_code(area_code: AreaCode) -> Option<&'static BiddingZone> {
+pub fn get_zone_by_code(area_code: &str) -> Option<&'static BiddingZone> {
     BIDDING_ZONES
         .values()
         .flatten()
         .find(|zone| zone.code == area_code)
 }
 
+/// Code the load (`outBiddingZone_Domain`) of the zone with code `zone_code` is requested
+/// and reported under; `zone_code` itself for zones without override
+pub fn load_code(zone_code: &str) -> &str {
+    get_zone_by_code(zone_code)
+        .and_then(|zone| zone.load_code)
+        .unwrap_or(zone_code)
+}
+
+/// Code the generation (`in_Domain`) of the zone with code `zone_code` is requested and
+/// reported under; `zone_code` itself for zones without override
+pub fn generation_code(zone_code: &str) -> &str {
+    get_zone_by_code(zone_code)
+        .and_then(|zone| zone.generation_code)
+        .unwrap_or(zone_code)
+}
+
+/// Code of the zone whose load or generation is published under `area`, `area` itself if
+/// it is no override
+pub fn zone_code_of(area: &str) -> &str {
+    BIDDING_ZONES
+        .values()
+        .flatten()
+        .find(|zone| zone.load_code == Some(area) || zone.generation_code == Some(area))
+        .map_or(area, |zone| zone.code)
+}
+
+/// TSO control areas of a country, empty for countries without separate ones
+pub fn get_control_areas(country_code: &str) -> Vec<&'static BiddingZone> {
+    get_zones_by_country(country_code)
+        .into_iter()
+        .flatten()
+        .filter(|zone| zone.kind == ZoneKind::ControlArea)
+        .collect()
+}
+
 /// Get the primary bidding zone for a country (first one if multiple exist)
 pub fn get_primary_zone(country_code: &str) -> Option<&'static BiddingZone> {
     BIDDING_ZONES
@@ -115,6 +416,50 @@ pub fn get_primary_zone(country_code: &str) -> Option<&'static BiddingZone> {
         .and_then(|zones| zones.first())
 }
 
+/// Case-insensitive search over code, country code, name, alias and TSO. Zones whose
+/// code, country code or alias equals `query` come first, then prefix matches, then
+/// substring matches; at most [`MAX_SEARCH_RESULTS`] are returned.
+pub fn search_zones(query: &str) -> Vec<&'static BiddingZone> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let rank = |zone: &BiddingZone| {
+        let identifiers = [Some(zone.code), Some(zone.country_code), zone.alias];
+        let fields = identifiers
+            .into_iter()
+            .chain([Some(zone.name), zone.tso])
+            .flatten()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+
+        if identifiers
+            .into_iter()
+            .flatten()
+            .any(|id| id.eq_ignore_ascii_case(&query))
+        {
+            Some(0)
+        } else if fields.iter().any(|field| field.starts_with(&query)) {
+            Some(1)
+        } else if fields.iter().any(|field| field.contains(&query)) {
+            Some(2)
+        } else {
+            None
+        }
+    };
+
+    let mut matches: Vec<(u8, &'static BiddingZone)> = BIDDING_ZONES
+        .values()
+        .flatten()
+        .filter_map(|zone| rank(zone).map(|rank| (rank, zone)))
+        .collect();
+    matches.sort_by_key(|(rank, zone)| (*rank, zone.country_code, zone.code));
+    matches.truncate(MAX_SEARCH_RESULTS);
+
+    matches.into_iter().map(|(_, zone)| zone).collect()
+}
+
 /// List all available country codes
 pub fn list_countries() -> Vec<CountryCode> {
     let mut countries: Vec<_> = BIDDING_ZONES.keys().copied().collect();
@@ -130,3 +475,117 @@ impl std::fmt::Display for BiddingZone {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(query: &str) -> Vec<&'static str> {
+        search_zones(query).iter().map(|zone| zone.code).collect()
+    }
+
+    #[test]
+    fn test_search_ranks_exact_then_prefix_then_substring() {
+        // Exact country code first, then names starting with "de", then names containing it
+        let results = search_zones("DE");
+        assert_eq!(results[0].code, "10Y1001A1001A83F");
+        assert!(results[..5].iter().all(|zone| zone.country_code == "DE"));
+        assert_eq!(results[5].name, "Denmark");
+        let substring_matches: Vec<_> = results[6..].iter().map(|zone| zone.name).collect();
+        assert_eq!(substring_matches, ["Luxembourg", "Sweden"]);
+
+        assert_eq!(codes("de-lu"), ["10Y1001A1001A83F"]);
+        assert_eq!(codes("10YDE-EON------1"), ["10YDE-EON------1"]);
+    }
+
+    #[test]
+    fn test_seeded_support() {
+        let zone = |code| get_primary_zone(code).unwrap();
+        assert_eq!(
+            seeded_support(zone("DE"), DocumentKind::LoadForecast),
+            Support::Supported
+        );
+        assert_eq!(
+            seeded_support(zone("GB"), DocumentKind::DayAheadPrices),
+            Support::Unsupported
+        );
+        assert_eq!(
+            seeded_support(zone("BA"), DocumentKind::WindSolarForecast),
+            Support::Unsupported
+        );
+        assert_eq!(
+            seeded_support(zone("RU"), DocumentKind::LoadForecast),
+            Support::Unsupported
+        );
+        assert_eq!(
+            seeded_support(zone("UA"), DocumentKind::LoadForecast),
+            Support::Unknown
+        );
+        let tennet = get_zone_by_code("10YDE-EON------1").unwrap();
+        assert_eq!(
+            seeded_support(tennet, DocumentKind::DayAheadPrices),
+            Support::Unsupported
+        );
+        for zone in get_zones_by_country("FR").unwrap() {
+            assert_eq!(
+                seeded_support(zone, DocumentKind::IntradayPrices),
+                Support::Unsupported
+            );
+        }
+        assert_eq!(
+            DocumentKind::of_document_type("A44"),
+            Some(DocumentKind::DayAheadPrices)
+        );
+        assert_eq!(DocumentKind::of_document_type("A83"), None);
+    }
+
+    #[test]
+    fn test_control_areas() {
+        let tsos: Vec<_> = get_control_areas("DE")
+            .iter()
+            .map(|zone| zone.tso.unwrap())
+            .collect();
+        assert_eq!(tsos, ["50Hertz", "Amprion", "TenneT", "TransnetBW"]);
+        assert!(get_control_areas("FR").is_empty());
+        assert!(get_control_areas("XX").is_empty());
+    }
+
+    #[test]
+    fn test_load_and_generation_codes() {
+        const DENMARK: &str = "10Y1001A1001A796";
+        assert_eq!(load_code(DENMARK), "10Y1001A1001A65H");
+        assert_eq!(generation_code(DENMARK), DENMARK);
+        assert_eq!(zone_code_of("10Y1001A1001A65H"), DENMARK);
+
+        // Without override and for unknown codes, the code itself
+        assert_eq!(load_code("10YFR-RTE------C"), "10YFR-RTE------C");
+        assert_eq!(generation_code("10YFR-RTE------C"), "10YFR-RTE------C");
+        assert_eq!(load_code("10YXX-NOWHERE--0"), "10YXX-NOWHERE--0");
+        assert_eq!(zone_code_of("10YFR-RTE------C"), "10YFR-RTE------C");
+    }
+
+    #[test]
+    fn test_country_stats() {
+        let germany = country_stats("DE").unwrap();
+        assert!(germany.population > country_stats("DK").unwrap().population);
+        assert!(germany.mean_load_mw > 0.0);
+        assert_eq!(country_stats("UA"), None);
+        assert_eq!(country_stats("de"), None);
+    }
+
+    #[test]
+    fn test_search_matches_tso_case_insensitively() {
+        let results = search_zones("tennet");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].tso, Some("TenneT"));
+        assert_eq!(results[0].kind, ZoneKind::ControlArea);
+    }
+
+    #[test]
+    fn test_search_empty_and_broad_queries() {
+        assert!(search_zones("").is_empty());
+        assert!(search_zones("   ").is_empty());
+        assert!(search_zones("atlantis").is_empty());
+        assert_eq!(search_zones("1").len(), MAX_SEARCH_RESULTS);
+    }
+}
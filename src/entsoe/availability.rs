@@ -0,0 +1,127 @@
+//! How far the published forecasts of a zone reach, read from the `time_Period.timeInterval`
+//! of a wide request rather than from its points
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+
+use super::analysis::Interval;
+use super::request::QueryParams;
+use super::{EntsoeClient, EntsoeError, ForecastSource, GlMarketDocument, parse_timestamp};
+
+/// Days before today the availability request starts
+const DAYS_BACK: i64 = 1;
+/// Days after today it ends; day-ahead forecasts never reach further
+const DAYS_AHEAD: i64 = 3;
+
+/// Interval a forecast document covers
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentAvailability {
+    /// `[start, end)` of `time_Period.timeInterval`
+    pub interval: Interval,
+    /// `createdDateTime` of the document
+    pub published: DateTime<Utc>,
+}
+
+impl DocumentAvailability {
+    pub fn of(document: &GlMarketDocument) -> Result<Self, EntsoeError> {
+        Ok(Self {
+            interval: Interval {
+                start: parse_timestamp(&document.time_period_interval.start)?,
+                end: parse_timestamp(&document.time_period_interval.end)?,
+            },
+            published: parse_timestamp(&document.created_date_time)?,
+        })
+    }
+}
+
+/// Day-ahead load (A65) and wind and solar (A69) forecasts of a zone
+#[derive(Debug, Clone, PartialEq)]
+pub struct Availability {
+    pub load: DocumentAvailability,
+    pub generation: DocumentAvailability,
+}
+
+impl Availability {
+    /// Where both forecasts are available and surplus can be computed, `None` if they
+    /// do not overlap
+    pub fn surplus(&self) -> Option<Interval> {
+        let start = self.load.interval.start.max(self.generation.interval.start);
+        let end = self.load.interval.end.min(self.generation.interval.end);
+        (start < end).then_some(Interval { start, end })
+    }
+}
+
+impl EntsoeClient {
+    /// Reach of the day-ahead forecasts of `zone` from yesterday to three days ahead (UTC
+    /// days). The request is the same all day, so a document cache answers repeats.
+    pub async fn availability(&self, zone: &str) -> Result<Availability, EntsoeError> {
+        let today = Utc::now()
+            .duration_trunc(Duration::days(1))
+            .map_err(|e| EntsoeError::InvalidTimestamp(e.to_string()))?;
+        let (start, end) = (
+            today - Duration::days(DAYS_BACK),
+            today + Duration::days(DAYS_AHEAD),
+        );
+
+        let (load, generation) = tokio::try_join!(
+            self.fetch_range(
+                QueryParams::total_load_forecast(zone, ForecastSource::DayAhead),
+                start,
+                end
+            ),
+            self.fetch_range(
+                QueryParams::generation_forecast(zone, ForecastSource::DayAhead),
+                start,
+                end
+            ),
+        )?;
+        Ok(Availability {
+            load: DocumentAvailability::of(&load)?,
+            generation: DocumentAvailability::of(&generation)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::{DEFAULT_ZONE, MockTransport, query_param};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_availability_reads_the_document_intervals() {
+        // Published up to 30 hours after the start of the request
+        let transport = Arc::new(MockTransport::truncated_forecasts(30));
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+
+        let availability = client.availability(DEFAULT_ZONE).await.unwrap();
+
+        let yesterday = Utc::now().duration_trunc(Duration::days(1)).unwrap() - Duration::days(1);
+        let surplus = availability.surplus().unwrap();
+        assert_eq!(surplus.start, yesterday);
+        assert_eq!(surplus.end, yesterday + Duration::hours(30));
+        assert_eq!(availability.load.interval, availability.generation.interval);
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        let period_start = |url: &str| query_param(url, "periodStart");
+        assert_eq!(period_start(&requests[0]), period_start(&requests[1]));
+    }
+
+    #[test]
+    fn test_disjoint_forecasts_have_no_surplus() {
+        let at =
+            |hour| Utc::now().duration_trunc(Duration::days(1)).unwrap() + Duration::hours(hour);
+        let document = |start, end| DocumentAvailability {
+            interval: Interval {
+                start: at(start),
+                end: at(end),
+            },
+            published: at(0),
+        };
+        let availability = Availability {
+            load: document(0, 24),
+            generation: document(24, 48),
+        };
+        assert!(availability.surplus().is_none());
+    }
+}
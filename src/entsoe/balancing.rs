@@ -0,0 +1,257 @@
+//! Activated balancing energy (A83), published in Balancing_MarketDocuments
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::request::{ApiRequest, QueryParams, TimeRange};
+use super::{EntsoeClient, EntsoeError, MeasureUnit, Period, TimeInterval, parse_document};
+
+/// Reserve product of activated balancing energy, selected through `businessType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReserveType {
+    /// Frequency containment reserve (A95)
+    Fcr,
+    /// Automatic frequency restoration reserve (A96)
+    #[default]
+    Afrr,
+    /// Manual frequency restoration reserve (A97)
+    Mfrr,
+    /// Replacement reserve (A98)
+    Rr,
+}
+
+impl ReserveType {
+    pub fn business_type(self) -> &'static str {
+        match self {
+            ReserveType::Fcr => "A95",
+            ReserveType::Afrr => "A96",
+            ReserveType::Mfrr => "A97",
+            ReserveType::Rr => "A98",
+        }
+    }
+}
+
+/// Direction of an activation (`flowDirection.direction`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowDirection {
+    /// More generation or less consumption (A01)
+    Up,
+    /// Less generation or more consumption (A02)
+    Down,
+}
+
+impl FlowDirection {
+    pub fn from_code(code: &str) -> Result<Self, EntsoeError> {
+        match code.trim() {
+            "A01" => Ok(FlowDirection::Up),
+            "A02" => Ok(FlowDirection::Down),
+            other => Err(EntsoeError::InvalidResponse(format!(
+                "Unknown flow direction: {}",
+                other
+            ))),
+        }
+    }
+
+    fn sign(self) -> f64 {
+        match self {
+            FlowDirection::Up => 1.0,
+            FlowDirection::Down => -1.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename = "Balancing_MarketDocument")]
+pub struct BalancingMarketDocument {
+    #[serde(rename = "mRID")]
+    pub mrid: String,
+    #[serde(rename = "revisionNumber")]
+    pub revision_number: String,
+    #[serde(rename = "type")]
+    pub doc_type: String,
+    #[serde(rename = "createdDateTime")]
+    pub created_date_time: String,
+    #[serde(rename = "period.timeInterval")]
+    pub time_period_interval: TimeInterval,
+    #[serde(rename = "TimeSeries", default)]
+    pub time_series: Vec<BalancingTimeSeries>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BalancingTimeSeries {
+    #[serde(rename = "mRID")]
+    pub mrid: String,
+    #[serde(rename = "businessType")]
+    pub business_type: String,
+    #[serde(rename = "flowDirection.direction")]
+    pub flow_direction: String,
+    #[serde(rename = "quantity_Measure_Unit.name")]
+    pub quantity_measure_unit: String,
+    #[serde(rename = "Period", default)]
+    pub periods: Vec<Period>,
+}
+
+/// Balancing energy activated over one interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct Activation {
+    pub timestamp: DateTime<Utc>,
+    pub duration: Duration,
+    pub direction: FlowDirection,
+    /// Average power in MW, positive upward and negative downward
+    pub quantity: f64,
+}
+
+impl Activation {
+    pub fn end(&self) -> DateTime<Utc> {
+        self.timestamp + self.duration
+    }
+}
+
+impl BalancingMarketDocument {
+    /// Activations of all series, by time and upward before downward
+    pub fn activations(&self) -> Result<Vec<Activation>, EntsoeError> {
+        let mut activations = Vec::new();
+        for series in &self.time_series {
+            let direction = FlowDirection::from_code(&series.flow_direction)?;
+            let unit = MeasureUnit::from_code(&series.quantity_measure_unit)?;
+            for period in &series.periods {
+                let points = period
+                    .timestamped_points(unit)
+                    .map_err(|e| e.in_series(&series.mrid))?;
+                for point in points {
+                    let point = point.in_unit(MeasureUnit::Megawatt);
+                    activations.push(Activation {
+                        timestamp: point.timestamp,
+                        duration: point.duration,
+                        direction,
+                        quantity: direction.sign() * point.quantity,
+                    });
+                }
+            }
+        }
+
+        activations.sort_by_key(|a| (a.timestamp, a.direction == FlowDirection::Down));
+        Ok(activations)
+    }
+}
+
+/// Activated balancing energy (A83) of one reserve product in a control area
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivationsRequest {
+    pub control_area: String,
+    pub interval: TimeRange,
+    pub reserve_type: ReserveType,
+}
+
+impl ApiRequest for ActivationsRequest {
+    type Output = Vec<Activation>;
+
+    fn document_type(&self) -> &'static str {
+        "A83"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.control_area]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<Vec<Activation>, EntsoeError> {
+        let params = QueryParams::activated_balancing_energy(&self.control_area, self.reserve_type);
+        let mut activations = Vec::new();
+        for (request, _, _) in client.range_requests(params, self.interval.start, self.interval.end)
+        {
+            let xml = client.fetch_body(&request).await?;
+            let document: BalancingMarketDocument = parse_document(&xml, &request)?;
+            activations.extend(document.activations()?);
+        }
+
+        *client.last_success.lock().unwrap() = Some(Utc::now());
+        Ok(activations)
+    }
+}
+
+impl EntsoeClient {
+    /// Fetch the activated balancing energy (A83) of `reserve_type` in `control_area`
+    /// over `[start, end)`
+    pub async fn fetch_activated_balancing_energy(
+        &self,
+        control_area: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        reserve_type: ReserveType,
+    ) -> Result<Vec<Activation>, EntsoeError> {
+        self.fetch(ActivationsRequest {
+            control_area: control_area.to_string(),
+            interval: TimeRange::new(start, end),
+            reserve_type,
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::{MockTransport, balancing_document, ok, query_param};
+    use chrono::TimeZone;
+    use std::sync::Arc;
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_activations_are_signed_by_direction() {
+        let xml = balancing_document(
+            midnight(),
+            15,
+            &[("A02", &[30.0, 40.0]), ("A01", &[10.0, 0.0, 25.0])],
+        );
+        let document: BalancingMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+
+        let activations: Vec<_> = document
+            .activations()
+            .unwrap()
+            .into_iter()
+            .map(|a| ((a.timestamp - midnight()).num_minutes(), a.quantity))
+            .collect();
+
+        assert_eq!(
+            activations,
+            [(0, 10.0), (0, -30.0), (15, 0.0), (15, -40.0), (30, 25.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_activated_balancing_energy() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            assert_eq!(query_param(url, "documentType").as_deref(), Some("A83"));
+            ok(balancing_document(midnight(), 60, &[("A01", &[5.0; 24])]))
+        }));
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+
+        let activations = client
+            .fetch_activated_balancing_energy(
+                "10YCZ-CEPS-----N",
+                midnight(),
+                midnight() + Duration::days(1),
+                ReserveType::Mfrr,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(activations.len(), 24);
+        assert_eq!(activations[23].end(), midnight() + Duration::days(1));
+        let url = &transport.requests()[0];
+        assert_eq!(query_param(url, "businessType").as_deref(), Some("A97"));
+        assert_eq!(
+            query_param(url, "controlArea_Domain").as_deref(),
+            Some("10YCZ-CEPS-----N")
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Synchronous client for pipelines that don't run an async runtime. Requests, parsing
+//! and document merging are shared with the async [`super::EntsoeClient`].
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use super::request::{QueryParams, Request};
+use super::{
+    EntsoeError, ForecastSource, GlMarketDocument, TransportResponse, concat_documents,
+    decode_body, parse_document, parse_period, range_requests, response_body, retry_after,
+};
+
+/// Performs the HTTP requests on behalf of [`EntsoeClient`], swappable for tests
+pub trait Transport: Send + Sync {
+    fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError>;
+}
+
+/// Default transport backed by `reqwest::blocking`
+pub struct ReqwestTransport {
+    client: reqwest::blocking::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        let client = reqwest::blocking::Client::builder()
+            .gzip(true)
+            .deflate(true)
+            .build()
+            .expect("Failed to build HTTP client");
+
+        Self { client }
+    }
+}
+
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+        // reqwest errors quote the URL, security token included
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .map_err(reqwest::Error::without_url)?;
+        let status = response.status().as_u16();
+        let retry_after = retry_after(response.headers());
+        let body = decode_body(&response.bytes().map_err(reqwest::Error::without_url)?)?;
+
+        Ok(TransportResponse {
+            status,
+            body,
+            retry_after,
+        })
+    }
+}
+
+/// Blocking counterpart of [`super::EntsoeClient`], without its document cache
+pub struct EntsoeClient {
+    transport: Arc<dyn Transport>,
+    api_key: String,
+}
+
+impl std::fmt::Debug for EntsoeClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntsoeClient")
+            .field("api_key", &"***")
+            .finish_non_exhaustive()
+    }
+}
+
+impl EntsoeClient {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_transport(api_key, Arc::new(ReqwestTransport::new()))
+    }
+
+    /// Create a client that sends its requests through a custom transport
+    pub fn with_transport(api_key: impl Into<String>, transport: Arc<dyn Transport>) -> Self {
+        Self {
+            transport,
+            api_key: api_key.into(),
+        }
+    }
+
+    /// A request against the API, authenticated with the key of this client
+    pub fn request(&self, params: QueryParams) -> Request {
+        Request::new(&self.api_key, params)
+    }
+
+    /// Fetch day-ahead total load forecast (A65)
+    pub fn fetch_day_ahead_total_load_forecast(
+        &self,
+        out_bidding_zone: &str,
+        period_start: &str,
+        period_end: &str,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch_total_load_forecast(
+            out_bidding_zone,
+            period_start,
+            period_end,
+            ForecastSource::DayAhead,
+        )
+    }
+
+    /// Fetch day-ahead generation solar/wind forecast (A69)
+    pub fn fetch_day_ahead_generation_forecast(
+        &self,
+        in_domain: &str,
+        period_start: &str,
+        period_end: &str,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch_generation_forecast(
+            in_domain,
+            period_start,
+            period_end,
+            ForecastSource::DayAhead,
+        )
+    }
+
+    /// Fetch day-ahead aggregated generation forecast (A71), all production types
+    pub fn fetch_day_ahead_total_generation_forecast(
+        &self,
+        in_domain: &str,
+        period_start: &str,
+        period_end: &str,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch_range(
+            QueryParams::total_generation_forecast(in_domain),
+            parse_period(period_start)?,
+            parse_period(period_end)?,
+        )
+    }
+
+    /// Fetch the total load forecast (A65) of the given horizon
+    pub fn fetch_total_load_forecast(
+        &self,
+        out_bidding_zone: &str,
+        period_start: &str,
+        period_end: &str,
+        source: ForecastSource,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch_range(
+            QueryParams::total_load_forecast(out_bidding_zone, source),
+            parse_period(period_start)?,
+            parse_period(period_end)?,
+        )
+    }
+
+    /// Fetch the solar/wind generation forecast (A69) of the given horizon
+    pub fn fetch_generation_forecast(
+        &self,
+        in_domain: &str,
+        period_start: &str,
+        period_end: &str,
+        source: ForecastSource,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch_range(
+            QueryParams::generation_forecast(in_domain, source),
+            parse_period(period_start)?,
+            parse_period(period_end)?,
+        )
+    }
+
+    /// Fetch a document over `[start, end)`, in consecutive chunks merged into one
+    /// document like [`super::EntsoeClient::fetch_range`]
+    pub fn fetch_range(
+        &self,
+        params: QueryParams,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        let documents = range_requests(&self.api_key, params, start, end)
+            .into_iter()
+            .map(|(request, _, _)| self.fetch_and_parse(&request))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        concat_documents(documents)
+    }
+
+    fn fetch_and_parse(&self, request: &Request) -> Result<GlMarketDocument, EntsoeError> {
+        let xml = response_body(self.transport.get(&request.url())?)?;
+        parse_document(&xml, request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::MockTransport;
+
+    #[tokio::test]
+    async fn test_blocking_client_matches_async_client() {
+        let blocking_transport = Arc::new(MockTransport::forecasts());
+        let async_transport = Arc::new(MockTransport::forecasts());
+        let blocking = EntsoeClient::with_transport("test-token", blocking_transport.clone());
+        let asynchronous =
+            super::super::EntsoeClient::with_transport("test-token", async_transport.clone());
+
+        // Two years, so both clients split the request the same way
+        let (start, end) = ("202401010000", "202601010000");
+        let expected = asynchronous
+            .fetch_day_ahead_total_load_forecast("10Y1001A1001A83F", start, end)
+            .await
+            .unwrap();
+        let document = blocking
+            .fetch_day_ahead_total_load_forecast("10Y1001A1001A83F", start, end)
+            .unwrap();
+
+        assert_eq!(blocking_transport.requests(), async_transport.requests());
+        assert_eq!(blocking_transport.requests().len(), 3);
+        assert_eq!(
+            document.all_points().unwrap(),
+            expected.all_points().unwrap()
+        );
+
+        let generation = blocking
+            .fetch_day_ahead_generation_forecast("10Y1001A1001A83F", start, "202401020000")
+            .unwrap();
+        assert_eq!(generation.doc_type, "A69");
+    }
+}
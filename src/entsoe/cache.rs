@@ -0,0 +1,347 @@
+use crate::entsoe::{GlMarketDocument, fnv1a, parse_response, parse_timestamp};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long an expired document may still be served while ENTSO-E is unreachable
+const MAX_STALENESS: Duration = Duration::from_secs(3600);
+
+/// Where the data behind a document or series came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStatus {
+    /// Fetched from ENTSO-E or served from the cache within its TTL
+    #[default]
+    Fresh,
+    /// Re-fetched after the TTL ran out, found unchanged and served from the cache
+    Revalidated,
+    /// Served from the cache after the TTL ran out because ENTSO-E was unreachable
+    Stale,
+}
+
+/// Result of a cache lookup
+pub enum CachedDocument {
+    Fresh(GlMarketDocument),
+    /// Older than the TTL; revalidate before use, or serve as stale on upstream failure
+    Expired(GlMarketDocument),
+    /// Read back from disk by [`DocumentCache::hydrate`] and not revalidated since; may
+    /// be served as stale while upstream fails
+    Hydrated(GlMarketDocument),
+    Missing,
+}
+
+/// A document listed by [`DocumentCache::fresh_documents`]
+#[derive(Debug, Clone)]
+pub struct CachedEntry {
+    pub document: GlMarketDocument,
+    pub fetched_at: DateTime<Utc>,
+}
+
+struct CacheEntry {
+    document: GlMarketDocument,
+    /// Response body the document was parsed from
+    xml: Arc<str>,
+    fetched_at: Instant,
+    /// Hydrated from disk and not yet revalidated upstream
+    hydrated: bool,
+}
+
+/// A cached document as written to disk, one file each
+#[derive(Serialize, Deserialize)]
+struct StoredDocument {
+    /// Cache key: the request with its token masked
+    request: String,
+    /// RFC3339
+    fetched_at: String,
+    xml: String,
+}
+
+/// Hit/miss counters and size of a [`DocumentCache`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Expired entries found unchanged upstream and kept
+    pub revalidations: u64,
+    /// Expired entries served because the upstream request failed
+    pub stale_hits: u64,
+}
+
+/// In-memory TTL cache of parsed documents and their raw XML, keyed by request, written
+/// through to a directory if [`DocumentCache::persisted_to`] one, so a restarted process
+/// can [`hydrate`](DocumentCache::hydrate) from it
+pub struct DocumentCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    disk: Option<PathBuf>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    revalidations: AtomicU64,
+    stale_hits: AtomicU64,
+}
+
+impl DocumentCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            disk: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            revalidations: AtomicU64::new(0),
+            stale_hits: AtomicU64::new(0),
+        }
+    }
+
+    /// Also write every document to `dir`, which has to exist
+    pub fn persisted_to(mut self, dir: PathBuf) -> Self {
+        self.disk = Some(dir);
+        self
+    }
+
+    /// Look up a document; only documents younger than the TTL count as hits
+    pub fn get(&self, key: &str) -> CachedDocument {
+        let entries = self.entries.lock().unwrap();
+        let cached = match entries.get(key) {
+            Some(entry) if entry.hydrated && entry.fetched_at.elapsed() < self.max_age() => {
+                CachedDocument::Hydrated(entry.document.clone())
+            }
+            Some(entry) if entry.fetched_at.elapsed() < self.ttl => {
+                CachedDocument::Fresh(entry.document.clone())
+            }
+            Some(entry) => CachedDocument::Expired(entry.document.clone()),
+            None => CachedDocument::Missing,
+        };
+
+        match cached {
+            CachedDocument::Fresh(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            _ => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        cached
+    }
+
+    /// Restart the TTL of an expired entry whose document turned out unchanged upstream
+    pub fn revalidate(&self, key: &str) {
+        let xml = match self.entries.lock().unwrap().get_mut(key) {
+            Some(entry) => {
+                entry.fetched_at = Instant::now();
+                entry.hydrated = false;
+                self.revalidations.fetch_add(1, Ordering::Relaxed);
+                entry.xml.clone()
+            }
+            None => return,
+        };
+        self.persist(key, &xml);
+    }
+
+    /// The newest hydrated document whose key `same_series` accepts and whose time
+    /// interval covers `[start, end)`, standing in for a request of another period the
+    /// previous process did not make. Not counted as a lookup.
+    pub fn hydrated_covering(
+        &self,
+        same_series: impl Fn(&str) -> bool,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Option<GlMarketDocument> {
+        let covers = |document: &GlMarketDocument| {
+            let interval = &document.time_period_interval;
+            parse_timestamp(&interval.start).is_ok_and(|covered| covered <= start)
+                && parse_timestamp(&interval.end).is_ok_and(|covered| end <= covered)
+        };
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|(key, entry)| {
+                entry.hydrated
+                    && entry.fetched_at.elapsed() < self.max_age()
+                    && same_series(key)
+                    && covers(&entry.document)
+            })
+            // The later key of equally old entries, not the one first in hash order
+            .max_by(|(a_key, a), (b_key, b)| a.fetched_at.cmp(&b.fetched_at).then(a_key.cmp(b_key)))
+            .map(|(_, entry)| entry.document.clone())
+    }
+
+    /// Treat the hydrated entries whose key `same_series` accepts like any other, once
+    /// upstream answered their series again
+    pub fn settle_hydrated(&self, same_series: impl Fn(&str) -> bool) {
+        let mut entries = self.entries.lock().unwrap();
+        for (key, entry) in entries.iter_mut() {
+            if entry.hydrated && same_series(key) {
+                entry.hydrated = false;
+            }
+        }
+    }
+
+    /// Count an expired entry served in place of a failed request
+    pub fn record_stale_hit(&self) {
+        self.stale_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Every document younger than the TTL, whatever request fetched it, ordered by
+    /// request. Not counted as hits, so enumerating leaves the statistics alone.
+    pub fn fresh_documents(&self) -> Vec<CachedEntry> {
+        let now = Utc::now();
+        let entries = self.entries.lock().unwrap();
+        let mut fresh: Vec<(&String, &CacheEntry)> = entries
+            .iter()
+            .filter(|(_, entry)| entry.fetched_at.elapsed() < self.ttl)
+            .collect();
+        // Merging prefers the later of equally old documents, which must not depend on
+        // the hash order
+        fresh.sort_by_key(|(key, _)| *key);
+        fresh
+            .into_iter()
+            .map(|(_, entry)| CachedEntry {
+                document: entry.document.clone(),
+                fetched_at: now
+                    - chrono::Duration::from_std(entry.fetched_at.elapsed()).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// The response body of a cached document, expired or not. Not counted as a hit.
+    pub fn raw(&self, key: &str) -> Option<Arc<str>> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key).map(|entry| entry.xml.clone())
+    }
+
+    /// Store a freshly fetched document, dropping entries too old to be served stale
+    pub fn insert(&self, key: &str, document: GlMarketDocument, xml: String) {
+        let xml: Arc<str> = xml.into();
+        {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|_, entry| entry.fetched_at.elapsed() < self.max_age());
+            entries.insert(
+                key.to_string(),
+                CacheEntry {
+                    document,
+                    xml: xml.clone(),
+                    fetched_at: Instant::now(),
+                    hydrated: false,
+                },
+            );
+        }
+        self.persist(key, &xml);
+    }
+
+    /// Read back the documents written to disk whose key `keep` accepts, skipping those
+    /// already cached and removing files too old to be served. They are looked up as
+    /// [`CachedDocument::Hydrated`] until [settled](Self::settle_hydrated). Returns how
+    /// many were read.
+    pub fn hydrate(&self, keep: impl Fn(&str) -> bool) -> std::io::Result<usize> {
+        let Some(dir) = &self.disk else {
+            return Ok(0);
+        };
+        let now = Utc::now();
+        let mut hydrated = 0;
+        for file in std::fs::read_dir(dir)? {
+            let path = file?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let Some(stored) = std::fs::read(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<StoredDocument>(&bytes).ok())
+            else {
+                eprintln!("Ignoring unreadable cache file {}", path.display());
+                continue;
+            };
+            let age = DateTime::parse_from_rfc3339(&stored.fetched_at)
+                .ok()
+                // Written "in the future" by a clock set back since: as good as new
+                .map(|fetched_at| {
+                    (now - fetched_at.with_timezone(&Utc))
+                        .to_std()
+                        .unwrap_or_default()
+                })
+                .unwrap_or(Duration::MAX);
+            if age >= self.max_age() {
+                // Best effort, it is skipped the next time as well
+                let _ = std::fs::remove_file(&path);
+                continue;
+            }
+            if !keep(&stored.request) {
+                continue;
+            }
+            let document = match parse_response::<GlMarketDocument>(stored.xml.as_bytes()) {
+                Ok(document) => document,
+                Err(e) => {
+                    eprintln!("Ignoring cache file {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            let mut entries = self.entries.lock().unwrap();
+            let fetched_at = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+            let newer = entries
+                .get(&stored.request)
+                .is_some_and(|entry| !entry.hydrated || entry.fetched_at > fetched_at);
+            if !newer {
+                entries.insert(
+                    stored.request,
+                    CacheEntry {
+                        document,
+                        xml: stored.xml.into(),
+                        fetched_at,
+                        hydrated: true,
+                    },
+                );
+                hydrated += 1;
+            }
+        }
+        Ok(hydrated)
+    }
+
+    /// Age after which entries are neither served nor kept
+    fn max_age(&self) -> Duration {
+        self.ttl + MAX_STALENESS
+    }
+
+    /// Write an entry to disk, replacing the file atomically. Failures are logged, the
+    /// in-memory entry stays valid.
+    fn persist(&self, key: &str, xml: &str) {
+        let Some(dir) = &self.disk else {
+            return;
+        };
+        let stored = StoredDocument {
+            request: key.to_string(),
+            fetched_at: Utc::now().to_rfc3339(),
+            xml: xml.to_string(),
+        };
+        if let Err(e) = write_stored(dir, &stored) {
+            eprintln!("Writing {} to the disk cache failed: {}", key, e);
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.lock().unwrap().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            revalidations: self.revalidations.load(Ordering::Relaxed),
+            stale_hits: self.stale_hits.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Write `stored` to a temporary file and rename it over the file of its key, so a
+/// crash never leaves a truncated document behind
+fn write_stored(dir: &Path, stored: &StoredDocument) -> std::io::Result<()> {
+    let name = format!("{:016x}", fnv1a(stored.request.bytes()));
+    // Unique, so concurrent writes of the same key do not share a temporary file
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let temporary = dir.join(format!(
+        "{}.{}.tmp",
+        name,
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&temporary, serde_json::to_vec(stored)?)?;
+    std::fs::rename(&temporary, dir.join(format!("{}.json", name)))
+}
@@ -0,0 +1,331 @@
+//! Which documents each zone publishes: the [`seeded_support`] of the areas table,
+//! corrected by what fetches return. A zone seeded as unsupported that answers becomes
+//! supported, one answering "no matching data" repeatedly becomes unsupported. Unsupported
+//! kinds are probed again after [`RECHECK_AFTER`], and the learned support is kept next to
+//! the disk cache so it survives restarts.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::EntsoeError;
+use super::areas::{DocumentKind, Support, get_zone_by_code, seeded_support};
+
+/// Consecutive "no matching data" answers after which a kind is learned unsupported
+const MISSES_UNTIL_UNSUPPORTED: u32 = 3;
+/// How long a kind stays unsupported before one request is let through again
+pub const RECHECK_AFTER: Duration = Duration::hours(24);
+/// File of the learned support in the cache directory. Not `.json`, which the document
+/// cache reads back as documents.
+const LEARNED_FILE: &str = "capabilities.jsonl";
+
+/// Support of a kind of document in a zone, as reported by `/api/v1/capabilities`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Capability {
+    pub kind: DocumentKind,
+    pub support: Support,
+    /// Whether `support` was learned from fetches rather than seeded
+    pub learned: bool,
+}
+
+/// A learned support as written to disk, one line each
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LearnedLine {
+    zone: String,
+    kind: DocumentKind,
+    support: Support,
+    /// RFC3339
+    checked_at: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Learned {
+    support: Support,
+    /// Last answer, or last probe of an unsupported kind
+    checked_at: DateTime<Utc>,
+}
+
+type Key = (String, DocumentKind);
+
+/// Seeded and learned support of the zones, shared by everything fetching through a
+/// client
+#[derive(Debug)]
+pub struct Capabilities {
+    learned: Mutex<HashMap<Key, Learned>>,
+    misses: Mutex<HashMap<Key, u32>>,
+    /// Seeded unsupported kinds are first probed [`RECHECK_AFTER`] after this
+    since: DateTime<Utc>,
+    file: Option<PathBuf>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Capabilities {
+    pub fn new(since: DateTime<Utc>) -> Self {
+        Self {
+            learned: Mutex::default(),
+            misses: Mutex::default(),
+            since,
+            file: None,
+        }
+    }
+
+    /// Keep the learned support in `dir`, reading back what a previous process learned
+    pub fn persisted_to(mut self, dir: PathBuf) -> Self {
+        let file = dir.join(LEARNED_FILE);
+        if let Ok(text) = std::fs::read_to_string(&file) {
+            let mut learned = self.learned.lock().unwrap();
+            for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                let Some((key, entry)) =
+                    serde_json::from_str::<LearnedLine>(line)
+                        .ok()
+                        .and_then(|line| {
+                            let checked_at = DateTime::parse_from_rfc3339(&line.checked_at).ok()?;
+                            Some((
+                                (line.zone, line.kind),
+                                Learned {
+                                    support: line.support,
+                                    checked_at: checked_at.with_timezone(&Utc),
+                                },
+                            ))
+                        })
+                else {
+                    eprintln!("Ignoring unreadable line of {}", file.display());
+                    continue;
+                };
+                learned.insert(key, entry);
+            }
+        }
+        self.file = Some(file);
+        self
+    }
+
+    /// Support of `kind` in the zone with EIC code `zone_code`
+    pub fn of(&self, zone_code: &str, kind: DocumentKind) -> Capability {
+        if let Some(learned) = self.learned_support(zone_code, kind) {
+            return Capability {
+                kind,
+                support: learned.support,
+                learned: true,
+            };
+        }
+        Capability {
+            kind,
+            support: get_zone_by_code(zone_code)
+                .map_or(Support::Unknown, |zone| seeded_support(zone, kind)),
+            learned: false,
+        }
+    }
+
+    /// Whether a fetch of `kind` in the zone should go upstream at `now`. Unsupported
+    /// kinds are refused, except for one probe every [`RECHECK_AFTER`].
+    pub fn admits(&self, zone_code: &str, kind: DocumentKind, now: DateTime<Utc>) -> bool {
+        let capability = self.of(zone_code, kind);
+        if capability.support != Support::Unsupported {
+            return true;
+        }
+        if kind.document_type().is_none() {
+            return false;
+        }
+        let checked_at = self
+            .learned_support(zone_code, kind)
+            .map_or(self.since, |learned| learned.checked_at);
+        if now - checked_at < RECHECK_AFTER {
+            return false;
+        }
+        self.learned.lock().unwrap().insert(
+            (zone_code.to_string(), kind),
+            Learned {
+                support: Support::Unsupported,
+                checked_at: now,
+            },
+        );
+        true
+    }
+
+    /// Learn from the outcome of a fetch of `document_type` in the zone. Errors other
+    /// than missing data say nothing about support and are ignored.
+    pub fn record<T>(&self, zone_code: &str, document_type: &str, result: &Result<T, EntsoeError>) {
+        let Some(kind) = DocumentKind::of_document_type(document_type) else {
+            return;
+        };
+        let key = (zone_code.to_string(), kind);
+        let now = Utc::now();
+        let support = match result {
+            Ok(_) => {
+                self.misses.lock().unwrap().remove(&key);
+                Support::Supported
+            }
+            Err(e) if is_missing_data(e) => {
+                let mut misses = self.misses.lock().unwrap();
+                let count = misses.entry(key.clone()).or_default();
+                *count += 1;
+                if *count < MISSES_UNTIL_UNSUPPORTED {
+                    return;
+                }
+                Support::Unsupported
+            }
+            Err(_) => return,
+        };
+
+        let previous = self.learned.lock().unwrap().insert(
+            key,
+            Learned {
+                support,
+                checked_at: now,
+            },
+        );
+        if previous.is_none_or(|previous| previous.support != support) {
+            tracing::info!(zone_code, ?kind, ?support, "Learned the support of a zone");
+            self.persist();
+        }
+    }
+
+    fn learned_support(&self, zone_code: &str, kind: DocumentKind) -> Option<Learned> {
+        self.learned
+            .lock()
+            .unwrap()
+            .get(&(zone_code.to_string(), kind))
+            .copied()
+    }
+
+    /// Rewrite the learned support, replacing the file atomically. Failures are logged,
+    /// the support stays learned in memory.
+    fn persist(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let mut lines: Vec<LearnedLine> = self
+            .learned
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((zone, kind), learned)| LearnedLine {
+                zone: zone.clone(),
+                kind: *kind,
+                support: learned.support,
+                checked_at: learned.checked_at.to_rfc3339(),
+            })
+            .collect();
+        lines.sort_by(|a, b| (&a.zone, a.checked_at.as_str()).cmp(&(&b.zone, &b.checked_at)));
+        let text: String = lines
+            .iter()
+            .filter_map(|line| serde_json::to_string(line).ok())
+            .map(|line| line + "\n")
+            .collect();
+        let temporary = file.with_extension("jsonl.tmp");
+        if let Err(e) =
+            std::fs::write(&temporary, text).and_then(|()| std::fs::rename(&temporary, file))
+        {
+            eprintln!("Writing {} failed: {}", file.display(), e);
+        }
+    }
+}
+
+/// Upstream has nothing for the request: an acknowledgement saying so, or a document
+/// without points
+fn is_missing_data(e: &EntsoeError) -> bool {
+    match e {
+        EntsoeError::NoData(_) => true,
+        EntsoeError::InvalidResponse(body) => body.contains("No matching data"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const TENNET: &str = "10YDE-EON------1";
+    const GERMANY: &str = "10Y1001A1001A83F";
+
+    fn no_data() -> Result<(), EntsoeError> {
+        Err(EntsoeError::InvalidResponse(
+            "<Reason><text>No matching data found</text></Reason>".to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_repeated_misses_learn_unsupported() {
+        let since = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let capabilities = Capabilities::new(since);
+        assert_eq!(
+            capabilities.of(GERMANY, DocumentKind::ActualGeneration),
+            Capability {
+                kind: DocumentKind::ActualGeneration,
+                support: Support::Supported,
+                learned: false,
+            }
+        );
+
+        // Transient failures and single misses change nothing
+        let unavailable = EntsoeError::InvalidResponse("Upstream answered HTTP 503".to_string());
+        capabilities.record(GERMANY, "A75", &Err::<(), _>(unavailable));
+        capabilities.record(GERMANY, "A75", &no_data());
+        capabilities.record(GERMANY, "A75", &no_data());
+        assert!(capabilities.admits(GERMANY, DocumentKind::ActualGeneration, since));
+        capabilities.record(GERMANY, "A75", &no_data());
+        let capability = capabilities.of(GERMANY, DocumentKind::ActualGeneration);
+        assert_eq!(capability.support, Support::Unsupported);
+        assert!(capability.learned);
+        assert!(!capabilities.admits(GERMANY, DocumentKind::ActualGeneration, Utc::now()));
+
+        // A probe a day later that answers makes it supported again
+        let later = Utc::now() + RECHECK_AFTER;
+        assert!(capabilities.admits(GERMANY, DocumentKind::ActualGeneration, later));
+        assert!(!capabilities.admits(GERMANY, DocumentKind::ActualGeneration, later));
+        capabilities.record(GERMANY, "A75", &Ok(()));
+        assert_eq!(
+            capabilities
+                .of(GERMANY, DocumentKind::ActualGeneration)
+                .support,
+            Support::Supported
+        );
+    }
+
+    #[test]
+    fn test_seeded_unsupported_kinds_are_probed_after_a_day() {
+        let since = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let capabilities = Capabilities::new(since);
+        let prices = DocumentKind::DayAheadPrices;
+
+        assert!(!capabilities.admits(TENNET, prices, since + Duration::hours(1)));
+        assert!(capabilities.admits(TENNET, prices, since + RECHECK_AFTER));
+        assert!(!capabilities.admits(TENNET, DocumentKind::IntradayPrices, since + RECHECK_AFTER));
+        // An unexpected answer overrides the seed
+        capabilities.record(TENNET, "A44", &Ok(()));
+        assert!(capabilities.admits(TENNET, prices, since + RECHECK_AFTER));
+        assert_eq!(capabilities.of(TENNET, prices).support, Support::Supported);
+    }
+
+    #[test]
+    fn test_learned_support_survives_a_restart() {
+        let dir = std::env::temp_dir().join(format!("educk-{}-capabilities", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let _ = std::fs::remove_file(dir.join(LEARNED_FILE));
+
+        let capabilities = Capabilities::default().persisted_to(dir.clone());
+        capabilities.record(TENNET, "A44", &Ok(()));
+        capabilities.record(TENNET, "A65", &Ok(()));
+
+        let restarted = Capabilities::default().persisted_to(dir.clone());
+        let capability = restarted.of(TENNET, DocumentKind::DayAheadPrices);
+        assert_eq!(capability.support, Support::Supported);
+        assert!(capability.learned);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(LEARNED_FILE))
+                .unwrap()
+                .lines()
+                .count(),
+            2
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
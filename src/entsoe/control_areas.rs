@@ -0,0 +1,218 @@
+//! Load forecasts of the TSO control areas of a country, e.g. the four German ones,
+//! fetched side by side and aligned on common timestamps
+
+use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
+
+use super::analysis::load_series;
+use super::areas::BiddingZone;
+use super::request::{FetchRequest, QueryParams};
+use super::{EntsoeClient, EntsoeError, ForecastSource, TimestampedPoint};
+
+/// Day-ahead load forecast (A65) of one control area, or why it could not be fetched
+#[derive(Debug)]
+pub struct AreaLoad {
+    pub zone: &'static BiddingZone,
+    pub points: Result<Vec<TimestampedPoint>, EntsoeError>,
+}
+
+/// One control area of a [`LoadBreakdown`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AreaValues {
+    pub zone: &'static BiddingZone,
+    /// Load at each timestamp of the breakdown, `None` where the area has no point
+    pub values: Vec<Option<f64>>,
+    /// Set when the forecast of the area could not be fetched; `values` is then empty
+    pub error: Option<String>,
+}
+
+/// Load of several control areas on the union of their timestamps
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadBreakdown {
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub areas: Vec<AreaValues>,
+    /// Sum over the areas that were fetched, `None` where one of them has no point
+    pub total: Vec<Option<f64>>,
+}
+
+impl LoadBreakdown {
+    /// Align the forecasts of `areas` on the union of their timestamps. Points are
+    /// matched by start time only, so areas published at different resolutions only
+    /// add up where their points start together.
+    pub fn new(areas: Vec<AreaLoad>) -> Self {
+        let timestamps: Vec<DateTime<Utc>> = areas
+            .iter()
+            .filter_map(|area| area.points.as_ref().ok())
+            .flatten()
+            .map(|point| point.timestamp)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let areas: Vec<AreaValues> = areas
+            .into_iter()
+            .map(|area| match area.points {
+                Ok(points) => AreaValues {
+                    zone: area.zone,
+                    values: timestamps
+                        .iter()
+                        .map(|timestamp| {
+                            points
+                                .iter()
+                                .find(|point| point.timestamp == *timestamp)
+                                .map(|point| point.quantity)
+                        })
+                        .collect(),
+                    error: None,
+                },
+                Err(e) => AreaValues {
+                    zone: area.zone,
+                    values: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            })
+            .collect();
+
+        let fetched: Vec<&AreaValues> = areas.iter().filter(|area| area.error.is_none()).collect();
+        let total = (0..timestamps.len())
+            .map(|i| fetched.iter().map(|area| area.values[i]).sum())
+            .collect();
+        Self {
+            timestamps,
+            areas,
+            total,
+        }
+    }
+
+    /// Whether every area was fetched
+    pub fn is_complete(&self) -> bool {
+        self.areas.iter().all(|area| area.error.is_none())
+    }
+}
+
+impl EntsoeClient {
+    /// Day-ahead load forecasts of `zones` with points starting in `[start, end)`, all
+    /// requested at once. A failing area does not fail the others.
+    pub async fn fetch_area_loads(
+        &self,
+        zones: &[&'static BiddingZone],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<AreaLoad> {
+        let requests: Vec<FetchRequest> = zones
+            .iter()
+            .map(|zone| FetchRequest {
+                params: QueryParams::total_load_forecast(zone.code, ForecastSource::DayAhead),
+                start,
+                end,
+            })
+            .collect();
+        let documents = self.fetch_many(requests, zones.len()).await;
+
+        zones
+            .iter()
+            .zip(documents)
+            .map(|(zone, fetch)| AreaLoad {
+                zone,
+                points: fetch
+                    .into_document()
+                    .and_then(|document| document.timestamped_points_where(&load_series(zone.code)))
+                    .map(|mut points| {
+                        points.retain(|point| point.timestamp >= start && point.timestamp < end);
+                        points
+                    }),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::MeasureUnit;
+    use crate::entsoe::areas::get_control_areas;
+    use crate::entsoe::testing::{MockTransport, query_param};
+    use chrono::{Duration, TimeZone};
+    use std::sync::Arc;
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    fn points(minutes: &[i64], quantity: f64) -> Vec<TimestampedPoint> {
+        minutes
+            .iter()
+            .enumerate()
+            .map(|(i, minute)| TimestampedPoint {
+                timestamp: midnight() + Duration::minutes(*minute),
+                position: i as u32 + 1,
+                quantity,
+                unit: MeasureUnit::Megawatt,
+                duration: Duration::minutes(15),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_breakdown_aligns_areas_and_flags_failures() {
+        let zones = get_control_areas("DE");
+        let breakdown = LoadBreakdown::new(vec![
+            AreaLoad {
+                zone: zones[0],
+                points: Ok(points(&[0, 15, 30], 10_000.0)),
+            },
+            AreaLoad {
+                zone: zones[1],
+                points: Ok(points(&[15, 30, 45], 20_000.0)),
+            },
+            AreaLoad {
+                zone: zones[2],
+                points: Err(EntsoeError::InvalidResponse("unavailable".to_string())),
+            },
+        ]);
+
+        assert_eq!(breakdown.timestamps.len(), 4);
+        assert_eq!(breakdown.timestamps[0], midnight());
+        assert_eq!(
+            breakdown.areas[0].values,
+            [Some(10_000.0), Some(10_000.0), Some(10_000.0), None]
+        );
+        assert_eq!(
+            breakdown.areas[1].values,
+            [None, Some(20_000.0), Some(20_000.0), Some(20_000.0)]
+        );
+        assert_eq!(
+            breakdown.total,
+            [None, Some(30_000.0), Some(30_000.0), None]
+        );
+        assert!(breakdown.areas[2].values.is_empty());
+        assert!(breakdown.areas[2].error.is_some());
+        assert!(!breakdown.is_complete());
+    }
+
+    #[tokio::test]
+    async fn test_area_loads_are_fetched_per_area() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let zones = get_control_areas("DE");
+
+        let loads = client
+            .fetch_area_loads(&zones, midnight(), midnight() + Duration::hours(6))
+            .await;
+
+        assert_eq!(loads.len(), 4);
+        for (zone, load) in zones.iter().zip(&loads) {
+            assert_eq!(load.zone.code, zone.code);
+            assert_eq!(load.points.as_ref().unwrap().len(), 6);
+        }
+        let mut requested: Vec<String> = transport
+            .requests()
+            .iter()
+            .filter_map(|url| query_param(url, "outBiddingZone_Domain"))
+            .collect();
+        requested.sort();
+        let mut expected: Vec<String> = zones.iter().map(|zone| zone.code.to_string()).collect();
+        expected.sort();
+        assert_eq!(requested, expected);
+    }
+}
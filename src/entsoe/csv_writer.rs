@@ -0,0 +1,260 @@
+//! CSV output of GL_MarketDocuments
+
+use chrono::{DateTime, Utc};
+use std::io::Write;
+
+use super::{EntsoeError, GlMarketDocument, TimeSeries, TimestampedPoint};
+
+/// How timestamps are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    /// `2024-06-01T00:00:00+00:00`
+    #[default]
+    Rfc3339,
+    /// Seconds since the Unix epoch
+    EpochSeconds,
+}
+
+/// Which rows are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvRows {
+    /// One row per point of every time series
+    #[default]
+    PerSeries,
+    /// One row per timestamp, summing all time series
+    Aggregated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// Prefix every row with the `mRID`, `businessType` and `psrType` of its series.
+    /// Ignored for [`CsvRows::Aggregated`], whose rows belong to no single series.
+    pub series_metadata: bool,
+    pub timestamp_format: TimestampFormat,
+    pub rows: CsvRows,
+    /// Only write the points in `[start, end)`
+    pub window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            series_metadata: false,
+            timestamp_format: TimestampFormat::default(),
+            rows: CsvRows::default(),
+            window: None,
+        }
+    }
+}
+
+impl CsvOptions {
+    fn with_metadata(&self) -> bool {
+        self.series_metadata && self.rows == CsvRows::PerSeries
+    }
+
+    fn in_window(&self, point: &TimestampedPoint) -> bool {
+        self.window
+            .is_none_or(|(start, end)| point.timestamp >= start && point.timestamp < end)
+    }
+
+    fn header(&self) -> Vec<&'static str> {
+        let mut header = Vec::new();
+        if self.with_metadata() {
+            header.extend(["mRID", "businessType", "psrType"]);
+        }
+        header.extend(["timestamp", "end", "quantity", "unit"]);
+        header
+    }
+
+    fn record(&self, series: Option<&TimeSeries>, point: &TimestampedPoint) -> Vec<String> {
+        let mut record = Vec::new();
+        if self.with_metadata() {
+            let series = series.expect("per series rows belong to a series");
+            record.push(series.mrid.clone());
+            record.push(series.business_type.clone());
+            record.push(
+                series
+                    .mkt_psr_type
+                    .as_ref()
+                    .map(|psr| psr.psr_type.clone())
+                    .unwrap_or_default(),
+            );
+        }
+        for timestamp in [point.timestamp, point.end()] {
+            record.push(match self.timestamp_format {
+                TimestampFormat::Rfc3339 => timestamp.to_rfc3339(),
+                TimestampFormat::EpochSeconds => timestamp.timestamp().to_string(),
+            });
+        }
+        record.push(point.quantity.to_string());
+        record.push(point.unit.code().to_string());
+        record
+    }
+
+    fn writer<W: Write>(&self, writer: W) -> csv::Writer<W> {
+        csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .from_writer(writer)
+    }
+
+    /// The header line, for writers that emit rows one at a time
+    pub fn encode_header(&self) -> Result<Vec<u8>, EntsoeError> {
+        let mut writer = self.writer(Vec::new());
+        writer.write_record(self.header())?;
+        writer
+            .into_inner()
+            .map_err(|e| csv::Error::from(e.into_error()).into())
+    }
+
+    /// A single row without series metadata, for writers that emit rows one at a time
+    pub fn encode_point(&self, point: &TimestampedPoint) -> Result<Vec<u8>, EntsoeError> {
+        let options = CsvOptions {
+            series_metadata: false,
+            ..*self
+        };
+        let mut writer = self.writer(Vec::new());
+        writer.write_record(options.record(None, point))?;
+        writer
+            .into_inner()
+            .map_err(|e| csv::Error::from(e.into_error()).into())
+    }
+}
+
+impl GlMarketDocument {
+    /// Write the points of this document as CSV with a header line
+    pub fn write_csv<W: Write>(&self, writer: W, options: CsvOptions) -> Result<(), EntsoeError> {
+        let mut writer = options.writer(writer);
+        writer.write_record(options.header())?;
+
+        match options.rows {
+            CsvRows::PerSeries => {
+                for series in &self.time_series {
+                    for point in series.timestamped_points()? {
+                        if options.in_window(&point) {
+                            writer.write_record(options.record(Some(series), &point))?;
+                        }
+                    }
+                }
+            }
+            CsvRows::Aggregated => {
+                for point in self.all_timestamped_points()? {
+                    if options.in_window(&point) {
+                        writer.write_record(options.record(None, &point))?;
+                    }
+                }
+            }
+        }
+
+        writer.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::{DEFAULT_ZONE, MockSeries, gl_document_series};
+    use chrono::TimeZone;
+
+    fn two_series_document() -> GlMarketDocument {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let mut solar = MockSeries::new("A69", DEFAULT_ZONE, &[100.0, 200.0, 300.0]);
+        solar.psr_type = Some("B16");
+        let mut wind = MockSeries::new("A69", DEFAULT_ZONE, &[1_000.0, 1_500.5, 2_000.0]);
+        wind.psr_type = Some("B19");
+
+        let xml = gl_document_series("A69", "2024-05-31T12:00:00Z", start, 60, &[solar, wind]);
+        quick_xml::de::from_str(&xml).unwrap()
+    }
+
+    fn write(document: &GlMarketDocument, options: CsvOptions) -> String {
+        let mut out = Vec::new();
+        document.write_csv(&mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_per_series_rows_with_metadata() {
+        let csv = write(
+            &two_series_document(),
+            CsvOptions {
+                delimiter: b';',
+                series_metadata: true,
+                ..CsvOptions::default()
+            },
+        );
+        let lines: Vec<_> = csv.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "mRID;businessType;psrType;timestamp;end;quantity;unit"
+        );
+        assert_eq!(lines.len(), 1 + 6);
+        assert_eq!(
+            lines[1],
+            "1;A04;B16;2024-06-01T00:00:00+00:00;2024-06-01T01:00:00+00:00;100;MAW"
+        );
+        assert_eq!(
+            lines[5],
+            "2;A04;B19;2024-06-01T01:00:00+00:00;2024-06-01T02:00:00+00:00;1500.5;MAW"
+        );
+    }
+
+    #[test]
+    fn test_aggregated_rows() {
+        let csv = write(
+            &two_series_document(),
+            CsvOptions {
+                series_metadata: true,
+                timestamp_format: TimestampFormat::EpochSeconds,
+                rows: CsvRows::Aggregated,
+                ..CsvOptions::default()
+            },
+        );
+
+        assert_eq!(
+            csv,
+            "timestamp,end,quantity,unit\n\
+             1717200000,1717203600,1100,MAW\n\
+             1717203600,1717207200,1700.5,MAW\n\
+             1717207200,1717210800,2300,MAW\n"
+        );
+    }
+
+    #[test]
+    fn test_window_and_quoting() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 1, 0, 0).unwrap();
+        let csv = write(
+            &two_series_document(),
+            CsvOptions {
+                rows: CsvRows::Aggregated,
+                window: Some((start, start + chrono::Duration::hours(1))),
+                ..CsvOptions::default()
+            },
+        );
+        assert_eq!(
+            csv,
+            "timestamp,end,quantity,unit\n\
+             2024-06-01T01:00:00+00:00,2024-06-01T02:00:00+00:00,1700.5,MAW\n"
+        );
+
+        // A delimiter inside a field is quoted
+        let options = CsvOptions {
+            delimiter: b'.',
+            ..CsvOptions::default()
+        };
+        let point = TimestampedPoint {
+            timestamp: start,
+            position: 1,
+            quantity: 1.5,
+            unit: crate::entsoe::MeasureUnit::Megawatt,
+            duration: chrono::Duration::minutes(15),
+        };
+        assert_eq!(
+            String::from_utf8(options.encode_point(&point).unwrap()).unwrap(),
+            "2024-06-01T01:00:00+00:00.2024-06-01T01:15:00+00:00.\"1.5\".MAW\n"
+        );
+    }
+}
@@ -0,0 +1,696 @@
+//! Actual generation per production type (A75) and the generation mix it adds up to, and
+//! actual generation per production unit (A73)
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::request::{ApiRequest, QueryParams, TimeRange};
+use super::{
+    EntsoeClient, EntsoeError, GlMarketDocument, MeasureUnit, RegisteredResource, TimestampedPoint,
+};
+
+/// Group of production types the mix is summarized by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenerationCategory {
+    Renewable,
+    Fossil,
+    Nuclear,
+    /// Storage, waste and production types this build does not know
+    Other,
+}
+
+impl GenerationCategory {
+    pub const ALL: [GenerationCategory; 4] = [
+        GenerationCategory::Renewable,
+        GenerationCategory::Fossil,
+        GenerationCategory::Nuclear,
+        GenerationCategory::Other,
+    ];
+}
+
+/// Production type of a generation series (`MktPSRType.psrType`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsrType {
+    Biomass,
+    FossilBrownCoal,
+    FossilCoalDerivedGas,
+    FossilGas,
+    FossilHardCoal,
+    FossilOil,
+    FossilOilShale,
+    FossilPeat,
+    Geothermal,
+    HydroPumpedStorage,
+    HydroRunOfRiver,
+    HydroWaterReservoir,
+    Marine,
+    Nuclear,
+    OtherRenewable,
+    Solar,
+    Waste,
+    WindOffshore,
+    WindOnshore,
+    Other,
+    EnergyStorage,
+}
+
+/// Code, name and category of every production type
+const PSR_TYPES: [(PsrType, &str, &str, GenerationCategory); 21] = [
+    (
+        PsrType::Biomass,
+        "B01",
+        "Biomass",
+        GenerationCategory::Renewable,
+    ),
+    (
+        PsrType::FossilBrownCoal,
+        "B02",
+        "Fossil Brown coal/Lignite",
+        GenerationCategory::Fossil,
+    ),
+    (
+        PsrType::FossilCoalDerivedGas,
+        "B03",
+        "Fossil Coal-derived gas",
+        GenerationCategory::Fossil,
+    ),
+    (
+        PsrType::FossilGas,
+        "B04",
+        "Fossil Gas",
+        GenerationCategory::Fossil,
+    ),
+    (
+        PsrType::FossilHardCoal,
+        "B05",
+        "Fossil Hard coal",
+        GenerationCategory::Fossil,
+    ),
+    (
+        PsrType::FossilOil,
+        "B06",
+        "Fossil Oil",
+        GenerationCategory::Fossil,
+    ),
+    (
+        PsrType::FossilOilShale,
+        "B07",
+        "Fossil Oil shale",
+        GenerationCategory::Fossil,
+    ),
+    (
+        PsrType::FossilPeat,
+        "B08",
+        "Fossil Peat",
+        GenerationCategory::Fossil,
+    ),
+    (
+        PsrType::Geothermal,
+        "B09",
+        "Geothermal",
+        GenerationCategory::Renewable,
+    ),
+    // Pumped water is mostly stored energy of other sources
+    (
+        PsrType::HydroPumpedStorage,
+        "B10",
+        "Hydro Pumped Storage",
+        GenerationCategory::Other,
+    ),
+    (
+        PsrType::HydroRunOfRiver,
+        "B11",
+        "Hydro Run-of-river and poundage",
+        GenerationCategory::Renewable,
+    ),
+    (
+        PsrType::HydroWaterReservoir,
+        "B12",
+        "Hydro Water Reservoir",
+        GenerationCategory::Renewable,
+    ),
+    (
+        PsrType::Marine,
+        "B13",
+        "Marine",
+        GenerationCategory::Renewable,
+    ),
+    (
+        PsrType::Nuclear,
+        "B14",
+        "Nuclear",
+        GenerationCategory::Nuclear,
+    ),
+    (
+        PsrType::OtherRenewable,
+        "B15",
+        "Other renewable",
+        GenerationCategory::Renewable,
+    ),
+    (
+        PsrType::Solar,
+        "B16",
+        "Solar",
+        GenerationCategory::Renewable,
+    ),
+    (PsrType::Waste, "B17", "Waste", GenerationCategory::Other),
+    (
+        PsrType::WindOffshore,
+        "B18",
+        "Wind Offshore",
+        GenerationCategory::Renewable,
+    ),
+    (
+        PsrType::WindOnshore,
+        "B19",
+        "Wind Onshore",
+        GenerationCategory::Renewable,
+    ),
+    (PsrType::Other, "B20", "Other", GenerationCategory::Other),
+    (
+        PsrType::EnergyStorage,
+        "B25",
+        "Energy storage",
+        GenerationCategory::Other,
+    ),
+];
+
+impl PsrType {
+    fn entry(self) -> &'static (PsrType, &'static str, &'static str, GenerationCategory) {
+        PSR_TYPES
+            .iter()
+            .find(|(psr_type, _, _, _)| *psr_type == self)
+            .expect("every production type is in the table")
+    }
+
+    /// `None` for codes this build does not know
+    pub fn from_code(code: &str) -> Option<Self> {
+        PSR_TYPES
+            .iter()
+            .find(|(_, known, _, _)| *known == code.trim())
+            .map(|(psr_type, _, _, _)| *psr_type)
+    }
+
+    pub fn code(self) -> &'static str {
+        self.entry().1
+    }
+
+    /// Name as ENTSO-E's transparency platform shows it
+    pub fn name(self) -> &'static str {
+        self.entry().2
+    }
+
+    pub fn category(self) -> GenerationCategory {
+        self.entry().3
+    }
+}
+
+/// Energy generated by one production type over the window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsrTypeShare {
+    pub psr_type: String,
+    /// The code itself for unknown production types
+    pub name: String,
+    pub category: GenerationCategory,
+    pub energy_mwh: f64,
+    /// Percent of the total generation
+    pub share_percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryShare {
+    pub category: GenerationCategory,
+    pub energy_mwh: f64,
+    pub share_percent: f64,
+}
+
+/// Generation of a window split by production type and by category. Shares add up to
+/// 100 unless nothing was generated, when they are all 0.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GenerationMix {
+    pub total_mwh: f64,
+    /// Largest first
+    pub psr_types: Vec<PsrTypeShare>,
+    /// Every category, in the order of [`GenerationCategory::ALL`]
+    pub categories: Vec<CategoryShare>,
+    /// Codes counted as [`GenerationCategory::Other`] because this build does not know them
+    pub unknown_psr_types: Vec<String>,
+}
+
+fn share(energy: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        energy / total * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Mix of the generation series of `document` over `[start, end)`. Points are integrated
+/// to energy over their own interval and counted with the part of it inside the window.
+/// Consumption series (e.g. pumping, on the out side of the zone) and series without a
+/// production type are left out.
+pub fn generation_mix(
+    document: &GlMarketDocument,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<GenerationMix, EntsoeError> {
+    let mut energy: BTreeMap<String, f64> = BTreeMap::new();
+    for series in &document.time_series {
+        let key = series.key();
+        let Some(code) = key.psr_type.filter(|_| key.in_bidding_zone.is_some()) else {
+            continue;
+        };
+        let generated = energy.entry(code).or_default();
+        for point in series.timestamped_points()? {
+            let overlap = point.end().min(end) - point.timestamp.max(start);
+            if overlap <= chrono::Duration::zero() {
+                continue;
+            }
+            let fraction = overlap.num_seconds() as f64 / point.duration.num_seconds() as f64;
+            *generated += point.in_unit(MeasureUnit::MegawattHour).quantity * fraction;
+        }
+    }
+
+    let total_mwh: f64 = energy.values().sum();
+    let mut unknown_psr_types = Vec::new();
+    let mut psr_types: Vec<PsrTypeShare> = energy
+        .into_iter()
+        .map(|(code, energy_mwh)| {
+            let known = PsrType::from_code(&code);
+            if known.is_none() {
+                unknown_psr_types.push(code.clone());
+            }
+            PsrTypeShare {
+                name: known.map_or_else(|| code.clone(), |psr| psr.name().to_string()),
+                category: known.map_or(GenerationCategory::Other, PsrType::category),
+                psr_type: code,
+                energy_mwh,
+                share_percent: share(energy_mwh, total_mwh),
+            }
+        })
+        .collect();
+    // Stable, so equal energies stay ordered by code
+    psr_types.sort_by(|a, b| b.energy_mwh.total_cmp(&a.energy_mwh));
+
+    let categories = GenerationCategory::ALL
+        .into_iter()
+        .map(|category| {
+            let energy_mwh = psr_types
+                .iter()
+                .filter(|share| share.category == category)
+                .map(|share| share.energy_mwh)
+                .sum();
+            CategoryShare {
+                category,
+                energy_mwh,
+                share_percent: share(energy_mwh, total_mwh),
+            }
+        })
+        .collect();
+
+    Ok(GenerationMix {
+        total_mwh,
+        psr_types,
+        categories,
+        unknown_psr_types,
+    })
+}
+
+/// Actual generation of one production unit
+#[derive(Debug, Clone)]
+pub struct UnitGeneration {
+    pub resource: RegisteredResource,
+    /// Ordered by time, one per interval
+    pub points: Vec<TimestampedPoint>,
+}
+
+impl UnitGeneration {
+    /// Energy generated over all points
+    pub fn energy_mwh(&self) -> f64 {
+        self.points
+            .iter()
+            .map(|point| point.in_unit(MeasureUnit::MegawattHour).quantity)
+            .sum()
+    }
+
+    /// Highest power of any interval, `None` without points
+    pub fn peak_mw(&self) -> Option<f64> {
+        self.points
+            .iter()
+            .map(|point| point.in_unit(MeasureUnit::Megawatt).quantity)
+            .max_by(f64::total_cmp)
+    }
+
+    /// Power of the last interval
+    pub fn latest_mw(&self) -> Option<f64> {
+        self.points
+            .last()
+            .map(|point| point.in_unit(MeasureUnit::Megawatt).quantity)
+    }
+}
+
+/// The series of a per-unit document (A73) joined per production unit, ordered by name,
+/// then code. Series without a unit are left out; where series of one unit overlap, the
+/// one appearing later in the document wins, like [`super::AggregationPolicy::PreferLatest`].
+pub fn generation_per_unit(
+    document: &GlMarketDocument,
+) -> Result<Vec<UnitGeneration>, EntsoeError> {
+    let mut units: BTreeMap<String, UnitGeneration> = BTreeMap::new();
+    for series in document.time_series.iter().rev() {
+        let Some(resource) = &series.resource else {
+            continue;
+        };
+        let unit = units
+            .entry(resource.mrid.clone())
+            .or_insert_with(|| UnitGeneration {
+                resource: resource.clone(),
+                points: Vec::new(),
+            });
+        unit.points.extend(series.timestamped_points()?);
+    }
+
+    let mut units: Vec<UnitGeneration> = units.into_values().collect();
+    for unit in &mut units {
+        // Stable, so of equal timestamps the later series, added first, is kept
+        unit.points.sort_by_key(|point| point.timestamp);
+        unit.points.dedup_by_key(|point| point.timestamp);
+    }
+    units.sort_by(|a, b| {
+        (&a.resource.name, &a.resource.mrid).cmp(&(&b.resource.name, &b.resource.mrid))
+    });
+    Ok(units)
+}
+
+/// Actual generation per production unit (A73) of a control area, optionally of one unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitGenerationRequest {
+    pub control_area: String,
+    pub interval: TimeRange,
+    /// EIC code of the only unit to fetch
+    pub resource: Option<String>,
+}
+
+impl ApiRequest for UnitGenerationRequest {
+    type Output = Vec<UnitGeneration>;
+
+    fn document_type(&self) -> &'static str {
+        "A73"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.control_area]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<Vec<UnitGeneration>, EntsoeError> {
+        let mut params = QueryParams::generation_per_unit(&self.control_area);
+        if let Some(resource) = &self.resource {
+            params = params.registered_resource(resource);
+        }
+        let document = client
+            .fetch_range(params, self.interval.start, self.interval.end)
+            .await?;
+        generation_per_unit(&document)
+    }
+}
+
+/// Actual generation per production type (A75) of a bidding zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActualGenerationRequest {
+    pub zone: String,
+    pub interval: TimeRange,
+}
+
+impl ApiRequest for ActualGenerationRequest {
+    type Output = GlMarketDocument;
+
+    fn document_type(&self) -> &'static str {
+        "A75"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.zone]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<GlMarketDocument, EntsoeError> {
+        let params = QueryParams::actual_generation(&self.zone);
+        client
+            .fetch_range(params, self.interval.start, self.interval.end)
+            .await
+    }
+}
+
+impl EntsoeClient {
+    /// Fetch the actual generation per production type (A75) of `in_domain` over
+    /// `[start, end)`
+    pub async fn fetch_actual_generation(
+        &self,
+        in_domain: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch(ActualGenerationRequest {
+            zone: in_domain.to_string(),
+            interval: TimeRange::new(start, end),
+        })
+        .await
+    }
+
+    /// Fetch the actual generation of every production unit (A73) of `control_area` over
+    /// `[start, end)`, grouped by unit
+    pub async fn fetch_generation_per_unit(
+        &self,
+        control_area: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<UnitGeneration>, EntsoeError> {
+        self.fetch(UnitGenerationRequest {
+            control_area: control_area.to_string(),
+            interval: TimeRange::new(start, end),
+            resource: None,
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::{
+        DEFAULT_ZONE, MockTransport, actual_generation_document, generation_per_unit_document, ok,
+        query_param,
+    };
+    use chrono::{Duration, TimeZone};
+    use std::sync::Arc;
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_psr_type_table() {
+        for (psr_type, code, _, _) in PSR_TYPES {
+            assert_eq!(PsrType::from_code(code), Some(psr_type));
+            assert_eq!(psr_type.code(), code);
+        }
+        assert_eq!(PsrType::from_code("B19"), Some(PsrType::WindOnshore));
+        assert_eq!(PsrType::WindOnshore.name(), "Wind Onshore");
+        assert_eq!(PsrType::Nuclear.category(), GenerationCategory::Nuclear);
+        assert_eq!(PsrType::from_code("B99"), None);
+    }
+
+    #[test]
+    fn test_generation_mix() {
+        let xml = actual_generation_document(midnight());
+        let document: GlMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+        // Half of the first hour and all of the next two
+        let mix = generation_mix(
+            &document,
+            midnight() + Duration::minutes(30),
+            midnight() + Duration::hours(3),
+        )
+        .unwrap();
+
+        assert_eq!(mix.total_mwh, 12_500.0);
+        let types: Vec<_> = mix
+            .psr_types
+            .iter()
+            .map(|share| (share.psr_type.as_str(), share.energy_mwh))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("B16", 7_500.0),
+                ("B04", 2_500.0),
+                ("B14", 1_250.0),
+                ("B99", 1_250.0)
+            ]
+        );
+        assert_eq!(mix.psr_types[0].share_percent, 60.0);
+        assert_eq!(mix.psr_types[3].name, "B99");
+        assert_eq!(mix.psr_types[3].category, GenerationCategory::Other);
+        assert_eq!(mix.unknown_psr_types, ["B99"]);
+
+        let categories: Vec<_> = mix
+            .categories
+            .iter()
+            .map(|share| (share.category, share.share_percent))
+            .collect();
+        assert_eq!(
+            categories,
+            [
+                (GenerationCategory::Renewable, 60.0),
+                (GenerationCategory::Fossil, 20.0),
+                (GenerationCategory::Nuclear, 10.0),
+                (GenerationCategory::Other, 10.0),
+            ]
+        );
+
+        let empty = generation_mix(&document, midnight() - Duration::hours(2), midnight()).unwrap();
+        assert_eq!(empty.total_mwh, 0.0);
+        assert!(empty.categories.iter().all(|c| c.share_percent == 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_actual_generation() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            assert_eq!(query_param(url, "documentType").as_deref(), Some("A75"));
+            ok(actual_generation_document(midnight()))
+        }));
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+
+        let document = client
+            .fetch_actual_generation(DEFAULT_ZONE, midnight(), midnight() + Duration::hours(4))
+            .await
+            .unwrap();
+
+        assert_eq!(document.time_series.len(), 5);
+        let url = &transport.requests()[0];
+        assert_eq!(query_param(url, "processType").as_deref(), Some("A16"));
+        assert_eq!(query_param(url, "in_Domain").as_deref(), Some(DEFAULT_ZONE));
+    }
+
+    #[test]
+    fn test_generation_per_unit() {
+        let xml = generation_per_unit_document(midnight());
+        let mut document: GlMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+        // The second Isar hour, a revision of its first, and a series without a unit
+        let mut second_hour = document.time_series[1].clone();
+        second_hour.period.time_interval.start = "2024-06-01T01:00Z".to_string();
+        second_hour.period.time_interval.end = "2024-06-01T02:00Z".to_string();
+        second_hour.period.points[0].quantity = 1_200.0;
+        let mut revised = document.time_series[1].clone();
+        revised.period.points[0].quantity = 1_300.0;
+        document.time_series.extend([
+            second_hour,
+            revised.clone(),
+            crate::entsoe::TimeSeries {
+                resource: None,
+                ..revised
+            },
+        ]);
+
+        let units = generation_per_unit(&document).unwrap();
+        let names: Vec<_> = units
+            .iter()
+            .map(|unit| unit.resource.name.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["Borkum Riffgrund 1", "Borkum Riffgrund 2", "Isar 2"]
+        );
+        let isar = &units[2];
+        assert_eq!(isar.resource.mrid, "11WD2ISA2-K");
+        assert_eq!(isar.resource.psr_type.as_deref(), Some("B14"));
+        let quantities: Vec<_> = isar.points.iter().map(|point| point.quantity).collect();
+        assert_eq!(quantities, [1_300.0, 1_200.0]);
+        assert_eq!(isar.energy_mwh(), 2_500.0);
+        assert_eq!(isar.peak_mw(), Some(1_300.0));
+        assert_eq!(isar.latest_mw(), Some(1_200.0));
+        assert_eq!(units[0].energy_mwh(), 300.0);
+    }
+
+    #[test]
+    fn test_registered_resource_of_the_series() {
+        let xml = generation_per_unit_document(midnight()).replacen(
+            "</MktPSRType>",
+            r#"<nominalIP_PowerSystemResources.nominalP unit="MAW">465</nominalIP_PowerSystemResources.nominalP></MktPSRType>
+            <registeredResource.name>Borkum Riffgrund II</registeredResource.name>"#,
+            1,
+        );
+        let document: GlMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+        // The name sent with `registeredResource` wins over the one of the production type
+        assert_eq!(
+            document.time_series[0].resource,
+            Some(RegisteredResource {
+                mrid: "11W0-BORKUM-2".to_string(),
+                name: Some("Borkum Riffgrund II".to_string()),
+                psr_type: Some("B18".to_string()),
+                nominal_power_mw: Some(465.0),
+            })
+        );
+        assert_eq!(
+            document.time_series[1]
+                .resource
+                .as_ref()
+                .unwrap()
+                .nominal_power_mw,
+            None
+        );
+        assert!(
+            document.time_series[0]
+                .key()
+                .to_string()
+                .ends_with("resource=11W0-BORKUM-2")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_generation_per_unit() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            assert_eq!(query_param(url, "documentType").as_deref(), Some("A73"));
+            ok(generation_per_unit_document(midnight()))
+        }));
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+
+        let units = client
+            .fetch(UnitGenerationRequest {
+                control_area: "10YDE-EON------1".to_string(),
+                interval: TimeRange::new(midnight(), midnight() + Duration::hours(2)),
+                resource: Some("11WD2ISA2-K".to_string()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(units.len(), 3);
+        let url = &transport.requests()[0];
+        assert_eq!(query_param(url, "processType").as_deref(), Some("A16"));
+        assert_eq!(
+            query_param(url, "in_Domain").as_deref(),
+            Some("10YDE-EON------1")
+        );
+        assert_eq!(
+            query_param(url, "registeredResource").as_deref(),
+            Some("11WD2ISA2-K")
+        );
+
+        // A day per request
+        let error = client
+            .fetch_generation_per_unit(
+                "10YDE-EON------1",
+                midnight(),
+                midnight() + Duration::days(11),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, EntsoeError::InvalidRequest(_)), "{}", error);
+    }
+}
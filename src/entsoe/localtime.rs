@@ -0,0 +1,233 @@
+//! Local market time of the bidding zones. Every time zone of the catalog either follows
+//! the EU summer time rule or keeps one offset all year (as they do since 2016), so the
+//! conversion needs no time zone database.
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+
+const HOUR: i32 = 3600;
+
+/// Time zone names known to [`LocalZone::named`] with their standard offset and whether
+/// they observe summer time
+const ZONES: [(&str, i32, SummerTime); 47] = [
+    ("UTC", 0, SummerTime::None),
+    ("Atlantic/Reykjavik", 0, SummerTime::None),
+    ("Europe/Dublin", 0, SummerTime::Eu),
+    ("Europe/Lisbon", 0, SummerTime::Eu),
+    ("Europe/London", 0, SummerTime::Eu),
+    ("Europe/Amsterdam", HOUR, SummerTime::Eu),
+    ("Europe/Belgrade", HOUR, SummerTime::Eu),
+    ("Europe/Berlin", HOUR, SummerTime::Eu),
+    ("Europe/Bratislava", HOUR, SummerTime::Eu),
+    ("Europe/Brussels", HOUR, SummerTime::Eu),
+    ("Europe/Budapest", HOUR, SummerTime::Eu),
+    ("Europe/Copenhagen", HOUR, SummerTime::Eu),
+    ("Europe/Ljubljana", HOUR, SummerTime::Eu),
+    ("Europe/Luxembourg", HOUR, SummerTime::Eu),
+    ("Europe/Madrid", HOUR, SummerTime::Eu),
+    ("Europe/Malta", HOUR, SummerTime::Eu),
+    ("Europe/Oslo", HOUR, SummerTime::Eu),
+    ("Europe/Paris", HOUR, SummerTime::Eu),
+    ("Europe/Podgorica", HOUR, SummerTime::Eu),
+    ("Europe/Prague", HOUR, SummerTime::Eu),
+    ("Europe/Rome", HOUR, SummerTime::Eu),
+    ("Europe/Sarajevo", HOUR, SummerTime::Eu),
+    ("Europe/Skopje", HOUR, SummerTime::Eu),
+    ("Europe/Stockholm", HOUR, SummerTime::Eu),
+    ("Europe/Tirane", HOUR, SummerTime::Eu),
+    ("Europe/Vienna", HOUR, SummerTime::Eu),
+    ("Europe/Warsaw", HOUR, SummerTime::Eu),
+    ("Europe/Zagreb", HOUR, SummerTime::Eu),
+    ("Europe/Zurich", HOUR, SummerTime::Eu),
+    ("Asia/Nicosia", 2 * HOUR, SummerTime::Eu),
+    ("Europe/Athens", 2 * HOUR, SummerTime::Eu),
+    ("Europe/Bucharest", 2 * HOUR, SummerTime::Eu),
+    ("Europe/Helsinki", 2 * HOUR, SummerTime::Eu),
+    ("Europe/Kyiv", 2 * HOUR, SummerTime::Eu),
+    ("Europe/Riga", 2 * HOUR, SummerTime::Eu),
+    ("Europe/Sofia", 2 * HOUR, SummerTime::Eu),
+    ("Europe/Tallinn", 2 * HOUR, SummerTime::Eu),
+    ("Europe/Vilnius", 2 * HOUR, SummerTime::Eu),
+    // Switches at 00:00 UTC, local 02:00 in March and 03:00 in October
+    ("Europe/Chisinau", 2 * HOUR, SummerTime::EuMidnightUtc),
+    ("Europe/Kaliningrad", 2 * HOUR, SummerTime::None),
+    ("Europe/Istanbul", 3 * HOUR, SummerTime::None),
+    ("Europe/Minsk", 3 * HOUR, SummerTime::None),
+    ("Europe/Moscow", 3 * HOUR, SummerTime::None),
+    ("CET", HOUR, SummerTime::Eu),
+    ("EET", 2 * HOUR, SummerTime::Eu),
+    ("WET", 0, SummerTime::Eu),
+    ("Etc/UTC", 0, SummerTime::None),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SummerTime {
+    None,
+    /// From the last Sunday of March to the last Sunday of October, switching at 01:00 UTC
+    Eu,
+    /// The EU dates, switching at 00:00 UTC
+    EuMidnightUtc,
+}
+
+/// A time zone to show timestamps in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalZone {
+    /// IANA name
+    pub name: &'static str,
+    standard_offset: i32,
+    summer_time: SummerTime,
+}
+
+/// The last Sunday of `month` in `year`
+fn last_sunday(year: i32, month: u32) -> NaiveDate {
+    let first_of_next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    }
+    .expect("valid month");
+    let last = first_of_next - Duration::days(1);
+    last - Duration::days(last.weekday().num_days_from_sunday() as i64)
+}
+
+impl LocalZone {
+    pub const UTC: LocalZone = LocalZone {
+        name: "UTC",
+        standard_offset: 0,
+        summer_time: SummerTime::None,
+    };
+
+    /// The zone of an IANA name, case-insensitive; `None` for zones outside the catalog
+    pub fn named(name: &str) -> Option<Self> {
+        ZONES
+            .iter()
+            .find(|(known, _, _)| known.eq_ignore_ascii_case(name.trim()))
+            .map(|&(name, standard_offset, summer_time)| Self {
+                name,
+                standard_offset,
+                summer_time,
+            })
+    }
+
+    /// Start and end of summer time in `year`, if observed
+    fn summer(&self, year: i32) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let hour = match self.summer_time {
+            SummerTime::None => return None,
+            SummerTime::Eu => 1,
+            SummerTime::EuMidnightUtc => 0,
+        };
+        let at = |date: NaiveDate| date.and_hms_opt(hour, 0, 0).unwrap().and_utc();
+        Some((at(last_sunday(year, 3)), at(last_sunday(year, 10))))
+    }
+
+    /// UTC offset in effect at `utc`
+    pub fn offset_at(&self, utc: DateTime<Utc>) -> FixedOffset {
+        let summer = self
+            .summer(utc.year())
+            .is_some_and(|(start, end)| start <= utc && utc < end);
+        let seconds = self.standard_offset + if summer { HOUR } else { 0 };
+        FixedOffset::east_opt(seconds).expect("offset within a day")
+    }
+
+    pub fn to_local(&self, utc: DateTime<Utc>) -> DateTime<FixedOffset> {
+        utc.with_timezone(&self.offset_at(utc))
+    }
+
+    /// The instant a wall-clock time of this zone stands for. A time repeated when the
+    /// clocks go back is taken with the earlier (summer) offset; a time skipped when they
+    /// go forward is read with the standard offset, i.e. as the time after the gap.
+    pub fn from_local(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let standard = Duration::seconds(self.standard_offset as i64);
+        let candidates = [standard + Duration::hours(1), standard];
+        candidates
+            .into_iter()
+            .map(|offset| (local - offset).and_utc())
+            .find(|utc| {
+                Duration::seconds(self.offset_at(*utc).local_minus_utc() as i64)
+                    == local - utc.naive_utc()
+            })
+            .unwrap_or_else(|| (local - standard).and_utc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_catalog_time_zones_are_known() {
+        for (country_code, zones) in crate::entsoe::areas::BIDDING_ZONES.iter() {
+            for zone in zones.iter() {
+                assert!(
+                    LocalZone::named(zone.timezone).is_some(),
+                    "{} {}",
+                    country_code,
+                    zone.timezone
+                );
+            }
+        }
+        assert_eq!(
+            LocalZone::named("europe/berlin").unwrap().name,
+            "Europe/Berlin"
+        );
+        assert!(LocalZone::named("America/New_York").is_none());
+    }
+
+    #[test]
+    fn test_offsets_around_transitions() {
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 3, 31, h, m, 0).unwrap();
+
+        assert_eq!(berlin.offset_at(at(0, 59)).local_minus_utc(), 3600);
+        assert_eq!(berlin.offset_at(at(1, 0)).local_minus_utc(), 7200);
+        assert_eq!(
+            berlin.to_local(at(1, 0)).to_rfc3339(),
+            "2024-03-31T03:00:00+02:00"
+        );
+
+        let autumn = Utc.with_ymd_and_hms(2024, 10, 27, 0, 30, 0).unwrap();
+        assert_eq!(
+            berlin.to_local(autumn).to_rfc3339(),
+            "2024-10-27T02:30:00+02:00"
+        );
+        assert_eq!(
+            berlin.to_local(autumn + Duration::hours(1)).to_rfc3339(),
+            "2024-10-27T02:30:00+01:00"
+        );
+
+        // Moldova switches an hour before the EU, Turkey not at all
+        let chisinau = LocalZone::named("Europe/Chisinau").unwrap();
+        assert_eq!(chisinau.offset_at(at(0, 30)).local_minus_utc(), 3 * 3600);
+        let istanbul = LocalZone::named("Europe/Istanbul").unwrap();
+        assert_eq!(istanbul.offset_at(autumn).local_minus_utc(), 3 * 3600);
+        assert_eq!(LocalZone::UTC.offset_at(autumn).local_minus_utc(), 0);
+    }
+
+    #[test]
+    fn test_from_local_disambiguates() {
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+
+        // 02:30 happens twice on 2024-10-27, the earlier offset wins
+        assert_eq!(
+            berlin.from_local(local("2024-10-27 02:30")).to_rfc3339(),
+            "2024-10-27T00:30:00+00:00"
+        );
+        assert_eq!(
+            berlin.from_local(local("2024-10-27 03:30")).to_rfc3339(),
+            "2024-10-27T02:30:00+00:00"
+        );
+        // 02:30 does not exist on 2024-03-31 and is read as 03:30 CEST
+        assert_eq!(
+            berlin.from_local(local("2024-03-31 02:30")).to_rfc3339(),
+            "2024-03-31T01:30:00+00:00"
+        );
+        assert_eq!(
+            berlin.from_local(local("2024-06-01 12:00")).to_rfc3339(),
+            "2024-06-01T10:00:00+00:00"
+        );
+    }
+}
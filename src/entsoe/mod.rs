@@ -1,33 +1,142 @@
-pub(crate) mod analysis;
-pub(crate) mod areas;
+pub mod analysis;
+mod archive;
+pub mod areas;
+pub mod availability;
+pub mod balancing;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod capabilities;
+pub mod control_areas;
+pub mod csv_writer;
+pub mod generation;
+pub mod localtime;
+pub mod outages;
+pub mod prices;
+pub mod progress;
+pub mod request;
+pub mod stats;
+pub mod stream;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod transmission;
+pub mod window;
 
 use anyhow::Result;
-use chrono::{DateTime, Duration, Utc};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use futures_util::StreamExt;
 use reqwest::Client;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
+use tracing::{Instrument, Span};
+
+use crate::entsoe::cache::{CacheStats, CacheStatus, CachedDocument, DocumentCache};
+use crate::entsoe::capabilities::Capabilities;
+use crate::entsoe::outages::FetchedOutages;
+use crate::entsoe::prices::DayAheadPrices;
+use crate::entsoe::progress::{FinishedRequest, NoProgress, ProgressSink, RequestMeta};
+use crate::entsoe::request::{
+    ActualLoadRequest, ApiRequest, FetchRequest, GenerationForecastRequest, LoadForecastRequest,
+    QueryParams, Request, TimeRange, TotalGenerationForecastRequest, ValidationIssue,
+};
+use crate::entsoe::stats::DocumentStats;
 
 const BASE_URL: &str = "https://web-api.tp.entsoe.eu/api";
 
+/// Characters of an unparseable response quoted in the error
+const PARSE_ERROR_EXCERPT: usize = 500;
+
+/// Documents returned per request by multi-document queries; a full page means more follow
+const MULTI_DOCUMENT_PAGE_SIZE: usize = 200;
+
+/// Largest `offset` upstream accepts
+const MAX_OFFSET: usize = 4800;
+
+/// How long requests are held back after a rate limit without a `Retry-After`; ENTSO-E
+/// bans a token for ten minutes once it exceeds 400 requests per minute
+pub const RATE_LIMIT_COOL_DOWN: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Requests per minute upstream allows a token before banning it
+pub const MAX_REQUESTS_PER_MINUTE: u32 = 400;
+
+/// Lowercase fragments of the message ENTSO-E answers banned tokens with
+const RATE_LIMIT_MESSAGES: &[&str] = &[
+    "max allowed requests per minute",
+    "too many requests",
+    "temporarily banned",
+];
+
 #[derive(Error, Debug)]
 pub enum EntsoeError {
     #[error("HTTP request failed: {0}")]
     Request(#[from] reqwest::Error),
     #[error("XML parsing failed: {0}")]
     XmlParsing(#[from] quick_xml::DeError),
+    #[error("XML parsing failed for {url}: {source}; the response starts with: {excerpt}")]
+    DocumentParsing {
+        /// Request URL with the security token masked
+        url: String,
+        excerpt: String,
+        source: Box<quick_xml::DeError>,
+    },
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+    #[error("Invalid request: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidRequest(Vec<ValidationIssue>),
     #[error("Invalid resolution format: {0}")]
     InvalidResolution(String),
     #[error("Invalid timestamp: {0}")]
     InvalidTimestamp(String),
+    #[error("No time series matches {0}")]
+    NoMatchingSeries(String),
+    /// The documents parsed but hold no points to answer with, e.g. a Period without
+    /// `Point` elements or a document without TimeSeries
+    #[error("No data: {0}")]
+    NoData(String),
+    /// Upstream throttles the token, by a 429 or by its ban message
+    #[error("Rate limited by ENTSO-E{}", .retry_after.map(|d| format!(", retry in {}s", d.as_secs())).unwrap_or_default())]
+    RateLimited {
+        retry_after: Option<std::time::Duration>,
+    },
+    #[error("Several time series match {filter}: {matches}")]
+    AmbiguousSeries { filter: String, matches: String },
+    #[error("Unknown measure unit: {0}")]
+    UnknownUnit(String),
+    #[error("Cannot combine a series in {left} with a series in {right}")]
+    UnitMismatch {
+        left: MeasureUnit,
+        right: MeasureUnit,
+    },
+    #[error("CSV output failed: {0}")]
+    Csv(#[from] csv::Error),
+}
+
+impl EntsoeError {
+    /// Name the time series an invalid response was found in
+    pub(crate) fn in_series(self, mrid: &str) -> Self {
+        match self {
+            EntsoeError::InvalidResponse(message) => {
+                EntsoeError::InvalidResponse(format!("Time series {}: {}", mrid, message))
+            }
+            other => other,
+        }
+    }
 }
 
 // Main response structure
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename = "GL_MarketDocument")]
 pub struct GlMarketDocument {
+    /// Default namespace, naming the schema and its version, see
+    /// [`GlMarketDocument::schema_version`]. Elements and attributes are matched by their
+    /// local names, so documents of every version parse alike.
+    #[serde(rename = "@xmlns", default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
     #[serde(rename = "mRID")]
     pub mrid: String,
     #[serde(rename = "revisionNumber")]
@@ -48,11 +157,59 @@ pub struct GlMarketDocument {
     pub created_date_time: String,
     #[serde(rename = "time_Period.timeInterval")]
     pub time_period_interval: TimeInterval,
-    #[serde(rename = "TimeSeries")]
+    /// One entry per Period: series upstream sends with several are split up, see
+    /// [`one_period_per_series`]
+    #[serde(
+        rename = "TimeSeries",
+        default,
+        deserialize_with = "one_period_per_series"
+    )]
     pub time_series: Vec<TimeSeries>,
+    /// Whether this copy was fetched, revalidated or served stale by the cache
+    #[serde(skip)]
+    pub cache_status: CacheStatus,
+}
+
+impl GlMarketDocument {
+    /// Version of the schema the document was written in, `None` without a default
+    /// namespace or with one not ending in a version
+    pub fn schema_version(&self) -> Option<SchemaVersion> {
+        self.namespace
+            .as_deref()
+            .and_then(SchemaVersion::from_namespace)
+    }
+}
+
+/// Version of an IEC 62325 document schema, the last two parts of its namespace: `3:0`
+/// in `urn:iec62325.351:tc57wg16:451-6:generationloaddocument:3:0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SchemaVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl SchemaVersion {
+    pub fn from_namespace(namespace: &str) -> Option<Self> {
+        let mut parts = namespace.trim().rsplit(':');
+        let minor = parts.next()?.parse().ok()?;
+        let major = parts.next()?.parse().ok()?;
+        Some(Self { major, minor })
+    }
+}
+
+impl std::fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.major, self.minor)
+    }
+}
+
+impl Serialize for SchemaVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ParticipantId {
     #[serde(rename = "$value")]
     pub value: String,
@@ -60,13 +217,13 @@ pub struct ParticipantId {
     pub coding_scheme: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TimeInterval {
     pub start: String,
     pub end: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TimeSeries {
     #[serde(rename = "mRID")]
     pub mrid: String,
@@ -82,11 +239,316 @@ pub struct TimeSeries {
     pub quantity_measure_unit: String,
     #[serde(rename = "curveType")]
     pub curve_type: String,
+    #[serde(rename = "MktPSRType")]
+    pub mkt_psr_type: Option<MktPsrType>,
+    /// Production unit of per-unit series (A73)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<RegisteredResource>,
     #[serde(rename = "Period")]
     pub period: Period,
 }
 
-#[derive(Debug, Deserialize)]
+/// A TimeSeries as sent: ENTSO-E puts one Period per day into a series spanning several
+/// days, and around clock changes the periods of one series differ in length
+#[derive(Deserialize)]
+struct SeriesWithPeriods {
+    #[serde(rename = "mRID")]
+    mrid: String,
+    #[serde(rename = "businessType")]
+    business_type: String,
+    #[serde(rename = "objectAggregation")]
+    object_aggregation: String,
+    #[serde(rename = "outBiddingZone_Domain.mRID")]
+    out_bidding_zone: Option<AreaId>,
+    #[serde(rename = "inBiddingZone_Domain.mRID")]
+    in_bidding_zone: Option<AreaId>,
+    #[serde(rename = "quantity_Measure_Unit.name")]
+    quantity_measure_unit: String,
+    #[serde(rename = "curveType")]
+    curve_type: String,
+    #[serde(rename = "MktPSRType")]
+    mkt_psr_type: Option<MktPsrType>,
+    #[serde(rename = "registeredResource.mRID")]
+    registered_resource: Option<ResourceId>,
+    #[serde(rename = "registeredResource.name")]
+    registered_resource_name: Option<String>,
+    #[serde(rename = "Period")]
+    periods: Vec<Period>,
+}
+
+impl SeriesWithPeriods {
+    /// The unit the series is about: its `registeredResource`, or the
+    /// `PowerSystemResources` of its production type
+    fn resource(&self) -> Option<RegisteredResource> {
+        let psr = self.mkt_psr_type.as_ref();
+        let unit = psr.and_then(|psr| psr.power_system_resources.as_ref());
+        let mrid = self
+            .registered_resource
+            .as_ref()
+            .or(unit.map(|unit| &unit.mrid))?
+            .value
+            .clone();
+        Some(RegisteredResource {
+            mrid,
+            name: self
+                .registered_resource_name
+                .clone()
+                .or_else(|| unit.and_then(|unit| unit.name.clone())),
+            psr_type: psr.map(|psr| psr.psr_type.clone()),
+            nominal_power_mw: psr
+                .and_then(|psr| psr.nominal_power.as_ref())
+                .and_then(NominalPower::megawatts),
+        })
+    }
+}
+
+/// A series of several periods becomes one series per period, all with its mRID and key.
+/// They do not overlap, so summing them by key joins the periods back together.
+fn one_period_per_series<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<TimeSeries>, D::Error> {
+    let sent: Vec<SeriesWithPeriods> = Deserialize::deserialize(deserializer)?;
+    Ok(sent
+        .into_iter()
+        .flat_map(|series| {
+            let resource = series.resource();
+            series.periods.into_iter().map(move |period| TimeSeries {
+                mrid: series.mrid.clone(),
+                business_type: series.business_type.clone(),
+                object_aggregation: series.object_aggregation.clone(),
+                out_bidding_zone: series.out_bidding_zone.clone(),
+                in_bidding_zone: series.in_bidding_zone.clone(),
+                quantity_measure_unit: series.quantity_measure_unit.clone(),
+                curve_type: series.curve_type.clone(),
+                mkt_psr_type: series.mkt_psr_type.clone(),
+                resource: resource.clone(),
+                period,
+            })
+        })
+        .collect())
+}
+
+impl TimeSeries {
+    pub fn measure_unit(&self) -> Result<MeasureUnit, EntsoeError> {
+        MeasureUnit::from_code(&self.quantity_measure_unit)
+    }
+
+    /// Points of the period with their timestamps and the unit of the series
+    pub fn timestamped_points(&self) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        self.period
+            .timestamped_points(self.measure_unit()?)
+            .map_err(|e| e.in_series(&self.mrid))
+    }
+
+    /// Like [`TimeSeries::timestamped_points`], keeping non-finite quantities
+    fn all_positioned_points(&self) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        self.period
+            .positioned_points(self.measure_unit()?)
+            .map_err(|e| e.in_series(&self.mrid))
+    }
+
+    pub fn key(&self) -> SeriesKey {
+        SeriesKey {
+            business_type: self.business_type.clone(),
+            psr_type: self.mkt_psr_type.as_ref().map(|psr| psr.psr_type.clone()),
+            in_bidding_zone: self.in_bidding_zone.as_ref().map(|zone| zone.value.clone()),
+            out_bidding_zone: self
+                .out_bidding_zone
+                .as_ref()
+                .map(|zone| zone.value.clone()),
+            curve_type: self.curve_type.clone(),
+            resource: self.resource.as_ref().map(|resource| resource.mrid.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MktPsrType {
+    #[serde(rename = "psrType")]
+    pub psr_type: String,
+    /// The production unit of per-unit series
+    #[serde(
+        rename = "PowerSystemResources",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub power_system_resources: Option<PowerSystemResources>,
+    /// Installed capacity of that unit
+    #[serde(
+        rename = "nominalIP_PowerSystemResources.nominalP",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub nominal_power: Option<NominalPower>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PowerSystemResources {
+    #[serde(rename = "mRID")]
+    pub mrid: ResourceId,
+    pub name: Option<String>,
+}
+
+/// EIC code of a production unit
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ResourceId {
+    #[serde(rename = "$value")]
+    pub value: String,
+    #[serde(rename = "@codingScheme", default)]
+    pub coding_scheme: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NominalPower {
+    #[serde(rename = "$value")]
+    pub value: f64,
+    #[serde(rename = "@unit", default)]
+    pub unit: Option<String>,
+}
+
+impl NominalPower {
+    /// `None` for units other than MW (`MAW`)
+    pub fn megawatts(&self) -> Option<f64> {
+        match self.unit.as_deref() {
+            None | Some("MAW") => Some(self.value),
+            Some(_) => None,
+        }
+    }
+}
+
+/// A production unit (`registeredResource`) and what its series tell about it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisteredResource {
+    /// EIC code
+    pub mrid: String,
+    pub name: Option<String>,
+    /// Production type, e.g. `B14` (nuclear)
+    pub psr_type: Option<String>,
+    /// Installed capacity
+    pub nominal_power_mw: Option<f64>,
+}
+
+/// The attributes telling the time series of one document apart
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SeriesKey {
+    pub business_type: String,
+    /// Production type, e.g. `B16` (solar), only set on generation series
+    pub psr_type: Option<String>,
+    pub in_bidding_zone: Option<String>,
+    pub out_bidding_zone: Option<String>,
+    pub curve_type: String,
+    /// Production unit, only set on per-unit series
+    pub resource: Option<String>,
+}
+
+impl std::fmt::Display for SeriesKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "businessType={}", self.business_type)?;
+        if let Some(psr_type) = &self.psr_type {
+            write!(f, " psrType={}", psr_type)?;
+        }
+        if let Some(zone) = &self.in_bidding_zone {
+            write!(f, " in={}", zone)?;
+        }
+        if let Some(zone) = &self.out_bidding_zone {
+            write!(f, " out={}", zone)?;
+        }
+        write!(f, " curveType={}", self.curve_type)?;
+        if let Some(resource) = &self.resource {
+            write!(f, " resource={}", resource)?;
+        }
+        Ok(())
+    }
+}
+
+/// How series sharing a [`SeriesKey`] and overlapping in time are aggregated. Revised
+/// documents can carry the original series next to its revision, and summing both
+/// would double every value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AggregationPolicy {
+    /// Where they overlap, take the series appearing later in the document as the revision
+    #[default]
+    PreferLatest,
+    /// Fail with [`EntsoeError::AmbiguousSeries`] naming the `mRID`s of the series
+    Error,
+}
+
+/// Selects time series by their [`SeriesKey`] attributes; unset criteria match anything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeriesFilter {
+    business_type: Option<String>,
+    psr_type: Option<String>,
+    in_bidding_zone: Option<String>,
+    out_bidding_zone: Option<String>,
+    curve_type: Option<String>,
+}
+
+impl SeriesFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn business_type(mut self, code: impl Into<String>) -> Self {
+        self.business_type = Some(code.into());
+        self
+    }
+
+    pub fn psr_type(mut self, code: impl Into<String>) -> Self {
+        self.psr_type = Some(code.into());
+        self
+    }
+
+    pub fn in_bidding_zone(mut self, code: impl Into<String>) -> Self {
+        self.in_bidding_zone = Some(code.into());
+        self
+    }
+
+    pub fn out_bidding_zone(mut self, code: impl Into<String>) -> Self {
+        self.out_bidding_zone = Some(code.into());
+        self
+    }
+
+    pub fn curve_type(mut self, code: impl Into<String>) -> Self {
+        self.curve_type = Some(code.into());
+        self
+    }
+
+    pub fn matches(&self, series: &TimeSeries) -> bool {
+        let key = series.key();
+        let criterion = |wanted: &Option<String>, actual: Option<&String>| {
+            wanted.as_ref().is_none_or(|wanted| Some(wanted) == actual)
+        };
+
+        criterion(&self.business_type, Some(&key.business_type))
+            && criterion(&self.psr_type, key.psr_type.as_ref())
+            && criterion(&self.in_bidding_zone, key.in_bidding_zone.as_ref())
+            && criterion(&self.out_bidding_zone, key.out_bidding_zone.as_ref())
+            && criterion(&self.curve_type, Some(&key.curve_type))
+    }
+}
+
+impl std::fmt::Display for SeriesFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let criteria: Vec<String> = [
+            ("businessType", &self.business_type),
+            ("psrType", &self.psr_type),
+            ("in", &self.in_bidding_zone),
+            ("out", &self.out_bidding_zone),
+            ("curveType", &self.curve_type),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_ref().map(|value| format!("{}={}", name, value)))
+        .collect();
+
+        if criteria.is_empty() {
+            write!(f, "any series")
+        } else {
+            write!(f, "{}", criteria.join(" "))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct AreaId {
     #[serde(rename = "$value")]
     pub value: String,
@@ -94,16 +556,16 @@ pub struct AreaId {
     pub coding_scheme: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Period {
     #[serde(rename = "timeInterval")]
     pub time_interval: TimeInterval,
     pub resolution: String,
-    #[serde(rename = "Point")]
+    #[serde(rename = "Point", default)]
     pub points: Vec<Point>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Point {
     pub position: u32,
     pub quantity: f64,
@@ -112,185 +574,1633 @@ pub struct Point {
 /// Represents a time series point with its actual timestamp
 #[derive(Debug, Clone)]
 pub struct TimestampedPoint {
+    /// Start of the interval the point covers
     pub timestamp: DateTime<Utc>,
     pub position: u32,
     pub quantity: f64,
+    pub unit: MeasureUnit,
+    /// Length of the interval, the resolution of the period
+    pub duration: Duration,
 }
 
-pub struct EntsoeClient {
-    client: Client,
-    api_key: String,
-}
+impl TimestampedPoint {
+    /// End of the interval the point covers
+    pub fn end(&self) -> DateTime<Utc> {
+        self.timestamp + self.duration
+    }
 
-impl EntsoeClient {
-    pub fn new(api_key: impl Into<String>) -> Self {
+    /// The point with its quantity expressed in `unit`
+    pub fn in_unit(&self, unit: MeasureUnit) -> Self {
         Self {
-            client: Client::new(),
-            api_key: api_key.into(),
+            quantity: self.unit.convert(self.quantity, unit, self.duration),
+            unit,
+            ..self.clone()
         }
     }
 
-    /// Fetch day-ahead total load forecast (A65)
-    /// Example: Czech Republic bidding zone "10YCZ-CEPS-----N"
-    pub async fn fetch_day_ahead_total_load_forecast(
-        &self,
-        out_bidding_zone: &str,
-        period_start: &str,
-        period_end: &str,
-    ) -> Result<GlMarketDocument, EntsoeError> {
-        let url = format!(
-            "{}?securityToken={}&documentType=A65&processType=A01&outBiddingZone_Domain={}&periodStart={}&periodEnd={}",
-            BASE_URL, self.api_key, out_bidding_zone, period_start, period_end
-        );
+    /// The point split into `duration` long intervals; `duration` must divide the
+    /// interval of the point. Power stays the same in every part, energy is shared out.
+    fn split(&self, duration: Duration) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        let (whole, part) = (self.duration.num_seconds(), duration.num_seconds());
+        if part <= 0 || whole % part != 0 {
+            return Err(EntsoeError::InvalidResolution(format!(
+                "{} minute points can't be split into {} minute intervals",
+                self.duration.num_minutes(),
+                duration.num_minutes()
+            )));
+        }
 
-        self.fetch_and_parse(&url).await
+        let parts = whole / part;
+        let quantity = match self.unit {
+            MeasureUnit::Megawatt => self.quantity,
+            MeasureUnit::MegawattHour => self.quantity / parts as f64,
+        };
+        Ok((0..parts)
+            .map(|i| TimestampedPoint {
+                timestamp: self.timestamp + duration * i as i32,
+                quantity,
+                duration,
+                ..self.clone()
+            })
+            .collect())
     }
+}
 
-    /// Fetch day-ahead generation solar/wind forecast (A69)
-    /// Example: Belgium domain "10YBE----------2"
-    pub async fn fetch_day_ahead_generation_forecast(
-        &self,
-        in_domain: &str,
-        period_start: &str,
-        period_end: &str,
-    ) -> Result<GlMarketDocument, EntsoeError> {
-        let url = format!(
-            "{}?securityToken={}&documentType=A69&processType=A01&in_Domain={}&periodStart={}&periodEnd={}",
-            BASE_URL, self.api_key, in_domain, period_start, period_end
-        );
+/// Unit of the quantities in a time series (`quantity_Measure_Unit.name`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MeasureUnit {
+    /// Average power over the interval (MAW)
+    #[default]
+    Megawatt,
+    /// Energy over the interval (MWH)
+    MegawattHour,
+}
 
-        self.fetch_and_parse(&url).await
+impl MeasureUnit {
+    pub fn from_code(code: &str) -> Result<Self, EntsoeError> {
+        match code.trim() {
+            "MAW" => Ok(MeasureUnit::Megawatt),
+            "MWH" => Ok(MeasureUnit::MegawattHour),
+            other => Err(EntsoeError::UnknownUnit(other.to_string())),
+        }
     }
 
-    async fn fetch_and_parse(&self, url: &str) -> Result<GlMarketDocument, EntsoeError> {
-        let xml = self.client.get(url).send().await?.text().await?;
+    pub fn code(self) -> &'static str {
+        match self {
+            MeasureUnit::Megawatt => "MAW",
+            MeasureUnit::MegawattHour => "MWH",
+        }
+    }
 
-        // Check for error response
-        if xml.contains("<Reason>") || xml.contains("<code>") {
-            return Err(EntsoeError::InvalidResponse(xml));
+    /// Series can only be added or subtracted when their units agree
+    pub fn ensure_compatible(self, other: MeasureUnit) -> Result<(), EntsoeError> {
+        if self == other {
+            Ok(())
+        } else {
+            Err(EntsoeError::UnitMismatch {
+                left: self,
+                right: other,
+            })
         }
+    }
 
-        let document: GlMarketDocument = quick_xml::de::from_str(&xml).map_err(|e| {
-            eprintln!("Failed to parse XML: {}", e);
-            eprintln!("XML content: {}", xml);
-            e
-        })?;
+    /// Convert the quantity of a point covering `resolution` from this unit into `unit`
+    pub fn convert(self, quantity: f64, unit: MeasureUnit, resolution: Duration) -> f64 {
+        let hours = resolution.num_seconds() as f64 / 3600.0;
+        match (self, unit) {
+            (MeasureUnit::Megawatt, MeasureUnit::MegawattHour) => quantity * hours,
+            (MeasureUnit::MegawattHour, MeasureUnit::Megawatt) => quantity / hours,
+            _ => quantity,
+        }
+    }
+}
 
-        Ok(document)
+impl std::fmt::Display for MeasureUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
     }
 }
 
-/// Parse ISO 8601 duration format (PT15M, PT30M, PT60M, etc.)
-fn parse_resolution(resolution: &str) -> Result<Duration, EntsoeError> {
-    // Format: PT[n]M where n is minutes
-    if !resolution.starts_with("PT") || !resolution.ends_with("M") {
-        return Err(EntsoeError::InvalidResolution(resolution.to_string()));
+/// Unit power values are reported in; ENTSO-E quantities are in MW
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PowerUnit {
+    #[serde(rename = "kW", alias = "kw", alias = "KW")]
+    Kilowatt,
+    #[default]
+    #[serde(rename = "MW", alias = "mw")]
+    Megawatt,
+    #[serde(rename = "GW", alias = "gw")]
+    Gigawatt,
+}
+
+impl PowerUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PowerUnit::Kilowatt => "kW",
+            PowerUnit::Megawatt => "MW",
+            PowerUnit::Gigawatt => "GW",
+        }
     }
 
-    let minutes_str = &resolution[2..resolution.len() - 1];
-    let minutes: i64 = minutes_str
-        .parse()
-        .map_err(|_| EntsoeError::InvalidResolution(resolution.to_string()))?;
+    /// `mw` in this unit. GW keep three significant digits, fractions of a GW being
+    /// noise at the precision of the forecasts.
+    pub fn from_mw(self, mw: f64) -> f64 {
+        match self {
+            PowerUnit::Kilowatt => mw * 1_000.0,
+            PowerUnit::Megawatt => mw,
+            PowerUnit::Gigawatt => significant_digits(mw / 1_000.0, 3),
+        }
+    }
+}
 
-    Ok(Duration::minutes(minutes))
+impl std::fmt::Display for PowerUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
 }
 
-fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>, EntsoeError> {
-    let normalized = if timestamp.len() == 17 && timestamp.ends_with('Z') {
-        let mut s = timestamp.to_string();
-        s.insert_str(16, ":00"); // add seconds
-        s
+/// `value` rounded to `digits` significant digits
+fn significant_digits(value: f64, digits: i32) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+    let decimals = digits - 1 - value.abs().log10().floor() as i32;
+    if decimals >= 0 {
+        let scale = 10f64.powi(decimals);
+        (value * scale).round() / scale
     } else {
-        timestamp.to_string()
-    };
+        let scale = 10f64.powi(-decimals);
+        (value / scale).round() * scale
+    }
+}
 
-    DateTime::parse_from_rfc3339(&normalized)
-        .map(|dt| dt.with_timezone(&Utc))
-        .map_err(|e| {
-            eprintln!("Failed to parse timestamp: {}", e);
-            EntsoeError::InvalidTimestamp(timestamp.to_string())
-        })
+/// Which forecast document to fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastKind {
+    /// Total load (A65)
+    #[default]
+    Load,
+    /// Wind and solar generation (A69)
+    Generation,
+    /// Generation of all production types (A71)
+    TotalGeneration,
 }
 
-impl Period {
-    /// Get all points with their actual timestamps based on resolution
-    pub fn timestamped_points(&self) -> Result<Vec<TimestampedPoint>, EntsoeError> {
-        let start_time = parse_timestamp(&self.time_interval.start)?;
-        let resolution_duration = parse_resolution(&self.resolution)?;
+impl ForecastKind {
+    pub fn document_type(self) -> &'static str {
+        match self {
+            ForecastKind::Load => "A65",
+            ForecastKind::Generation => "A69",
+            ForecastKind::TotalGeneration => "A71",
+        }
+    }
 
-        let timestamped = self
-            .points
-            .iter()
-            .map(|point| {
-                // Position starts at 1, so subtract 1 to get offset
-                let offset = resolution_duration * (point.position as i32 - 1);
-                TimestampedPoint {
-                    timestamp: start_time + offset,
-                    position: point.position,
-                    quantity: point.quantity,
-                }
-            })
-            .collect();
+    /// Query parameter naming the bidding zone
+    pub fn zone_parameter(self) -> &'static str {
+        match self {
+            ForecastKind::Load => "outBiddingZone_Domain",
+            ForecastKind::Generation | ForecastKind::TotalGeneration => "in_Domain",
+        }
+    }
 
-        Ok(timestamped)
+    /// Code [`zone_parameter`](Self::zone_parameter) names `zone_code` by, see
+    /// [`areas::load_code`] and [`areas::generation_code`]
+    pub fn zone_code(self, zone_code: &str) -> &str {
+        match self {
+            ForecastKind::Load => areas::load_code(zone_code),
+            ForecastKind::Generation | ForecastKind::TotalGeneration => {
+                areas::generation_code(zone_code)
+            }
+        }
     }
 }
 
-// Helper functions to work with the data
-impl GlMarketDocument {
-    /// Get all timestamped points across all time series
-    pub fn all_timestamped_points(&self) -> Result<Vec<TimestampedPoint>, EntsoeError> {
-        let mut timestamp_map: HashMap<DateTime<Utc>, f64> = HashMap::new();
+/// Forecast horizon of a document, selected through `processType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastSource {
+    /// Published the day before (A01)
+    DayAhead,
+    /// Updated during the delivery day (A40)
+    Intraday,
+    /// Latest update shortly before delivery (A18), wind and solar generation only
+    Current,
+    /// Published for the coming week (A31), total load only, as a daily minimum and
+    /// maximum, see [`analysis::load_band`]
+    WeekAhead,
+}
 
-        // Aggregate all points by timestamp
-        for series in &self.time_series {
-            let points = series.period.timestamped_points()?;
-            for point in points {
-                *timestamp_map.entry(point.timestamp).or_insert(0.0) += point.quantity;
-            }
+impl ForecastSource {
+    pub fn process_type(self) -> &'static str {
+        match self {
+            ForecastSource::DayAhead => "A01",
+            ForecastSource::Intraday => "A40",
+            ForecastSource::Current => "A18",
+            ForecastSource::WeekAhead => "A31",
         }
+    }
 
-        // Convert back to Vec and sort by timestamp
-        let mut result: Vec<TimestampedPoint> = timestamp_map
-            .into_iter()
-            .map(|(timestamp, quantity)| TimestampedPoint {
-                timestamp,
-                position: 0, // Position doesn't make sense for aggregated data
-                quantity,
-            })
-            .collect();
+    /// Name as serialized
+    pub fn name(self) -> &'static str {
+        match self {
+            ForecastSource::DayAhead => "day_ahead",
+            ForecastSource::Intraday => "intraday",
+            ForecastSource::Current => "current",
+            ForecastSource::WeekAhead => "week_ahead",
+        }
+    }
+}
 
-        result.sort_by_key(|p| p.timestamp);
+/// Raw HTTP response returned by a [`Transport`]
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    pub status: u16,
+    pub body: String,
+    /// Delay of a `Retry-After` header given in seconds
+    pub retry_after: Option<std::time::Duration>,
+}
 
-        // Reassign positions based on sorted order
-        for (i, point) in result.iter_mut().enumerate() {
-            point.position = (i + 1) as u32;
-        }
+/// Performs the HTTP requests on behalf of [`EntsoeClient`], swappable for tests
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError>;
+}
 
-        Ok(result)
-    }
+/// Default transport backed by reqwest
+pub struct ReqwestTransport {
+    client: Client,
+}
 
-    /// Get all points with their timestamps as ISO strings
-    pub fn all_points_with_time(&self) -> Result<Vec<(String, u32, f64)>, EntsoeError> {
-        let timestamped = self.all_timestamped_points()?;
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        // Large documents (e.g. a week of 15-minute data) shrink considerably when compressed
+        let client = Client::builder()
+            .gzip(true)
+            .deflate(true)
+            .build()
+            .expect("Failed to build HTTP client");
 
-        Ok(timestamped
-            .into_iter()
-            .map(|tp| (tp.timestamp.to_rfc3339(), tp.position, tp.quantity))
-            .collect())
+        Self { client }
     }
+}
 
-    /// Get all points flattened (timestamp, quantity)
-    pub fn all_points(&self) -> Result<Vec<(DateTime<Utc>, f64)>, EntsoeError> {
-        let timestamped = self.all_timestamped_points()?;
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        Ok(timestamped
-            .into_iter()
-            .map(|tp| (tp.timestamp, tp.quantity))
-            .collect())
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+        // reqwest errors quote the URL, security token included
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+        let status = response.status().as_u16();
+        let retry_after = retry_after(response.headers());
+        let body = decode_body(
+            &response
+                .bytes()
+                .await
+                .map_err(reqwest::Error::without_url)?,
+        )?;
+
+        Ok(TransportResponse {
+            status,
+            body,
+            retry_after,
+        })
     }
+}
 
-    /// Get total forecast across all points
+/// Delay of a `Retry-After` header in seconds; HTTP dates are not used by ENTSO-E
+pub(crate) fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<std::time::Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(std::time::Duration::from_secs)
+}
+
+/// Result of a lightweight connectivity check against the ENTSO-E API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpstreamHealth {
+    /// The API answered and accepted the token
+    Ok,
+    /// The API answered but not successfully (server errors, throttling, garbage)
+    Degraded(String),
+    /// The API is unreachable or rejected the token
+    Down(String),
+}
+
+/// Why the API key could not be confirmed by [`EntsoeClient::validate_key`]
+#[derive(Debug, Error)]
+pub enum KeyValidationError {
+    #[error("token rejected by ENTSO-E — check ENTSOE_API_KEY ({0})")]
+    Rejected(String),
+    #[error("ENTSO-E could not be reached to check the token: {0}")]
+    Unreachable(String),
+}
+
+/// A parsed document with the response body it was parsed from
+#[derive(Debug, Clone)]
+pub struct RawDocument {
+    pub document: GlMarketDocument,
+    pub xml: String,
+}
+
+pub struct EntsoeClient {
+    transport: Arc<dyn Transport>,
+    api_key: String,
+    cache: Option<DocumentCache>,
+    last_success: Mutex<Option<DateTime<Utc>>>,
+    /// Set by a rate limit: no request goes upstream before then
+    paused_until: Mutex<Option<std::time::Instant>>,
+    /// Day-ahead prices last fetched per zone and when, kept with the cache enabled
+    latest_prices: Mutex<HashMap<String, (DayAheadPrices, DateTime<Utc>)>>,
+    /// Generation outages last fetched per zone, kept with the cache enabled
+    latest_outages: Mutex<HashMap<String, FetchedOutages>>,
+    progress: Arc<dyn ProgressSink>,
+    /// Which documents the zones publish, learned from the fetches of this client
+    capabilities: Capabilities,
+}
+
+impl std::fmt::Debug for EntsoeClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntsoeClient")
+            .field("api_key", &"***")
+            .field("cache", &self.cache.is_some())
+            .field("last_success", &self.last_successful_fetch())
+            .finish_non_exhaustive()
+    }
+}
+
+impl EntsoeClient {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_transport(api_key, Arc::new(ReqwestTransport::new()))
+    }
+
+    /// Create a client that sends its requests through a custom transport
+    pub fn with_transport(api_key: impl Into<String>, transport: Arc<dyn Transport>) -> Self {
+        Self {
+            transport,
+            api_key: api_key.into(),
+            cache: None,
+            last_success: Mutex::new(None),
+            paused_until: Mutex::new(None),
+            latest_prices: Mutex::default(),
+            latest_outages: Mutex::default(),
+            progress: Arc::new(NoProgress),
+            capabilities: Capabilities::default(),
+        }
+    }
+
+    /// Keep parsed documents for `ttl` so repeated identical requests skip the upstream
+    pub fn with_cache(mut self, ttl: std::time::Duration) -> Self {
+        self.cache = Some(DocumentCache::new(ttl));
+        self
+    }
+
+    /// Also keep the cached documents in `dir`, to [`hydrate`](Self::hydrate_cache) the
+    /// cache of the next process from. Only with [`Self::with_cache`] before. The learned
+    /// [`capabilities`](Self::capabilities) are kept there too.
+    pub fn with_disk_cache(mut self, dir: PathBuf) -> Self {
+        self.capabilities = self.capabilities.persisted_to(dir.clone());
+        self.cache = self.cache.map(|cache| cache.persisted_to(dir));
+        self
+    }
+
+    /// Seeded and learned support of the documents per zone
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Read back the documents of `zones` a previous process kept on disk. Nothing is
+    /// asked upstream until a request uses them: the first request of a series
+    /// revalidates it, falling back to the newest document read back whose interval
+    /// covers the request while upstream fails. Blocks on the file system; returns how
+    /// many documents were read.
+    pub fn hydrate_cache(&self, zones: &[&str]) -> std::io::Result<usize> {
+        let Some(cache) = &self.cache else {
+            return Ok(0);
+        };
+        cache.hydrate(|key| {
+            QueryParams::from_url(key)
+                .is_some_and(|params| params.zone().is_some_and(|zone| zones.contains(&zone)))
+        })
+    }
+
+    /// Report every request of a range fetch to `progress` as it starts and finishes
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressSink>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Statistics of the document cache, if enabled
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(DocumentCache::stats)
+    }
+
+    /// A request against the API, authenticated with the key of this client
+    pub fn request(&self, params: QueryParams) -> Request {
+        Request::new(&self.api_key, params)
+    }
+
+    /// Time of the last document successfully fetched from upstream
+    pub fn last_successful_fetch(&self) -> Option<DateTime<Utc>> {
+        *self.last_success.lock().unwrap()
+    }
+
+    /// Time left until upstream is asked again after a rate limit
+    pub fn rate_limit_pause(&self) -> Option<std::time::Duration> {
+        let paused_until = (*self.paused_until.lock().unwrap())?;
+        paused_until
+            .checked_duration_since(std::time::Instant::now())
+            .filter(|left| !left.is_zero())
+    }
+
+    /// Send `url` through the transport unless a rate limit paused the client. A rate
+    /// limited answer pauses every request of the client for the cool-down upstream asks
+    /// for, or [`RATE_LIMIT_COOL_DOWN`].
+    async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+        if let Some(left) = self.rate_limit_pause() {
+            return Err(EntsoeError::RateLimited {
+                retry_after: Some(left),
+            });
+        }
+
+        let response = self.transport.get(url).await?;
+        if let Some(EntsoeError::RateLimited { retry_after }) = rate_limit(&response) {
+            let cool_down = retry_after.unwrap_or(RATE_LIMIT_COOL_DOWN);
+            *self.paused_until.lock().unwrap() = Some(std::time::Instant::now() + cool_down);
+            return Err(EntsoeError::RateLimited {
+                retry_after: Some(cool_down),
+            });
+        }
+        Ok(response)
+    }
+
+    /// A deliberately tiny load forecast request, for probing the API
+    fn probe_request(&self) -> Request {
+        let end = Utc::now();
+        let start = end - Duration::hours(1);
+        self.request(
+            QueryParams::new("A65")
+                .process_type("A01")
+                .out_bidding_zone("10Y1001A1001A83F")
+                .period(start, end),
+        )
+    }
+
+    /// Check connectivity and token validity with a deliberately tiny load forecast request
+    pub async fn check_upstream(&self) -> UpstreamHealth {
+        match self.get(&self.probe_request().url()).await {
+            Err(e @ EntsoeError::RateLimited { .. }) => UpstreamHealth::Degraded(e.to_string()),
+            Err(e) => UpstreamHealth::Down(e.to_string()),
+            Ok(response) => match response.status {
+                // An acknowledgement like "no matching data" still proves the token works
+                200 => {
+                    *self.last_success.lock().unwrap() = Some(Utc::now());
+                    UpstreamHealth::Ok
+                }
+                401 | 403 => {
+                    UpstreamHealth::Down(format!("Token rejected (HTTP {})", response.status))
+                }
+                status => UpstreamHealth::Degraded(format!("Upstream answered HTTP {}", status)),
+            },
+        }
+    }
+
+    /// Confirm that ENTSO-E accepts the API key, telling a rejected token apart from an
+    /// API that cannot be reached or answers with server errors
+    pub async fn validate_key(&self) -> Result<(), KeyValidationError> {
+        let response = self
+            .get(&self.probe_request().url())
+            .await
+            .map_err(|e| KeyValidationError::Unreachable(e.to_string()))?;
+
+        if token_rejected(&response) {
+            return Err(KeyValidationError::Rejected(format!(
+                "HTTP {}",
+                response.status
+            )));
+        }
+        match response.status {
+            // An acknowledgement like "no matching data" still proves the token works
+            200..=299 => {
+                *self.last_success.lock().unwrap() = Some(Utc::now());
+                Ok(())
+            }
+            status => Err(KeyValidationError::Unreachable(format!(
+                "Upstream answered HTTP {}",
+                status
+            ))),
+        }
+    }
+
+    /// Fetch day-ahead total load forecast (A65)
+    /// Example: Czech Republic bidding zone "10YCZ-CEPS-----N"
+    pub async fn fetch_day_ahead_total_load_forecast(
+        &self,
+        out_bidding_zone: &str,
+        period_start: &str,
+        period_end: &str,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch_total_load_forecast(
+            out_bidding_zone,
+            period_start,
+            period_end,
+            ForecastSource::DayAhead,
+        )
+        .await
+    }
+
+    /// Fetch day-ahead generation solar/wind forecast (A69)
+    /// Example: Belgium domain "10YBE----------2"
+    pub async fn fetch_day_ahead_generation_forecast(
+        &self,
+        in_domain: &str,
+        period_start: &str,
+        period_end: &str,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch_generation_forecast(
+            in_domain,
+            period_start,
+            period_end,
+            ForecastSource::DayAhead,
+        )
+        .await
+    }
+
+    /// Fetch day-ahead aggregated generation forecast (A71), all production types
+    pub async fn fetch_day_ahead_total_generation_forecast(
+        &self,
+        in_domain: &str,
+        period_start: &str,
+        period_end: &str,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch(TotalGenerationForecastRequest {
+            zone: in_domain.to_string(),
+            interval: TimeRange::parse(period_start, period_end)?,
+        })
+        .await
+    }
+
+    /// Fetch the total load forecast (A65) of the given horizon
+    pub async fn fetch_total_load_forecast(
+        &self,
+        out_bidding_zone: &str,
+        period_start: &str,
+        period_end: &str,
+        source: ForecastSource,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch(LoadForecastRequest {
+            zone: out_bidding_zone.to_string(),
+            interval: TimeRange::parse(period_start, period_end)?,
+            horizon: source,
+        })
+        .await
+    }
+
+    /// Fetch the week-ahead load forecast of `out_bidding_zone` over `[start, end)` as
+    /// the range the load is expected to stay within each day
+    pub async fn fetch_load_band(
+        &self,
+        out_bidding_zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<analysis::LoadBand>, EntsoeError> {
+        let document = self
+            .fetch(LoadForecastRequest {
+                zone: out_bidding_zone.to_string(),
+                interval: TimeRange::new(start, end),
+                horizon: ForecastSource::WeekAhead,
+            })
+            .await?;
+        analysis::load_band(&document, out_bidding_zone)
+    }
+
+    /// Fetch the actual generation (A75) and load of `zone` over `[start, end)` as the
+    /// surplus of solar and wind over the load that came true
+    pub async fn fetch_actual_surplus(
+        &self,
+        zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<BTreeMap<DateTime<Utc>, f64>, EntsoeError> {
+        let (generation, load) = tokio::try_join!(
+            self.fetch_actual_generation(areas::generation_code(zone), start, end),
+            self.fetch(ActualLoadRequest {
+                zone: zone.to_string(),
+                interval: TimeRange::new(start, end),
+            })
+        )?;
+        analysis::actual_surplus(&generation, &load, zone)
+    }
+
+    /// Fetch the solar/wind generation forecast (A69) of the given horizon
+    pub async fn fetch_generation_forecast(
+        &self,
+        in_domain: &str,
+        period_start: &str,
+        period_end: &str,
+        source: ForecastSource,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        self.fetch(GenerationForecastRequest {
+            zone: in_domain.to_string(),
+            interval: TimeRange::parse(period_start, period_end)?,
+            horizon: source,
+        })
+        .await
+    }
+
+    /// Validate a typed request and send it, reporting every issue at once
+    pub async fn fetch<R: ApiRequest>(&self, request: R) -> Result<R::Output, EntsoeError> {
+        request.validate()?;
+        request.send(self).await
+    }
+
+    /// Fetch a document over `[start, end)`. Periods longer than upstream serves in one
+    /// request are fetched as consecutive chunks, one after the other, and merged into
+    /// a single document.
+    pub async fn fetch_range(
+        &self,
+        params: QueryParams,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        let requests = self.range_requests(params, start, end);
+        let chunks = requests.len();
+        let mut documents = Vec::new();
+        for (chunk, (request, chunk_start, chunk_end)) in requests.into_iter().enumerate() {
+            documents.push(
+                self.fetch_chunk(&request, chunk_start, chunk_end, chunk, chunks)
+                    .await?,
+            );
+        }
+
+        concat_documents(documents)
+    }
+
+    /// Like [`EntsoeClient::fetch_range`], but a failed chunk does not stop the others:
+    /// every chunk comes back with its own outcome, so [`EntsoeClient::retry_failed`] can
+    /// fetch again only those that failed
+    pub async fn fetch_range_chunks(
+        &self,
+        params: QueryParams,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> RangeFetch {
+        let requests = self.range_requests(params, start, end);
+        let count = requests.len();
+        let mut chunks = Vec::with_capacity(count);
+        for (chunk, (request, chunk_start, chunk_end)) in requests.into_iter().enumerate() {
+            let document = self
+                .fetch_chunk(&request, chunk_start, chunk_end, chunk, count)
+                .await;
+            chunks.push(RangeChunk {
+                request,
+                start: chunk_start,
+                end: chunk_end,
+                document,
+            });
+        }
+        RangeFetch { chunks }
+    }
+
+    /// Fetch the chunks of `fetch` that failed once more, keeping those that succeeded
+    pub async fn retry_failed(&self, fetch: &mut RangeFetch) {
+        let count = fetch.chunks.len();
+        for (chunk, range_chunk) in fetch.chunks.iter_mut().enumerate() {
+            if range_chunk.document.is_err() {
+                range_chunk.document = self
+                    .fetch_chunk(
+                        &range_chunk.request,
+                        range_chunk.start,
+                        range_chunk.end,
+                        chunk,
+                        count,
+                    )
+                    .await;
+            }
+        }
+    }
+
+    /// One request of a range, reported to the progress sink
+    async fn fetch_chunk(
+        &self,
+        request: &Request,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        chunk: usize,
+        chunks: usize,
+    ) -> Result<GlMarketDocument, EntsoeError> {
+        let meta = RequestMeta::new(request.params(), start, end, chunk, chunks);
+        self.progress.on_request_start(&meta);
+        let started = std::time::Instant::now();
+        let document = self.fetch_and_parse(request).await;
+        self.progress.on_request_finish(&FinishedRequest {
+            request: &meta,
+            elapsed: started.elapsed(),
+            error: document.as_ref().err(),
+        });
+        document
+    }
+
+    /// Run [`EntsoeClient::fetch_range_chunks`] for each of `requests`, with at most
+    /// `concurrency` of them in flight. Results come back in the order of `requests`.
+    pub async fn fetch_many<I>(&self, requests: I, concurrency: usize) -> Vec<RangeFetch>
+    where
+        I: IntoIterator<Item = FetchRequest>,
+    {
+        futures_util::stream::iter(requests)
+            .map(|request| self.fetch_range_chunks(request.params, request.start, request.end))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Requests covering `[start, end)`, with the interval each of them covers
+    fn range_requests(
+        &self,
+        params: QueryParams,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<(Request, DateTime<Utc>, DateTime<Utc>)> {
+        range_requests(&self.api_key, params, start, end)
+    }
+
+    /// Fetch a document through the cache. Expired entries are re-fetched; if ENTSO-E
+    /// still serves the same `createdDateTime` and `revisionNumber` the cached copy is kept,
+    /// and if the request fails it is served as stale. Hydrated entries are revalidated
+    /// alike, and while upstream fails a request missing from the cache is answered by the
+    /// newest hydrated document of its series covering its period. Entries are keyed by
+    /// the request with its token masked, so the token never reaches the disk cache.
+    async fn fetch_and_parse(&self, request: &Request) -> Result<GlMarketDocument, EntsoeError> {
+        let key = request.to_string();
+        let url = key.as_str();
+        let same_series = |other: &str| {
+            QueryParams::from_url(other).is_some_and(|other| request.params().same_series(&other))
+        };
+        let mut hydrated = false;
+        let mut covering = None;
+        let expired = match self.cache.as_ref().map(|cache| (cache, cache.get(url))) {
+            Some((_, CachedDocument::Fresh(document))) => return Ok(document),
+            Some((_, CachedDocument::Expired(document))) => Some(document),
+            Some((_, CachedDocument::Hydrated(document))) => {
+                hydrated = true;
+                Some(document)
+            }
+            // Hydrated documents are of the requests the previous process made, whose
+            // periods rarely match a later one
+            Some((cache, CachedDocument::Missing)) => {
+                covering = request
+                    .params()
+                    .time_range()
+                    .and_then(|range| cache.hydrated_covering(same_series, range.start, range.end));
+                hydrated = covering.is_some();
+                None
+            }
+            None => None,
+        };
+
+        let fetched = self.fetch_uncached(request).await;
+        let Some(cache) = &self.cache else {
+            return fetched.map(|(document, _)| document);
+        };
+        if hydrated && fetched.is_ok() {
+            cache.settle_hydrated(same_series);
+        }
+
+        match (fetched, expired) {
+            (Ok((document, _)), Some(mut cached)) if same_revision(&document, &cached) => {
+                cache.revalidate(url);
+                cached.cache_status = CacheStatus::Revalidated;
+                Ok(cached)
+            }
+            (Ok((document, xml)), _) => {
+                cache.insert(url, document.clone(), self.redact(&xml));
+                Ok(document)
+            }
+            (Err(e), expired) => match expired.or(covering) {
+                Some(mut cached) => {
+                    eprintln!(
+                        "Serving cached document after failed refresh of {}: {}",
+                        request, e
+                    );
+                    cache.record_stale_hit();
+                    cached.cache_status = CacheStatus::Stale;
+                    Ok(cached)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Fetch and parse a document, with the body it was parsed from
+    async fn fetch_uncached(
+        &self,
+        request: &Request,
+    ) -> Result<(GlMarketDocument, String), EntsoeError> {
+        let span = stats::request_span(request);
+        let fetched = async {
+            let xml = self.fetch_body(request).await?;
+            let started = std::time::Instant::now();
+            let document: GlMarketDocument = parse_document(&xml, request)?;
+            let stats = DocumentStats::of(&document, request.params().zone(), started.elapsed());
+            stats.record(&Span::current());
+            stats.emit();
+
+            *self.last_success.lock().unwrap() = Some(Utc::now());
+            Ok((document, xml))
+        }
+        .instrument(span)
+        .await;
+
+        let params = request.params();
+        if let Some(zone) = params.zone() {
+            self.capabilities
+                .record(areas::zone_code_of(zone), params.document_type(), &fetched);
+        }
+        fetched
+    }
+
+    /// Fetch the document answering a single request together with the XML it was parsed
+    /// from, through the cache if enabled. The security token is redacted from the XML
+    /// before parsing, so neither of them contains it.
+    pub async fn fetch_raw(&self, request: &Request) -> Result<RawDocument, EntsoeError> {
+        let mut cached = None;
+        if let Some(cache) = &self.cache {
+            self.fetch_and_parse(request).await?;
+            cached = cache.raw(&request.to_string());
+        }
+        let xml = match cached {
+            Some(xml) => self.redact(&xml),
+            None => self.redact(&self.fetch_uncached(request).await?.1),
+        };
+
+        Ok(RawDocument {
+            document: parse_document(&xml, request)?,
+            xml,
+        })
+    }
+
+    /// `text` with every occurrence of the security token masked
+    fn redact(&self, text: &str) -> String {
+        if self.api_key.is_empty() {
+            return text.to_string();
+        }
+        text.replace(&self.api_key, request::REDACTED)
+    }
+
+    /// Fetch a response body, turning acknowledgement documents into errors
+    async fn fetch_body(&self, request: &Request) -> Result<String, EntsoeError> {
+        response_body(self.get(&request.url()).await?)
+    }
+
+    /// Fetch every document of a query matching more than one page, re-requesting
+    /// with an increasing `offset` until a page comes back short
+    pub async fn fetch_and_parse_multi(
+        &self,
+        request: &Request,
+    ) -> Result<Vec<GlMarketDocument>, EntsoeError> {
+        self.fetch_documents(request, "GL_MarketDocument").await
+    }
+
+    /// Every document named `root` of a multi-document query, page by page like
+    /// [`Self::fetch_and_parse_multi`]
+    async fn fetch_documents<T: DeserializeOwned>(
+        &self,
+        request: &Request,
+        root: &str,
+    ) -> Result<Vec<T>, EntsoeError> {
+        let mut documents = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let page_request = request.with(|params| params.offset(offset));
+            let xml = match response_body(self.get(&page_request.url()).await?) {
+                Ok(xml) => xml,
+                // Upstream answers "no matching data" once the previous page was the last one
+                Err(EntsoeError::InvalidResponse(body))
+                    if offset > 0 && is_acknowledgement(&body) =>
+                {
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let page = split_documents(&xml, root)
+                .into_iter()
+                .map(|document| parse_document(document, &page_request))
+                .collect::<Result<Vec<_>, _>>()?;
+            let page_len = page.len();
+            documents.extend(page);
+            *self.last_success.lock().unwrap() = Some(Utc::now());
+
+            if page_len < MULTI_DOCUMENT_PAGE_SIZE {
+                break;
+            }
+            offset += MULTI_DOCUMENT_PAGE_SIZE;
+            if offset > MAX_OFFSET {
+                eprintln!(
+                    "Stopping after {} documents: upstream serves no offset beyond {}",
+                    documents.len(),
+                    MAX_OFFSET
+                );
+                break;
+            }
+            println!(
+                "Fetched {} documents so far, requesting offset {}",
+                documents.len(),
+                offset
+            );
+        }
+
+        Ok(documents)
+    }
+}
+
+/// Parse ISO 8601 duration format (PT15M, PT30M, PT60M, etc.)
+/// Split a response body holding one or more concatenated documents
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Intervals a fetch of `document_type` over `[start, end)` is split into, one request each
+pub fn request_chunks(
+    document_type: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    split_period(start, end, max_request_span(document_type))
+}
+
+/// Requests covering `[start, end)` in chunks upstream accepts, with the interval each
+/// of them covers
+fn range_requests(
+    api_key: &str,
+    params: QueryParams,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(Request, DateTime<Utc>, DateTime<Utc>)> {
+    request_chunks(params.document_type(), start, end)
+        .into_iter()
+        .map(|(chunk_start, chunk_end)| {
+            let request = Request::new(api_key, params.clone().period(chunk_start, chunk_end));
+            (request, chunk_start, chunk_end)
+        })
+        .collect()
+}
+
+/// Whether upstream refused the security token. ENTSO-E answers unknown or missing tokens
+/// with HTTP 401, sometimes with an acknowledgement naming the token instead.
+fn token_rejected(response: &TransportResponse) -> bool {
+    if matches!(response.status, 401 | 403) {
+        return true;
+    }
+    let body = response.body.to_ascii_lowercase();
+    has_element(&body, "reason")
+        && (body.contains("unauthorized") || body.contains("security token"))
+}
+
+/// Whether `xml` is an acknowledgement explaining why there is no document
+fn is_acknowledgement(xml: &str) -> bool {
+    // Outage documents give a Reason for every unavailability
+    if xml.contains(outages::DOCUMENT_ROOT) {
+        return false;
+    }
+    has_element(xml, "Reason") || has_element(xml, "code")
+}
+
+/// Whether `xml` is the acknowledgement upstream answers queries without results with
+fn is_no_matching_data(xml: &str) -> bool {
+    is_acknowledgement(xml) && xml.contains("999")
+}
+
+/// Whether `xml` has an element named `local_name`, with or without a namespace prefix
+fn has_element(xml: &str, local_name: &str) -> bool {
+    xml.contains(&format!("<{}>", local_name)) || xml.contains(&format!(":{}>", local_name))
+}
+
+/// Offset just past the first closing tag of `local_name`, with or without a prefix
+fn closing_tag_end(xml: &str, local_name: &str) -> Option<usize> {
+    let mut from = 0;
+    while let Some(found) = xml[from..].find("</") {
+        let name_start = from + found + 2;
+        let tag_end = name_start + xml[name_start..].find('>')?;
+        let name = xml[name_start..tag_end].trim_end();
+        if name.rsplit(':').next() == Some(local_name) {
+            return Some(tag_end + 1);
+        }
+        from = tag_end + 1;
+    }
+    None
+}
+
+/// The body of a response, turning acknowledgement documents and failed requests into errors
+fn response_body(response: TransportResponse) -> Result<String, EntsoeError> {
+    if let Some(rate_limited) = rate_limit(&response) {
+        return Err(rate_limited);
+    }
+    let xml = match response.body.strip_prefix(BYTE_ORDER_MARK) {
+        Some(stripped) => stripped.to_string(),
+        None => response.body,
+    };
+
+    // Check for error response
+    if is_acknowledgement(&xml) {
+        return Err(EntsoeError::InvalidResponse(xml));
+    }
+    if !(200..300).contains(&response.status) {
+        return Err(EntsoeError::InvalidResponse(format!(
+            "Upstream answered HTTP {}",
+            response.status
+        )));
+    }
+
+    Ok(xml)
+}
+
+/// The rate limit a response reports: HTTP 429, or the ban message in any status. The
+/// cool-down is taken from `Retry-After`, else from a ban message naming its minutes.
+fn rate_limit(response: &TransportResponse) -> Option<EntsoeError> {
+    let body = response.body.to_lowercase();
+    if response.status != 429 && !RATE_LIMIT_MESSAGES.iter().any(|m| body.contains(m)) {
+        return None;
+    }
+    let retry_after = response.retry_after.or_else(|| {
+        // e.g. "... you are banned for 10 minutes"
+        let (before, _) = body.split_once(" minute")?;
+        let minutes: u64 = before.rsplit(' ').next()?.parse().ok()?;
+        Some(std::time::Duration::from_secs(minutes * 60))
+    });
+    Some(EntsoeError::RateLimited { retry_after })
+}
+
+/// Turn a raw response body into text: unzip gzip bodies served without a
+/// `Content-Encoding` header and join the files of ZIP archives, drop a UTF-8 byte order mark and decode according to the
+/// encoding named in the XML declaration (UTF-8 or ISO-8859-1)
+fn decode_body(bytes: &[u8]) -> Result<String, EntsoeError> {
+    let unzipped;
+    let mut bytes = bytes;
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decoder = flate2::read::GzDecoder::new(bytes);
+        let mut buf = Vec::new();
+        std::io::Read::read_to_end(&mut decoder, &mut buf).map_err(|e| {
+            EntsoeError::InvalidResponse(format!("Failed to unzip the response: {}", e))
+        })?;
+        unzipped = buf;
+        bytes = &unzipped;
+    }
+    // Unavailability documents come one per file, split again by their root element
+    if archive::is_zip(bytes) {
+        let files = archive::unzip(bytes)?
+            .iter()
+            .map(|file| decode_body(file))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(files.join("\n"));
+    }
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+
+    match declared_encoding(bytes).as_deref() {
+        None | Some("utf-8" | "utf8") => String::from_utf8(bytes.to_vec()).map_err(|e| {
+            EntsoeError::InvalidResponse(format!("Response is not valid UTF-8: {}", e))
+        }),
+        // Every byte is the code point of the same number
+        Some("iso-8859-1" | "latin1" | "latin-1") => Ok(bytes.iter().map(|&b| b as char).collect()),
+        Some(other) => Err(EntsoeError::InvalidResponse(format!(
+            "Unsupported XML encoding {}",
+            other
+        ))),
+    }
+}
+
+/// The lowercased `encoding` of the XML declaration, if the body starts with one
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    let declaration = bytes.strip_prefix(b"<?xml")?;
+    let end = declaration.windows(2).position(|w| w == b"?>")?;
+    let declaration = std::str::from_utf8(&declaration[..end]).ok()?;
+
+    let (_, value) = declaration.split_once("encoding")?;
+    let value = value.trim_start().strip_prefix('=')?.trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let (encoding, _) = value[1..].split_once(quote)?;
+    Some(encoding.to_ascii_lowercase())
+}
+
+/// Parse a document, usually a GL_MarketDocument, quoting the start of the response and
+/// the request on failure
+fn parse_document<T: DeserializeOwned>(xml: &str, request: &Request) -> Result<T, EntsoeError> {
+    deserialize_document(xml, request.to_string())
+}
+
+/// Parse a stored response body, e.g. a test fixture, the way the client parses a
+/// fetched one: decode it, reject acknowledgements and deserialize the document
+pub fn parse_response<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, EntsoeError> {
+    let xml = response_body(TransportResponse {
+        status: 200,
+        body: decode_body(bytes)?,
+        retry_after: None,
+    })?;
+    deserialize_document(&xml, "stored response".to_string())
+}
+
+/// `source` names where `xml` came from in errors
+fn deserialize_document<T: DeserializeOwned>(xml: &str, source: String) -> Result<T, EntsoeError> {
+    let xml = xml.strip_prefix(BYTE_ORDER_MARK).unwrap_or(xml);
+    quick_xml::de::from_str(xml).map_err(|e| EntsoeError::DocumentParsing {
+        url: source,
+        excerpt: xml.chars().take(PARSE_ERROR_EXCERPT).collect(),
+        source: Box::new(e),
+    })
+}
+
+fn split_documents<'a>(xml: &'a str, root: &str) -> Vec<&'a str> {
+    let mut documents = Vec::new();
+    let mut rest = xml;
+    while let Some(end) = closing_tag_end(rest, root) {
+        let (document, remainder) = rest.split_at(end);
+        documents.push(document.trim_start());
+        rest = remainder;
+    }
+    documents
+}
+
+/// Longest period a single request may cover, per `documentType`
+fn max_request_span(document_type: &str) -> Duration {
+    match document_type {
+        // Load and generation forecasts are capped at one year
+        "A65" | "A69" | "A71" => Duration::days(365),
+        // Generation per unit is served a day at a time
+        "A73" => Duration::days(1),
+        // Stay within the shorter limits of other endpoints
+        _ => Duration::days(31),
+    }
+}
+
+/// Split `[start, end)` into consecutive intervals no longer than `max_span`
+fn split_period(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    max_span: Duration,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut chunks = Vec::new();
+    let mut chunk_start = start;
+    loop {
+        let chunk_end = (chunk_start + max_span).min(end);
+        chunks.push((chunk_start, chunk_end));
+        if chunk_end >= end {
+            return chunks;
+        }
+        chunk_start = chunk_end;
+    }
+}
+
+/// The chunks of one range fetched by [`EntsoeClient::fetch_range_chunks`], in order
+pub struct RangeFetch {
+    pub chunks: Vec<RangeChunk>,
+}
+
+/// One request of a [`RangeFetch`] and its outcome
+pub struct RangeChunk {
+    request: Request,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub document: Result<GlMarketDocument, EntsoeError>,
+}
+
+impl RangeFetch {
+    /// Number of chunks that failed
+    pub fn failed_chunks(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.document.is_err())
+            .count()
+    }
+
+    /// The chunks merged into one document like [`EntsoeClient::fetch_range`] does, or
+    /// the error of the first chunk that failed
+    pub fn into_document(self) -> Result<GlMarketDocument, EntsoeError> {
+        let documents = self
+            .chunks
+            .into_iter()
+            .map(|chunk| chunk.document)
+            .collect::<Result<Vec<_>, _>>()?;
+        concat_documents(documents)
+    }
+}
+
+/// Join the documents of consecutive requests. Points of a later document that an
+/// earlier series with the same [`SeriesKey`] already covers are dropped.
+fn concat_documents(documents: Vec<GlMarketDocument>) -> Result<GlMarketDocument, EntsoeError> {
+    let mut documents = documents.into_iter();
+    let mut merged = documents
+        .next()
+        .ok_or_else(|| EntsoeError::InvalidResponse("No documents to merge".to_string()))?;
+
+    let mut covered: HashMap<SeriesKey, DateTime<Utc>> = HashMap::new();
+    for series in &merged.time_series {
+        extend_coverage(&mut covered, series)?;
+    }
+
+    for document in documents {
+        merged.time_period_interval.end = document.time_period_interval.end;
+        merged.cache_status = merged.cache_status.max(document.cache_status);
+        for mut series in document.time_series {
+            if let Some(&until) = covered.get(&series.key()) {
+                series.period.trim_before(until)?;
+                if series.period.points.is_empty() {
+                    continue;
+                }
+            }
+            extend_coverage(&mut covered, &series)?;
+            merged.time_series.push(series);
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Whether two copies of a document were issued as the same revision
+fn same_revision(a: &GlMarketDocument, b: &GlMarketDocument) -> bool {
+    a.created_date_time == b.created_date_time && a.revision_number == b.revision_number
+}
+
+fn extend_coverage(
+    covered: &mut HashMap<SeriesKey, DateTime<Utc>>,
+    series: &TimeSeries,
+) -> Result<(), EntsoeError> {
+    let end = parse_timestamp(&series.period.time_interval.end)?;
+    let until = covered.entry(series.key()).or_insert(end);
+    *until = (*until).max(end);
+    Ok(())
+}
+
+/// Parse a `periodStart`/`periodEnd` query value (`yyyyMMddHHmm`, UTC)
+fn parse_period(value: &str) -> Result<DateTime<Utc>, EntsoeError> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M")
+        .map(|dt| dt.and_utc())
+        .map_err(|_| EntsoeError::InvalidTimestamp(value.to_string()))
+}
+
+fn format_period(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%Y%m%d%H%M").to_string()
+}
+
+fn parse_resolution(resolution: &str) -> Result<Duration, EntsoeError> {
+    // Week-ahead forecasts come in days: P[n]D
+    if let Some(days) = resolution
+        .strip_prefix('P')
+        .and_then(|days| days.strip_suffix('D'))
+    {
+        return days
+            .parse()
+            .ok()
+            .filter(|days| *days > 0)
+            .map(Duration::days)
+            .ok_or_else(|| EntsoeError::InvalidResolution(resolution.to_string()));
+    }
+
+    // Format: PT[n]M where n is minutes
+    if !resolution.starts_with("PT") || !resolution.ends_with("M") {
+        return Err(EntsoeError::InvalidResolution(resolution.to_string()));
+    }
+
+    let minutes_str = &resolution[2..resolution.len() - 1];
+    let minutes: i64 = minutes_str
+        .parse()
+        .map_err(|_| EntsoeError::InvalidResolution(resolution.to_string()))?;
+
+    Ok(Duration::minutes(minutes))
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>, EntsoeError> {
+    let normalized = if timestamp.len() == 17 && timestamp.ends_with('Z') {
+        let mut s = timestamp.to_string();
+        s.insert_str(16, ":00"); // add seconds
+        s
+    } else {
+        timestamp.to_string()
+    };
+
+    DateTime::parse_from_rfc3339(&normalized)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| {
+            eprintln!("Failed to parse timestamp: {}", e);
+            EntsoeError::InvalidTimestamp(timestamp.to_string())
+        })
+}
+
+/// 64-bit FNV-1a of `bytes`. Unlike `DefaultHasher` it is specified, so it stays the same
+/// across releases and may name what outlives the process.
+pub(crate) fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Places the points of one period: the timestamp of each position and whether a position
+/// was already seen. Shared by [`Period::timestamped_points`] and the streaming
+/// [`stream::PointReader`], so both reject the same positions.
+struct PeriodGrid {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    resolution: Duration,
+    /// One bit per position within the period
+    seen: Vec<u64>,
+}
+
+impl PeriodGrid {
+    fn new(start: DateTime<Utc>, end: DateTime<Utc>, resolution: Duration) -> Self {
+        Self {
+            start,
+            end,
+            resolution,
+            seen: Vec::new(),
+        }
+    }
+
+    /// Start of the interval at `position`, counted from 1. A position whose interval does
+    /// not lie within the period is an [`EntsoeError::InvalidResponse`].
+    fn timestamp(&self, position: u32) -> Result<DateTime<Utc>, EntsoeError> {
+        i64::from(position)
+            .checked_sub(1)
+            .filter(|offset| *offset >= 0)
+            .and_then(|offset| self.resolution.num_seconds().checked_mul(offset))
+            .and_then(Duration::try_seconds)
+            .and_then(|offset| self.start.checked_add_signed(offset))
+            .filter(|timestamp| *timestamp + self.resolution <= self.end)
+            .ok_or_else(|| {
+                EntsoeError::InvalidResponse(format!(
+                    "Point position {} lies outside the period {}/{} at a resolution of {} minutes",
+                    position,
+                    self.start.format("%Y-%m-%dT%H:%MZ"),
+                    self.end.format("%Y-%m-%dT%H:%MZ"),
+                    self.resolution.num_minutes()
+                ))
+            })
+    }
+
+    /// Marks `position` as seen, returning whether it was new. Call it only with positions
+    /// [`PeriodGrid::timestamp`] accepted, which bounds the set by the period length.
+    fn first_occurrence(&mut self, position: u32) -> bool {
+        let (word, bit) = (position as usize / 64, position % 64);
+        if self.seen.len() <= word {
+            self.seen.resize(word + 1, 0);
+        }
+        let new = self.seen[word] & (1 << bit) == 0;
+        self.seen[word] |= 1 << bit;
+        new
+    }
+}
+
+impl Period {
+    /// Start and end of the period
+    pub fn bounds(&self) -> Result<(DateTime<Utc>, DateTime<Utc>), EntsoeError> {
+        Ok((
+            parse_timestamp(&self.time_interval.start)?,
+            parse_timestamp(&self.time_interval.end)?,
+        ))
+    }
+
+    /// Drop the points before `until`, moving the start of the period forward
+    pub fn trim_before(&mut self, until: DateTime<Utc>) -> Result<(), EntsoeError> {
+        let start = parse_timestamp(&self.time_interval.start)?;
+        if until <= start {
+            return Ok(());
+        }
+        let resolution = parse_resolution(&self.resolution)?;
+        let resolution_seconds = resolution.num_seconds();
+        if resolution_seconds <= 0 {
+            return Err(EntsoeError::InvalidResolution(self.resolution.clone()));
+        }
+
+        // Number of whole intervals before `until`, rounded up
+        let skipped = ((until - start).num_seconds() + resolution_seconds - 1) / resolution_seconds;
+        let new_start = start + resolution * skipped as i32;
+
+        self.points
+            .retain(|point| i64::from(point.position) > skipped);
+        for point in &mut self.points {
+            point.position -= skipped as u32;
+        }
+        self.time_interval.start = new_start.format("%Y-%m-%dT%H:%MZ").to_string();
+
+        Ok(())
+    }
+
+    /// Get all points with their actual timestamps based on resolution, by position.
+    /// Of a repeated position the last point is kept; a position whose interval does not
+    /// lie within the period is an [`EntsoeError::InvalidResponse`]. Points upstream sent
+    /// as NaN or infinity are left out, see [`Period::non_finite_points`].
+    pub fn timestamped_points(
+        &self,
+        unit: MeasureUnit,
+    ) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        let mut points = self.positioned_points(unit)?;
+        points.retain(|point| point.quantity.is_finite());
+        Ok(points)
+    }
+
+    /// Number of points whose quantity is NaN or infinite
+    pub fn non_finite_points(&self) -> usize {
+        self.points
+            .iter()
+            .filter(|point| !point.quantity.is_finite())
+            .count()
+    }
+
+    fn positioned_points(&self, unit: MeasureUnit) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        let (start_time, end_time) = self.bounds()?;
+        let resolution_duration = parse_resolution(&self.resolution)?;
+        let mut grid = PeriodGrid::new(start_time, end_time, resolution_duration);
+
+        // Walk backwards so the last point of a repeated position is the one kept
+        let mut points = Vec::with_capacity(self.points.len());
+        for point in self.points.iter().rev() {
+            let timestamp = grid.timestamp(point.position)?;
+            if grid.first_occurrence(point.position) {
+                points.push(TimestampedPoint {
+                    timestamp,
+                    position: point.position,
+                    quantity: point.quantity,
+                    unit,
+                    duration: resolution_duration,
+                });
+            }
+        }
+        points.sort_by_key(|point| point.position);
+        Ok(points)
+    }
+}
+
+// Helper functions to work with the data
+impl GlMarketDocument {
+    /// Number of points of the series matching `filter` upstream sent as NaN or
+    /// infinity. The sums of [`GlMarketDocument::timestamped_points_where`] leave out
+    /// their intervals.
+    pub fn non_finite_points(&self, filter: &SeriesFilter) -> usize {
+        self.series_where(filter)
+            .iter()
+            .map(|series| series.period.non_finite_points())
+            .sum()
+    }
+
+    /// Time series matching `filter`, in document order
+    pub fn series_where(&self, filter: &SeriesFilter) -> Vec<&TimeSeries> {
+        self.time_series
+            .iter()
+            .filter(|series| filter.matches(series))
+            .collect()
+    }
+
+    /// The only time series matching `filter`; errors when none or several match
+    pub fn single_series(&self, filter: &SeriesFilter) -> Result<&TimeSeries, EntsoeError> {
+        match self.series_where(filter).as_slice() {
+            [] => Err(EntsoeError::NoMatchingSeries(filter.to_string())),
+            [series] => Ok(series),
+            several => Err(EntsoeError::AmbiguousSeries {
+                filter: filter.to_string(),
+                matches: several
+                    .iter()
+                    .map(|series| series.key().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            }),
+        }
+    }
+
+    /// Get all timestamped points across all time series
+    pub fn all_timestamped_points(&self) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        self.timestamped_points_where(&SeriesFilter::new())
+    }
+
+    /// Sum the time series matching `filter` point by point; all of them must share a unit.
+    /// Duplicated series are resolved with [`AggregationPolicy::PreferLatest`].
+    ///
+    /// When the series differ in resolution the sum is taken at the finest one: coarser
+    /// points are split into intervals of that length first, keeping their power or
+    /// sharing out their energy.
+    pub fn timestamped_points_where(
+        &self,
+        filter: &SeriesFilter,
+    ) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        self.timestamped_points_with(filter, AggregationPolicy::default())
+    }
+
+    /// Like [`GlMarketDocument::timestamped_points_where`], resolving series with the
+    /// same key that overlap in time according to `policy`
+    pub fn timestamped_points_with(
+        &self,
+        filter: &SeriesFilter,
+        policy: AggregationPolicy,
+    ) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        let mut unit: Option<MeasureUnit> = None;
+        let mut kept: Vec<(&TimeSeries, Vec<TimestampedPoint>)> = Vec::new();
+
+        for series in self.series_where(filter) {
+            let series_unit = series.measure_unit()?;
+            unit.unwrap_or(series_unit).ensure_compatible(series_unit)?;
+            unit = Some(series_unit);
+
+            let key = series.key();
+            let (start, end) = series.period.bounds()?;
+            for (earlier, points) in &mut kept {
+                let (earlier_start, earlier_end) = earlier.period.bounds()?;
+                if earlier.key() != key || earlier_end <= start || end <= earlier_start {
+                    continue;
+                }
+                match policy {
+                    AggregationPolicy::PreferLatest => {
+                        points.retain(|point| point.end() <= start || point.timestamp >= end)
+                    }
+                    AggregationPolicy::Error => {
+                        return Err(EntsoeError::AmbiguousSeries {
+                            filter: key.to_string(),
+                            matches: format!("mRID {}, mRID {}", earlier.mrid, series.mrid),
+                        });
+                    }
+                }
+            }
+            // Non-finite quantities are kept until summed, so they void the sum instead of
+            // leaving out one series of it
+            kept.push((series, series.all_positioned_points()?));
+        }
+        let all_points: Vec<Vec<TimestampedPoint>> =
+            kept.into_iter().map(|(_, points)| points).collect();
+
+        let finest = all_points
+            .iter()
+            .flatten()
+            .map(|point| point.duration)
+            .min()
+            .unwrap_or_else(|| Duration::hours(1));
+
+        // Aggregate all points by timestamp
+        let mut timestamp_map: HashMap<DateTime<Utc>, f64> = HashMap::new();
+        for point in all_points.iter().flatten() {
+            let parts = if point.duration == finest {
+                vec![point.clone()]
+            } else {
+                point.split(finest)?
+            };
+            for part in parts {
+                *timestamp_map.entry(part.timestamp).or_insert(0.0) += part.quantity;
+            }
+        }
+
+        // Convert back to Vec and sort by timestamp
+        let mut result: Vec<TimestampedPoint> = timestamp_map
+            .into_iter()
+            .filter(|(_, quantity)| quantity.is_finite())
+            .map(|(timestamp, quantity)| TimestampedPoint {
+                timestamp,
+                position: 0, // Position doesn't make sense for aggregated data
+                quantity,
+                unit: unit.unwrap_or_default(),
+                duration: finest,
+            })
+            .collect();
+
+        result.sort_by_key(|p| p.timestamp);
+
+        // Reassign positions based on sorted order
+        for (i, point) in result.iter_mut().enumerate() {
+            point.position = (i + 1) as u32;
+        }
+
+        Ok(result)
+    }
+
+    /// Get all points with their timestamps as ISO strings
+    pub fn all_points_with_time(&self) -> Result<Vec<(String, u32, f64)>, EntsoeError> {
+        let timestamped = self.all_timestamped_points()?;
+
+        Ok(timestamped
+            .into_iter()
+            .map(|tp| (tp.timestamp.to_rfc3339(), tp.position, tp.quantity))
+            .collect())
+    }
+
+    /// Get all points flattened (timestamp, quantity)
+    pub fn all_points(&self) -> Result<Vec<(DateTime<Utc>, f64)>, EntsoeError> {
+        let timestamped = self.all_timestamped_points()?;
+
+        Ok(timestamped
+            .into_iter()
+            .map(|tp| (tp.timestamp, tp.quantity))
+            .collect())
+    }
+
+    /// Get total forecast across all points
     pub fn total_forecast(&self) -> f64 {
         self.time_series
             .iter()
@@ -319,23 +2229,18 @@ impl GlMarketDocument {
     ) -> Result<Option<(TimestampedPoint, TimestampedPoint)>, EntsoeError> {
         let points = self.all_timestamped_points()?;
 
-        if points.is_empty() {
+        let (Some(min_point), Some(max_point)) = (
+            points
+                .iter()
+                .min_by(|a, b| a.quantity.total_cmp(&b.quantity)),
+            points
+                .iter()
+                .max_by(|a, b| a.quantity.total_cmp(&b.quantity)),
+        ) else {
             return Ok(None);
-        }
+        };
 
-        let min_point = points
-            .iter()
-            .min_by(|a, b| a.quantity.partial_cmp(&b.quantity).unwrap())
-            .unwrap()
-            .clone();
-
-        let max_point = points
-            .iter()
-            .max_by(|a, b| a.quantity.partial_cmp(&b.quantity).unwrap())
-            .unwrap()
-            .clone();
-
-        Ok(Some((min_point, max_point)))
+        Ok(Some((min_point.clone(), max_point.clone())))
     }
 
     /// Get min and max values
@@ -359,96 +2264,1335 @@ impl GlMarketDocument {
 
 #[cfg(test)]
 mod tests {
+    use super::testing::{
+        DEFAULT_ZONE, MockSeries, MockTransport, XorShift, document_without_series,
+        empty_period_document, gl_document, gl_document_series, ok, query_param,
+    };
     use super::*;
     use chrono::{Datelike, Timelike};
 
     #[test]
-    fn test_parse_resolution() {
-        assert_eq!(parse_resolution("PT15M").unwrap(), Duration::minutes(15));
-        assert_eq!(parse_resolution("PT30M").unwrap(), Duration::minutes(30));
-        assert_eq!(parse_resolution("PT60M").unwrap(), Duration::minutes(60));
-        assert!(parse_resolution("invalid").is_err());
+    fn test_parse_resolution() {
+        assert_eq!(parse_resolution("PT15M").unwrap(), Duration::minutes(15));
+        assert_eq!(parse_resolution("PT30M").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_resolution("P1D").unwrap(), Duration::days(1));
+        assert!(parse_resolution("P0D").is_err());
+        assert!(parse_resolution("PxD").is_err());
+        assert_eq!(parse_resolution("PT60M").unwrap(), Duration::minutes(60));
+        assert!(parse_resolution("invalid").is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let ts = parse_timestamp("2023-08-14T22:00Z").unwrap();
+        assert_eq!(ts.year(), 2023);
+        assert_eq!(ts.month(), 8);
+        assert_eq!(ts.day(), 14);
+        assert_eq!(ts.hour(), 22);
+    }
+
+    #[tokio::test]
+    async fn test_timestamped_points() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<GL_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-6:generationloaddocument:3:0">
+    <mRID>test123</mRID>
+    <revisionNumber>1</revisionNumber>
+    <type>A65</type>
+    <process.processType>A01</process.processType>
+    <sender_MarketParticipant.mRID codingScheme="A01">10X1001A1001A450</sender_MarketParticipant.mRID>
+    <sender_MarketParticipant.marketRole.type>A32</sender_MarketParticipant.marketRole.type>
+    <receiver_MarketParticipant.mRID codingScheme="A01">10X1001A1001A450</receiver_MarketParticipant.mRID>
+    <receiver_MarketParticipant.marketRole.type>A33</receiver_MarketParticipant.marketRole.type>
+    <createdDateTime>2026-01-07T19:26:41Z</createdDateTime>
+    <time_Period.timeInterval>
+        <start>2023-08-14T00:00Z</start>
+        <end>2023-08-17T00:00Z</end>
+    </time_Period.timeInterval>
+    <TimeSeries>
+        <mRID>1</mRID>
+        <businessType>A04</businessType>
+        <objectAggregation>A01</objectAggregation>
+        <outBiddingZone_Domain.mRID codingScheme="A01">10YCZ-CEPS-----N</outBiddingZone_Domain.mRID>
+        <quantity_Measure_Unit.name>MAW</quantity_Measure_Unit.name>
+        <curveType>A03</curveType>
+        <Period>
+            <timeInterval>
+                <start>2023-08-14T00:00Z</start>
+                <end>2023-08-17T00:00Z</end>
+            </timeInterval>
+            <resolution>PT60M</resolution>
+            <Point>
+                <position>1</position>
+                <quantity>4933</quantity>
+            </Point>
+            <Point>
+                <position>2</position>
+                <quantity>4832</quantity>
+            </Point>
+            <Point>
+                <position>3</position>
+                <quantity>4911</quantity>
+            </Point>
+        </Period>
+    </TimeSeries>
+</GL_MarketDocument>"#;
+
+        let doc: GlMarketDocument = quick_xml::de::from_str(xml).unwrap();
+        let points = doc.all_timestamped_points().unwrap();
+
+        assert_eq!(points.len(), 3);
+
+        // First point at 2023-08-14T00:00Z
+        assert_eq!(points[0].position, 1);
+        assert_eq!(
+            points[0].timestamp.to_rfc3339(),
+            "2023-08-14T00:00:00+00:00"
+        );
+
+        // Second point at 2023-08-14T01:00Z (60 minutes later)
+        assert_eq!(points[1].position, 2);
+        assert_eq!(
+            points[1].timestamp.to_rfc3339(),
+            "2023-08-14T01:00:00+00:00"
+        );
+
+        // Third point at 2023-08-14T02:00Z (120 minutes after start)
+        assert_eq!(points[2].position, 3);
+        assert_eq!(
+            points[2].timestamp.to_rfc3339(),
+            "2023-08-14T02:00:00+00:00"
+        );
+        assert_eq!(points[2].duration, Duration::hours(1));
+        assert_eq!(points[2].end().to_rfc3339(), "2023-08-14T03:00:00+00:00");
+    }
+
+    fn period(resolution: &str, positions: &[u32]) -> Period {
+        Period {
+            time_interval: TimeInterval {
+                start: "2024-06-01T00:00Z".to_string(),
+                end: "2024-06-02T00:00Z".to_string(),
+            },
+            resolution: resolution.to_string(),
+            points: positions
+                .iter()
+                .map(|&position| Point {
+                    position,
+                    quantity: position as f64,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_timestamped_points_reject_positions_outside_the_period() {
+        for position in [0, 25, i32::MAX as u32 + 2, u32::MAX] {
+            let error = period("PT60M", &[1, position])
+                .timestamped_points(MeasureUnit::Megawatt)
+                .unwrap_err();
+            assert!(
+                matches!(&error, EntsoeError::InvalidResponse(message)
+                    if message.contains(&format!("position {} ", position))),
+                "position {}: {}",
+                position,
+                error
+            );
+        }
+
+        // The last interval ends with the period
+        let points = period("PT15M", &[96])
+            .timestamped_points(MeasureUnit::Megawatt)
+            .unwrap();
+        assert_eq!(points[0].end().to_rfc3339(), "2024-06-02T00:00:00+00:00");
+
+        let series = TimeSeries {
+            mrid: "7".to_string(),
+            business_type: "A04".to_string(),
+            object_aggregation: "A01".to_string(),
+            out_bidding_zone: None,
+            in_bidding_zone: None,
+            quantity_measure_unit: "MAW".to_string(),
+            curve_type: "A01".to_string(),
+            mkt_psr_type: None,
+            resource: None,
+            period: period("PT60M", &[0]),
+        };
+        let error = series.timestamped_points().unwrap_err().to_string();
+        assert!(
+            error.contains("Time series 7: Point position 0"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_timestamped_points_deduplicate_positions() {
+        let mut period = period("PT60M", &[2, 1, 2]);
+        period.points[2].quantity = 20.0;
+
+        let points = period.timestamped_points(MeasureUnit::Megawatt).unwrap();
+        let positions: Vec<(u32, f64)> = points.iter().map(|p| (p.position, p.quantity)).collect();
+        // Sorted by position, a repeated one keeps the later point
+        assert_eq!(positions, [(1, 1.0), (2, 20.0)]);
+    }
+
+    #[test]
+    fn test_timestamped_points_random_positions() {
+        let mut random = XorShift::new(0x9e37_79b9_7f4a_7c15);
+        let mut next = || random.next_u64();
+
+        for _ in 0..500 {
+            let resolution = ["PT15M", "PT30M", "PT60M"][(next() % 3) as usize];
+            let positions: Vec<u32> = (0..next() % 8)
+                .map(|_| match next() % 4 {
+                    0 => next() as u32,
+                    _ => (next() % 100) as u32,
+                })
+                .collect();
+            let period = period(resolution, &positions);
+            let (start, end) = period.bounds().unwrap();
+
+            match period.timestamped_points(MeasureUnit::Megawatt) {
+                Ok(points) => {
+                    assert!(positions.iter().all(|&p| p >= 1));
+                    for point in points {
+                        assert!(point.timestamp >= start && point.end() <= end);
+                    }
+                }
+                Err(EntsoeError::InvalidResponse(_)) => {
+                    let last = (end - start).num_seconds()
+                        / parse_resolution(resolution).unwrap().num_seconds();
+                    assert!(positions.iter().any(|&p| p == 0 || i64::from(p) > last));
+                }
+                Err(other) => panic!("unexpected error {}", other),
+            }
+        }
+    }
+    /// Wind and solar forecast of Germany with one series per production type,
+    /// plus a series of another zone
+    fn multi_series_document() -> GlMarketDocument {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let germany = "10Y1001A1001A83F";
+        let series = [
+            ("B16", germany, [100.0]),
+            ("B18", germany, [200.0]),
+            ("B19", germany, [300.0]),
+            ("B16", "10YFR-RTE------C", [1_000.0]),
+        ];
+        let series: Vec<MockSeries> = series
+            .iter()
+            .map(|(psr_type, zone, quantities)| MockSeries {
+                psr_type: Some(psr_type),
+                ..MockSeries::new("A69", zone, quantities)
+            })
+            .collect();
+
+        quick_xml::de::from_str(&gl_document_series(
+            "A69",
+            "2024-06-01T12:00:00Z",
+            start,
+            60,
+            &series,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_power_units() {
+        assert_eq!(PowerUnit::default().from_mw(45_123.4), 45_123.4);
+        assert_eq!(PowerUnit::Kilowatt.from_mw(1.5), 1_500.0);
+        assert_eq!(PowerUnit::Gigawatt.from_mw(45_123.4), 45.1);
+        assert_eq!(PowerUnit::Gigawatt.from_mw(-987.6), -0.988);
+        assert_eq!(PowerUnit::Gigawatt.from_mw(4.56), 0.00456);
+        assert_eq!(PowerUnit::Gigawatt.from_mw(1_234_567.0), 1_230.0);
+        assert_eq!(PowerUnit::Gigawatt.from_mw(0.0), 0.0);
+        assert!(PowerUnit::Gigawatt.from_mw(f64::NAN).is_nan());
+
+        let unit: PowerUnit = serde_json::from_str(r#""gw""#).unwrap();
+        assert_eq!(unit, PowerUnit::Gigawatt);
+        assert_eq!(
+            serde_json::to_string(&PowerUnit::Kilowatt).unwrap(),
+            r#""kW""#
+        );
+        assert_eq!(PowerUnit::Megawatt.to_string(), "MW");
+    }
+
+    #[test]
+    fn test_non_finite_quantities_are_dropped() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let (wind, solar) = ([100.0, f64::NAN, 300.0], [10.0, 20.0, f64::INFINITY]);
+        let series = [
+            MockSeries {
+                psr_type: Some("B19"),
+                ..MockSeries::new("A69", DEFAULT_ZONE, &wind)
+            },
+            MockSeries {
+                psr_type: Some("B16"),
+                ..MockSeries::new("A69", DEFAULT_ZONE, &solar)
+            },
+        ];
+        let doc: GlMarketDocument = quick_xml::de::from_str(&gl_document_series(
+            "A69",
+            "2024-06-01T12:00:00Z",
+            start,
+            60,
+            &series,
+        ))
+        .unwrap();
+
+        let wind = doc.time_series[0].timestamped_points().unwrap();
+        assert_eq!(wind.len(), 2);
+        assert_eq!(doc.non_finite_points(&SeriesFilter::new()), 2);
+        assert_eq!(
+            doc.non_finite_points(&SeriesFilter::new().psr_type("B19")),
+            1
+        );
+
+        // A sum missing one of its series is left out rather than understated
+        let sum = doc.timestamped_points_where(&SeriesFilter::new()).unwrap();
+        assert_eq!(sum.len(), 1);
+        assert_eq!(sum[0].quantity, 110.0);
+        let (min, max) = doc.min_max_with_time().unwrap().unwrap();
+        assert_eq!((min.quantity, max.quantity), (110.0, 110.0));
+    }
+
+    #[test]
+    fn test_series_where() {
+        let doc = multi_series_document();
+        let germany = SeriesFilter::new().in_bidding_zone("10Y1001A1001A83F");
+
+        assert_eq!(doc.series_where(&SeriesFilter::new()).len(), 4);
+        assert_eq!(doc.series_where(&germany).len(), 3);
+        assert_eq!(
+            doc.series_where(&SeriesFilter::new().psr_type("B16")).len(),
+            2
+        );
+        // Criteria are combined
+        let solar = germany.clone().psr_type("B16").curve_type("A01");
+        assert_eq!(doc.series_where(&solar).len(), 1);
+        // Generation never matches on the out side
+        let out = SeriesFilter::new().out_bidding_zone("10Y1001A1001A83F");
+        assert!(doc.series_where(&out).is_empty());
+
+        let points = doc.timestamped_points_where(&germany).unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].quantity, 600.0);
+    }
+
+    #[tokio::test]
+    async fn test_documents_without_points_are_no_data() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let empty_period: GlMarketDocument =
+            quick_xml::de::from_str(&empty_period_document("A65", start)).unwrap();
+        assert_eq!(empty_period.time_series.len(), 1);
+        assert!(empty_period.time_series[0].period.points.is_empty());
+        let without_series: GlMarketDocument =
+            quick_xml::de::from_str(&document_without_series("A65", start)).unwrap();
+        assert!(without_series.time_series.is_empty());
+
+        for document in [empty_period, without_series] {
+            let points = document.all_timestamped_points().unwrap();
+            assert!(points.is_empty());
+        }
+
+        for fixture in [empty_period_document, document_without_series] {
+            let transport = MockTransport::new(move |url| {
+                ok(fixture(&query_param(url, "documentType").unwrap(), start))
+            });
+            let client = EntsoeClient::with_transport("test-token", Arc::new(transport));
+            let result = client
+                .get_surplus_series(
+                    DEFAULT_ZONE,
+                    "202406010000",
+                    "202406020000",
+                    analysis::Freshness::DayAhead,
+                )
+                .await;
+            assert!(
+                matches!(result, Err(EntsoeError::NoData(_))),
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_single_series() {
+        let doc = multi_series_document();
+        let germany = SeriesFilter::new().in_bidding_zone("10Y1001A1001A83F");
+
+        let wind = doc.single_series(&germany.clone().psr_type("B19")).unwrap();
+        assert_eq!(wind.key().psr_type.as_deref(), Some("B19"));
+        assert_eq!(wind.period.points[0].quantity, 300.0);
+
+        match doc.single_series(&germany.clone().psr_type("B20")) {
+            Err(EntsoeError::NoMatchingSeries(filter)) => {
+                assert_eq!(filter, "psrType=B20 in=10Y1001A1001A83F")
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        match doc.single_series(&germany) {
+            Err(error @ EntsoeError::AmbiguousSeries { .. }) => {
+                let message = error.to_string();
+                assert!(message.starts_with("Several time series match in=10Y1001A1001A83F"));
+                assert!(message.contains("psrType=B18"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_measure_unit_conversion() {
+        assert_eq!(
+            MeasureUnit::from_code("MAW").unwrap(),
+            MeasureUnit::Megawatt
+        );
+        assert_eq!(
+            MeasureUnit::from_code("MWH").unwrap(),
+            MeasureUnit::MegawattHour
+        );
+        assert!(matches!(
+            MeasureUnit::from_code("KWT"),
+            Err(EntsoeError::UnknownUnit(code)) if code == "KWT"
+        ));
+
+        // 250 MWh within 15 minutes is an average of 1 GW
+        let energy = TimestampedPoint {
+            timestamp: Utc::now(),
+            position: 1,
+            quantity: 250.0,
+            unit: MeasureUnit::MegawattHour,
+            duration: Duration::minutes(15),
+        };
+        let power = energy.in_unit(MeasureUnit::Megawatt);
+        assert_eq!(power.unit, MeasureUnit::Megawatt);
+        assert_eq!(power.quantity, 1_000.0);
+        assert_eq!(power.end(), energy.timestamp + Duration::minutes(15));
+        assert_eq!(power.in_unit(MeasureUnit::MegawattHour).quantity, 250.0);
+        assert_eq!(power.in_unit(MeasureUnit::Megawatt).quantity, 1_000.0);
     }
 
     #[test]
-    fn test_parse_timestamp() {
-        let ts = parse_timestamp("2023-08-14T22:00Z").unwrap();
-        assert_eq!(ts.year(), 2023);
-        assert_eq!(ts.month(), 8);
-        assert_eq!(ts.day(), 14);
-        assert_eq!(ts.hour(), 22);
+    fn test_aggregating_mixed_resolutions() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        // Hourly wind next to solar in another resolution
+        let document = |resolution_minutes, unit, quantities: &[f64]| -> GlMarketDocument {
+            let series = MockSeries {
+                unit,
+                psr_type: Some(if resolution_minutes == 60 {
+                    "B19"
+                } else {
+                    "B16"
+                }),
+                ..MockSeries::new("A69", DEFAULT_ZONE, quantities)
+            };
+            quick_xml::de::from_str(&gl_document_series(
+                "A69",
+                "2024-06-01T12:00:00Z",
+                start,
+                resolution_minutes,
+                &[series],
+            ))
+            .unwrap()
+        };
+
+        // Hourly power is held over each quarter hour
+        let mut doc = document(15, "MAW", &[10.0, 20.0, 30.0, 40.0]);
+        doc.time_series
+            .extend(document(60, "MAW", &[100.0]).time_series);
+        let points = doc.all_timestamped_points().unwrap();
+        let quantities: Vec<f64> = points.iter().map(|point| point.quantity).collect();
+        assert_eq!(quantities, [110.0, 120.0, 130.0, 140.0]);
+        assert!(
+            points
+                .iter()
+                .all(|point| point.duration == Duration::minutes(15))
+        );
+        assert_eq!(points[3].end(), start + Duration::hours(1));
+
+        // Hourly energy is shared out over the quarter hours
+        let mut doc = document(15, "MWH", &[1.0, 2.0, 3.0, 4.0]);
+        doc.time_series
+            .extend(document(60, "MWH", &[100.0]).time_series);
+        let quantities: Vec<f64> = doc
+            .all_timestamped_points()
+            .unwrap()
+            .iter()
+            .map(|point| point.quantity)
+            .collect();
+        assert_eq!(quantities, [26.0, 27.0, 28.0, 29.0]);
+
+        // Resolutions that don't divide each other can't be aligned
+        let mut doc = document(40, "MAW", &[1.0]);
+        doc.time_series
+            .extend(document(60, "MAW", &[1.0]).time_series);
+        assert!(matches!(
+            doc.all_timestamped_points(),
+            Err(EntsoeError::InvalidResolution(_))
+        ));
     }
 
-    #[tokio::test]
-    async fn test_timestamped_points() {
-        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+    /// A load forecast revised after publication, as served with the original series
+    /// still attached: both share the key, the revision overlaps the last two hours
+    const REVISED_LOAD_FORECAST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
 <GL_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-6:generationloaddocument:3:0">
-    <mRID>test123</mRID>
-    <revisionNumber>1</revisionNumber>
+    <mRID>c3c5b6a0e8e84c1daa4f4a6f9d2b1f01</mRID>
+    <revisionNumber>2</revisionNumber>
     <type>A65</type>
     <process.processType>A01</process.processType>
     <sender_MarketParticipant.mRID codingScheme="A01">10X1001A1001A450</sender_MarketParticipant.mRID>
     <sender_MarketParticipant.marketRole.type>A32</sender_MarketParticipant.marketRole.type>
     <receiver_MarketParticipant.mRID codingScheme="A01">10X1001A1001A450</receiver_MarketParticipant.mRID>
     <receiver_MarketParticipant.marketRole.type>A33</receiver_MarketParticipant.marketRole.type>
-    <createdDateTime>2026-01-07T19:26:41Z</createdDateTime>
+    <createdDateTime>2024-06-01T09:41:07Z</createdDateTime>
     <time_Period.timeInterval>
-        <start>2023-08-14T00:00Z</start>
-        <end>2023-08-17T00:00Z</end>
+        <start>2024-06-01T00:00Z</start>
+        <end>2024-06-01T04:00Z</end>
     </time_Period.timeInterval>
     <TimeSeries>
         <mRID>1</mRID>
         <businessType>A04</businessType>
         <objectAggregation>A01</objectAggregation>
-        <outBiddingZone_Domain.mRID codingScheme="A01">10YCZ-CEPS-----N</outBiddingZone_Domain.mRID>
+        <outBiddingZone_Domain.mRID codingScheme="A01">10Y1001A1001A83F</outBiddingZone_Domain.mRID>
         <quantity_Measure_Unit.name>MAW</quantity_Measure_Unit.name>
-        <curveType>A03</curveType>
+        <curveType>A01</curveType>
         <Period>
             <timeInterval>
-                <start>2023-08-14T00:00Z</start>
-                <end>2023-08-17T00:00Z</end>
+                <start>2024-06-01T00:00Z</start>
+                <end>2024-06-01T04:00Z</end>
             </timeInterval>
             <resolution>PT60M</resolution>
-            <Point>
-                <position>1</position>
-                <quantity>4933</quantity>
-            </Point>
-            <Point>
-                <position>2</position>
-                <quantity>4832</quantity>
-            </Point>
-            <Point>
-                <position>3</position>
-                <quantity>4911</quantity>
-            </Point>
+            <Point><position>1</position><quantity>42100</quantity></Point>
+            <Point><position>2</position><quantity>40850</quantity></Point>
+            <Point><position>3</position><quantity>40120</quantity></Point>
+            <Point><position>4</position><quantity>40310</quantity></Point>
+        </Period>
+    </TimeSeries>
+    <TimeSeries>
+        <mRID>2</mRID>
+        <businessType>A04</businessType>
+        <objectAggregation>A01</objectAggregation>
+        <outBiddingZone_Domain.mRID codingScheme="A01">10Y1001A1001A83F</outBiddingZone_Domain.mRID>
+        <quantity_Measure_Unit.name>MAW</quantity_Measure_Unit.name>
+        <curveType>A01</curveType>
+        <Period>
+            <timeInterval>
+                <start>2024-06-01T02:00Z</start>
+                <end>2024-06-01T04:00Z</end>
+            </timeInterval>
+            <resolution>PT60M</resolution>
+            <Point><position>1</position><quantity>39870</quantity></Point>
+            <Point><position>2</position><quantity>40005</quantity></Point>
         </Period>
     </TimeSeries>
 </GL_MarketDocument>"#;
 
-        let doc: GlMarketDocument = quick_xml::de::from_str(xml).unwrap();
-        let points = doc.all_timestamped_points().unwrap();
+    #[test]
+    fn test_duplicate_series_are_not_summed() {
+        let doc: GlMarketDocument = quick_xml::de::from_str(REVISED_LOAD_FORECAST).unwrap();
 
-        assert_eq!(points.len(), 3);
+        let quantities: Vec<f64> = doc
+            .all_timestamped_points()
+            .unwrap()
+            .iter()
+            .map(|point| point.quantity)
+            .collect();
+        assert_eq!(quantities, [42_100.0, 40_850.0, 39_870.0, 40_005.0]);
 
-        // First point at 2023-08-14T00:00Z
-        assert_eq!(points[0].position, 1);
+        match doc.timestamped_points_with(&SeriesFilter::new(), AggregationPolicy::Error) {
+            Err(error @ EntsoeError::AmbiguousSeries { .. }) => assert_eq!(
+                error.to_string(),
+                "Several time series match businessType=A04 out=10Y1001A1001A83F \
+                 curveType=A01: mRID 1, mRID 2"
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // Series of the same key that only touch are both kept
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let mut doc: GlMarketDocument =
+            quick_xml::de::from_str(&gl_document("A65", start, 60, &[1.0, 2.0])).unwrap();
+        let later = gl_document("A65", start + Duration::hours(2), 60, &[3.0]);
+        doc.time_series.extend(
+            quick_xml::de::from_str::<GlMarketDocument>(&later)
+                .unwrap()
+                .time_series,
+        );
         assert_eq!(
-            points[0].timestamp.to_rfc3339(),
-            "2023-08-14T00:00:00+00:00"
+            doc.timestamped_points_with(&SeriesFilter::new(), AggregationPolicy::Error)
+                .unwrap()
+                .len(),
+            3
         );
+    }
+
+    #[test]
+    fn test_aggregating_mixed_units_fails() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let series = [
+            MockSeries::new("A69", "10Y1001A1001A83F", &[100.0]),
+            MockSeries {
+                unit: "MWH",
+                ..MockSeries::new("A69", "10Y1001A1001A83F", &[100.0])
+            },
+        ];
+        let doc: GlMarketDocument = quick_xml::de::from_str(&gl_document_series(
+            "A69",
+            "2024-06-01T12:00:00Z",
+            start,
+            60,
+            &series,
+        ))
+        .unwrap();
 
-        // Second point at 2023-08-14T01:00Z (60 minutes later)
-        assert_eq!(points[1].position, 2);
         assert_eq!(
-            points[1].timestamp.to_rfc3339(),
-            "2023-08-14T01:00:00+00:00"
+            doc.series_where(&SeriesFilter::new())[1]
+                .measure_unit()
+                .unwrap(),
+            MeasureUnit::MegawattHour
         );
+        assert!(matches!(
+            doc.all_timestamped_points(),
+            Err(EntsoeError::UnitMismatch {
+                left: MeasureUnit::Megawatt,
+                right: MeasureUnit::MegawattHour,
+            })
+        ));
+    }
+
+    const NAMESPACE_V3: &str = "urn:iec62325.351:tc57wg16:451-6:generationloaddocument:3:0";
+
+    /// `xml` with every element in the namespace bound to `prefix`
+    fn prefixed(xml: &str, prefix: &str) -> String {
+        xml.replace("</", "\u{0}")
+            .replace('<', &format!("<{}:", prefix))
+            .replace('\u{0}', &format!("</{}:", prefix))
+            .replace(&format!("<{}:?", prefix), "<?")
+            .replace("xmlns=", &format!("xmlns:{}=", prefix))
+    }
+
+    #[test]
+    fn test_documents_of_every_schema_version_parse_alike() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let v3 = gl_document("A65", start, 60, &[1.0, 2.0, 3.0]);
+        assert!(v3.contains(NAMESPACE_V3));
+        let versioned = |version: &str| v3.replace(":3:0\"", &format!(":{}\"", version));
+
+        let expected = [1.0, 2.0, 3.0];
+        for (xml, version) in [
+            (v3.clone(), Some("3:0")),
+            (versioned("4:0"), Some("4:0")),
+            (versioned("5:0"), Some("5:0")),
+            (v3.replace(NAMESPACE_V3, "urn:example"), None),
+            // A prefix binds the namespace, the default namespace stays unset
+            (prefixed(&versioned("5:0"), "ns2"), None),
+        ] {
+            let document: GlMarketDocument = parse_response(xml.as_bytes()).unwrap();
+            assert_eq!(
+                document.schema_version().map(|v| v.to_string()).as_deref(),
+                version,
+                "{}",
+                xml
+            );
+            assert_eq!(document.doc_type, "A65");
+            assert_eq!(
+                document.time_series[0].key().out_bidding_zone.as_deref(),
+                Some(DEFAULT_ZONE)
+            );
+            let quantities: Vec<f64> = document
+                .all_timestamped_points()
+                .unwrap()
+                .iter()
+                .map(|point| point.quantity)
+                .collect();
+            assert_eq!(quantities, expected);
+        }
 
-        // Third point at 2023-08-14T02:00Z (120 minutes after start)
-        assert_eq!(points[2].position, 3);
         assert_eq!(
-            points[2].timestamp.to_rfc3339(),
-            "2023-08-14T02:00:00+00:00"
+            SchemaVersion::from_namespace(NAMESPACE_V3),
+            Some(SchemaVersion { major: 3, minor: 0 })
+        );
+        assert_eq!(
+            SchemaVersion::from_namespace("urn:example:10:1").map(|v| v.to_string()),
+            Some("10:1".to_string())
+        );
+        assert_eq!(SchemaVersion::from_namespace("urn:example:v2"), None);
+        assert!(SchemaVersion { major: 5, minor: 0 } > SchemaVersion { major: 4, minor: 9 });
+    }
+
+    #[test]
+    fn test_fields_of_newer_schemas_are_optional() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let per_unit =
+            super::testing::generation_per_unit_document(start).replace(":3:0\"", ":5:0\"");
+        let document: GlMarketDocument = parse_response(per_unit.as_bytes()).unwrap();
+        assert_eq!(
+            document.schema_version(),
+            Some(SchemaVersion { major: 5, minor: 0 })
+        );
+        assert!(
+            document
+                .time_series
+                .iter()
+                .all(|series| series.resource.is_some())
+        );
+
+        let without = gl_document("A75", start, 60, &[1.0]);
+        let document: GlMarketDocument = parse_response(without.as_bytes()).unwrap();
+        assert!(document.time_series[0].resource.is_none());
+        assert!(document.time_series[0].mkt_psr_type.is_none());
+    }
+
+    #[test]
+    fn test_prefixed_documents_are_split_and_acknowledgements_recognized() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let page: String = [0, 1]
+            .map(|hour| {
+                prefixed(
+                    &gl_document("A65", start + Duration::hours(hour), 60, &[1.0]),
+                    "ns",
+                )
+            })
+            .concat();
+        let documents = split_documents(&page, "GL_MarketDocument");
+        assert_eq!(documents.len(), 2);
+        assert!(documents[1].trim_end().ends_with("</ns:GL_MarketDocument>"));
+
+        let acknowledgement = prefixed(
+            r#"<Acknowledgement_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-1:acknowledgementdocument:7:0"><Reason><code>999</code><text>No matching data found</text></Reason></Acknowledgement_MarketDocument>"#,
+            "ack",
+        );
+        assert!(is_acknowledgement(&acknowledgement));
+        assert!(!is_acknowledgement(documents[0]));
+        let error = response_body(TransportResponse {
+            status: 200,
+            body: acknowledgement,
+            retry_after: None,
+        })
+        .unwrap_err();
+        assert!(matches!(error, EntsoeError::InvalidResponse(_)));
+    }
+
+    #[test]
+    fn test_split_period() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 1, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            split_period(start, start + Duration::days(2), Duration::days(365)),
+            [(start, start + Duration::days(2))]
+        );
+        assert_eq!(
+            split_period(start, start + Duration::days(10), Duration::days(4)),
+            [
+                (start, start + Duration::days(4)),
+                (start + Duration::days(4), start + Duration::days(8)),
+                (start + Duration::days(8), start + Duration::days(10)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_range_splits_long_periods() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 1, 1, 0, 0, 0).unwrap();
+        let end = chrono::TimeZone::with_ymd_and_hms(&Utc, 2026, 1, 1, 0, 0, 0).unwrap();
+
+        let document = client
+            .fetch_range(
+                QueryParams::new("A65")
+                    .process_type("A01")
+                    .out_bidding_zone("10Y1001A1001A83F"),
+                start,
+                end,
+            )
+            .await
+            .unwrap();
+
+        let boundaries: Vec<(String, String)> = transport
+            .requests()
+            .iter()
+            .map(|url| {
+                (
+                    query_param(url, "periodStart").unwrap(),
+                    query_param(url, "periodEnd").unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            boundaries,
+            [
+                ("202401010000".to_string(), "202412310000".to_string()),
+                ("202412310000".to_string(), "202512310000".to_string()),
+                ("202512310000".to_string(), "202601010000".to_string()),
+            ]
+        );
+
+        // The mock answers each chunk with a day of extra data, overlapping the next chunk
+        let points = document.all_timestamped_points().unwrap();
+        assert_eq!(points.first().unwrap().timestamp, start);
+        assert!(
+            points
+                .windows(2)
+                .all(|pair| pair[1].timestamp - pair[0].timestamp == Duration::hours(1))
+        );
+        assert!(points.iter().all(|p| p.quantity == 50_000.0));
+        assert_eq!(points.len(), 732 * 24);
+    }
+
+    fn multi_document_transport(documents_per_page: usize) -> Arc<MockTransport> {
+        Arc::new(MockTransport::new(move |url| {
+            let count = match query_param(url, "offset").as_deref() {
+                Some("0") => MULTI_DOCUMENT_PAGE_SIZE,
+                Some("200") => documents_per_page,
+                _ => 0,
+            };
+            if count == 0 {
+                return ok(
+                    "<Acknowledgement_MarketDocument><Reason><code>999</code><text>No matching data found</text></Reason></Acknowledgement_MarketDocument>",
+                );
+            }
+            let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+            ok((0..count)
+                .map(|i| gl_document("A65", start + Duration::hours(i as i64), 60, &[1.0]))
+                .collect::<String>())
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_fetch_multi_follows_offsets() {
+        let transport = multi_document_transport(3);
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+
+        let documents = client
+            .fetch_and_parse_multi(&client.request(QueryParams::new("A65")))
+            .await
+            .unwrap();
+
+        assert_eq!(documents.len(), MULTI_DOCUMENT_PAGE_SIZE + 3);
+        let offsets: Vec<String> = transport
+            .requests()
+            .iter()
+            .map(|url| query_param(url, "offset").unwrap())
+            .collect();
+        assert_eq!(offsets, ["0", "200"]);
+
+        // A full last page is followed by an empty one
+        let transport = multi_document_transport(0);
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let documents = client
+            .fetch_and_parse_multi(&client.request(QueryParams::new("A65")))
+            .await
+            .unwrap();
+        assert_eq!(documents.len(), MULTI_DOCUMENT_PAGE_SIZE);
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_multi_checks_every_page() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let transport = Arc::new(MockTransport::new(move |url| {
+            match query_param(url, "offset").as_deref() {
+                // A byte order mark in front of the first page
+                Some("0") => ok(format!(
+                    "{}{}",
+                    BYTE_ORDER_MARK,
+                    (0..MULTI_DOCUMENT_PAGE_SIZE)
+                        .map(|i| gl_document("A65", start + Duration::hours(i as i64), 60, &[1.0]))
+                        .collect::<String>()
+                )),
+                _ => TransportResponse {
+                    status: 503,
+                    body: "<html>Service unavailable</html>".to_string(),
+                    retry_after: None,
+                },
+            }
+        }));
+        let client = EntsoeClient::with_transport("test-token", transport);
+
+        let result = client
+            .fetch_and_parse_multi(&client.request(QueryParams::new("A65")))
+            .await;
+
+        // A failed later page is an error rather than a silently shortened result
+        assert!(
+            matches!(&result, Err(EntsoeError::InvalidResponse(message)) if message.contains("503")),
+            "{:?}",
+            result.map(|documents| documents.len())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_documents_are_revalidated_by_revision() {
+        use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+        let revision = Arc::new(AtomicU32::new(1));
+        let failing = Arc::new(AtomicBool::new(false));
+        let transport = {
+            let (revision, failing) = (revision.clone(), failing.clone());
+            MockTransport::new(move |_| {
+                if failing.load(Ordering::SeqCst) {
+                    return TransportResponse {
+                        status: 503,
+                        body: String::new(),
+                        retry_after: None,
+                    };
+                }
+                let created = format!("2024-06-01T{:02}:00:00Z", revision.load(Ordering::SeqCst));
+                let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+                ok(super::testing::gl_document_created(
+                    "A65",
+                    &created,
+                    start,
+                    60,
+                    &[1.0],
+                ))
+            })
+        };
+        // Every entry is expired immediately, so each fetch revalidates
+        let client = EntsoeClient::with_transport("test-token", Arc::new(transport))
+            .with_cache(std::time::Duration::ZERO);
+        let request = client.request(QueryParams::new("A65"));
+
+        let first = client.fetch_and_parse(&request).await.unwrap();
+        assert_eq!(first.cache_status, CacheStatus::Fresh);
+
+        let unchanged = client.fetch_and_parse(&request).await.unwrap();
+        assert_eq!(unchanged.cache_status, CacheStatus::Revalidated);
+
+        revision.store(2, Ordering::SeqCst);
+        let updated = client.fetch_and_parse(&request).await.unwrap();
+        assert_eq!(updated.cache_status, CacheStatus::Fresh);
+        assert_eq!(updated.created_date_time, "2024-06-01T02:00:00Z");
+
+        failing.store(true, Ordering::SeqCst);
+        let stale = client.fetch_and_parse(&request).await.unwrap();
+        assert_eq!(stale.cache_status, CacheStatus::Stale);
+        assert_eq!(stale.created_date_time, "2024-06-01T02:00:00Z");
+
+        let stats = client.cache_stats().unwrap();
+        assert_eq!((stats.revalidations, stats.stale_hits), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_raw_keeps_the_body_without_the_token() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        // A body echoing the token, as some acknowledgements do
+        let xml = gl_document("A65", start, 60, &[1.0, 2.0])
+            .replace("<mRID>mock</mRID>", "<mRID>test-token</mRID>");
+        let transport = Arc::new({
+            let xml = xml.clone();
+            MockTransport::new(move |_| ok(xml.clone()))
+        });
+        let client = EntsoeClient::with_transport("test-token", transport.clone())
+            .with_cache(std::time::Duration::from_secs(60));
+        let request = client.request(QueryParams::new("A65"));
+
+        let raw = client.fetch_raw(&request).await.unwrap();
+        assert_eq!(raw.document.all_timestamped_points().unwrap().len(), 2);
+        assert!(!raw.xml.contains("test-token"));
+        assert_eq!(raw.xml, xml.replace("test-token", "***"));
+
+        // Served from the cache the second time
+        let again = client.fetch_raw(&request).await.unwrap();
+        assert_eq!(again.xml, raw.xml);
+        assert_eq!(transport.requests().len(), 1);
+        let json = serde_json::to_value(&again.document).unwrap();
+        assert_eq!(json["type"], "A65");
+        assert!(!json.to_string().contains("test-token"));
+    }
+
+    #[tokio::test]
+    async fn test_hydrated_documents_are_revalidated_on_first_use() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let midnight = || chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("educk-{}-disk-cache", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let failing = Arc::new(AtomicBool::new(false));
+        let transport = Arc::new({
+            let failing = failing.clone();
+            MockTransport::new(move |_| {
+                if failing.load(Ordering::SeqCst) {
+                    return TransportResponse {
+                        status: 503,
+                        body: String::new(),
+                        retry_after: None,
+                    };
+                }
+                ok(super::testing::gl_document_created(
+                    "A65",
+                    "2024-06-01T01:00:00Z",
+                    midnight(),
+                    60,
+                    &[1.0; 24],
+                ))
+            })
+        });
+        let client = || {
+            EntsoeClient::with_transport("test-token", transport.clone())
+                .with_cache(std::time::Duration::from_secs(60))
+                .with_disk_cache(dir.clone())
+        };
+        let day = |start: Duration, hours: i64| {
+            QueryParams::total_load_forecast("10Y1001A1001A82H", ForecastSource::DayAhead).period(
+                midnight() + start,
+                midnight() + start + Duration::hours(hours),
+            )
+        };
+        let germany = day(Duration::zero(), 24);
+        let france = QueryParams::total_load_forecast("10YFR-RTE------C", ForecastSource::DayAhead)
+            .period(midnight(), midnight() + Duration::days(1));
+
+        let previous = client();
+        for params in [&germany, &france] {
+            previous
+                .fetch_and_parse(&previous.request(params.clone()))
+                .await
+                .unwrap();
+        }
+        for file in std::fs::read_dir(&dir).unwrap() {
+            let path = file.unwrap().path();
+            let stored = std::fs::read_to_string(&path).unwrap();
+            assert!(!stored.contains("test-token"), "{}", stored);
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            // Named by a hash of the request that no toolchain upgrade changes
+            let stored: serde_json::Value = serde_json::from_str(&stored).unwrap();
+            let key = stored["request"].as_str().unwrap();
+            assert_eq!(
+                path.file_name().unwrap().to_str().unwrap(),
+                format!("{:016x}.json", fnv1a(key.bytes()))
+            );
+        }
+
+        failing.store(true, Ordering::SeqCst);
+        let restarted = client();
+        assert_eq!(restarted.hydrate_cache(&["10Y1001A1001A82H"]).unwrap(), 1);
+        // Reading back asks nothing upstream
+        let requests = transport.requests().len();
+        let fetch = async |params: &QueryParams| {
+            restarted
+                .fetch_and_parse(&restarted.request(params.clone()))
+                .await
+        };
+        assert_eq!(transport.requests().len(), requests);
+
+        // A later period within the hydrated document is revalidated on first use and,
+        // with upstream failing, answered from it like the request it was fetched for
+        let later = day(Duration::minutes(15), 12);
+        for params in [&later, &germany] {
+            let stale = fetch(params).await.unwrap();
+            assert_eq!(stale.cache_status, CacheStatus::Stale);
+        }
+        assert_eq!(transport.requests().len(), requests + 2);
+        // Beyond the hydrated document, and not a prefetched zone, so not read back
+        for params in [&day(Duration::hours(12), 24), &france] {
+            assert!(fetch(params).await.is_err());
+        }
+
+        // Once upstream answers the series, its hydrated documents are cached as usual
+        failing.store(false, Ordering::SeqCst);
+        assert_eq!(
+            fetch(&later).await.unwrap().cache_status,
+            CacheStatus::Fresh
+        );
+        let requests = transport.requests().len();
+        let cached = fetch(&germany).await.unwrap();
+        assert_eq!(cached.cache_status, CacheStatus::Fresh);
+        assert_eq!(transport.requests().len(), requests);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_byte_order_mark_is_skipped() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let xml = gl_document("A65", start, 60, &[1.0, 2.0]);
+        let transport = {
+            let xml = xml.clone();
+            MockTransport::new(move |_| ok(format!("\u{feff}{}", xml)))
+        };
+        let client = EntsoeClient::with_transport("test-token", Arc::new(transport));
+
+        let document = client
+            .fetch_and_parse(&client.request(QueryParams::new("A65")))
+            .await
+            .unwrap();
+        assert_eq!(document.all_timestamped_points().unwrap().len(), 2);
+
+        let mut body = b"\xef\xbb\xbf".to_vec();
+        body.extend(xml.as_bytes());
+        assert_eq!(decode_body(&body).unwrap(), xml);
+    }
+
+    #[test]
+    fn test_latin1_and_gzip_bodies_are_decoded() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let xml = gl_document("A65", start, 60, &[1.0])
+            .replace(r#"encoding="utf-8""#, r#"encoding="ISO-8859-1""#)
+            .replace("<mRID>mock</mRID>", "<mRID>Prévision</mRID>");
+        // Latin-1 encodes é as the single byte 0xE9
+        let latin1: Vec<u8> = xml.chars().map(|c| c as u8).collect();
+        assert!(String::from_utf8(latin1.clone()).is_err());
+
+        let decoded = decode_body(&latin1).unwrap();
+        let document: GlMarketDocument = parse_document(
+            &decoded,
+            &Request::new("test-token", QueryParams::new("A65")),
+        )
+        .unwrap();
+        assert_eq!(document.mrid, "Prévision");
+
+        let mut gzipped = Vec::new();
+        {
+            use std::io::Write;
+            let mut encoder =
+                flate2::write::GzEncoder::new(&mut gzipped, flate2::Compression::default());
+            encoder.write_all(&latin1).unwrap();
+            encoder.finish().unwrap();
+        }
+        assert_eq!(decode_body(&gzipped).unwrap(), decoded);
+
+        assert!(matches!(
+            decode_body(br#"<?xml version="1.0" encoding="UTF-16"?><a/>"#),
+            Err(EntsoeError::InvalidResponse(message)) if message.contains("utf-16")
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors_quote_the_response_and_request() {
+        let body = format!("<GL_MarketDocument>{}", "x".repeat(1_000));
+        let request = Request::new("secret", QueryParams::new("A65"));
+        let error = parse_document::<GlMarketDocument>(&body, &request).unwrap_err();
+
+        let EntsoeError::DocumentParsing { url, excerpt, .. } = &error else {
+            panic!("unexpected error {:?}", error);
+        };
+        assert_eq!(
+            url,
+            &format!("{}?securityToken=***&documentType=A65", BASE_URL)
+        );
+        assert_eq!(excerpt.chars().count(), PARSE_ERROR_EXCERPT);
+        assert!(body.starts_with(excerpt.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_errors_never_contain_the_token() {
+        const TOKEN: &str = "d41d8cd9-8f00-b204-e980-0998ecf8427e";
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let end = start + Duration::days(1);
+
+        let garbage = EntsoeClient::with_transport(
+            TOKEN,
+            Arc::new(MockTransport::new(|_| {
+                ok("<GL_MarketDocument><mRID>1</GL_MarketDocument>")
+            })),
+        );
+        let failing = EntsoeClient::with_transport(
+            TOKEN,
+            Arc::new(MockTransport::new(|_| TransportResponse {
+                status: 500,
+                body: String::new(),
+                retry_after: None,
+            })),
+        );
+        // Nothing listens on port 9 of the loopback interface
+        let unreachable = ReqwestTransport::new()
+            .get(&format!("http://127.0.0.1:9/api?securityToken={}", TOKEN))
+            .await
+            .unwrap_err();
+
+        let mut messages = vec![unreachable.to_string(), format!("{:?}", unreachable)];
+        for client in [&garbage, &failing] {
+            let error = client
+                .fetch_range(QueryParams::new("A65").process_type("A01"), start, end)
+                .await
+                .unwrap_err();
+            messages.push(error.to_string());
+            messages.push(format!("{:?}", error));
+            messages.push(format!("{:?}", client));
+        }
+        let multi = garbage
+            .fetch_and_parse_multi(&garbage.request(QueryParams::new("A65")))
+            .await
+            .unwrap_err();
+        messages.push(multi.to_string());
+
+        assert!(messages[2].contains("securityToken=***"));
+        for message in messages {
+            assert!(!message.contains(TOKEN), "token leaked in {}", message);
+        }
+    }
+
+    /// Fails every request as if the network were down
+    struct UnreachableTransport;
+
+    #[async_trait]
+    impl Transport for UnreachableTransport {
+        async fn get(&self, _url: &str) -> Result<TransportResponse, EntsoeError> {
+            Err(EntsoeError::InvalidResponse(
+                "connection refused".to_string(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_key() {
+        let answering = |status: u16, body: &'static str| {
+            EntsoeClient::with_transport(
+                "test-token",
+                Arc::new(MockTransport::new(move |_| TransportResponse {
+                    status,
+                    body: body.to_string(),
+                    retry_after: None,
+                })),
+            )
+        };
+        let no_data = "<Acknowledgement_MarketDocument><Reason><code>999</code><text>No matching data found</text></Reason></Acknowledgement_MarketDocument>";
+        let invalid_token = "<Acknowledgement_MarketDocument><Reason><code>999</code><text>Invalid security token</text></Reason></Acknowledgement_MarketDocument>";
+
+        let accepted = answering(200, no_data);
+        accepted.validate_key().await.unwrap();
+        assert!(accepted.last_successful_fetch().is_some());
+
+        for client in [
+            answering(401, "Unauthorized"),
+            answering(200, invalid_token),
+        ] {
+            let error = client.validate_key().await.unwrap_err();
+            assert!(matches!(error, KeyValidationError::Rejected(_)));
+            assert!(error.to_string().contains("check ENTSOE_API_KEY"));
+        }
+
+        assert!(matches!(
+            answering(503, "").validate_key().await,
+            Err(KeyValidationError::Unreachable(_))
+        ));
+        let offline = EntsoeClient::with_transport("test-token", Arc::new(UnreachableTransport));
+        let error = offline.validate_key().await.unwrap_err();
+        assert!(matches!(error, KeyValidationError::Unreachable(_)));
+        assert!(error.to_string().contains("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_pause_the_client() {
+        let ban = "<Acknowledgement_MarketDocument><Reason><code>999</code><text>Max allowed requests per minute from each unique IP is max up to 400 only. You are banned for 10 minutes.</text></Reason></Acknowledgement_MarketDocument>";
+        let answering = |status: u16, body: &'static str, retry_after: Option<u64>| {
+            Arc::new(MockTransport::new(move |_| TransportResponse {
+                status,
+                body: body.to_string(),
+                retry_after: retry_after.map(std::time::Duration::from_secs),
+            }))
+        };
+        async fn fetch(client: &EntsoeClient) -> Result<GlMarketDocument, EntsoeError> {
+            let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+            client
+                .fetch_range(
+                    QueryParams::total_load_forecast(DEFAULT_ZONE, ForecastSource::DayAhead),
+                    start,
+                    start + Duration::hours(6),
+                )
+                .await
+        }
+
+        for (transport, cool_down) in [
+            (answering(429, "", Some(120)), 120),
+            (answering(200, ban, None), 600),
+            (answering(429, "Too Many Requests", None), 600),
+        ] {
+            let client = EntsoeClient::with_transport("test-token", transport.clone());
+            let error = fetch(&client).await.unwrap_err();
+            assert!(
+                matches!(error, EntsoeError::RateLimited { retry_after: Some(d) } if d.as_secs() == cool_down),
+                "{}",
+                error
+            );
+
+            // The whole client waits out the cool-down without asking upstream
+            let left = client.rate_limit_pause().unwrap();
+            assert!(left.as_secs() <= cool_down && left.as_secs() >= cool_down - 5);
+            assert!(matches!(
+                fetch(&client).await,
+                Err(EntsoeError::RateLimited {
+                    retry_after: Some(_)
+                })
+            ));
+            assert!(matches!(
+                client.check_upstream().await,
+                UpstreamHealth::Degraded(_)
+            ));
+            assert_eq!(transport.requests().len(), 1);
+        }
+
+        // Retry-After: 0 lets the next request through
+        let transport = answering(429, "", Some(0));
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        assert!(fetch(&client).await.is_err());
+        assert_eq!(client.rate_limit_pause(), None);
+        assert!(fetch(&client).await.is_err());
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    /// Serves a one point load forecast after a short delay, counting concurrent requests
+    #[derive(Default)]
+    struct CountingTransport {
+        in_flight: std::sync::atomic::AtomicUsize,
+        max_in_flight: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Transport for CountingTransport {
+        async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+            use std::sync::atomic::Ordering;
+
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            let start = query_param(url, "periodStart")
+                .and_then(|start| parse_period(&start).ok())
+                .unwrap();
+            Ok(ok(gl_document("A65", start, 60, &[1.0])))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_many_bounds_concurrency_and_keeps_order() {
+        let transport = Arc::new(CountingTransport::default());
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 6, 1, 0, 0, 0).unwrap();
+        let requests: Vec<FetchRequest> = (0..10)
+            .map(|day| FetchRequest {
+                params: QueryParams::total_load_forecast(DEFAULT_ZONE, ForecastSource::DayAhead),
+                start: start + Duration::days(day),
+                end: start + Duration::days(day + 1),
+            })
+            .collect();
+
+        let documents = client.fetch_many(requests.clone(), 3).await;
+
+        assert_eq!(
+            transport
+                .max_in_flight
+                .load(std::sync::atomic::Ordering::SeqCst),
+            3
         );
+        let starts: Vec<DateTime<Utc>> = documents
+            .into_iter()
+            .map(|fetch| fetch.into_document().unwrap().all_points().unwrap()[0].0)
+            .collect();
+        let expected: Vec<DateTime<Utc>> = requests.iter().map(|request| request.start).collect();
+        assert_eq!(starts, expected);
+    }
+
+    #[test]
+    fn test_fnv1a_matches_the_reference_values() {
+        assert_eq!(fnv1a(*b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(*b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(*b"foobar"), 0x8594_4171_f739_67e8);
     }
 }
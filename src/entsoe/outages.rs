@@ -0,0 +1,510 @@
+//! Unavailability of generation units (A80), published in Unavailability_MarketDocuments:
+//! one document per outage and revision, served in a ZIP archive
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::request::{ApiRequest, QueryParams, TimeRange};
+use super::{
+    AreaId, EntsoeClient, EntsoeError, MeasureUnit, Period, PeriodGrid, TimeInterval,
+    is_no_matching_data, parse_resolution, parse_timestamp,
+};
+
+/// Root element of the documents, to split the files of an archive at
+pub(crate) const DOCUMENT_ROOT: &str = "Unavailability_MarketDocument";
+
+/// `docStatus` of an outage that was called off
+const CANCELLED: &str = "A09";
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename = "Unavailability_MarketDocument")]
+pub struct UnavailabilityMarketDocument {
+    /// Same for every revision of an outage
+    #[serde(rename = "mRID")]
+    pub mrid: String,
+    #[serde(rename = "revisionNumber")]
+    pub revision_number: u32,
+    #[serde(rename = "type")]
+    pub doc_type: String,
+    #[serde(rename = "createdDateTime")]
+    pub created_date_time: String,
+    #[serde(rename = "unavailability_Time_Period.timeInterval")]
+    pub time_period_interval: TimeInterval,
+    /// Active outages come without a status
+    #[serde(rename = "docStatus", default)]
+    pub doc_status: Option<DocStatus>,
+    #[serde(rename = "TimeSeries", default)]
+    pub time_series: Vec<UnavailabilitySeries>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DocStatus {
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct UnavailabilitySeries {
+    #[serde(rename = "mRID")]
+    pub mrid: String,
+    /// `A53` for planned maintenance, `A54` for a forced outage
+    #[serde(rename = "businessType")]
+    pub business_type: String,
+    #[serde(rename = "biddingZone_Domain.mRID")]
+    pub bidding_zone: AreaId,
+    #[serde(rename = "start_DateAndOrTime.date")]
+    pub start_date: String,
+    #[serde(rename = "start_DateAndOrTime.time")]
+    pub start_time: String,
+    #[serde(rename = "end_DateAndOrTime.date")]
+    pub end_date: String,
+    #[serde(rename = "end_DateAndOrTime.time")]
+    pub end_time: String,
+    #[serde(rename = "quantity_Measure_Unit.name")]
+    pub quantity_measure_unit: String,
+    #[serde(rename = "production_RegisteredResource.mRID")]
+    pub resource: AreaId,
+    #[serde(rename = "production_RegisteredResource.name")]
+    pub resource_name: String,
+    #[serde(rename = "production_RegisteredResource.pSRType.psrType")]
+    pub psr_type: String,
+    #[serde(rename = "production_RegisteredResource.pSRType.powerSystemResources.nominalP")]
+    pub nominal_power: NominalPower,
+    /// Capacity left available while the outage lasts
+    #[serde(rename = "Available_Period", default)]
+    pub available_periods: Vec<Period>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NominalPower {
+    #[serde(rename = "$value")]
+    pub value: f64,
+    #[serde(rename = "@unit")]
+    pub unit: String,
+}
+
+/// Why a unit is unavailable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutageKind {
+    /// Maintenance announced in advance (`A53`)
+    Planned,
+    /// An unplanned outage (`A54`)
+    Forced,
+}
+
+impl OutageKind {
+    fn from_business_type(code: &str) -> Result<Self, EntsoeError> {
+        match code.trim() {
+            "A53" => Ok(OutageKind::Planned),
+            "A54" => Ok(OutageKind::Forced),
+            other => Err(EntsoeError::InvalidResponse(format!(
+                "Unknown outage business type {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Capacity of a unit left available over `[start, end)`, in MW
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvailableCapacity {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub available_mw: f64,
+}
+
+/// The latest revision of the outage of one generation unit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outage {
+    /// Shared by the revisions of the outage
+    pub mrid: String,
+    pub revision: u32,
+    pub kind: OutageKind,
+    /// EIC code of the unit
+    pub unit_mrid: String,
+    pub unit_name: String,
+    pub psr_type: String,
+    pub nominal_mw: f64,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// By time; where none is given the whole unit is unavailable
+    pub available: Vec<AvailableCapacity>,
+}
+
+impl Outage {
+    /// Whether the outage lasts at `at`
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.start <= at && at < self.end
+    }
+
+    /// Whether any of the outage falls within `[start, end)`
+    pub fn overlaps(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+        self.start < end && start < self.end
+    }
+
+    /// MW the outage takes from the unit at `at`: its nominal power less the capacity left
+    /// available, 0 when the outage does not last at `at`
+    pub fn unavailable_mw(&self, at: DateTime<Utc>) -> f64 {
+        if !self.is_active(at) {
+            return 0.0;
+        }
+        let available = self
+            .available
+            .iter()
+            .find(|capacity| capacity.start <= at && at < capacity.end)
+            .map_or(0.0, |capacity| capacity.available_mw);
+        (self.nominal_mw - available).max(0.0)
+    }
+}
+
+/// Available capacity of a period. A point holds until the position of the next one or the
+/// end of the period, as in the variable-sized blocks (curve type `A03`) outages use.
+fn available_capacity(period: &Period) -> Result<Vec<AvailableCapacity>, EntsoeError> {
+    let (start, end) = period.bounds()?;
+    let mut grid = PeriodGrid::new(start, end, parse_resolution(&period.resolution)?);
+    let mut blocks: Vec<(DateTime<Utc>, f64)> = Vec::with_capacity(period.points.len());
+    for point in &period.points {
+        let timestamp = grid.timestamp(point.position)?;
+        if !grid.first_occurrence(point.position) {
+            return Err(EntsoeError::InvalidResponse(format!(
+                "Point position {} appears more than once",
+                point.position
+            )));
+        }
+        blocks.push((timestamp, point.quantity));
+    }
+    blocks.sort_by_key(|(timestamp, _)| *timestamp);
+
+    let ends = blocks.iter().skip(1).map(|(timestamp, _)| *timestamp);
+    Ok(blocks
+        .iter()
+        .zip(ends.chain([end]))
+        .map(|(&(start, available_mw), end)| AvailableCapacity {
+            start,
+            end,
+            available_mw,
+        })
+        .collect())
+}
+
+/// Capacities are given in MW; anything else is refused rather than misread
+fn ensure_megawatts(code: &str) -> Result<(), EntsoeError> {
+    match MeasureUnit::from_code(code)? {
+        MeasureUnit::Megawatt => Ok(()),
+        other => Err(EntsoeError::InvalidResponse(format!(
+            "Outage capacities in {} are not supported",
+            other
+        ))),
+    }
+}
+
+fn date_and_time(date: &str, time: &str) -> Result<DateTime<Utc>, EntsoeError> {
+    parse_timestamp(&format!("{}T{}", date.trim(), time.trim()))
+}
+
+impl UnavailabilityMarketDocument {
+    /// Whether the outage was called off
+    pub fn is_cancelled(&self) -> bool {
+        self.doc_status
+            .as_ref()
+            .is_some_and(|status| status.value.trim() == CANCELLED)
+    }
+
+    /// The outage of every unit the document names
+    pub fn outages(&self) -> Result<Vec<Outage>, EntsoeError> {
+        self.time_series
+            .iter()
+            .map(|series| {
+                ensure_megawatts(&series.quantity_measure_unit)?;
+                ensure_megawatts(&series.nominal_power.unit)?;
+                let mut available = Vec::new();
+                for period in &series.available_periods {
+                    available
+                        .extend(available_capacity(period).map_err(|e| e.in_series(&series.mrid))?);
+                }
+                available.sort_by_key(|capacity| capacity.start);
+
+                Ok(Outage {
+                    mrid: self.mrid.clone(),
+                    revision: self.revision_number,
+                    kind: OutageKind::from_business_type(&series.business_type)?,
+                    unit_mrid: series.resource.value.trim().to_string(),
+                    unit_name: series.resource_name.trim().to_string(),
+                    psr_type: series.psr_type.trim().to_string(),
+                    nominal_mw: series.nominal_power.value,
+                    start: date_and_time(&series.start_date, &series.start_time)?,
+                    end: date_and_time(&series.end_date, &series.end_time)?,
+                    available,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The outages of the latest revision of each document by start, leaving out those called
+/// off
+pub fn latest_outages(
+    documents: &[UnavailabilityMarketDocument],
+) -> Result<Vec<Outage>, EntsoeError> {
+    let mut latest: HashMap<&str, &UnavailabilityMarketDocument> = HashMap::new();
+    for document in documents {
+        let newer = latest
+            .get(document.mrid.as_str())
+            .is_none_or(|seen| seen.revision_number < document.revision_number);
+        if newer {
+            latest.insert(&document.mrid, document);
+        }
+    }
+
+    let mut outages = Vec::new();
+    for document in latest.into_values() {
+        if !document.is_cancelled() {
+            outages.extend(document.outages()?);
+        }
+    }
+    outages.sort_by(|a, b| (a.start, &a.unit_mrid).cmp(&(b.start, &b.unit_mrid)));
+    Ok(outages)
+}
+
+/// The outages of a zone fetched last, kept by the client with the cache enabled
+#[derive(Debug, Clone)]
+pub(crate) struct FetchedOutages {
+    outages: Vec<Outage>,
+    interval: TimeRange,
+    fetched_at: DateTime<Utc>,
+}
+
+/// Unavailability of the generation units (A80) of a bidding zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationOutagesRequest {
+    pub zone: String,
+    pub interval: TimeRange,
+}
+
+impl ApiRequest for GenerationOutagesRequest {
+    type Output = Vec<Outage>;
+
+    fn document_type(&self) -> &'static str {
+        "A80"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.zone]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<Vec<Outage>, EntsoeError> {
+        let params = QueryParams::generation_outages(&self.zone);
+        let mut documents = Vec::new();
+        for (request, _, _) in client.range_requests(params, self.interval.start, self.interval.end)
+        {
+            match client.fetch_documents(&request, DOCUMENT_ROOT).await {
+                Ok(page) => documents.extend(page),
+                // No unit of the zone is out during the interval
+                Err(EntsoeError::InvalidResponse(body)) if is_no_matching_data(&body) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let mut outages = latest_outages(&documents)?;
+        outages.retain(|outage| outage.overlaps(self.interval.start, self.interval.end));
+
+        let fetched_at = Utc::now();
+        *client.last_success.lock().unwrap() = Some(fetched_at);
+        if client.cache.is_some() {
+            client.latest_outages.lock().unwrap().insert(
+                self.zone,
+                FetchedOutages {
+                    outages: outages.clone(),
+                    interval: self.interval,
+                    fetched_at,
+                },
+            );
+        }
+        Ok(outages)
+    }
+}
+
+impl EntsoeClient {
+    /// Fetch the outages of the generation units (A80) of `zone` lasting at any time of
+    /// `[start, end)`
+    pub async fn fetch_generation_outages(
+        &self,
+        zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Outage>, EntsoeError> {
+        self.fetch(GenerationOutagesRequest {
+            zone: zone.to_string(),
+            interval: TimeRange::new(start, end),
+        })
+        .await
+    }
+
+    /// The outages of `zone` active at `at` among those fetched last, with the time of the
+    /// fetch. Only kept with the cache enabled, and `None` when `at` lies outside of the
+    /// interval fetched.
+    pub fn cached_generation_outages(
+        &self,
+        zone: &str,
+        at: DateTime<Utc>,
+    ) -> Option<(Vec<Outage>, DateTime<Utc>)> {
+        let latest = self.latest_outages.lock().unwrap();
+        let fetched = latest.get(zone)?;
+        if at < fetched.interval.start || at >= fetched.interval.end {
+            return None;
+        }
+        let active = fetched
+            .outages
+            .iter()
+            .filter(|outage| outage.is_active(at))
+            .cloned()
+            .collect();
+        Some((active, fetched.fetched_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::parse_response;
+    use crate::entsoe::testing::{
+        DEFAULT_ZONE, MockOutage, MockTransport, NO_MATCHING_DATA, ok, outage_archive,
+        outage_document, outages_response, query_param,
+    };
+    use chrono::{Duration, TimeZone};
+    use std::sync::Arc;
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_outage_takes_nominal_less_available_capacity() {
+        let xml = outage_document(&MockOutage {
+            available: &[(1, 400.0), (121, 0.0)],
+            ..MockOutage::new("outage-1", midnight(), midnight() + Duration::hours(4))
+        });
+        let document: UnavailabilityMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+        let outages = document.outages().unwrap();
+
+        assert_eq!(outages.len(), 1);
+        let outage = &outages[0];
+        assert_eq!(outage.kind, OutageKind::Forced);
+        assert_eq!(outage.unit_name, "Isar 2");
+        assert_eq!(outage.nominal_mw, 1_400.0);
+        assert_eq!(outage.unavailable_mw(midnight()), 1_000.0);
+        assert_eq!(
+            outage.unavailable_mw(midnight() + Duration::hours(3)),
+            1_400.0
+        );
+        assert_eq!(outage.unavailable_mw(midnight() + Duration::hours(4)), 0.0);
+        assert_eq!(outage.available[0].end, midnight() + Duration::hours(2));
+    }
+
+    #[test]
+    fn test_points_outside_the_period_are_refused() {
+        let end = midnight() + Duration::hours(4);
+        for position in [0, 241, u32::MAX] {
+            let xml = outage_document(&MockOutage {
+                available: &[(position, 0.0)],
+                ..MockOutage::new("outage-1", midnight(), end)
+            });
+            let document: UnavailabilityMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+            assert!(
+                matches!(document.outages(), Err(EntsoeError::InvalidResponse(_))),
+                "{}",
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn test_archives_keep_the_latest_revision_of_each_outage() {
+        let end = midnight() + Duration::days(1);
+        let archive = outage_archive(&[
+            MockOutage::new("outage-1", midnight(), end),
+            MockOutage {
+                revision: 2,
+                unit: ("11WD7BRUN1--K", "Brunsbüttel"),
+                ..MockOutage::new("outage-1", midnight() + Duration::hours(6), end)
+            },
+            MockOutage {
+                cancelled: true,
+                ..MockOutage::new("outage-2", midnight(), end)
+            },
+            MockOutage {
+                planned: true,
+                ..MockOutage::new("outage-3", midnight() + Duration::hours(1), end)
+            },
+        ]);
+        let documents: Vec<UnavailabilityMarketDocument> = crate::entsoe::split_documents(
+            &crate::entsoe::decode_body(&archive).unwrap(),
+            DOCUMENT_ROOT,
+        )
+        .into_iter()
+        .map(|xml| parse_response(xml.as_bytes()).unwrap())
+        .collect();
+        assert_eq!(documents.len(), 4);
+
+        let outages = latest_outages(&documents).unwrap();
+        let units: Vec<_> = outages
+            .iter()
+            .map(|outage| (outage.mrid.as_str(), outage.revision, outage.kind))
+            .collect();
+        assert_eq!(
+            units,
+            [
+                ("outage-3", 1, OutageKind::Planned),
+                ("outage-1", 2, OutageKind::Forced)
+            ]
+        );
+        assert_eq!(outages[1].unit_name, "Brunsbüttel");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_generation_outages() {
+        let end = midnight() + Duration::days(1);
+        let transport = Arc::new(MockTransport::new(move |url| {
+            assert_eq!(query_param(url, "documentType").as_deref(), Some("A80"));
+            match query_param(url, "biddingZone_Domain").as_deref() {
+                Some(DEFAULT_ZONE) => outages_response(&[
+                    MockOutage::new("outage-1", midnight(), end),
+                    // Ended before the requested interval
+                    MockOutage::new("outage-2", midnight() - Duration::days(2), midnight()),
+                ]),
+                _ => ok(NO_MATCHING_DATA),
+            }
+        }));
+        let client = EntsoeClient::with_transport("test-token", transport.clone())
+            .with_cache(std::time::Duration::from_secs(300));
+
+        let outages = client
+            .fetch_generation_outages(DEFAULT_ZONE, midnight(), end)
+            .await
+            .unwrap();
+        assert_eq!(outages.len(), 1);
+        assert_eq!(outages[0].mrid, "outage-1");
+
+        let noon = midnight() + Duration::hours(12);
+        let (active, _) = client
+            .cached_generation_outages(DEFAULT_ZONE, noon)
+            .unwrap();
+        assert_eq!(active, outages);
+        assert!(
+            client
+                .cached_generation_outages(DEFAULT_ZONE, end + Duration::days(2))
+                .is_none()
+        );
+
+        let france = client
+            .fetch_generation_outages("10YFR-RTE------C", midnight(), end)
+            .await
+            .unwrap();
+        assert!(france.is_empty());
+        assert_eq!(transport.requests().len(), 2);
+    }
+}
@@ -0,0 +1,305 @@
+//! Day-ahead prices (A44), published in Publication_MarketDocuments with `price.amount`
+//! points in the currency of the bidding zone
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use super::request::{ApiRequest, QueryParams, TimeRange};
+use super::{EntsoeClient, EntsoeError, MeasureUnit, Period, Point, TimeInterval, parse_document};
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename = "Publication_MarketDocument")]
+pub struct PriceMarketDocument {
+    #[serde(rename = "mRID")]
+    pub mrid: String,
+    #[serde(rename = "revisionNumber")]
+    pub revision_number: String,
+    #[serde(rename = "type")]
+    pub doc_type: String,
+    #[serde(rename = "createdDateTime")]
+    pub created_date_time: String,
+    #[serde(rename = "period.timeInterval")]
+    pub time_period_interval: TimeInterval,
+    #[serde(rename = "TimeSeries", default)]
+    pub time_series: Vec<PriceSeries>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PriceSeries {
+    #[serde(rename = "mRID")]
+    pub mrid: String,
+    #[serde(rename = "businessType")]
+    pub business_type: String,
+    /// ISO 4217 code, e.g. `EUR` or `PLN`
+    #[serde(rename = "currency_Unit.name")]
+    pub currency: String,
+    /// Unit the price refers to, `MWH`
+    #[serde(rename = "price_Measure_Unit.name")]
+    pub price_measure_unit: String,
+    #[serde(rename = "Period", default)]
+    pub periods: Vec<PricePeriod>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PricePeriod {
+    #[serde(rename = "timeInterval")]
+    pub time_interval: TimeInterval,
+    pub resolution: String,
+    #[serde(rename = "Point", default)]
+    pub points: Vec<PricePoint>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PricePoint {
+    pub position: u32,
+    #[serde(rename = "price.amount")]
+    pub amount: f64,
+}
+
+/// Price of one interval, per MWh
+#[derive(Debug, Clone, PartialEq)]
+pub struct Price {
+    pub timestamp: DateTime<Utc>,
+    pub duration: Duration,
+    pub amount: f64,
+}
+
+impl Price {
+    pub fn end(&self) -> DateTime<Utc> {
+        self.timestamp + self.duration
+    }
+}
+
+/// Prices of one bidding zone, all in the same currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayAheadPrices {
+    pub currency: String,
+    /// By time
+    pub points: Vec<Price>,
+}
+
+impl DayAheadPrices {
+    /// Mean price over the points, `None` without points
+    pub fn mean(&self) -> Option<f64> {
+        if self.points.is_empty() {
+            return None;
+        }
+        Some(self.points.iter().map(|point| point.amount).sum::<f64>() / self.points.len() as f64)
+    }
+
+    /// Add the points of `other`, refusing prices in another currency
+    fn extend(&mut self, other: DayAheadPrices) -> Result<(), EntsoeError> {
+        if other.points.is_empty() {
+            return Ok(());
+        }
+        if self.points.is_empty() {
+            self.currency = other.currency;
+        } else if other.currency != self.currency {
+            return Err(mixed_currencies(&self.currency, &other.currency));
+        }
+        self.points.extend(other.points);
+        self.points.sort_by_key(|point| point.timestamp);
+        Ok(())
+    }
+}
+
+fn mixed_currencies(a: &str, b: &str) -> EntsoeError {
+    EntsoeError::InvalidResponse(format!(
+        "Prices of one zone are quoted in both {} and {}",
+        a, b
+    ))
+}
+
+impl PriceMarketDocument {
+    /// Prices of all series by time. Series in different currencies or per other units
+    /// than MWh are refused rather than mixed.
+    pub fn prices(&self) -> Result<DayAheadPrices, EntsoeError> {
+        let mut prices = DayAheadPrices {
+            currency: String::new(),
+            points: Vec::new(),
+        };
+        for series in &self.time_series {
+            if series.price_measure_unit.trim() != MeasureUnit::MegawattHour.code() {
+                return Err(EntsoeError::InvalidResponse(format!(
+                    "Prices per {} are not supported",
+                    series.price_measure_unit
+                )));
+            }
+            let currency = series.currency.trim().to_string();
+            if !prices.currency.is_empty() && prices.currency != currency {
+                return Err(mixed_currencies(&prices.currency, &currency));
+            }
+            prices.currency = currency;
+
+            for period in &series.periods {
+                // Reuse the position arithmetic of quantity periods
+                let period = Period {
+                    time_interval: period.time_interval.clone(),
+                    resolution: period.resolution.clone(),
+                    points: period
+                        .points
+                        .iter()
+                        .map(|point| Point {
+                            position: point.position,
+                            quantity: point.amount,
+                        })
+                        .collect(),
+                };
+                let points = period
+                    .timestamped_points(MeasureUnit::MegawattHour)
+                    .map_err(|e| e.in_series(&series.mrid))?;
+                prices.points.extend(points.into_iter().map(|point| Price {
+                    timestamp: point.timestamp,
+                    duration: point.duration,
+                    amount: point.quantity,
+                }));
+            }
+        }
+
+        prices.points.sort_by_key(|point| point.timestamp);
+        Ok(prices)
+    }
+}
+
+/// Day-ahead prices (A44) of a bidding zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayAheadPricesRequest {
+    pub zone: String,
+    pub interval: TimeRange,
+}
+
+impl ApiRequest for DayAheadPricesRequest {
+    type Output = DayAheadPrices;
+
+    fn document_type(&self) -> &'static str {
+        "A44"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.zone]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<DayAheadPrices, EntsoeError> {
+        let params = QueryParams::day_ahead_prices(&self.zone);
+        let mut prices = DayAheadPrices {
+            currency: String::new(),
+            points: Vec::new(),
+        };
+        for (request, _, _) in client.range_requests(params, self.interval.start, self.interval.end)
+        {
+            let xml = client.fetch_body(&request).await?;
+            let document: PriceMarketDocument = parse_document(&xml, &request)?;
+            prices.extend(document.prices()?)?;
+        }
+        // Upstream answers whole market days
+        prices.points.retain(|point| {
+            point.timestamp >= self.interval.start && point.timestamp < self.interval.end
+        });
+
+        let fetched_at = Utc::now();
+        *client.last_success.lock().unwrap() = Some(fetched_at);
+        if client.cache.is_some() {
+            client
+                .latest_prices
+                .lock()
+                .unwrap()
+                .insert(self.zone, (prices.clone(), fetched_at));
+        }
+        Ok(prices)
+    }
+}
+
+impl EntsoeClient {
+    /// Fetch the day-ahead prices (A44) of `zone` over `[start, end)`
+    pub async fn fetch_day_ahead_prices(
+        &self,
+        zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<DayAheadPrices, EntsoeError> {
+        self.fetch(DayAheadPricesRequest {
+            zone: zone.to_string(),
+            interval: TimeRange::new(start, end),
+        })
+        .await
+    }
+
+    /// The price of `zone` in force at `at` among the prices fetched last, with the
+    /// currency and the time of the fetch. Only kept with the cache enabled; published
+    /// prices do not change, so no expiry applies.
+    pub fn cached_day_ahead_price(
+        &self,
+        zone: &str,
+        at: DateTime<Utc>,
+    ) -> Option<(Price, String, DateTime<Utc>)> {
+        let latest = self.latest_prices.lock().unwrap();
+        let (prices, fetched_at) = latest.get(zone)?;
+        let price = prices
+            .points
+            .iter()
+            .find(|price| price.timestamp <= at && at < price.end())?;
+        Some((price.clone(), prices.currency.clone(), *fetched_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::{DEFAULT_ZONE, MockTransport, ok, price_document, query_param};
+    use chrono::TimeZone;
+    use std::sync::Arc;
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_prices_keep_the_currency() {
+        let xml = price_document(DEFAULT_ZONE, "PLN", midnight(), 60, &[400.0, 420.0]);
+        let document: PriceMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+        let prices = document.prices().unwrap();
+
+        assert_eq!(prices.currency, "PLN");
+        assert_eq!(prices.points.len(), 2);
+        assert_eq!(prices.points[1].end(), midnight() + Duration::hours(2));
+        assert_eq!(prices.mean(), Some(410.0));
+
+        let mut mixed = document.clone();
+        mixed.time_series[0].currency = "EUR".to_string();
+        mixed.time_series.push(document.time_series[0].clone());
+        assert!(mixed.prices().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_day_ahead_prices() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            assert_eq!(query_param(url, "documentType").as_deref(), Some("A44"));
+            ok(price_document(
+                DEFAULT_ZONE,
+                "EUR",
+                midnight(),
+                60,
+                &[90.0; 24],
+            ))
+        }));
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+
+        let prices = client
+            .fetch_day_ahead_prices(DEFAULT_ZONE, midnight(), midnight() + Duration::days(1))
+            .await
+            .unwrap();
+
+        assert_eq!(prices.currency, "EUR");
+        assert_eq!(prices.points.len(), 24);
+        let url = &transport.requests()[0];
+        assert_eq!(query_param(url, "in_Domain").as_deref(), Some(DEFAULT_ZONE));
+        assert_eq!(
+            query_param(url, "out_Domain").as_deref(),
+            Some(DEFAULT_ZONE)
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Progress of the upstream requests of a fetch, for long running commands. The client
+//! reports each request of [`EntsoeClient::fetch_range`](super::EntsoeClient::fetch_range)
+//! to its [`ProgressSink`]; by default to [`NoProgress`].
+
+use chrono::{DateTime, Utc};
+
+use super::EntsoeError;
+use super::request::QueryParams;
+
+/// One request of a range fetch: a chunk of the period upstream serves at once
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestMeta {
+    pub document_type: String,
+    /// Area the request is about, see [`QueryParams::zone`]
+    pub zone: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Position of the chunk in its range, from 0
+    pub chunk: usize,
+    /// Chunks the range is split into
+    pub chunks: usize,
+}
+
+impl RequestMeta {
+    pub(crate) fn new(
+        params: &QueryParams,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        chunk: usize,
+        chunks: usize,
+    ) -> Self {
+        Self {
+            document_type: params.document_type().to_string(),
+            zone: params.zone().map(str::to_string),
+            start,
+            end,
+            chunk,
+            chunks,
+        }
+    }
+}
+
+/// A request that got its answer, or failed
+#[derive(Debug)]
+pub struct FinishedRequest<'a> {
+    pub request: &'a RequestMeta,
+    pub elapsed: std::time::Duration,
+    /// `None` if the document was fetched and parsed
+    pub error: Option<&'a EntsoeError>,
+}
+
+/// Receives the requests of a client as they start and finish. Requests of concurrent
+/// fetches interleave, so implementations tell them apart by their [`RequestMeta`].
+pub trait ProgressSink: Send + Sync {
+    fn on_request_start(&self, _request: &RequestMeta) {}
+
+    fn on_request_finish(&self, _finished: &FinishedRequest<'_>) {}
+}
+
+/// Ignores every request, the sink of clients not given another
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {}
@@ -0,0 +1,776 @@
+//! Requests to the ENTSO-E API, keeping the security token out of anything printed
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use reqwest::Url;
+
+use super::areas::{self, get_zone_by_code};
+use super::balancing::ReserveType;
+use super::{
+    BASE_URL, EntsoeClient, EntsoeError, ForecastSource, GlMarketDocument, format_period,
+    max_request_span, parse_period,
+};
+
+/// Shown instead of the security token
+pub(crate) const REDACTED: &str = "***";
+
+/// Step of `periodStart` and `periodEnd`; anything finer would be cut off silently
+const PERIOD_GRANULARITY: Duration = Duration::minutes(1);
+
+/// Most requests a single fetch is split into before it is rejected as too long
+const MAX_REQUESTS_PER_FETCH: i32 = 10;
+
+/// Parameters the setters of [`QueryParams`] send, recognized by [`QueryParams::from_url`]
+const KNOWN_PARAMS: [&str; 13] = [
+    "processType",
+    "in_Domain",
+    "out_Domain",
+    "outBiddingZone_Domain",
+    "controlArea_Domain",
+    "biddingZone_Domain",
+    "businessType",
+    "psrType",
+    "offset",
+    "periodStart",
+    "periodEnd",
+    "contract_MarketAgreement.Type",
+    "registeredResource",
+];
+
+/// Query parameters of a request besides the security token, in the order they are sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryParams {
+    document_type: String,
+    pairs: Vec<(&'static str, String)>,
+}
+
+impl QueryParams {
+    pub fn new(document_type: impl Into<String>) -> Self {
+        Self {
+            document_type: document_type.into(),
+            pairs: Vec::new(),
+        }
+    }
+
+    /// Total load forecast (A65) of `out_bidding_zone`, under its [`areas::load_code`]
+    pub fn total_load_forecast(out_bidding_zone: &str, source: ForecastSource) -> Self {
+        Self::new("A65")
+            .process_type(source.process_type())
+            .out_bidding_zone(areas::load_code(out_bidding_zone))
+    }
+
+    /// Actual total load (A65 of process type A16) of `out_bidding_zone`, under its
+    /// [`areas::load_code`]
+    pub fn actual_total_load(out_bidding_zone: &str) -> Self {
+        Self::new("A65")
+            .process_type("A16")
+            .out_bidding_zone(areas::load_code(out_bidding_zone))
+    }
+
+    /// Solar and wind generation forecast (A69) of `in_domain`, under its
+    /// [`areas::generation_code`]
+    pub fn generation_forecast(in_domain: &str, source: ForecastSource) -> Self {
+        Self::new("A69")
+            .process_type(source.process_type())
+            .in_domain(areas::generation_code(in_domain))
+    }
+
+    /// Day-ahead generation forecast of all production types (A71) of `in_domain`, under
+    /// its [`areas::generation_code`]
+    pub fn total_generation_forecast(in_domain: &str) -> Self {
+        Self::new("A71")
+            .process_type(ForecastSource::DayAhead.process_type())
+            .in_domain(areas::generation_code(in_domain))
+    }
+
+    /// Actual generation per production type (A75) of `in_domain`
+    pub fn actual_generation(in_domain: &str) -> Self {
+        Self::new("A75").process_type("A16").in_domain(in_domain)
+    }
+
+    /// Actual generation per production unit (A73) of the control area `in_domain`
+    pub fn generation_per_unit(in_domain: &str) -> Self {
+        Self::new("A73").process_type("A16").in_domain(in_domain)
+    }
+
+    /// Day-ahead prices (A44) of a bidding zone
+    pub fn day_ahead_prices(zone: &str) -> Self {
+        Self::new("A44").in_domain(zone).out_domain(zone)
+    }
+
+    /// Activated balancing energy (A83) of `reserve_type` in `control_area`
+    pub fn activated_balancing_energy(control_area: &str, reserve_type: ReserveType) -> Self {
+        Self::new("A83")
+            .business_type(reserve_type.business_type())
+            .control_area(control_area)
+    }
+
+    /// Day-ahead net transfer capacity (A61) from `out_domain` into `in_domain`
+    pub fn offered_capacity(in_domain: &str, out_domain: &str) -> Self {
+        Self::new("A61")
+            .param("contract_MarketAgreement.Type", "A01")
+            .in_domain(in_domain)
+            .out_domain(out_domain)
+    }
+
+    /// Physical flows (A11) from `out_domain` into `in_domain`
+    pub fn physical_flows(in_domain: &str, out_domain: &str) -> Self {
+        Self::new("A11").in_domain(in_domain).out_domain(out_domain)
+    }
+
+    /// Outages of the generation units (A80) of a bidding zone
+    pub fn generation_outages(zone: &str) -> Self {
+        Self::new("A80").bidding_zone(zone)
+    }
+
+    /// The parameters of a request URL, in either form [`Request`] writes: the one it
+    /// sends or the masked one it prints. `None` without a `documentType` or with a
+    /// parameter outside of [`KNOWN_PARAMS`].
+    pub fn from_url(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let mut document_type = None;
+        let mut pairs = Vec::new();
+        for (name, value) in url.query_pairs() {
+            match name.as_ref() {
+                "securityToken" => {}
+                "documentType" => document_type = Some(value.into_owned()),
+                name => {
+                    let known = KNOWN_PARAMS.iter().find(|known| **known == name)?;
+                    pairs.push((*known, value.into_owned()));
+                }
+            }
+        }
+        Some(Self {
+            document_type: document_type?,
+            pairs,
+        })
+    }
+
+    pub fn document_type(&self) -> &str {
+        &self.document_type
+    }
+
+    /// Area the request is about: the bidding zone, control area or `in_Domain`
+    pub fn zone(&self) -> Option<&str> {
+        [
+            "outBiddingZone_Domain",
+            "biddingZone_Domain",
+            "controlArea_Domain",
+            "in_Domain",
+        ]
+        .iter()
+        .find_map(|key| {
+            self.pairs
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+        })
+    }
+
+    /// `periodStart` and `periodEnd`, if both are set and valid
+    pub fn time_range(&self) -> Option<TimeRange> {
+        let value = |key| {
+            self.pairs
+                .iter()
+                .find(|(name, _)| *name == key)
+                .and_then(|(_, value)| parse_period(value).ok())
+        };
+        Some(TimeRange::new(value("periodStart")?, value("periodEnd")?))
+    }
+
+    /// Whether `other` asks for the same series, over any period
+    pub fn same_series(&self, other: &QueryParams) -> bool {
+        let series = |params: &QueryParams| -> Vec<(&'static str, String)> {
+            params
+                .pairs
+                .iter()
+                .filter(|(name, _)| !matches!(*name, "periodStart" | "periodEnd"))
+                .cloned()
+                .collect()
+        };
+        self.document_type == other.document_type && series(self) == series(other)
+    }
+
+    /// Any parameter without a dedicated setter
+    pub fn param(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.pairs.push((key, value.into()));
+        self
+    }
+
+    pub fn process_type(self, code: &str) -> Self {
+        self.param("processType", code)
+    }
+
+    pub fn in_domain(self, area: &str) -> Self {
+        self.param("in_Domain", area)
+    }
+
+    pub fn out_domain(self, area: &str) -> Self {
+        self.param("out_Domain", area)
+    }
+
+    pub fn out_bidding_zone(self, area: &str) -> Self {
+        self.param("outBiddingZone_Domain", area)
+    }
+
+    pub fn bidding_zone(self, area: &str) -> Self {
+        self.param("biddingZone_Domain", area)
+    }
+
+    pub fn control_area(self, area: &str) -> Self {
+        self.param("controlArea_Domain", area)
+    }
+
+    pub fn business_type(self, code: &str) -> Self {
+        self.param("businessType", code)
+    }
+
+    pub fn psr_type(self, code: &str) -> Self {
+        self.param("psrType", code)
+    }
+
+    /// Only the series of one production unit, by EIC code
+    pub fn registered_resource(self, mrid: &str) -> Self {
+        self.param("registeredResource", mrid)
+    }
+
+    /// Index of the first document of a multi-document query
+    pub fn offset(self, offset: usize) -> Self {
+        self.param("offset", offset.to_string())
+    }
+
+    /// `periodStart` and `periodEnd` of `[start, end)`
+    pub fn period(self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.param("periodStart", format_period(start))
+            .param("periodEnd", format_period(end))
+    }
+}
+
+/// One [`super::EntsoeClient::fetch_range`] call of a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchRequest {
+    pub params: QueryParams,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Half-open interval `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl TimeRange {
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self { start, end }
+    }
+
+    /// From timestamps in the `yyyyMMddHHmm` format of the API
+    pub fn parse(start: &str, end: &str) -> Result<Self, EntsoeError> {
+        Ok(Self::new(parse_period(start)?, parse_period(end)?))
+    }
+}
+
+/// One thing wrong with a typed request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// No known bidding zone or control area has this code
+    UnknownArea(String),
+    /// The interval does not end after it starts
+    EmptyInterval(TimeRange),
+    /// The interval would need more than [`MAX_REQUESTS_PER_FETCH`] requests
+    IntervalTooLong {
+        document_type: &'static str,
+        max: Duration,
+    },
+    /// A timestamp with seconds, which the API can't express
+    Misaligned(DateTime<Utc>),
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::UnknownArea(code) => write!(f, "unknown area {}", code),
+            ValidationIssue::EmptyInterval(interval) => write!(
+                f,
+                "interval ends at {} before it starts at {}",
+                interval.end, interval.start
+            ),
+            ValidationIssue::IntervalTooLong { document_type, max } => write!(
+                f,
+                "{} can be fetched for at most {} days at once",
+                document_type,
+                max.num_days()
+            ),
+            ValidationIssue::Misaligned(timestamp) => {
+                write!(f, "{} is not a whole minute", timestamp)
+            }
+        }
+    }
+}
+
+/// A request with typed parameters, sent by [`EntsoeClient::fetch`] once it validates
+pub trait ApiRequest {
+    type Output;
+
+    fn document_type(&self) -> &'static str;
+
+    /// Codes of the areas the request refers to
+    fn areas(&self) -> Vec<&str>;
+
+    fn interval(&self) -> TimeRange;
+
+    /// Everything wrong with the request
+    fn issues(&self) -> Vec<ValidationIssue> {
+        let mut issues: Vec<ValidationIssue> = self
+            .areas()
+            .into_iter()
+            .filter(|code| get_zone_by_code(code).is_none())
+            .map(|code| ValidationIssue::UnknownArea(code.to_string()))
+            .collect();
+
+        let interval = self.interval();
+        let max = max_request_span(self.document_type()) * MAX_REQUESTS_PER_FETCH;
+        if interval.start >= interval.end {
+            issues.push(ValidationIssue::EmptyInterval(interval));
+        } else if interval.end - interval.start > max {
+            issues.push(ValidationIssue::IntervalTooLong {
+                document_type: self.document_type(),
+                max,
+            });
+        }
+
+        for timestamp in [interval.start, interval.end] {
+            if timestamp.duration_trunc(PERIOD_GRANULARITY) != Ok(timestamp) {
+                issues.push(ValidationIssue::Misaligned(timestamp));
+            }
+        }
+        issues
+    }
+
+    fn validate(&self) -> Result<(), EntsoeError> {
+        let issues = self.issues();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(EntsoeError::InvalidRequest(issues))
+        }
+    }
+
+    /// Send the request as is; [`EntsoeClient::fetch`] validates it first
+    fn send(
+        self,
+        client: &EntsoeClient,
+    ) -> impl Future<Output = Result<Self::Output, EntsoeError>> + Send;
+}
+
+/// Total load forecast (A65) of a bidding zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadForecastRequest {
+    pub zone: String,
+    pub interval: TimeRange,
+    pub horizon: ForecastSource,
+}
+
+impl ApiRequest for LoadForecastRequest {
+    type Output = GlMarketDocument;
+
+    fn document_type(&self) -> &'static str {
+        "A65"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.zone]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<GlMarketDocument, EntsoeError> {
+        let params = QueryParams::total_load_forecast(&self.zone, self.horizon);
+        client
+            .fetch_range(params, self.interval.start, self.interval.end)
+            .await
+    }
+}
+
+/// Actual total load (A65 of process type A16) of a bidding zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActualLoadRequest {
+    pub zone: String,
+    pub interval: TimeRange,
+}
+
+impl ApiRequest for ActualLoadRequest {
+    type Output = GlMarketDocument;
+
+    fn document_type(&self) -> &'static str {
+        "A65"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.zone]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<GlMarketDocument, EntsoeError> {
+        let params = QueryParams::actual_total_load(&self.zone);
+        client
+            .fetch_range(params, self.interval.start, self.interval.end)
+            .await
+    }
+}
+
+/// Solar and wind generation forecast (A69) of a bidding zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationForecastRequest {
+    pub zone: String,
+    pub interval: TimeRange,
+    pub horizon: ForecastSource,
+}
+
+impl ApiRequest for GenerationForecastRequest {
+    type Output = GlMarketDocument;
+
+    fn document_type(&self) -> &'static str {
+        "A69"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.zone]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<GlMarketDocument, EntsoeError> {
+        let params = QueryParams::generation_forecast(&self.zone, self.horizon);
+        client
+            .fetch_range(params, self.interval.start, self.interval.end)
+            .await
+    }
+}
+
+/// Day-ahead generation forecast of all production types (A71) of a bidding zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TotalGenerationForecastRequest {
+    pub zone: String,
+    pub interval: TimeRange,
+}
+
+impl ApiRequest for TotalGenerationForecastRequest {
+    type Output = GlMarketDocument;
+
+    fn document_type(&self) -> &'static str {
+        "A71"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.zone]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<GlMarketDocument, EntsoeError> {
+        let params = QueryParams::total_generation_forecast(&self.zone);
+        client
+            .fetch_range(params, self.interval.start, self.interval.end)
+            .await
+    }
+}
+
+/// Query against the API. `Display` and `Debug` mask the security token, so a request
+/// can go into logs and error messages; only [`Request::url`] carries the token.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Request {
+    base_url: String,
+    token: String,
+    params: QueryParams,
+}
+
+impl Request {
+    pub fn new(token: impl Into<String>, params: QueryParams) -> Self {
+        Self {
+            base_url: BASE_URL.to_string(),
+            token: token.into(),
+            params,
+        }
+    }
+
+    pub fn params(&self) -> &QueryParams {
+        &self.params
+    }
+
+    /// The same request with `params` adjusted
+    pub fn with(&self, adjust: impl FnOnce(QueryParams) -> QueryParams) -> Self {
+        Self {
+            params: adjust(self.params.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// The URL to send, including the security token
+    pub fn url(&self) -> String {
+        self.format(&self.token)
+    }
+
+    /// Values are percent-encoded, so zone codes and tokens pass through unchanged
+    fn format(&self, token: &str) -> String {
+        let mut url = Url::parse(&self.base_url).expect("invalid base URL");
+        url.query_pairs_mut()
+            .append_pair("securityToken", token)
+            .append_pair("documentType", &self.params.document_type)
+            .extend_pairs(&self.params.pairs);
+        url.into()
+    }
+}
+
+impl std::fmt::Display for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.format(REDACTED))
+    }
+}
+
+impl std::fmt::Debug for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_display_masks_the_token() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let request = Request::new(
+            "secret",
+            QueryParams::new("A65")
+                .process_type("A01")
+                .period(start, start + chrono::Duration::days(1)),
+        );
+
+        assert_eq!(
+            request.url(),
+            format!(
+                "{}?securityToken=secret&documentType=A65&processType=A01\
+                 &periodStart=202406010000&periodEnd=202406020000",
+                BASE_URL
+            )
+        );
+        assert_eq!(
+            request.to_string(),
+            request.url().replace("secret", REDACTED)
+        );
+        assert!(!format!("{:?}", request).contains("secret"));
+    }
+
+    #[test]
+    fn test_params_are_read_back_from_urls() {
+        let params = QueryParams::offered_capacity("10YFR-RTE------C", "10Y1001A1001A82H")
+            .period(midnight(), midnight() + Duration::days(1));
+        let request = Request::new("secret", params.clone());
+
+        assert_eq!(QueryParams::from_url(&request.url()), Some(params.clone()));
+        assert_eq!(QueryParams::from_url(&request.to_string()), Some(params));
+        assert_eq!(
+            QueryParams::from_url(&format!("{}?documentType=A65&unknown=1", BASE_URL)),
+            None
+        );
+        assert_eq!(
+            QueryParams::from_url(&format!("{}?processType=A01", BASE_URL)),
+            None
+        );
+    }
+
+    fn load_request(zone: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> LoadForecastRequest {
+        LoadForecastRequest {
+            zone: zone.to_string(),
+            interval: TimeRange::new(start, end),
+            horizon: ForecastSource::DayAhead,
+        }
+    }
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_valid_request_has_no_issues() {
+        let request = load_request(
+            "10YCZ-CEPS-----N",
+            midnight(),
+            midnight() + Duration::days(1),
+        );
+        assert_eq!(request.issues(), []);
+        assert!(request.validate().is_ok());
+    }
+
+    #[test]
+    fn test_unknown_area_is_reported() {
+        let request = load_request(
+            "10YXX-NOWHERE--0",
+            midnight(),
+            midnight() + Duration::days(1),
+        );
+        assert_eq!(
+            request.issues(),
+            [ValidationIssue::UnknownArea("10YXX-NOWHERE--0".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_interval_must_not_be_empty() {
+        let request = load_request("10YCZ-CEPS-----N", midnight(), midnight());
+        assert_eq!(
+            request.issues(),
+            [ValidationIssue::EmptyInterval(TimeRange::new(
+                midnight(),
+                midnight()
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_interval_length_depends_on_document_type() {
+        let start = midnight();
+        // Forecasts are fetched a year per request, other documents a month
+        let load = load_request("10YCZ-CEPS-----N", start, start + Duration::days(5 * 365));
+        assert_eq!(load.issues(), []);
+        let too_long = load_request("10YCZ-CEPS-----N", start, start + Duration::days(11 * 365));
+        assert_eq!(
+            too_long.issues(),
+            [ValidationIssue::IntervalTooLong {
+                document_type: "A65",
+                max: Duration::days(3650),
+            }]
+        );
+
+        let balancing = crate::entsoe::balancing::ActivationsRequest {
+            control_area: "10YCZ-CEPS-----N".to_string(),
+            interval: TimeRange::new(start, start + Duration::days(365)),
+            reserve_type: ReserveType::Afrr,
+        };
+        assert!(matches!(
+            balancing.issues()[..],
+            [ValidationIssue::IntervalTooLong {
+                document_type: "A83",
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_timestamps_must_be_whole_minutes() {
+        let start = midnight() + Duration::seconds(30);
+        let request = load_request("10YCZ-CEPS-----N", start, midnight() + Duration::hours(1));
+        assert_eq!(request.issues(), [ValidationIssue::Misaligned(start)]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_reports_every_issue_without_sending() {
+        let transport = std::sync::Arc::new(crate::entsoe::testing::MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let end = midnight() + Duration::seconds(1);
+
+        let error = client
+            .fetch(load_request("nowhere", end, midnight()))
+            .await
+            .unwrap_err();
+
+        let EntsoeError::InvalidRequest(issues) = &error else {
+            panic!("expected an invalid request, got {}", error);
+        };
+        assert_eq!(issues.len(), 3);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Invalid request: unknown area nowhere; interval ends at {} before it starts \
+                 at {}; {} is not a whole minute",
+                midnight(),
+                end,
+                end
+            )
+        );
+        assert!(transport.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_forecasts_are_requested_under_the_published_codes() {
+        // Denmark publishes its load for the country area, its generation for the
+        // control area
+        const DENMARK: &str = "10Y1001A1001A796";
+        let zone = |params: QueryParams| params.zone().map(str::to_string);
+        let day_ahead = ForecastSource::DayAhead;
+        assert_eq!(
+            zone(QueryParams::total_load_forecast(DENMARK, day_ahead)).as_deref(),
+            Some("10Y1001A1001A65H")
+        );
+        assert_eq!(
+            zone(QueryParams::generation_forecast(DENMARK, day_ahead)).as_deref(),
+            Some(DENMARK)
+        );
+        assert_eq!(
+            zone(QueryParams::total_generation_forecast(DENMARK)).as_deref(),
+            Some(DENMARK)
+        );
+        assert_eq!(
+            zone(QueryParams::total_load_forecast(
+                "10YCZ-CEPS-----N",
+                day_ahead
+            ))
+            .as_deref(),
+            Some("10YCZ-CEPS-----N")
+        );
+
+        // The typed request validates the zone code and sends the override
+        let transport = std::sync::Arc::new(crate::entsoe::testing::MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let request = load_request(DENMARK, midnight(), midnight() + Duration::days(1));
+        assert_eq!(request.issues(), []);
+        client.fetch(request).await.unwrap();
+        let requests = transport.requests();
+        assert_eq!(
+            crate::entsoe::testing::query_param(&requests[0], "outBiddingZone_Domain").as_deref(),
+            Some("10Y1001A1001A65H")
+        );
+    }
+
+    #[test]
+    fn test_values_are_escaped() {
+        let zone = "10Y&in_Domain=FR #+ü";
+        let request = Request::new(
+            "to/ken=",
+            QueryParams::new("A69").in_domain(zone).offset(200),
+        );
+        let url = Url::parse(&request.url()).unwrap();
+
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert_eq!(
+            pairs,
+            [
+                ("securityToken", "to/ken="),
+                ("documentType", "A69"),
+                ("in_Domain", zone),
+                ("offset", "200"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+        assert_eq!(
+            request.with(|params| params.psr_type("B16")).params(),
+            &QueryParams::new("A69")
+                .in_domain(zone)
+                .offset(200)
+                .psr_type("B16")
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! What a parsed document contained, reported as a `tracing` event and as fields of the
+//! request span, so odd numbers can be traced back without logging raw XML
+
+use std::time::Duration;
+use tracing::Span;
+use tracing::field::Empty;
+
+use super::GlMarketDocument;
+use super::request::Request;
+
+/// Event target of the statistics, for filtering them
+pub const TARGET: &str = "educk::entsoe::document";
+
+/// Summary of a parsed document
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentStats {
+    pub document_type: String,
+    /// Bidding zone or control area the request asked for
+    pub zone: Option<String>,
+    pub series: usize,
+    pub points: usize,
+    /// Distinct resolutions of the periods, e.g. `PT15M,PT60M`
+    pub resolutions: Vec<String>,
+    /// `time_Period.timeInterval` as sent
+    pub covered_start: String,
+    pub covered_end: String,
+    pub created: String,
+    pub parse_duration: Duration,
+}
+
+/// Span of one upstream request, with the statistics of its document left empty until
+/// [`DocumentStats::record`]. Names the [API request](crate::request_id) it was made
+/// for, if any.
+pub fn request_span(request: &Request) -> Span {
+    let span = tracing::info_span!(
+        "entsoe_request",
+        request = %request,
+        request_id = Empty,
+        document_type = request.params().document_type(),
+        zone = request.params().zone().unwrap_or(""),
+        series = Empty,
+        points = Empty,
+        resolutions = Empty,
+        covered_start = Empty,
+        covered_end = Empty,
+        created = Empty,
+        parse_ms = Empty,
+    );
+    if let Some(id) = crate::request_id::current() {
+        span.record("request_id", id);
+    }
+    span
+}
+
+impl DocumentStats {
+    pub fn of(document: &GlMarketDocument, zone: Option<&str>, parse_duration: Duration) -> Self {
+        let mut resolutions: Vec<String> = document
+            .time_series
+            .iter()
+            .map(|series| series.period.resolution.clone())
+            .collect();
+        resolutions.sort();
+        resolutions.dedup();
+        Self {
+            document_type: document.doc_type.clone(),
+            zone: zone.map(str::to_string),
+            series: document.time_series.len(),
+            points: document
+                .time_series
+                .iter()
+                .map(|series| series.period.points.len())
+                .sum(),
+            resolutions,
+            covered_start: document.time_period_interval.start.clone(),
+            covered_end: document.time_period_interval.end.clone(),
+            created: document.created_date_time.clone(),
+            parse_duration,
+        }
+    }
+
+    fn parse_ms(&self) -> f64 {
+        self.parse_duration.as_secs_f64() * 1000.0
+    }
+
+    /// Fill in the fields of the request span, see [`request_span`]
+    pub fn record(&self, span: &Span) {
+        span.record("series", self.series);
+        span.record("points", self.points);
+        span.record("resolutions", self.resolutions.join(",").as_str());
+        span.record("covered_start", self.covered_start.as_str());
+        span.record("covered_end", self.covered_end.as_str());
+        span.record("created", self.created.as_str());
+        span.record("parse_ms", self.parse_ms());
+    }
+
+    /// Emit the statistics as an info event on [`TARGET`]
+    pub fn emit(&self) {
+        tracing::info!(
+            target: TARGET,
+            document_type = self.document_type.as_str(),
+            zone = self.zone.as_deref().unwrap_or(""),
+            series = self.series,
+            points = self.points,
+            resolutions = self.resolutions.join(",").as_str(),
+            covered_start = self.covered_start.as_str(),
+            covered_end = self.covered_end.as_str(),
+            created = self.created.as_str(),
+            parse_ms = self.parse_ms(),
+            "parsed document"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::EntsoeClient;
+    use crate::entsoe::ForecastSource;
+    use crate::entsoe::request::QueryParams;
+    use crate::entsoe::testing::{DEFAULT_ZONE, MockTransport};
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    type Fields = HashMap<String, String>;
+
+    #[derive(Default)]
+    struct FieldVisitor(Fields);
+
+    impl Visit for FieldVisitor {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    /// Fields of the events and of the spans, by span name
+    #[derive(Clone, Default)]
+    struct Captured {
+        events: Arc<Mutex<Vec<(String, Fields)>>>,
+        spans: Arc<Mutex<HashMap<String, Fields>>>,
+    }
+
+    impl<S> Layer<S> for Captured
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            let mut visitor = FieldVisitor::default();
+            attrs.record(&mut visitor);
+            self.spans
+                .lock()
+                .unwrap()
+                .insert(attrs.metadata().name().to_string(), visitor.0);
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            let name = ctx.span(id).unwrap().name().to_string();
+            let mut visitor = FieldVisitor::default();
+            values.record(&mut visitor);
+            self.spans
+                .lock()
+                .unwrap()
+                .entry(name)
+                .or_default()
+                .extend(visitor.0);
+        }
+
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut visitor = FieldVisitor::default();
+            event.record(&mut visitor);
+            self.events
+                .lock()
+                .unwrap()
+                .push((event.metadata().target().to_string(), visitor.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parsed_documents_are_traced() {
+        let captured = Captured::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(captured.clone()));
+
+        let client =
+            EntsoeClient::with_transport("secret-token", Arc::new(MockTransport::forecasts()));
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        crate::request_id::scope(
+            "api-request-7".to_string(),
+            client.fetch_range(
+                QueryParams::total_load_forecast(DEFAULT_ZONE, ForecastSource::DayAhead),
+                start,
+                start + chrono::Duration::hours(6),
+            ),
+        )
+        .await
+        .unwrap();
+
+        let events = captured.events.lock().unwrap();
+        let (_, event) = events
+            .iter()
+            .find(|(target, _)| target == TARGET)
+            .expect("no document event");
+        assert_eq!(event["document_type"], "A65");
+        assert_eq!(event["zone"], DEFAULT_ZONE);
+        assert_eq!(event["series"], "1");
+        // The mock serves whole days
+        assert_eq!(event["points"], "24");
+        assert_eq!(event["resolutions"], "PT60M");
+        assert_eq!(event["covered_start"], "2024-06-01T00:00Z");
+        assert_eq!(event["covered_end"], "2024-06-02T00:00Z");
+        assert_eq!(event["created"], "2024-06-01T12:00:00Z");
+        assert!(event["parse_ms"].parse::<f64>().unwrap() >= 0.0);
+
+        let spans = captured.spans.lock().unwrap();
+        let span = &spans["entsoe_request"];
+        assert_eq!(span["document_type"], "A65");
+        assert_eq!(span["request_id"], "api-request-7");
+        assert_eq!(span["points"], "24");
+        assert_eq!(span["resolutions"], "PT60M");
+        assert!(span.contains_key("parse_ms"));
+        assert!(
+            !span["request"].contains("secret-token"),
+            "{}",
+            span["request"]
+        );
+    }
+}
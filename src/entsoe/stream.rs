@@ -0,0 +1,423 @@
+//! Incremental parsing of large documents into points, without building the document first
+
+use chrono::{DateTime, Duration, Utc};
+use futures_util::stream::{self, Stream, StreamExt};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::io::{BufRead, Cursor};
+use std::sync::Arc;
+
+use crate::entsoe::request::QueryParams;
+use crate::entsoe::{
+    EntsoeClient, EntsoeError, MeasureUnit, PeriodGrid, TimestampedPoint, parse_resolution,
+    parse_timestamp,
+};
+
+/// Series attributes needed to place the points that follow
+#[derive(Default)]
+struct SeriesState {
+    unit: MeasureUnit,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    resolution: Option<Duration>,
+    /// Positions of the current period, set up by its first point
+    grid: Option<PeriodGrid>,
+    /// Last quantity of each position of the current period, NaN where none was read yet
+    quantities: Vec<f64>,
+    position: Option<u32>,
+    quantity: Option<f64>,
+}
+
+/// Points of a completed period, by position
+struct PeriodPoints {
+    grid: PeriodGrid,
+    unit: MeasureUnit,
+    resolution: Duration,
+    quantities: std::vec::IntoIter<f64>,
+    position: u32,
+}
+
+impl Iterator for PeriodPoints {
+    type Item = Result<TimestampedPoint, EntsoeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for quantity in self.quantities.by_ref() {
+            self.position += 1;
+            if !quantity.is_finite() {
+                continue;
+            }
+            return Some(
+                self.grid
+                    .timestamp(self.position)
+                    .map(|timestamp| TimestampedPoint {
+                        timestamp,
+                        position: self.position,
+                        quantity,
+                        unit: self.unit,
+                        duration: self.resolution,
+                    }),
+            );
+        }
+        None
+    }
+}
+
+/// Reads the points of every TimeSeries of a GL_MarketDocument, period by period in
+/// document order and by position within a period. Points of different series are not
+/// aggregated. Like
+/// [`Period::timestamped_points`](crate::entsoe::Period::timestamped_points), positions
+/// outside the period are refused, the last point of a repeated position is the one kept
+/// and NaN or infinite quantities are left out. A period is held as one quantity per
+/// position until it closes, as a later point may still repeat an earlier position.
+pub struct PointReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    /// Local names of the open elements
+    path: Vec<Vec<u8>>,
+    series: SeriesState,
+    /// Points of the last completed period not yielded yet
+    ready: Option<PeriodPoints>,
+    done: bool,
+}
+
+impl<R: BufRead> PointReader<R> {
+    pub fn new(source: R) -> Self {
+        let mut reader = Reader::from_reader(source);
+        reader.config_mut().trim_text(true);
+        Self {
+            reader,
+            buf: Vec::new(),
+            path: Vec::new(),
+            series: SeriesState::default(),
+            ready: None,
+            done: false,
+        }
+    }
+
+    fn in_element(&self, names: &[&str]) -> bool {
+        self.path.len() >= names.len()
+            && self.path[self.path.len() - names.len()..]
+                .iter()
+                .zip(names)
+                .all(|(open, name)| open.as_slice() == name.as_bytes())
+    }
+
+    fn handle_text(&mut self, text: &str) -> Result<(), EntsoeError> {
+        let invalid =
+            |what: &str| EntsoeError::InvalidResponse(format!("Invalid {}: {}", what, text));
+
+        if self.in_element(&["Point", "position"]) {
+            self.series.position = Some(text.parse().map_err(|_| invalid("position"))?);
+        } else if self.in_element(&["Point", "quantity"]) {
+            self.series.quantity = Some(text.parse().map_err(|_| invalid("quantity"))?);
+        } else if self.in_element(&["Period", "timeInterval", "start"]) {
+            self.series.start = Some(parse_timestamp(text)?);
+        } else if self.in_element(&["Period", "timeInterval", "end"]) {
+            self.series.end = Some(parse_timestamp(text)?);
+        } else if self.in_element(&["Period", "resolution"]) {
+            self.series.resolution = Some(parse_resolution(text)?);
+        } else if self.in_element(&["TimeSeries", "quantity_Measure_Unit.name"]) {
+            self.series.unit = MeasureUnit::from_code(text)?;
+        }
+
+        Ok(())
+    }
+
+    /// Records the point completed by a closing `</Point>`, replacing an earlier point of
+    /// its position
+    fn take_point(&mut self) -> Result<(), EntsoeError> {
+        let (Some(start), Some(end), Some(resolution)) =
+            (self.series.start, self.series.end, self.series.resolution)
+        else {
+            return Err(EntsoeError::InvalidResponse(
+                "Point before the period interval and resolution".to_string(),
+            ));
+        };
+        let (Some(position), Some(quantity)) =
+            (self.series.position.take(), self.series.quantity.take())
+        else {
+            return Err(EntsoeError::InvalidResponse(
+                "Point without position or quantity".to_string(),
+            ));
+        };
+
+        let grid = self
+            .series
+            .grid
+            .get_or_insert_with(|| PeriodGrid::new(start, end, resolution));
+        grid.timestamp(position)?;
+        // The grid bounds the position by the length of the period
+        let index = position as usize - 1;
+        if self.series.quantities.len() <= index {
+            self.series.quantities.resize(index + 1, f64::NAN);
+        }
+        self.series.quantities[index] = quantity;
+        Ok(())
+    }
+
+    /// Hands the points of the closing period to [`PointReader::ready`]
+    fn take_period(&mut self) {
+        let quantities = std::mem::take(&mut self.series.quantities);
+        if let (Some(grid), Some(resolution)) = (self.series.grid.take(), self.series.resolution) {
+            self.ready = Some(PeriodPoints {
+                grid,
+                unit: self.series.unit,
+                resolution,
+                quantities: quantities.into_iter(),
+                position: 0,
+            });
+        }
+    }
+
+    fn next_point(&mut self) -> Result<Option<TimestampedPoint>, EntsoeError> {
+        loop {
+            if let Some(point) = self.ready.as_mut().and_then(Iterator::next) {
+                return point.map(Some);
+            }
+            self.ready = None;
+
+            self.buf.clear();
+            let event = self
+                .reader
+                .read_event_into(&mut self.buf)
+                .map_err(quick_xml::DeError::from)?;
+
+            match event {
+                Event::Start(element) => self.path.push(element.local_name().as_ref().to_vec()),
+                Event::Text(text) => {
+                    let text = text
+                        .decode()
+                        .map_err(|e| EntsoeError::InvalidResponse(e.to_string()))?
+                        .into_owned();
+                    self.handle_text(&text)?;
+                }
+                Event::End(element) => {
+                    self.path.pop();
+                    match element.local_name().as_ref() {
+                        b"Point" => self.take_point()?,
+                        b"Period" => self.take_period(),
+                        b"TimeSeries" => self.series = SeriesState::default(),
+                        _ => {}
+                    }
+                }
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PointReader<R> {
+    type Item = Result<TimestampedPoint, EntsoeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let point = self.next_point().transpose();
+        // Stop at the end of the document and after the first error
+        self.done = !matches!(point, Some(Ok(_)));
+        point
+    }
+}
+
+impl EntsoeClient {
+    /// Like [`EntsoeClient::fetch_range`], but yields the points of every series while each
+    /// response is parsed instead of building the document first. Points outside
+    /// `[start, end)` are dropped, so chunk boundaries never repeat a point.
+    pub fn fetch_points_stream(
+        self: Arc<Self>,
+        params: QueryParams,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> impl Stream<Item = Result<TimestampedPoint, EntsoeError>> + Send + 'static {
+        let requests = self.range_requests(params, start, end);
+
+        stream::iter(requests)
+            .then(move |(request, chunk_start, chunk_end)| {
+                let client = self.clone();
+                async move { (client.fetch_body(&request).await, chunk_start, chunk_end) }
+            })
+            .flat_map(|(body, chunk_start, chunk_end)| {
+                let points: Box<dyn Iterator<Item = _> + Send> = match body {
+                    Ok(xml) => Box::new(PointReader::new(Cursor::new(xml.into_bytes())).filter(
+                        move |point| match point {
+                            Ok(point) => {
+                                chunk_start <= point.timestamp && point.timestamp < chunk_end
+                            }
+                            Err(_) => true,
+                        },
+                    )),
+                    Err(e) => Box::new(std::iter::once(Err(e))),
+                };
+                stream::iter(points)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::GlMarketDocument;
+    use crate::entsoe::testing::{MockSeries, MockTransport, gl_document_series, peak_allocation};
+    use chrono::TimeZone;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_reader_yields_points_of_every_series() {
+        let series = [
+            MockSeries {
+                psr_type: Some("B16"),
+                ..MockSeries::new("A69", "10Y1001A1001A83F", &[1.0, 2.0])
+            },
+            MockSeries {
+                psr_type: Some("B19"),
+                unit: "MWH",
+                ..MockSeries::new("A69", "10Y1001A1001A83F", &[3.0, 4.0])
+            },
+        ];
+        let xml = gl_document_series("A69", "2024-06-01T12:00:00Z", start(), 15, &series);
+
+        let points: Vec<TimestampedPoint> = PointReader::new(xml.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let quantities: Vec<f64> = points.iter().map(|p| p.quantity).collect();
+        assert_eq!(quantities, [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(points[1].timestamp, start() + Duration::minutes(15));
+        assert_eq!(points[2].timestamp, start());
+        assert_eq!(points[0].unit, MeasureUnit::Megawatt);
+        assert_eq!(points[3].unit, MeasureUnit::MegawattHour);
+    }
+
+    #[test]
+    fn test_reader_rejects_positions_outside_the_period() {
+        let series = [MockSeries::new("A65", "10Y1001A1001A83F", &[1.0, 2.0])];
+        let xml = gl_document_series("A65", "2024-06-01T12:00:00Z", start(), 15, &series);
+
+        for position in ["0", &i32::MAX.to_string(), &u32::MAX.to_string()] {
+            let bad = xml.replacen(
+                "<position>2</position>",
+                &format!("<position>{}</position>", position),
+                1,
+            );
+            let points: Vec<_> = PointReader::new(bad.as_bytes()).collect();
+
+            // The period is refused before any of its points is yielded, like the document
+            assert!(
+                matches!(points[..], [Err(EntsoeError::InvalidResponse(_))]),
+                "position {} gave {:?}",
+                position,
+                points
+            );
+        }
+    }
+
+    #[test]
+    fn test_reader_keeps_the_last_point_of_a_repeated_position() {
+        let series = [MockSeries::new("A65", "10Y1001A1001A83F", &[1.0, 2.0, 3.0])];
+        let xml = gl_document_series("A65", "2024-06-01T12:00:00Z", start(), 15, &series).replacen(
+            "<position>3</position>",
+            "<position>1</position>",
+            1,
+        );
+
+        let points: Vec<TimestampedPoint> = PointReader::new(xml.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let quantities: Vec<f64> = points.iter().map(|p| p.quantity).collect();
+        assert_eq!(quantities, [3.0, 2.0]);
+    }
+
+    #[test]
+    fn test_reader_yields_the_points_of_the_document() {
+        // The first series starts with a NaN and an infinite quantity, and its third point
+        // repeats the position of the NaN
+        let xml = include_str!("../../tests/fixtures/a69_wind_solar_forecast.xml")
+            .replacen("<quantity>0</quantity>", "<quantity>NaN</quantity>", 1)
+            .replacen("<quantity>0</quantity>", "<quantity>inf</quantity>", 1)
+            .replacen("<position>3</position>", "<position>1</position>", 1);
+        let document: GlMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+        assert!(document.non_finite_points(&Default::default()) > 0);
+
+        let fields = |point: &TimestampedPoint| {
+            (
+                point.timestamp,
+                point.position,
+                point.quantity.to_bits(),
+                point.unit,
+                point.duration,
+            )
+        };
+        let dom: Vec<_> = document
+            .time_series
+            .iter()
+            .flat_map(|series| series.timestamped_points().unwrap())
+            .map(|point| fields(&point))
+            .collect();
+        let streamed: Vec<_> = PointReader::new(xml.as_bytes())
+            .map(|point| fields(&point.unwrap()))
+            .collect();
+
+        assert_eq!(streamed, dom);
+    }
+
+    #[test]
+    fn test_reader_uses_less_memory_than_the_document() {
+        // A year of quarter-hourly values
+        let quantities: Vec<f64> = (0..365 * 96).map(|i| i as f64).collect();
+        let series = [MockSeries::new("A65", "10Y1001A1001A83F", &quantities)];
+        let xml = gl_document_series("A65", "2024-06-01T12:00:00Z", start(), 15, &series);
+
+        let (dom_points, dom_peak) = peak_allocation(|| {
+            let document: GlMarketDocument = quick_xml::de::from_str(&xml).unwrap();
+            document.all_timestamped_points().unwrap().len()
+        });
+        let (streamed_points, stream_peak) = peak_allocation(|| {
+            PointReader::new(xml.as_bytes())
+                .map(|point| point.unwrap().quantity)
+                .sum::<f64>()
+        });
+
+        assert_eq!(dom_points, quantities.len());
+        assert_eq!(streamed_points, quantities.iter().sum::<f64>());
+        // The reader holds no more than the quantities of the one period it reads
+        assert!(
+            stream_peak < quantities.len() * 3 * std::mem::size_of::<f64>()
+                && stream_peak * 5 < dom_peak,
+            "streaming peaked at {} bytes, the document at {} bytes",
+            stream_peak,
+            dom_peak
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_drops_points_outside_each_chunk() {
+        let client = Arc::new(EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(MockTransport::forecasts()),
+        ));
+        let end = start() + Duration::days(400);
+
+        let points: Vec<TimestampedPoint> = client
+            .fetch_points_stream(
+                QueryParams::new("A65")
+                    .process_type("A01")
+                    .out_bidding_zone("10Y1001A1001A83F"),
+                start(),
+                end,
+            )
+            .map(|point| point.unwrap())
+            .collect()
+            .await;
+
+        // Two requests, each answered with a day beyond its end
+        assert_eq!(points.len(), 400 * 24);
+        assert_eq!(points.first().unwrap().timestamp, start());
+        assert_eq!(points.last().unwrap().timestamp, end - Duration::hours(1));
+    }
+}
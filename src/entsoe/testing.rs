@@ -0,0 +1,1020 @@
+//! Test helpers: synthetic ENTSO-E documents and a mock transport
+
+use super::analysis::RenewableSurplus;
+use super::{EntsoeError, Transport, TransportResponse};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, NaiveDateTime, TimeZone, Utc};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::Mutex;
+
+type Handler = Box<dyn Fn(&str) -> TransportResponse + Send + Sync>;
+
+/// Transport answering every request through a closure and recording the requested URLs
+pub(crate) struct MockTransport {
+    handler: Handler,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockTransport {
+    pub(crate) fn new(handler: impl Fn(&str) -> TransportResponse + Send + Sync + 'static) -> Self {
+        Self {
+            handler: Box::new(handler),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Serve hourly load (A65), wind/solar (A69) and total generation (A71) forecasts
+    /// covering the requested period, and day-ahead prices (A44) of 100 EUR, or 400 PLN
+    /// in Poland.
+    /// Documents are aligned to whole days so repeated requests yield identical data.
+    pub(crate) fn forecasts() -> Self {
+        Self::truncated_forecasts(usize::MAX)
+    }
+
+    /// Like [`MockTransport::forecasts`], with documents ending after `hours`, as
+    /// upstream answers while later days are not published yet
+    pub(crate) fn truncated_forecasts(hours: usize) -> Self {
+        Self::new(move |url| {
+            let start = query_param(url, "periodStart")
+                .and_then(|s| parse_period(&s))
+                .expect("request without periodStart")
+                .duration_trunc(Duration::days(1))
+                .unwrap();
+            let end = query_param(url, "periodEnd")
+                .and_then(|s| parse_period(&s))
+                .expect("request without periodEnd");
+            let days = (end - start).num_days() + 1;
+            let hours = ((days * 24) as usize).min(hours);
+
+            let doc_type = query_param(url, "documentType").expect("request without documentType");
+
+            if doc_type == "A44" {
+                let zone = query_param(url, "in_Domain").expect("request without in_Domain");
+                let (currency, price) = if zone == "10YPL-AREA-----S" {
+                    ("PLN", 400.0)
+                } else {
+                    ("EUR", 100.0)
+                };
+                return ok(price_document(
+                    &zone,
+                    currency,
+                    start,
+                    60,
+                    &vec![price; hours],
+                ));
+            }
+
+            if query_param(url, "processType").as_deref() == Some("A31") {
+                let zone = query_param(url, "outBiddingZone_Domain")
+                    .expect("request without outBiddingZone_Domain");
+                return ok(week_ahead_load_document(&zone, start, days as usize));
+            }
+
+            let quantities: Vec<f64> = match doc_type.as_str() {
+                "A65" => vec![50_000.0; hours],
+                "A71" => vec![70_000.0; hours],
+                "A69" => (0..hours)
+                    .map(|i| 40_000.0 + (i % 24) as f64 * 1_000.0)
+                    .collect(),
+                other => panic!("unexpected documentType {}", other),
+            };
+
+            let zone = query_param(url, "outBiddingZone_Domain")
+                .or_else(|| query_param(url, "in_Domain"))
+                .expect("request without bidding zone");
+            let series = MockSeries::new(&doc_type, &zone, &quantities);
+
+            ok(gl_document_series(
+                &doc_type,
+                "2024-06-01T12:00:00Z",
+                start,
+                60,
+                &[series],
+            ))
+        })
+    }
+
+    /// Hourly forecasts of whole days like [`MockTransport::forecasts`], with the wind and
+    /// solar forecast (A69) split into solar (B16), onshore (B19) and offshore wind (B18).
+    /// Solar peaks at noon UTC with 12 GW, wind blows 8 GW onshore and 2 GW offshore, the
+    /// load is 30 GW.
+    pub(crate) fn split_forecasts() -> Self {
+        Self::new(|url| {
+            let start = query_param(url, "periodStart")
+                .and_then(|s| parse_period(&s))
+                .expect("request without periodStart")
+                .duration_trunc(Duration::days(1))
+                .unwrap();
+            let end = query_param(url, "periodEnd")
+                .and_then(|s| parse_period(&s))
+                .expect("request without periodEnd");
+            let hours = ((end - start).num_days() as usize + 1) * 24;
+            let zone = query_param(url, "outBiddingZone_Domain")
+                .or_else(|| query_param(url, "in_Domain"))
+                .expect("request without bidding zone");
+
+            let doc_type = query_param(url, "documentType").expect("request without documentType");
+            let typed = |psr_type, quantities| MockSeries {
+                psr_type: Some(psr_type),
+                ..MockSeries::new("A69", &zone, quantities)
+            };
+            let document = |series: &[MockSeries]| {
+                ok(gl_document_series(
+                    &doc_type,
+                    "2024-06-01T12:00:00Z",
+                    start,
+                    60,
+                    series,
+                ))
+            };
+            match doc_type.as_str() {
+                "A65" => document(&[MockSeries::new("A65", &zone, &vec![30_000.0; hours])]),
+                "A71" => document(&[MockSeries::new("A71", &zone, &vec![70_000.0; hours])]),
+                "A69" => {
+                    let solar: Vec<f64> = (0..hours)
+                        .map(|i| (12_000.0 - (i % 24).abs_diff(12) as f64 * 2_000.0).max(0.0))
+                        .collect();
+                    let (onshore, offshore) = (vec![8_000.0; hours], vec![2_000.0; hours]);
+                    document(&[
+                        typed("B16", &solar),
+                        typed("B19", &onshore),
+                        typed("B18", &offshore),
+                    ])
+                }
+                other => panic!("unexpected documentType {}", other),
+            }
+        })
+    }
+
+    /// Like [`MockTransport::forecasts`], answering "no matching data" for documents of
+    /// `document_type`, as upstream does before they are published
+    pub(crate) fn forecasts_without(document_type: &'static str) -> Self {
+        let forecasts = Self::forecasts();
+        Self::new(move |url| {
+            match query_param(url, "documentType").as_deref() == Some(document_type) {
+                true => ok(NO_MATCHING_DATA),
+                false => (forecasts.handler)(url),
+            }
+        })
+    }
+
+    /// Like [`MockTransport::forecasts`], also answering the actual generation (A75) and
+    /// load (A65 of process type A16) hour by hour: a load of 40 GW, 10 GW of gas and
+    /// onshore wind of 48 GW at even and 38 GW at odd hours (UTC)
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn forecasts_and_actuals() -> Self {
+        let forecasts = Self::forecasts();
+        Self::new(move |url| {
+            let period = |name| {
+                query_param(url, name)
+                    .and_then(|s| parse_period(&s))
+                    .expect("request without period")
+            };
+            let (start, end) = (period("periodStart"), period("periodEnd"));
+            let hours = (end - start).num_hours() as usize;
+            let zone = query_param(url, "outBiddingZone_Domain")
+                .or_else(|| query_param(url, "in_Domain"))
+                .expect("request without bidding zone");
+            let document = |doc_type, series: &[MockSeries]| {
+                ok(gl_document_series(
+                    doc_type,
+                    "2024-06-05T00:00:00Z",
+                    start,
+                    60,
+                    series,
+                ))
+            };
+            match (
+                query_param(url, "documentType").as_deref(),
+                query_param(url, "processType").as_deref(),
+            ) {
+                (Some("A75"), _) => {
+                    let wind: Vec<f64> = (0..hours)
+                        .map(|hour| if hour % 2 == 0 { 48_000.0 } else { 38_000.0 })
+                        .collect();
+                    let typed = |psr_type, quantities| MockSeries {
+                        psr_type: Some(psr_type),
+                        ..MockSeries::new("A75", &zone, quantities)
+                    };
+                    document(
+                        "A75",
+                        &[typed("B19", &wind), typed("B04", &vec![10_000.0; hours])],
+                    )
+                }
+                (Some("A65"), Some("A16")) => document(
+                    "A65",
+                    &[MockSeries::new("A65", &zone, &vec![40_000.0; hours])],
+                ),
+                _ => (forecasts.handler)(url),
+            }
+        })
+    }
+
+    /// All URLs requested so far
+    pub(crate) fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+        self.requests.lock().unwrap().push(url.to_string());
+        Ok((self.handler)(url))
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl super::blocking::Transport for MockTransport {
+    fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+        self.requests.lock().unwrap().push(url.to_string());
+        Ok((self.handler)(url))
+    }
+}
+
+/// Small xorshift64 generator for randomized tests, seeded so failures reproduce
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    /// A seed of 0, which xorshift never leaves, is taken as 1
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// `items` reordered by a Fisher-Yates shuffle drawing from [`XorShift`] seeded with `seed`
+pub(crate) fn shuffled<T: Clone>(items: &[T], seed: u64) -> Vec<T> {
+    let mut random = XorShift::new(seed);
+    let mut items = items.to_vec();
+    for i in (1..items.len()).rev() {
+        items.swap(i, (random.next_u64() % (i as u64 + 1)) as usize);
+    }
+    items
+}
+
+/// Acknowledgement upstream answers requests without data with
+pub(crate) const NO_MATCHING_DATA: &str = "<Acknowledgement_MarketDocument><Reason><code>999</code><text>No matching data found</text></Reason></Acknowledgement_MarketDocument>";
+
+/// A 200 response with the given body
+pub(crate) fn ok(body: impl Into<String>) -> TransportResponse {
+    TransportResponse {
+        status: 200,
+        body: body.into(),
+        retry_after: None,
+    }
+}
+
+/// Extract a query parameter from a URL
+pub(crate) fn query_param(url: &str, name: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+fn parse_period(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M")
+        .ok()
+        .map(|dt| dt.and_utc())
+}
+
+fn format_xml_time(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%dT%H:%MZ").to_string()
+}
+
+/// 24 hourly points from midnight of 2024-06-01, generation rising by 1 GW an hour
+/// against a flat load of 45 GW
+pub(crate) fn sample_surplus() -> Vec<RenewableSurplus> {
+    let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+    (0..24)
+        .map(|i| {
+            let generation = 20_000.0 + 1_000.0 * i as f64;
+            let load = 45_000.0;
+            RenewableSurplus {
+                timestamp: start + Duration::hours(i),
+                generation,
+                load,
+                surplus: generation - load,
+                total_generation: None,
+            }
+        })
+        .collect()
+}
+
+/// Build a GL_MarketDocument with a single TimeSeries of consecutive points
+pub(crate) fn gl_document(
+    doc_type: &str,
+    start: DateTime<Utc>,
+    resolution_minutes: i64,
+    quantities: &[f64],
+) -> String {
+    gl_document_created(
+        doc_type,
+        "2024-06-01T12:00:00Z",
+        start,
+        resolution_minutes,
+        quantities,
+    )
+}
+
+/// Like [`gl_document`], with an explicit `createdDateTime`
+pub(crate) fn gl_document_created(
+    doc_type: &str,
+    created: &str,
+    start: DateTime<Utc>,
+    resolution_minutes: i64,
+    quantities: &[f64],
+) -> String {
+    let series = MockSeries::new(doc_type, DEFAULT_ZONE, quantities);
+    gl_document_series(doc_type, created, start, resolution_minutes, &[series])
+}
+
+/// A document of `doc_type` whose one TimeSeries has a Period without `Point` elements
+pub(crate) fn empty_period_document(doc_type: &str, start: DateTime<Utc>) -> String {
+    let series = MockSeries::new(doc_type, DEFAULT_ZONE, &[]);
+    gl_document_series(doc_type, "2024-06-01T12:00:00Z", start, 60, &[series])
+}
+
+/// A document of `doc_type` without any TimeSeries
+pub(crate) fn document_without_series(doc_type: &str, start: DateTime<Utc>) -> String {
+    gl_document_series(doc_type, "2024-06-01T12:00:00Z", start, 60, &[])
+}
+
+/// Hourly actual generation (A75) of four hours: 3 GW solar, 1 GW gas, 500 MW nuclear,
+/// 500 MW of the unknown production type `B99` and 500 MW pumping consumption
+pub(crate) fn actual_generation_document(start: DateTime<Utc>) -> String {
+    let generation = |psr_type, quantities| MockSeries {
+        psr_type: Some(psr_type),
+        ..MockSeries::new("A75", DEFAULT_ZONE, quantities)
+    };
+    let pumping = MockSeries {
+        psr_type: Some("B10"),
+        ..MockSeries::new("A65", DEFAULT_ZONE, &[500.0; 4])
+    };
+    gl_document_series(
+        "A75",
+        "2024-06-01T12:00:00Z",
+        start,
+        60,
+        &[
+            generation("B16", &[3_000.0; 4]),
+            generation("B04", &[1_000.0; 4]),
+            generation("B14", &[500.0; 4]),
+            generation("B99", &[500.0; 4]),
+            pumping,
+        ],
+    )
+}
+
+/// Hourly generation per unit (A73) of two hours: two offshore wind parks and a nuclear
+/// plant reporting only the first hour
+pub(crate) fn generation_per_unit_document(start: DateTime<Utc>) -> String {
+    let unit = |psr_type, mrid, name, quantities| MockSeries {
+        psr_type: Some(psr_type),
+        resource: Some((mrid, name)),
+        ..MockSeries::new("A73", DEFAULT_ZONE, quantities)
+    };
+    gl_document_series(
+        "A73",
+        "2024-06-01T12:00:00Z",
+        start,
+        60,
+        &[
+            unit(
+                "B18",
+                "11W0-BORKUM-2",
+                "Borkum Riffgrund 2",
+                &[400.0, 300.0],
+            ),
+            unit("B14", "11WD2ISA2-K", "Isar 2", &[1_400.0]),
+            unit(
+                "B18",
+                "11W0-BORKUM-1",
+                "Borkum Riffgrund 1",
+                &[200.0, 100.0],
+            ),
+        ],
+    )
+}
+
+/// Week-ahead load forecast (A65, processType A31) of `days` days: a minimum of 45 GW
+/// and a maximum of 55 GW each day
+pub(crate) fn week_ahead_load_document(zone: &str, start: DateTime<Utc>, days: usize) -> String {
+    let (min, max) = (vec![45_000.0; days], vec![55_000.0; days]);
+    let bound = |business_type, quantities| MockSeries {
+        business_type,
+        ..MockSeries::new("A65", zone, quantities)
+    };
+    gl_document_series(
+        "A65",
+        "2024-05-30T12:00:00Z",
+        start,
+        24 * 60,
+        &[bound("A60", &min), bound("A61", &max)],
+    )
+    .replace(
+        "<resolution>PT1440M</resolution>",
+        "<resolution>P1D</resolution>",
+    )
+}
+
+/// Hourly day-ahead load forecast (A65) of Germany of one day laid out per control area:
+/// 20 GW in Amprion, 15 GW in TenneT, 10 GW in TransnetBW and 5 GW in 50Hertz. With
+/// `national`, the national series of 50 GW comes alongside, as some zones answer.
+pub(crate) fn control_area_load_document(start: DateTime<Utc>, national: bool) -> String {
+    let loads = [
+        ("10YDE-RWENET---I", [20_000.0; 24]),
+        ("10YDE-EON------1", [15_000.0; 24]),
+        ("10YDE-ENBW-----N", [10_000.0; 24]),
+        ("10YDE-VE-------2", [5_000.0; 24]),
+        (DEFAULT_ZONE, [50_000.0; 24]),
+    ];
+    let series: Vec<MockSeries> = loads
+        .iter()
+        .take(if national { 5 } else { 4 })
+        .map(|(zone, quantities)| MockSeries::new("A65", zone, quantities))
+        .collect();
+    gl_document_series("A65", "2024-06-01T12:00:00Z", start, 60, &series)
+}
+
+/// Bidding zone of documents built without an explicit zone (Germany)
+pub(crate) const DEFAULT_ZONE: &str = "10Y1001A1001A83F";
+
+/// One TimeSeries of a synthetic document
+pub(crate) struct MockSeries<'a> {
+    pub business_type: &'a str,
+    pub psr_type: Option<&'a str>,
+    pub in_bidding_zone: Option<&'a str>,
+    pub out_bidding_zone: Option<&'a str>,
+    /// `quantity_Measure_Unit.name`
+    pub unit: &'a str,
+    /// EIC code and name of the production unit, laid out like A73 documents
+    pub resource: Option<(&'a str, &'a str)>,
+    pub quantities: &'a [f64],
+}
+
+impl<'a> MockSeries<'a> {
+    /// A series laid out like ENTSO-E does for `doc_type`: load on the out side of
+    /// `zone`, generation on the in side
+    pub(crate) fn new(doc_type: &str, zone: &'a str, quantities: &'a [f64]) -> Self {
+        let (in_bidding_zone, out_bidding_zone) = match doc_type {
+            "A65" => (None, Some(zone)),
+            _ => (Some(zone), None),
+        };
+        Self {
+            business_type: "A04",
+            psr_type: None,
+            in_bidding_zone,
+            out_bidding_zone,
+            unit: "MAW",
+            resource: None,
+            quantities,
+        }
+    }
+}
+
+/// Build a GL_MarketDocument with one TimeSeries per entry of `series`, all
+/// starting at `start`
+pub(crate) fn gl_document_series(
+    doc_type: &str,
+    created: &str,
+    start: DateTime<Utc>,
+    resolution_minutes: i64,
+    series: &[MockSeries],
+) -> String {
+    let longest = series.iter().map(|s| s.quantities.len()).max().unwrap_or(0);
+    let end = start + Duration::minutes(resolution_minutes * longest as i64);
+    let time_series: String = series
+        .iter()
+        .enumerate()
+        .map(|(i, series)| time_series_xml(i + 1, series, start, resolution_minutes))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<GL_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-6:generationloaddocument:3:0">
+    <mRID>mock</mRID>
+    <revisionNumber>1</revisionNumber>
+    <type>{doc_type}</type>
+    <process.processType>A01</process.processType>
+    <sender_MarketParticipant.mRID codingScheme="A01">10X1001A1001A450</sender_MarketParticipant.mRID>
+    <sender_MarketParticipant.marketRole.type>A32</sender_MarketParticipant.marketRole.type>
+    <receiver_MarketParticipant.mRID codingScheme="A01">10X1001A1001A450</receiver_MarketParticipant.mRID>
+    <receiver_MarketParticipant.marketRole.type>A33</receiver_MarketParticipant.marketRole.type>
+    <createdDateTime>{created}</createdDateTime>
+    <time_Period.timeInterval>
+        <start>{start}</start>
+        <end>{end}</end>
+    </time_Period.timeInterval>
+    {time_series}
+</GL_MarketDocument>"#,
+        start = format_xml_time(start),
+        end = format_xml_time(end),
+    )
+}
+
+fn time_series_xml(
+    mrid: usize,
+    series: &MockSeries,
+    start: DateTime<Utc>,
+    resolution_minutes: i64,
+) -> String {
+    let end = start + Duration::minutes(resolution_minutes * series.quantities.len() as i64);
+    let points: String = series
+        .quantities
+        .iter()
+        .enumerate()
+        .map(|(i, quantity)| {
+            format!(
+                "<Point><position>{}</position><quantity>{}</quantity></Point>",
+                i + 1,
+                quantity
+            )
+        })
+        .collect();
+    let in_zone = series
+        .in_bidding_zone
+        .map(|zone| {
+            format!(
+                r#"<inBiddingZone_Domain.mRID codingScheme="A01">{}</inBiddingZone_Domain.mRID>"#,
+                zone
+            )
+        })
+        .unwrap_or_default();
+    let out_zone = series
+        .out_bidding_zone
+        .map(|zone| {
+            format!(
+                r#"<outBiddingZone_Domain.mRID codingScheme="A01">{}</outBiddingZone_Domain.mRID>"#,
+                zone
+            )
+        })
+        .unwrap_or_default();
+    let registered_resource = series
+        .resource
+        .map(|(mrid, _)| {
+            format!(
+                r#"<registeredResource.mRID codingScheme="A01">{}</registeredResource.mRID>"#,
+                mrid
+            )
+        })
+        .unwrap_or_default();
+    let power_system_resources = series
+        .resource
+        .map(|(mrid, name)| {
+            format!(
+                r#"<PowerSystemResources><mRID codingScheme="A01">{}</mRID><name>{}</name></PowerSystemResources>"#,
+                mrid, name
+            )
+        })
+        .unwrap_or_default();
+    let psr_type = series
+        .psr_type
+        .map(|psr_type| {
+            format!(
+                "<MktPSRType><psrType>{}</psrType>{}</MktPSRType>",
+                psr_type, power_system_resources
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        r#"<TimeSeries>
+        <mRID>{mrid}</mRID>
+        <businessType>{business_type}</businessType>
+        <objectAggregation>A01</objectAggregation>
+        {in_zone}
+        {out_zone}
+        {registered_resource}
+        <quantity_Measure_Unit.name>{unit}</quantity_Measure_Unit.name>
+        <curveType>A01</curveType>
+        {psr_type}
+        <Period>
+            <timeInterval>
+                <start>{start}</start>
+                <end>{end}</end>
+            </timeInterval>
+            <resolution>PT{resolution_minutes}M</resolution>
+            {points}
+        </Period>
+    </TimeSeries>"#,
+        business_type = series.business_type,
+        unit = series.unit,
+        start = format_xml_time(start),
+        end = format_xml_time(end),
+    )
+}
+
+/// Allocator recording the heap use of each thread, so tests can compare peak memory
+struct TrackingAllocator;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+fn record_allocation(delta: isize) {
+    // Thread locals may already be gone while a thread shuts down
+    let _ = ALLOCATED.try_with(|allocated| {
+        let now = allocated.get() + delta;
+        allocated.set(now);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+    });
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            record_allocation(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        record_allocation(-(layout.size() as isize));
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+/// Run `f` and report how far the heap use of the current thread grew at its peak
+pub(crate) fn peak_allocation<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let base = ALLOCATED.with(Cell::get);
+    PEAK.with(|peak| peak.set(base));
+    let result = f();
+    let peak = PEAK.with(Cell::get) - base;
+    (result, peak.max(0) as usize)
+}
+
+/// Build a Balancing_MarketDocument (A83) with one aFRR series per `(direction, quantities)`,
+/// all starting at `start`
+pub(crate) fn balancing_document(
+    start: DateTime<Utc>,
+    resolution_minutes: i64,
+    series: &[(&str, &[f64])],
+) -> String {
+    let longest = series.iter().map(|(_, q)| q.len()).max().unwrap_or(0);
+    let end = start + Duration::minutes(resolution_minutes * longest as i64);
+    let time_series: String = series
+        .iter()
+        .enumerate()
+        .map(|(i, (direction, quantities))| {
+            let points: String = quantities
+                .iter()
+                .enumerate()
+                .map(|(i, quantity)| {
+                    format!(
+                        "<Point><position>{}</position><quantity>{}</quantity></Point>",
+                        i + 1,
+                        quantity
+                    )
+                })
+                .collect();
+            format!(
+                r#"<TimeSeries>
+        <mRID>{mrid}</mRID>
+        <businessType>A96</businessType>
+        <flowDirection.direction>{direction}</flowDirection.direction>
+        <quantity_Measure_Unit.name>MAW</quantity_Measure_Unit.name>
+        <curveType>A01</curveType>
+        <Period>
+            <timeInterval><start>{start}</start><end>{end}</end></timeInterval>
+            <resolution>PT{resolution_minutes}M</resolution>
+            {points}
+        </Period>
+    </TimeSeries>"#,
+                mrid = i + 1,
+                start = format_xml_time(start),
+                end = format_xml_time(
+                    start + Duration::minutes(resolution_minutes * quantities.len() as i64)
+                ),
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<Balancing_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-6:balancingdocument:4:4">
+    <mRID>mock</mRID>
+    <revisionNumber>1</revisionNumber>
+    <type>A83</type>
+    <process.processType>A16</process.processType>
+    <createdDateTime>2024-06-01T12:00:00Z</createdDateTime>
+    <area_Domain.mRID codingScheme="A01">{DEFAULT_ZONE}</area_Domain.mRID>
+    <period.timeInterval>
+        <start>{start}</start>
+        <end>{end}</end>
+    </period.timeInterval>
+    {time_series}
+</Balancing_MarketDocument>"#,
+        start = format_xml_time(start),
+        end = format_xml_time(end),
+    )
+}
+
+/// Build a Publication_MarketDocument with a single series of consecutive points, sent
+/// from the default zone into France
+pub(crate) fn publication_document(
+    doc_type: &str,
+    start: DateTime<Utc>,
+    resolution_minutes: i64,
+    quantities: &[f64],
+) -> String {
+    let end = start + Duration::minutes(resolution_minutes * quantities.len() as i64);
+    let points: String = quantities
+        .iter()
+        .enumerate()
+        .map(|(i, quantity)| {
+            format!(
+                "<Point><position>{}</position><quantity>{}</quantity></Point>",
+                i + 1,
+                quantity
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<Publication_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-3:publicationdocument:7:0">
+    <mRID>mock</mRID>
+    <revisionNumber>1</revisionNumber>
+    <type>{doc_type}</type>
+    <createdDateTime>2024-06-01T12:00:00Z</createdDateTime>
+    <period.timeInterval>
+        <start>{start}</start>
+        <end>{end}</end>
+    </period.timeInterval>
+    <TimeSeries>
+        <mRID>1</mRID>
+        <businessType>A26</businessType>
+        <in_Domain.mRID codingScheme="A01">10YFR-RTE------C</in_Domain.mRID>
+        <out_Domain.mRID codingScheme="A01">{DEFAULT_ZONE}</out_Domain.mRID>
+        <quantity_Measure_Unit.name>MAW</quantity_Measure_Unit.name>
+        <curveType>A01</curveType>
+        <Period>
+            <timeInterval><start>{start}</start><end>{end}</end></timeInterval>
+            <resolution>PT{resolution_minutes}M</resolution>
+            {points}
+        </Period>
+    </TimeSeries>
+</Publication_MarketDocument>"#,
+        start = format_xml_time(start),
+        end = format_xml_time(end),
+    )
+}
+
+/// Day-ahead prices (A44) of `zone` in `currency` per MWh
+pub(crate) fn price_document(
+    zone: &str,
+    currency: &str,
+    start: DateTime<Utc>,
+    resolution_minutes: i64,
+    prices: &[f64],
+) -> String {
+    let end = start + Duration::minutes(resolution_minutes * prices.len() as i64);
+    let points: String = prices
+        .iter()
+        .enumerate()
+        .map(|(i, price)| {
+            format!(
+                "<Point><position>{}</position><price.amount>{}</price.amount></Point>",
+                i + 1,
+                price
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<Publication_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-3:publicationdocument:7:3">
+    <mRID>mock</mRID>
+    <revisionNumber>1</revisionNumber>
+    <type>A44</type>
+    <createdDateTime>2024-06-01T12:00:00Z</createdDateTime>
+    <period.timeInterval>
+        <start>{start}</start>
+        <end>{end}</end>
+    </period.timeInterval>
+    <TimeSeries>
+        <mRID>1</mRID>
+        <businessType>A62</businessType>
+        <in_Domain.mRID codingScheme="A01">{zone}</in_Domain.mRID>
+        <out_Domain.mRID codingScheme="A01">{zone}</out_Domain.mRID>
+        <currency_Unit.name>{currency}</currency_Unit.name>
+        <price_Measure_Unit.name>MWH</price_Measure_Unit.name>
+        <curveType>A01</curveType>
+        <Period>
+            <timeInterval><start>{start}</start><end>{end}</end></timeInterval>
+            <resolution>PT{resolution_minutes}M</resolution>
+            {points}
+        </Period>
+    </TimeSeries>
+</Publication_MarketDocument>"#,
+        start = format_xml_time(start),
+        end = format_xml_time(end),
+    )
+}
+
+/// One outage of a synthetic Unavailability_MarketDocument (A80)
+pub(crate) struct MockOutage<'a> {
+    pub mrid: &'a str,
+    pub revision: u32,
+    /// With `docStatus` A09
+    pub cancelled: bool,
+    /// Planned maintenance (A53) rather than a forced outage (A54)
+    pub planned: bool,
+    /// EIC code and name of the unit
+    pub unit: (&'a str, &'a str),
+    pub nominal_mw: f64,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Positions and available MW of one period at a resolution of a minute, none
+    /// without any period
+    pub available: &'a [(u32, f64)],
+}
+
+impl<'a> MockOutage<'a> {
+    /// A forced outage of the whole 1.4 GW nuclear unit Isar 2 over `[start, end)`
+    pub(crate) fn new(mrid: &'a str, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self {
+            mrid,
+            revision: 1,
+            cancelled: false,
+            planned: false,
+            unit: ("11WD2ISA2-K", "Isar 2"),
+            nominal_mw: 1_400.0,
+            start,
+            end,
+            available: &[],
+        }
+    }
+}
+
+/// Build the Unavailability_MarketDocument of an outage in Germany
+pub(crate) fn outage_document(outage: &MockOutage) -> String {
+    let (start, end) = (format_xml_time(outage.start), format_xml_time(outage.end));
+    let status = match outage.cancelled {
+        true => "<docStatus><value>A09</value></docStatus>",
+        false => "",
+    };
+    let period = match outage.available {
+        [] => String::new(),
+        points => {
+            let points: String = points
+                .iter()
+                .map(|(position, quantity)| {
+                    format!(
+                        "<Point><position>{}</position><quantity>{}</quantity></Point>",
+                        position, quantity
+                    )
+                })
+                .collect();
+            format!(
+                "<Available_Period><timeInterval><start>{start}</start><end>{end}</end></timeInterval><resolution>PT1M</resolution>{points}</Available_Period>"
+            )
+        }
+    };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Unavailability_MarketDocument xmlns="urn:iec62325.351:tc57wg16:451-6:outagedocument:3:0">
+    <mRID>{mrid}</mRID>
+    <revisionNumber>{revision}</revisionNumber>
+    <type>A80</type>
+    <process.processType>A26</process.processType>
+    <createdDateTime>2024-05-31T12:00:00Z</createdDateTime>
+    <unavailability_Time_Period.timeInterval>
+        <start>{start}</start>
+        <end>{end}</end>
+    </unavailability_Time_Period.timeInterval>
+    {status}
+    <TimeSeries>
+        <mRID>1</mRID>
+        <businessType>{business_type}</businessType>
+        <biddingZone_Domain.mRID codingScheme="A01">{DEFAULT_ZONE}</biddingZone_Domain.mRID>
+        <start_DateAndOrTime.date>{start_date}</start_DateAndOrTime.date>
+        <start_DateAndOrTime.time>{start_time}</start_DateAndOrTime.time>
+        <end_DateAndOrTime.date>{end_date}</end_DateAndOrTime.date>
+        <end_DateAndOrTime.time>{end_time}</end_DateAndOrTime.time>
+        <quantity_Measure_Unit.name>MAW</quantity_Measure_Unit.name>
+        <curveType>A03</curveType>
+        <production_RegisteredResource.mRID codingScheme="A01">{unit_mrid}</production_RegisteredResource.mRID>
+        <production_RegisteredResource.name>{unit_name}</production_RegisteredResource.name>
+        <production_RegisteredResource.location.name>Essenbach</production_RegisteredResource.location.name>
+        <production_RegisteredResource.pSRType.psrType>B14</production_RegisteredResource.pSRType.psrType>
+        <production_RegisteredResource.pSRType.powerSystemResources.mRID codingScheme="A01">{unit_mrid}</production_RegisteredResource.pSRType.powerSystemResources.mRID>
+        <production_RegisteredResource.pSRType.powerSystemResources.nominalP unit="MAW">{nominal_mw}</production_RegisteredResource.pSRType.powerSystemResources.nominalP>
+        {period}
+        <Reason>
+            <code>B18</code>
+            <text>Failure</text>
+        </Reason>
+    </TimeSeries>
+</Unavailability_MarketDocument>"#,
+        mrid = outage.mrid,
+        revision = outage.revision,
+        business_type = if outage.planned { "A53" } else { "A54" },
+        start_date = outage.start.format("%Y-%m-%d"),
+        start_time = outage.start.format("%H:%M:%SZ"),
+        end_date = outage.end.format("%Y-%m-%d"),
+        end_time = outage.end.format("%H:%M:%SZ"),
+        unit_mrid = outage.unit.0,
+        unit_name = outage.unit.1,
+        nominal_mw = outage.nominal_mw,
+    )
+}
+
+/// A ZIP archive of the documents of `outages`, one deflated file each, as upstream
+/// answers unavailability queries
+pub(crate) fn outage_archive(outages: &[MockOutage]) -> Vec<u8> {
+    let documents: Vec<(String, String)> = outages
+        .iter()
+        .enumerate()
+        .map(|(i, outage)| {
+            (
+                format!("{:03}-{}.xml", i, outage.mrid),
+                outage_document(outage),
+            )
+        })
+        .collect();
+    let files: Vec<(&str, &[u8], bool)> = documents
+        .iter()
+        .map(|(name, xml)| (name.as_str(), xml.as_bytes(), true))
+        .collect();
+    zip_archive(&files)
+}
+
+/// The archive of `outages` as [`super::ReqwestTransport`] hands it on, its files joined
+pub(crate) fn outages_response(outages: &[MockOutage]) -> TransportResponse {
+    ok(super::decode_body(&outage_archive(outages)).unwrap())
+}
+
+/// A ZIP archive of files given by name, contents and whether to deflate them
+pub(crate) fn zip_archive(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (name, contents, deflate) in files {
+        let data = match deflate {
+            true => {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(contents).unwrap();
+                encoder.finish().unwrap()
+            }
+            false => contents.to_vec(),
+        };
+        let mut crc = flate2::Crc::new();
+        crc.update(contents);
+        // Version needed, flags, method, modification time and date, CRC and sizes
+        let mut common = Vec::new();
+        common.extend(20u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(if *deflate { 8u16 } else { 0u16 }.to_le_bytes());
+        common.extend([0; 4]);
+        common.extend(crc.sum().to_le_bytes());
+        common.extend((data.len() as u32).to_le_bytes());
+        common.extend((contents.len() as u32).to_le_bytes());
+        common.extend((name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+
+        let offset = archive.len() as u32;
+        archive.extend(b"PK\x03\x04");
+        archive.extend(&common);
+        archive.extend(name.as_bytes());
+        archive.extend(&data);
+
+        directory.extend(b"PK\x01\x02");
+        directory.extend(20u16.to_le_bytes());
+        directory.extend(&common);
+        // Comment length, disk, internal and external attributes
+        directory.extend([0; 10]);
+        directory.extend(offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+    }
+
+    let directory_offset = archive.len() as u32;
+    archive.extend(&directory);
+    archive.extend(b"PK\x05\x06");
+    archive.extend([0; 4]);
+    archive.extend((files.len() as u16).to_le_bytes());
+    archive.extend((files.len() as u16).to_le_bytes());
+    archive.extend((directory.len() as u32).to_le_bytes());
+    archive.extend(directory_offset.to_le_bytes());
+    archive.extend(0u16.to_le_bytes());
+    archive
+}
@@ -0,0 +1,222 @@
+//! Cross-border transmission: offered capacity (A61) and physical flows (A11), published
+//! in Publication_MarketDocuments
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::request::{ApiRequest, QueryParams, TimeRange};
+use super::{
+    AreaId, EntsoeClient, EntsoeError, MeasureUnit, Period, TimeInterval, TimestampedPoint,
+    parse_document,
+};
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename = "Publication_MarketDocument")]
+pub struct PublicationMarketDocument {
+    #[serde(rename = "mRID")]
+    pub mrid: String,
+    #[serde(rename = "revisionNumber")]
+    pub revision_number: String,
+    #[serde(rename = "type")]
+    pub doc_type: String,
+    #[serde(rename = "createdDateTime")]
+    pub created_date_time: String,
+    #[serde(rename = "period.timeInterval")]
+    pub time_period_interval: TimeInterval,
+    #[serde(rename = "TimeSeries", default)]
+    pub time_series: Vec<TransmissionSeries>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TransmissionSeries {
+    #[serde(rename = "mRID")]
+    pub mrid: String,
+    #[serde(rename = "businessType")]
+    pub business_type: String,
+    /// Receiving area
+    #[serde(rename = "in_Domain.mRID")]
+    pub in_domain: AreaId,
+    /// Sending area
+    #[serde(rename = "out_Domain.mRID")]
+    pub out_domain: AreaId,
+    #[serde(rename = "quantity_Measure_Unit.name")]
+    pub quantity_measure_unit: String,
+    #[serde(rename = "Period", default)]
+    pub periods: Vec<Period>,
+}
+
+impl PublicationMarketDocument {
+    /// Points of all series in MW, by time
+    pub fn timestamped_points(&self) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        let mut points = Vec::new();
+        for series in &self.time_series {
+            let unit = MeasureUnit::from_code(&series.quantity_measure_unit)?;
+            for period in &series.periods {
+                points.extend(
+                    period
+                        .timestamped_points(unit)
+                        .map_err(|e| e.in_series(&series.mrid))?
+                        .iter()
+                        .map(|point| point.in_unit(MeasureUnit::Megawatt)),
+                );
+            }
+        }
+
+        points.sort_by_key(|point| point.timestamp);
+        Ok(points)
+    }
+}
+
+/// Day-ahead net transfer capacity (A61) from `out_domain` into `in_domain`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OfferedCapacityRequest {
+    pub in_domain: String,
+    pub out_domain: String,
+    pub interval: TimeRange,
+}
+
+impl ApiRequest for OfferedCapacityRequest {
+    type Output = Vec<TimestampedPoint>;
+
+    fn document_type(&self) -> &'static str {
+        "A61"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.in_domain, &self.out_domain]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        let params = QueryParams::offered_capacity(&self.in_domain, &self.out_domain);
+        client.fetch_publication_points(params, self.interval).await
+    }
+}
+
+/// Physical flows (A11) from `out_domain` into `in_domain`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhysicalFlowsRequest {
+    pub in_domain: String,
+    pub out_domain: String,
+    pub interval: TimeRange,
+}
+
+impl ApiRequest for PhysicalFlowsRequest {
+    type Output = Vec<TimestampedPoint>;
+
+    fn document_type(&self) -> &'static str {
+        "A11"
+    }
+
+    fn areas(&self) -> Vec<&str> {
+        vec![&self.in_domain, &self.out_domain]
+    }
+
+    fn interval(&self) -> TimeRange {
+        self.interval
+    }
+
+    async fn send(self, client: &EntsoeClient) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        let params = QueryParams::physical_flows(&self.in_domain, &self.out_domain);
+        client.fetch_publication_points(params, self.interval).await
+    }
+}
+
+impl EntsoeClient {
+    /// Fetch the day-ahead net transfer capacity (A61) from `out_domain` into `in_domain`
+    /// over `[start, end)`
+    pub async fn fetch_offered_capacity(
+        &self,
+        in_domain: &str,
+        out_domain: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        self.fetch(OfferedCapacityRequest {
+            in_domain: in_domain.to_string(),
+            out_domain: out_domain.to_string(),
+            interval: TimeRange::new(start, end),
+        })
+        .await
+    }
+
+    /// Fetch the physical flows (A11) from `out_domain` into `in_domain` over `[start, end)`
+    pub async fn fetch_physical_flows(
+        &self,
+        in_domain: &str,
+        out_domain: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        self.fetch(PhysicalFlowsRequest {
+            in_domain: in_domain.to_string(),
+            out_domain: out_domain.to_string(),
+            interval: TimeRange::new(start, end),
+        })
+        .await
+    }
+
+    async fn fetch_publication_points(
+        &self,
+        params: QueryParams,
+        interval: TimeRange,
+    ) -> Result<Vec<TimestampedPoint>, EntsoeError> {
+        let mut points = Vec::new();
+        for (request, _, _) in self.range_requests(params, interval.start, interval.end) {
+            let xml = self.fetch_body(&request).await?;
+            let document: PublicationMarketDocument = parse_document(&xml, &request)?;
+            points.extend(document.timestamped_points()?);
+        }
+
+        *self.last_success.lock().unwrap() = Some(Utc::now());
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::{MockTransport, ok, publication_document, query_param};
+    use chrono::{Duration, TimeZone};
+    use std::sync::Arc;
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_offered_capacity_and_flows() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            match query_param(url, "documentType").as_deref() {
+                Some("A61") => ok(publication_document("A61", midnight(), 60, &[1200.0; 24])),
+                _ => ok(publication_document("A11", midnight(), 15, &[800.0; 96])),
+            }
+        }));
+        let client = EntsoeClient::with_transport("test-token", transport.clone());
+        let end = midnight() + Duration::days(1);
+
+        let capacity = client
+            .fetch_offered_capacity("10YFR-RTE------C", "10Y1001A1001A83F", midnight(), end)
+            .await
+            .unwrap();
+        let flows = client
+            .fetch_physical_flows("10YFR-RTE------C", "10Y1001A1001A83F", midnight(), end)
+            .await
+            .unwrap();
+
+        assert_eq!(capacity.len(), 24);
+        assert_eq!(flows.len(), 96);
+        let requests = transport.requests();
+        assert_eq!(
+            query_param(&requests[0], "contract_MarketAgreement.Type").as_deref(),
+            Some("A01")
+        );
+        assert_eq!(
+            query_param(&requests[1], "out_Domain").as_deref(),
+            Some("10Y1001A1001A83F")
+        );
+    }
+}
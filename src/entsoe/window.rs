@@ -0,0 +1,149 @@
+//! Intervals queries cover: the hours ahead of now, a calendar day in local time or an
+//! explicit range, and the upstream period parameters they translate to
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Deserialize;
+
+use super::format_period;
+use super::localtime::LocalZone;
+
+/// How the bounds of a [`TimeWindow`] were chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowKind {
+    /// From now, shifting with every request
+    NextHours,
+    /// Midnight to midnight in a local time zone, 23 or 25 hours on clock change days
+    CalendarDay,
+    /// Bounds given by the caller
+    Explicit,
+}
+
+/// A local calendar day relative to now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CalendarDay {
+    Today,
+    Tomorrow,
+}
+
+/// Interval `[start, end)` of a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub kind: WindowKind,
+}
+
+impl TimeWindow {
+    pub fn between(start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            end,
+            kind: WindowKind::Explicit,
+        }
+    }
+
+    /// The next `hours` from the current minute
+    pub fn next_hours(hours: i64) -> Self {
+        Self::next_hours_from(Utc::now(), hours)
+    }
+
+    /// The `hours` following `now`, truncated to the minute since upstream periods have
+    /// minute precision
+    pub fn next_hours_from(now: DateTime<Utc>, hours: i64) -> Self {
+        let start = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+        Self {
+            start,
+            end: start + Duration::hours(hours),
+            kind: WindowKind::NextHours,
+        }
+    }
+
+    pub fn today_local(tz: &LocalZone) -> Self {
+        Self::calendar_day_from(Utc::now(), CalendarDay::Today, tz)
+    }
+
+    pub fn tomorrow_local(tz: &LocalZone) -> Self {
+        Self::calendar_day_from(Utc::now(), CalendarDay::Tomorrow, tz)
+    }
+
+    /// `day` as seen from `now` in `tz`, from local midnight to the next
+    pub fn calendar_day_from(now: DateTime<Utc>, day: CalendarDay, tz: &LocalZone) -> Self {
+        let today = tz.to_local(now).date_naive();
+        let date = match day {
+            CalendarDay::Today => today,
+            CalendarDay::Tomorrow => today + Duration::days(1),
+        };
+        let midnight = |date: chrono::NaiveDate| tz.from_local(date.and_hms_opt(0, 0, 0).unwrap());
+        Self {
+            start: midnight(date),
+            end: midnight(date + Duration::days(1)),
+            kind: WindowKind::CalendarDay,
+        }
+    }
+
+    /// The window starting `lead` earlier, e.g. an hour to include the point currently
+    /// in effect
+    pub fn extended_back(self, lead: Duration) -> Self {
+        Self {
+            start: self.start - lead,
+            ..self
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    /// Whether the bounds move with the current time, so data beyond them is kept
+    pub fn is_relative(&self) -> bool {
+        self.kind == WindowKind::NextHours
+    }
+
+    /// `periodStart` and `periodEnd` of an upstream request covering the window
+    pub fn period(&self) -> (String, String) {
+        (format_period(self.start), format_period(self.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_next_hours_from_the_current_minute() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 42).unwrap();
+        let window = TimeWindow::next_hours_from(now, 6).extended_back(Duration::hours(1));
+
+        assert_eq!(window.start.to_rfc3339(), "2024-06-01T11:00:00+00:00");
+        assert_eq!(window.duration(), Duration::hours(7));
+        assert!(window.is_relative());
+        assert_eq!(
+            window.period(),
+            ("202406011100".to_string(), "202406011800".to_string())
+        );
+    }
+
+    #[test]
+    fn test_calendar_days_in_local_time() {
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+        // Already the 2nd in Berlin
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 22, 30, 0).unwrap();
+
+        let today = TimeWindow::calendar_day_from(now, CalendarDay::Today, &berlin);
+        assert_eq!(today.start.to_rfc3339(), "2024-06-01T22:00:00+00:00");
+        assert_eq!(today.duration(), Duration::hours(24));
+        assert!(!today.is_relative());
+
+        // The autumn clock change makes tomorrow 25 hours long
+        let now = Utc.with_ymd_and_hms(2024, 10, 26, 9, 0, 0).unwrap();
+        let tomorrow = TimeWindow::calendar_day_from(now, CalendarDay::Tomorrow, &berlin);
+        assert_eq!(tomorrow.start.to_rfc3339(), "2024-10-26T22:00:00+00:00");
+        assert_eq!(tomorrow.end.to_rfc3339(), "2024-10-27T23:00:00+00:00");
+        assert_eq!(tomorrow.kind, WindowKind::CalendarDay);
+
+        let utc = TimeWindow::calendar_day_from(now, CalendarDay::Tomorrow, &LocalZone::UTC);
+        assert_eq!(utc.period().0, "202410270000");
+    }
+}
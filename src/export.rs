@@ -0,0 +1,431 @@
+//! File writers for exported forecasts and surplus series
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::entsoe::analysis::SurplusSeries;
+use crate::entsoe::{ForecastKind, TimestampedPoint};
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One JSON object per line (`.jsonl`)
+    JsonLines,
+    /// Apache Parquet (`.parquet`), requires the `parquet` feature
+    Parquet,
+    /// Comma separated values with a header line (`.csv`)
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "jsonl" | "ndjson" => Ok(OutputFormat::JsonLines),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "csv" => Ok(OutputFormat::Csv),
+            other => anyhow::bail!(
+                "Unknown output format {:?}, expected jsonl, parquet or csv",
+                other
+            ),
+        }
+    }
+
+    /// Format implied by the file extension
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .ok_or_else(|| {
+                anyhow::anyhow!("Cannot tell the format of {:?}, pass --format", path)
+            })?;
+        Self::from_name(extension)
+    }
+}
+
+fn rfc3339<S: Serializer>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&timestamp.to_rfc3339())
+}
+
+fn optional_rfc3339<S: Serializer>(
+    timestamp: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match timestamp {
+        Some(timestamp) => rfc3339(timestamp, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Values of one column, in record order
+#[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+pub enum Column {
+    Utf8(Vec<String>),
+    Double(Vec<f64>),
+    OptionalDouble(Vec<Option<f64>>),
+    Timestamp(Vec<DateTime<Utc>>),
+    OptionalTimestamp(Vec<Option<DateTime<Utc>>>),
+}
+
+/// A row of an export file
+pub trait ExportRecord: Serialize + Sized {
+    /// Parquet message type of a record; fields in the order of [`ExportRecord::columns`]
+    const PARQUET_SCHEMA: &'static str;
+
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    fn columns(records: &[Self]) -> Vec<Column>;
+}
+
+/// One point of a surplus series
+#[derive(Debug, Clone, Serialize)]
+pub struct SurplusRecord {
+    pub zone_code: String,
+    #[serde(serialize_with = "rfc3339")]
+    pub timestamp: DateTime<Utc>,
+    pub generation_mw: f64,
+    pub load_mw: f64,
+    pub surplus_mw: f64,
+    pub total_generation_mw: Option<f64>,
+    /// `createdDateTime` of the oldest generation forecast used
+    #[serde(serialize_with = "optional_rfc3339")]
+    pub generation_created_at: Option<DateTime<Utc>>,
+    /// `createdDateTime` of the oldest load forecast used
+    #[serde(serialize_with = "optional_rfc3339")]
+    pub load_created_at: Option<DateTime<Utc>>,
+}
+
+impl SurplusRecord {
+    pub fn from_series(zone_code: &str, series: &SurplusSeries) -> Vec<Self> {
+        series
+            .points
+            .iter()
+            .map(|point| SurplusRecord {
+                zone_code: zone_code.to_string(),
+                timestamp: point.timestamp,
+                generation_mw: point.generation,
+                load_mw: point.load,
+                surplus_mw: point.surplus,
+                total_generation_mw: point.total_generation,
+                generation_created_at: series.generation_doc_meta.map(|m| m.created_date_time),
+                load_created_at: series.load_doc_meta.map(|m| m.created_date_time),
+            })
+            .collect()
+    }
+}
+
+impl ExportRecord for SurplusRecord {
+    const PARQUET_SCHEMA: &'static str = "
+        message surplus {
+            REQUIRED BYTE_ARRAY zone_code (UTF8);
+            REQUIRED INT64 timestamp (TIMESTAMP(MICROS,true));
+            REQUIRED DOUBLE generation_mw;
+            REQUIRED DOUBLE load_mw;
+            REQUIRED DOUBLE surplus_mw;
+            OPTIONAL DOUBLE total_generation_mw;
+            OPTIONAL INT64 generation_created_at (TIMESTAMP(MICROS,true));
+            OPTIONAL INT64 load_created_at (TIMESTAMP(MICROS,true));
+        }
+    ";
+
+    fn columns(records: &[Self]) -> Vec<Column> {
+        vec![
+            Column::Utf8(records.iter().map(|r| r.zone_code.clone()).collect()),
+            Column::Timestamp(records.iter().map(|r| r.timestamp).collect()),
+            Column::Double(records.iter().map(|r| r.generation_mw).collect()),
+            Column::Double(records.iter().map(|r| r.load_mw).collect()),
+            Column::Double(records.iter().map(|r| r.surplus_mw).collect()),
+            Column::OptionalDouble(records.iter().map(|r| r.total_generation_mw).collect()),
+            Column::OptionalTimestamp(records.iter().map(|r| r.generation_created_at).collect()),
+            Column::OptionalTimestamp(records.iter().map(|r| r.load_created_at).collect()),
+        ]
+    }
+}
+
+/// One point of a raw forecast document
+#[derive(Debug, Clone, Serialize)]
+pub struct ForecastRecord {
+    pub zone_code: String,
+    pub document_type: &'static str,
+    #[serde(serialize_with = "rfc3339")]
+    pub timestamp: DateTime<Utc>,
+    pub quantity: f64,
+    /// Unit code as published, e.g. `MAW`
+    pub unit: &'static str,
+    /// `createdDateTime` of the document
+    #[serde(serialize_with = "rfc3339")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl ForecastRecord {
+    pub fn from_points(
+        zone_code: &str,
+        kind: ForecastKind,
+        created_at: DateTime<Utc>,
+        points: &[TimestampedPoint],
+    ) -> Vec<Self> {
+        points
+            .iter()
+            .map(|point| ForecastRecord {
+                zone_code: zone_code.to_string(),
+                document_type: kind.document_type(),
+                timestamp: point.timestamp,
+                quantity: point.quantity,
+                unit: point.unit.code(),
+                created_at,
+            })
+            .collect()
+    }
+}
+
+impl ExportRecord for ForecastRecord {
+    const PARQUET_SCHEMA: &'static str = "
+        message forecast {
+            REQUIRED BYTE_ARRAY zone_code (UTF8);
+            REQUIRED BYTE_ARRAY document_type (UTF8);
+            REQUIRED INT64 timestamp (TIMESTAMP(MICROS,true));
+            REQUIRED DOUBLE quantity;
+            REQUIRED BYTE_ARRAY unit (UTF8);
+            REQUIRED INT64 created_at (TIMESTAMP(MICROS,true));
+        }
+    ";
+
+    fn columns(records: &[Self]) -> Vec<Column> {
+        vec![
+            Column::Utf8(records.iter().map(|r| r.zone_code.clone()).collect()),
+            Column::Utf8(
+                records
+                    .iter()
+                    .map(|r| r.document_type.to_string())
+                    .collect(),
+            ),
+            Column::Timestamp(records.iter().map(|r| r.timestamp).collect()),
+            Column::Double(records.iter().map(|r| r.quantity).collect()),
+            Column::Utf8(records.iter().map(|r| r.unit.to_string()).collect()),
+            Column::Timestamp(records.iter().map(|r| r.created_at).collect()),
+        ]
+    }
+}
+
+/// Write `records` to `path`, replacing the file
+pub fn write_records<R: ExportRecord>(
+    path: &Path,
+    format: OutputFormat,
+    records: &[R],
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::JsonLines => write_json_lines(path, records),
+        OutputFormat::Parquet => write_parquet(path, records),
+        OutputFormat::Csv => write_csv(path, records),
+    }
+}
+
+fn write_csv<R: Serialize>(path: &Path, records: &[R]) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_json_lines<R: Serialize>(path: &Path, records: &[R]) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for record in records {
+        serde_json::to_writer(&mut writer, record)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet<R: ExportRecord>(_path: &Path, _records: &[R]) -> anyhow::Result<()> {
+    anyhow::bail!("Parquet output requires building with the `parquet` feature")
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet<R: ExportRecord>(path: &Path, records: &[R]) -> anyhow::Result<()> {
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    fn micros(timestamp: &DateTime<Utc>) -> i64 {
+        timestamp.timestamp_micros()
+    }
+
+    /// Values of the present entries and the definition level of every entry
+    fn optional<T: Copy, V>(values: &[Option<T>], map: impl Fn(T) -> V) -> (Vec<V>, Vec<i16>) {
+        let present = values.iter().flatten().map(|&value| map(value)).collect();
+        let levels = values
+            .iter()
+            .map(|value| i16::from(value.is_some()))
+            .collect();
+        (present, levels)
+    }
+
+    let schema = Arc::new(parse_message_type(R::PARQUET_SCHEMA)?);
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, Default::default())?;
+    let mut row_group = writer.next_row_group()?;
+    let mut columns = R::columns(records).into_iter();
+
+    while let Some(mut column_writer) = row_group.next_column()? {
+        let column = columns
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Schema has more fields than the record"))?;
+        match column {
+            Column::Utf8(values) => {
+                let values: Vec<ByteArray> = values
+                    .iter()
+                    .map(|value| ByteArray::from(value.as_str()))
+                    .collect();
+                column_writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)?;
+            }
+            Column::Double(values) => {
+                column_writer
+                    .typed::<DoubleType>()
+                    .write_batch(&values, None, None)?;
+            }
+            Column::OptionalDouble(values) => {
+                let (values, levels) = optional(&values, |value| value);
+                column_writer
+                    .typed::<DoubleType>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            Column::Timestamp(values) => {
+                let values: Vec<i64> = values.iter().map(micros).collect();
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&values, None, None)?;
+            }
+            Column::OptionalTimestamp(values) => {
+                let (values, levels) = optional(&values, |value| micros(&value));
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+        }
+        column_writer.close()?;
+    }
+
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::analysis::{DocumentMeta, RenewableSurplus};
+    use chrono::{Duration, TimeZone};
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("educk-{}-{}", std::process::id(), name))
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    fn surplus_records() -> Vec<SurplusRecord> {
+        let series = SurplusSeries {
+            points: (0..24)
+                .map(|i| RenewableSurplus {
+                    timestamp: start() + Duration::hours(i),
+                    generation: 1_000.0 * i as f64,
+                    load: 10_000.0,
+                    surplus: 1_000.0 * i as f64 - 10_000.0,
+                    total_generation: (i % 2 == 0).then_some(30_000.0),
+                })
+                .collect(),
+            generation_doc_meta: Some(DocumentMeta {
+                created_date_time: start() - Duration::hours(12),
+                revision_number: 1,
+            }),
+            ..SurplusSeries::default()
+        };
+        SurplusRecord::from_series("10Y1001A1001A83F", &series)
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            OutputFormat::from_path(Path::new("out/data.jsonl")).unwrap(),
+            OutputFormat::JsonLines
+        );
+        assert_eq!(
+            OutputFormat::from_path(Path::new("surplus.PARQUET")).unwrap(),
+            OutputFormat::Parquet
+        );
+        assert_eq!(
+            OutputFormat::from_path(Path::new("forecast.csv")).unwrap(),
+            OutputFormat::Csv
+        );
+        assert!(OutputFormat::from_path(Path::new("surplus.txt")).is_err());
+        assert!(OutputFormat::from_path(Path::new("surplus")).is_err());
+    }
+
+    #[test]
+    fn test_json_lines_round_trip() {
+        let path = temp_path("surplus.jsonl");
+        let records = surplus_records();
+
+        write_records(&path, OutputFormat::JsonLines, &records).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let rows: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), records.len());
+        assert_eq!(rows[0]["zone_code"], "10Y1001A1001A83F");
+        assert_eq!(rows[3]["timestamp"], "2024-06-01T03:00:00+00:00");
+        assert_eq!(rows[3]["surplus_mw"], -7_000.0);
+        assert_eq!(rows[0]["total_generation_mw"], 30_000.0);
+        assert!(rows[1]["total_generation_mw"].is_null());
+        assert_eq!(
+            rows[0]["generation_created_at"],
+            "2024-05-31T12:00:00+00:00"
+        );
+        assert!(rows[0]["load_created_at"].is_null());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_round_trip() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+
+        let path = temp_path("surplus.parquet");
+        let records = surplus_records();
+
+        write_records(&path, OutputFormat::Parquet, &records).unwrap();
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(rows.len(), records.len());
+        assert_eq!(rows[0].get_string(0).unwrap(), "10Y1001A1001A83F");
+        assert_eq!(
+            rows[3].get_timestamp_micros(1).unwrap(),
+            (start() + Duration::hours(3)).timestamp_micros()
+        );
+        assert_eq!(rows[3].get_double(4).unwrap(), -7_000.0);
+        assert_eq!(rows[0].get_double(5).unwrap(), 30_000.0);
+        assert!(rows[1].get_double(5).is_err());
+        assert_eq!(
+            rows[0].get_timestamp_micros(6).unwrap(),
+            (start() - Duration::hours(12)).timestamp_micros()
+        );
+    }
+}
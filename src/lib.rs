@@ -0,0 +1,27 @@
+//! ENTSO-E load and generation forecasts, renewable surplus analysis and the HTTP API
+//! serving them
+
+pub mod alerts;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod currency;
+pub mod entsoe;
+pub mod export;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod notify;
+pub mod openmetrics;
+#[cfg(feature = "opentelemetry")]
+pub mod otlp;
+pub mod plotting;
+pub mod provider;
+pub mod refresher;
+pub mod request_id;
+pub mod server;
+pub mod snapshot;
+pub mod storage;
+pub mod watch;
+
+#[cfg(feature = "blocking")]
+pub use entsoe::blocking;
@@ -1,15 +1,14 @@
-pub mod entsoe;
-pub mod server;
-
-use crate::entsoe::EntsoeClient;
-use crate::entsoe::analysis::RenewableSurplus;
-use crate::server::start_server;
 use anyhow::Result;
-use plotly::{
-    Plot, Scatter,
-    common::{Mode},
-};
+use educk::cli::{self, Command, SurplusSource};
+use educk::entsoe::analysis::RenewableSurplus;
+use educk::entsoe::{EntsoeClient, ReqwestTransport};
+use educk::provider::CsvForecast;
+use educk::server::start_server;
+use educk::{snapshot, watch};
+use plotly::{Plot, Scatter, common::Mode};
+use std::sync::Arc;
 
+#[allow(dead_code)]
 fn plot_renewable_surplus(surplus_series: &[RenewableSurplus]) {
     // Extract data
     let timestamps: Vec<String> = surplus_series
@@ -57,16 +56,55 @@ fn plot_renewable_surplus(surplus_series: &[RenewableSurplus]) {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let api_key =
-        std::env::var("ENTSOE_API_KEY").expect("ENTSOE_API_KEY environment variable not set");
+    let command = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // The server reads the token itself and still serves metadata without one
+    if let Command::Serve { offline } = &command {
+        return start_server(offline.as_deref()).await;
+    }
 
-    let client = EntsoeClient::new(api_key);
+    // Local forecasts need no token either
+    if let Command::Surplus(args) = &command
+        && let SurplusSource::Csv(path) = &args.selection
+    {
+        return cli::export_surplus(&CsvForecast::load(path)?, args).await;
+    }
 
-    // run once to test it works
-    let _ = client
-        .find_max_renewable_surplus("10YBE----------2", "202308152200", "202308162200")
-        .await?;
+    let api_key = std::env::var("ENTSOE_API_KEY")
+        .map_err(|_| anyhow::anyhow!("ENTSOE_API_KEY environment variable not set"))?;
 
-    start_server().await?;
+    match command {
+        Command::Serve { .. } => unreachable!("handled above"),
+        Command::CheckAuth => {
+            EntsoeClient::new(api_key).validate_key().await?;
+            println!("API key accepted");
+        }
+        Command::Snapshot(args) => {
+            snapshot::write_snapshot(Arc::new(ReqwestTransport::new()), &api_key, &args).await?;
+        }
+        Command::Surplus(args) => {
+            cli::export_surplus(&EntsoeClient::new(api_key), &args).await?;
+        }
+        Command::Watch(args) => {
+            watch::watch(&EntsoeClient::new(api_key), &args).await?;
+        }
+        Command::Forecast(args) => {
+            cli::export_forecast(&EntsoeClient::new(api_key), &args).await?;
+        }
+        Command::Backfill(args) => {
+            let progress = Arc::new(cli::backfill::BackfillProgress::new(&args)?);
+            let client = EntsoeClient::new(api_key).with_progress(progress);
+            cli::backfill::backfill(&client, &args).await?;
+        }
+        Command::Unit(args) => {
+            cli::unit::report_units(&EntsoeClient::new(api_key), &args).await?;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,321 @@
+//! Publishes every background refresh to an MQTT broker as retained messages
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Outgoing, Packet, QoS};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+use crate::config::MqttConfig;
+use crate::entsoe::analysis::{RenewableSurplus, max_surplus};
+use crate::refresher::RefreshEvent;
+
+/// First and longest wait between reconnection attempts
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// How long a clean shutdown waits for the DISCONNECT to be sent
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+/// Destination of published messages, swappable for tests
+#[async_trait]
+pub trait MessageSink: Send + Sync {
+    async fn publish(&self, message: MqttMessage) -> anyhow::Result<()>;
+}
+
+#[derive(Serialize)]
+struct SurplusPayload {
+    timestamp: String,
+    generation_mw: f64,
+    load_mw: f64,
+    surplus_mw: f64,
+}
+
+impl From<&RenewableSurplus> for SurplusPayload {
+    fn from(surplus: &RenewableSurplus) -> Self {
+        Self {
+            timestamp: surplus.timestamp.to_rfc3339(),
+            generation_mw: surplus.generation,
+            load_mw: surplus.load,
+            surplus_mw: surplus.surplus,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SeriesPayload {
+    country_code: String,
+    refreshed_at: String,
+    forecast_created_at: Option<String>,
+    points: Vec<SurplusPayload>,
+}
+
+fn json_message(topic: String, payload: &impl Serialize) -> MqttMessage {
+    MqttMessage {
+        topic,
+        payload: serde_json::to_vec(payload).expect("payload serializes"),
+    }
+}
+
+/// Build the messages for one refresh: the point in effect at `now`, the maximum
+/// ahead of `now` and the full series, under `<prefix>/<country>/surplus/`
+pub fn surplus_messages(
+    prefix: &str,
+    event: &RefreshEvent,
+    now: DateTime<Utc>,
+) -> Vec<MqttMessage> {
+    let base = format!("{}/{}/surplus", prefix, event.country_code);
+    let points = &event.series.points;
+    let mut messages = Vec::new();
+
+    if let Some(current) = points.iter().rev().find(|s| s.timestamp <= now) {
+        messages.push(json_message(
+            format!("{}/current", base),
+            &SurplusPayload::from(current),
+        ));
+    }
+
+    let next_max = max_surplus(points.iter().filter(|s| s.timestamp >= now));
+    if let Some(next_max) = next_max {
+        messages.push(json_message(
+            format!("{}/next_max", base),
+            &SurplusPayload::from(next_max),
+        ));
+    }
+
+    messages.push(json_message(
+        format!("{}/series", base),
+        &SeriesPayload {
+            country_code: event.country_code.clone(),
+            refreshed_at: event.refreshed_at.to_rfc3339(),
+            forecast_created_at: event.series.forecast_created_at().map(|t| t.to_rfc3339()),
+            points: points.iter().map(Into::into).collect(),
+        },
+    ));
+
+    messages
+}
+
+/// Publish every refresh event until `shutdown` turns true or the refresher goes away.
+/// Failed publishes are logged and skipped.
+pub async fn publish_refreshes(
+    sink: Arc<dyn MessageSink>,
+    topic_prefix: String,
+    mut events: broadcast::Receiver<RefreshEvent>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = shutdown.changed() => break,
+        };
+
+        match event {
+            Ok(event) => {
+                for message in surplus_messages(&topic_prefix, &event, Utc::now()) {
+                    let topic = message.topic.clone();
+                    if let Err(e) = sink.publish(message).await {
+                        eprintln!("MQTT publish to {} failed: {}", topic, e);
+                    }
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                eprintln!("MQTT publisher skipped {} refresh events", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// [`MessageSink`] publishing retained messages through rumqttc
+pub struct BrokerSink {
+    client: AsyncClient,
+}
+
+#[async_trait]
+impl MessageSink for BrokerSink {
+    async fn publish(&self, message: MqttMessage) -> anyhow::Result<()> {
+        // Never block the publisher while the broker is unreachable
+        self.client
+            .try_publish(message.topic, QoS::AtLeastOnce, true, message.payload)?;
+        Ok(())
+    }
+}
+
+/// Connect to the broker; the returned task keeps the connection alive,
+/// reconnecting with exponential backoff, and disconnects cleanly on shutdown
+pub fn connect(
+    config: &MqttConfig,
+    shutdown: watch::Receiver<bool>,
+) -> (BrokerSink, tokio::task::JoinHandle<()>) {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+
+    let (client, event_loop) = AsyncClient::new(options, 64);
+    let task = tokio::spawn(drive_event_loop(event_loop, client.clone(), shutdown));
+
+    (BrokerSink { client }, task)
+}
+
+async fn drive_event_loop(
+    mut event_loop: EventLoop,
+    client: AsyncClient,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut delay = MIN_RECONNECT_DELAY;
+
+    loop {
+        tokio::select! {
+            event = event_loop.poll() => match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    println!("Connected to MQTT broker");
+                    delay = MIN_RECONNECT_DELAY;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("MQTT connection error: {}, retrying in {:?}", e, delay);
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = shutdown.changed() => return,
+                    }
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            },
+            _ = shutdown.changed() => break,
+        }
+    }
+
+    if client.disconnect().await.is_ok() {
+        let flushed = tokio::time::timeout(DISCONNECT_TIMEOUT, async {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+                    Ok(_) => {}
+                }
+            }
+        });
+        let _ = flushed.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::analysis::SurplusSeries;
+    use chrono::{Duration, TimeZone};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingSink {
+        messages: Mutex<Vec<MqttMessage>>,
+        fail_next: Mutex<bool>,
+    }
+
+    #[async_trait]
+    impl MessageSink for RecordingSink {
+        async fn publish(&self, message: MqttMessage) -> anyhow::Result<()> {
+            if std::mem::take(&mut *self.fail_next.lock().unwrap()) {
+                anyhow::bail!("broker unavailable");
+            }
+            self.messages.lock().unwrap().push(message);
+            Ok(())
+        }
+    }
+
+    fn event(country_code: &str) -> RefreshEvent {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let points = [1_000.0, -500.0, 3_000.0, 2_000.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &surplus)| RenewableSurplus {
+                timestamp: start + Duration::hours(i as i64),
+                generation: 40_000.0 + surplus,
+                load: 40_000.0,
+                surplus,
+                total_generation: None,
+            })
+            .collect();
+
+        RefreshEvent {
+            country_code: country_code.to_string(),
+            refreshed_at: start,
+            series: Arc::new(SurplusSeries {
+                points,
+                ..SurplusSeries::default()
+            }),
+        }
+    }
+
+    fn payload(message: &MqttMessage) -> serde_json::Value {
+        serde_json::from_slice(&message.payload).unwrap()
+    }
+
+    #[test]
+    fn test_surplus_messages() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 1, 30, 0).unwrap();
+        let messages = surplus_messages("educk", &event("DE"), now);
+
+        let topics: Vec<_> = messages.iter().map(|m| m.topic.as_str()).collect();
+        assert_eq!(
+            topics,
+            [
+                "educk/DE/surplus/current",
+                "educk/DE/surplus/next_max",
+                "educk/DE/surplus/series"
+            ]
+        );
+
+        assert_eq!(
+            payload(&messages[0])["timestamp"],
+            "2024-06-01T01:00:00+00:00"
+        );
+        assert_eq!(payload(&messages[0])["surplus_mw"], -500.0);
+        assert_eq!(
+            payload(&messages[1])["timestamp"],
+            "2024-06-01T02:00:00+00:00"
+        );
+        assert_eq!(payload(&messages[1])["surplus_mw"], 3_000.0);
+        assert_eq!(payload(&messages[2])["points"].as_array().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_publish_failure_does_not_stop_publisher() {
+        let sink = Arc::new(RecordingSink::default());
+        *sink.fail_next.lock().unwrap() = true;
+        let (events, receiver) = broadcast::channel(8);
+        let (stop, shutdown) = watch::channel(false);
+
+        let task = tokio::spawn(publish_refreshes(
+            sink.clone(),
+            "educk".to_string(),
+            receiver,
+            shutdown,
+        ));
+        events.send(event("DE")).unwrap();
+        events.send(event("FR")).unwrap();
+        drop(events);
+        task.await.unwrap();
+        drop(stop);
+
+        let topics: Vec<_> = sink
+            .messages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|m| m.topic.clone())
+            .collect();
+        assert!(topics.contains(&"educk/DE/surplus/series".to_string()));
+        assert!(topics.contains(&"educk/FR/surplus/series".to_string()));
+    }
+}
@@ -0,0 +1,766 @@
+//! Where `educk surplus --watch` and the server's [alerts](crate::alerts) send their
+//! notifications: a shell command, a webhook, an ntfy.sh topic or email, each behind
+//! [`Notifier`]
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// How long a network channel may take to accept a notification
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Port of SMTP relays named without one
+const DEFAULT_SMTP_PORT: u16 = 25;
+
+/// What a notification says: the new maximum of the surplus forecast
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub country_code: String,
+    pub max_surplus_mw: Option<f64>,
+    /// RFC3339
+    pub max_surplus_at: Option<String>,
+}
+
+impl Notification {
+    pub fn new(country_code: &str, max_surplus: Option<(DateTime<Utc>, f64)>) -> Self {
+        Self {
+            country_code: country_code.to_string(),
+            max_surplus_mw: max_surplus.map(|(_, surplus)| surplus.round()),
+            max_surplus_at: max_surplus.map(|(timestamp, _)| timestamp.to_rfc3339()),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        format!("Renewable surplus in {}", self.country_code)
+    }
+
+    pub fn message(&self) -> String {
+        match (self.max_surplus_mw, &self.max_surplus_at) {
+            (Some(surplus), Some(at)) => format!("Max surplus {:.0} MW at {}", surplus, at),
+            _ => "No surplus forecast".to_string(),
+        }
+    }
+}
+
+/// A channel notifications are delivered through
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Name of the channel in logs, e.g. `ntfy`
+    fn channel(&self) -> &'static str;
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()>;
+}
+
+/// Channel of a notifier, as configured on the command line or in the alerts file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyChannel {
+    /// Shell command, with the notification in `EDUCK_*` environment variables
+    Command(String),
+    /// URL the notification is POSTed to as JSON
+    Webhook(String),
+    /// ntfy.sh topic URL, e.g. `https://ntfy.sh/my-topic`, and message priority 1 to 5
+    Ntfy {
+        url: String,
+        #[serde(default = "default_ntfy_priority")]
+        priority: u8,
+    },
+    /// Email handed to an SMTP relay
+    Email(EmailChannel),
+}
+
+/// Where emails are sent: an SMTP relay accepting mail for the recipients without
+/// authentication, typically the local MTA
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EmailChannel {
+    /// `host` or `host:port`, port 25 by default
+    pub smtp: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl EmailChannel {
+    /// Relay of `educk surplus --watch` when `--notify-smtp` is not given
+    pub const DEFAULT_SMTP: &str = "localhost";
+    /// Sender of `educk surplus --watch` when `--notify-from` is not given
+    pub const DEFAULT_FROM: &str = "educk@localhost";
+
+    fn validate(&self) -> anyhow::Result<(String, u16)> {
+        let relay = smtp_relay(&self.smtp)?;
+        if self.to.is_empty() {
+            anyhow::bail!("The email channel names no recipient");
+        }
+        for address in std::iter::once(&self.from).chain(&self.to) {
+            email_address(address)?;
+        }
+        Ok(relay)
+    }
+}
+
+fn default_ntfy_priority() -> u8 {
+    NotifyChannel::DEFAULT_NTFY_PRIORITY
+}
+
+/// Host and port of an SMTP relay given as `host` or `host:port`
+fn smtp_relay(relay: &str) -> anyhow::Result<(String, u16)> {
+    let (host, port) = match relay.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| anyhow::anyhow!("The SMTP relay {:?} has an invalid port", relay))?;
+            (host, port)
+        }
+        None => (relay, DEFAULT_SMTP_PORT),
+    };
+    if host.trim().is_empty() {
+        anyhow::bail!("The SMTP relay {:?} has no host", relay);
+    }
+    // `[::1]:25`
+    let host = host.trim().trim_start_matches('[').trim_end_matches(']');
+    Ok((host.to_string(), port))
+}
+
+/// Check that `address` looks like `local@domain` and cannot inject SMTP commands
+fn email_address(address: &str) -> anyhow::Result<()> {
+    let valid = address
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && !domain.is_empty())
+        && !address
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | ','));
+    if !valid {
+        anyhow::bail!("{:?} is not an email address", address);
+    }
+    Ok(())
+}
+
+impl NotifyChannel {
+    /// Default priority of ntfy messages
+    pub const DEFAULT_NTFY_PRIORITY: u8 = 3;
+
+    /// Check the settings of the channel before the first notification is due
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            NotifyChannel::Command(command) if command.trim().is_empty() => {
+                anyhow::bail!("The notify command is empty")
+            }
+            NotifyChannel::Command(_) => Ok(()),
+            NotifyChannel::Webhook(url) => http_url(url, "webhook").map(|_| ()),
+            NotifyChannel::Ntfy { url, priority } => {
+                let path = http_url(url, "ntfy")?;
+                if !(1..=5).contains(priority) {
+                    anyhow::bail!(
+                        "The ntfy priority must be between 1 and 5, not {}",
+                        priority
+                    );
+                }
+                if path.trim_matches('/').is_empty() {
+                    anyhow::bail!("The ntfy URL {:?} names no topic", url);
+                }
+                Ok(())
+            }
+            NotifyChannel::Email(email) => email.validate().map(|_| ()),
+        }
+    }
+
+    /// Notifier delivering through the channel
+    pub fn notifier(&self) -> anyhow::Result<Arc<dyn Notifier>> {
+        self.validate()?;
+        Ok(match self {
+            NotifyChannel::Command(command) => Arc::new(CommandNotifier {
+                command: command.clone(),
+            }),
+            NotifyChannel::Webhook(url) => Arc::new(WebhookNotifier {
+                client: http_client()?,
+                url: url.clone(),
+            }),
+            NotifyChannel::Ntfy { url, priority } => Arc::new(NtfyNotifier {
+                client: http_client()?,
+                url: url.clone(),
+                priority: *priority,
+            }),
+            NotifyChannel::Email(email) => {
+                let (host, port) = email.validate()?;
+                Arc::new(SmtpNotifier {
+                    host,
+                    port,
+                    from: email.from.clone(),
+                    to: email.to.clone(),
+                })
+            }
+        })
+    }
+}
+
+/// Path of an http(s) `url` with a host
+fn http_url<'a>(url: &'a str, channel: &str) -> anyhow::Result<&'a str> {
+    let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    else {
+        anyhow::bail!("The {} URL {:?} is not an http(s) URL", channel, url);
+    };
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    if host.is_empty() {
+        anyhow::bail!("The {} URL {:?} has no host", channel, url);
+    }
+    Ok(path)
+}
+
+fn http_client() -> anyhow::Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .build()?)
+}
+
+/// Runs a command through the shell with the new maximum in the environment
+pub struct CommandNotifier {
+    command: String,
+}
+
+#[async_trait]
+impl Notifier for CommandNotifier {
+    fn channel(&self) -> &'static str {
+        "command"
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        let mut process = tokio::process::Command::new("sh");
+        process
+            .arg("-c")
+            .arg(&self.command)
+            .env("EDUCK_COUNTRY", &notification.country_code);
+        if let (Some(surplus), Some(at)) =
+            (notification.max_surplus_mw, &notification.max_surplus_at)
+        {
+            process
+                .env("EDUCK_MAX_SURPLUS_MW", format!("{:.0}", surplus))
+                .env("EDUCK_MAX_SURPLUS_AT", at);
+        }
+
+        let status = process.status().await?;
+        if !status.success() {
+            anyhow::bail!("Notify command exited with {}", status);
+        }
+        Ok(())
+    }
+}
+
+/// POSTs the notification as JSON
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn channel(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(notification)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Publishes the message to an ntfy topic, with the title and priority as headers
+pub struct NtfyNotifier {
+    client: reqwest::Client,
+    url: String,
+    priority: u8,
+}
+
+#[async_trait]
+impl Notifier for NtfyNotifier {
+    fn channel(&self) -> &'static str {
+        "ntfy"
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .header("Title", notification.title())
+            .header("Priority", self.priority.to_string())
+            .body(notification.message())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Mails the notification through an SMTP relay, without TLS or authentication
+pub struct SmtpNotifier {
+    host: String,
+    port: u16,
+    from: String,
+    to: Vec<String>,
+}
+
+impl SmtpNotifier {
+    async fn deliver(&self, notification: &Notification) -> anyhow::Result<()> {
+        let stream = tokio::net::TcpStream::connect((self.host.as_str(), self.port)).await?;
+        let (read, mut write) = stream.into_split();
+        let mut replies = BufReader::new(read);
+
+        smtp_reply(&mut replies, 220).await?;
+        smtp_command(&mut write, &mut replies, "EHLO localhost", 250).await?;
+        smtp_command(
+            &mut write,
+            &mut replies,
+            &format!("MAIL FROM:<{}>", self.from),
+            250,
+        )
+        .await?;
+        for recipient in &self.to {
+            smtp_command(
+                &mut write,
+                &mut replies,
+                &format!("RCPT TO:<{}>", recipient),
+                250,
+            )
+            .await?;
+        }
+        smtp_command(&mut write, &mut replies, "DATA", 354).await?;
+        write
+            .write_all(email_message(&self.from, &self.to, notification, Utc::now()).as_bytes())
+            .await?;
+        smtp_reply(&mut replies, 250).await?;
+        // The mail is accepted, a failing goodbye does not matter
+        let _ = smtp_command(&mut write, &mut replies, "QUIT", 221).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn channel(&self) -> &'static str {
+        "email"
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        tokio::time::timeout(DELIVERY_TIMEOUT, self.deliver(notification))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "The SMTP relay {}:{} did not accept the mail within {} s",
+                    self.host,
+                    self.port,
+                    DELIVERY_TIMEOUT.as_secs()
+                )
+            })?
+    }
+}
+
+/// Send one SMTP command and wait for its reply
+async fn smtp_command<W, R>(
+    write: &mut W,
+    replies: &mut R,
+    command: &str,
+    expected: u16,
+) -> anyhow::Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+    R: AsyncBufReadExt + Unpin,
+{
+    write
+        .write_all(format!("{}\r\n", command).as_bytes())
+        .await?;
+    smtp_reply(replies, expected)
+        .await
+        .map_err(|e| anyhow::anyhow!("{} refused: {}", command, e))
+}
+
+/// Read a reply, of one or more `250-` continuation lines, and check its code
+async fn smtp_reply<R: AsyncBufReadExt + Unpin>(
+    replies: &mut R,
+    expected: u16,
+) -> anyhow::Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if replies.read_line(&mut line).await? == 0 {
+            anyhow::bail!("the SMTP relay closed the connection");
+        }
+        let code: u16 = line
+            .get(..3)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("not an SMTP reply: {:?}", line.trim_end()))?;
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        if code != expected {
+            anyhow::bail!("the SMTP relay answered {}", line.trim_end());
+        }
+        return Ok(());
+    }
+}
+
+/// The mail of `notification`, terminated by the line with a single dot
+fn email_message(
+    from: &str,
+    to: &[String],
+    notification: &Notification,
+    date: DateTime<Utc>,
+) -> String {
+    let body: String = notification
+        .message()
+        .lines()
+        // Lines starting with a dot would end the mail early
+        .map(|line| {
+            if line.starts_with('.') {
+                format!(".{}\r\n", line)
+            } else {
+                format!("{}\r\n", line)
+            }
+        })
+        .collect();
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}.\r\n",
+        from,
+        to.join(", "),
+        notification.title(),
+        date.to_rfc2822(),
+        body
+    )
+}
+
+/// Keeps what it was sent instead of delivering it, to assert on in tests
+#[derive(Default)]
+pub struct RecordingNotifier {
+    sent: Mutex<Vec<Notification>>,
+    /// Fail every delivery with this message
+    failure: Option<String>,
+}
+
+impl RecordingNotifier {
+    pub fn failing(message: &str) -> Self {
+        Self {
+            sent: Mutex::default(),
+            failure: Some(message.to_string()),
+        }
+    }
+
+    /// Notifications sent so far, failed ones included
+    pub fn sent(&self) -> Vec<Notification> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    fn channel(&self) -> &'static str {
+        "recording"
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.sent.lock().unwrap().push(notification.clone());
+        match &self.failure {
+            Some(message) => anyhow::bail!("{}", message),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Outcome of delivering a notification through one channel
+#[derive(Debug)]
+pub struct Delivery {
+    pub channel: &'static str,
+    pub result: anyhow::Result<()>,
+}
+
+/// Deliver `notification` through every notifier at once. A failing channel does not
+/// hold up or fail the others; failures are logged.
+pub async fn notify_all(
+    notifiers: &[Arc<dyn Notifier>],
+    notification: &Notification,
+) -> Vec<Delivery> {
+    let deliveries = join_all(notifiers.iter().map(|notifier| async move {
+        Delivery {
+            channel: notifier.channel(),
+            result: notifier.send(notification).await,
+        }
+    }))
+    .await;
+
+    for delivery in &deliveries {
+        if let Err(e) = &delivery.result {
+            eprintln!("Notification via {} failed: {}", delivery.channel, e);
+        }
+    }
+    deliveries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use chrono::TimeZone;
+
+    fn notification() -> Notification {
+        let noon = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        Notification::new("DE", Some((noon, 5_400.4)))
+    }
+
+    #[test]
+    fn test_channels_are_validated() {
+        assert!(
+            NotifyChannel::Command("true".to_string())
+                .validate()
+                .is_ok()
+        );
+        assert!(NotifyChannel::Command(" ".to_string()).validate().is_err());
+        assert!(
+            NotifyChannel::Webhook("https://example.com/hook".to_string())
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            NotifyChannel::Webhook("example.com/hook".to_string())
+                .validate()
+                .is_err()
+        );
+        assert!(
+            NotifyChannel::Webhook("https://".to_string())
+                .validate()
+                .is_err()
+        );
+
+        let ntfy = |url: &str, priority| NotifyChannel::Ntfy {
+            url: url.to_string(),
+            priority,
+        };
+        assert!(ntfy("https://ntfy.sh/educk", 3).validate().is_ok());
+        assert!(ntfy("https://ntfy.sh/educk", 0).validate().is_err());
+        assert!(ntfy("https://ntfy.sh/educk", 6).validate().is_err());
+        assert!(ntfy("https://ntfy.sh/", 3).validate().is_err());
+
+        let email = |smtp: &str, from: &str, to: &[&str]| {
+            NotifyChannel::Email(EmailChannel {
+                smtp: smtp.to_string(),
+                from: from.to_string(),
+                to: to.iter().map(|to| to.to_string()).collect(),
+            })
+        };
+        assert!(
+            email("localhost", "educk@localhost", &["me@example.com"])
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            email("mail:587", "educk@localhost", &["me@example.com"])
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            email("mail:smtp", "educk@localhost", &["me@example.com"])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            email(":25", "educk@localhost", &["me@example.com"])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            email("localhost", "educk@localhost", &[])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            email("localhost", "educk", &["me@example.com"])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            email(
+                "localhost",
+                "educk@localhost",
+                &["me@example.com>\r\nRCPT TO:<x@y"]
+            )
+            .validate()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_email_message_is_dot_stuffed() {
+        let date = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let message = email_message(
+            "educk@localhost",
+            &["a@example.com".to_string(), "b@example.com".to_string()],
+            &notification(),
+            date,
+        );
+
+        assert!(
+            message.starts_with("From: educk@localhost\r\nTo: a@example.com, b@example.com\r\n")
+        );
+        assert!(message.contains("Subject: Renewable surplus in DE\r\n"));
+        assert!(message.contains("Date: Sat, 1 Jun 2024 12:00:00 +0000\r\n"));
+        assert!(
+            message.ends_with("\r\n\r\nMax surplus 5400 MW at 2024-06-01T12:00:00+00:00\r\n.\r\n")
+        );
+    }
+
+    /// Accepts one mail like an SMTP relay, returning the commands and the mail
+    async fn smtp_relay_once(
+        listener: tokio::net::TcpListener,
+        refuse_recipient: bool,
+    ) -> (Vec<String>, String) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let (mut commands, mut mail) = (Vec::new(), String::new());
+
+        write.write_all(b"220 relay ready\r\n").await.unwrap();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let reply: &[u8] = match line.as_str() {
+                "EHLO localhost" => b"250-relay\r\n250 8BITMIME\r\n",
+                "DATA" => b"354 go ahead\r\n",
+                "QUIT" => b"221 bye\r\n",
+                rcpt if rcpt.starts_with("RCPT") && refuse_recipient => b"550 no such user\r\n",
+                _ => b"250 ok\r\n",
+            };
+            commands.push(line.clone());
+            write.write_all(reply).await.unwrap();
+            if line == "DATA" {
+                while let Some(line) = lines.next_line().await.unwrap() {
+                    mail.push_str(&line);
+                    mail.push('\n');
+                    if line == "." {
+                        break;
+                    }
+                }
+                write.write_all(b"250 queued\r\n").await.unwrap();
+            }
+            if line == "QUIT" || reply.starts_with(b"5") {
+                break;
+            }
+        }
+        (commands, mail)
+    }
+
+    #[tokio::test]
+    async fn test_email_is_handed_to_the_relay() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let relay = tokio::spawn(smtp_relay_once(listener, false));
+
+        let notifier = NotifyChannel::Email(EmailChannel {
+            smtp: address.to_string(),
+            from: "educk@localhost".to_string(),
+            to: vec!["me@example.com".to_string()],
+        })
+        .notifier()
+        .unwrap();
+        assert_eq!(notifier.channel(), "email");
+        notifier.send(&notification()).await.unwrap();
+
+        let (commands, mail) = relay.await.unwrap();
+        assert_eq!(
+            commands,
+            [
+                "EHLO localhost",
+                "MAIL FROM:<educk@localhost>",
+                "RCPT TO:<me@example.com>",
+                "DATA",
+                "QUIT"
+            ]
+        );
+        assert!(mail.contains("Subject: Renewable surplus in DE\n"));
+        assert!(mail.ends_with("Max surplus 5400 MW at 2024-06-01T12:00:00+00:00\n.\n"));
+
+        // A refused recipient fails the delivery
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(smtp_relay_once(listener, true));
+        let notifier = NotifyChannel::Email(EmailChannel {
+            smtp: address.to_string(),
+            from: "educk@localhost".to_string(),
+            to: vec!["nobody@example.com".to_string()],
+        })
+        .notifier()
+        .unwrap();
+        let error = notifier.send(&notification()).await.unwrap_err();
+        assert!(error.to_string().contains("550 no such user"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_failing_channels_do_not_block_the_others() {
+        let failing = Arc::new(RecordingNotifier::failing("unreachable"));
+        let recording = Arc::new(RecordingNotifier::default());
+        let notifiers: Vec<Arc<dyn Notifier>> = vec![failing.clone(), recording.clone()];
+
+        let deliveries = notify_all(&notifiers, &notification()).await;
+        assert_eq!(deliveries.len(), 2);
+        assert!(deliveries[0].result.is_err());
+        assert!(deliveries[1].result.is_ok());
+        assert_eq!(failing.sent().len(), 1);
+        assert_eq!(recording.sent(), [notification()]);
+    }
+
+    #[tokio::test]
+    async fn test_http_channels_post_the_notification() {
+        let received = Arc::new(Mutex::new(Vec::<(String, HeaderMap, String)>::new()));
+        let record = |path: &'static str| {
+            let received = received.clone();
+            post(move |headers: HeaderMap, body: String| async move {
+                received
+                    .lock()
+                    .unwrap()
+                    .push((path.to_string(), headers, body));
+            })
+        };
+        let app = Router::new()
+            .route("/hook", record("/hook"))
+            .route("/educk", record("/educk"));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let notifiers = [
+            NotifyChannel::Webhook(format!("http://{address}/hook")),
+            NotifyChannel::Ntfy {
+                url: format!("http://{address}/educk"),
+                priority: 4,
+            },
+            NotifyChannel::Webhook(format!("http://{address}/missing")),
+        ]
+        .iter()
+        .map(|channel| channel.notifier().unwrap())
+        .collect::<Vec<_>>();
+
+        let deliveries = notify_all(&notifiers, &notification()).await;
+        assert!(deliveries[0].result.is_ok());
+        assert!(deliveries[1].result.is_ok());
+        // 404
+        assert!(deliveries[2].result.is_err());
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort_by(|a, b| a.0.cmp(&b.0));
+        let (_, headers, body) = &received[0];
+        assert_eq!(received[0].0, "/educk");
+        assert_eq!(headers["title"], "Renewable surplus in DE");
+        assert_eq!(headers["priority"], "4");
+        assert_eq!(body, "Max surplus 5400 MW at 2024-06-01T12:00:00+00:00");
+        let (_, headers, body) = &received[1];
+        assert_eq!(headers["content-type"], "application/json");
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["country_code"], "DE");
+        assert_eq!(body["max_surplus_mw"], 5_400.0);
+    }
+}
@@ -0,0 +1,546 @@
+//! Forecasts in the OpenMetrics text format, for Prometheus to scrape
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::RwLock;
+
+use crate::entsoe::ForecastSource;
+use crate::entsoe::analysis::{
+    Interpolation, RenewableSurplus, SourceSegment, SurplusSeries, value_at,
+};
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// A gauge exported for each surplus point
+struct SurplusGauge {
+    name: &'static str,
+    help: &'static str,
+    value: fn(&RenewableSurplus) -> f64,
+    /// Forecast the value is based on, for the `source` label
+    source: fn(&SourceSegment) -> ForecastSource,
+}
+
+const SURPLUS_GAUGES: [SurplusGauge; 3] = [
+    SurplusGauge {
+        name: "educk_surplus_mw",
+        help: "Forecast wind and solar generation minus load in MW",
+        value: |point| point.surplus,
+        source: |segment| segment.generation,
+    },
+    SurplusGauge {
+        name: "educk_generation_mw",
+        help: "Forecast wind and solar generation in MW",
+        value: |point| point.generation,
+        source: |segment| segment.generation,
+    },
+    SurplusGauge {
+        name: "educk_load_mw",
+        help: "Forecast total load in MW",
+        value: |point| point.load,
+        source: |segment| segment.load,
+    },
+];
+
+/// Headline values of a prefetched country as of its last refresh
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct CountrySummary {
+    current_surplus_mw: Option<f64>,
+    next_24h_max_surplus_mw: Option<f64>,
+    /// Issue time of the oldest forecast behind the series
+    forecast_created_at: Option<DateTime<Utc>>,
+}
+
+/// A gauge of [`SummaryGauges`], with one sample per country
+struct SummaryGauge {
+    name: &'static str,
+    help: &'static str,
+    value: fn(&CountrySummary, DateTime<Utc>) -> Option<f64>,
+}
+
+const SUMMARY_GAUGES: [SummaryGauge; 3] = [
+    SummaryGauge {
+        name: "educk_current_surplus_mw",
+        help: "Wind and solar surplus in effect at the last refresh in MW",
+        value: |summary, _| summary.current_surplus_mw,
+    },
+    SummaryGauge {
+        name: "educk_next_24h_max_surplus_mw",
+        help: "Largest forecast surplus of the 24 hours after the last refresh in MW",
+        value: |summary, _| summary.next_24h_max_surplus_mw,
+    },
+    SummaryGauge {
+        name: "educk_forecast_age_seconds",
+        help: "Seconds since the oldest forecast behind the series was issued",
+        value: |summary, now| {
+            summary
+                .forecast_created_at
+                .map(|created| (now - created).num_milliseconds() as f64 / 1000.0)
+        },
+    },
+];
+
+/// Summary gauges of the prefetched countries. The refresher updates the values of a
+/// country in place on every refresh; the forecast age is taken at scrape time so it
+/// keeps growing when ENTSO-E stops publishing.
+#[derive(Debug, Default)]
+pub struct SummaryGauges {
+    countries: RwLock<BTreeMap<String, CountrySummary>>,
+}
+
+impl SummaryGauges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the values of `country` from a series refreshed at `now`
+    pub fn update(&self, country: &str, series: &SurplusSeries, now: DateTime<Utc>) {
+        // From the point in effect now
+        let from = series
+            .points
+            .iter()
+            .map(|point| point.timestamp)
+            .filter(|timestamp| *timestamp <= now)
+            .max()
+            .unwrap_or(now);
+        let summary = CountrySummary {
+            current_surplus_mw: value_at(&series.points, now, Interpolation::default())
+                .map(|current| current.surplus),
+            next_24h_max_surplus_mw: series
+                .points
+                .iter()
+                .filter(|point| {
+                    point.timestamp >= from && point.timestamp < now + Duration::hours(24)
+                })
+                .map(|point| point.surplus)
+                .reduce(f64::max),
+            forecast_created_at: series.forecast_created_at(),
+        };
+        *self
+            .countries
+            .write()
+            .unwrap()
+            .entry(country.to_string())
+            .or_default() = summary;
+    }
+
+    /// Every gauge with a sample per country that has a value, ages as of `now`
+    pub fn samples(&self, exposition: &mut Exposition, now: DateTime<Utc>) {
+        let countries = self.countries.read().unwrap();
+        for gauge in &SUMMARY_GAUGES {
+            exposition.gauge(gauge.name, gauge.help);
+            for (country, summary) in countries.iter() {
+                if let Some(value) = (gauge.value)(summary, now) {
+                    exposition.sample(gauge.name, &[("country", country)], value, None);
+                }
+            }
+        }
+    }
+}
+
+/// OpenMetrics exposition built one metric family at a time
+#[derive(Debug, Default)]
+pub struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a gauge family; its samples must follow before the next family starts
+    pub fn gauge(&mut self, name: &str, help: &str) -> &mut Self {
+        let help = help.replace('\\', r"\\").replace('\n', r"\n");
+        let _ = writeln!(self.text, "# TYPE {name} gauge");
+        let _ = writeln!(self.text, "# HELP {name} {help}");
+        self
+    }
+
+    /// Add a sample to the current family. Samples of one label set must be contiguous
+    /// and, when timestamped, oldest first.
+    pub fn sample(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        value: f64,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> &mut Self {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = write!(self.text, " {}", format_value(value));
+        if let Some(timestamp) = timestamp {
+            // Seconds since the epoch, the unit OpenMetrics timestamps are given in
+            let _ = write!(
+                self.text,
+                " {}.{:03}",
+                timestamp.timestamp(),
+                timestamp.timestamp_subsec_millis()
+            );
+        }
+        self.text.push('\n');
+        self
+    }
+
+    /// The exposition text, terminated by `# EOF`
+    pub fn finish(mut self) -> String {
+        self.text.push_str("# EOF\n");
+        self.text
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Every point of `series` as a timestamped sample. The `source` label is the
+/// generation forecast for surplus and generation and the load forecast for load.
+pub fn series_samples(
+    exposition: &mut Exposition,
+    country: &str,
+    zone: &str,
+    series: &SurplusSeries,
+) {
+    for gauge in &SURPLUS_GAUGES {
+        // One label set per source, each in time order
+        let mut points: Vec<(ForecastSource, &RenewableSurplus)> = series
+            .points
+            .iter()
+            .map(|point| {
+                let source = series
+                    .source_at(point.timestamp)
+                    .map_or(ForecastSource::DayAhead, gauge.source);
+                (source, point)
+            })
+            .collect();
+        points.sort_by_key(|(source, point)| (source.name(), point.timestamp));
+
+        exposition.gauge(gauge.name, gauge.help);
+        for (source, point) in points {
+            exposition.sample(
+                gauge.name,
+                &[
+                    ("country", country),
+                    ("zone", zone),
+                    ("source", source.name()),
+                ],
+                (gauge.value)(point),
+                Some(point.timestamp),
+            );
+        }
+    }
+}
+
+/// The values in effect now, without timestamps, for regular scraping
+pub fn current_samples(
+    exposition: &mut Exposition,
+    country: &str,
+    zone: &str,
+    current: &RenewableSurplus,
+) {
+    for gauge in &SURPLUS_GAUGES {
+        exposition.gauge(gauge.name, gauge.help).sample(
+            gauge.name,
+            &[("country", country), ("zone", zone)],
+            (gauge.value)(current),
+            None,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use std::collections::{HashMap, HashSet};
+
+    #[derive(Debug)]
+    struct Sample {
+        name: String,
+        labels: Vec<(String, String)>,
+        value: f64,
+        timestamp: Option<f64>,
+    }
+
+    fn is_metric_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    }
+
+    fn parse_labels(text: &str) -> Vec<(String, String)> {
+        let mut labels = Vec::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (name, after) = rest.split_once("=\"").expect("label without value");
+            assert!(is_metric_name(name) && !name.contains(':'), "label {name}");
+            let mut value = String::new();
+            let mut chars = after.char_indices();
+            let end = loop {
+                match chars.next().expect("unterminated label value") {
+                    (_, '\\') => match chars.next().unwrap().1 {
+                        'n' => value.push('\n'),
+                        c @ ('\\' | '"') => value.push(c),
+                        c => panic!("invalid escape \\{c}"),
+                    },
+                    (i, '"') => break i,
+                    (_, c) => value.push(c),
+                }
+            };
+            labels.push((name.to_string(), value));
+            rest = &after[end + 1..];
+            rest = rest.strip_prefix(',').unwrap_or(rest);
+        }
+        labels
+    }
+
+    /// Parse an exposition and check the OpenMetrics rules it relies on: metadata
+    /// before samples, one family at a time, contiguous label sets with increasing
+    /// timestamps and a closing `# EOF`
+    fn parse(text: &str) -> Vec<Sample> {
+        let body = text.strip_suffix("# EOF\n").expect("missing # EOF");
+        let mut types = HashMap::new();
+        let mut finished_families = HashSet::new();
+        let mut family: Option<String> = None;
+        let mut seen_label_sets: HashSet<String> = HashSet::new();
+        let mut last: Option<(String, Option<f64>)> = None;
+        let mut samples = Vec::new();
+
+        for line in body.lines() {
+            if let Some(metadata) = line.strip_prefix("# ") {
+                let mut parts = metadata.splitn(3, ' ');
+                let (kind, name) = (parts.next().unwrap(), parts.next().unwrap());
+                assert!(is_metric_name(name), "metric name {name}");
+                if family.as_deref() != Some(name) {
+                    if let Some(previous) = family.replace(name.to_string()) {
+                        finished_families.insert(previous);
+                    }
+                    assert!(!finished_families.contains(name), "{name} split");
+                    seen_label_sets.clear();
+                    last = None;
+                }
+                match kind {
+                    "TYPE" => {
+                        assert!(
+                            types
+                                .insert(name.to_string(), parts.next().unwrap())
+                                .is_none()
+                        )
+                    }
+                    "HELP" => assert!(parts.next().is_some()),
+                    other => panic!("unexpected metadata {other}"),
+                }
+                continue;
+            }
+
+            let (series, rest) = match line.find('{') {
+                Some(_) => {
+                    let close = line.find('}').unwrap();
+                    (&line[..close + 1], &line[close + 2..])
+                }
+                None => line.split_once(' ').unwrap(),
+            };
+            let name = &series[..series.find('{').unwrap_or(series.len())];
+            assert_eq!(family.as_deref(), Some(name), "sample outside its family");
+            assert_eq!(types[name], "gauge");
+            let labels = match series.find('{') {
+                Some(open) => parse_labels(&series[open + 1..series.len() - 1]),
+                None => Vec::new(),
+            };
+
+            let mut fields = rest.split(' ');
+            let value: f64 = match fields.next().unwrap() {
+                "+Inf" => f64::INFINITY,
+                "-Inf" => f64::NEG_INFINITY,
+                value => value.parse().expect("value"),
+            };
+            let timestamp = fields.next().map(|t| t.parse::<f64>().expect("timestamp"));
+            assert!(fields.next().is_none());
+
+            match &last {
+                Some((previous, previous_time)) if previous == series => {
+                    assert!(timestamp > *previous_time, "timestamps must increase")
+                }
+                _ => assert!(
+                    seen_label_sets.insert(series.to_string()),
+                    "{series} is not contiguous"
+                ),
+            }
+            last = Some((series.to_string(), timestamp));
+
+            samples.push(Sample {
+                name: name.to_string(),
+                labels,
+                value,
+                timestamp,
+            });
+        }
+        samples
+    }
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 5, 0, 0, 0).unwrap()
+    }
+
+    fn series() -> SurplusSeries {
+        let segment = |hours: std::ops::Range<i64>, generation| SourceSegment {
+            start: midnight() + Duration::hours(hours.start),
+            end: midnight() + Duration::hours(hours.end - 1),
+            generation,
+            load: ForecastSource::DayAhead,
+        };
+        SurplusSeries {
+            points: (0..6)
+                .map(|hour| RenewableSurplus {
+                    timestamp: midnight() + Duration::hours(hour),
+                    generation: 1000.0 * hour as f64,
+                    load: 2000.0,
+                    surplus: 1000.0 * hour as f64 - 2000.0,
+                    total_generation: None,
+                })
+                .collect(),
+            // Intraday in the middle splits the day-ahead label set in two stretches
+            sources: vec![
+                segment(0..2, ForecastSource::DayAhead),
+                segment(2..4, ForecastSource::Intraday),
+                segment(4..6, ForecastSource::DayAhead),
+            ],
+            ..SurplusSeries::default()
+        }
+    }
+
+    #[test]
+    fn test_series_exposition_is_valid() {
+        let mut exposition = Exposition::new();
+        series_samples(&mut exposition, "DE", "10Y1001A1001A83F", &series());
+        let text = exposition.finish();
+
+        let samples = parse(&text);
+
+        assert_eq!(samples.len(), 18);
+        let surplus: Vec<_> = samples
+            .iter()
+            .filter(|s| s.name == "educk_surplus_mw")
+            .map(|s| {
+                let source = &s
+                    .labels
+                    .iter()
+                    .find(|(name, _)| name == "source")
+                    .unwrap()
+                    .1;
+                (source.as_str(), s.value)
+            })
+            .collect();
+        assert_eq!(
+            surplus,
+            [
+                ("day_ahead", -2000.0),
+                ("day_ahead", -1000.0),
+                ("day_ahead", 2000.0),
+                ("day_ahead", 3000.0),
+                ("intraday", 0.0),
+                ("intraday", 1000.0),
+            ]
+        );
+        assert_eq!(samples[0].timestamp, Some(midnight().timestamp() as f64));
+        assert!(text.contains(
+            "educk_surplus_mw{country=\"DE\",zone=\"10Y1001A1001A83F\",source=\"day_ahead\"} -2000 1717545600.000\n"
+        ));
+    }
+
+    #[test]
+    fn test_current_exposition_is_valid() {
+        let mut exposition = Exposition::new();
+        current_samples(
+            &mut exposition,
+            "DE",
+            "10Y1001A1001A83F",
+            &series().points[3],
+        );
+
+        let samples = parse(&exposition.finish());
+
+        let values: Vec<_> = samples.iter().map(|s| (s.name.as_str(), s.value)).collect();
+        assert_eq!(
+            values,
+            [
+                ("educk_surplus_mw", 1000.0),
+                ("educk_generation_mw", 3000.0),
+                ("educk_load_mw", 2000.0)
+            ]
+        );
+        assert!(samples.iter().all(|s| s.timestamp.is_none()));
+    }
+
+    #[test]
+    fn test_summary_gauges() {
+        let gauges = SummaryGauges::new();
+        let mut series = series();
+        series.generation_doc_meta = Some(crate::entsoe::analysis::DocumentMeta {
+            created_date_time: midnight() - Duration::hours(12),
+            revision_number: 1,
+        });
+        gauges.update("DE", &series, midnight() + Duration::minutes(90));
+        gauges.update("FR", &SurplusSeries::default(), midnight());
+
+        let mut exposition = Exposition::new();
+        gauges.samples(&mut exposition, midnight() + Duration::hours(2));
+        let samples = parse(&exposition.finish());
+
+        // FR has no values yet, so only DE shows up
+        let values: Vec<_> = samples.iter().map(|s| (s.name.as_str(), s.value)).collect();
+        assert_eq!(
+            values,
+            [
+                // Halfway between -1000 and 0
+                ("educk_current_surplus_mw", -500.0),
+                ("educk_next_24h_max_surplus_mw", 3000.0),
+                ("educk_forecast_age_seconds", 14.0 * 3600.0),
+            ]
+        );
+        assert_eq!(
+            samples[0].labels,
+            [("country".to_string(), "DE".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_escaping() {
+        let mut exposition = Exposition::new();
+        exposition
+            .gauge("educk_test", "Line one\nline \\ two")
+            .sample(
+                "educk_test",
+                &[("note", "say \"hi\"\\\n")],
+                f64::NEG_INFINITY,
+                None,
+            );
+        let text = exposition.finish();
+
+        assert!(text.contains("# HELP educk_test Line one\\nline \\\\ two\n"));
+        let samples = parse(&text);
+        assert_eq!(samples[0].labels[0].1, "say \"hi\"\\\n");
+        assert_eq!(samples[0].value, f64::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,558 @@
+//! Export of the `tracing` spans to an OpenTelemetry collector, over OTLP/HTTP with JSON
+//! encoding. Configured from the standard `OTEL_*` environment variables, see
+//! [`OtlpConfig::from_env`]. Events within a span are exported as its span events, so
+//! the statistics of [`crate::entsoe::stats`] reach the tracing backend with the
+//! request they were parsed for.
+
+use serde_json::{Value, json};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, watch};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Finished spans waiting for export; more are dropped (`OTEL_BSP_MAX_QUEUE_SIZE`)
+const MAX_QUEUE_SIZE: usize = 2048;
+/// Most spans per export request (`OTEL_BSP_MAX_EXPORT_BATCH_SIZE`)
+const MAX_EXPORT_BATCH_SIZE: usize = 512;
+/// Interval batches are exported at when not full (`OTEL_BSP_SCHEDULE_DELAY`)
+const SCHEDULE_DELAY: Duration = Duration::from_secs(5);
+/// How long the collector may take to accept a batch (`OTEL_EXPORTER_OTLP_TIMEOUT`)
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+/// `SPAN_KIND_INTERNAL`
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// Where and as which service spans are exported
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtlpConfig {
+    /// URL the batches are POSTed to, e.g. `http://localhost:4318/v1/traces`
+    pub endpoint: String,
+    /// Sent with every export, e.g. an API key of the backend
+    pub headers: Vec<(String, String)>,
+    /// `service.name` of the exported resource
+    pub service_name: String,
+}
+
+impl OtlpConfig {
+    /// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`, else `OTEL_EXPORTER_OTLP_ENDPOINT` with
+    /// `/v1/traces` appended, with the headers of `OTEL_EXPORTER_OTLP_HEADERS` and
+    /// `OTEL_EXPORTER_OTLP_TRACES_HEADERS` (`key=value,key=value`) and the name of
+    /// `OTEL_SERVICE_NAME`. `None` without an endpoint or with `OTEL_TRACES_EXPORTER=none`.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        Self::from_vars(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Option<Self>> {
+        match var("OTEL_TRACES_EXPORTER").as_deref() {
+            None | Some("otlp") => {}
+            Some("none") => return Ok(None),
+            Some(other) => anyhow::bail!(
+                "OTEL_TRACES_EXPORTER={} is not supported, only otlp or none",
+                other
+            ),
+        }
+        if let Some(protocol) = var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")
+            .or_else(|| var("OTEL_EXPORTER_OTLP_PROTOCOL"))
+            .filter(|protocol| protocol != "http/json")
+        {
+            anyhow::bail!(
+                "OTLP protocol {} is not supported, only http/json",
+                protocol
+            );
+        }
+
+        let endpoint = match var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT") {
+            Some(endpoint) => endpoint,
+            None => match var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+                Some(base) => format!("{}/v1/traces", base.trim_end_matches('/')),
+                None => return Ok(None),
+            },
+        };
+        if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) {
+            anyhow::bail!("The OTLP endpoint {:?} is not an http(s) URL", endpoint);
+        }
+
+        let mut headers = Vec::new();
+        for name in [
+            "OTEL_EXPORTER_OTLP_HEADERS",
+            "OTEL_EXPORTER_OTLP_TRACES_HEADERS",
+        ] {
+            for pair in var(name).iter().flat_map(|value| value.split(',')) {
+                let (key, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("{} must be key=value pairs", name))?;
+                headers.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        Ok(Some(Self {
+            endpoint,
+            headers,
+            service_name: var("OTEL_SERVICE_NAME").unwrap_or_else(|| "educk".to_string()),
+        }))
+    }
+}
+
+/// A span or event attribute
+#[derive(Debug, Clone, PartialEq)]
+enum AttributeValue {
+    String(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+}
+
+impl AttributeValue {
+    fn json(&self) -> Value {
+        match self {
+            AttributeValue::String(value) => json!({ "stringValue": value }),
+            // 64-bit integers are strings in OTLP/JSON
+            AttributeValue::Int(value) => json!({ "intValue": value.to_string() }),
+            AttributeValue::Double(value) => json!({ "doubleValue": value }),
+            AttributeValue::Bool(value) => json!({ "boolValue": value }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct AttributeList(Vec<(String, AttributeValue)>);
+
+impl AttributeList {
+    fn set(&mut self, key: &str, value: AttributeValue) {
+        match self.0.iter_mut().find(|(existing, _)| existing == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((key.to_string(), value)),
+        }
+    }
+
+    fn json(&self) -> Value {
+        self.0
+            .iter()
+            .map(|(key, value)| json!({ "key": key, "value": value.json() }))
+            .collect()
+    }
+}
+
+impl Visit for AttributeList {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field.name(), AttributeValue::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field.name(), AttributeValue::Int(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match i64::try_from(value) {
+            Ok(value) => self.set(field.name(), AttributeValue::Int(value)),
+            Err(_) => self.set(field.name(), AttributeValue::String(value.to_string())),
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field.name(), AttributeValue::Double(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field.name(), AttributeValue::Bool(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.set(field.name(), AttributeValue::String(format!("{:?}", value)));
+    }
+}
+
+struct SpanEvent {
+    time: SystemTime,
+    name: String,
+    attributes: AttributeList,
+}
+
+/// A span while it is open, kept in the extensions of the registry
+struct SpanData {
+    trace_id: u128,
+    span_id: u64,
+    parent_span_id: Option<u64>,
+    name: &'static str,
+    start: SystemTime,
+    attributes: AttributeList,
+    events: Vec<SpanEvent>,
+}
+
+/// A closed span, queued for export
+struct FinishedSpan {
+    data: SpanData,
+    end: SystemTime,
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl FinishedSpan {
+    fn json(&self) -> Value {
+        let data = &self.data;
+        let mut span = json!({
+            "traceId": format!("{:032x}", data.trace_id),
+            "spanId": format!("{:016x}", data.span_id),
+            "name": data.name,
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": unix_nanos(data.start),
+            "endTimeUnixNano": unix_nanos(self.end),
+            "attributes": data.attributes.json(),
+            "events": data.events.iter().map(|event| json!({
+                "timeUnixNano": unix_nanos(event.time),
+                "name": event.name,
+                "attributes": event.attributes.json(),
+            })).collect::<Vec<_>>(),
+        });
+        if let Some(parent) = data.parent_span_id {
+            span["parentSpanId"] = json!(format!("{:016x}", parent));
+        }
+        span
+    }
+}
+
+/// Body of an export request of `spans`
+fn export_request(service_name: &str, spans: &[FinishedSpan]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{
+                    "key": "service.name",
+                    "value": { "stringValue": service_name },
+                }],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "educk", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans.iter().map(FinishedSpan::json).collect::<Vec<_>>(),
+            }],
+        }],
+    })
+}
+
+/// A random, non-zero id
+fn random_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    static STATE: std::sync::OnceLock<RandomState> = std::sync::OnceLock::new();
+    let id = STATE
+        .get_or_init(RandomState::new)
+        .hash_one(NEXT.fetch_add(1, Ordering::Relaxed));
+    id.max(1)
+}
+
+/// [`Layer`] queueing every closed span for [`export`]
+pub struct OtlpLayer {
+    spans: mpsc::Sender<FinishedSpan>,
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span.parent().and_then(|parent| {
+            let extensions = parent.extensions();
+            let parent = extensions.get::<SpanData>()?;
+            Some((parent.trace_id, parent.span_id))
+        });
+        let mut attributes = AttributeList::default();
+        attrs.record(&mut attributes);
+
+        span.extensions_mut().insert(SpanData {
+            trace_id: parent.map_or_else(
+                || (u128::from(random_id()) << 64) | u128::from(random_id()),
+                |(trace_id, _)| trace_id,
+            ),
+            span_id: random_id(),
+            parent_span_id: parent.map(|(_, span_id)| span_id),
+            name: attrs.metadata().name(),
+            start: SystemTime::now(),
+            attributes,
+            events: Vec::new(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(data) = span.extensions_mut().get_mut::<SpanData>()
+        {
+            values.record(&mut data.attributes);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut attributes = AttributeList::default();
+        event.record(&mut attributes);
+        let name = match attributes.0.iter().position(|(key, _)| key == "message") {
+            Some(index) => match attributes.0.remove(index).1 {
+                AttributeValue::String(message) => message,
+                other => format!("{:?}", other),
+            },
+            None => event.metadata().name().to_string(),
+        };
+        attributes.set(
+            "target",
+            AttributeValue::String(event.metadata().target().to_string()),
+        );
+        attributes.set(
+            "level",
+            AttributeValue::String(event.metadata().level().to_string()),
+        );
+
+        if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+            data.events.push(SpanEvent {
+                time: SystemTime::now(),
+                name,
+                attributes,
+            });
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        if let Some(data) = span.extensions_mut().remove::<SpanData>() {
+            // A full queue means the collector cannot keep up; drop rather than block
+            let _ = self.spans.try_send(FinishedSpan {
+                data,
+                end: SystemTime::now(),
+            });
+        }
+    }
+}
+
+/// The layer to add to the subscriber and the task exporting what it queues, in
+/// batches, until `shutdown`, when the spans still queued are exported last
+pub fn layer(
+    config: OtlpConfig,
+    shutdown: watch::Receiver<bool>,
+) -> anyhow::Result<(OtlpLayer, tokio::task::JoinHandle<()>)> {
+    let client = reqwest::Client::builder().timeout(EXPORT_TIMEOUT).build()?;
+    let (spans, queued) = mpsc::channel(MAX_QUEUE_SIZE);
+    let exporter = tokio::spawn(export(client, config, queued, shutdown));
+    Ok((OtlpLayer { spans }, exporter))
+}
+
+async fn export(
+    client: reqwest::Client,
+    config: OtlpConfig,
+    mut queued: mpsc::Receiver<FinishedSpan>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(SCHEDULE_DELAY);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut batch = Vec::new();
+
+    loop {
+        let (flush, stopping) = tokio::select! {
+            span = queued.recv() => match span {
+                Some(span) => {
+                    batch.push(span);
+                    (batch.len() >= MAX_EXPORT_BATCH_SIZE, false)
+                }
+                None => (true, true),
+            },
+            _ = ticker.tick() => (true, false),
+            _ = shutdown.changed() => (true, true),
+        };
+        if stopping {
+            while let Ok(span) = queued.try_recv() {
+                batch.push(span);
+            }
+        }
+
+        if flush && !batch.is_empty() {
+            for spans in batch.chunks(MAX_EXPORT_BATCH_SIZE) {
+                if let Err(e) = send(&client, &config, spans).await {
+                    eprintln!("Exporting {} spans over OTLP failed: {}", spans.len(), e);
+                }
+            }
+            batch.clear();
+        }
+        if stopping {
+            break;
+        }
+    }
+}
+
+async fn send(
+    client: &reqwest::Client,
+    config: &OtlpConfig,
+    spans: &[FinishedSpan],
+) -> anyhow::Result<()> {
+    let mut request = client
+        .post(&config.endpoint)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    for (key, value) in &config.headers {
+        request = request.header(key, value);
+    }
+    request
+        .body(serde_json::to_vec(&export_request(
+            &config.service_name,
+            spans,
+        ))?)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_config_from_standard_variables() {
+        let config = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            OtlpConfig::from_vars(|name| vars.get(name).cloned())
+        };
+
+        assert_eq!(config(&[]).unwrap(), None);
+        assert_eq!(
+            config(&[
+                ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318/"),
+                (
+                    "OTEL_EXPORTER_OTLP_HEADERS",
+                    "x-api-key=secret, tenant=educk"
+                ),
+                ("OTEL_SERVICE_NAME", "educk-prod"),
+            ])
+            .unwrap(),
+            Some(OtlpConfig {
+                endpoint: "http://collector:4318/v1/traces".to_string(),
+                headers: vec![
+                    ("x-api-key".to_string(), "secret".to_string()),
+                    ("tenant".to_string(), "educk".to_string())
+                ],
+                service_name: "educk-prod".to_string(),
+            })
+        );
+        let traces = config(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318"),
+            (
+                "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+                "https://traces.example.com/ingest",
+            ),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(traces.endpoint, "https://traces.example.com/ingest");
+        assert_eq!(traces.service_name, "educk");
+
+        let endpoint = ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318");
+        assert_eq!(
+            config(&[endpoint, ("OTEL_TRACES_EXPORTER", "none")]).unwrap(),
+            None
+        );
+        assert!(config(&[endpoint, ("OTEL_TRACES_EXPORTER", "zipkin")]).is_err());
+        assert!(config(&[endpoint, ("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc")]).is_err());
+        assert!(config(&[endpoint, ("OTEL_EXPORTER_OTLP_HEADERS", "no-value")]).is_err());
+        assert!(config(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "collector:4318")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spans_are_exported_to_the_collector() {
+        let received = Arc::new(Mutex::new(Vec::<(HeaderMap, Value)>::new()));
+        let app = {
+            let received = received.clone();
+            Router::new().route(
+                "/v1/traces",
+                post(move |headers: HeaderMap, body: String| async move {
+                    let body = serde_json::from_str(&body).unwrap();
+                    received.lock().unwrap().push((headers, body));
+                }),
+            )
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (stop, shutdown) = watch::channel(false);
+        let (layer, exporter) = layer(
+            OtlpConfig {
+                endpoint: format!("http://{address}/v1/traces"),
+                headers: vec![("x-api-key".to_string(), "secret".to_string())],
+                service_name: "educk-test".to_string(),
+            },
+            shutdown,
+        )
+        .unwrap();
+        {
+            let _guard =
+                tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+            let request = tracing::info_span!("request", path = "/api/v1/countries");
+            let _entered = request.enter();
+            let upstream = tracing::info_span!("entsoe_request", points = tracing::field::Empty);
+            upstream.record("points", 24);
+            upstream.in_scope(|| {
+                tracing::info!(target: crate::entsoe::stats::TARGET, parse_ms = 1.5, "parsed document")
+            });
+        }
+        stop.send(true).unwrap();
+        exporter.await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let (headers, body) = &received[0];
+        assert_eq!(headers["x-api-key"], "secret");
+        let resource = &body["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "educk-test"
+        );
+        let spans = resource["scopeSpans"][0]["spans"].as_array().unwrap();
+        // Children close first
+        let (upstream, request) = (&spans[0], &spans[1]);
+        assert_eq!(upstream["name"], "entsoe_request");
+        assert_eq!(request["name"], "request");
+        assert_eq!(upstream["traceId"], request["traceId"]);
+        assert_eq!(upstream["parentSpanId"], request["spanId"]);
+        assert!(request.get("parentSpanId").is_none());
+        assert_eq!(upstream["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(
+            upstream["attributes"],
+            json!([{"key": "points", "value": {"intValue": "24"}}])
+        );
+        assert_eq!(
+            request["attributes"],
+            json!([{"key": "path", "value": {"stringValue": "/api/v1/countries"}}])
+        );
+
+        let event = &upstream["events"][0];
+        assert_eq!(event["name"], "parsed document");
+        assert_eq!(
+            event["attributes"][0],
+            json!({"key": "parse_ms", "value": {"doubleValue": 1.5}})
+        );
+        assert_eq!(
+            event["attributes"][1]["value"]["stringValue"],
+            crate::entsoe::stats::TARGET
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Static PNG and SVG charts of a surplus series, rendered with plotters
+
+use chrono::{DateTime, Duration, Utc};
+use plotters::backend::{BitMapBackend, DrawingBackend, SVGBackend};
+use plotters::chart::{ChartBuilder, SeriesLabelPosition};
+use plotters::coord::Shift;
+use plotters::drawing::{DrawingArea, IntoDrawingArea};
+use plotters::element::PathElement;
+use plotters::series::LineSeries;
+use plotters::style::{BLACK, Color, FontStyle, RGBColor, WHITE};
+
+use crate::entsoe::PowerUnit;
+use crate::entsoe::analysis::RenewableSurplus;
+
+/// Size of images without a requested one, in pixels
+pub const DEFAULT_IMAGE_WIDTH: u32 = 1200;
+pub const DEFAULT_IMAGE_HEIGHT: u32 = 600;
+
+/// Embedded so that rendering works on hosts without any system fonts (e.g. the slim Docker image)
+const PLOT_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotImageFormat {
+    Png,
+    Svg,
+}
+
+impl PlotImageFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            PlotImageFormat::Png => "image/png",
+            PlotImageFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+fn register_plot_font() {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    REGISTER.call_once(|| {
+        if plotters::style::register_font("sans-serif", FontStyle::Normal, PLOT_FONT).is_err() {
+            eprintln!("Failed to register embedded plot font");
+        }
+    });
+}
+
+type SurplusValue = fn(&RenewableSurplus) -> f64;
+
+/// Draw generation, load and surplus traces onto the given drawing area
+fn draw_surplus_chart<DB>(
+    root: &DrawingArea<DB, Shift>,
+    surplus_series: &[RenewableSurplus],
+    unit: PowerUnit,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let (Some(first), Some(last)) = (surplus_series.first(), surplus_series.last()) else {
+        anyhow::bail!("Cannot plot an empty series");
+    };
+    let surplus_series: Vec<RenewableSurplus> = surplus_series
+        .iter()
+        .map(|s| s.in_power_unit(unit))
+        .collect();
+
+    let start = first.timestamp;
+    // A single point still needs a non-empty time axis
+    let end = if last.timestamp > start {
+        last.timestamp
+    } else {
+        start + Duration::hours(1)
+    };
+
+    let (y_min, y_max) = surplus_series
+        .iter()
+        .flat_map(|s| [s.generation, s.load, s.surplus])
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+    let padding = ((y_max - y_min) * 0.05).max(1.0);
+
+    root.fill(&WHITE)?;
+
+    let mut chart = ChartBuilder::on(root)
+        .caption("Renewable Energy Forecast", ("sans-serif", 20))
+        .margin(15)
+        .x_label_area_size(50)
+        .y_label_area_size(80)
+        .build_cartesian_2d(start..end, (y_min - padding)..(y_max + padding))?;
+
+    chart
+        .configure_mesh()
+        .x_labels(8)
+        .y_labels(10)
+        .x_label_formatter(&|t: &DateTime<Utc>| t.format("%d.%m %H:%M").to_string())
+        .y_label_formatter(&|v: &f64| match unit {
+            PowerUnit::Gigawatt => format!("{:.1}", v),
+            _ => format!("{:.0}", v),
+        })
+        .x_desc("Time (UTC)")
+        .y_desc(format!("Power ({})", unit))
+        .light_line_style(RGBColor(235, 235, 235))
+        .draw()?;
+
+    let traces: [(&str, RGBColor, SurplusValue); 3] = [
+        ("Wind + Solar Generation", RGBColor(34, 139, 34), |s| {
+            s.generation
+        }),
+        ("Total Load", RGBColor(30, 144, 255), |s| s.load),
+        ("Surplus (Generation - Load)", RGBColor(255, 140, 0), |s| {
+            s.surplus
+        }),
+    ];
+
+    for (name, color, value) in traces {
+        chart
+            .draw_series(LineSeries::new(
+                surplus_series.iter().map(|s| (s.timestamp, value(s))),
+                color.stroke_width(2),
+            ))?
+            .label(name)
+            .legend(move |(x, y)| {
+                PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+            });
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK.mix(0.2))
+        .draw()?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// Render the surplus series as a static PNG or SVG image
+pub fn render_plot_image(
+    surplus_series: &[RenewableSurplus],
+    width: u32,
+    height: u32,
+    format: PlotImageFormat,
+    unit: PowerUnit,
+) -> anyhow::Result<Vec<u8>> {
+    register_plot_font();
+
+    match format {
+        PlotImageFormat::Png => {
+            let mut buffer = vec![0u8; width as usize * height as usize * 3];
+            {
+                let root =
+                    BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
+                draw_surplus_chart(&root, surplus_series, unit)?;
+            }
+
+            let image = image::RgbImage::from_raw(width, height, buffer)
+                .ok_or_else(|| anyhow::anyhow!("Bitmap buffer does not match image size"))?;
+            let mut png = Vec::new();
+            image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+            Ok(png)
+        }
+        PlotImageFormat::Svg => {
+            let mut svg = String::new();
+            {
+                let root = SVGBackend::with_string(&mut svg, (width, height)).into_drawing_area();
+                draw_surplus_chart(&root, surplus_series, unit)?;
+            }
+            Ok(svg.into_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::sample_surplus;
+
+    #[test]
+    fn test_render_plot_png_has_requested_dimensions() {
+        let png = render_plot_image(
+            &sample_surplus(),
+            800,
+            400,
+            PlotImageFormat::Png,
+            PowerUnit::default(),
+        )
+        .unwrap();
+
+        assert!(png.starts_with(b"\x89PNG"));
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(decoded.width(), 800);
+        assert_eq!(decoded.height(), 400);
+    }
+
+    #[test]
+    fn test_render_plot_svg() {
+        let svg = render_plot_image(
+            &sample_surplus(),
+            640,
+            320,
+            PlotImageFormat::Svg,
+            PowerUnit::Gigawatt,
+        )
+        .unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+
+        assert!(svg.contains("<svg"));
+        assert!(svg.contains(r#"width="640""#));
+        assert!(svg.contains(r#"height="320""#));
+        assert!(svg.contains("Total Load"));
+        assert!(svg.contains("Power (GW)"));
+    }
+
+    #[test]
+    fn test_render_plot_rejects_empty_series() {
+        assert!(
+            render_plot_image(&[], 800, 400, PlotImageFormat::Png, PowerUnit::default()).is_err()
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Charts of surplus series: the Plotly figure of the plot page, static images, and
+//! Vega-Lite specs for other renderers
+
+use serde_json::{Value, json};
+
+use crate::entsoe::analysis::RenewableSurplus;
+
+pub mod image;
+pub mod plotly;
+
+/// `$schema` of the generated specs
+pub const VEGA_LITE_SCHEMA: &str = "https://vega.github.io/schema/vega-lite/v5.json";
+
+/// Field, legend label and colour of each layer, matching the Plotly page
+const VEGA_LAYERS: [(&str, &str, &str); 3] = [
+    (
+        "generation_mw",
+        "Wind + Solar Generation",
+        "rgb(34, 139, 34)",
+    ),
+    ("load_mw", "Total Load", "rgb(30, 144, 255)"),
+    (
+        "surplus_mw",
+        "Surplus (Generation - Load)",
+        "rgb(255, 140, 0)",
+    ),
+];
+
+/// Presentation options of [`vega_spec`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VegaOptions {
+    pub title: Option<String>,
+    /// Pixels, the width of the embedding container when `None`
+    pub width: Option<u32>,
+    /// Pixels, 400 when `None`
+    pub height: Option<u32>,
+}
+
+/// Complete Vega-Lite v5 spec with the series inlined: one line layer each for
+/// generation, load and surplus over a temporal x axis, values in MW
+pub fn vega_spec(series: &[RenewableSurplus], options: &VegaOptions) -> Value {
+    let values: Vec<Value> = series
+        .iter()
+        .map(|point| {
+            json!({
+                "timestamp": point.timestamp.to_rfc3339(),
+                "generation_mw": point.generation,
+                "load_mw": point.load,
+                "surplus_mw": point.surplus,
+            })
+        })
+        .collect();
+
+    let labels: Vec<&str> = VEGA_LAYERS.iter().map(|(_, label, _)| *label).collect();
+    let colors: Vec<&str> = VEGA_LAYERS.iter().map(|(_, _, color)| *color).collect();
+    let layers: Vec<Value> = VEGA_LAYERS
+        .iter()
+        .map(|(field, label, _)| {
+            json!({
+                "mark": { "type": "line", "point": true },
+                "encoding": {
+                    "y": { "field": field, "type": "quantitative", "title": "Power (MW)" },
+                    "color": {
+                        "datum": label,
+                        "scale": { "domain": labels, "range": colors },
+                        "legend": { "title": null }
+                    },
+                    "tooltip": [
+                        {
+                            "field": "timestamp",
+                            "type": "temporal",
+                            "title": "Time",
+                            "format": "%Y-%m-%d %H:%M"
+                        },
+                        {
+                            "field": field,
+                            "type": "quantitative",
+                            "title": format!("{} (MW)", label),
+                            "format": ",.0f"
+                        }
+                    ]
+                }
+            })
+        })
+        .collect();
+
+    let mut spec = json!({
+        "$schema": VEGA_LITE_SCHEMA,
+        "width": options.width.map_or(json!("container"), |width| json!(width)),
+        "height": options.height.unwrap_or(400),
+        "data": { "values": values },
+        "encoding": {
+            "x": { "field": "timestamp", "type": "temporal", "title": "Time" }
+        },
+        "layer": layers,
+    });
+    if let Some(title) = &options.title {
+        spec["title"] = json!(title);
+    }
+    spec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn series() -> Vec<RenewableSurplus> {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        (0..3)
+            .map(|i| RenewableSurplus {
+                timestamp: start + Duration::hours(i),
+                generation: 40_000.0 + 1_000.0 * i as f64,
+                load: 45_000.0,
+                surplus: 1_000.0 * i as f64 - 5_000.0,
+                total_generation: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_vega_spec_structure() {
+        let spec = vega_spec(
+            &series(),
+            &VegaOptions {
+                title: Some("DE".to_string()),
+                ..VegaOptions::default()
+            },
+        );
+
+        assert_eq!(spec["$schema"], VEGA_LITE_SCHEMA);
+        assert_eq!(spec["title"], "DE");
+        assert_eq!(spec["width"], "container");
+        assert_eq!(spec["encoding"]["x"]["type"], "temporal");
+
+        let values = spec["data"]["values"].as_array().unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[2]["timestamp"], "2024-06-01T02:00:00+00:00");
+        assert_eq!(values[2]["surplus_mw"], -3_000.0);
+
+        let layers = spec["layer"].as_array().unwrap();
+        assert_eq!(layers.len(), 3);
+        for layer in layers {
+            assert_eq!(layer["mark"]["type"], "line");
+            let encoding = &layer["encoding"];
+            let field = encoding["y"]["field"].as_str().unwrap();
+            // Every layer plots a field of the inlined values and shows it in the tooltip
+            assert!(values[0].get(field).is_some());
+            assert_eq!(encoding["y"]["type"], "quantitative");
+            assert_eq!(encoding["tooltip"][1]["field"], field);
+            assert!(encoding["color"]["datum"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_vega_spec_dimensions() {
+        let spec = vega_spec(
+            &series(),
+            &VegaOptions {
+                width: Some(800),
+                height: Some(300),
+                ..VegaOptions::default()
+            },
+        );
+
+        assert_eq!(spec["width"], 800);
+        assert_eq!(spec["height"], 300);
+        assert!(spec.get("title").is_none());
+    }
+}
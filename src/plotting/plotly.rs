@@ -0,0 +1,381 @@
+//! Plotly figure of a surplus series, as embedded by the plot page
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::entsoe::PowerUnit;
+use crate::entsoe::analysis::{
+    ForecastSide, GenerationSplit, LoadBand, PartialSurplus, RenewableSurplus, downsample,
+    load_band_at,
+};
+use crate::entsoe::localtime::LocalZone;
+
+/// Plotly traces and layout of a surplus series
+pub struct PlotFigure {
+    /// JSON of the traces
+    pub data: String,
+    /// JSON of the layout
+    pub layout: String,
+    /// Bucket length the series was averaged over, `None` when plotted as is
+    pub resolution: Option<Duration>,
+}
+
+/// `hourly means`, `3-hour means`, ... for a downsampling bucket
+pub fn resolution_label(bucket: Duration) -> String {
+    match bucket.num_hours() {
+        1 => "hourly means".to_string(),
+        24 => "daily means".to_string(),
+        hours => format!("{}-hour means", hours),
+    }
+}
+
+/// Generate Plotly plot data from surplus series. Series longer than `max_points` are
+/// averaged into coarser buckets first and the title names the effective resolution.
+/// Plotly figure of a series, the time axis in UTC or in `local` time. A `load_band`
+/// is shaded around the load.
+pub fn generate_plot_data(
+    surplus_series: &[RenewableSurplus],
+    split: &BTreeMap<DateTime<Utc>, GenerationSplit>,
+    load_band: &[LoadBand],
+    max_points: Option<usize>,
+    local: Option<&LocalZone>,
+    unit: PowerUnit,
+) -> PlotFigure {
+    let downsampled = max_points.and_then(|max_points| {
+        downsample(surplus_series, max_points, local.unwrap_or(&LocalZone::UTC))
+    });
+    let (resolution, surplus_series) = match &downsampled {
+        Some((bucket, points)) => (Some(*bucket), points.as_slice()),
+        None => (None, surplus_series),
+    };
+    let title = match resolution {
+        Some(bucket) => format!(
+            "Renewable Energy Forecast<br><sub>{}</sub>",
+            resolution_label(bucket)
+        ),
+        None => "Renewable Energy Forecast".to_string(),
+    };
+
+    let timestamps = time_labels(surplus_series.iter().map(|s| s.timestamp), local);
+
+    let generation: Vec<f64> = surplus_series
+        .iter()
+        .map(|s| unit.from_mw(s.generation))
+        .collect();
+    let load: Vec<f64> = surplus_series
+        .iter()
+        .map(|s| unit.from_mw(s.load))
+        .collect();
+    let surplus: Vec<f64> = surplus_series
+        .iter()
+        .map(|s| unit.from_mw(s.surplus))
+        .collect();
+
+    let [generation_trace, load_trace, surplus_trace] = TRACES;
+    let mut traces = json!([
+        line_trace(&timestamps, &generation, generation_trace),
+        line_trace(&timestamps, &load, load_trace),
+        line_trace(&timestamps, &surplus, surplus_trace),
+    ]);
+
+    // Solar and wind of each generation point for its tooltip; means of buckets have none
+    if downsampled.is_none() && !split.is_empty() {
+        let customdata: Vec<Option<[f64; 2]>> = surplus_series
+            .iter()
+            .map(|s| {
+                split
+                    .get(&s.timestamp)
+                    .map(|split| [unit.from_mw(split.solar), unit.from_mw(split.wind)])
+            })
+            .collect();
+        traces[0]["customdata"] = json!(customdata);
+        traces[0]["hovertemplate"] = json!(format!(
+            "%{{y}} {unit}<br>Solar: %{{customdata[0]}} {unit}<br>Wind: %{{customdata[1]}} {unit}",
+            unit = unit
+        ));
+    }
+
+    // The lower bound, then the upper one filled down to it
+    if !load_band.is_empty() {
+        let bound = |bound: fn(&LoadBand) -> f64| -> Vec<Option<f64>> {
+            surplus_series
+                .iter()
+                .map(|s| load_band_at(load_band, s.timestamp).map(|band| unit.from_mw(bound(band))))
+                .collect()
+        };
+        let traces = traces.as_array_mut().expect("traces are an array");
+        traces.push(json!({
+            "x": timestamps,
+            "y": bound(|band| band.min_mw),
+            "name": "Load Minimum (week ahead)",
+            "type": "scatter",
+            "mode": "lines",
+            "line": {
+                "color": "rgba(30, 144, 255, 0.4)",
+                "width": 1
+            },
+            "showlegend": false
+        }));
+        traces.push(json!({
+            "x": timestamps,
+            "y": bound(|band| band.max_mw),
+            "name": "Load Range (week ahead)",
+            "type": "scatter",
+            "mode": "lines",
+            "fill": "tonexty",
+            "fillcolor": "rgba(30, 144, 255, 0.15)",
+            "line": {
+                "color": "rgba(30, 144, 255, 0.4)",
+                "width": 1
+            }
+        }));
+    }
+
+    PlotFigure {
+        data: serde_json::to_string(&traces).unwrap(),
+        layout: serde_json::to_string(&layout(&title, local, unit)).unwrap(),
+        resolution,
+    }
+}
+
+/// Plotly figure of a series that may lack a forecast, with a trace for each side that
+/// has points; gaps are left where a value is missing. Plotted as is, without
+/// downsampling.
+pub fn partial_plot_data(
+    points: &[PartialSurplus],
+    local: Option<&LocalZone>,
+    unit: PowerUnit,
+) -> PlotFigure {
+    let timestamps = time_labels(points.iter().map(|point| point.timestamp), local);
+    let values = |value: fn(&PartialSurplus) -> Option<f64>| -> Option<Vec<Option<f64>>> {
+        points.iter().any(|point| value(point).is_some()).then(|| {
+            points
+                .iter()
+                .map(|point| value(point).map(|mw| unit.from_mw(mw)))
+                .collect()
+        })
+    };
+    let traces: Vec<serde_json::Value> = [
+        values(|point| point.generation),
+        values(|point| point.load),
+        values(|point| point.surplus),
+    ]
+    .into_iter()
+    .zip(TRACES)
+    .filter_map(|(values, trace)| Some(line_trace(&timestamps, &values?, trace)))
+    .collect();
+
+    PlotFigure {
+        data: serde_json::to_string(&traces).unwrap(),
+        layout: serde_json::to_string(&layout("Renewable Energy Forecast", local, unit)).unwrap(),
+        resolution: None,
+    }
+}
+
+/// Name of a missing forecast as shown on the plot page
+pub fn forecast_label(side: ForecastSide) -> &'static str {
+    match side {
+        ForecastSide::Generation => "wind and solar forecast",
+        ForecastSide::Load => "load forecast",
+    }
+}
+
+/// Name and colour of the generation, load and surplus traces
+const TRACES: [(&str, &str); 3] = [
+    ("Wind + Solar Generation", "rgb(34, 139, 34)"),
+    ("Total Load", "rgb(30, 144, 255)"),
+    ("Surplus (Generation - Load)", "rgb(255, 140, 0)"),
+];
+
+/// Labels of the time axis. Plotly shows times as given, so local ones are passed as
+/// wall-clock times (the hour repeated when clocks go back appears twice).
+fn time_labels(
+    timestamps: impl Iterator<Item = DateTime<Utc>>,
+    local: Option<&LocalZone>,
+) -> Vec<String> {
+    timestamps
+        .map(|timestamp| match local {
+            Some(local) => local.to_local(timestamp).format("%Y-%m-%d %H:%M"),
+            None => timestamp.format("%Y-%m-%d %H:%M"),
+        })
+        .map(|timestamp| timestamp.to_string())
+        .collect()
+}
+
+fn line_trace(
+    timestamps: &[String],
+    values: &impl serde::Serialize,
+    (name, color): (&str, &str),
+) -> serde_json::Value {
+    json!({
+        "x": timestamps,
+        "y": values,
+        "name": name,
+        "type": "scatter",
+        "mode": "lines+markers",
+        "line": {
+            "color": color,
+            "width": 2
+        },
+        "marker": {
+            "size": 4
+        }
+    })
+}
+
+fn layout(title: &str, local: Option<&LocalZone>, unit: PowerUnit) -> serde_json::Value {
+    let time_axis = match local {
+        Some(local) => format!("Time ({})", local.name),
+        None => "Time (UTC)".to_string(),
+    };
+    json!({
+        "title": {
+            "text": title,
+            "font": {
+                "size": 20
+            }
+        },
+        "xaxis": {
+            "title": time_axis,
+            "tickangle": -45
+        },
+        "yaxis": {
+            "title": format!("Power ({})", unit)
+        },
+        "hovermode": "x unified",
+        "plot_bgcolor": "rgb(250, 250, 250)",
+        "paper_bgcolor": "white",
+        "showlegend": true,
+        "legend": {
+            "x": 0.01,
+            "y": 0.99,
+            "bgcolor": "rgba(255, 255, 255, 0.8)",
+            "bordercolor": "rgba(0, 0, 0, 0.2)",
+            "borderwidth": 1
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_generate_plot_data_downsamples_long_series() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        // Two weeks of 15-minute points
+        let series: Vec<RenewableSurplus> = (0..14 * 96)
+            .map(|i| RenewableSurplus {
+                timestamp: start + Duration::minutes(15 * i),
+                generation: 40_000.0 + (i % 96) as f64 * 100.0,
+                load: 50_000.0,
+                surplus: (i % 96) as f64 * 100.0 - 10_000.0,
+                total_generation: None,
+            })
+            .collect();
+        let traces = |figure: &PlotFigure| -> serde_json::Value {
+            serde_json::from_str(&figure.data).unwrap()
+        };
+
+        let figure = generate_plot_data(
+            &series,
+            &BTreeMap::new(),
+            &[],
+            Some(500),
+            None,
+            PowerUnit::default(),
+        );
+        assert_eq!(figure.resolution, Some(Duration::hours(1)));
+        let surplus = traces(&figure)[2]["y"].as_array().unwrap().clone();
+        assert_eq!(surplus.len(), 14 * 24);
+        let surplus: Vec<f64> = surplus.iter().map(|v| v.as_f64().unwrap()).collect();
+        assert!(surplus.iter().all(|v| (-10_000.0..=-500.0).contains(v)));
+        assert!(figure.layout.contains("hourly means"));
+
+        let figure = generate_plot_data(
+            &series,
+            &BTreeMap::new(),
+            &[],
+            None,
+            None,
+            PowerUnit::default(),
+        );
+        assert_eq!(figure.resolution, None);
+        assert_eq!(traces(&figure)[0]["x"].as_array().unwrap().len(), 14 * 96);
+        assert!(!figure.layout.contains("means"));
+    }
+
+    #[test]
+    fn test_load_band_is_filled_between_its_bounds() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let series: Vec<RenewableSurplus> = (0..36)
+            .map(|i| RenewableSurplus {
+                timestamp: start + Duration::hours(i),
+                generation: 40_000.0,
+                load: 50_000.0,
+                surplus: -10_000.0,
+                total_generation: None,
+            })
+            .collect();
+        // The first day only
+        let band = [LoadBand {
+            timestamp: start,
+            duration: Duration::days(1),
+            min_mw: 45_000.0,
+            max_mw: 55_000.0,
+        }];
+
+        let figure = generate_plot_data(
+            &series,
+            &BTreeMap::new(),
+            &band,
+            None,
+            None,
+            PowerUnit::Gigawatt,
+        );
+        let traces: serde_json::Value = serde_json::from_str(&figure.data).unwrap();
+        let traces = traces.as_array().unwrap();
+        assert_eq!(traces.len(), 5);
+        assert_eq!(traces[4]["fill"], "tonexty");
+        assert_eq!(traces[3]["y"][23], 45.0);
+        assert_eq!(traces[4]["y"][0], 55.0);
+        // Not forecast beyond the band
+        assert!(traces[4]["y"][24].is_null());
+    }
+
+    #[test]
+    fn test_partial_plot_shows_the_available_traces() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let points: Vec<PartialSurplus> = (0..3)
+            .map(|i| PartialSurplus {
+                timestamp: start + Duration::hours(i),
+                generation: Some(40_000.0),
+                load: (i > 0).then_some(50_000.0),
+                surplus: (i > 0).then_some(-10_000.0),
+            })
+            .collect();
+        let traces = |points: &[PartialSurplus]| -> serde_json::Value {
+            let figure = partial_plot_data(points, None, PowerUnit::Gigawatt);
+            serde_json::from_str(&figure.data).unwrap()
+        };
+
+        let all = traces(&points);
+        assert_eq!(all.as_array().unwrap().len(), 3);
+        assert!(all[1]["y"][0].is_null());
+        assert_eq!(all[2]["y"][1], -10.0);
+
+        let without_load: Vec<PartialSurplus> = points
+            .into_iter()
+            .map(|point| PartialSurplus {
+                load: None,
+                surplus: None,
+                ..point
+            })
+            .collect();
+        let generation_only = traces(&without_load);
+        assert_eq!(generation_only.as_array().unwrap().len(), 1);
+        assert_eq!(generation_only[0]["name"], "Wind + Solar Generation");
+    }
+}
@@ -0,0 +1,308 @@
+//! Sources of load and wind/solar forecasts for the surplus analysis: ENTSO-E, or a
+//! forecast of one's own read from a CSV file
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+
+use crate::entsoe::analysis::{
+    Freshness, SurplusSeries, generation_series, join_surplus, load_series,
+};
+use crate::entsoe::request::QueryParams;
+use crate::entsoe::window::TimeWindow;
+use crate::entsoe::{EntsoeClient, ForecastSource, MeasureUnit, TimestampedPoint};
+
+/// Load and renewable generation forecasts of bidding zones
+#[async_trait]
+pub trait ForecastProvider: Send + Sync {
+    /// Total load forecast of `zone` with points starting in `[start, end)`, by time
+    async fn fetch_load(
+        &self,
+        zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TimestampedPoint>>;
+
+    /// Wind and solar generation forecast of `zone` with points starting in
+    /// `[start, end)`, by time
+    async fn fetch_renewables(
+        &self,
+        zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TimestampedPoint>>;
+
+    /// Surplus series of `zone` over `window`. Providers with a single forecast per
+    /// zone ignore `freshness`.
+    async fn surplus_series(
+        &self,
+        zone: &str,
+        window: TimeWindow,
+        _freshness: Freshness,
+    ) -> anyhow::Result<SurplusSeries> {
+        let (generation, load) = tokio::try_join!(
+            self.fetch_renewables(zone, window.start, window.end),
+            self.fetch_load(zone, window.start, window.end)
+        )?;
+        Ok(join_surplus(&generation, &load)?)
+    }
+}
+
+fn retain_between(points: &mut Vec<TimestampedPoint>, start: DateTime<Utc>, end: DateTime<Utc>) {
+    points.retain(|point| point.timestamp >= start && point.timestamp < end);
+}
+
+#[async_trait]
+impl ForecastProvider for EntsoeClient {
+    /// The day-ahead total load forecast (A65)
+    async fn fetch_load(
+        &self,
+        zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TimestampedPoint>> {
+        let params = QueryParams::total_load_forecast(zone, ForecastSource::DayAhead);
+        let document = self.fetch_range(params, start, end).await?;
+        let mut points = document.timestamped_points_where(&load_series(zone))?;
+        retain_between(&mut points, start, end);
+        Ok(points)
+    }
+
+    /// The day-ahead wind and solar forecast (A69)
+    async fn fetch_renewables(
+        &self,
+        zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TimestampedPoint>> {
+        let params = QueryParams::generation_forecast(zone, ForecastSource::DayAhead);
+        let document = self.fetch_range(params, start, end).await?;
+        let mut points = document.timestamped_points_where(&generation_series(zone))?;
+        retain_between(&mut points, start, end);
+        Ok(points)
+    }
+
+    /// Stitched from the forecasts `freshness` selects, with the total generation
+    /// forecast where the zone publishes one
+    async fn surplus_series(
+        &self,
+        zone: &str,
+        window: TimeWindow,
+        freshness: Freshness,
+    ) -> anyhow::Result<SurplusSeries> {
+        let (period_start, period_end) = window.period();
+        let mut series = self
+            .get_surplus_series(zone, &period_start, &period_end, freshness)
+            .await?;
+        series.retain_between(window.start, window.end);
+        Ok(series)
+    }
+}
+
+#[derive(Deserialize)]
+struct CsvRow {
+    timestamp: String,
+    generation_mw: f64,
+    load_mw: f64,
+}
+
+/// A local forecast of one zone in a CSV file with the columns `timestamp` (RFC3339,
+/// start of the interval), `generation_mw` (wind and solar) and `load_mw`. Each point
+/// lasts until the next; the last one as long as the one before it.
+#[derive(Debug, Clone)]
+pub struct CsvForecast {
+    generation: Vec<TimestampedPoint>,
+    load: Vec<TimestampedPoint>,
+}
+
+impl CsvForecast {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read forecast {}: {}", path.display(), e))?;
+        Self::from_reader(file).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn from_reader(reader: impl Read) -> anyhow::Result<Self> {
+        let mut rows = Vec::new();
+        for (line, row) in csv::Reader::from_reader(reader)
+            .deserialize::<CsvRow>()
+            .enumerate()
+        {
+            // Line 1 is the header
+            let row = row.map_err(|e| anyhow::anyhow!("Line {}: {}", line + 2, e))?;
+            let timestamp = DateTime::parse_from_rfc3339(row.timestamp.trim())
+                .map_err(|_| {
+                    anyhow::anyhow!(
+                        "Line {}: invalid timestamp {:?}, expected RFC3339",
+                        line + 2,
+                        row.timestamp
+                    )
+                })?
+                .with_timezone(&Utc);
+            rows.push((timestamp, row.generation_mw, row.load_mw));
+        }
+
+        rows.sort_by_key(|(timestamp, _, _)| *timestamp);
+        if rows.len() < 2 {
+            anyhow::bail!("A forecast needs at least two points to tell their duration");
+        }
+        if let Some(pair) = rows.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            anyhow::bail!("Two points at {}", pair[0].0.to_rfc3339());
+        }
+
+        let durations: Vec<Duration> = rows
+            .windows(2)
+            .map(|pair| pair[1].0 - pair[0].0)
+            .chain(std::iter::once(
+                rows[rows.len() - 1].0 - rows[rows.len() - 2].0,
+            ))
+            .collect();
+        let point = |i: usize, quantity: f64| TimestampedPoint {
+            timestamp: rows[i].0,
+            position: i as u32 + 1,
+            quantity,
+            unit: MeasureUnit::Megawatt,
+            duration: durations[i],
+        };
+        Ok(Self {
+            generation: (0..rows.len()).map(|i| point(i, rows[i].1)).collect(),
+            load: (0..rows.len()).map(|i| point(i, rows[i].2)).collect(),
+        })
+    }
+
+    fn between(
+        points: &[TimestampedPoint],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<TimestampedPoint> {
+        let mut points = points.to_vec();
+        retain_between(&mut points, start, end);
+        points
+    }
+}
+
+#[async_trait]
+impl ForecastProvider for CsvForecast {
+    /// The `load_mw` column, whatever `zone`
+    async fn fetch_load(
+        &self,
+        _zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TimestampedPoint>> {
+        Ok(Self::between(&self.load, start, end))
+    }
+
+    /// The `generation_mw` column, whatever `zone`
+    async fn fetch_renewables(
+        &self,
+        _zone: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<TimestampedPoint>> {
+        Ok(Self::between(&self.generation, start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::analysis::find_windows;
+    use crate::entsoe::testing::{DEFAULT_ZONE, MockTransport};
+    use chrono::TimeZone;
+    use std::sync::Arc;
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    const CSV: &str = "\
+timestamp,generation_mw,load_mw
+2024-06-01T03:00:00+02:00,1200,1000
+2024-06-01T02:00:00Z,800,1000
+2024-06-01T00:00:00Z,1500,1000
+2024-06-01T03:00:00Z,900,1000
+";
+
+    #[tokio::test]
+    async fn test_csv_forecast_feeds_the_analysis() {
+        let forecast = CsvForecast::from_reader(CSV.as_bytes()).unwrap();
+        let window = TimeWindow::between(midnight(), midnight() + Duration::hours(6));
+
+        let load = forecast
+            .fetch_load("any", window.start, window.end)
+            .await
+            .unwrap();
+        let durations: Vec<_> = load.iter().map(|p| p.duration.num_minutes()).collect();
+        assert_eq!(durations, [60, 60, 60, 60]);
+
+        let series = forecast
+            .surplus_series("any", window, Freshness::default())
+            .await
+            .unwrap();
+        let surpluses: Vec<_> = series.points.iter().map(|p| p.surplus).collect();
+        assert_eq!(surpluses, [500.0, 200.0, -200.0, -100.0]);
+        assert_eq!(series.covered[0].end, midnight() + Duration::hours(4));
+        assert_eq!(series.unit, MeasureUnit::Megawatt);
+        let windows = find_windows(&series.points, Duration::hours(2), |p| p.surplus > 0.0);
+        assert_eq!(windows.len(), 1);
+
+        let later = forecast
+            .fetch_renewables("any", midnight() + Duration::hours(1), window.end)
+            .await
+            .unwrap();
+        assert_eq!(later.len(), 3);
+    }
+
+    #[test]
+    fn test_invalid_csv_forecasts() {
+        let error = |csv: &str| {
+            CsvForecast::from_reader(csv.as_bytes())
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(
+            error("timestamp,generation_mw,load_mw\n2024-06-01,1,2\n2024-06-01T01:00:00Z,1,2\n")
+                .contains("Line 2: invalid timestamp")
+        );
+        assert!(error("timestamp,generation_mw\n2024-06-01T00:00:00Z,1\n").contains("Line 2"));
+        assert!(
+            error("timestamp,generation_mw,load_mw\n2024-06-01T00:00:00Z,1,2\n")
+                .contains("at least two points")
+        );
+        assert!(
+            error("timestamp,generation_mw,load_mw\n2024-06-01T00:00:00Z,1,2\n2024-06-01T02:00:00+02:00,1,2\n")
+                .contains("Two points at")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entsoe_client_as_provider() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let provider: &dyn ForecastProvider = &client;
+        let (start, end) = (
+            midnight() + Duration::hours(6),
+            midnight() + Duration::hours(12),
+        );
+
+        let load = provider.fetch_load(DEFAULT_ZONE, start, end).await.unwrap();
+        assert_eq!(load.len(), 6);
+        assert_eq!(load[0].timestamp, start);
+
+        let series = provider
+            .surplus_series(
+                DEFAULT_ZONE,
+                TimeWindow::between(start, end),
+                Freshness::DayAhead,
+            )
+            .await
+            .unwrap();
+        assert_eq!(series.points.len(), 6);
+        assert_eq!(series.points[0].surplus, 46_000.0 - 50_000.0);
+        assert!(series.generation_doc_meta.is_some());
+    }
+}
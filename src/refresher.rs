@@ -0,0 +1,265 @@
+//! Periodic background refresh of the prefetched countries
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{broadcast, watch};
+
+use crate::entsoe::EntsoeClient;
+use crate::entsoe::analysis::{
+    FINGERPRINT_PRECISION_MW, Freshness, SurplusSeries, diff_summary, series_fingerprint,
+};
+use crate::entsoe::areas::get_primary_zone;
+use crate::entsoe::window::TimeWindow;
+use crate::openmetrics::SummaryGauges;
+
+/// How far ahead each refresh fetches
+const REFRESH_HORIZON_HOURS: i64 = 48;
+
+/// Capacity of the event channel; slow subscribers skip older events
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Result of refreshing one country
+#[derive(Debug, Clone)]
+pub struct RefreshEvent {
+    pub country_code: String,
+    pub refreshed_at: DateTime<Utc>,
+    pub series: Arc<SurplusSeries>,
+}
+
+/// Fetches the surplus series of a fixed set of countries on an interval and
+/// broadcasts the refreshes that brought a new forecast revision with new values to its
+/// subscribers
+pub struct Refresher {
+    client: Arc<EntsoeClient>,
+    countries: Vec<String>,
+    interval: std::time::Duration,
+    events: broadcast::Sender<RefreshEvent>,
+    latest: RwLock<HashMap<String, RefreshEvent>>,
+    gauges: SummaryGauges,
+}
+
+impl Refresher {
+    pub fn new(
+        client: Arc<EntsoeClient>,
+        countries: Vec<String>,
+        interval: std::time::Duration,
+    ) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            client,
+            countries,
+            interval,
+            events,
+            latest: RwLock::new(HashMap::new()),
+            gauges: SummaryGauges::new(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RefreshEvent> {
+        self.events.subscribe()
+    }
+
+    /// Most recent successful refresh of a country
+    pub fn latest(&self, country_code: &str) -> Option<RefreshEvent> {
+        self.latest.read().unwrap().get(country_code).cloned()
+    }
+
+    /// Headline gauges of the countries refreshed so far, see `GET /metrics`
+    pub fn gauges(&self) -> &SummaryGauges {
+        &self.gauges
+    }
+
+    /// Refresh every country once; failures are logged and skipped.
+    /// A refresh bringing no new forecast revision, or one changing no value by
+    /// [`FINGERPRINT_PRECISION_MW`] or more, updates [`Self::latest`] silently.
+    pub async fn refresh_once(&self) {
+        for country_code in &self.countries {
+            if let Err(e) = self.refresh_country(country_code).await {
+                eprintln!("Background refresh of {} failed: {}", country_code, e);
+            }
+        }
+    }
+
+    async fn refresh_country(&self, country_code: &str) -> anyhow::Result<()> {
+        let zone = get_primary_zone(country_code)
+            .ok_or_else(|| anyhow::anyhow!("Unknown country code"))?;
+
+        let now = Utc::now();
+        // Start an hour back so the point currently in effect is included
+        let (period_start, period_end) = TimeWindow::next_hours_from(now, REFRESH_HORIZON_HOURS)
+            .extended_back(Duration::hours(1))
+            .period();
+        let series = self
+            .client
+            .get_surplus_series(zone.code, &period_start, &period_end, Freshness::default())
+            .await?;
+
+        self.gauges.update(country_code, &series, now);
+        let event = RefreshEvent {
+            country_code: country_code.to_string(),
+            refreshed_at: now,
+            series: Arc::new(series),
+        };
+        let previous = self
+            .latest
+            .write()
+            .unwrap()
+            .insert(country_code.to_string(), event.clone());
+
+        // Subscribers only hear about new forecast revisions that change the series by
+        // more than floating-point noise
+        let changed = previous.is_none_or(|previous| {
+            let (old, new) = (&previous.series, &event.series);
+            !old.same_revision(new)
+                && series_fingerprint(&old.points) != series_fingerprint(&new.points)
+                && diff_summary(&old.points, &new.points).is_significant(FINGERPRINT_PRECISION_MW)
+        });
+        if changed {
+            // Sending only fails without subscribers
+            let _ = self.events.send(event);
+        }
+
+        Ok(())
+    }
+
+    /// Refresh on every interval tick until `shutdown` turns true
+    pub async fn run(self: Arc<Self>, mut shutdown: watch::Receiver<bool>) {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => self.refresh_once().await,
+                _ = shutdown.changed() => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::{MockTransport, gl_document_created, ok, query_param};
+    use chrono::DurationRound;
+
+    #[tokio::test]
+    async fn test_refresh_broadcasts_each_country() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let refresher = Refresher::new(
+            Arc::new(client),
+            vec!["DE".to_string(), "XX".to_string(), "FR".to_string()],
+            std::time::Duration::from_secs(60),
+        );
+        let mut events = refresher.subscribe();
+
+        refresher.refresh_once().await;
+
+        // The unknown country is skipped without stopping the others
+        let first = events.try_recv().unwrap();
+        let second = events.try_recv().unwrap();
+        assert!(events.try_recv().is_err());
+        assert_eq!(first.country_code, "DE");
+        assert_eq!(second.country_code, "FR");
+        assert!(!first.series.points.is_empty());
+
+        assert!(refresher.latest("DE").is_some());
+        assert!(refresher.latest("XX").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_revision_is_not_broadcast() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let refresher = Refresher::new(
+            Arc::new(client),
+            vec!["DE".to_string()],
+            std::time::Duration::from_secs(60),
+        );
+        let mut events = refresher.subscribe();
+
+        refresher.refresh_once().await;
+        let first_refresh = refresher.latest("DE").unwrap().refreshed_at;
+        refresher.refresh_once().await;
+
+        // The mock keeps serving the same createdDateTime and revision
+        assert!(events.try_recv().is_ok());
+        assert!(events.try_recv().is_err());
+        assert!(refresher.latest("DE").unwrap().refreshed_at >= first_refresh);
+    }
+
+    /// Day-aligned hourly forecasts issued anew on every request, with `offset(n)` MW
+    /// added to the load of the `n`-th set of documents
+    fn reissued_forecasts(offset: fn(usize) -> f64) -> MockTransport {
+        let requests = std::sync::atomic::AtomicUsize::new(0);
+        MockTransport::new(move |url| {
+            let start = query_param(url, "periodStart")
+                .and_then(|s| chrono::NaiveDateTime::parse_from_str(&s, "%Y%m%d%H%M").ok())
+                .unwrap()
+                .and_utc()
+                .duration_trunc(Duration::days(1))
+                .unwrap();
+            let doc_type = query_param(url, "documentType").unwrap();
+            // Each refresh asks for the three documents
+            let issue = requests.fetch_add(1, std::sync::atomic::Ordering::SeqCst) / 3;
+            let quantity = match doc_type.as_str() {
+                "A65" => 50_000.0 + offset(issue),
+                _ => 40_000.0,
+            };
+            let created = format!("2024-06-01T12:{:02}:00Z", issue);
+            ok(gl_document_created(
+                &doc_type,
+                &created,
+                start,
+                60,
+                &[quantity; 96],
+            ))
+        })
+    }
+
+    async fn broadcasts(transport: MockTransport) -> usize {
+        let client = EntsoeClient::with_transport("test-token", Arc::new(transport));
+        let refresher = Refresher::new(
+            Arc::new(client),
+            vec!["DE".to_string()],
+            std::time::Duration::from_secs(60),
+        );
+        let mut events = refresher.subscribe();
+        refresher.refresh_once().await;
+        refresher.refresh_once().await;
+        std::iter::from_fn(|| events.try_recv().ok()).count()
+    }
+
+    #[tokio::test]
+    async fn test_new_revisions_within_noise_are_not_broadcast() {
+        assert_eq!(
+            broadcasts(reissued_forecasts(|issue| issue as f64 * 0.1)).await,
+            1
+        );
+        assert_eq!(
+            broadcasts(reissued_forecasts(|issue| issue as f64 * 5.0)).await,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_shutdown() {
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let refresher = Arc::new(Refresher::new(
+            Arc::new(client),
+            vec!["DE".to_string()],
+            std::time::Duration::from_secs(3600),
+        ));
+        let mut events = refresher.subscribe();
+        let (stop, shutdown) = watch::channel(false);
+
+        let task = tokio::spawn(refresher.clone().run(shutdown));
+        // The first tick fires immediately
+        assert_eq!(events.recv().await.unwrap().country_code, "DE");
+
+        stop.send(true).unwrap();
+        task.await.unwrap();
+    }
+}
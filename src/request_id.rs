@@ -0,0 +1,72 @@
+//! Ids tying an API request to the log lines and upstream requests it caused. The server
+//! answers under the `X-Request-Id` a client sent, or a new one, and runs the request in
+//! [`scope`]; ENTSO-E requests made meanwhile name the id in their span.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Header a request id is taken from and answered in
+pub const HEADER: &str = "x-request-id";
+/// Longest id of a client that is honoured
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// Run `future` on behalf of the request with id `id`
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+    CURRENT.scope(id, future).await
+}
+
+/// Id of the request being answered, `None` outside of [`scope`]
+pub fn current() -> Option<String> {
+    CURRENT.try_with(Clone::clone).ok()
+}
+
+/// Whether an id sent by a client is safe to log and to echo as header
+pub fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_LEN
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_.:".contains(&byte))
+}
+
+/// A new id: the microsecond the server started handing them out and a counter, so ids
+/// are unique within the process and unlikely to repeat after a restart
+pub fn generate() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    static STARTED: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+    let started = STARTED.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros() as u64)
+            .unwrap_or_default()
+    });
+    format!("{:x}-{:x}", started, NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_current_id_is_scoped() {
+        assert_eq!(current(), None);
+        let inner = scope("abc".to_string(), async { current() }).await;
+        assert_eq!(inner.as_deref(), Some("abc"));
+        assert_eq!(current(), None);
+    }
+
+    #[test]
+    fn test_generated_ids_are_distinct_and_valid() {
+        let (first, second) = (generate(), generate());
+        assert_ne!(first, second);
+        assert!(is_valid(&first), "{}", first);
+        assert!(!is_valid(""));
+        assert!(!is_valid("line\nbreak"));
+        assert!(!is_valid(&"x".repeat(MAX_LEN + 1)));
+        assert!(is_valid("3f2c-req_1.a:b"));
+    }
+}
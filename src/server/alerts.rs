@@ -0,0 +1,151 @@
+//! The alert subscriptions of `EDUCK_ALERTS` and what was delivered for them
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde::Serialize;
+
+use super::AppState;
+use super::dto::ApiResponse;
+use super::error::ApiError;
+use super::routes::ApiRoute;
+use crate::alerts::{Alert, DeliveryRecord};
+
+#[derive(Serialize)]
+struct AlertSummary {
+    id: String,
+    country_code: String,
+    threshold_mw: f64,
+    channels: Vec<&'static str>,
+    /// Recorded delivery attempts
+    deliveries: usize,
+}
+
+impl AlertSummary {
+    fn of(alert: &Alert) -> Self {
+        Self {
+            id: alert.id.clone(),
+            country_code: alert.country_code.clone(),
+            threshold_mw: alert.threshold_mw,
+            channels: alert.channels(),
+            deliveries: alert.deliveries().len(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DeliveriesResponse {
+    id: String,
+    /// Oldest first, the last [`crate::alerts::MAX_RECORDED_DELIVERIES`] attempts
+    deliveries: Vec<DeliveryRecord>,
+}
+
+/// Empty without `EDUCK_ALERTS`
+async fn list_alerts(State(state): State<AppState>) -> Json<ApiResponse<Vec<AlertSummary>>> {
+    let alerts = state
+        .alerts
+        .iter()
+        .flat_map(|alerts| alerts.iter())
+        .map(AlertSummary::of)
+        .collect();
+    Json(ApiResponse::success(alerts))
+}
+
+async fn get_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<DeliveriesResponse>>, ApiError> {
+    let alert = state
+        .alerts
+        .as_ref()
+        .and_then(|alerts| alerts.get(&id))
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, format!("No alert {:?}", id)))?;
+
+    Ok(Json(ApiResponse::success(DeliveriesResponse {
+        id: alert.id.clone(),
+        deliveries: alert.deliveries(),
+    })))
+}
+
+pub(super) fn routes() -> [ApiRoute; 2] {
+    [
+        ApiRoute::get("/api/v1/alerts", list_alerts).usage("configured by EDUCK_ALERTS"),
+        ApiRoute::get("/api/v1/alerts/{id}/deliveries", get_deliveries),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::Alerts;
+    use crate::entsoe::analysis::{RenewableSurplus, SurplusSeries};
+    use crate::entsoe::testing::MockTransport;
+    use crate::notify::RecordingNotifier;
+    use crate::refresher::RefreshEvent;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state};
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_deliveries_of_an_alert() {
+        let now = chrono::Utc::now();
+        let series = SurplusSeries {
+            points: vec![RenewableSurplus {
+                timestamp: now,
+                generation: 3_000.0,
+                load: 1_000.0,
+                surplus: 2_000.0,
+                total_generation: None,
+            }],
+            ..SurplusSeries::default()
+        };
+        let alerts = Arc::new(Alerts::new(vec![Alert::new(
+            "de",
+            "DE",
+            0.0,
+            vec![
+                Arc::new(RecordingNotifier::default()),
+                Arc::new(RecordingNotifier::failing("unreachable")),
+            ],
+        )]));
+        alerts
+            .handle(&RefreshEvent {
+                country_code: "DE".to_string(),
+                refreshed_at: now,
+                series: Arc::new(series),
+            })
+            .await;
+        let app = router(test_state(Arc::new(MockTransport::forecasts())).with_alerts(alerts));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/alerts"))
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"][0]["id"], "de");
+        assert_eq!(body["data"][0]["channels"][0], "recording");
+        assert_eq!(body["data"][0]["deliveries"], 2);
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/alerts/de/deliveries"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let deliveries = body["data"]["deliveries"].as_array().unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(deliveries[0]["error"], Value::Null);
+        assert_eq!(deliveries[1]["error"], "unreachable");
+        assert_eq!(deliveries[1]["notification"]["country_code"], "DE");
+
+        let response = app
+            .oneshot(get_request("/api/v1/alerts/fr/deliveries"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,212 @@
+//! When a home battery should charge and discharge along the surplus forecast, to store
+//! as much wind and solar as it can
+
+use axum::Json;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, State};
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use super::dto::{ApiResponse, ForecastInfo};
+use super::error::{ApiError, NO_SURPLUS_POINTS, ValidQuery};
+use super::query::{fetch_window_series, query_window, requested_freshness, requested_zone};
+use super::routes::ApiRoute;
+use crate::entsoe::analysis::{
+    BatteryParams, BatterySimulation, BatteryStep, Freshness, simulate_battery,
+};
+
+#[derive(Deserialize)]
+pub(super) struct SimulationQuery {
+    /// Number of hours to simulate (default: 24)
+    hours: Option<u32>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct BatteryStepResponse {
+    timestamp: String,
+    duration_minutes: i64,
+    surplus_mw: f64,
+    /// Drawn from the grid while charging, negative while discharging
+    power_kw: f64,
+    /// At the end of the step
+    soc_kwh: f64,
+}
+
+impl From<&BatteryStep> for BatteryStepResponse {
+    fn from(step: &BatteryStep) -> Self {
+        Self {
+            timestamp: step.timestamp.to_rfc3339(),
+            duration_minutes: step.duration.num_minutes(),
+            surplus_mw: step.surplus,
+            power_kw: step.power_kw,
+            soc_kwh: step.soc_kwh,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct BatterySimulationResponse {
+    country_code: String,
+    charged_kwh: f64,
+    discharged_kwh: f64,
+    final_soc_kwh: f64,
+    /// Percentage of the charged energy covered by wind and solar, `null` if the battery
+    /// never charges
+    renewable_share_of_charge: Option<f64>,
+    steps: Vec<BatteryStepResponse>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+}
+
+impl BatterySimulationResponse {
+    fn new(country_code: String, simulation: &BatterySimulation, forecast: ForecastInfo) -> Self {
+        Self {
+            country_code,
+            charged_kwh: simulation.charged_kwh,
+            discharged_kwh: simulation.discharged_kwh,
+            final_soc_kwh: simulation.final_soc_kwh,
+            renewable_share_of_charge: simulation.renewable_share_of_charge,
+            steps: simulation.steps.iter().map(Into::into).collect(),
+            forecast,
+        }
+    }
+}
+
+/// POST /api/v1/simulate/battery/:country?hours=24
+/// Charge and discharge schedule of the battery in the JSON body over the next hours
+async fn simulate(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<SimulationQuery>,
+    battery: Result<Json<BatteryParams>, JsonRejection>,
+) -> Result<Json<ApiResponse<BatterySimulationResponse>>, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let Json(battery) =
+        battery.map_err(|rejection| ApiError::bad_request(rejection.body_text()))?;
+    battery.validate().map_err(ApiError::bad_request)?;
+
+    let window = query_window(
+        Some(query.hours.unwrap_or(24)),
+        None,
+        None,
+        None,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let series = fetch_window_series(
+        &state,
+        zone.code,
+        &window,
+        requested_freshness(query.freshness, query.use_intraday),
+    )
+    .await?;
+    if series.points.is_empty() {
+        return Err(ApiError::no_data(NO_SURPLUS_POINTS));
+    }
+
+    let simulation = simulate_battery(&series.points, &battery);
+    Ok(Json(ApiResponse::success(BatterySimulationResponse::new(
+        country_code.to_ascii_uppercase(),
+        &simulation,
+        (&series).into(),
+    ))))
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [
+        ApiRoute::post("/api/v1/simulate/battery/{country}", simulate)
+            .usage("?hours=24, JSON body: capacity_kwh, max_power_kw, efficiency, initial_soc"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, test_state};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn post(uri: &str, body: &str) -> Request<Body> {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_battery_simulation_endpoint() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let battery = json!({
+            "capacity_kwh": 10.0,
+            "max_power_kw": 3.0,
+            "efficiency": 0.9,
+            "initial_soc": 0.5
+        });
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                "/api/v1/simulate/battery/de?hours=6",
+                &battery.to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+        assert_eq!(data["country_code"], "DE");
+        let steps = data["steps"].as_array().unwrap();
+        assert!(steps.len() >= 6, "{}", steps.len());
+        for step in steps {
+            assert_eq!(step["duration_minutes"], 60);
+            let soc = step["soc_kwh"].as_f64().unwrap();
+            assert!((0.0..=10.0).contains(&soc), "{}", soc);
+            assert!(step["power_kw"].as_f64().unwrap().abs() <= 3.0 + 1e-9);
+        }
+        assert!(data["charged_kwh"].as_f64().unwrap() > 0.0);
+        assert!(data["discharged_kwh"].as_f64().unwrap() > 0.0);
+        assert!(data["forecast_created_at"].is_string());
+
+        for (payload, message) in [
+            (
+                json!({"capacity_kwh": 10.0, "max_power_kw": 3.0, "efficiency": 1.5, "initial_soc": 0.0})
+                    .to_string(),
+                "efficiency",
+            ),
+            (
+                json!({"capacity_kwh": 10.0, "max_power_kw": 3.0}).to_string(),
+                "efficiency",
+            ),
+            ("not json".to_string(), "JSON"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(post("/api/v1/simulate/battery/DE", &payload))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", payload);
+            let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["success"], false);
+            assert!(
+                body["error"].as_str().unwrap().contains(message),
+                "{}",
+                body
+            );
+        }
+
+        let response = app
+            .oneshot(post("/api/v1/simulate/battery/XX", &battery.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
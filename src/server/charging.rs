@@ -0,0 +1,340 @@
+//! When to charge an electric vehicle: the energy it needs, placed into the intervals
+//! with the highest surplus, or the lowest price, between plugging in and the deadline
+
+use axum::Json;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use chrono::{Duration, DurationRound};
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use super::dto::{ApiResponse, ForecastInfo};
+use super::error::{ApiError, ValidQuery};
+use super::query::{
+    fetch_window_series, parse_query_time, requested_freshness, requested_zone, require_supported,
+};
+use super::routes::ApiRoute;
+use crate::entsoe::analysis::{
+    EnergyPlan, EnergySlot, Freshness, PlanObjective, PlannedInterval, plan_energy,
+};
+use crate::entsoe::areas::DocumentKind;
+use crate::entsoe::window::TimeWindow;
+
+/// Error code of plans that cannot meet the demand by the deadline
+const INFEASIBLE: &str = "infeasible";
+
+#[derive(Deserialize)]
+pub(super) struct PlanQuery {
+    /// `surplus` (default) or `price`
+    objective: Option<PlanObjective>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ChargeRequest {
+    energy_kwh: f64,
+    max_power_kw: f64,
+    /// RFC3339
+    ready_by: String,
+    /// RFC3339, default: now; plans never start in the past
+    plugged_in_from: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct PlannedIntervalResponse {
+    start: String,
+    end: String,
+    energy_kwh: f64,
+    surplus_mw: f64,
+    /// Per MWh, `null` without a published price
+    price: Option<f64>,
+}
+
+impl From<&PlannedInterval> for PlannedIntervalResponse {
+    fn from(interval: &PlannedInterval) -> Self {
+        Self {
+            start: interval.start.to_rfc3339(),
+            end: interval.end.to_rfc3339(),
+            energy_kwh: interval.energy_kwh,
+            surplus_mw: interval.surplus,
+            price: interval.price,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct ChargePlanResponse {
+    country_code: String,
+    objective: &'static str,
+    energy_kwh: f64,
+    /// Means over the planned energy
+    mean_surplus_mw: f64,
+    mean_price: Option<f64>,
+    /// Currency of the prices, only with `objective=price`
+    currency: Option<String>,
+    intervals: Vec<PlannedIntervalResponse>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+}
+
+impl ChargePlanResponse {
+    fn new(
+        country_code: String,
+        objective: PlanObjective,
+        plan: &EnergyPlan,
+        currency: Option<String>,
+        forecast: ForecastInfo,
+    ) -> Self {
+        Self {
+            country_code,
+            objective: match objective {
+                PlanObjective::Surplus => "surplus",
+                PlanObjective::Price => "price",
+            },
+            energy_kwh: plan.energy_kwh,
+            mean_surplus_mw: plan.mean_surplus,
+            mean_price: plan.mean_price,
+            currency,
+            intervals: plan.intervals.iter().map(Into::into).collect(),
+            forecast,
+        }
+    }
+}
+
+/// POST /api/v1/plan/charge/:country?objective=surplus|price
+/// Charging intervals delivering the energy of the JSON body before its `ready_by`
+async fn plan_charge(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<PlanQuery>,
+    request: Result<Json<ChargeRequest>, JsonRejection>,
+) -> Result<Json<ApiResponse<ChargePlanResponse>>, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let Json(request) =
+        request.map_err(|rejection| ApiError::bad_request(rejection.body_text()))?;
+    if !(request.energy_kwh.is_finite() && request.energy_kwh > 0.0) {
+        return Err(ApiError::bad_request("`energy_kwh` must be positive"));
+    }
+    if !(request.max_power_kw.is_finite() && request.max_power_kw > 0.0) {
+        return Err(ApiError::bad_request("`max_power_kw` must be positive"));
+    }
+    let now = state.now();
+    let ready_by = parse_query_time("ready_by", &request.ready_by)?;
+    let from = match &request.plugged_in_from {
+        Some(from) => parse_query_time("plugged_in_from", from)?.max(now),
+        None => now,
+    };
+    if ready_by <= from {
+        return Err(ApiError::bad_request(
+            "`ready_by` must be after now and after `plugged_in_from`",
+        ));
+    }
+    if ready_by - now > Duration::hours(state.config.max_query_hours as i64) {
+        return Err(ApiError::bad_request(format!(
+            "`ready_by` must be within {} hours",
+            state.config.max_query_hours
+        )));
+    }
+    let objective = query.objective.unwrap_or_default();
+
+    // From the start of the hour, so the interval plugging in falls into is included
+    let window = TimeWindow::between(
+        from.duration_trunc(Duration::hours(1)).unwrap_or(from),
+        ready_by,
+    );
+    let series = fetch_window_series(
+        &state,
+        zone.code,
+        &window,
+        requested_freshness(query.freshness, query.use_intraday),
+    )
+    .await?;
+    let mut slots: Vec<EnergySlot> = EnergySlot::of_series(&series.points)
+        .into_iter()
+        .filter(|slot| slot.end > from)
+        .map(|slot| EnergySlot {
+            start: slot.start.max(from),
+            ..slot
+        })
+        .collect();
+
+    let mut currency = None;
+    if objective == PlanObjective::Price {
+        require_supported(&state, zone.code, &[DocumentKind::DayAheadPrices])?;
+        let prices = state
+            .client()?
+            .fetch_day_ahead_prices(zone.code, window.start, window.end)
+            .await
+            .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))?;
+        // The price in force when the slot starts
+        for slot in &mut slots {
+            slot.price = prices
+                .points
+                .iter()
+                .find(|price| price.timestamp <= slot.start && slot.start < price.end())
+                .map(|price| price.amount);
+        }
+        currency = Some(prices.currency);
+    }
+
+    let plan = plan_energy(
+        &slots,
+        request.energy_kwh,
+        request.max_power_kw,
+        ready_by,
+        objective,
+    )
+    .map_err(|shortfall| ApiError {
+        code: Some(INFEASIBLE),
+        ..ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Cannot charge by {}: {}", ready_by.to_rfc3339(), shortfall),
+        )
+    })?;
+
+    Ok(Json(ApiResponse::success(ChargePlanResponse::new(
+        country_code.to_ascii_uppercase(),
+        objective,
+        &plan,
+        currency,
+        (&series).into(),
+    ))))
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [
+        ApiRoute::post("/api/v1/plan/charge/{country}", plan_charge).usage(
+            "?objective=surplus|price, JSON body: energy_kwh, max_power_kw, ready_by, plugged_in_from",
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, test_state};
+    use axum::body::Body;
+    use axum::http::{Request, header};
+    use chrono::{TimeZone, Utc};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn post(uri: &str, body: &Value) -> Request<Body> {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_charge_plan_endpoint() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 30, 0).unwrap();
+        let app = router(
+            test_state(Arc::new(MockTransport::forecasts())).with_clock(Arc::new(FixedClock(now))),
+        );
+        let plan = |uri: &'static str, body: Value| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(post(uri, &body)).await.unwrap();
+                let status = response.status();
+                let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+                (status, body)
+            }
+        };
+
+        // The mock surplus rises by 1 GW an hour over the day
+        let (status, body) = plan(
+            "/api/v1/plan/charge/de",
+            json!({
+                "energy_kwh": 16.5,
+                "max_power_kw": 11.0,
+                "ready_by": "2024-06-01T08:00:00Z",
+                "plugged_in_from": "2024-06-01T00:00:00Z"
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let data = &body["data"];
+        assert_eq!(data["objective"], "surplus");
+        assert_eq!(data["energy_kwh"], 16.5);
+        let intervals: Vec<_> = data["intervals"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|interval| (interval["start"].clone(), interval["end"].clone()))
+            .collect();
+        // One and a half hours at 11 kW: the best hour, and half of the one before
+        assert_eq!(
+            intervals,
+            [
+                (
+                    json!("2024-06-01T06:00:00+00:00"),
+                    json!("2024-06-01T06:30:00+00:00")
+                ),
+                (
+                    json!("2024-06-01T07:00:00+00:00"),
+                    json!("2024-06-01T08:00:00+00:00")
+                )
+            ]
+        );
+
+        // Prices are flat, so the earliest hours win
+        let (status, body) = plan(
+            "/api/v1/plan/charge/DE?objective=price",
+            json!({"energy_kwh": 11.0, "max_power_kw": 11.0, "ready_by": "2024-06-01T08:00:00Z"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let data = &body["data"];
+        assert_eq!(data["currency"], "EUR");
+        assert_eq!(data["mean_price"], 100.0);
+        // Not before now
+        assert_eq!(data["intervals"][0]["start"], "2024-06-01T00:30:00+00:00");
+
+        let (status, body) = plan(
+            "/api/v1/plan/charge/DE",
+            json!({"energy_kwh": 100.0, "max_power_kw": 11.0, "ready_by": "2024-06-01T02:30:00Z"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], INFEASIBLE);
+        assert!(
+            body["error"].as_str().unwrap().contains("78.00 kWh short"),
+            "{}",
+            body
+        );
+
+        for (body, message) in [
+            (
+                json!({"energy_kwh": 10.0, "max_power_kw": 11.0, "ready_by": "2024-06-01T00:00:00Z"}),
+                "ready_by",
+            ),
+            (
+                json!({"energy_kwh": -1.0, "max_power_kw": 11.0, "ready_by": "2024-06-01T08:00:00Z"}),
+                "energy_kwh",
+            ),
+            (
+                json!({"energy_kwh": 10.0, "max_power_kw": 11.0, "ready_by": "tomorrow"}),
+                "RFC3339",
+            ),
+            (json!({"energy_kwh": 10.0}), "max_power_kw"),
+        ] {
+            let (status, body) = plan("/api/v1/plan/charge/DE", body).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+            assert!(
+                body["error"].as_str().unwrap().contains(message),
+                "{}",
+                body
+            );
+        }
+    }
+}
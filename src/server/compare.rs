@@ -0,0 +1,562 @@
+//! Surplus of several countries over the same window, absolute and normalized by
+//! population and load so that large and small countries can be ranked together.
+//! Day-ahead prices are compared in their common currency, or in euros through the
+//! configured exchange rates; prices in different currencies are never mixed.
+
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use super::dto::{ApiResponse, conditional_json};
+use super::error::{ApiError, ValidQuery};
+use super::query::{
+    fetch_window_series, query_window, requested_day, requested_freshness, require_supported,
+    unknown_country,
+};
+use super::routes::ApiRoute;
+use crate::currency::{BASE_CURRENCY, ExchangeRate};
+use crate::entsoe::analysis::{Freshness, NormalizedSurplus, normalized_surplus};
+use crate::entsoe::areas::{BiddingZone, DocumentKind, country_stats, get_primary_zone};
+use crate::entsoe::prices::DayAheadPrices;
+use crate::entsoe::window::{CalendarDay, TimeWindow};
+
+/// Most countries a single comparison may fetch
+const MAX_COMPARE_COUNTRIES: usize = 10;
+
+#[derive(Deserialize)]
+pub(super) struct CompareQuery {
+    /// Comma separated country codes, e.g. `DE,DK,FR`
+    countries: Option<String>,
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Calendar day to cover instead of the next hours, `today` or `tomorrow` in the local
+    /// time of the first country
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+    /// Include mean day-ahead prices
+    #[serde(default)]
+    prices: bool,
+}
+
+/// Exchange rate a price was converted with
+#[derive(Debug, Serialize)]
+struct RateUsed {
+    currency: String,
+    eur_per_unit: f64,
+    /// Day the rate was published, `YYYY-MM-DD`
+    date: String,
+}
+
+impl From<ExchangeRate> for RateUsed {
+    fn from(rate: ExchangeRate) -> Self {
+        Self {
+            currency: rate.currency,
+            eur_per_unit: rate.eur_per_unit,
+            date: rate.date.to_string(),
+        }
+    }
+}
+
+/// Mean day-ahead price of a country over the window
+#[derive(Debug, Serialize)]
+struct CountryPrice {
+    /// Per MWh in `currency`, `null` without published prices
+    mean: Option<f64>,
+    currency: Option<String>,
+    /// Set when the zone quotes in another currency than `currency`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exchange_rate: Option<RateUsed>,
+}
+
+/// One country of a comparison; values a country lacks data for are `null`
+#[derive(Debug, Serialize)]
+struct CountryComparison {
+    country_code: String,
+    population: Option<u64>,
+    /// Mean annual load of the reference figures
+    reference_load_mw: Option<f64>,
+    mean_surplus_mw: Option<f64>,
+    max_surplus_mw: Option<f64>,
+    /// Mean forecast load over the window
+    mean_load_mw: Option<f64>,
+    surplus_kw_per_capita: Option<f64>,
+    /// Mean surplus in percent of `mean_load_mw`
+    surplus_pct_of_load: Option<f64>,
+    /// Only with `prices=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price: Option<CountryPrice>,
+}
+
+impl CountryComparison {
+    fn new(country_code: &str, surplus: Option<NormalizedSurplus>) -> Self {
+        let stats = country_stats(country_code);
+        Self {
+            country_code: country_code.to_string(),
+            population: stats.map(|stats| stats.population),
+            reference_load_mw: stats.map(|stats| stats.mean_load_mw),
+            mean_surplus_mw: surplus.as_ref().map(|s| s.mean_surplus),
+            max_surplus_mw: surplus.as_ref().map(|s| s.max_surplus),
+            mean_load_mw: surplus.as_ref().map(|s| s.mean_load),
+            surplus_kw_per_capita: surplus.as_ref().and_then(|s| s.per_capita_kw),
+            surplus_pct_of_load: surplus.as_ref().and_then(|s| s.share_of_load),
+            price: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RankingEntry {
+    country_code: String,
+    value: Option<f64>,
+}
+
+/// Countries by each metric, largest first and countries without a value last
+#[derive(Debug, Serialize)]
+struct Rankings {
+    mean_surplus_mw: Vec<RankingEntry>,
+    max_surplus_mw: Vec<RankingEntry>,
+    surplus_kw_per_capita: Vec<RankingEntry>,
+    surplus_pct_of_load: Vec<RankingEntry>,
+    /// Only with `prices=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mean_price: Option<Vec<RankingEntry>>,
+}
+
+#[derive(Serialize)]
+struct CompareResponse {
+    period_start: String,
+    period_end: String,
+    /// Currency of all prices, only with `prices=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price_currency: Option<String>,
+    /// In the requested order
+    countries: Vec<CountryComparison>,
+    rankings: Rankings,
+}
+
+/// Primary zones of the distinct countries of the `countries` parameter
+fn requested_countries(countries: Option<&str>) -> Result<Vec<&'static BiddingZone>, ApiError> {
+    let mut requested: Vec<&'static BiddingZone> = Vec::new();
+    for country in countries
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|country| !country.is_empty())
+    {
+        let zone = get_primary_zone(&country.to_ascii_uppercase())
+            .ok_or_else(|| ApiError::bad_request(unknown_country(country)))?;
+        if !requested.contains(&zone) {
+            requested.push(zone);
+        }
+    }
+
+    if requested.is_empty() {
+        return Err(ApiError::bad_request(
+            "`countries` is required, e.g. countries=DE,DK",
+        ));
+    }
+    if requested.len() > MAX_COMPARE_COUNTRIES {
+        return Err(ApiError::bad_request(format!(
+            "At most {} countries can be compared",
+            MAX_COMPARE_COUNTRIES
+        )));
+    }
+    Ok(requested)
+}
+
+fn ranking(
+    countries: &[CountryComparison],
+    value: fn(&CountryComparison) -> Option<f64>,
+) -> Vec<RankingEntry> {
+    let mut entries: Vec<RankingEntry> = countries
+        .iter()
+        .map(|country| RankingEntry {
+            country_code: country.country_code.clone(),
+            value: value(country),
+        })
+        .collect();
+    // Ties and countries without a value by country code, so the ranking does not
+    // depend on the requested order
+    entries.sort_by(|a, b| {
+        match (a.value, b.value) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+        .then_with(|| a.country_code.cmp(&b.country_code))
+    });
+    entries
+}
+
+impl Rankings {
+    fn new(countries: &[CountryComparison]) -> Self {
+        Self {
+            mean_surplus_mw: ranking(countries, |c| c.mean_surplus_mw),
+            max_surplus_mw: ranking(countries, |c| c.max_surplus_mw),
+            surplus_kw_per_capita: ranking(countries, |c| c.surplus_kw_per_capita),
+            surplus_pct_of_load: ranking(countries, |c| c.surplus_pct_of_load),
+            mean_price: countries
+                .iter()
+                .any(|c| c.price.is_some())
+                .then(|| ranking(countries, |c| c.price.as_ref().and_then(|p| p.mean))),
+        }
+    }
+}
+
+/// Currencies of the zones with prices, in the requested order
+fn currencies(prices: &[DayAheadPrices]) -> Vec<&str> {
+    let mut currencies: Vec<&str> = Vec::new();
+    for prices in prices.iter().filter(|prices| !prices.points.is_empty()) {
+        if !currencies.contains(&prices.currency.as_str()) {
+            currencies.push(&prices.currency);
+        }
+    }
+    currencies
+}
+
+/// Mean prices of the countries in a common currency: their own if they share one, else
+/// euros through the configured exchange rates. Without rates the comparison is refused.
+fn common_currency_prices(
+    state: &AppState,
+    prices: &[DayAheadPrices],
+) -> Result<(String, Vec<CountryPrice>), ApiError> {
+    let currencies = currencies(prices);
+    if currencies.len() <= 1 {
+        let currency = currencies.first().unwrap_or(&BASE_CURRENCY).to_string();
+        let prices = prices
+            .iter()
+            .map(|prices| CountryPrice {
+                mean: prices.mean(),
+                currency: Some(currency.clone()),
+                exchange_rate: None,
+            })
+            .collect();
+        return Ok((currency, prices));
+    }
+
+    let Some(converter) = &state.currency_converter else {
+        return Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "Prices are quoted in {}; set EDUCK_EXCHANGE_RATES to compare them in {}",
+                currencies.join(", "),
+                BASE_CURRENCY
+            ),
+        ));
+    };
+
+    let mut converted = Vec::new();
+    for prices in prices {
+        let Some(mean) = prices.mean() else {
+            converted.push(CountryPrice {
+                mean: None,
+                currency: Some(BASE_CURRENCY.to_string()),
+                exchange_rate: None,
+            });
+            continue;
+        };
+        let rate = converter.rate_to_eur(&prices.currency).ok_or_else(|| {
+            ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("No exchange rate of {} is configured", prices.currency),
+            )
+        })?;
+        converted.push(CountryPrice {
+            mean: Some(mean * rate.eur_per_unit),
+            currency: Some(BASE_CURRENCY.to_string()),
+            exchange_rate: (prices.currency != BASE_CURRENCY).then(|| rate.into()),
+        });
+    }
+    Ok((BASE_CURRENCY.to_string(), converted))
+}
+
+async fn fetch_prices(
+    state: &AppState,
+    zone: &BiddingZone,
+    window: &TimeWindow,
+) -> Result<DayAheadPrices, ApiError> {
+    require_supported(state, zone.code, &[DocumentKind::DayAheadPrices])?;
+    state
+        .client()?
+        .fetch_day_ahead_prices(zone.code, window.start, window.end)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))
+}
+
+/// GET /api/v1/compare?countries=DE,DK&hours=N or ?countries=..&start=..&end=..
+/// Mean and peak surplus of each country with per-capita and load-relative values, and
+/// with `prices=true` the mean day-ahead price
+async fn compare(
+    State(state): State<AppState>,
+    ValidQuery(query): ValidQuery<CompareQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let countries = requested_countries(query.countries.as_deref())?;
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, None, countries[0])?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let freshness = requested_freshness(query.freshness, query.use_intraday);
+
+    let mut comparisons = Vec::new();
+    let mut prices = Vec::new();
+    for zone in countries {
+        let series = fetch_window_series(&state, zone.code, &window, freshness).await?;
+        let population = country_stats(zone.country_code).map(|stats| stats.population);
+        comparisons.push(CountryComparison::new(
+            zone.country_code,
+            normalized_surplus(&series.points, population),
+        ));
+        if query.prices {
+            prices.push(fetch_prices(&state, zone, &window).await?);
+        }
+    }
+
+    let mut price_currency = None;
+    if query.prices {
+        let (currency, prices) = common_currency_prices(&state, &prices)?;
+        for (comparison, price) in comparisons.iter_mut().zip(prices) {
+            comparison.price = Some(price);
+        }
+        price_currency = Some(currency);
+    }
+
+    let response = CompareResponse {
+        period_start: window.start.to_rfc3339(),
+        period_end: window.end.to_rfc3339(),
+        price_currency,
+        rankings: Rankings::new(&comparisons),
+        countries: comparisons,
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [ApiRoute::get("/api/v1/compare", compare).usage("?countries=DE,DK,FR&hours=N")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::StaticRates;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::test_state;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn comparison(
+        country_code: &str,
+        mean_surplus: f64,
+        per_capita: Option<f64>,
+    ) -> CountryComparison {
+        CountryComparison {
+            country_code: country_code.to_string(),
+            population: None,
+            reference_load_mw: None,
+            mean_surplus_mw: Some(mean_surplus),
+            max_surplus_mw: Some(mean_surplus),
+            mean_load_mw: None,
+            surplus_kw_per_capita: per_capita,
+            surplus_pct_of_load: None,
+            price: None,
+        }
+    }
+
+    fn codes(entries: &[RankingEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.country_code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_requested_countries() {
+        let countries: Vec<_> = requested_countries(Some("de, dk,DE,"))
+            .unwrap()
+            .iter()
+            .map(|zone| zone.country_code)
+            .collect();
+        assert_eq!(countries, ["DE", "DK"]);
+        assert!(requested_countries(None).is_err());
+        assert!(requested_countries(Some("DE,XX")).is_err());
+        let many = [
+            "AT", "BE", "BG", "CH", "CZ", "DE", "DK", "EE", "ES", "FI", "FR",
+        ];
+        assert!(requested_countries(Some(&many.join(","))).is_err());
+    }
+
+    #[test]
+    fn test_rankings_put_missing_values_last() {
+        let countries = [
+            comparison("DE", 20_000.0, Some(0.24)),
+            comparison("UA", 5_000.0, None),
+            comparison("DK", 2_000.0, Some(0.34)),
+        ];
+        let rankings = Rankings::new(&countries);
+
+        assert_eq!(codes(&rankings.mean_surplus_mw), ["DE", "UA", "DK"]);
+        assert_eq!(codes(&rankings.surplus_kw_per_capita), ["DK", "DE", "UA"]);
+        assert_eq!(rankings.surplus_kw_per_capita[2].value, None);
+        // Without values at all, by country code
+        assert_eq!(codes(&rankings.surplus_pct_of_load), ["DE", "DK", "UA"]);
+    }
+
+    #[test]
+    fn test_rankings_do_not_depend_on_the_requested_order() {
+        // DE and FR tie on the mean surplus, AT and UA have no per-capita value
+        let countries = [
+            ("DE", 2_000.0, Some(0.1)),
+            ("UA", 500.0, None),
+            ("FR", 2_000.0, Some(0.1)),
+            ("AT", 4_000.0, None),
+        ];
+        let rankings = |order: &[usize]| {
+            let countries: Vec<_> = order
+                .iter()
+                .map(|&i| {
+                    let (code, mean, per_capita) = countries[i];
+                    comparison(code, mean, per_capita)
+                })
+                .collect();
+            Rankings::new(&countries)
+        };
+        let expected = rankings(&[0, 1, 2, 3]);
+        assert_eq!(codes(&expected.mean_surplus_mw), ["AT", "DE", "FR", "UA"]);
+        assert_eq!(
+            codes(&expected.surplus_kw_per_capita),
+            ["DE", "FR", "AT", "UA"]
+        );
+
+        // Every order of the four countries, by Heap's algorithm
+        let mut order = [0, 1, 2, 3];
+        let mut counters = [0; 4];
+        let mut i = 0;
+        while i < order.len() {
+            if counters[i] < i {
+                order.swap(if i % 2 == 0 { 0 } else { counters[i] }, i);
+                let rankings = rankings(&order);
+                assert_eq!(
+                    codes(&rankings.mean_surplus_mw),
+                    codes(&expected.mean_surplus_mw)
+                );
+                assert_eq!(
+                    codes(&rankings.surplus_kw_per_capita),
+                    codes(&expected.surplus_kw_per_capita)
+                );
+                counters[i] += 1;
+                i = 0;
+            } else {
+                counters[i] = 0;
+                i += 1;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compare_endpoint() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/v1/compare?countries=DE,DK,UA&start=2024-06-01T00:00:00Z")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let data = &body["data"];
+
+        // Every country gets the same mocked series: 1.5 GW mean surplus over 50 GW load
+        let countries = data["countries"].as_array().unwrap();
+        assert_eq!(countries.len(), 3);
+        assert_eq!(countries[0]["country_code"], "DE");
+        assert_eq!(countries[0]["mean_surplus_mw"], 1_500.0);
+        assert_eq!(countries[0]["surplus_pct_of_load"], 3.0);
+        assert_eq!(countries[2]["country_code"], "UA");
+        assert_eq!(countries[2]["mean_surplus_mw"], 1_500.0);
+        assert!(countries[2]["population"].is_null());
+        assert!(countries[2]["surplus_kw_per_capita"].is_null());
+        assert!(countries[0].get("price").is_none());
+
+        let per_capita: Vec<_> = data["rankings"]["surplus_kw_per_capita"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["country_code"].as_str().unwrap())
+            .collect();
+        assert_eq!(per_capita, ["DK", "DE", "UA"]);
+
+        let response = app
+            .oneshot(Request::get("/api/v1/compare").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_compare_prices_across_currencies() {
+        let uri = "/api/v1/compare?countries=DE,PL&start=2024-06-01T00:00:00Z&prices=true";
+        let state = test_state(Arc::new(MockTransport::forecasts()));
+
+        // Euros and zloty are not compared without exchange rates
+        let response = router(state.clone())
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("EUR, PLN"));
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let rates = StaticRates::new(date, [("PLN".to_string(), 0.25)]);
+        let response = router(state.with_currency_converter(Arc::new(rates)))
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let data = &body["data"];
+
+        assert_eq!(data["price_currency"], "EUR");
+        let countries = data["countries"].as_array().unwrap();
+        assert_eq!(countries[0]["price"]["mean"], 100.0);
+        assert!(countries[0]["price"].get("exchange_rate").is_none());
+        // 400 PLN at 0.25 EUR each
+        let pl = &countries[1]["price"];
+        assert_eq!(pl["mean"], 100.0);
+        assert_eq!(pl["currency"], "EUR");
+        assert_eq!(pl["exchange_rate"]["currency"], "PLN");
+        assert_eq!(pl["exchange_rate"]["eur_per_unit"], 0.25);
+        assert_eq!(pl["exchange_rate"]["date"], "2024-06-01");
+        assert_eq!(data["rankings"]["mean_price"].as_array().unwrap().len(), 2);
+    }
+}
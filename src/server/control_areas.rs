@@ -0,0 +1,406 @@
+//! Load forecasts of a country per TSO control area and their sum, as JSON under
+//! `/api/v1/load/{country}/by-tso` and as the `view=by-tso` plot
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::AppState;
+use super::dto::ApiResponse;
+use super::error::{ApiError, ValidQuery};
+use super::query::{query_window, requested_day, requested_zone};
+use super::routes::ApiRoute;
+use crate::entsoe::areas::{BiddingZone, get_control_areas};
+use crate::entsoe::control_areas::LoadBreakdown;
+use crate::entsoe::localtime::LocalZone;
+use crate::entsoe::window::{CalendarDay, TimeWindow};
+use crate::entsoe::{EntsoeError, PowerUnit};
+use crate::plotting::plotly::PlotFigure;
+
+/// Line colors of the area traces, one per German TSO and a spare
+const AREA_COLORS: [&str; 5] = [
+    "rgb(30, 144, 255)",
+    "rgb(34, 139, 34)",
+    "rgb(148, 103, 189)",
+    "rgb(214, 39, 40)",
+    "rgb(140, 86, 75)",
+];
+
+#[derive(Deserialize)]
+pub(super) struct ByTsoQuery {
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// Comma separated control areas to include and add up, by alias, TSO or code
+    /// (default: all of the country)
+    areas: Option<String>,
+    /// Unit of the power values: `MW` (default), `GW` or `kW`
+    #[serde(default)]
+    unit: PowerUnit,
+}
+
+#[derive(Serialize)]
+struct AreaLoadResponse {
+    code: &'static str,
+    alias: Option<&'static str>,
+    tso: Option<&'static str>,
+    /// One per timestamp of the response, `null` where the area has no point
+    values: Vec<Option<f64>>,
+    /// Why the forecast of the area could not be fetched; it is then left out of `total`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LoadByTsoResponse {
+    country_code: String,
+    period_start: String,
+    period_end: String,
+    unit: PowerUnit,
+    timestamps: Vec<String>,
+    areas: Vec<AreaLoadResponse>,
+    /// Sum of the fetched areas, `null` where one of them has no point
+    total: Vec<Option<f64>>,
+    /// Whether every area was fetched
+    complete: bool,
+}
+
+/// Control areas of the country of `zone` the `areas` parameter selects
+fn requested_areas(
+    zone: &BiddingZone,
+    areas: Option<&str>,
+) -> Result<Vec<&'static BiddingZone>, ApiError> {
+    let known = get_control_areas(zone.country_code);
+    if known.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("{} has no separate TSO control areas", zone.country_code),
+        ));
+    }
+    let Some(areas) = areas else {
+        return Ok(known);
+    };
+
+    let mut requested: Vec<&'static BiddingZone> = Vec::new();
+    for name in areas
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let area = known
+            .iter()
+            .find(|area| {
+                [Some(area.code), area.alias, area.tso]
+                    .into_iter()
+                    .flatten()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(name))
+            })
+            .ok_or_else(|| {
+                let names: Vec<&str> = known.iter().filter_map(|area| area.alias).collect();
+                ApiError::bad_request(format!(
+                    "Unknown control area `{}`, expected one of {}",
+                    name,
+                    names.join(", ")
+                ))
+            })?;
+        if !requested.contains(area) {
+            requested.push(area);
+        }
+    }
+    if requested.is_empty() {
+        return Err(ApiError::bad_request("`areas` names no control area"));
+    }
+    Ok(requested)
+}
+
+/// Load forecasts of `areas` over `window`, fetched concurrently
+pub(super) async fn fetch_load_breakdown(
+    state: &AppState,
+    areas: &[&'static BiddingZone],
+    window: &TimeWindow,
+) -> Result<LoadBreakdown, ApiError> {
+    let loads = state
+        .client()?
+        .fetch_area_loads(areas, window.start, window.end)
+        .await;
+    for load in &loads {
+        if let Err(e) = &load.points {
+            eprintln!("ENTSO-E API error for {}: {}", load.zone.code, e);
+        }
+    }
+    // The client pauses as a whole once rate-limited, so no area is left to show
+    let rate_limited = loads.iter().find_map(|load| match &load.points {
+        Err(EntsoeError::RateLimited { retry_after }) => Some(*retry_after),
+        _ => None,
+    });
+    if let Some(retry_after) = rate_limited
+        && loads.iter().all(|load| load.points.is_err())
+    {
+        return Err(ApiError::upstream(
+            EntsoeError::RateLimited { retry_after },
+            StatusCode::BAD_GATEWAY,
+        ));
+    }
+    Ok(LoadBreakdown::new(loads))
+}
+
+/// Control areas of the country of `zone` for the `view=by-tso` plot
+pub(super) async fn plot_breakdown(
+    state: &AppState,
+    zone: &BiddingZone,
+    window: &TimeWindow,
+) -> Result<LoadBreakdown, ApiError> {
+    let areas = requested_areas(zone, None)?;
+    fetch_load_breakdown(state, &areas, window).await
+}
+
+/// Plotly traces and layout with one line per control area and their sum
+pub(super) fn breakdown_plot_data(
+    zone: &BiddingZone,
+    breakdown: &LoadBreakdown,
+    local: Option<&LocalZone>,
+    unit: PowerUnit,
+) -> PlotFigure {
+    let timestamps: Vec<String> = breakdown
+        .timestamps
+        .iter()
+        .map(|timestamp| match local {
+            Some(local) => local.to_local(*timestamp).format("%Y-%m-%d %H:%M"),
+            None => timestamp.format("%Y-%m-%d %H:%M"),
+        })
+        .map(|timestamp| timestamp.to_string())
+        .collect();
+    let values = |values: &[Option<f64>]| -> Vec<Option<f64>> {
+        values.iter().map(|v| v.map(|v| unit.from_mw(v))).collect()
+    };
+
+    let mut traces: Vec<serde_json::Value> = breakdown
+        .areas
+        .iter()
+        .filter(|area| area.error.is_none())
+        .enumerate()
+        .map(|(i, area)| {
+            json!({
+                "x": timestamps,
+                "y": values(&area.values),
+                "name": area.zone.tso.unwrap_or(area.zone.code),
+                "type": "scatter",
+                "mode": "lines",
+                "line": {
+                    "color": AREA_COLORS[i % AREA_COLORS.len()],
+                    "width": 2
+                }
+            })
+        })
+        .collect();
+    traces.push(json!({
+        "x": timestamps,
+        "y": values(&breakdown.total),
+        "name": "Total",
+        "type": "scatter",
+        "mode": "lines",
+        "line": {
+            "color": "rgb(0, 0, 0)",
+            "width": 3,
+            "dash": "dot"
+        }
+    }));
+
+    let failed: Vec<&str> = breakdown
+        .areas
+        .iter()
+        .filter(|area| area.error.is_some())
+        .map(|area| area.zone.tso.unwrap_or(area.zone.code))
+        .collect();
+    let title = match failed.is_empty() {
+        true => format!("Load Forecast per Control Area - {}", zone.name),
+        false => format!(
+            "Load Forecast per Control Area - {} (missing {})",
+            zone.name,
+            failed.join(", ")
+        ),
+    };
+    let layout = json!({
+        "title": {
+            "text": title,
+            "font": {
+                "size": 20
+            }
+        },
+        "xaxis": {
+            "title": match local {
+                Some(local) => format!("Time ({})", local.name),
+                None => "Time (UTC)".to_string(),
+            },
+            "tickangle": -45
+        },
+        "yaxis": {
+            "title": format!("Power ({})", unit)
+        },
+        "hovermode": "x unified",
+        "plot_bgcolor": "rgb(250, 250, 250)",
+        "paper_bgcolor": "white",
+        "showlegend": true
+    });
+
+    PlotFigure {
+        data: serde_json::to_string(&traces).unwrap(),
+        layout: serde_json::to_string(&layout).unwrap(),
+        resolution: None,
+    }
+}
+
+/// GET /api/v1/load/:country/by-tso?hours=24&areas=DE-50HZ,DE-AMPRION
+/// Day-ahead load forecast of each TSO control area aligned on common timestamps, and
+/// their sum
+async fn get_load_by_tso(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<ByTsoQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let areas = requested_areas(zone, query.areas.as_deref())?;
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, None, zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+
+    let breakdown = fetch_load_breakdown(&state, &areas, &window).await?;
+    if !breakdown.areas.iter().any(|area| area.error.is_none()) {
+        return Err(ApiError::new(
+            StatusCode::BAD_GATEWAY,
+            "None of the control areas could be fetched",
+        ));
+    }
+    if breakdown.timestamps.is_empty() {
+        return Err(ApiError::no_data("No load points in the requested period"));
+    }
+
+    let unit = query.unit;
+    let values = |values: Vec<Option<f64>>| -> Vec<Option<f64>> {
+        values
+            .into_iter()
+            .map(|v| v.map(|v| unit.from_mw(v)))
+            .collect()
+    };
+    let complete = breakdown.is_complete();
+    Ok(Json(ApiResponse::success(LoadByTsoResponse {
+        country_code,
+        period_start: window.start.to_rfc3339(),
+        period_end: window.end.to_rfc3339(),
+        unit,
+        timestamps: breakdown
+            .timestamps
+            .iter()
+            .map(|timestamp| timestamp.to_rfc3339())
+            .collect(),
+        areas: breakdown
+            .areas
+            .into_iter()
+            .map(|area| AreaLoadResponse {
+                code: area.zone.code,
+                alias: area.zone.alias,
+                tso: area.zone.tso,
+                values: values(area.values),
+                error: area.error,
+            })
+            .collect(),
+        total: values(breakdown.total),
+        complete,
+    }))
+    .into_response())
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [
+        ApiRoute::get("/api/v1/load/{country}/by-tso", get_load_by_tso)
+            .usage("?hours=24&areas=DE-50HZ,DE-AMPRION"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::test_state;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn get(uri: &str) -> (StatusCode, String) {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_load_by_tso() {
+        let (status, body) = get(
+            "/api/v1/load/DE/by-tso?start=2024-06-01T00:00:00Z&end=2024-06-01T06:00:00Z&unit=GW",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let data = &body["data"];
+        assert_eq!(data["timestamps"].as_array().unwrap().len(), 6);
+        assert_eq!(data["areas"].as_array().unwrap().len(), 4);
+        assert_eq!(data["areas"][0]["tso"], "50Hertz");
+        assert_eq!(data["areas"][0]["values"][0], 50.0);
+        assert_eq!(data["total"][0], 200.0);
+        assert_eq!(data["complete"], true);
+
+        let (status, body) =
+            get("/api/v1/load/DE/by-tso?start=2024-06-01T00:00:00Z&areas=amprion,DE-50HZ,Amprion")
+                .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let tsos: Vec<&str> = body["data"]["areas"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|area| area["tso"].as_str().unwrap())
+            .collect();
+        assert_eq!(tsos, ["Amprion", "50Hertz"]);
+        assert_eq!(body["data"]["total"][0], 100_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_load_by_tso_refuses_unknown_areas() {
+        let (status, body) = get("/api/v1/load/DE/by-tso?areas=DE-XYZ").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("DE-TRANSNET"), "{}", body);
+
+        let (status, _) = get("/api/v1/load/FR/by-tso").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_plot_by_tso_view() {
+        let (status, body) =
+            get("/api/v1/renewable-surplus/DE/plot?start=2024-06-01T00:00:00Z&view=by-tso").await;
+        assert_eq!(status, StatusCode::OK);
+        for tso in ["50Hertz", "Amprion", "TenneT", "TransnetBW", "Total"] {
+            assert!(body.contains(&format!("\"name\":\"{}\"", tso)), "{}", tso);
+        }
+        assert!(!body.contains("Wind + Solar Generation"));
+    }
+}
@@ -0,0 +1,171 @@
+//! Landing page listing the headline numbers of the prefetched countries, rendered from
+//! cached data only
+
+use askama::Template;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Html;
+
+use super::AppState;
+use super::error::ApiError;
+use super::routes::ApiRoute;
+use super::surplus::{CountrySurplusSummary, cached_summary};
+use crate::entsoe::PowerUnit;
+use crate::entsoe::areas::{self, get_primary_zone};
+
+/// Seconds between reloads of the page
+const REFRESH_SECONDS: u32 = 60;
+/// Shown for values without data
+const NO_VALUE: &str = "—";
+
+struct DashboardRow {
+    country_code: String,
+    name: &'static str,
+    current_surplus: String,
+    penetration: String,
+    today_max: String,
+    plot_url: String,
+}
+
+#[derive(Template)]
+#[template(path = "dashboard.html")]
+struct DashboardTemplate {
+    generated_at: String,
+    refresh_seconds: u32,
+    rows: Vec<DashboardRow>,
+}
+
+fn format_value(value: Option<f64>, suffix: &str) -> String {
+    match value {
+        Some(value) => format!("{:.0}{}", value, suffix),
+        None => NO_VALUE.to_string(),
+    }
+}
+
+/// GET /
+/// Table of the prefetched countries, or without any of the countries with cached data
+async fn get_dashboard(State(state): State<AppState>) -> Result<Html<String>, ApiError> {
+    let prefetched = !state.config.prefetch_countries.is_empty();
+    let countries: Vec<String> = match prefetched {
+        true => state.config.prefetch_countries.clone(),
+        false => areas::list_countries()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    };
+
+    let mut rows = Vec::new();
+    for country_code in countries {
+        let Some(zone) = get_primary_zone(&country_code.to_ascii_uppercase()) else {
+            continue;
+        };
+        let summary = cached_summary(
+            &state,
+            zone.country_code.to_string(),
+            zone,
+            PowerUnit::default(),
+        );
+        if summary.is_none() && !prefetched {
+            continue;
+        }
+        let summary = summary.as_ref();
+        let value = |value: fn(&CountrySurplusSummary) -> Option<f64>| summary.and_then(value);
+        rows.push(DashboardRow {
+            country_code: zone.country_code.to_string(),
+            name: zone.name,
+            current_surplus: format_value(value(|summary| summary.current_surplus_mw), ""),
+            penetration: format_value(value(|summary| summary.renewable_penetration), " %"),
+            today_max: format_value(value(|summary| summary.today_max_surplus_mw), ""),
+            plot_url: format!("/api/v1/renewable-surplus/{}/plot", zone.country_code),
+        });
+    }
+
+    let template = DashboardTemplate {
+        generated_at: state.now().format("%Y-%m-%d %H:%M UTC").to_string(),
+        refresh_seconds: REFRESH_SECONDS,
+        rows,
+    };
+    let html = template.render().map_err(|e| {
+        eprintln!("Template rendering error: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Html(html))
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [ApiRoute::get("/", get_dashboard)]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::FixedClock;
+    use crate::config::ServerConfig;
+    use crate::entsoe::EntsoeClient;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::tests::{body_bytes, get_request};
+    use crate::server::{AppState, router};
+    use axum::http::StatusCode;
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn page(app: &axum::Router) -> String {
+        let response = app.clone().oneshot(get_request("/")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        String::from_utf8(body_bytes(response).await.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_lists_the_prefetched_countries() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 5, 30, 0).unwrap();
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone())
+            .with_cache(std::time::Duration::from_secs(300));
+        let config = ServerConfig {
+            prefetch_countries: vec!["DE".to_string(), "FR".to_string()],
+            ..ServerConfig::default()
+        };
+        let app = router(
+            AppState::new(Some(Arc::new(client)), config).with_clock(Arc::new(FixedClock(now))),
+        );
+
+        let html = page(&app).await;
+        assert!(transport.requests().is_empty());
+        assert!(html.contains(r#"<meta http-equiv="refresh" content="60">"#));
+        assert!(html.contains("<td>Germany (DE)</td>"));
+        assert!(html.contains("<td>France (FR)</td>"));
+        assert!(!html.contains("-4500"));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let fetched = transport.requests().len();
+
+        // The mock surplus rises from -10 GW at midnight by 1 GW an hour, and the local
+        // day of Germany ends at 22:00 UTC
+        let html = page(&app).await;
+        assert_eq!(transport.requests().len(), fetched);
+        assert!(html.contains("<td>-4500</td>"), "{}", html);
+        assert!(html.contains("<td>11000</td>"), "{}", html);
+        assert!(html.contains(r#"<a href="/api/v1/renewable-surplus/DE/plot">Plot</a>"#));
+        // France has no cached data
+        let france = html.split("<td>France (FR)</td>").nth(1).unwrap();
+        assert!(france.trim_start().starts_with("<td>—</td>"), "{}", france);
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_without_prefetched_countries() {
+        let app = router(AppState::new(
+            Some(Arc::new(EntsoeClient::with_transport(
+                "test-token",
+                Arc::new(MockTransport::forecasts()),
+            ))),
+            ServerConfig::default(),
+        ));
+
+        assert!(page(&app).await.contains("EDUCK_PREFETCH_COUNTRIES"));
+    }
+}
@@ -0,0 +1,830 @@
+//! Payloads shared by the answers of several endpoints, and the envelope and caching
+//! headers every JSON answer goes out with
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::error::ApiError;
+use super::schema::SCHEMA_VERSION;
+use crate::config::ServerConfig;
+use crate::entsoe::analysis::{
+    Baseload, Coverage, DocumentMeta, Interval, LoadAggregation, PartialSurplus, RenewableSurplus,
+    SourceSegment, SurplusModel, SurplusSeries,
+};
+use crate::entsoe::cache::CacheStatus;
+use crate::entsoe::localtime::LocalZone;
+use crate::entsoe::{ForecastSource, fnv1a};
+
+#[derive(Serialize)]
+pub(super) struct ApiResponse<T> {
+    pub(super) success: bool,
+    /// Version of the shape of the response, see [`schema`](super::schema)
+    pub(super) schema_version: u32,
+    pub(super) data: Option<T>,
+    pub(super) error: Option<String>,
+    /// Machine-readable kind of `error`, e.g. [`NO_DATA`](super::error::NO_DATA)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) code: Option<&'static str>,
+    /// Id of the request an error answers, as in the logs, see [`crate::request_id`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) request_id: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    pub(super) fn success(data: T) -> Self {
+        Self {
+            success: true,
+            schema_version: SCHEMA_VERSION,
+            data: Some(data),
+            error: None,
+            code: None,
+            request_id: None,
+        }
+    }
+
+    pub(super) fn error(message: String) -> Self {
+        Self {
+            success: false,
+            schema_version: SCHEMA_VERSION,
+            data: None,
+            error: Some(message),
+            code: None,
+            request_id: crate::request_id::current(),
+        }
+    }
+}
+
+/// Strong ETag derived from the serialized response body, the same across builds and
+/// instances
+fn payload_etag(body: &[u8]) -> String {
+    format!("\"{:016x}\"", fnv1a(body.iter().copied()))
+}
+
+/// Check whether any `If-None-Match` value matches the given ETag
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Serialize a response with ETag and Cache-Control headers, answering 304 when the
+/// client already holds the same payload. Error envelopes are passed through uncached.
+/// With API tokens configured the payload is only cacheable by the client that
+/// authenticated, so shared caches never answer another caller with it.
+pub(super) fn conditional_json<T: Serialize>(
+    headers: &HeaderMap,
+    config: &ServerConfig,
+    response: ApiResponse<T>,
+) -> Response {
+    if !response.success {
+        return Json(response).into_response();
+    }
+
+    let body = match serde_json::to_vec(&response) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("Response serialization error: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let etag = payload_etag(&body);
+    let authenticated = !config.api_tokens.is_empty();
+    let cache_control = format!(
+        "{}, max-age={}",
+        if authenticated { "private" } else { "public" },
+        config.cache_max_age.as_secs()
+    );
+
+    let mut response = if etag_matches(headers, &etag) {
+        (
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+        )
+            .into_response()
+    } else {
+        (
+            [
+                (header::CONTENT_TYPE, "application/json".to_string()),
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, cache_control),
+            ],
+            body,
+        )
+            .into_response()
+    };
+    if authenticated {
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("authorization"));
+    }
+    response
+}
+
+/// `max_surplus_mw` as `maxSurplusMw`
+fn camel_case_key(key: &str) -> String {
+    let mut words = key.split('_');
+    let mut camel = words.next().unwrap_or_default().to_string();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+fn camel_case_keys_of(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (camel_case_key(&key), camel_case_keys_of(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(camel_case_keys_of).collect()),
+        value => value,
+    }
+}
+
+/// Rewrite the keys of enveloped JSON answers to camelCase, for `EDUCK_JSON_CASE=camel`.
+/// Answers in other shapes, e.g. Vega specs and the Grafana datasource, keep the keys
+/// their consumers expect; values such as `day_ahead` are left as they are.
+pub(super) async fn camel_case_keys(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Response body error: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(value)
+            if value
+                .get("success")
+                .is_some_and(serde_json::Value::is_boolean) =>
+        {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(camel_case_keys_of(value).to_string())
+        }
+        _ => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
+
+#[derive(Serialize)]
+pub(super) struct MaxSurplusResponse {
+    pub(super) country_code: String,
+    timestamp: String,
+    timestamp_utc: String,
+    /// `timestamp` in `timezone`, RFC3339 with offset
+    timestamp_local: String,
+    timezone: &'static str,
+    generation_mw: f64,
+    load_mw: f64,
+    surplus_mw: f64,
+    surplus_percentage: f64,
+    /// Total generation forecast (A71), absent where the zone doesn't publish it
+    total_generation_mw: Option<f64>,
+    /// Wind and solar as a percentage of load
+    renewable_penetration: f64,
+    /// Wind and solar as a percentage of total generation
+    renewable_share_of_generation: Option<f64>,
+    /// Solar and wind parts of `generation_mw`, absent where the forecast is not split by
+    /// production type
+    solar_mw: Option<f64>,
+    wind_mw: Option<f64>,
+    /// Percentages of solar and wind in `solar_mw + wind_mw`
+    solar_share: Option<f64>,
+    wind_share: Option<f64>,
+    pub(super) filter_applied: String,
+    generation_source: Option<ForecastSource>,
+    load_source: Option<ForecastSource>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) surplus_model: Option<SurplusModelResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) coverage: Option<CoverageResponse>,
+    /// Instant a relative window was filtered from, RFC3339: now, or the start of the
+    /// forecast when now falls outside it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) reference_time: Option<String>,
+}
+
+impl From<RenewableSurplus> for MaxSurplusResponse {
+    fn from(surplus: RenewableSurplus) -> Self {
+        Self {
+            country_code: String::new(), // Will be set later
+            timestamp: surplus.timestamp.to_rfc3339(),
+            timestamp_utc: surplus
+                .timestamp
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string(),
+            timestamp_local: surplus.timestamp.to_rfc3339(),
+            timezone: LocalZone::UTC.name,
+            generation_mw: surplus.generation,
+            load_mw: surplus.load,
+            surplus_mw: surplus.surplus,
+            surplus_percentage: surplus.surplus_percentage(),
+            total_generation_mw: surplus.total_generation,
+            renewable_penetration: surplus.renewable_penetration(),
+            renewable_share_of_generation: surplus.renewable_share_of_generation(),
+            solar_mw: None,                    // Will be set later
+            wind_mw: None,                     // Will be set later
+            solar_share: None,                 // Will be set later
+            wind_share: None,                  // Will be set later
+            filter_applied: String::new(),     // Will be set later
+            generation_source: None,           // Will be set later
+            load_source: None,                 // Will be set later
+            forecast: ForecastInfo::default(), // Will be set later
+            surplus_model: None,
+            coverage: None,
+            reference_time: None,
+        }
+    }
+}
+
+impl MaxSurplusResponse {
+    /// Express the local timestamp in `local`
+    pub(super) fn in_zone(mut self, local: &LocalZone, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp_local = local.to_local(timestamp).to_rfc3339();
+        self.timezone = local.name;
+        self
+    }
+
+    /// Attach the sources and document metadata of the series the point was taken from
+    pub(super) fn with_series(mut self, series: &SurplusSeries, timestamp: DateTime<Utc>) -> Self {
+        let segment = series.source_at(timestamp);
+        self.generation_source = segment.map(|s| s.generation);
+        self.load_source = segment.map(|s| s.load);
+        self.forecast = series.into();
+        if let Some(split) = series.generation_split.get(&timestamp) {
+            self.solar_mw = Some(split.solar);
+            self.wind_mw = Some(split.wind);
+            self.solar_share = split.solar_share();
+            self.wind_share = split.wind_share();
+        }
+        self
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct DocumentMetaResponse {
+    created_date_time: String,
+    revision_number: u32,
+}
+
+impl From<DocumentMeta> for DocumentMetaResponse {
+    fn from(meta: DocumentMeta) -> Self {
+        Self {
+            created_date_time: meta.created_date_time.to_rfc3339(),
+            revision_number: meta.revision_number,
+        }
+    }
+}
+
+/// When the forecasts behind a response were issued, so clients can detect stale data
+#[derive(Serialize, Default)]
+pub(super) struct ForecastInfo {
+    /// Issue time of the oldest forecast document involved
+    forecast_created_at: Option<String>,
+    generation_forecast: Option<DocumentMetaResponse>,
+    load_forecast: Option<DocumentMetaResponse>,
+    /// `fresh`, `revalidated` (unchanged upstream, served from cache) or `stale`
+    /// (ENTSO-E unreachable, served from cache)
+    cache_status: CacheStatus,
+    /// `national`, `control_area_sum` (summed over the control areas of the zone) or
+    /// `national_over_control_areas` (control area series sent alongside left out)
+    load_aggregation: LoadAggregation,
+}
+
+impl From<&SurplusSeries> for ForecastInfo {
+    fn from(series: &SurplusSeries) -> Self {
+        Self {
+            forecast_created_at: series.forecast_created_at().map(|t| t.to_rfc3339()),
+            generation_forecast: series.generation_doc_meta.map(Into::into),
+            load_forecast: series.load_doc_meta.map(Into::into),
+            cache_status: series.cache_status,
+            load_aggregation: series.load_aggregation,
+        }
+    }
+}
+
+/// Forecast horizon and the time span it covers in a response
+#[derive(Serialize)]
+pub(super) struct SourceSegmentResponse {
+    start: String,
+    end: String,
+    generation: ForecastSource,
+    load: ForecastSource,
+}
+
+impl From<&SourceSegment> for SourceSegmentResponse {
+    fn from(segment: &SourceSegment) -> Self {
+        Self {
+            start: segment.start.to_rfc3339(),
+            end: segment.end.to_rfc3339(),
+            generation: segment.generation,
+            load: segment.load,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct IntervalResponse {
+    start: String,
+    end: String,
+}
+
+impl From<&Interval> for IntervalResponse {
+    fn from(interval: &Interval) -> Self {
+        Self {
+            start: interval.start.to_rfc3339(),
+            end: interval.end.to_rfc3339(),
+        }
+    }
+}
+
+/// Which parts of the requested window the forecasts cover
+#[derive(Serialize)]
+pub(super) struct CoverageResponse {
+    requested: IntervalResponse,
+    covered: Vec<IntervalResponse>,
+    gaps: Vec<IntervalResponse>,
+    complete: bool,
+    /// Points upstream sent as NaN or infinity, whose surplus is left out
+    dropped_points: usize,
+    /// Part of `requested` fetched when it reaches past the published forecasts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) clamped_to: Option<IntervalResponse>,
+}
+
+impl From<&Coverage> for CoverageResponse {
+    fn from(coverage: &Coverage) -> Self {
+        Self {
+            requested: (&coverage.requested).into(),
+            covered: coverage.covered.iter().map(Into::into).collect(),
+            gaps: coverage.gaps.iter().map(Into::into).collect(),
+            complete: coverage.is_complete(),
+            dropped_points: coverage.dropped_points,
+            clamped_to: None,
+        }
+    }
+}
+
+/// Coverage of `[start, end)` by `series`, logged when incomplete. With `strict`, an
+/// incomplete coverage is a 404 naming the gaps.
+pub(super) fn checked_coverage(
+    series: &SurplusSeries,
+    zone_code: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    strict: bool,
+) -> Result<CoverageResponse, ApiError> {
+    let coverage = series.coverage(start, end);
+    if !coverage.is_complete() {
+        let gaps: Vec<String> = coverage
+            .gaps
+            .iter()
+            .map(|gap| format!("{}/{}", gap.start.to_rfc3339(), gap.end.to_rfc3339()))
+            .collect();
+        let message = format!(
+            "Forecasts for {} cover {:.1} of the requested {:.1} hours, missing {}",
+            zone_code,
+            coverage.covered_duration().num_minutes() as f64 / 60.0,
+            coverage.requested.duration().num_minutes() as f64 / 60.0,
+            gaps.join(", ")
+        );
+        eprintln!("Warning: {}", message);
+        if strict {
+            return Err(ApiError::new(StatusCode::NOT_FOUND, message));
+        }
+    }
+    Ok((&coverage).into())
+}
+
+/// The surplus model a response was computed with
+#[derive(Serialize)]
+pub(super) struct SurplusModelResponse {
+    /// A constant or 24 hourly values
+    baseload_mw: Option<BaseloadResponse>,
+    export_mw: f64,
+    efficiency: f64,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BaseloadResponse {
+    Constant(f64),
+    Hourly([f64; 24]),
+}
+
+impl SurplusModelResponse {
+    /// Echoed only for non-default models, keeping default responses unchanged
+    pub(super) fn echo(model: &SurplusModel) -> Option<Self> {
+        if model.is_default() {
+            return None;
+        }
+        Some(Self {
+            baseload_mw: match model.baseload {
+                Baseload::None => None,
+                Baseload::Constant(value) => Some(BaseloadResponse::Constant(value)),
+                Baseload::Hourly(values) => Some(BaseloadResponse::Hourly(values)),
+            },
+            export_mw: model.export,
+            efficiency: model.efficiency,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct SeriesPoint {
+    timestamp: String,
+    /// `timestamp` in the time zone of the response, RFC3339 with offset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp_local: Option<String>,
+    generation_mw: f64,
+    load_mw: f64,
+    surplus_mw: f64,
+    total_generation_mw: Option<f64>,
+}
+
+impl From<&RenewableSurplus> for SeriesPoint {
+    fn from(point: &RenewableSurplus) -> Self {
+        Self {
+            timestamp: point.timestamp.to_rfc3339(),
+            timestamp_local: None,
+            generation_mw: point.generation,
+            load_mw: point.load,
+            surplus_mw: point.surplus,
+            total_generation_mw: point.total_generation,
+        }
+    }
+}
+
+impl SeriesPoint {
+    pub(super) fn in_zone(point: &RenewableSurplus, local: &LocalZone) -> Self {
+        Self {
+            timestamp_local: Some(local.to_local(point.timestamp).to_rfc3339()),
+            ..point.into()
+        }
+    }
+}
+
+/// A point of a series requested with `partial=true`, `null` where a forecast is missing
+#[derive(Serialize)]
+pub(super) struct PartialSeriesPoint {
+    timestamp: String,
+    /// `timestamp` in the time zone of the response, RFC3339 with offset
+    timestamp_local: String,
+    generation_mw: Option<f64>,
+    load_mw: Option<f64>,
+    surplus_mw: Option<f64>,
+}
+
+impl PartialSeriesPoint {
+    pub(super) fn in_zone(point: &PartialSurplus, local: &LocalZone) -> Self {
+        Self {
+            timestamp: point.timestamp.to_rfc3339(),
+            timestamp_local: local.to_local(point.timestamp).to_rfc3339(),
+            generation_mw: point.generation,
+            load_mw: point.load,
+            surplus_mw: point.surplus,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct SurplusPoint {
+    timestamp: String,
+    surplus_mw: f64,
+}
+
+impl From<&RenewableSurplus> for SurplusPoint {
+    fn from(surplus: &RenewableSurplus) -> Self {
+        Self {
+            timestamp: surplus.timestamp.to_rfc3339(),
+            surplus_mw: surplus.surplus,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::JsonCase;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state, test_state_with_config};
+    use axum::http::Request;
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_data_endpoints_set_etag_and_cache_control() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let app = router(test_state(transport.clone()));
+
+        for uri in [
+            "/api/v1/renewable-surplus/DE/next-24h",
+            "/api/v1/renewable-surplus/DE/night",
+            "/api/v1/renewable-surplus/DE/plot-json",
+        ] {
+            let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+            assert!(response.headers().contains_key(header::ETAG), "{}", uri);
+            assert_eq!(
+                response.headers()[header::CACHE_CONTROL],
+                "public, max-age=300",
+                "{}",
+                uri
+            );
+        }
+
+        // Each endpoint fetches the load, the wind/solar and the total generation forecast
+        assert_eq!(transport.requests().len(), 9);
+    }
+
+    #[tokio::test]
+    async fn test_authenticated_responses_are_private() {
+        let state = test_state_with_config(
+            Arc::new(MockTransport::forecasts()),
+            ServerConfig {
+                api_tokens: vec!["secret".to_string()],
+                ..ServerConfig::default()
+            },
+        );
+        let uri = "/api/v1/renewable-surplus/DE/plot-json?hours=12";
+        let authorized = |etag: Option<&HeaderValue>| {
+            let request = Request::get(uri).header(header::AUTHORIZATION, "Bearer secret");
+            match etag {
+                Some(etag) => request.header(header::IF_NONE_MATCH, etag),
+                None => request,
+            }
+            .body(Body::empty())
+            .unwrap()
+        };
+
+        let first = router(state.clone())
+            .oneshot(authorized(None))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(
+            first.headers()[header::CACHE_CONTROL],
+            "private, max-age=300"
+        );
+        assert_eq!(first.headers()[header::VARY], "authorization");
+
+        let etag = first.headers()[header::ETAG].clone();
+        let second = router(state)
+            .oneshot(authorized(Some(&etag)))
+            .await
+            .unwrap();
+        assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            second.headers()[header::CACHE_CONTROL],
+            "private, max-age=300"
+        );
+        assert_eq!(second.headers()[header::VARY], "authorization");
+    }
+
+    #[tokio::test]
+    async fn test_matching_if_none_match_returns_not_modified() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let uri = "/api/v1/renewable-surplus/DE/plot-json?hours=12";
+
+        let first = app.clone().oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[header::ETAG].clone();
+        assert!(!body_bytes(first).await.is_empty());
+
+        let request = Request::get(uri)
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .body(Body::empty())
+            .unwrap();
+        let second = app.oneshot(request).await.unwrap();
+
+        assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(second.headers()[header::ETAG], etag);
+        assert!(body_bytes(second).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stale_if_none_match_returns_full_body() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        let request = Request::get("/api/v1/renewable-surplus/DE/next-6h")
+            .header(header::IF_NONE_MATCH, "\"0000000000000000\"")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!body_bytes(response).await.is_empty());
+    }
+
+    #[test]
+    fn test_payload_etag_is_the_fnv1a_of_the_body() {
+        assert_eq!(payload_etag(b"foobar"), "\"85944171f73967e8\"");
+    }
+
+    #[test]
+    fn test_etag_matches_lists_and_weak_validators() {
+        let etag = payload_etag(b"payload");
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            format!("\"other\", W/{}", etag).parse().unwrap(),
+        );
+
+        assert!(etag_matches(&headers, &etag));
+        assert!(!etag_matches(&HeaderMap::new(), &etag));
+    }
+
+    #[test]
+    fn test_camel_case_key() {
+        assert_eq!(camel_case_key("max_surplus_mw"), "maxSurplusMw");
+        assert_eq!(camel_case_key("success"), "success");
+        assert_eq!(camel_case_key("DE"), "DE");
+    }
+
+    /// Sorted keys of the object at `pointer` of the answer to `uri`
+    async fn answered_keys(json_case: JsonCase, uri: &str, pointer: &str) -> Vec<String> {
+        let config = ServerConfig {
+            json_case,
+            ..ServerConfig::default()
+        };
+        let app = router(test_state_with_config(
+            Arc::new(MockTransport::forecasts()),
+            config,
+        ));
+        let response = app.oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let mut keys: Vec<String> = body
+            .pointer(pointer)
+            .and_then(Value::as_object)
+            .unwrap_or_else(|| panic!("no object at {} in {}", pointer, body))
+            .keys()
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    async fn test_json_case_of_max_surplus_response() {
+        let uri = "/api/v1/renewable-surplus/DE/next-24h";
+        assert_eq!(
+            answered_keys(JsonCase::Snake, uri, "/data").await,
+            [
+                "cache_status",
+                "country_code",
+                "coverage",
+                "filter_applied",
+                "forecast_created_at",
+                "generation_forecast",
+                "generation_mw",
+                "generation_source",
+                "load_aggregation",
+                "load_forecast",
+                "load_mw",
+                "load_source",
+                "reference_time",
+                "renewable_penetration",
+                "renewable_share_of_generation",
+                "solar_mw",
+                "solar_share",
+                "surplus_mw",
+                "surplus_percentage",
+                "timestamp",
+                "timestamp_local",
+                "timestamp_utc",
+                "timezone",
+                "total_generation_mw",
+                "wind_mw",
+                "wind_share",
+            ]
+        );
+        assert_eq!(
+            answered_keys(JsonCase::Camel, uri, "/data").await,
+            [
+                "cacheStatus",
+                "countryCode",
+                "coverage",
+                "filterApplied",
+                "forecastCreatedAt",
+                "generationForecast",
+                "generationMw",
+                "generationSource",
+                "loadAggregation",
+                "loadForecast",
+                "loadMw",
+                "loadSource",
+                "referenceTime",
+                "renewablePenetration",
+                "renewableShareOfGeneration",
+                "solarMw",
+                "solarShare",
+                "surplusMw",
+                "surplusPercentage",
+                "timestamp",
+                "timestampLocal",
+                "timestampUtc",
+                "timezone",
+                "totalGenerationMw",
+                "windMw",
+                "windShare",
+            ]
+        );
+        // The envelope and nested objects are renamed too
+        assert_eq!(
+            answered_keys(JsonCase::Camel, uri, "").await,
+            ["data", "error", "schemaVersion", "success"]
+        );
+        assert_eq!(
+            answered_keys(JsonCase::Camel, uri, "/data/coverage").await,
+            ["complete", "covered", "droppedPoints", "gaps", "requested"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_case_of_series_points() {
+        let uri = "/api/v1/renewable-surplus/DE/series?hours=6";
+        assert_eq!(
+            answered_keys(JsonCase::Snake, uri, "/data/points/0").await,
+            [
+                "generation_mw",
+                "load_mw",
+                "surplus_mw",
+                "timestamp",
+                "timestamp_local",
+                "total_generation_mw",
+            ]
+        );
+        assert_eq!(
+            answered_keys(JsonCase::Camel, uri, "/data/points/0").await,
+            [
+                "generationMw",
+                "loadMw",
+                "surplusMw",
+                "timestamp",
+                "timestampLocal",
+                "totalGenerationMw",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_camel_case_leaves_values_and_other_answers() {
+        let config = ServerConfig {
+            json_case: JsonCase::Camel,
+            ..ServerConfig::default()
+        };
+        let app = router(test_state_with_config(
+            Arc::new(MockTransport::forecasts()),
+            config,
+        ));
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"]["generationSource"], "day_ahead");
+
+        // Refusals of the version negotiation are enveloped, so renamed as well
+        let response = app
+            .oneshot(get_request("/api/v2/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["schemaVersion"], 1, "{}", body);
+    }
+}
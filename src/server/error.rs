@@ -0,0 +1,385 @@
+//! Error answers of the API and the query extractor that names invalid parameters
+
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Json, Response};
+use serde::de::DeserializeOwned;
+
+use super::dto::ApiResponse;
+use crate::entsoe::areas::{BiddingZone, DocumentKind};
+use crate::entsoe::{EntsoeError, RATE_LIMIT_COOL_DOWN};
+
+/// Error answered by handlers in the standard JSON envelope
+#[derive(Debug)]
+pub(super) struct ApiError {
+    pub(super) status: StatusCode,
+    pub(super) message: String,
+    pub(super) code: Option<&'static str>,
+    /// Sent as `Retry-After`
+    pub(super) retry_after: Option<std::time::Duration>,
+}
+
+/// Error code of answers upstream had no points for
+pub(super) const NO_DATA: &str = "no_data";
+/// Error code of answers about documents the zone does not publish at all, unlike
+/// [`NO_DATA`] which may be temporary
+pub(super) const UNSUPPORTED_FOR_ZONE: &str = "unsupported_for_zone";
+/// Message of [`NO_DATA`] answers of the surplus routes
+pub(super) const NO_SURPLUS_POINTS: &str = "No surplus points in the requested period";
+
+impl ApiError {
+    pub(super) fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            code: None,
+            retry_after: None,
+        }
+    }
+
+    pub(super) fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    /// A 404 with code [`NO_DATA`], telling an empty forecast from an unknown route
+    pub(super) fn no_data(message: impl Into<String>) -> Self {
+        Self {
+            code: Some(NO_DATA),
+            ..Self::new(StatusCode::NOT_FOUND, message)
+        }
+    }
+
+    /// A 404 with code [`UNSUPPORTED_FOR_ZONE`], answered without asking upstream
+    pub(super) fn unsupported_for_zone(zone: &BiddingZone, kind: DocumentKind) -> Self {
+        Self {
+            code: Some(UNSUPPORTED_FOR_ZONE),
+            ..Self::new(
+                StatusCode::NOT_FOUND,
+                format!(
+                    "{} does not publish {}, see /api/v1/capabilities/{}",
+                    zone,
+                    kind.name(),
+                    zone.country_code
+                ),
+            )
+        }
+    }
+
+    /// Answer for a failed upstream request: a 429 while ENTSO-E rate-limits the key,
+    /// [`NO_DATA`] for documents without points and `status` for anything else
+    pub(super) fn upstream(e: EntsoeError, status: StatusCode) -> Self {
+        match e {
+            EntsoeError::NoData(_) => ApiError::no_data(e.to_string()),
+            EntsoeError::RateLimited { retry_after } => {
+                let retry_after = retry_after.unwrap_or(RATE_LIMIT_COOL_DOWN);
+                Self {
+                    retry_after: Some(retry_after),
+                    ..Self::new(
+                        StatusCode::TOO_MANY_REQUESTS,
+                        format!(
+                            "ENTSO-E rate-limits the API key, retry in {} seconds",
+                            retry_after_secs(retry_after)
+                        ),
+                    )
+                }
+            }
+            e => {
+                tracing::error!("ENTSO-E API error: {}", e);
+                ApiError::from(status)
+            }
+        }
+    }
+}
+
+/// Whole seconds of a `Retry-After`, rounded up so clients never retry too early
+pub(super) fn retry_after_secs(retry_after: std::time::Duration) -> u64 {
+    retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)
+}
+
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        Self::new(
+            status,
+            status.canonical_reason().unwrap_or("Request failed"),
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ApiResponse::<()> {
+            code: self.code,
+            ..ApiResponse::error(self.message)
+        };
+        let mut response = (self.status, Json(body)).into_response();
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after_secs(retry_after).into());
+        }
+        response
+    }
+}
+
+/// [`Query`] answering malformed parameters with the standard JSON error, naming the
+/// offending parameter, instead of axum's plain text rejection
+pub(super) struct ValidQuery<T>(pub(super) T);
+
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Query::<T>::from_request_parts(parts, state).await {
+            Ok(Query(query)) => Ok(ValidQuery(query)),
+            Err(rejection) => Err(ApiError::bad_request(query_error_message(
+                &rejection.body_text(),
+            ))),
+        }
+    }
+}
+
+/// `Invalid `hours`: ...` from axum's `Failed to deserialize query string: hours: ...`
+fn query_error_message(rejection: &str) -> String {
+    let detail = rejection
+        .strip_prefix("Failed to deserialize query string: ")
+        .unwrap_or(rejection);
+    match detail.split_once(": ") {
+        // Of the queries refusing parameters other endpoints take
+        Some((_, reason)) if reason.starts_with("unknown field ") => {
+            format!("Unknown parameter {}", &reason["unknown field ".len()..])
+        }
+        Some((parameter, reason)) if !parameter.contains(' ') => {
+            format!("Invalid `{}`: {}", parameter, reason)
+        }
+        _ => format!("Invalid query: {}", detail),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::routes::{self, Access};
+    use crate::server::tests::{body_bytes, get_request, test_state};
+    use axum::Router;
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_invalid_query_parameters_are_named() {
+        async fn bad_request(app: &Router, uri: &str) -> String {
+            let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["success"], false, "{}", uri);
+            body["error"].as_str().unwrap().to_string()
+        }
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        for hours in ["0", "169", "9999"] {
+            let uri = format!("/api/v1/renewable-surplus/DE/next?hours={}", hours);
+            assert_eq!(
+                bad_request(&app, &uri).await,
+                "`hours` must be between 1 and 168"
+            );
+        }
+        assert_eq!(
+            bad_request(&app, "/api/v1/renewable-surplus/DE/next?hours=abc").await,
+            "Invalid `hours`: invalid digit found in string"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/renewable-surplus/DE/next?hours=-1").await,
+            "Invalid `hours`: invalid digit found in string"
+        );
+        assert!(
+            bad_request(&app, "/api/v1/renewable-surplus/DE/series?freshness=weekly")
+                .await
+                .starts_with("Invalid `freshness`: unknown variant `weekly`")
+        );
+        // Parameters of other range endpoints are refused, not ignored
+        for (uri, parameter) in [
+            ("series?band=true&duration=3", "`band`"),
+            ("best-window?view=by-tso", "`view`"),
+            ("plot-json?partial=true", "`partial`"),
+            ("vega?unit=GW", "`unit`"),
+        ] {
+            let uri = format!("/api/v1/renewable-surplus/DE/{}", uri);
+            let message = bad_request(&app, &uri).await;
+            assert!(
+                message.starts_with(&format!("Unknown parameter {}, expected one of", parameter)),
+                "{}: {}",
+                uri,
+                message
+            );
+        }
+        assert_eq!(
+            bad_request(&app, "/api/v1/renewable-surplus/DE/deficits?threshold=high").await,
+            "Invalid `threshold`: invalid float literal"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/renewable-surplus/DE/deficits?threshold=100").await,
+            "`threshold` must be zero or negative"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/renewable-surplus/DE/deficits?hours=200").await,
+            "`hours` must be between 1 and 168"
+        );
+        assert_eq!(
+            bad_request(
+                &app,
+                "/api/v1/renewable-surplus/DE/next-24h?tz=Mars/Olympus"
+            )
+            .await,
+            "Unknown time zone `Mars/Olympus`"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/renewable-surplus/XX/next-24h").await,
+            "Unknown country `XX`, see /api/v1/countries"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/interconnector/DE/XX/utilization").await,
+            "Unknown country `XX`, see /api/v1/countries"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/renewable-surplus/DE/series?start=yesterday").await,
+            "Invalid `start`: expected an RFC3339 timestamp like 2024-06-01T00:00:00Z"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/forecast-drift/DE").await,
+            "Invalid query: missing field `date`"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/forecast-drift/DE?date=June").await,
+            "Invalid `date`: expected a day like 2024-06-01"
+        );
+        assert_eq!(
+            bad_request(&app, "/api/v1/metrics/forecast/DE?hours=0").await,
+            "`hours` must be between 1 and 168"
+        );
+
+        // Country codes are case-insensitive
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/de/next-6h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_documents_are_refused_without_asking_upstream() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let app = router(test_state(transport.clone()));
+
+        // Bosnia and Herzegovina publishes no wind and solar forecast, nor day-ahead prices
+        for uri in [
+            "/api/v1/renewable-surplus/BA/next-24h",
+            "/api/v1/renewable-surplus/BA/series?hours=6",
+            "/api/v1/compare?countries=DE,BA&prices=true",
+        ] {
+            let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["code"], UNSUPPORTED_FOR_ZONE, "{}", body);
+            assert!(
+                body["error"]
+                    .as_str()
+                    .unwrap()
+                    .contains("/api/v1/capabilities/BA"),
+                "{}",
+                body
+            );
+        }
+        let asked_for_bosnia = transport
+            .requests()
+            .iter()
+            .any(|url| url.contains("10YBA-JPCC-----D"));
+        assert!(!asked_for_bosnia);
+    }
+
+    #[tokio::test]
+    async fn test_empty_documents_answer_no_data() {
+        use crate::entsoe::testing::{
+            document_without_series, empty_period_document, ok, query_param,
+        };
+
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        for fixture in [empty_period_document, document_without_series] {
+            let app = router(
+                test_state(Arc::new(MockTransport::new(move |url| {
+                    ok(fixture(&query_param(url, "documentType").unwrap(), start))
+                })))
+                .with_clock(Arc::new(FixedClock(start))),
+            );
+            for route in routes::table() {
+                if route.method != axum::http::Method::GET
+                    || !route.path.contains("{country}")
+                    || route.access == Access::Debug
+                {
+                    continue;
+                }
+                let uri = route.path.replace("{country}", "DE");
+                let response = app.clone().oneshot(get_request(&uri)).await.unwrap();
+                let status = response.status();
+                assert_ne!(status, StatusCode::INTERNAL_SERVER_ERROR, "{}", uri);
+
+                // Diffs and profiles need the history database, availability reads the
+                // intervals
+                let expects_points = (uri.starts_with("/api/v1/renewable-surplus/")
+                    && !uri.ends_with("/diff")
+                    && !uri.ends_with("/profile")
+                    && !uri.ends_with("/availability"))
+                    || uri.starts_with("/api/v1/generation-mix/")
+                    || uri.starts_with("/api/v1/load/")
+                    || uri.starts_with("/api/v1/metrics/forecast/");
+                if expects_points {
+                    assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+                    let body: serde_json::Value =
+                        serde_json::from_slice(&body_bytes(response).await).unwrap();
+                    assert_eq!(body["code"], NO_DATA, "{}", uri);
+                    assert_eq!(body["success"], false);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upstream_rate_limits_answer_too_many_requests() {
+        let transport = Arc::new(MockTransport::new(|_| crate::entsoe::TransportResponse {
+            status: 429,
+            body: String::new(),
+            retry_after: Some(std::time::Duration::from_secs(30)),
+        }));
+        let app = router(test_state(transport.clone()));
+
+        for uri in [
+            "/api/v1/renewable-surplus/DE/next-24h",
+            "/api/v1/renewable-surplus/DE/forecast.csv",
+            "/api/v1/load/DE/by-tso",
+        ] {
+            let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS, "{}", uri);
+            let retry_after: u64 = response.headers()[header::RETRY_AFTER]
+                .to_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!((1..=30).contains(&retry_after), "{}", retry_after);
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert!(body["error"].as_str().unwrap().contains("rate-limits"));
+        }
+        // Only the first request reached upstream
+        assert_eq!(transport.requests().len(), 1);
+    }
+}
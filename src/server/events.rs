@@ -0,0 +1,113 @@
+//! Series events pushed to streaming clients: a snapshot when a client subscribes to a
+//! country, then an update whenever the refresher publishes a new forecast revision
+
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use super::AppState;
+use super::dto::{ForecastInfo, SeriesPoint};
+use super::error::ApiError;
+use super::query::{fetch_window_series, unknown_country};
+use crate::entsoe::analysis::{Freshness, SurplusSeries};
+use crate::entsoe::areas::get_primary_zone;
+use crate::entsoe::window::TimeWindow;
+use crate::refresher::RefreshEvent;
+
+/// Look-ahead of snapshots for countries the refresher does not prefetch
+const SNAPSHOT_HOURS: i64 = 24;
+
+#[derive(Serialize)]
+pub(super) struct SeriesEvent {
+    country_code: String,
+    refreshed_at: String,
+    points: Vec<SeriesPoint>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+}
+
+impl SeriesEvent {
+    fn new(
+        country_code: &str,
+        refreshed_at: chrono::DateTime<Utc>,
+        series: &SurplusSeries,
+    ) -> Self {
+        Self {
+            country_code: country_code.to_string(),
+            refreshed_at: refreshed_at.to_rfc3339(),
+            points: series.points.iter().map(Into::into).collect(),
+            forecast: series.into(),
+        }
+    }
+}
+
+impl From<&RefreshEvent> for SeriesEvent {
+    fn from(event: &RefreshEvent) -> Self {
+        Self::new(&event.country_code, event.refreshed_at, &event.series)
+    }
+}
+
+/// Messages sent to streaming clients, tagged by `type`
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum StreamEvent {
+    Snapshot(SeriesEvent),
+    Update(SeriesEvent),
+    Pong,
+    Error { message: String },
+}
+
+/// Current series of a known country, from the refresher when it prefetches the
+/// country and fetched otherwise
+pub(super) async fn snapshot(state: &AppState, country_code: &str) -> StreamEvent {
+    if let Some(event) = state
+        .refresher
+        .as_ref()
+        .and_then(|refresher| refresher.latest(country_code))
+    {
+        return StreamEvent::Snapshot((&event).into());
+    }
+
+    let Some(zone) = get_primary_zone(country_code) else {
+        return StreamEvent::Error {
+            message: unknown_country(country_code),
+        };
+    };
+    let now = state.now();
+    // Include the point currently in effect
+    let window = TimeWindow::next_hours_from(now, SNAPSHOT_HOURS).extended_back(Duration::hours(1));
+    match fetch_window_series(state, zone.code, &window, Freshness::default()).await {
+        Ok(series) => StreamEvent::Snapshot(SeriesEvent::new(country_code, now, &series)),
+        Err(ApiError { message, .. }) => StreamEvent::Error { message },
+    }
+}
+
+/// Refresh events of every country; without a refresher no updates ever arrive
+pub(super) fn updates(state: &AppState) -> Option<broadcast::Receiver<RefreshEvent>> {
+    state
+        .refresher
+        .as_ref()
+        .map(|refresher| refresher.subscribe())
+}
+
+/// Next refresh of `country_code`, `None` once no more can arrive
+pub(super) async fn next_update(
+    updates: &mut Option<broadcast::Receiver<RefreshEvent>>,
+    country_code: &str,
+) -> Option<StreamEvent> {
+    let receiver = updates.as_mut()?;
+    loop {
+        match receiver.recv().await {
+            Ok(event) if event.country_code == country_code => {
+                return Some(StreamEvent::Update((&event).into()));
+            }
+            Ok(_) => {}
+            // A skipped revision is superseded by the next one anyway
+            Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => {
+                *updates = None;
+                return None;
+            }
+        }
+    }
+}
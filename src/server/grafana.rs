@@ -0,0 +1,306 @@
+//! Grafana JSON datasource (SimpleJSON/Infinity) contract under `/api/v1/grafana`
+
+use axum::Json;
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::AppState;
+use super::error::ApiError;
+use super::history::stored_or_live_surplus;
+use super::query::query_window;
+use super::routes::ApiRoute;
+use crate::entsoe::analysis::RenewableSurplus;
+use crate::entsoe::areas::{self, get_primary_zone};
+
+/// Values a target can chart, the part after the colon in `DE:surplus`
+const METRICS: [&str; 3] = ["surplus", "generation", "load"];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QueryRequest {
+    range: QueryRange,
+    #[serde(default)]
+    targets: Vec<QueryTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryRange {
+    /// RFC3339
+    from: String,
+    /// RFC3339
+    to: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QueryTarget {
+    /// `COUNTRY:metric`, empty while a panel is being edited
+    #[serde(default)]
+    target: String,
+    #[serde(default)]
+    hide: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct TimeSeries {
+    target: String,
+    /// `[value, epoch milliseconds]`, oldest first
+    datapoints: Vec<(f64, i64)>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SearchRequest {
+    /// Case-insensitive prefix the targets must start with
+    #[serde(default)]
+    target: String,
+}
+
+/// Country and metric of a `DE:surplus` target
+fn parse_target(target: &str) -> Result<(&str, &'static str), ApiError> {
+    let invalid = || {
+        ApiError::bad_request(format!(
+            "Invalid target `{}`: expected COUNTRY:{}",
+            target,
+            METRICS.join("|")
+        ))
+    };
+    let (country, metric) = target.split_once(':').ok_or_else(invalid)?;
+    let metric = METRICS
+        .into_iter()
+        .find(|m| m.eq_ignore_ascii_case(metric.trim()))
+        .ok_or_else(invalid)?;
+    Ok((country.trim(), metric))
+}
+
+fn datapoints(points: &[RenewableSurplus], metric: &str) -> Vec<(f64, i64)> {
+    points
+        .iter()
+        .map(|point| {
+            let value = match metric {
+                "generation" => point.generation,
+                "load" => point.load,
+                _ => point.surplus,
+            };
+            (value, point.timestamp.timestamp_millis())
+        })
+        .collect()
+}
+
+/// GET /api/v1/grafana/
+/// Lets "Save & test" of the datasource succeed
+async fn datasource_test() -> &'static str {
+    "OK"
+}
+
+/// POST /api/v1/grafana/search
+/// Targets of every country with a primary bidding zone
+async fn search(body: Option<Json<SearchRequest>>) -> Json<Vec<String>> {
+    let prefix = body.map(|Json(body)| body.target).unwrap_or_default();
+    let prefix = prefix.to_ascii_uppercase();
+    let targets = areas::list_countries()
+        .into_iter()
+        .flat_map(|country| METRICS.map(|metric| format!("{}:{}", country, metric)))
+        .filter(|target| target.to_ascii_uppercase().starts_with(&prefix))
+        .collect();
+
+    Json(targets)
+}
+
+/// POST /api/v1/grafana/query
+/// One series per visible target over the dashboard range. Past ranges come from the
+/// stored history when available, everything else from (cached) ENTSO-E forecasts.
+async fn query(
+    State(state): State<AppState>,
+    Json(request): Json<QueryRequest>,
+) -> Result<Json<Vec<TimeSeries>>, ApiError> {
+    let window = query_window(
+        None,
+        Some(&request.range.from),
+        Some(&request.range.to),
+        None,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+
+    // Targets of one country share a fetch
+    let mut surplus: HashMap<&str, Vec<RenewableSurplus>> = HashMap::new();
+    let mut response = Vec::new();
+    for target in request
+        .targets
+        .iter()
+        .filter(|t| !t.hide && !t.target.is_empty())
+    {
+        let (country, metric) = parse_target(&target.target)?;
+        let zone = get_primary_zone(&country.to_ascii_uppercase()).ok_or_else(|| {
+            ApiError::bad_request(format!("Unknown country in target `{}`", target.target))
+        })?;
+
+        if !surplus.contains_key(zone.code) {
+            let (_, points) = stored_or_live_surplus(&state, zone.code, &window).await?;
+            surplus.insert(zone.code, points);
+        }
+        response.push(TimeSeries {
+            target: target.target.clone(),
+            datapoints: datapoints(&surplus[zone.code], metric),
+        });
+    }
+
+    Ok(Json(response))
+}
+
+pub(super) fn routes() -> [ApiRoute; 4] {
+    [
+        ApiRoute::get("/api/v1/grafana", datasource_test).usage("Grafana JSON datasource"),
+        ApiRoute::get("/api/v1/grafana/", datasource_test),
+        ApiRoute::post("/api/v1/grafana/search", search),
+        ApiRoute::post("/api/v1/grafana/query", query),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::test_state;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn post_json(uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn json_body(response: axum::response::Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_query_request_from_grafana() {
+        let request: QueryRequest = serde_json::from_value(serde_json::json!({
+            "app": "dashboard",
+            "requestId": "Q100",
+            "range": {
+                "from": "2024-06-01T00:00:00.000Z",
+                "to": "2024-06-01T06:00:00.000Z",
+                "raw": { "from": "now-6h", "to": "now" }
+            },
+            "interval": "30s",
+            "intervalMs": 30000,
+            "maxDataPoints": 550,
+            "targets": [
+                { "target": "DE:surplus", "refId": "A", "type": "timeserie" },
+                { "target": "FR:load", "refId": "B", "type": "timeserie", "hide": true },
+                { "refId": "C" }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(request.range.from, "2024-06-01T00:00:00.000Z");
+        let targets: Vec<_> = request
+            .targets
+            .iter()
+            .map(|t| (t.target.as_str(), t.hide))
+            .collect();
+        assert_eq!(
+            targets,
+            [("DE:surplus", false), ("FR:load", true), ("", false)]
+        );
+    }
+
+    #[test]
+    fn test_time_series_response_shape() {
+        let series = TimeSeries {
+            target: "DE:surplus".to_string(),
+            datapoints: vec![(-1200.5, 1717200000000), (300.0, 1717203600000)],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&series).unwrap(),
+            serde_json::json!({
+                "target": "DE:surplus",
+                "datapoints": [[-1200.5, 1717200000000_i64], [300.0, 1717203600000_i64]]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(parse_target("DE:surplus").unwrap(), ("DE", "surplus"));
+        assert_eq!(parse_target("fr: Load").unwrap(), ("fr", "load"));
+        assert!(parse_target("DE").is_err());
+        assert!(parse_target("DE:price").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_and_search_endpoints() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let app = router(test_state(transport.clone()));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/v1/grafana/")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(post_json(
+                "/api/v1/grafana/search",
+                serde_json::json!({ "target": "de:" }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            json_body(response).await,
+            serde_json::json!(["DE:surplus", "DE:generation", "DE:load"])
+        );
+
+        let query = |targets: serde_json::Value| {
+            post_json(
+                "/api/v1/grafana/query",
+                serde_json::json!({
+                    "range": { "from": "2024-06-01T00:00:00Z", "to": "2024-06-01T06:00:00Z" },
+                    "targets": targets
+                }),
+            )
+        };
+        let response = app
+            .clone()
+            .oneshot(query(serde_json::json!([{ "target": "DE:surplus" }])))
+            .await
+            .unwrap();
+        let single_target_requests = transport.requests().len();
+        assert_eq!(json_body(response).await[0]["target"], "DE:surplus");
+
+        let response = app
+            .oneshot(query(serde_json::json!([
+                { "target": "DE:surplus" },
+                { "target": "DE:load" }
+            ])))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json_body(response).await;
+
+        let datapoints = body[1]["datapoints"].as_array().unwrap();
+        assert_eq!(body[1]["target"], "DE:load");
+        assert_eq!(datapoints.len(), 6);
+        assert_eq!(datapoints[0][1], 1717200000000_i64);
+        // Both targets are served from one fetched series
+        assert_eq!(transport.requests().len(), 2 * single_target_requests);
+    }
+}
@@ -0,0 +1,527 @@
+//! Grid data besides the surplus: balancing activations, generation mix, interconnector
+//! flows and generation outages
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use chrono::{Duration, DurationRound};
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use super::dto::ApiResponse;
+use super::error::{ApiError, ValidQuery};
+use super::query::{look_ahead_hours, past_hours, requested_zone, require_supported};
+use super::routes::ApiRoute;
+use crate::entsoe::analysis::interconnector_utilization;
+use crate::entsoe::areas::DocumentKind;
+use crate::entsoe::balancing::{FlowDirection, ReserveType};
+use crate::entsoe::generation::{GenerationMix, PsrType, generation_mix};
+use crate::entsoe::outages::OutageKind;
+
+#[derive(Deserialize)]
+struct ActivationsQuery {
+    /// Number of past hours to report (default: 24)
+    hours: Option<u32>,
+    /// `fcr`, `afrr` (default), `mfrr` or `rr`
+    reserve: Option<ReserveType>,
+}
+
+#[derive(Serialize)]
+struct ActivationPoint {
+    start: String,
+    end: String,
+    direction: FlowDirection,
+    /// Positive for upward, negative for downward activation
+    activation_mw: f64,
+}
+
+#[derive(Serialize)]
+struct ActivationsResponse {
+    country_code: String,
+    control_area: String,
+    reserve: ReserveType,
+    activations: Vec<ActivationPoint>,
+}
+
+/// GET /api/v1/balancing/:country/activations?hours=24&reserve=afrr|mfrr|fcr|rr
+/// Balancing energy activated over the past hours. The control area is taken to be the
+/// primary bidding zone of the country.
+async fn get_balancing_activations(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<ActivationsQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let hours = past_hours(query.hours)?;
+    let reserve = query.reserve.unwrap_or_default();
+
+    let now = state.now();
+    let end = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+    let activations = state
+        .client()?
+        .fetch_activated_balancing_energy(zone.code, end - Duration::hours(hours), end, reserve)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))?;
+
+    Ok(Json(ApiResponse::success(ActivationsResponse {
+        country_code,
+        control_area: zone.code.to_string(),
+        reserve,
+        activations: activations
+            .iter()
+            .map(|activation| ActivationPoint {
+                start: activation.timestamp.to_rfc3339(),
+                end: activation.end().to_rfc3339(),
+                direction: activation.direction,
+                activation_mw: activation.quantity,
+            })
+            .collect(),
+    }))
+    .into_response())
+}
+
+#[derive(Deserialize)]
+struct GenerationMixQuery {
+    /// Number of past hours to aggregate (default: 24)
+    hours: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct GenerationMixResponse {
+    country_code: String,
+    from: String,
+    to: String,
+    #[serde(flatten)]
+    mix: GenerationMix,
+}
+
+/// GET /api/v1/generation-mix/:country?hours=24
+/// Energy generated per production type and per category over the past hours, from the
+/// actual generation (A75)
+async fn get_generation_mix(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<GenerationMixQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let hours = past_hours(query.hours)?;
+
+    let now = state.now();
+    let end = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+    let start = end - Duration::hours(hours);
+    require_supported(&state, zone.code, &[DocumentKind::ActualGeneration])?;
+    let mix = state
+        .client()?
+        .fetch_actual_generation(zone.code, start, end)
+        .await
+        .and_then(|document| generation_mix(&document, start, end))
+        .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))?;
+    if mix.psr_types.is_empty() {
+        return Err(ApiError::no_data(
+            "No generation points in the requested period",
+        ));
+    }
+
+    Ok(Json(ApiResponse::success(GenerationMixResponse {
+        country_code,
+        from: start.to_rfc3339(),
+        to: end.to_rfc3339(),
+        mix,
+    }))
+    .into_response())
+}
+
+#[derive(Deserialize)]
+struct UtilizationQuery {
+    /// Number of past hours to report (default: 24)
+    hours: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct UtilizationPointResponse {
+    start: String,
+    end: String,
+    flow_mw: f64,
+    capacity_mw: f64,
+    /// `null` when no capacity is offered
+    utilization_percent: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct UtilizationResponse {
+    from: String,
+    to: String,
+    points: Vec<UtilizationPointResponse>,
+}
+
+/// GET /api/v1/interconnector/:from/:to/utilization?hours=24
+/// Physical flow from one country into another as a share of the day-ahead net transfer
+/// capacity, over the past hours
+async fn get_interconnector_utilization(
+    State(state): State<AppState>,
+    Path((from, to)): Path<(String, String)>,
+    ValidQuery(query): ValidQuery<UtilizationQuery>,
+) -> Result<Response, ApiError> {
+    let out_zone = requested_zone(&from)?;
+    let in_zone = requested_zone(&to)?;
+    let hours = past_hours(query.hours)?;
+
+    let now = state.now();
+    let end = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+    let start = end - Duration::hours(hours);
+    let client = state.client()?;
+    let (capacity, flows) = tokio::try_join!(
+        client.fetch_offered_capacity(in_zone.code, out_zone.code, start, end),
+        client.fetch_physical_flows(in_zone.code, out_zone.code, start, end)
+    )
+    .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))?;
+
+    let points = interconnector_utilization(&capacity, &flows)
+        .into_iter()
+        .filter(|point| point.timestamp >= start && point.timestamp < end)
+        .map(|point| UtilizationPointResponse {
+            start: point.timestamp.to_rfc3339(),
+            end: (point.timestamp + point.duration).to_rfc3339(),
+            flow_mw: point.flow,
+            capacity_mw: point.capacity,
+            utilization_percent: point.utilization,
+        })
+        .collect();
+
+    Ok(Json(ApiResponse::success(UtilizationResponse {
+        from,
+        to,
+        points,
+    }))
+    .into_response())
+}
+
+#[derive(Deserialize)]
+struct OutagesQuery {
+    /// Number of hours to look ahead (default: 24)
+    hours: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct OutageResponse {
+    unit_mrid: String,
+    unit_name: String,
+    psr_type: String,
+    /// The code itself for unknown production types
+    production_type: String,
+    kind: OutageKind,
+    nominal_mw: f64,
+    /// MW out of service now, 0 for outages yet to start
+    unavailable_mw: f64,
+    start: String,
+    end: String,
+}
+
+#[derive(Serialize)]
+struct OutagesResponse {
+    country_code: String,
+    from: String,
+    to: String,
+    /// Outages lasting now
+    active: usize,
+    /// Generation capacity they take out of service now
+    unavailable_mw: f64,
+    /// By start
+    outages: Vec<OutageResponse>,
+}
+
+/// GET /api/v1/outages/:country?hours=24
+/// Planned and forced outages of generation units (A80) lasting now or starting within the
+/// next hours, with the capacity out of service now
+async fn get_outages(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<OutagesQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let hours = look_ahead_hours(query.hours, 24, state.config.max_query_hours)?;
+
+    let now = state.now();
+    let start = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+    let end = start + Duration::hours(i64::from(hours));
+    let outages = state
+        .client()?
+        .fetch_generation_outages(zone.code, start, end)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))?;
+
+    Ok(Json(ApiResponse::success(OutagesResponse {
+        country_code,
+        from: start.to_rfc3339(),
+        to: end.to_rfc3339(),
+        active: outages
+            .iter()
+            .filter(|outage| outage.is_active(now))
+            .count(),
+        unavailable_mw: outages
+            .iter()
+            .map(|outage| outage.unavailable_mw(now))
+            .sum(),
+        outages: outages
+            .iter()
+            .map(|outage| OutageResponse {
+                unit_mrid: outage.unit_mrid.clone(),
+                unit_name: outage.unit_name.clone(),
+                psr_type: outage.psr_type.clone(),
+                production_type: PsrType::from_code(&outage.psr_type).map_or_else(
+                    || outage.psr_type.clone(),
+                    |psr_type| psr_type.name().to_string(),
+                ),
+                kind: outage.kind,
+                nominal_mw: outage.nominal_mw,
+                unavailable_mw: outage.unavailable_mw(now),
+                start: outage.start.to_rfc3339(),
+                end: outage.end.to_rfc3339(),
+            })
+            .collect(),
+    }))
+    .into_response())
+}
+
+pub(super) fn routes() -> [ApiRoute; 4] {
+    [
+        ApiRoute::get(
+            "/api/v1/balancing/{country}/activations",
+            get_balancing_activations,
+        )
+        .usage("?hours=24&reserve=afrr"),
+        ApiRoute::get("/api/v1/generation-mix/{country}", get_generation_mix).usage("?hours=24"),
+        ApiRoute::get(
+            "/api/v1/interconnector/{from}/{to}/utilization",
+            get_interconnector_utilization,
+        )
+        .usage("?hours=24"),
+        ApiRoute::get("/api/v1/outages/{country}", get_outages).usage("?hours=24"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_generation_mix_endpoint() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            let start = crate::entsoe::testing::query_param(url, "periodStart").unwrap();
+            let start = chrono::NaiveDateTime::parse_from_str(&start, "%Y%m%d%H%M")
+                .unwrap()
+                .and_utc();
+            crate::entsoe::testing::ok(crate::entsoe::testing::actual_generation_document(start))
+        }));
+        let app = router(test_state(transport.clone()));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/generation-mix/de?hours=4"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+
+        assert_eq!(data["total_mwh"], 20_000.0);
+        assert_eq!(data["psr_types"][0]["psr_type"], "B16");
+        assert_eq!(data["psr_types"][0]["name"], "Solar");
+        assert_eq!(data["unknown_psr_types"], serde_json::json!(["B99"]));
+        let categories: Vec<_> = data["categories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| (c["category"].clone(), c["share_percent"].as_f64().unwrap()))
+            .collect();
+        assert_eq!(
+            categories,
+            [
+                ("renewable".into(), 60.0),
+                ("fossil".into(), 20.0),
+                ("nuclear".into(), 10.0),
+                ("other".into(), 10.0)
+            ]
+        );
+        assert!(transport.requests()[0].contains("documentType=A75"));
+
+        let response = app
+            .oneshot(get_request("/api/v1/generation-mix/XX"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_balancing_activations_endpoint() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            let start = crate::entsoe::testing::query_param(url, "periodStart").unwrap();
+            let start = chrono::NaiveDateTime::parse_from_str(&start, "%Y%m%d%H%M")
+                .unwrap()
+                .and_utc();
+            crate::entsoe::testing::ok(crate::entsoe::testing::balancing_document(
+                start,
+                60,
+                &[("A01", &[12.0, 0.0]), ("A02", &[0.0, 8.5])],
+            ))
+        }));
+        let app = router(test_state(transport.clone()));
+
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/balancing/CZ/activations?hours=2&reserve=mfrr",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+
+        assert_eq!(data["reserve"], "mfrr");
+        assert_eq!(data["control_area"], "10YCZ-CEPS-----N");
+        let activations: Vec<_> = data["activations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|a| (a["direction"].clone(), a["activation_mw"].as_f64().unwrap()))
+            .collect();
+        assert_eq!(
+            activations,
+            [
+                ("up".into(), 12.0),
+                ("down".into(), -0.0),
+                ("up".into(), 0.0),
+                ("down".into(), -8.5)
+            ]
+        );
+        assert!(transport.requests()[0].contains("businessType=A97"));
+
+        let response = app
+            .oneshot(get_request("/api/v1/balancing/CZ/activations?hours=0"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_interconnector_utilization_endpoint() {
+        let transport = Arc::new(MockTransport::new(|url| {
+            let start = crate::entsoe::testing::query_param(url, "periodStart").unwrap();
+            let start = chrono::NaiveDateTime::parse_from_str(&start, "%Y%m%d%H%M")
+                .unwrap()
+                .and_utc();
+            let document = match crate::entsoe::testing::query_param(url, "documentType").as_deref()
+            {
+                Some("A61") => {
+                    crate::entsoe::testing::publication_document("A61", start, 60, &[1000.0, 0.0])
+                }
+                _ => crate::entsoe::testing::publication_document("A11", start, 30, &[250.0; 4]),
+            };
+            crate::entsoe::testing::ok(document)
+        }));
+        let app = router(test_state(transport.clone()));
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/interconnector/DE/FR/utilization?hours=2",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        let utilization: Vec<_> = body["data"]["points"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["utilization_percent"].clone())
+            .collect();
+        assert_eq!(
+            utilization,
+            [
+                25.0.into(),
+                25.0.into(),
+                serde_json::Value::Null,
+                serde_json::Value::Null
+            ]
+        );
+        let requests = transport.requests();
+        assert!(
+            requests
+                .iter()
+                .all(|url| url.contains("in_Domain=10YFR-RTE------C"))
+        );
+        assert!(
+            requests
+                .iter()
+                .all(|url| url.contains("out_Domain=10Y1001A1001A83F"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_outages_endpoint() {
+        use crate::entsoe::testing::{MockOutage, outages_response, query_param};
+
+        let transport = Arc::new(MockTransport::new(|url| {
+            assert_eq!(query_param(url, "documentType").as_deref(), Some("A80"));
+            let start = query_param(url, "periodStart").unwrap();
+            let start = chrono::NaiveDateTime::parse_from_str(&start, "%Y%m%d%H%M")
+                .unwrap()
+                .and_utc();
+            outages_response(&[
+                MockOutage {
+                    planned: true,
+                    unit: ("11WD7BRUN1--K", "Brunsbüttel"),
+                    ..MockOutage::new(
+                        "outage-2",
+                        start + Duration::hours(6),
+                        start + Duration::days(2),
+                    )
+                },
+                MockOutage {
+                    available: &[(1, 400.0)],
+                    ..MockOutage::new(
+                        "outage-1",
+                        start - Duration::hours(1),
+                        start + Duration::hours(3),
+                    )
+                },
+            ])
+        }));
+        let app = router(test_state(transport.clone()));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/outages/de?hours=12"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+        assert_eq!(data["active"], 1);
+        assert_eq!(data["unavailable_mw"], 1_000.0);
+        let outages = data["outages"].as_array().unwrap();
+        assert_eq!(outages.len(), 2);
+        assert_eq!(outages[0]["unit_name"], "Isar 2");
+        assert_eq!(outages[0]["kind"], "forced");
+        assert_eq!(outages[0]["production_type"], "Nuclear");
+        assert_eq!(outages[1]["kind"], "planned");
+        assert_eq!(outages[1]["unavailable_mw"], 0.0);
+        assert_eq!(
+            query_param(&transport.requests()[0], "biddingZone_Domain").as_deref(),
+            Some("10Y1001A1001A83F")
+        );
+
+        let response = app
+            .oneshot(get_request("/api/v1/outages/de?hours=0"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,192 @@
+//! Home Assistant REST sensor of the surplus
+
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Json, Response};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::AppState;
+use super::error::{ApiError, ValidQuery};
+use super::query::{fetch_window_series, requested_zone};
+use super::routes::ApiRoute;
+use crate::entsoe::PowerUnit;
+use crate::entsoe::analysis::{
+    Freshness, Interpolation, RenewableSurplus, SurplusPoints, SurplusSeries, best_window, value_at,
+};
+use crate::entsoe::window::TimeWindow;
+
+/// Length of the window reported as `best_3h_window_start`
+const HA_BEST_WINDOW_HOURS: i64 = 3;
+
+/// Look-ahead of the Home Assistant sensor when nothing is prefetched
+const HA_HORIZON_HOURS: i64 = 48;
+
+#[derive(Serialize)]
+struct HaSensorAttributes {
+    available: bool,
+    /// Wind and solar as a percentage of load
+    penetration_pct: Option<f64>,
+    /// Wind and solar as a percentage of total generation
+    share_of_generation_pct: Option<f64>,
+    next_max_surplus_mw: Option<f64>,
+    next_max_at: Option<String>,
+    best_3h_window_start: Option<String>,
+    forecast_created_at: Option<String>,
+    unit_of_measurement: &'static str,
+}
+
+/// Flat document for a Home Assistant `rest` sensor
+#[derive(Serialize)]
+struct HaSensorResponse {
+    /// Current surplus in `unit_of_measurement`
+    state: Option<f64>,
+    attributes: HaSensorAttributes,
+}
+
+impl HaSensorResponse {
+    fn from_series(series: &SurplusSeries, now: DateTime<Utc>, unit: PowerUnit) -> Self {
+        let current = value_at(&series.points, now, Interpolation::Linear);
+        let ahead: Vec<RenewableSurplus> = series
+            .points
+            .iter()
+            .filter(|s| s.timestamp >= now)
+            .cloned()
+            .collect();
+        let next_max = ahead.iter().max_surplus();
+        let best_window = best_window(&ahead, Duration::hours(HA_BEST_WINDOW_HOURS));
+
+        Self {
+            state: current.as_ref().map(|s| unit.from_mw(s.surplus)),
+            attributes: HaSensorAttributes {
+                available: current.is_some(),
+                penetration_pct: current.as_ref().map(|s| s.renewable_penetration()),
+                share_of_generation_pct: current
+                    .as_ref()
+                    .and_then(|s| s.renewable_share_of_generation()),
+                next_max_surplus_mw: next_max.as_ref().map(|s| unit.from_mw(s.surplus)),
+                next_max_at: next_max.map(|s| s.timestamp.to_rfc3339()),
+                best_3h_window_start: best_window.map(|(start, _)| start.to_rfc3339()),
+                forecast_created_at: series.forecast_created_at().map(|t| t.to_rfc3339()),
+                unit_of_measurement: unit.symbol(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct HaQuery {
+    /// Unit of the sensor: `MW` (default), `GW` or `kW`
+    #[serde(default)]
+    unit: PowerUnit,
+}
+
+/// GET /api/v1/ha/:country?unit=MW|GW|kW
+/// Current surplus and outlook as a Home Assistant REST sensor payload.
+/// Missing data yields `"state": null` and `available: false` instead of an error.
+async fn get_ha_sensor(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<HaQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let now = state.now();
+
+    let prefetched = state
+        .refresher
+        .as_ref()
+        .and_then(|refresher| refresher.latest(&country_code.to_ascii_uppercase()));
+
+    let series = match prefetched {
+        Some(event) => event.series,
+        None => {
+            // Include the point currently in effect
+            let window = TimeWindow::next_hours_from(state.now(), HA_HORIZON_HOURS)
+                .extended_back(Duration::hours(1));
+            match fetch_window_series(&state, zone.code, &window, Freshness::default()).await {
+                Ok(series) => Arc::new(series),
+                Err(_) => Arc::new(SurplusSeries::default()),
+            }
+        }
+    };
+
+    Ok(Json(HaSensorResponse::from_series(&series, now, query.unit)).into_response())
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [ApiRoute::get("/api/v1/ha/{country}", get_ha_sensor)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::entsoe::EntsoeClient;
+    use crate::entsoe::testing::MockTransport;
+    use crate::refresher::Refresher;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state, upstream_answering};
+    use axum::http::StatusCode;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_ha_sensor_payload() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        let response = app.oneshot(get_request("/api/v1/ha/DE")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert!(body["state"].is_number());
+        let attributes = &body["attributes"];
+        assert_eq!(attributes["available"], true);
+        assert_eq!(attributes["unit_of_measurement"], "MW");
+        assert!(attributes["penetration_pct"].is_number());
+        assert!(attributes["next_max_surplus_mw"].is_number());
+        assert!(attributes["next_max_at"].is_string());
+        assert!(attributes["best_3h_window_start"].is_string());
+        assert_eq!(
+            attributes["forecast_created_at"],
+            "2024-06-01T12:00:00+00:00"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ha_sensor_without_data_is_unavailable() {
+        let app = router(test_state(upstream_answering(503)));
+
+        let response = app.oneshot(get_request("/api/v1/ha/DE")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert!(body["state"].is_null());
+        assert_eq!(body["attributes"]["available"], false);
+        assert!(body["attributes"]["next_max_surplus_mw"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_ha_sensor_prefers_prefetched_series() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = Arc::new(EntsoeClient::with_transport(
+            "test-token",
+            transport.clone(),
+        ));
+        let refresher = Arc::new(Refresher::new(
+            client.clone(),
+            vec!["DE".to_string()],
+            std::time::Duration::from_secs(60),
+        ));
+        refresher.refresh_once().await;
+        let fetched = transport.requests().len();
+
+        let state = AppState::new(Some(client), ServerConfig::default()).with_refresher(refresher);
+        let response = router(state)
+            .oneshot(get_request("/api/v1/ha/DE"))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert_eq!(body["attributes"]["available"], true);
+        assert_eq!(transport.requests().len(), fetched);
+    }
+}
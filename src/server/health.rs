@@ -0,0 +1,355 @@
+//! Health and readiness probes and the metrics endpoints
+
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Json, Response};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::AppState;
+use super::error::{ApiError, ValidQuery};
+use super::query::{fetch_window_series, look_ahead_hours, requested_zone};
+use super::routes::{Access, ApiRoute};
+use crate::entsoe::UpstreamHealth;
+use crate::entsoe::analysis::{Freshness, Interpolation, value_at};
+use crate::entsoe::cache::CacheStats;
+use crate::entsoe::window::TimeWindow;
+use crate::openmetrics::{self, Exposition};
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum MetricsMode {
+    /// Every forecast point with its timestamp
+    #[default]
+    Series,
+    /// The interpolated values in effect now, untimestamped
+    Current,
+}
+
+#[derive(Deserialize)]
+struct ForecastMetricsQuery {
+    /// `series` (default) or `current`
+    #[serde(default)]
+    mode: MetricsMode,
+    /// Number of hours to look ahead (default: 24), for countries not prefetched
+    hours: Option<u32>,
+}
+
+/// GET /api/v1/metrics/forecast/:country?mode=series|current
+/// The surplus forecast as OpenMetrics gauges, from the refresher's series when the
+/// country is prefetched
+async fn get_forecast_metrics(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<ForecastMetricsQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let hours = look_ahead_hours(query.hours, 24, state.config.max_query_hours)?;
+    let country_code = country_code.to_ascii_uppercase();
+    let now = state.now();
+
+    let prefetched = state
+        .refresher
+        .as_ref()
+        .and_then(|refresher| refresher.latest(&country_code));
+    let series = match prefetched {
+        Some(event) => event.series,
+        None => {
+            // Include the point currently in effect
+            let window = TimeWindow::next_hours_from(state.now(), hours as i64)
+                .extended_back(Duration::hours(1));
+            Arc::new(fetch_window_series(&state, zone.code, &window, Freshness::default()).await?)
+        }
+    };
+
+    let mut exposition = Exposition::new();
+    match query.mode {
+        MetricsMode::Series => {
+            openmetrics::series_samples(&mut exposition, &country_code, zone.code, &series)
+        }
+        MetricsMode::Current => {
+            if let Some(current) = value_at(&series.points, now, Interpolation::default()) {
+                openmetrics::current_samples(&mut exposition, &country_code, zone.code, &current);
+            }
+        }
+    }
+
+    Ok((
+        [(header::CONTENT_TYPE, openmetrics::CONTENT_TYPE)],
+        exposition.finish(),
+    )
+        .into_response())
+}
+
+/// GET /metrics
+/// Headline gauges of the prefetched countries as of their last background refresh
+async fn get_metrics(State(state): State<AppState>) -> Response {
+    let mut exposition = Exposition::new();
+    match &state.refresher {
+        Some(refresher) => refresher.gauges().samples(&mut exposition, state.now()),
+        // The gauges exist without samples when nothing is prefetched
+        None => openmetrics::SummaryGauges::new().samples(&mut exposition, state.now()),
+    }
+    (
+        [(header::CONTENT_TYPE, openmetrics::CONTENT_TYPE)],
+        exposition.finish(),
+    )
+        .into_response()
+}
+
+/// GET /health
+pub(super) async fn health() -> &'static str {
+    "OK"
+}
+
+#[derive(Serialize)]
+struct ReadinessResponse {
+    upstream: &'static str,
+    detail: Option<String>,
+    checked_at: String,
+    last_successful_fetch: Option<String>,
+    cache: Option<CacheStats>,
+}
+
+/// GET /health/ready
+/// Readiness probe checking that ENTSO-E is reachable and accepts the token
+async fn health_ready(State(state): State<AppState>) -> Response {
+    let check = state.readiness_check().await;
+
+    let (status, upstream, detail) = match check.health {
+        UpstreamHealth::Ok => (StatusCode::OK, "ok", None),
+        UpstreamHealth::Degraded(detail) => (StatusCode::OK, "degraded", Some(detail)),
+        UpstreamHealth::Down(detail) => (StatusCode::SERVICE_UNAVAILABLE, "down", Some(detail)),
+    };
+
+    let response = ReadinessResponse {
+        upstream,
+        detail,
+        checked_at: check.checked_at.to_rfc3339(),
+        last_successful_fetch: state
+            .entsoe_client
+            .as_ref()
+            .and_then(|client| client.last_successful_fetch())
+            .map(|t| t.to_rfc3339()),
+        cache: state
+            .entsoe_client
+            .as_ref()
+            .and_then(|client| client.cache_stats()),
+    };
+
+    (status, Json(response)).into_response()
+}
+
+pub(super) fn routes() -> [ApiRoute; 4] {
+    [
+        ApiRoute::get("/health", health).access(Access::Open),
+        ApiRoute::get("/health/ready", health_ready).access(Access::Open),
+        ApiRoute::get("/metrics", get_metrics)
+            .usage("headline gauges of EDUCK_PREFETCH_COUNTRIES")
+            .access(Access::Open),
+        ApiRoute::get("/api/v1/metrics/forecast/{country}", get_forecast_metrics)
+            .usage("?mode=series|current"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::entsoe::EntsoeClient;
+    use crate::entsoe::analysis::SurplusModel;
+    use crate::entsoe::testing::MockTransport;
+    use crate::refresher::Refresher;
+    use crate::server::tests::{body_bytes, get_request, test_state, upstream_answering};
+    use crate::server::{NO_API_KEY, router};
+    use axum::Router;
+    use tower::ServiceExt;
+
+    async fn readiness(app: Router) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(get_request("/health/ready")).await.unwrap();
+        let status = response.status();
+        let body = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_metadata_routes_work_without_api_key() {
+        let unconfigured = router(AppState::new(None, ServerConfig::default()));
+        let configured = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        for app in [&unconfigured, &configured] {
+            for uri in ["/health", "/api/v1/countries", "/api/v1/zones/DE"] {
+                let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+            }
+        }
+
+        let uri = "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z";
+        let response = configured.oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for uri in [uri, "/api/v1/balancing/DE/activations"] {
+            let response = unconfigured
+                .clone()
+                .oneshot(get_request(uri))
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "{}",
+                uri
+            );
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["error"], NO_API_KEY);
+        }
+
+        let (status, body) = readiness(unconfigured).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["detail"], NO_API_KEY);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_healthy_upstream() {
+        let (status, body) = readiness(router(test_state(upstream_answering(200)))).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["upstream"], "ok");
+        assert!(body["last_successful_fetch"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_readiness_fails_when_token_rejected() {
+        let (status, body) = readiness(router(test_state(upstream_answering(401)))).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["upstream"], "down");
+        assert!(body["last_successful_fetch"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_readiness_degraded_on_server_errors() {
+        let (status, body) = readiness(router(test_state(upstream_answering(503)))).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["upstream"], "degraded");
+    }
+
+    #[tokio::test]
+    async fn test_readiness_result_is_cached() {
+        let transport = upstream_answering(200);
+        let app = router(test_state(transport.clone()));
+
+        readiness(app.clone()).await;
+        readiness(app).await;
+
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_includes_cache_statistics() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = Arc::new(
+            EntsoeClient::with_transport("test-token", transport.clone())
+                .with_cache(std::time::Duration::from_secs(60)),
+        );
+
+        for _ in 0..2 {
+            client
+                .get_renewable_surplus_series(
+                    "10Y1001A1001A83F",
+                    "202406010000",
+                    "202406020000",
+                    &SurplusModel::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let app = router(AppState::new(Some(client), ServerConfig::default()));
+        let (_, body) = readiness(app).await;
+
+        assert_eq!(body["cache"]["entries"], 3);
+        assert_eq!(body["cache"]["hits"], 3);
+        assert_eq!(body["cache"]["misses"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_forecast_metrics_endpoint() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/metrics/forecast/DE?hours=6"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            openmetrics::CONTENT_TYPE
+        );
+        let text = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(text.starts_with("# TYPE educk_surplus_mw gauge\n"));
+        assert!(text.contains(
+            "educk_surplus_mw{country=\"DE\",zone=\"10Y1001A1001A83F\",source=\"day_ahead\"} "
+        ));
+        assert!(text.ends_with("# EOF\n"));
+
+        let response = app
+            .oneshot(get_request("/api/v1/metrics/forecast/DE?mode=current"))
+            .await
+            .unwrap();
+        let text = String::from_utf8(body_bytes(response).await).unwrap();
+        let samples: Vec<_> = text.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(samples.len(), 3);
+        // Current values carry no timestamp
+        assert!(samples.iter().all(|l| l.split(' ').count() == 2));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_after_refresh() {
+        let client = Arc::new(EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(MockTransport::forecasts()),
+        ));
+        let refresher = Arc::new(Refresher::new(
+            client.clone(),
+            vec!["DE".to_string(), "FR".to_string()],
+            std::time::Duration::from_secs(60),
+        ));
+        // A second refresh updates the values instead of adding samples
+        refresher.refresh_once().await;
+        refresher.refresh_once().await;
+
+        let state = AppState::new(Some(client), ServerConfig::default()).with_refresher(refresher);
+        let response = router(state)
+            .oneshot(get_request("/metrics"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = String::from_utf8(body_bytes(response).await).unwrap();
+
+        for name in [
+            "educk_current_surplus_mw",
+            "educk_next_24h_max_surplus_mw",
+            "educk_forecast_age_seconds",
+        ] {
+            assert_eq!(text.matches(&format!("# TYPE {} gauge", name)).count(), 1);
+            for country in ["DE", "FR"] {
+                let prefix = format!("{}{{country=\"{}\"}} ", name, country);
+                assert_eq!(text.lines().filter(|l| l.starts_with(&prefix)).count(), 1);
+            }
+        }
+        // The mock forecasts were issued on 2024-06-01
+        let age: f64 = text
+            .lines()
+            .find_map(|l| l.strip_prefix("educk_forecast_age_seconds{country=\"DE\"} "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(age > 86_400.0);
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,590 @@
+//! Stored surplus history: past series, hourly profiles and forecast revisions
+
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use super::dto::{ApiResponse, DocumentMetaResponse, SeriesPoint, conditional_json};
+use super::error::{ApiError, ValidQuery};
+use super::query::{fetch_window_series, query_window, requested_local_zone, requested_zone};
+use super::routes::ApiRoute;
+use crate::entsoe::analysis::{
+    DayKind, Freshness, HourStats, RenewableSurplus, hourly_profile, hourly_profile_on,
+};
+use crate::entsoe::window::TimeWindow;
+use crate::storage::{revision_drift, surplus_history};
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    /// Start of the interval (RFC3339)
+    start: Option<String>,
+    /// End of the interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+}
+
+#[derive(Serialize)]
+struct HistoryResponse {
+    country_code: String,
+    period_start: String,
+    period_end: String,
+    /// `history` when served from the local database, `live` when fetched from ENTSO-E
+    source: &'static str,
+    points: Vec<SeriesPoint>,
+}
+
+/// Surplus over an explicit window from the local history when the window is past and
+/// stored, otherwise fetched; returns `history` or `live` for where it came from
+pub(super) async fn stored_or_live_surplus(
+    state: &AppState,
+    zone_code: &str,
+    window: &TimeWindow,
+) -> Result<(&'static str, Vec<RenewableSurplus>), ApiError> {
+    let stored = match &state.storage {
+        Some(storage) if window.end <= state.now() => {
+            surplus_history(storage.as_ref(), zone_code, window.start, window.end)
+                .await
+                .unwrap_or_else(|e| {
+                    eprintln!("Reading the surplus history failed: {}", e);
+                    Vec::new()
+                })
+        }
+        _ => Vec::new(),
+    };
+    if !stored.is_empty() {
+        return Ok(("history", stored));
+    }
+
+    let series = fetch_window_series(state, zone_code, window, Freshness::DayAhead).await?;
+    Ok(("live", series.points))
+}
+
+/// GET /api/v1/history/:country/surplus?start=..&end=..
+/// Surplus over a past interval as last forecast, from the local history when it has
+/// the interval and from ENTSO-E otherwise
+async fn get_surplus_history(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<HistoryQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let Some(start) = query.start.as_deref() else {
+        return Err(ApiError::bad_request("`start` is required"));
+    };
+    let window = query_window(
+        None,
+        Some(start),
+        query.end.as_deref(),
+        None,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let (source, points) = stored_or_live_surplus(&state, zone.code, &window).await?;
+
+    if points.is_empty() {
+        return Err(ApiError::no_data("No data available"));
+    }
+
+    let response = HistoryResponse {
+        country_code,
+        period_start: window.start.to_rfc3339(),
+        period_end: window.end.to_rfc3339(),
+        source,
+        points: points.iter().map(Into::into).collect(),
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+/// Longest history a profile is taken over
+const MAX_PROFILE_DAYS: u32 = 365;
+
+#[derive(Deserialize)]
+struct ProfileQuery {
+    /// Number of past days to take the profile over (default: 30)
+    days: Option<u32>,
+    /// `weekday` for separate weekday and weekend profiles
+    split: Option<ProfileSplit>,
+    /// IANA time zone of the hours, the zone's own by default
+    tz: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ProfileSplit {
+    Weekday,
+}
+
+#[derive(Serialize)]
+struct HourStatsResponse {
+    hour: u32,
+    samples: usize,
+    mean_mw: Option<f64>,
+    p25_mw: Option<f64>,
+    p75_mw: Option<f64>,
+}
+
+impl From<&HourStats> for HourStatsResponse {
+    fn from(stats: &HourStats) -> Self {
+        Self {
+            hour: stats.hour,
+            samples: stats.samples,
+            mean_mw: stats.mean,
+            p25_mw: stats.p25,
+            p75_mw: stats.p75,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DayProfileResponse {
+    /// `all`, `weekday` or `weekend`
+    days: &'static str,
+    hours: Vec<HourStatsResponse>,
+}
+
+impl DayProfileResponse {
+    fn new(days: &'static str, profile: &[HourStats; 24]) -> Self {
+        Self {
+            days,
+            hours: profile.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProfileResponse {
+    country_code: String,
+    timezone: &'static str,
+    period_start: String,
+    period_end: String,
+    days_requested: u32,
+    /// Local days with stored points in the period
+    days_available: usize,
+    profiles: Vec<DayProfileResponse>,
+}
+
+/// GET /api/v1/renewable-surplus/:country/profile?days=30&split=weekday
+/// Typical surplus by local hour of the day over the stored history of the past days
+async fn get_surplus_profile(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<ProfileQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let days = query.days.unwrap_or(30);
+    if days == 0 || days > MAX_PROFILE_DAYS {
+        return Err(ApiError::bad_request(format!(
+            "`days` must be between 1 and {}",
+            MAX_PROFILE_DAYS
+        )));
+    }
+    let local = requested_local_zone(query.tz.as_deref(), zone)?;
+    let Some(storage) = &state.storage else {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Surplus profiles need the history database (EDUCK_HISTORY_DB)",
+        ));
+    };
+
+    let end = state.now();
+    let start = end - Duration::days(days as i64);
+    let history = surplus_history(storage.as_ref(), zone.code, start, end)
+        .await
+        .map_err(|e| {
+            eprintln!("Reading the surplus history failed: {}", e);
+            ApiError::from(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    if history.is_empty() {
+        return Err(ApiError::no_data(format!(
+            "No surplus of {} stored in the last {} days (0 of {} days available)",
+            zone.code, days, days
+        )));
+    }
+    let days_available = history
+        .iter()
+        .map(|point| local.to_local(point.timestamp).date_naive())
+        .collect::<std::collections::BTreeSet<_>>()
+        .len();
+
+    let profiles = match query.split {
+        None => vec![DayProfileResponse::new(
+            "all",
+            &hourly_profile(&history, &local),
+        )],
+        Some(ProfileSplit::Weekday) => vec![
+            DayProfileResponse::new(
+                "weekday",
+                &hourly_profile_on(&history, &local, DayKind::Weekday),
+            ),
+            DayProfileResponse::new(
+                "weekend",
+                &hourly_profile_on(&history, &local, DayKind::Weekend),
+            ),
+        ],
+    };
+    let response = ProfileResponse {
+        country_code,
+        timezone: local.name,
+        period_start: start.to_rfc3339(),
+        period_end: end.to_rfc3339(),
+        days_requested: days,
+        days_available,
+        profiles,
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ForecastDriftQuery {
+    /// Delivery day (`YYYY-MM-DD`, UTC)
+    date: String,
+}
+
+#[derive(Serialize)]
+struct DriftPointResponse {
+    timestamp: String,
+    first_mw: f64,
+    latest_mw: f64,
+    delta_mw: f64,
+}
+
+#[derive(Serialize)]
+struct ForecastDriftResponse {
+    country_code: String,
+    date: String,
+    first_revision: DocumentMetaResponse,
+    latest_revision: DocumentMetaResponse,
+    revisions: usize,
+    rmse_mw: f64,
+    points: Vec<DriftPointResponse>,
+}
+
+/// GET /api/v1/forecast-drift/:country?date=YYYY-MM-DD
+/// How much the stored wind/solar forecast of a delivery day changed between its first
+/// and latest revision
+async fn get_forecast_drift(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<ForecastDriftQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let date = chrono::NaiveDate::parse_from_str(&query.date, "%Y-%m-%d")
+        .map_err(|_| ApiError::bad_request("Invalid `date`: expected a day like 2024-06-01"))?;
+    let Some(storage) = &state.storage else {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Forecast drift needs the history database (EDUCK_HISTORY_DB)",
+        ));
+    };
+
+    let drift = revision_drift(storage.as_ref(), zone.code, date)
+        .await
+        .map_err(|e| {
+            eprintln!("Reading the forecast history failed: {}", e);
+            ApiError::from(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    let Some(drift) = drift else {
+        return Err(ApiError::no_data(format!(
+            "No revisions: the forecast for {} was stored only once",
+            date
+        )));
+    };
+
+    let response = ForecastDriftResponse {
+        country_code,
+        date: date.to_string(),
+        first_revision: drift.first_revision.into(),
+        latest_revision: drift.latest_revision.into(),
+        revisions: drift.revisions,
+        rmse_mw: drift.rmse,
+        points: drift
+            .points
+            .iter()
+            .map(|p| DriftPointResponse {
+                timestamp: p.timestamp.to_rfc3339(),
+                first_mw: p.first,
+                latest_mw: p.latest,
+                delta_mw: p.delta,
+            })
+            .collect(),
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+pub(super) fn routes() -> [ApiRoute; 3] {
+    [
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/profile",
+            get_surplus_profile,
+        )
+        .usage("?days=30&split=weekday (needs EDUCK_HISTORY_DB)"),
+        ApiRoute::get("/api/v1/history/{country}/surplus", get_surplus_history)
+            .usage("?start=RFC3339&end=RFC3339"),
+        ApiRoute::get("/api/v1/forecast-drift/{country}", get_forecast_drift)
+            .usage("?date=2024-06-01"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_history_requires_start_and_falls_back_to_live() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let app = router(test_state(transport.clone()));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/history/DE/surplus"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/history/DE/surplus?start=2024-06-01T00:00:00Z&end=2024-06-01T06:00:00Z",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"]["source"], "live");
+        assert!(!transport.requests().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_history_served_from_storage() {
+        use crate::entsoe::analysis::{DocumentMeta, SurplusSeries};
+        use crate::entsoe::testing::sample_surplus;
+        use crate::storage::sqlite::SqliteStorage;
+        use crate::storage::{Storage, documents_of};
+        use chrono::{TimeZone, Utc};
+
+        let storage = Arc::new(SqliteStorage::open_in_memory().unwrap());
+        let meta = DocumentMeta {
+            created_date_time: Utc.with_ymd_and_hms(2024, 5, 31, 12, 0, 0).unwrap(),
+            revision_number: 1,
+        };
+        let series = SurplusSeries {
+            points: sample_surplus(),
+            generation_doc_meta: Some(meta),
+            load_doc_meta: Some(meta),
+            ..SurplusSeries::default()
+        };
+        for document in documents_of("10Y1001A1001A83F", &series, Utc::now()) {
+            storage.store(&document).await.unwrap();
+        }
+        let transport = Arc::new(MockTransport::forecasts());
+        let app = router(test_state(transport.clone()).with_storage(storage));
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/history/DE/surplus?start=2024-06-01T00:00:00Z&end=2024-06-01T06:00:00Z",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert_eq!(body["data"]["source"], "history");
+        assert_eq!(body["data"]["points"].as_array().unwrap().len(), 6);
+        assert_eq!(body["data"]["points"][0]["surplus_mw"], -25_000.0);
+        assert!(transport.requests().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_surplus_profile_from_storage() {
+        use crate::clock::FixedClock;
+        use crate::entsoe::analysis::{DocumentMeta, SurplusSeries};
+        use crate::server::error::NO_DATA;
+        use crate::storage::sqlite::SqliteStorage;
+        use crate::storage::{Storage, documents_of};
+        use chrono::{TimeZone, Utc};
+
+        let storage = Arc::new(SqliteStorage::open_in_memory().unwrap());
+        let meta = DocumentMeta {
+            created_date_time: Utc.with_ymd_and_hms(2024, 5, 31, 12, 0, 0).unwrap(),
+            revision_number: 1,
+        };
+        // Friday 31 May to Sunday 2 June, the weekend one 10 GW higher
+        let first = Utc.with_ymd_and_hms(2024, 5, 31, 0, 0, 0).unwrap();
+        let points: Vec<RenewableSurplus> = (0..3 * 24)
+            .map(|i| {
+                let generation =
+                    if i < 24 { 20_000.0 } else { 30_000.0 } + 1_000.0 * (i % 24) as f64;
+                RenewableSurplus {
+                    timestamp: first + Duration::hours(i),
+                    generation,
+                    load: 40_000.0,
+                    surplus: generation - 40_000.0,
+                    total_generation: None,
+                }
+            })
+            .collect();
+        let series = SurplusSeries {
+            points,
+            generation_doc_meta: Some(meta),
+            load_doc_meta: Some(meta),
+            ..SurplusSeries::default()
+        };
+        for document in documents_of("10Y1001A1001A83F", &series, first) {
+            storage.store(&document).await.unwrap();
+        }
+        let now = Utc.with_ymd_and_hms(2024, 6, 4, 0, 0, 0).unwrap();
+        let app = router(
+            test_state(Arc::new(MockTransport::forecasts()))
+                .with_storage(storage)
+                .with_clock(Arc::new(FixedClock(now))),
+        );
+
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/profile?days=7&tz=UTC",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+        assert_eq!(data["days_requested"], 7);
+        assert_eq!(data["days_available"], 3);
+        let profiles = data["profiles"].as_array().unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0]["days"], "all");
+        let noon = &profiles[0]["hours"][12];
+        assert_eq!(noon["hour"], 12);
+        assert_eq!(noon["samples"], 3);
+        // -8, 2 and 2 GW
+        assert_eq!(noon["p25_mw"], -3_000.0);
+        assert_eq!(noon["p75_mw"], 2_000.0);
+
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/profile?days=7&tz=UTC&split=weekday",
+            ))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let profiles = body["data"]["profiles"].as_array().unwrap();
+        assert_eq!(profiles[0]["days"], "weekday");
+        assert_eq!(profiles[0]["hours"][12]["mean_mw"], -8_000.0);
+        assert_eq!(profiles[1]["days"], "weekend");
+        assert_eq!(profiles[1]["hours"][12]["mean_mw"], 2_000.0);
+        assert_eq!(profiles[1]["hours"][12]["samples"], 2);
+
+        // Nothing stored in the day before now
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/profile?days=1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["code"], NO_DATA);
+        assert!(
+            body["error"].as_str().unwrap().contains("0 of 1 days"),
+            "{}",
+            body["error"]
+        );
+
+        let response = app
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/profile?days=0"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_forecast_drift_endpoint() {
+        use crate::entsoe::analysis::DocumentMeta;
+        use crate::storage::sqlite::SqliteStorage;
+        use crate::storage::{GENERATION, Storage, StoredDocument};
+        use chrono::{TimeZone, Utc};
+
+        let storage = Arc::new(SqliteStorage::open_in_memory().unwrap());
+        let midnight = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let document = |revision_number, values: &[f64]| StoredDocument {
+            zone: "10Y1001A1001A83F".to_string(),
+            doc_type: GENERATION.to_string(),
+            psr_type: String::new(),
+            meta: DocumentMeta {
+                created_date_time: Utc.with_ymd_and_hms(2024, 5, 31, 18, 0, 0).unwrap(),
+                revision_number,
+            },
+            fetched_at: midnight,
+            points: values
+                .iter()
+                .enumerate()
+                .map(|(hour, &value)| (midnight + Duration::hours(hour as i64), value))
+                .collect(),
+        };
+        storage.store(&document(1, &[100.0, 100.0])).await.unwrap();
+        let app =
+            router(test_state(Arc::new(MockTransport::forecasts())).with_storage(storage.clone()));
+
+        let uri = "/api/v1/forecast-drift/DE?date=2024-06-01";
+        let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["code"], crate::server::error::NO_DATA);
+        assert!(body["error"].as_str().unwrap().starts_with("No revisions"));
+
+        storage.store(&document(2, &[120.0, 60.0])).await.unwrap();
+        let response = app.oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert_eq!(body["data"]["revisions"], 2);
+        assert_eq!(body["data"]["points"][1]["delta_mw"], -40.0);
+        assert_eq!(body["data"]["latest_revision"]["revision_number"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_forecast_drift_needs_history() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        for uri in [
+            "/api/v1/forecast-drift/DE?date=2024-06-01",
+            "/api/v1/renewable-surplus/DE/diff",
+        ] {
+            let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "{}",
+                uri
+            );
+        }
+    }
+}
@@ -0,0 +1,1286 @@
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use axum::{Router, middleware};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::CorsLayer;
+use tracing::Instrument;
+
+use crate::alerts::{AlertConfig, Alerts};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{JsonCase, ServerConfig};
+use crate::currency::{CurrencyConverter, StaticRates};
+use crate::entsoe::areas::get_primary_zone;
+use crate::entsoe::{EntsoeClient, UpstreamHealth};
+use crate::refresher::Refresher;
+use crate::request_id;
+use crate::snapshot::{OfflineTransport, Snapshot};
+use crate::storage::Storage;
+
+mod alerts;
+mod battery;
+mod charging;
+mod compare;
+mod control_areas;
+mod dashboard;
+mod dto;
+mod error;
+mod events;
+mod grafana;
+mod grid;
+mod ha;
+mod health;
+mod history;
+mod overview;
+mod plot;
+mod query;
+mod raw;
+mod routes;
+mod schema;
+mod sg_ready;
+mod surplus;
+mod websocket;
+mod zones;
+
+use dto::ApiResponse;
+use error::{ApiError, retry_after_secs};
+use health::health;
+use routes::Access;
+
+/// How long a readiness probe result is reused before asking ENTSO-E again
+const READINESS_CHECK_TTL: std::time::Duration = std::time::Duration::from_secs(120);
+
+#[derive(Clone)]
+struct AppState {
+    /// `None` without an API key, when only the metadata routes can answer
+    entsoe_client: Option<Arc<EntsoeClient>>,
+    config: Arc<ServerConfig>,
+    rate_limiter: Option<Arc<ClientRateLimiter>>,
+    readiness: Arc<tokio::sync::Mutex<Option<ReadinessCheck>>>,
+    refresher: Option<Arc<Refresher>>,
+    storage: Option<Arc<dyn Storage>>,
+    /// Alert subscriptions whose deliveries are served, see [`crate::alerts`]
+    alerts: Option<Arc<Alerts>>,
+    /// Converts prices of comparisons across currencies, refused without one
+    currency_converter: Option<Arc<dyn CurrencyConverter>>,
+    /// Whether upstream requests are answered from a snapshot
+    offline: bool,
+    /// Instant the relative windows start from
+    clock: Arc<dyn Clock>,
+}
+
+/// Cached outcome of the last upstream readiness probe
+#[derive(Clone)]
+struct ReadinessCheck {
+    performed: Instant,
+    checked_at: DateTime<Utc>,
+    health: UpstreamHealth,
+}
+
+/// Answer of data routes while no API key is configured
+const NO_API_KEY: &str =
+    "No ENTSO-E API key is configured, set ENTSOE_API_KEY to enable the data endpoints";
+
+impl AppState {
+    fn new(entsoe_client: Option<Arc<EntsoeClient>>, config: ServerConfig) -> Self {
+        let rate_limiter = config.rate_limit_per_minute.map(|limit| {
+            Arc::new(ClientRateLimiter::new(
+                limit,
+                std::time::Duration::from_secs(60),
+            ))
+        });
+
+        Self {
+            entsoe_client,
+            config: Arc::new(config),
+            rate_limiter,
+            readiness: Arc::new(tokio::sync::Mutex::new(None)),
+            refresher: None,
+            storage: None,
+            alerts: None,
+            currency_converter: None,
+            offline: false,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Serve prefetched countries from the refresher's latest series
+    fn with_refresher(mut self, refresher: Arc<Refresher>) -> Self {
+        self.refresher = Some(refresher);
+        self
+    }
+
+    /// Serve past intervals of the history endpoint from `storage`
+    fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Serve the deliveries of `alerts`
+    fn with_alerts(mut self, alerts: Arc<Alerts>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Convert prices with `converter` when compared zones quote in different currencies
+    fn with_currency_converter(mut self, converter: Arc<dyn CurrencyConverter>) -> Self {
+        self.currency_converter = Some(converter);
+        self
+    }
+
+    /// Mark responses as served from a snapshot, see [`crate::snapshot`]
+    fn with_offline_mode(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
+    /// Read the current time from `clock` instead of the system, to freeze it in tests
+    #[cfg(test)]
+    fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// The ENTSO-E client, or a 503 for data routes when no API key is configured
+    fn client(&self) -> Result<&Arc<EntsoeClient>, ApiError> {
+        self.entsoe_client
+            .as_ref()
+            .ok_or_else(|| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, NO_API_KEY))
+    }
+
+    /// Probe the upstream, reusing a recent result. Concurrent callers share one probe.
+    async fn readiness_check(&self) -> ReadinessCheck {
+        let mut cached = self.readiness.lock().await;
+
+        if let Some(check) = cached.as_ref()
+            && check.performed.elapsed() < READINESS_CHECK_TTL
+        {
+            return check.clone();
+        }
+
+        let check = ReadinessCheck {
+            health: match &self.entsoe_client {
+                Some(client) => client.check_upstream().await,
+                None => UpstreamHealth::Down(NO_API_KEY.to_string()),
+            },
+            performed: Instant::now(),
+            checked_at: self.now(),
+        };
+        *cached = Some(check.clone());
+        check
+    }
+}
+
+/// Request counter of one client within the current window
+struct ClientWindow {
+    started: Instant,
+    requests: u32,
+}
+
+/// Fixed-window request limiter keyed by client IP
+struct ClientRateLimiter {
+    limit: u32,
+    window: std::time::Duration,
+    clients: Mutex<HashMap<IpAddr, ClientWindow>>,
+    last_cleanup: Mutex<Instant>,
+}
+
+impl ClientRateLimiter {
+    fn new(limit: u32, window: std::time::Duration) -> Self {
+        Self {
+            limit,
+            window,
+            clients: Mutex::new(HashMap::new()),
+            last_cleanup: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Count a request from `ip`, returning the time until the client may retry when over the limit
+    fn check(&self, ip: IpAddr, now: Instant) -> Result<(), std::time::Duration> {
+        self.cleanup(now);
+
+        let mut clients = self.clients.lock().unwrap();
+        let client = clients.entry(ip).or_insert(ClientWindow {
+            started: now,
+            requests: 0,
+        });
+
+        if now.duration_since(client.started) >= self.window {
+            client.started = now;
+            client.requests = 0;
+        }
+
+        if client.requests >= self.limit {
+            return Err(self.window - now.duration_since(client.started));
+        }
+
+        client.requests += 1;
+        Ok(())
+    }
+
+    /// Forget clients whose window has expired, at most once per window
+    fn cleanup(&self, now: Instant) {
+        let mut last_cleanup = self.last_cleanup.lock().unwrap();
+        if now.duration_since(*last_cleanup) < self.window {
+            return;
+        }
+        *last_cleanup = now;
+
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|_, client| now.duration_since(client.started) < self.window);
+    }
+
+    #[cfg(test)]
+    fn tracked_clients(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+}
+
+/// Compare two tokens in time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Check a token against every configured token without short-circuiting
+fn is_valid_token(configured: &[String], provided: &str) -> bool {
+    configured.iter().fold(false, |valid, token| {
+        constant_time_eq(token.as_bytes(), provided.as_bytes()) | valid
+    })
+}
+
+/// Determine the client address, honouring `X-Forwarded-For` only behind a trusted proxy
+fn client_ip(request: &Request, trusted_proxy: bool) -> Option<IpAddr> {
+    if trusted_proxy {
+        // The right-most entry is the one appended by our own proxy
+        let forwarded = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// Throttle clients exceeding the configured requests per minute
+async fn limit_request_rate(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = &state.rate_limiter else {
+        return next.run(request).await;
+    };
+    let Some(ip) = client_ip(&request, state.config.trusted_proxy) else {
+        return next.run(request).await;
+    };
+
+    match limiter.check(ip, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            let retry_after_secs = retry_after_secs(retry_after);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                Json(ApiResponse::<()>::error(format!(
+                    "Rate limit exceeded, retry in {} seconds",
+                    retry_after_secs
+                ))),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Require `Authorization: Bearer <token>` when API tokens are configured
+async fn require_api_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.config.api_tokens.is_empty() {
+        return next.run(request).await;
+    }
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+
+    match provided {
+        Some(token) if is_valid_token(&state.config.api_tokens, token) => next.run(request).await,
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(ApiResponse::<()>::error(
+                "Missing or invalid API token".to_string(),
+            )),
+        )
+            .into_response(),
+    }
+}
+
+/// Tells clients of offline mode that data may be stale
+async fn mark_offline(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert("x-educk-offline", header::HeaderValue::from_static("true"));
+    response
+}
+
+/// Answer under the `X-Request-Id` of the client, or a new one, and handle the request in
+/// a span naming it, so an error body a user pastes leads to the log lines of its request
+async fn propagate_request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(request_id::HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| request_id::is_valid(id))
+        .map(str::to_string)
+        .unwrap_or_else(request_id::generate);
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = request.uri().path(),
+    );
+    let mut response = request_id::scope(id.clone(), next.run(request).instrument(span)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(request_id::HEADER, value);
+    }
+    response
+}
+
+fn router(state: AppState) -> Router {
+    let compression = state.config.compression;
+    let json_case = state.config.json_case;
+    let offline = state.offline;
+
+    let mut open = Router::new();
+    // Everything under /api/v1 may be protected by API tokens, /health stays open
+    let mut api = Router::new();
+    for route in routes::table() {
+        match route.access {
+            Access::Open => open = open.route(route.path, route.handler),
+            Access::Api => api = api.route(route.path, route.handler),
+            // Raw documents are for debugging and stay unrouted unless enabled
+            Access::Debug if state.config.debug_endpoints => {
+                api = api.route(route.path, route.handler)
+            }
+            Access::Debug => {}
+        }
+    }
+    let api = api
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_api_token,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            limit_request_rate,
+        ));
+    let api = if offline {
+        api.layer(middleware::from_fn(mark_offline))
+    } else {
+        api
+    };
+
+    let router = open
+        .merge(api)
+        // Also around the fallback, so unknown versions get an answer in the envelope
+        .layer(middleware::from_fn(schema::negotiate_version));
+    // Around the version negotiation, so its refusals are renamed too
+    let router = match json_case {
+        JsonCase::Snake => router,
+        JsonCase::Camel => router.layer(middleware::from_fn(dto::camel_case_keys)),
+    };
+    let router = router
+        .layer(CorsLayer::permissive())
+        // Outermost, so every answer, refusals of the layers above included, has an id
+        .layer(middleware::from_fn(propagate_request_id))
+        .with_state(state);
+
+    if compression {
+        router.layer(CompressionLayer::new().gzip(true).deflate(true))
+    } else {
+        router
+    }
+}
+
+/// Open the history database configured by `EDUCK_HISTORY_DB`, migrating it if needed
+#[cfg(feature = "sqlite")]
+fn open_history(config: &ServerConfig) -> anyhow::Result<Option<Arc<dyn Storage>>> {
+    let Some(path) = &config.history_db else {
+        return Ok(None);
+    };
+    let storage = crate::storage::sqlite::SqliteStorage::open(path)
+        .map_err(|e| anyhow::anyhow!("Opening history database {}: {}", path.display(), e))?;
+    Ok(Some(Arc::new(storage)))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_history(config: &ServerConfig) -> anyhow::Result<Option<Arc<dyn Storage>>> {
+    if config.history_db.is_some() {
+        eprintln!("EDUCK_HISTORY_DB is set but educk was built without the `sqlite` feature");
+    }
+    Ok(None)
+}
+
+/// The upstream client: from the snapshot at `offline`, else authenticated with
+/// `ENTSOE_API_KEY`, `None` if that is not set
+async fn startup_client(
+    offline: Option<&std::path::Path>,
+    config: &ServerConfig,
+) -> anyhow::Result<Option<EntsoeClient>> {
+    let client = match offline {
+        Some(path) => {
+            let snapshot = Snapshot::load(path)?;
+            println!(
+                "📦 Offline mode: serving {} from {} captured at {}",
+                snapshot.countries.join(", "),
+                path.display(),
+                snapshot.created_at
+            );
+            EntsoeClient::with_transport("offline", Arc::new(OfflineTransport::new(&snapshot)))
+        }
+        None => {
+            let Some(api_key) = std::env::var("ENTSOE_API_KEY")
+                .ok()
+                .filter(|key| !key.trim().is_empty())
+            else {
+                eprintln!("⚠️  ENTSOE_API_KEY is not set: only the metadata and health endpoints");
+                eprintln!(
+                    "⚠️  will answer, data endpoints respond with 503 until a key is configured"
+                );
+                return Ok(None);
+            };
+            let client = EntsoeClient::new(api_key);
+            if config.validate_api_key {
+                client.validate_key().await?;
+                println!("🔑 API key accepted");
+            }
+            client
+        }
+    };
+
+    if config.document_cache_ttl.is_zero() {
+        if config.cache_dir.is_some() {
+            eprintln!("EDUCK_CACHE_DIR is set but EDUCK_DOCUMENT_CACHE_TTL=0 disables the cache");
+        }
+        return Ok(Some(client));
+    }
+    let client = client.with_cache(config.document_cache_ttl);
+    // Snapshot documents must not end up in the cache of online runs
+    match &config.cache_dir {
+        Some(dir) if offline.is_none() => {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                anyhow::anyhow!("Creating cache directory {}: {}", dir.display(), e)
+            })?;
+            Ok(Some(client.with_disk_cache(dir.clone())))
+        }
+        _ => Ok(Some(client)),
+    }
+}
+
+/// Read the documents of the prefetched countries back from `EDUCK_CACHE_DIR`, so the
+/// first requests after a restart do not all go upstream. Waits at most
+/// `EDUCK_CACHE_HYDRATION_BUDGET`; documents read later are served once they are in.
+async fn hydrate_cache(client: &Arc<EntsoeClient>, config: &ServerConfig) {
+    let zones: Vec<&'static str> = config
+        .prefetch_countries
+        .iter()
+        .filter_map(|country_code| get_primary_zone(country_code))
+        .map(|zone| zone.code)
+        .collect();
+    let hydrating = tokio::task::spawn_blocking({
+        let client = client.clone();
+        move || client.hydrate_cache(&zones)
+    });
+    match tokio::time::timeout(config.cache_hydration_budget, hydrating).await {
+        Ok(Ok(Ok(documents))) => println!("💾 {} cached documents read back", documents),
+        Ok(Ok(Err(e))) => eprintln!("Reading the disk cache failed: {}", e),
+        Ok(Err(e)) => eprintln!("Reading the disk cache failed: {}", e),
+        Err(_) => eprintln!(
+            "Reading the disk cache takes longer than {} s, serving meanwhile",
+            config.cache_hydration_budget.as_secs()
+        ),
+    }
+}
+
+/// Log to stdout and, with the `opentelemetry` feature and an `OTEL_*` endpoint, export
+/// the spans, returning the exporter task
+fn init_tracing(
+    #[cfg_attr(not(feature = "opentelemetry"), allow(unused_variables))]
+    shutdown: tokio::sync::watch::Receiver<bool>,
+) -> anyhow::Result<Option<tokio::task::JoinHandle<()>>> {
+    #[cfg(feature = "opentelemetry")]
+    if let Some(otlp) = crate::otlp::OtlpConfig::from_env()? {
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::util::SubscriberInitExt;
+
+        println!("📡 Exporting traces to {}", otlp.endpoint);
+        let (layer, exporter) = crate::otlp::layer(otlp, shutdown)?;
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::filter::LevelFilter::INFO)
+            .with(tracing_subscriber::fmt::layer())
+            .with(layer)
+            .init();
+        return Ok(Some(exporter));
+    }
+
+    tracing_subscriber::fmt::init();
+    #[cfg(not(feature = "opentelemetry"))]
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some()
+        || std::env::var_os("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some()
+    {
+        eprintln!(
+            "An OTLP endpoint is set but educk was built without the `opentelemetry` feature"
+        );
+    }
+    Ok(None)
+}
+
+/// The API answering through `client`, without the refresher, history and offline mode
+/// [`start_server`] may add, for embedding it into another server or driving it in tests.
+///
+/// The per-client rate limit tells clients apart by their address, so serve the router
+/// with `into_make_service_with_connect_info::<SocketAddr>()`. Served any other way,
+/// requests carry no address and are not rate limited at all, unless a trusted proxy
+/// names the client in `X-Forwarded-For`.
+pub fn app(client: Option<Arc<EntsoeClient>>, config: ServerConfig) -> Router {
+    router(AppState::new(client, config))
+}
+
+/// Run the server, answering upstream requests from the snapshot at `offline` if given
+pub async fn start_server(offline: Option<&std::path::Path>) -> anyhow::Result<()> {
+    let (stop, shutdown) = tokio::sync::watch::channel(false);
+    let mut background = Vec::new();
+    background.extend(init_tracing(shutdown.clone())?);
+
+    let config = ServerConfig::from_env()?;
+
+    let client = startup_client(offline, &config).await?.map(Arc::new);
+    let mut prefetched = None;
+    let storage = open_history(&config)?;
+    let alerts = match &config.alerts {
+        Some(path) => Some(Arc::new(Alerts::from_config(&AlertConfig::load(path)?)?)),
+        None => None,
+    };
+    for alert in alerts.iter().flat_map(|alerts| alerts.iter()) {
+        if !config.prefetch_countries.contains(&alert.country_code) {
+            eprintln!(
+                "Alert {:?} never fires: {} is not in EDUCK_PREFETCH_COUNTRIES",
+                alert.id, alert.country_code
+            );
+        }
+    }
+
+    if client.is_none() && !config.prefetch_countries.is_empty() {
+        eprintln!("EDUCK_PREFETCH_COUNTRIES is set but there is no API key to fetch with");
+    } else if let Some(client) = client
+        .as_ref()
+        .filter(|_| !config.prefetch_countries.is_empty())
+    {
+        if config.cache_dir.is_some() {
+            hydrate_cache(client, &config).await;
+        }
+        let refresher = Arc::new(Refresher::new(
+            client.clone(),
+            config.prefetch_countries.clone(),
+            config.refresh_interval,
+        ));
+
+        #[cfg(feature = "mqtt")]
+        if let Some(mqtt) = &config.mqtt {
+            let (sink, connection) = crate::mqtt::connect(mqtt, shutdown.clone());
+            background.push(connection);
+            background.push(tokio::spawn(crate::mqtt::publish_refreshes(
+                Arc::new(sink),
+                mqtt.topic_prefix.clone(),
+                refresher.subscribe(),
+                shutdown.clone(),
+            )));
+        }
+
+        if let Some(storage) = &storage {
+            background.push(tokio::spawn(crate::storage::record_refreshes(
+                storage.clone(),
+                refresher.subscribe(),
+                shutdown.clone(),
+            )));
+        }
+
+        if let Some(alerts) = &alerts {
+            background.push(tokio::spawn(crate::alerts::check_refreshes(
+                alerts.clone(),
+                refresher.subscribe(),
+                shutdown.clone(),
+            )));
+        }
+
+        background.push(tokio::spawn(refresher.clone().run(shutdown.clone())));
+        prefetched = Some(refresher);
+    } else if config.mqtt.is_some() {
+        eprintln!(
+            "EDUCK_MQTT_URL is set but EDUCK_PREFETCH_COUNTRIES is empty, nothing to publish"
+        );
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    if config.mqtt.is_some() {
+        eprintln!("EDUCK_MQTT_URL is set but educk was built without the `mqtt` feature");
+    }
+
+    let mut state = AppState::new(client, config);
+    if let Some(refresher) = prefetched {
+        state = state.with_refresher(refresher);
+    }
+    if let Some(storage) = storage {
+        state = state.with_storage(storage);
+    }
+    if let Some(alerts) = alerts {
+        state = state.with_alerts(alerts);
+    }
+    if let Some(path) = state.config.exchange_rates.clone() {
+        state = state.with_currency_converter(Arc::new(StaticRates::load(&path)?));
+    }
+    if offline.is_some() {
+        state = state.with_offline_mode();
+    }
+
+    let debug_endpoints = state.config.debug_endpoints;
+    let app = router(state);
+
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:3044").await?;
+    println!("🚀 Server running on http://0.0.0.0:3044");
+    println!("\nAvailable endpoints:");
+    for line in routes::listing(&routes::table(), debug_endpoints) {
+        println!("{}", line);
+    }
+    println!("\nExamples:");
+    println!("  curl http://localhost:3044/api/v1/renewable-surplus/DE/night");
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+    .await?;
+
+    // Stop the refresher and let the MQTT connection disconnect cleanly
+    let _ = stop.send(true);
+    for task in background {
+        let _ = task.await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::entsoe::testing::{
+        MockOutage, MockTransport, actual_generation_document, balancing_document, ok,
+        outages_response, publication_document, query_param,
+    };
+    use crate::entsoe::{EntsoeError, Transport, TransportResponse};
+    use axum::body::Body;
+    use axum::http::Request;
+    use chrono::{Duration, NaiveDateTime, TimeZone};
+    use std::collections::HashSet;
+    use tower::ServiceExt;
+
+    pub(super) fn test_state(transport: Arc<MockTransport>) -> AppState {
+        test_state_with_config(transport, ServerConfig::default())
+    }
+
+    pub(super) fn test_state_with_config(
+        transport: Arc<MockTransport>,
+        config: ServerConfig,
+    ) -> AppState {
+        AppState::new(
+            Some(Arc::new(EntsoeClient::with_transport(
+                "test-token",
+                transport,
+            ))),
+            config,
+        )
+    }
+
+    pub(super) fn get_request(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    pub(super) async fn body_bytes(response: Response) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    /// History of the surplus of Germany on 1 June 2024 in two revisions: the second
+    /// lowers the generation by 1 GW at even and by 12 GW at odd hours
+    #[cfg(feature = "sqlite")]
+    pub(super) async fn revised_storage() -> Arc<crate::storage::sqlite::SqliteStorage> {
+        use crate::entsoe::analysis::{DocumentMeta, RenewableSurplus, SurplusSeries};
+        use crate::storage::{Storage, documents_of};
+
+        let storage = Arc::new(crate::storage::sqlite::SqliteStorage::open_in_memory().unwrap());
+        let first = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        for revision in [1, 2] {
+            let lowered_by = |hour: i64| match (revision, hour % 2) {
+                (1, _) => 0.0,
+                (_, 0) => 1_000.0,
+                _ => 12_000.0,
+            };
+            let meta = DocumentMeta {
+                created_date_time: first - Duration::hours(12) + Duration::hours(revision),
+                revision_number: 1,
+            };
+            let points = (0..24)
+                .map(|hour| {
+                    let generation = 50_000.0 - lowered_by(hour);
+                    RenewableSurplus {
+                        timestamp: first + Duration::hours(hour),
+                        generation,
+                        load: 40_000.0,
+                        surplus: generation - 40_000.0,
+                        total_generation: None,
+                    }
+                })
+                .collect();
+            let series = SurplusSeries {
+                points,
+                generation_doc_meta: Some(meta),
+                load_doc_meta: Some(meta),
+                ..SurplusSeries::default()
+            };
+            for document in documents_of("10Y1001A1001A83F", &series, meta.created_date_time) {
+                storage.store(&document).await.unwrap();
+            }
+        }
+        storage
+    }
+
+    /// The forecasts and prices of [`MockTransport::forecasts`] and a document of every
+    /// other type a route asks for
+    struct EveryDocument(MockTransport);
+
+    #[async_trait::async_trait]
+    impl Transport for EveryDocument {
+        async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+            let start = query_param(url, "periodStart").unwrap();
+            let start = NaiveDateTime::parse_from_str(&start, "%Y%m%d%H%M")
+                .unwrap()
+                .and_utc();
+            match query_param(url, "documentType").as_deref() {
+                Some("A75") => Ok(ok(actual_generation_document(start))),
+                Some("A83") => Ok(ok(balancing_document(
+                    start,
+                    60,
+                    &[("A01", &[12.0, 0.0]), ("A02", &[0.0, 8.5])],
+                ))),
+                Some("A61") => Ok(ok(publication_document("A61", start, 60, &[1000.0, 0.0]))),
+                Some("A11") => Ok(ok(publication_document("A11", start, 30, &[250.0; 4]))),
+                Some("A80") => Ok(outages_response(&[
+                    MockOutage {
+                        available: &[(1, 400.0)],
+                        ..MockOutage::new("outage-1", start, start + Duration::days(2))
+                    },
+                    MockOutage {
+                        planned: true,
+                        unit: ("11WD7BRUN1--K", "Brunsbüttel"),
+                        nominal_mw: 800.0,
+                        ..MockOutage::new(
+                            "outage-2",
+                            start + Duration::hours(6),
+                            start + Duration::hours(12),
+                        )
+                    },
+                ])),
+                _ => self.0.get(url).await,
+            }
+        }
+    }
+
+    /// Every route with data: debug endpoints on, at noon of 2024-06-01
+    pub(super) fn snapshot_app() -> Router {
+        let noon = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let client = EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(EveryDocument(MockTransport::forecasts())),
+        )
+        .with_cache(std::time::Duration::from_secs(300));
+        let config = ServerConfig {
+            debug_endpoints: true,
+            ..ServerConfig::default()
+        };
+        router(AppState::new(Some(Arc::new(client)), config).with_clock(Arc::new(FixedClock(noon))))
+    }
+
+    /// A request of `path` with its placeholders and required parameters filled in
+    pub(super) fn example_uri(path: &str) -> String {
+        let query = match path {
+            "/api/v1/renewable-surplus/summary" => "?countries=DE",
+            "/api/v1/zones/search" => "?q=de",
+            "/api/v1/compare" => "?countries=DE,FR",
+            "/api/v1/forecast-drift/{country}" => "?date=2024-06-01",
+            "/api/v1/history/{country}/surplus" => {
+                "?start=2024-06-01T00:00:00Z&end=2024-06-02T00:00:00Z"
+            }
+            _ => "",
+        };
+        path.replace("{country}", "DE")
+            .replace("{from}", "DE")
+            .replace("{to}", "FR")
+            .replace("{id}", "de")
+            + query
+    }
+
+    /// Compare `actual`, as pretty JSON, with the golden file at `path`, or rewrite the
+    /// file with `UPDATE_GOLDEN=1`. `tests/common` has the same for the integration tests.
+    pub(super) fn assert_golden(path: impl AsRef<std::path::Path>, actual: &impl serde::Serialize) {
+        let path = path.as_ref();
+        let actual = serde_json::to_string_pretty(actual).unwrap() + "\n";
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, &actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(path).unwrap_or_else(|_| {
+            panic!(
+                "{} does not exist, create it with UPDATE_GOLDEN=1",
+                path.display()
+            )
+        });
+        assert!(
+            expected == actual,
+            "{} differs; rerun with UPDATE_GOLDEN=1 if the change is intended\n--- expected\n{}\n+++ actual\n{}",
+            path.display(),
+            expected,
+            actual
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline_mode_serves_snapshot() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let snapshot = crate::snapshot::capture(
+            Arc::new(MockTransport::forecasts()),
+            "test-token",
+            &["DE".to_string()],
+            start,
+            start + Duration::hours(48),
+        )
+        .await
+        .unwrap();
+        let client =
+            EntsoeClient::with_transport("offline", Arc::new(OfflineTransport::new(&snapshot)));
+        let app = router(
+            AppState::new(Some(Arc::new(client)), ServerConfig::default()).with_offline_mode(),
+        );
+
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/series?start=2024-06-01T12:00:00Z",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-educk-offline"], "true");
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"]["points"].as_array().unwrap().len(), 24);
+
+        // Countries missing from the snapshot fail like an upstream without data
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/FR/series?start=2024-06-01T12:00:00Z",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let response = app.oneshot(get_request("/health")).await.unwrap();
+        assert!(response.headers().get("x-educk-offline").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_gzip_response_decodes_to_same_json() {
+        use std::io::Read;
+
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let uri = "/api/v1/renewable-surplus/DE/plot-json?hours=48";
+
+        let plain = app.clone().oneshot(get_request(uri)).await.unwrap();
+        assert!(!plain.headers().contains_key(header::CONTENT_ENCODING));
+        let plain_body = body_bytes(plain).await;
+
+        let request = Request::get(uri)
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let compressed = app.oneshot(request).await.unwrap();
+        assert_eq!(compressed.headers()[header::CONTENT_ENCODING], "gzip");
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(body_bytes(compressed).await.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+
+        let plain_json: serde_json::Value = serde_json::from_slice(&plain_body).unwrap();
+        let decoded_json: serde_json::Value = serde_json::from_slice(&decoded).unwrap();
+        assert_eq!(decoded_json, plain_json);
+    }
+
+    #[tokio::test]
+    async fn test_compression_can_be_disabled() {
+        let state = test_state_with_config(
+            Arc::new(MockTransport::forecasts()),
+            ServerConfig {
+                compression: false,
+                ..ServerConfig::default()
+            },
+        );
+
+        let request = Request::get("/api/v1/renewable-surplus/DE/plot-json")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+    }
+
+    fn state_with_tokens(tokens: &[&str]) -> AppState {
+        test_state_with_config(
+            Arc::new(MockTransport::forecasts()),
+            ServerConfig {
+                api_tokens: tokens.iter().map(|t| t.to_string()).collect(),
+                ..ServerConfig::default()
+            },
+        )
+    }
+
+    fn authorized_request(uri: &str, token: &str) -> Request<Body> {
+        Request::get(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_auth_unconfigured_allows_all_requests() {
+        let app = router(state_with_tokens(&[]));
+
+        let response = app.oneshot(get_request("/api/v1/countries")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_auth_accepts_configured_tokens() {
+        let app = router(state_with_tokens(&["first", "second"]));
+
+        for token in ["first", "second"] {
+            let response = app
+                .clone()
+                .oneshot(authorized_request("/api/v1/countries", token))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_rejects_missing_and_invalid_tokens() {
+        let app = router(state_with_tokens(&["secret"]));
+
+        let missing = app
+            .clone()
+            .oneshot(get_request("/api/v1/zones/DE"))
+            .await
+            .unwrap();
+        let invalid = app
+            .oneshot(authorized_request("/api/v1/zones/DE", "secreT"))
+            .await
+            .unwrap();
+
+        for response in [missing, invalid] {
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["success"], false);
+            assert_eq!(body["error"], "Missing or invalid API token");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_leaves_health_open() {
+        let app = router(state_with_tokens(&["secret"]));
+
+        let response = app.oneshot(get_request("/health")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+
+    fn rate_limited_app(limit: u32, trusted_proxy: bool) -> Router {
+        router(test_state_with_config(
+            Arc::new(MockTransport::forecasts()),
+            ServerConfig {
+                rate_limit_per_minute: Some(limit),
+                trusted_proxy,
+                ..ServerConfig::default()
+            },
+        ))
+    }
+
+    fn request_from(peer: &str, forwarded_for: Option<&str>) -> Request<Body> {
+        let mut builder = Request::get("/api/v1/countries");
+        if let Some(forwarded_for) = forwarded_for {
+            builder = builder.header("x-forwarded-for", forwarded_for);
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        let addr: SocketAddr = format!("{}:40000", peer).parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        request
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_rejects_with_retry_after() {
+        let app = rate_limited_app(2, false);
+
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(request_from("192.0.2.1", None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let limited = app
+            .clone()
+            .oneshot(request_from("192.0.2.1", None))
+            .await
+            .unwrap();
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = limited.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after));
+
+        // Other clients have their own budget
+        let other = app.oneshot(request_from("192.0.2.2", None)).await.unwrap();
+        assert_eq!(other.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_ignores_forwarded_for_without_trusted_proxy() {
+        let app = rate_limited_app(1, false);
+
+        let first = app
+            .clone()
+            .oneshot(request_from("192.0.2.1", Some("198.51.100.1")))
+            .await
+            .unwrap();
+        let second = app
+            .oneshot(request_from("192.0.2.1", Some("198.51.100.2")))
+            .await
+            .unwrap();
+
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_uses_forwarded_for_behind_trusted_proxy() {
+        let app = rate_limited_app(1, true);
+
+        let first = app
+            .clone()
+            .oneshot(request_from("10.0.0.1", Some("198.51.100.1")))
+            .await
+            .unwrap();
+        let second = app
+            .clone()
+            .oneshot(request_from("10.0.0.1", Some("198.51.100.2")))
+            .await
+            .unwrap();
+        let repeated = app
+            .oneshot(request_from("10.0.0.1", Some("203.0.113.9, 198.51.100.2")))
+            .await
+            .unwrap();
+
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(second.status(), StatusCode::OK);
+        assert_eq!(repeated.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn test_rate_limiter_resets_and_forgets_idle_clients() {
+        let window = std::time::Duration::from_secs(60);
+        let limiter = ClientRateLimiter::new(1, window);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check(ip, start).is_ok());
+        assert_eq!(
+            limiter.check(ip, start + std::time::Duration::from_secs(15)),
+            Err(std::time::Duration::from_secs(45))
+        );
+        assert!(limiter.check(ip, start + window).is_ok());
+        assert_eq!(limiter.tracked_clients(), 1);
+
+        // A request from another client after the idle window triggers cleanup
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        assert!(limiter.check(other, start + window * 3).is_ok());
+        assert_eq!(limiter.tracked_clients(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_echoed_when_provided() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let request = Request::get("/health")
+            .header(request_id::HEADER, "client-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[request_id::HEADER], "client-42");
+
+        // Ids that are unsafe to log are replaced
+        let request = Request::get("/health")
+            .header(request_id::HEADER, "a b")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_ne!(response.headers()[request_id::HEADER], "a b");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_generated_when_absent() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let mut ids = HashSet::new();
+        for _ in 0..2 {
+            let response = app.clone().oneshot(get_request("/health")).await.unwrap();
+            let id = response.headers()[request_id::HEADER].to_str().unwrap();
+            assert!(request_id::is_valid(id), "{}", id);
+            ids.insert(id.to_string());
+        }
+        assert_eq!(ids.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_error_body_names_the_request_id() {
+        let app = router(test_state(upstream_answering(500)));
+        let request = Request::get("/api/v1/renewable-surplus/DE/next-24h")
+            .header(request_id::HEADER, "failing-1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert!(response.status().is_server_error());
+        assert_eq!(response.headers()[request_id::HEADER], "failing-1");
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["success"], false);
+        assert_eq!(body["request_id"], "failing-1");
+
+        // Successful answers stay the same for every request, so their ETags do too
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let response = app.oneshot(get_request("/api/v1/zones")).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert!(body.get("request_id").is_none(), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_restart_answers_from_the_disk_cache_while_upstream_fails() {
+        let dir = std::env::temp_dir().join(format!("educk-{}-warm-start", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = ServerConfig {
+            cache_dir: Some(dir.clone()),
+            prefetch_countries: vec!["DE".to_string()],
+            ..ServerConfig::default()
+        };
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let app = |transport: Arc<MockTransport>| {
+            let client = EntsoeClient::with_transport("test-token", transport)
+                .with_cache(config.document_cache_ttl)
+                .with_disk_cache(dir.clone());
+            Arc::new(client)
+        };
+        let uri = "/api/v1/renewable-surplus/DE/series?hours=12";
+
+        let previous = app(Arc::new(MockTransport::forecasts()));
+        let state =
+            AppState::new(Some(previous), config.clone()).with_clock(Arc::new(FixedClock(now)));
+        let response = router(state).oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let fetched: serde_json::Value =
+            serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        // Restarted a quarter of an hour later, asking for other periods than the cached
+        let restarted = app(upstream_answering(503));
+        hydrate_cache(&restarted, &config).await;
+        let later = now + Duration::minutes(15);
+        let state = AppState::new(Some(restarted), config).with_clock(Arc::new(FixedClock(later)));
+        let response = router(state).oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"]["cache_status"], "stale");
+        assert_eq!(body["data"]["points"], fetched["data"]["points"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    pub(super) fn upstream_answering(status: u16) -> Arc<MockTransport> {
+        Arc::new(MockTransport::new(move |_| {
+            crate::entsoe::TransportResponse {
+                status,
+                body: String::new(),
+                retry_after: None,
+            }
+        }))
+    }
+}
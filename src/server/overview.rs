@@ -0,0 +1,334 @@
+//! What is going on in a country right now, in one call: load, wind and solar, surplus,
+//! the day-ahead price and generation outages. Built from data already fetched, never from upstream; each
+//! section carries its own `as_of` and is `null` when its data is not at hand.
+
+use axum::Json;
+use axum::extract::{Path, State};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::AppState;
+use super::dto::ApiResponse;
+use super::error::ApiError;
+use super::query::{requested_zone, warm_series};
+use super::routes::ApiRoute;
+use crate::entsoe::analysis::{GenerationSplit, Interpolation, SurplusSeries, value_at};
+use crate::entsoe::areas::BiddingZone;
+
+#[derive(Debug, Serialize)]
+pub(super) struct LoadSection {
+    load_mw: f64,
+    as_of: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct RenewablesSection {
+    /// Wind and solar
+    generation_mw: f64,
+    /// `null` where the forecast is not split by production type
+    solar_mw: Option<f64>,
+    wind_mw: Option<f64>,
+    /// Wind and solar as a percentage of total generation, where that is forecast
+    share_of_generation: Option<f64>,
+    as_of: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct SurplusSection {
+    surplus_mw: f64,
+    /// Wind and solar as a percentage of load
+    renewable_penetration: f64,
+    as_of: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct PriceSection {
+    /// Per MWh
+    amount: f64,
+    currency: String,
+    /// Start and end of the market time unit the price applies to
+    start: String,
+    end: String,
+    as_of: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct OutagesSection {
+    /// Generation units out of service, planned or forced
+    active: usize,
+    unavailable_mw: f64,
+    as_of: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct OverviewResponse {
+    country_code: String,
+    /// The instant the values apply to
+    timestamp: String,
+    load: Option<LoadSection>,
+    renewables: Option<RenewablesSection>,
+    surplus: Option<SurplusSection>,
+    price: Option<PriceSection>,
+    outages: Option<OutagesSection>,
+}
+
+/// Split of the last point starting at or before `at`
+fn split_at(series: &SurplusSeries, at: DateTime<Utc>) -> Option<GenerationSplit> {
+    series
+        .generation_split
+        .range(..=at)
+        .next_back()
+        .map(|(_, split)| *split)
+}
+
+fn price_section(state: &AppState, zone: &BiddingZone, now: DateTime<Utc>) -> Option<PriceSection> {
+    let (price, currency, as_of) = state
+        .entsoe_client
+        .as_ref()?
+        .cached_day_ahead_price(zone.code, now)?;
+    Some(PriceSection {
+        amount: price.amount,
+        currency,
+        start: price.timestamp.to_rfc3339(),
+        end: price.end().to_rfc3339(),
+        as_of: as_of.to_rfc3339(),
+    })
+}
+
+fn outages_section(
+    state: &AppState,
+    zone: &BiddingZone,
+    now: DateTime<Utc>,
+) -> Option<OutagesSection> {
+    let (outages, as_of) = state
+        .entsoe_client
+        .as_ref()?
+        .cached_generation_outages(zone.code, now)?;
+    Some(OutagesSection {
+        active: outages.len(),
+        unavailable_mw: outages
+            .iter()
+            .map(|outage| outage.unavailable_mw(now))
+            .sum(),
+        as_of: as_of.to_rfc3339(),
+    })
+}
+
+/// GET /api/v1/overview/:country
+/// Current load, wind and solar, surplus, price and outages of a country from prefetched
+/// or cached data
+async fn get_overview(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+) -> Result<Json<ApiResponse<OverviewResponse>>, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let country_code = country_code.to_ascii_uppercase();
+    let now = state.now();
+
+    let warm = warm_series(&state, &country_code, zone);
+    let current = warm.as_ref().and_then(|(series, as_of)| {
+        value_at(&series.points, now, Interpolation::default()).map(|point| (point, *as_of))
+    });
+    let load = current.as_ref().map(|(point, as_of)| LoadSection {
+        load_mw: point.load,
+        as_of: as_of.to_rfc3339(),
+    });
+    let renewables = current.as_ref().map(|(point, as_of)| {
+        let split = warm.as_ref().and_then(|(series, _)| split_at(series, now));
+        RenewablesSection {
+            generation_mw: point.generation,
+            solar_mw: split.map(|split| split.solar),
+            wind_mw: split.map(|split| split.wind),
+            share_of_generation: point.renewable_share_of_generation(),
+            as_of: as_of.to_rfc3339(),
+        }
+    });
+    let surplus = current.as_ref().map(|(point, as_of)| SurplusSection {
+        surplus_mw: point.surplus,
+        renewable_penetration: point.renewable_penetration(),
+        as_of: as_of.to_rfc3339(),
+    });
+
+    Ok(Json(ApiResponse::success(OverviewResponse {
+        country_code,
+        timestamp: now.to_rfc3339(),
+        load,
+        renewables,
+        surplus,
+        price: price_section(&state, zone, now),
+        outages: outages_section(&state, zone, now),
+    })))
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [ApiRoute::get("/api/v1/overview/{country}", get_overview).usage("cached data only")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::config::ServerConfig;
+    use crate::entsoe::testing::{MockOutage, MockTransport, outages_response};
+    use crate::entsoe::{EntsoeClient, EntsoeError, Transport, TransportResponse};
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request};
+    use chrono::TimeZone;
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    /// Split forecasts with the prices of [`MockTransport::forecasts`] and a forced outage
+    /// of Isar 2 leaving 400 MW of it
+    struct SplitWithPrices {
+        forecasts: Arc<MockTransport>,
+        prices: Arc<MockTransport>,
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for SplitWithPrices {
+        async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+            if url.contains("documentType=A44") {
+                self.prices.get(url).await
+            } else if url.contains("documentType=A80") {
+                let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+                Ok(outages_response(&[MockOutage {
+                    available: &[(1, 400.0)],
+                    ..MockOutage::new("outage-1", start, start + chrono::Duration::days(1))
+                }]))
+            } else {
+                self.forecasts.get(url).await
+            }
+        }
+    }
+
+    fn keys(value: &serde_json::Value) -> BTreeSet<&str> {
+        value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_overview_sections_are_filled_from_fetched_data() {
+        let noon = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let forecasts = Arc::new(MockTransport::split_forecasts());
+        let prices = Arc::new(MockTransport::forecasts());
+        let transport = SplitWithPrices {
+            forecasts: forecasts.clone(),
+            prices: prices.clone(),
+        };
+        let client = Arc::new(
+            EntsoeClient::with_transport("test-token", Arc::new(transport))
+                .with_cache(std::time::Duration::from_secs(300)),
+        );
+        let app = router(
+            AppState::new(Some(client.clone()), ServerConfig::default())
+                .with_clock(Arc::new(FixedClock(noon))),
+        );
+        let overview = || async {
+            let response = app
+                .clone()
+                .oneshot(get_request("/api/v1/overview/de"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            body["data"].clone()
+        };
+
+        // Nothing fetched yet: every section is null, and nothing goes upstream
+        let data = overview().await;
+        assert_eq!(
+            keys(&data),
+            BTreeSet::from([
+                "country_code",
+                "timestamp",
+                "load",
+                "renewables",
+                "surplus",
+                "price",
+                "outages"
+            ])
+        );
+        assert_eq!(data["country_code"], "DE");
+        for section in ["load", "renewables", "surplus", "price", "outages"] {
+            assert!(data[section].is_null(), "{}", section);
+        }
+        assert!(forecasts.requests().is_empty());
+
+        // Only the forecasts are at hand
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let data = overview().await;
+        assert!(data["price"].is_null());
+        assert!(data["outages"].is_null());
+        assert_eq!(keys(&data["load"]), BTreeSet::from(["load_mw", "as_of"]));
+        assert_eq!(data["load"]["load_mw"], 30_000.0);
+        assert_eq!(
+            keys(&data["renewables"]),
+            BTreeSet::from([
+                "generation_mw",
+                "solar_mw",
+                "wind_mw",
+                "share_of_generation",
+                "as_of"
+            ])
+        );
+        assert_eq!(data["renewables"]["generation_mw"], 22_000.0);
+        assert_eq!(data["renewables"]["solar_mw"], 12_000.0);
+        assert_eq!(data["renewables"]["wind_mw"], 10_000.0);
+        assert_eq!(
+            keys(&data["surplus"]),
+            BTreeSet::from(["surplus_mw", "renewable_penetration", "as_of"])
+        );
+        assert_eq!(data["surplus"]["surplus_mw"], -8_000.0);
+        assert!(DateTime::parse_from_rfc3339(data["surplus"]["as_of"].as_str().unwrap()).is_ok());
+
+        client
+            .fetch_day_ahead_prices(
+                "10Y1001A1001A83F",
+                noon - chrono::Duration::hours(1),
+                noon + chrono::Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        let fetched = forecasts.requests().len() + prices.requests().len();
+        let data = overview().await;
+        assert_eq!(
+            keys(&data["price"]),
+            BTreeSet::from(["amount", "currency", "start", "end", "as_of"])
+        );
+        assert_eq!(data["price"]["amount"], 100.0);
+        assert_eq!(data["price"]["currency"], "EUR");
+        assert_eq!(data["price"]["start"], noon.to_rfc3339());
+        assert!(data["outages"].is_null());
+
+        client
+            .fetch_generation_outages(
+                "10Y1001A1001A83F",
+                noon - chrono::Duration::hours(1),
+                noon + chrono::Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        let data = overview().await;
+        assert_eq!(
+            keys(&data["outages"]),
+            BTreeSet::from(["active", "unavailable_mw", "as_of"])
+        );
+        assert_eq!(data["outages"]["active"], 1);
+        assert_eq!(data["outages"]["unavailable_mw"], 1_000.0);
+        assert_eq!(
+            forecasts.requests().len() + prices.requests().len(),
+            fetched
+        );
+    }
+}
@@ -0,0 +1,783 @@
+//! Plotly page, static images and chart specs of a surplus series
+
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::dto::{
+    ApiResponse, ForecastInfo, SourceSegmentResponse, SurplusModelResponse, conditional_json,
+};
+use super::error::{ApiError, NO_SURPLUS_POINTS, ValidQuery};
+use super::query::{
+    Horizon, PlotView, fetch_partial_window_series, fetch_window_series, query_window,
+    requested_day, requested_error_stats, requested_freshness, requested_load_band,
+    requested_local_zone, requested_model, requested_zone,
+};
+use super::routes::ApiRoute;
+use super::{AppState, control_areas};
+use crate::entsoe::PowerUnit;
+use crate::entsoe::analysis::{Freshness, LoadBand, SurplusBand, load_band_at, uncertainty_band};
+use crate::entsoe::window::CalendarDay;
+use crate::plotting::image::{
+    DEFAULT_IMAGE_HEIGHT, DEFAULT_IMAGE_WIDTH, PlotImageFormat, render_plot_image,
+};
+use crate::plotting::plotly::{
+    forecast_label, generate_plot_data, partial_plot_data, resolution_label,
+};
+use crate::plotting::{VegaOptions, vega_spec};
+
+#[derive(Template)]
+#[template(path = "plot.html")]
+struct PlotTemplate {
+    /// HTML-escaped by askama like every variable not marked `safe`
+    country_code: String,
+    country_name: String,
+    period_start: String,
+    period_end: String,
+    data_points: usize,
+    /// e.g. `hourly means` when the series was downsampled
+    resolution: Option<String>,
+    forecast_issued_at: Option<String>,
+    /// Forecast missing from a `partial=true` plot, named in a banner
+    missing_forecast: Option<&'static str>,
+    /// JSON inserted into a `<script>` unescaped, see [`script_json`]
+    plot_data: String,
+    plot_layout: String,
+}
+
+/// JSON made safe to inline into a `<script>` element: a `</` inside a string, e.g. a
+/// trace name, would otherwise end the element early
+fn script_json(json: String) -> String {
+    json.replace("</", "<\\/")
+}
+
+/// Query of the plot page. Parameters of other endpoints are refused rather than ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlotQuery {
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+    /// Must-run generation added to wind and solar: one value or 24 hourly (UTC) values,
+    /// comma separated
+    baseload_mw: Option<String>,
+    /// Assumed exports subtracted from the surplus
+    export_mw: Option<f64>,
+    /// Usable share of a positive surplus (0 < efficiency <= 1)
+    efficiency: Option<f64>,
+    /// IANA time zone of the `timestamp_local` fields (default: the zone's market time)
+    tz: Option<String>,
+    /// Plot every point instead of averaging long series
+    #[serde(default)]
+    raw: bool,
+    /// Label the plot in local instead of UTC time
+    #[serde(default)]
+    local: bool,
+    /// Unit of the power values: `MW` (default), `GW` or `kW`
+    #[serde(default)]
+    unit: PowerUnit,
+    /// What the plot shows: `surplus` (default) or `by-tso`
+    #[serde(default)]
+    view: PlotView,
+    /// `week` adds the range of the week-ahead load forecast
+    #[serde(default)]
+    horizon: Horizon,
+    /// Keep the points of a forecast published without the other (day-ahead forecasts)
+    #[serde(default)]
+    partial: bool,
+}
+
+/// Query of `/plot-json`. Parameters of other endpoints are refused rather than ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlotJsonQuery {
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+    /// Must-run generation added to wind and solar: one value or 24 hourly (UTC) values,
+    /// comma separated
+    baseload_mw: Option<String>,
+    /// Assumed exports subtracted from the surplus
+    export_mw: Option<f64>,
+    /// Usable share of a positive surplus (0 < efficiency <= 1)
+    efficiency: Option<f64>,
+    /// IANA time zone of the `timestamp_local` fields (default: the zone's market time)
+    tz: Option<String>,
+    /// Unit of the power values: `MW` (default), `GW` or `kW`
+    #[serde(default)]
+    unit: PowerUnit,
+    /// `week` adds the range of the week-ahead load forecast
+    #[serde(default)]
+    horizon: Horizon,
+    /// Add the 10th and 90th percentile of the surplus from the stored forecast errors
+    #[serde(default)]
+    band: bool,
+}
+
+/// Query of `/vega`. Parameters of other endpoints are refused rather than ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VegaQuery {
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+    /// Must-run generation added to wind and solar: one value or 24 hourly (UTC) values,
+    /// comma separated
+    baseload_mw: Option<String>,
+    /// Assumed exports subtracted from the surplus
+    export_mw: Option<f64>,
+    /// Usable share of a positive surplus (0 < efficiency <= 1)
+    efficiency: Option<f64>,
+    /// IANA time zone of the `timestamp_local` fields (default: the zone's market time)
+    tz: Option<String>,
+}
+
+/// GET /api/v1/renewable-surplus/:country/plot
+/// Generate interactive Plotly visualization
+async fn get_plot(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<PlotQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, query.tz.as_deref(), zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+
+    let local = match query.local {
+        true => Some(requested_local_zone(query.tz.as_deref(), zone)?),
+        false => None,
+    };
+    let format_time = |timestamp: DateTime<Utc>| match &local {
+        Some(local) => local
+            .to_local(timestamp)
+            .format("%Y-%m-%d %H:%M %:z")
+            .to_string(),
+        None => timestamp.format("%Y-%m-%d %H:%M UTC").to_string(),
+    };
+
+    if query.view == PlotView::ByTso {
+        let breakdown = control_areas::plot_breakdown(&state, zone, &window).await?;
+        let (Some(first), Some(last)) = (breakdown.timestamps.first(), breakdown.timestamps.last())
+        else {
+            return Err(ApiError::no_data("No load points in the requested period"));
+        };
+        let figure =
+            control_areas::breakdown_plot_data(zone, &breakdown, local.as_ref(), query.unit);
+        return render_plot_page(PlotTemplate {
+            country_code: zone.country_code.to_string(),
+            country_name: zone.name.to_string(),
+            period_start: format_time(*first),
+            period_end: format_time(*last),
+            data_points: breakdown.timestamps.len(),
+            resolution: None,
+            forecast_issued_at: None,
+            missing_forecast: None,
+            plot_data: figure.data,
+            plot_layout: figure.layout,
+        });
+    }
+
+    let freshness = requested_freshness(query.freshness, query.use_intraday);
+    let model = requested_model(
+        query.baseload_mw.as_deref(),
+        query.export_mw,
+        query.efficiency,
+    )?;
+    if query.partial {
+        let series =
+            fetch_partial_window_series(&state, zone.code, &window, freshness, &model).await?;
+        let (Some(first), Some(last)) = (series.points.first(), series.points.last()) else {
+            return Err(ApiError::no_data(NO_SURPLUS_POINTS));
+        };
+        let figure = partial_plot_data(&series.points, local.as_ref(), query.unit);
+        return render_plot_page(PlotTemplate {
+            country_code: zone.country_code.to_string(),
+            country_name: zone.name.to_string(),
+            period_start: format_time(first.timestamp),
+            period_end: format_time(last.timestamp),
+            data_points: series.points.len(),
+            resolution: None,
+            forecast_issued_at: None,
+            missing_forecast: series.missing.map(forecast_label),
+            plot_data: figure.data,
+            plot_layout: figure.layout,
+        });
+    }
+
+    let mut surplus_series = fetch_window_series(&state, zone.code, &window, freshness).await?;
+    model.apply(&mut surplus_series.points);
+    let series = &surplus_series.points;
+
+    let (Some(first), Some(last)) = (series.first(), series.last()) else {
+        return Err(ApiError::no_data(NO_SURPLUS_POINTS));
+    };
+
+    let load_band = requested_load_band(&state, zone.code, &window, query.horizon).await?;
+    let max_points = state.config.plot_max_points.filter(|_| !query.raw);
+    let figure = generate_plot_data(
+        series,
+        &surplus_series.generation_split,
+        &load_band,
+        max_points,
+        local.as_ref(),
+        query.unit,
+    );
+
+    render_plot_page(PlotTemplate {
+        country_code: zone.country_code.to_string(),
+        country_name: zone.name.to_string(),
+        period_start: format_time(first.timestamp),
+        period_end: format_time(last.timestamp),
+        data_points: series.len(),
+        resolution: figure.resolution.map(resolution_label),
+        forecast_issued_at: surplus_series
+            .forecast_created_at()
+            .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string()),
+        missing_forecast: None,
+        plot_data: figure.data,
+        plot_layout: figure.layout,
+    })
+}
+
+fn render_plot_page(mut template: PlotTemplate) -> Result<axum::response::Html<String>, ApiError> {
+    template.plot_data = script_json(template.plot_data);
+    template.plot_layout = script_json(template.plot_layout);
+    let html = template.render().map_err(|e| {
+        eprintln!("Template rendering error: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(axum::response::Html(html))
+}
+
+const MIN_IMAGE_DIMENSION: u32 = 200;
+const MAX_IMAGE_DIMENSION: u32 = 4096;
+
+#[derive(Deserialize)]
+struct PlotImageQuery {
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+    /// Image width in pixels (default: 1200)
+    width: Option<u32>,
+    /// Image height in pixels (default: 600)
+    height: Option<u32>,
+    /// Unit of the power values: `MW` (default), `GW` or `kW`
+    #[serde(default)]
+    unit: PowerUnit,
+}
+
+/// GET /api/v1/renewable-surplus/:country/plot.png
+/// Render the forecast plot as a static PNG image
+async fn get_plot_png(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<PlotImageQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    get_plot_image(state, &country_code, query, PlotImageFormat::Png).await
+}
+
+/// GET /api/v1/renewable-surplus/:country/plot.svg
+/// Render the forecast plot as a static SVG image
+async fn get_plot_svg(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<PlotImageQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    get_plot_image(state, &country_code, query, PlotImageFormat::Svg).await
+}
+
+/// Helper function to fetch the series and render it server-side
+async fn get_plot_image(
+    state: AppState,
+    country_code: &str,
+    query: PlotImageQuery,
+    format: PlotImageFormat,
+) -> Result<impl IntoResponse + use<>, ApiError> {
+    let zone = requested_zone(country_code)?;
+
+    let width = query.width.unwrap_or(DEFAULT_IMAGE_WIDTH);
+    let height = query.height.unwrap_or(DEFAULT_IMAGE_HEIGHT);
+    let dimensions = MIN_IMAGE_DIMENSION..=MAX_IMAGE_DIMENSION;
+    if !dimensions.contains(&width) || !dimensions.contains(&height) {
+        return Err(ApiError::bad_request(format!(
+            "`width` and `height` must be between {} and {} pixels",
+            MIN_IMAGE_DIMENSION, MAX_IMAGE_DIMENSION
+        )));
+    }
+
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, None, zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let freshness = requested_freshness(query.freshness, query.use_intraday);
+    let series = fetch_window_series(&state, zone.code, &window, freshness)
+        .await?
+        .points;
+
+    if series.is_empty() {
+        return Err(ApiError::no_data(NO_SURPLUS_POINTS));
+    }
+
+    // Rasterizing is CPU-bound, keep it off the async workers
+    let image = tokio::task::spawn_blocking(move || {
+        render_plot_image(&series, width, height, format, query.unit)
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("Plot rendering task failed: {}", e);
+        ApiError::from(StatusCode::INTERNAL_SERVER_ERROR)
+    })?
+    .map_err(|e| {
+        eprintln!("Plot rendering error: {}", e);
+        ApiError::from(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    Ok(([(http::header::CONTENT_TYPE, format.content_type())], image))
+}
+
+/// GET /api/v1/renewable-surplus/:country/plot-json
+/// Get plot data as JSON (for frontend frameworks)
+async fn get_plot_json(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<PlotJsonQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, query.tz.as_deref(), zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+
+    let freshness = requested_freshness(query.freshness, query.use_intraday);
+    let model = requested_model(
+        query.baseload_mw.as_deref(),
+        query.export_mw,
+        query.efficiency,
+    )?;
+    let mut series = fetch_window_series(&state, zone.code, &window, freshness).await?;
+    model.apply(&mut series.points);
+
+    if series.points.is_empty() {
+        return Err(ApiError::no_data("No data available"));
+    }
+
+    let load_band = requested_load_band(&state, zone.code, &window, query.horizon).await?;
+    let surplus_band = requested_error_stats(&state, zone, query.band)
+        .await?
+        .and_then(|stats| uncertainty_band(&series.points, &stats));
+    let surplus_bound = |bound: fn(&SurplusBand) -> f64| -> Option<Vec<f64>> {
+        surplus_band.as_ref().map(|band| {
+            band.iter()
+                .map(|point| query.unit.from_mw(bound(point)))
+                .collect()
+        })
+    };
+    let band_bound = |bound: fn(&LoadBand) -> f64| -> Option<Vec<Option<f64>>> {
+        (!load_band.is_empty()).then(|| {
+            series
+                .points
+                .iter()
+                .map(|s| {
+                    load_band_at(&load_band, s.timestamp)
+                        .map(|band| query.unit.from_mw(bound(band)))
+                })
+                .collect()
+        })
+    };
+
+    let plot_data = PlotData {
+        period_start: window.start.to_rfc3339(),
+        period_end: window.end.to_rfc3339(),
+        timestamps: series
+            .points
+            .iter()
+            .map(|s| s.timestamp.to_rfc3339())
+            .collect(),
+        unit: query.unit,
+        generation: series
+            .points
+            .iter()
+            .map(|s| query.unit.from_mw(s.generation))
+            .collect(),
+        load: series
+            .points
+            .iter()
+            .map(|s| query.unit.from_mw(s.load))
+            .collect(),
+        surplus: series
+            .points
+            .iter()
+            .map(|s| query.unit.from_mw(s.surplus))
+            .collect(),
+        load_min: band_bound(|band| band.min_mw),
+        load_max: band_bound(|band| band.max_mw),
+        surplus_p10: surplus_bound(|band| band.p10),
+        surplus_p90: surplus_bound(|band| band.p90),
+        sources: series.sources.iter().map(Into::into).collect(),
+        forecast: (&series).into(),
+        surplus_model: SurplusModelResponse::echo(&model),
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(plot_data),
+    ))
+}
+
+/// GET /api/v1/renewable-surplus/:country/vega?hours=N
+/// Vega-Lite v5 spec of the series with the data inlined
+async fn get_vega(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<VegaQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, query.tz.as_deref(), zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+
+    let freshness = requested_freshness(query.freshness, query.use_intraday);
+    let model = requested_model(
+        query.baseload_mw.as_deref(),
+        query.export_mw,
+        query.efficiency,
+    )?;
+    let mut series = fetch_window_series(&state, zone.code, &window, freshness).await?;
+    model.apply(&mut series.points);
+
+    if series.points.is_empty() {
+        return Err(ApiError::no_data(NO_SURPLUS_POINTS));
+    }
+
+    let options = VegaOptions {
+        title: Some(format!("Renewable Energy Forecast - {}", zone.name)),
+        ..VegaOptions::default()
+    };
+    Ok(Json(vega_spec(&series.points, &options)))
+}
+
+#[derive(Serialize)]
+struct PlotData {
+    period_start: String,
+    period_end: String,
+    /// Of `generation`, `load` and `surplus`
+    unit: PowerUnit,
+    timestamps: Vec<String>,
+    generation: Vec<f64>,
+    load: Vec<f64>,
+    surplus: Vec<f64>,
+    /// Week-ahead load range at each timestamp with `horizon=week`, `null` outside of it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load_min: Option<Vec<Option<f64>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load_max: Option<Vec<Option<f64>>>,
+    /// Range the surplus stays within with 80 % probability by the stored forecast
+    /// errors, with `band=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    surplus_p10: Option<Vec<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    surplus_p90: Option<Vec<f64>>,
+    sources: Vec<SourceSegmentResponse>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    surplus_model: Option<SurplusModelResponse>,
+}
+
+pub(super) fn routes() -> [ApiRoute; 5] {
+    [
+        ApiRoute::get("/api/v1/renewable-surplus/{country}/plot", get_plot)
+            .usage("?hours=N&raw=true&view=surplus|by-tso&horizon=day|week&partial=true"),
+        ApiRoute::get("/api/v1/renewable-surplus/{country}/plot.png", get_plot_png)
+            .usage("?hours=N&width=W&height=H"),
+        ApiRoute::get("/api/v1/renewable-surplus/{country}/plot.svg", get_plot_svg)
+            .usage("?hours=N&width=W&height=H"),
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/plot-json",
+            get_plot_json,
+        )
+        .usage("?hours=N&horizon=day|week&band=true"),
+        ApiRoute::get("/api/v1/renewable-surplus/{country}/vega", get_vega)
+            .usage("?hours=N (Vega-Lite v5 spec)"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state, test_state_with_config};
+    use axum::http::header;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_markup_in_the_country_is_refused_unreflected() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        for uri in [
+            "/api/v1/renewable-surplus/%3Cscript%3E/plot",
+            "/api/v1/renewable-surplus/%3Cscript%3E/plot.svg",
+            "/api/v1/renewable-surplus/%3Cscript%3E/next-24h",
+        ] {
+            let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body = String::from_utf8(body_bytes(response).await.to_vec()).unwrap();
+            assert!(!body.contains("script"), "{}: {}", uri, body);
+        }
+
+        let response = app
+            .oneshot(get_request("/api/v1/renewable-surplus/de/plot"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let page = String::from_utf8(body_bytes(response).await.to_vec()).unwrap();
+        assert!(page.contains("<title>Renewable Energy Forecast - DE</title>"));
+    }
+
+    #[test]
+    fn test_script_json_cannot_close_the_element() {
+        let json = serde_json::json!({"name": "</script><script>alert(1)</script>"}).to_string();
+        let inlined = script_json(json);
+        assert!(!inlined.contains("</"), "{}", inlined);
+        let value: serde_json::Value = serde_json::from_str(&inlined).unwrap();
+        assert_eq!(value["name"], "</script><script>alert(1)</script>");
+    }
+
+    #[tokio::test]
+    async fn test_vega_endpoint() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/vega?start=2024-06-01T00:00:00Z",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let spec: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(spec["$schema"], crate::plotting::VEGA_LITE_SCHEMA);
+        assert_eq!(spec["title"], "Renewable Energy Forecast - Germany");
+        assert_eq!(spec["data"]["values"].as_array().unwrap().len(), 24);
+
+        let response = app
+            .oneshot(get_request("/api/v1/renewable-surplus/XX/vega"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_plot_downsampling_and_raw() {
+        let config = ServerConfig {
+            plot_max_points: Some(100),
+            ..ServerConfig::default()
+        };
+        let app = router(test_state_with_config(
+            Arc::new(MockTransport::forecasts()),
+            config,
+        ));
+        let uri =
+            "/api/v1/renewable-surplus/DE/plot?start=2024-06-01T00:00:00Z&end=2024-06-08T00:00:00Z";
+
+        let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+        let html = String::from_utf8(body_bytes(response).await).unwrap();
+        // 168 hourly points do not fit, 56 3-hour means do
+        assert!(html.contains("<strong>Data Points:</strong> 168"));
+        assert!(html.contains("<strong>Shown as:</strong> 3-hour means"));
+
+        let response = app
+            .oneshot(get_request(&format!("{}&raw=true", uri)))
+            .await
+            .unwrap();
+        let html = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(!html.contains("Shown as:"));
+    }
+
+    #[tokio::test]
+    async fn test_partial_plot_notes_the_missing_forecast() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts_without(
+            "A65",
+        ))));
+        let uri = "/api/v1/renewable-surplus/DE/plot?start=2024-06-01T00:00:00Z";
+
+        let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+        assert_ne!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(get_request(&format!("{}&partial=true", uri)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let html = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(html.contains("No load forecast is published"), "{}", html);
+        assert!(html.contains("Wind + Solar Generation"));
+        assert!(!html.contains("Total Load"));
+
+        // Without a missing forecast there is no banner
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let response = app
+            .oneshot(get_request(&format!("{}&partial=true", uri)))
+            .await
+            .unwrap();
+        let html = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(!html.contains(r#"class="missing""#));
+        assert!(html.contains("Total Load"));
+    }
+
+    #[tokio::test]
+    async fn test_week_horizon_adds_the_load_band() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let uri = "/api/v1/renewable-surplus/DE/plot-json?start=2024-06-01T12:00:00Z&end=2024-06-02T12:00:00Z&unit=GW";
+
+        let response = app
+            .clone()
+            .oneshot(get_request(&format!("{}&horizon=week", uri)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+        let timestamps = data["timestamps"].as_array().unwrap().len();
+        assert_eq!(data["load_min"].as_array().unwrap().len(), timestamps);
+        // The bounds of each day, not their sum
+        assert!(
+            data["load_min"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .all(|v| v == 45.0)
+        );
+        assert!(
+            data["load_max"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .all(|v| v == 55.0)
+        );
+        assert_eq!(data["load"][0], 50.0);
+
+        let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert!(body["data"].get("load_min").is_none());
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/plot?start=2024-06-01T00:00:00Z&horizon=week",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let html = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(html.contains("tonexty"));
+        assert!(html.contains("Load Range (week ahead)"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_band_widens_the_surplus_by_stored_errors() {
+        use crate::clock::FixedClock;
+        use crate::server::tests::revised_storage;
+        use chrono::TimeZone;
+
+        let uri = "/api/v1/renewable-surplus/DE/plot-json?start=2024-06-01T00:00:00Z&end=2024-06-01T06:00:00Z&unit=GW&band=true";
+        let response = router(test_state(Arc::new(MockTransport::forecasts())))
+            .oneshot(get_request(uri))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let now = Utc.with_ymd_and_hms(2024, 6, 5, 12, 0, 0).unwrap();
+        let app = router(
+            test_state(Arc::new(MockTransport::forecasts_and_actuals()))
+                .with_storage(revised_storage().await)
+                .with_clock(Arc::new(FixedClock(now))),
+        );
+        let response = app.oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+
+        // The first stored forecasts were 12 GW too high at odd and 2 GW at even hours,
+        // whatever their revisions said
+        let surplus = data["surplus"].as_array().unwrap();
+        let (p10, p90) = (
+            data["surplus_p10"].as_array().unwrap(),
+            data["surplus_p90"].as_array().unwrap(),
+        );
+        assert_eq!(p10.len(), surplus.len());
+        assert_eq!(surplus[0], -10.0);
+        assert_eq!(p10[0], -22.0);
+        assert_eq!(p90[0], -12.0);
+    }
+}
@@ -0,0 +1,741 @@
+//! Query parameters and series lookups most handlers share: the requested zone, time
+//! window, freshness and surplus model
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use super::AppState;
+use super::error::{ApiError, NO_SURPLUS_POINTS};
+use crate::entsoe::analysis::{
+    Baseload, ErrorStats, Freshness, LoadBand, PartialSurplusSeries, SurplusModel, SurplusSeries,
+    error_stats, forecast_outcomes,
+};
+use crate::entsoe::areas::{BiddingZone, DocumentKind, get_primary_zone, get_zone_by_code};
+use crate::entsoe::localtime::LocalZone;
+use crate::entsoe::window::{CalendarDay, TimeWindow};
+use crate::storage::first_forecast_surplus;
+
+#[derive(Deserialize)]
+pub(super) struct TimeQuery {
+    /// Number of hours to look ahead (default: 24)
+    pub(super) hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    pub(super) window: Option<CalendarDay>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    pub(super) freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    pub(super) use_intraday: bool,
+    /// Must-run generation added to wind and solar: one value or 24 hourly (UTC) values,
+    /// comma separated
+    pub(super) baseload_mw: Option<String>,
+    /// Assumed exports subtracted from the surplus
+    pub(super) export_mw: Option<f64>,
+    /// Usable share of a positive surplus (0 < efficiency <= 1)
+    pub(super) efficiency: Option<f64>,
+    /// Fail with 404 unless the forecasts cover the whole requested window
+    #[serde(default)]
+    pub(super) strict: bool,
+    /// IANA time zone of the `timestamp_local` fields (default: the zone's market time)
+    pub(super) tz: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct FreshnessQuery {
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    pub(super) window: Option<CalendarDay>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    pub(super) freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    pub(super) use_intraday: bool,
+    /// Must-run generation added to wind and solar: one value or 24 hourly (UTC) values,
+    /// comma separated
+    pub(super) baseload_mw: Option<String>,
+    /// Assumed exports subtracted from the surplus
+    pub(super) export_mw: Option<f64>,
+    /// Usable share of a positive surplus (0 < efficiency <= 1)
+    pub(super) efficiency: Option<f64>,
+    /// Fail with 404 unless the forecasts cover the whole requested window
+    #[serde(default)]
+    pub(super) strict: bool,
+    /// IANA time zone of the `timestamp_local` fields (default: the zone's market time)
+    pub(super) tz: Option<String>,
+}
+
+/// The `window` day of a query with the time zone it is taken in, see
+/// [`requested_local_zone`]
+pub(super) fn requested_day(
+    window: Option<CalendarDay>,
+    tz: Option<&str>,
+    zone: &BiddingZone,
+) -> Result<Option<(CalendarDay, LocalZone)>, ApiError> {
+    window
+        .map(|day| Ok((day, requested_local_zone(tz, zone)?)))
+        .transpose()
+}
+
+/// Time zone of the `tz` query parameter, otherwise the local market time of `zone`
+pub(super) fn requested_local_zone(
+    tz: Option<&str>,
+    zone: &BiddingZone,
+) -> Result<LocalZone, ApiError> {
+    match tz {
+        Some(tz) => LocalZone::named(tz)
+            .ok_or_else(|| ApiError::bad_request(format!("Unknown time zone `{}`", tz))),
+        None => Ok(LocalZone::named(zone.timezone).unwrap_or(LocalZone::UTC)),
+    }
+}
+
+/// `freshness` if given, otherwise `auto` for `use_intraday=true` and day-ahead by default
+pub(super) fn requested_freshness(freshness: Option<Freshness>, use_intraday: bool) -> Freshness {
+    match freshness {
+        Some(freshness) => freshness,
+        None if use_intraday => Freshness::Auto,
+        None => Freshness::default(),
+    }
+}
+
+/// Surplus model from the `baseload_mw`, `export_mw` and `efficiency` query parameters
+pub(super) fn requested_model(
+    baseload_mw: Option<&str>,
+    export_mw: Option<f64>,
+    efficiency: Option<f64>,
+) -> Result<SurplusModel, ApiError> {
+    let mut model = SurplusModel::default();
+
+    if let Some(baseload) = baseload_mw {
+        let values = baseload
+            .split(',')
+            .map(|value| value.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+            .collect::<Option<Vec<f64>>>()
+            .ok_or_else(|| ApiError::bad_request("Invalid `baseload_mw`: expected numbers"))?;
+        model.baseload = match values[..] {
+            [value] => Baseload::Constant(value),
+            _ => Baseload::Hourly(values.try_into().map_err(|_| {
+                ApiError::bad_request("`baseload_mw` takes one value or 24 hourly values")
+            })?),
+        };
+    }
+    if let Some(export) = export_mw {
+        if !export.is_finite() {
+            return Err(ApiError::bad_request("Invalid `export_mw`"));
+        }
+        model.export = export;
+    }
+    if let Some(efficiency) = efficiency {
+        if !(efficiency > 0.0 && efficiency <= 1.0) {
+            return Err(ApiError::bad_request(
+                "`efficiency` must be greater than 0 and at most 1",
+            ));
+        }
+        model.efficiency = efficiency;
+    }
+
+    Ok(model)
+}
+
+/// Primary zone of the `country` path parameter, case-insensitive
+pub(super) fn requested_zone(country_code: &str) -> Result<&'static BiddingZone, ApiError> {
+    get_primary_zone(&country_code.to_ascii_uppercase()).ok_or_else(|| {
+        ApiError::bad_request(format!(
+            "{}, see /api/v1/countries",
+            unknown_country(country_code)
+        ))
+    })
+}
+
+/// Longest text echoed as a country, the length of an EIC code
+const MAX_ECHOED_COUNTRY_LEN: usize = 16;
+
+/// Error message for a country that is not in the areas table. It names the country
+/// only when it looks like a code, so markup in a path is never reflected.
+pub(super) fn unknown_country(country_code: &str) -> String {
+    let plausible = !country_code.is_empty()
+        && country_code.len() <= MAX_ECHOED_COUNTRY_LEN
+        && country_code
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-');
+    match plausible {
+        true => format!("Unknown country `{}`", country_code),
+        false => "Unknown country".to_string(),
+    }
+}
+
+/// Look-ahead of an `hours` query parameter, `default` when not given
+pub(super) fn look_ahead_hours(
+    hours: Option<u32>,
+    default: u32,
+    max: u32,
+) -> Result<u32, ApiError> {
+    match hours.unwrap_or(default) {
+        hours if (1..=max).contains(&hours) => Ok(hours),
+        _ => Err(ApiError::bad_request(format!(
+            "`hours` must be between 1 and {}",
+            max
+        ))),
+    }
+}
+
+/// Longest interval accepted for explicit `start`/`end` queries
+const MAX_QUERY_SPAN_DAYS: i64 = 31;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum Horizon {
+    /// The day-ahead and intraday forecasts only
+    #[default]
+    Day,
+    /// With the daily minimum and maximum load of the week-ahead forecast
+    Week,
+}
+
+/// Days of stored forecasts the errors behind `band=true` are taken from
+pub(super) const ERROR_HISTORY_DAYS: i64 = 30;
+
+/// With `band=true`, the errors of the first stored forecasts of `zone` against the
+/// actual generation and load over the last [`ERROR_HISTORY_DAYS`] whole UTC days, so
+/// the actuals are fetched for the same days all day long; else none. Needs the history
+/// database; a zone without stored forecasts of those days is a 404.
+pub(super) async fn requested_error_stats(
+    state: &AppState,
+    zone: &BiddingZone,
+    band: bool,
+) -> Result<Option<ErrorStats>, ApiError> {
+    if !band {
+        return Ok(None);
+    }
+    let Some(storage) = &state.storage else {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Uncertainty bands need the history database (EDUCK_HISTORY_DB)",
+        ));
+    };
+    let end = state
+        .now()
+        .duration_trunc(Duration::days(1))
+        .expect("a day divides timestamps");
+    let start = end - Duration::days(ERROR_HISTORY_DAYS);
+    let forecast = first_forecast_surplus(storage.as_ref(), zone.code, start, end)
+        .await
+        .map_err(|e| {
+            eprintln!("Reading the forecast history failed: {}", e);
+            ApiError::from(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    if forecast.is_empty() {
+        return Err(ApiError::no_data(format!(
+            "No forecasts of {} stored in the last {} days to estimate errors from",
+            zone.code, ERROR_HISTORY_DAYS
+        )));
+    }
+    let actual = state
+        .client()?
+        .fetch_actual_surplus(zone.code, start, end)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))?;
+    let local = LocalZone::named(zone.timezone).unwrap_or(LocalZone::UTC);
+    let stats = error_stats(&forecast_outcomes(&forecast, &actual), local);
+    if stats.samples() == 0 {
+        return Err(ApiError::no_data(format!(
+            "No actual generation and load of {} at the times of its stored forecasts",
+            zone.code
+        )));
+    }
+    Ok(Some(stats))
+}
+
+/// The week-ahead load range of `zone_code` over `window` with `horizon=week`, else none.
+/// Its intervals are days, so it is requested from the midnight before the window.
+pub(super) async fn requested_load_band(
+    state: &AppState,
+    zone_code: &str,
+    window: &TimeWindow,
+    horizon: Horizon,
+) -> Result<Vec<LoadBand>, ApiError> {
+    if horizon == Horizon::Day {
+        return Ok(Vec::new());
+    }
+    let start = window
+        .start
+        .duration_trunc(Duration::days(1))
+        .unwrap_or(window.start);
+    state
+        .client()?
+        .fetch_load_band(zone_code, start, window.end)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum PlotView {
+    /// Generation, load and surplus
+    #[default]
+    Surplus,
+    /// Load of each TSO control area and their sum
+    ByTso,
+}
+
+pub(super) fn parse_query_time(name: &str, value: &str) -> Result<DateTime<Utc>, ApiError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|_| {
+            ApiError::bad_request(format!(
+                "Invalid `{}`: expected an RFC3339 timestamp like 2024-06-01T00:00:00Z",
+                name
+            ))
+        })
+}
+
+/// Resolve `hours`/`start`/`end`/`window` query parameters into the interval to fetch,
+/// with at most `max_hours` of look-ahead
+pub(super) fn query_window(
+    hours: Option<u32>,
+    start: Option<&str>,
+    end: Option<&str>,
+    day: Option<(CalendarDay, LocalZone)>,
+    now: DateTime<Utc>,
+    max_hours: u32,
+) -> Result<TimeWindow, ApiError> {
+    if let Some((day, local)) = day {
+        if start.is_some() || end.is_some() {
+            return Err(ApiError::bad_request(
+                "`window` cannot be combined with `start` or `end`",
+            ));
+        }
+        return Ok(TimeWindow::calendar_day_from(now, day, &local));
+    }
+
+    let Some(start) = start else {
+        if end.is_some() {
+            return Err(ApiError::bad_request("`end` requires `start`"));
+        }
+
+        let hours = look_ahead_hours(hours, 24, max_hours)?;
+        // Add 1 hour buffer
+        return Ok(TimeWindow::next_hours_from(now, (hours + 1) as i64));
+    };
+
+    let start = parse_query_time("start", start)?;
+    let end = match end {
+        Some(end) => parse_query_time("end", end)?,
+        None => start + Duration::hours(24),
+    };
+
+    if start >= end {
+        return Err(ApiError::bad_request("`start` must be before `end`"));
+    }
+    if end - start > Duration::days(MAX_QUERY_SPAN_DAYS) {
+        return Err(ApiError::bad_request(format!(
+            "Interval must not exceed {} days",
+            MAX_QUERY_SPAN_DAYS
+        )));
+    }
+
+    Ok(TimeWindow::between(start, end))
+}
+
+/// Documents a surplus series cannot do without; the total generation forecast is optional
+const SURPLUS_DOCUMENTS: [DocumentKind; 2] =
+    [DocumentKind::LoadForecast, DocumentKind::WindSolarForecast];
+
+/// `window` ending where the published forecasts of `zone_code` end, `None` unless it
+/// reaches past them. Windows starting beyond the forecasts are left to the fetch to
+/// report, as are failures to tell the availability.
+pub(super) async fn clamped_window(
+    state: &AppState,
+    zone_code: &str,
+    window: &TimeWindow,
+) -> Result<Option<TimeWindow>, ApiError> {
+    require_supported(state, zone_code, &SURPLUS_DOCUMENTS)?;
+    let availability = match state.client()?.availability(zone_code).await {
+        Ok(availability) => availability,
+        Err(e) => {
+            eprintln!("Availability of {} unknown: {}", zone_code, e);
+            return Ok(None);
+        }
+    };
+    Ok(availability
+        .surplus()
+        .filter(|available| window.start < available.end && available.end < window.end)
+        .map(|available| TimeWindow {
+            end: available.end,
+            ..*window
+        }))
+}
+
+/// Fetch the surplus series for a window; windows not relative to now are trimmed to
+/// `[start, end)`
+pub(super) async fn fetch_window_series(
+    state: &AppState,
+    zone_code: &str,
+    window: &TimeWindow,
+    freshness: Freshness,
+) -> Result<SurplusSeries, ApiError> {
+    let (period_start, period_end) = window.period();
+    require_supported(state, zone_code, &SURPLUS_DOCUMENTS)?;
+
+    let mut series = state
+        .client()?
+        .get_surplus_series(zone_code, &period_start, &period_end, freshness)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    if !window.is_relative() {
+        series.retain_between(window.start, window.end);
+    }
+    if series.points.is_empty() {
+        return Err(ApiError::no_data(NO_SURPLUS_POINTS));
+    }
+
+    Ok(series)
+}
+
+/// Fetch the day-ahead series of a window, tolerating a missing forecast, see
+/// [`crate::entsoe::EntsoeClient::get_partial_surplus_series`]. Support is not checked
+/// up front: either forecast may be the one published.
+pub(super) async fn fetch_partial_window_series(
+    state: &AppState,
+    zone_code: &str,
+    window: &TimeWindow,
+    freshness: Freshness,
+    model: &SurplusModel,
+) -> Result<PartialSurplusSeries, ApiError> {
+    if freshness != Freshness::DayAhead {
+        return Err(ApiError::bad_request(
+            "`partial` works on the day-ahead forecasts only",
+        ));
+    }
+    let (period_start, period_end) = window.period();
+    let mut series = state
+        .client()?
+        .get_partial_surplus_series(zone_code, &period_start, &period_end, model)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    if !window.is_relative() {
+        series
+            .points
+            .retain(|point| point.timestamp >= window.start && point.timestamp < window.end);
+    }
+    if series.points.is_empty() {
+        return Err(ApiError::no_data(NO_SURPLUS_POINTS));
+    }
+    Ok(series)
+}
+
+/// Refuse fetching `kinds` of documents in the zone with EIC code `zone_code` when it
+/// does not publish them, instead of asking upstream in vain
+pub(super) fn require_supported(
+    state: &AppState,
+    zone_code: &str,
+    kinds: &[DocumentKind],
+) -> Result<(), ApiError> {
+    let capabilities = state.client()?.capabilities();
+    let now = state.now();
+    match (
+        get_zone_by_code(zone_code),
+        kinds
+            .iter()
+            .find(|&&kind| !capabilities.admits(zone_code, kind, now)),
+    ) {
+        (Some(zone), Some(&kind)) => Err(ApiError::unsupported_for_zone(zone, kind)),
+        _ => Ok(()),
+    }
+}
+
+/// Series of a country the refresher or the document cache already holds
+pub(super) fn warm_series(
+    state: &AppState,
+    country_code: &str,
+    zone: &BiddingZone,
+) -> Option<(Arc<SurplusSeries>, DateTime<Utc>)> {
+    if let Some(event) = state
+        .refresher
+        .as_ref()
+        .and_then(|refresher| refresher.latest(country_code))
+    {
+        return Some((event.series, event.refreshed_at));
+    }
+    let (series, as_of) = state
+        .entsoe_client
+        .as_ref()?
+        .cached_surplus_series(zone.code)?;
+    Some((Arc::new(series), as_of))
+}
+
+/// Validated `hours` of a query looking back from now
+pub(super) fn past_hours(hours: Option<u32>) -> Result<i64, ApiError> {
+    let hours = hours.unwrap_or(24) as i64;
+    if hours == 0 || hours > MAX_QUERY_SPAN_DAYS * 24 {
+        return Err(ApiError::bad_request(format!(
+            "`hours` must be between 1 and {}",
+            MAX_QUERY_SPAN_DAYS * 24
+        )));
+    }
+    Ok(hours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::config::ServerConfig;
+    use crate::entsoe::testing::MockTransport;
+    use crate::entsoe::window::WindowKind;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state, test_state_with_config};
+    use axum::Router;
+    use chrono::TimeZone;
+    use tower::ServiceExt;
+
+    async fn error_message(app: Router, uri: &str) -> (StatusCode, String) {
+        let response = app.oneshot(get_request(uri)).await.unwrap();
+        let status = response.status();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["success"], false);
+        (status, body["error"].as_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_range_validation_errors() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        let cases = [
+            ("start=yesterday", "Invalid `start`"),
+            ("start=2024-06-01T00:00:00Z&end=2024-06-01", "Invalid `end`"),
+            (
+                "start=2024-06-02T00:00:00Z&end=2024-06-01T00:00:00Z",
+                "`start` must be before `end`",
+            ),
+            (
+                "start=2024-06-01T00:00:00Z&end=2024-06-01T00:00:00Z",
+                "`start` must be before `end`",
+            ),
+            (
+                "start=2024-06-01T00:00:00Z&end=2024-07-15T00:00:00Z",
+                "Interval must not exceed 31 days",
+            ),
+            ("end=2024-06-01T00:00:00Z", "`end` requires `start`"),
+            ("window=friday", "Invalid `window`"),
+            (
+                "window=tomorrow&start=2024-06-01T00:00:00Z",
+                "`window` cannot be combined with `start` or `end`",
+            ),
+        ];
+
+        for (query, expected) in cases {
+            for endpoint in ["series", "plot-json", "plot", "plot.png"] {
+                let uri = format!("/api/v1/renewable-surplus/DE/{}?{}", endpoint, query);
+                let (status, message) = error_message(app.clone(), &uri).await;
+                assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+                assert!(message.starts_with(expected), "{}: {}", uri, message);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_explicit_start_defaults_to_one_day() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let app = router(test_state(transport.clone()));
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/series?start=2024-06-01T12:00:00%2B02:00",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        assert_eq!(body["data"]["period_start"], "2024-06-01T10:00:00+00:00");
+        assert_eq!(body["data"]["period_end"], "2024-06-02T10:00:00+00:00");
+        let points = body["data"]["points"].as_array().unwrap();
+        assert_eq!(points.len(), 24);
+        assert_eq!(points[0]["timestamp"], "2024-06-01T10:00:00+00:00");
+
+        // After the two requests telling the availability
+        for url in transport.requests().iter().skip(2) {
+            assert!(url.contains("periodStart=202406011000"), "{}", url);
+            assert!(url.contains("periodEnd=202406021000"), "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_window_tomorrow_covers_the_local_day() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let app = router(test_state(transport.clone()));
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/series?window=tomorrow&tz=UTC",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+        let tomorrow = (Utc::now() + Duration::days(1)).date_naive();
+        let midnight = tomorrow.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        assert_eq!(body["data"]["period_start"], midnight.to_rfc3339());
+        assert_eq!(
+            body["data"]["period_end"],
+            (midnight + Duration::days(1)).to_rfc3339()
+        );
+        let points = body["data"]["points"].as_array().unwrap();
+        assert_eq!(points.len(), 24);
+        assert_eq!(points[0]["timestamp"], midnight.to_rfc3339());
+
+        let period_start = format!("periodStart={}", midnight.format("%Y%m%d%H%M"));
+        for url in transport.requests().iter().skip(2) {
+            assert!(url.contains(&period_start), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_query_window_relative_to_now() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let window =
+            query_window(Some(6), None, None, None, now + Duration::seconds(42), 168).unwrap();
+
+        assert_eq!(window.start, now);
+        assert_eq!(window.end, now + Duration::hours(7));
+        assert_eq!(window.kind, WindowKind::NextHours);
+
+        assert!(query_window(Some(0), None, None, None, now, 168).is_err());
+        assert!(query_window(Some(169), None, None, None, now, 168).is_err());
+        assert!(query_window(None, None, None, None, now, 12).is_err());
+        // `hours` is ignored for explicit windows
+        let explicit = query_window(Some(0), Some("2024-06-01T00:00:00Z"), None, None, now, 168);
+        assert_eq!(explicit.unwrap().kind, WindowKind::Explicit);
+    }
+
+    #[test]
+    fn test_query_window_calendar_day() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let berlin = LocalZone::named("Europe/Berlin").unwrap();
+        let tomorrow = Some((CalendarDay::Tomorrow, berlin));
+
+        // `hours` is ignored like for explicit windows
+        let window = query_window(Some(0), None, None, tomorrow, now, 168).unwrap();
+        assert_eq!(window.start.to_rfc3339(), "2024-06-01T22:00:00+00:00");
+        assert_eq!(window.end.to_rfc3339(), "2024-06-02T22:00:00+00:00");
+        assert_eq!(window.kind, WindowKind::CalendarDay);
+
+        let explicit = query_window(None, Some("2024-06-01T00:00:00Z"), None, tomorrow, now, 168);
+        assert!(explicit.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_max_query_hours_is_configurable() {
+        let config = ServerConfig {
+            max_query_hours: 48,
+            ..ServerConfig::default()
+        };
+        let app = router(test_state_with_config(
+            Arc::new(MockTransport::forecasts()),
+            config,
+        ));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next?hours=48"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next?hours=49"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["error"], "`hours` must be between 1 and 48");
+    }
+
+    #[tokio::test]
+    async fn test_relative_windows_fall_back_to_the_forecast_start() {
+        let noon = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let midnight = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let surplus = |app: Router, uri: &'static str| async move {
+            let response = app.oneshot(get_request(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["success"], true, "{}", body);
+            body["data"].clone()
+        };
+
+        // Published up to 06:00, hours before the frozen clock
+        let app = router(
+            test_state(Arc::new(MockTransport::truncated_forecasts(6)))
+                .with_clock(Arc::new(FixedClock(noon))),
+        );
+        let data = surplus(app.clone(), "/api/v1/renewable-surplus/DE/next-6h").await;
+        assert_eq!(data["reference_time"], midnight.to_rfc3339());
+        assert_eq!(
+            data["timestamp"],
+            (midnight + Duration::hours(5)).to_rfc3339()
+        );
+        assert_eq!(
+            data["filter_applied"],
+            "Next 6 hours from the start of the forecast"
+        );
+
+        // 02:00 to 04:00 in Berlin are the night hours in the data
+        let data = surplus(app, "/api/v1/renewable-surplus/DE/night").await;
+        assert_eq!(data["reference_time"], midnight.to_rfc3339());
+        assert_eq!(
+            data["timestamp"],
+            (midnight + Duration::hours(3)).to_rfc3339()
+        );
+
+        // Within the data, the window starts now
+        let early = midnight + Duration::hours(2);
+        let app = router(
+            test_state(Arc::new(MockTransport::forecasts()))
+                .with_clock(Arc::new(FixedClock(early))),
+        );
+        let data = surplus(app, "/api/v1/renewable-surplus/DE/next-6h").await;
+        assert_eq!(data["reference_time"], early.to_rfc3339());
+        assert_eq!(data["timestamp"], (early + Duration::hours(6)).to_rfc3339());
+        assert_eq!(data["filter_applied"], "Next 6 hours from now");
+    }
+
+    #[test]
+    fn test_requested_local_zone() {
+        let zone = get_primary_zone("PT").unwrap();
+        assert_eq!(
+            requested_local_zone(None, zone).unwrap().name,
+            "Europe/Lisbon"
+        );
+        assert_eq!(
+            requested_local_zone(Some("europe/berlin"), zone)
+                .unwrap()
+                .name,
+            "Europe/Berlin"
+        );
+        assert!(requested_local_zone(Some("Mars/Olympus"), zone).is_err());
+    }
+
+    #[test]
+    fn test_requested_model() {
+        assert!(requested_model(None, None, None).unwrap().is_default());
+
+        let model = requested_model(Some("1500"), Some(250.0), Some(0.8)).unwrap();
+        assert_eq!(model.baseload, Baseload::Constant(1500.0));
+        assert_eq!((model.export, model.efficiency), (250.0, 0.8));
+
+        let hourly = vec!["100"; 24].join(",");
+        assert_eq!(
+            requested_model(Some(&hourly), None, None).unwrap().baseload,
+            Baseload::Hourly([100.0; 24])
+        );
+
+        assert!(requested_model(Some("1,2"), None, None).is_err());
+        assert!(requested_model(Some("lots"), None, None).is_err());
+        assert!(requested_model(None, None, Some(0.0)).is_err());
+        assert!(requested_model(None, None, Some(1.5)).is_err());
+    }
+}
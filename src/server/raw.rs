@@ -0,0 +1,197 @@
+//! Documents as ENTSO-E returned them, for debugging numbers that look wrong. Only
+//! routed with `EDUCK_DEBUG_ENDPOINTS=true`.
+
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Json, Response};
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use super::dto::ApiResponse;
+use super::error::{ApiError, ValidQuery};
+use super::query::{query_window, requested_zone};
+use super::routes::{Access, ApiRoute};
+use crate::entsoe::request::QueryParams;
+use crate::entsoe::{ForecastSource, GlMarketDocument};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawFormat {
+    /// The parsed document
+    #[default]
+    Json,
+    /// The response body
+    Xml,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RawQuery {
+    /// Start of the interval (RFC3339, default: now)
+    start: Option<String>,
+    /// End of the interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// `json` (default) or `xml`
+    format: Option<RawFormat>,
+}
+
+#[derive(Serialize)]
+struct RawResponse {
+    country_code: String,
+    /// The upstream request, with the security token masked
+    request: String,
+    document: GlMarketDocument,
+}
+
+/// The day-ahead document answering `params` over the window of `query`
+async fn raw_document(
+    state: &AppState,
+    country_code: String,
+    params: QueryParams,
+    query: RawQuery,
+) -> Result<Response, ApiError> {
+    let window = query_window(
+        None,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        None,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let client = state.client()?;
+    let request = client.request(params.period(window.start, window.end));
+    let raw = client
+        .fetch_raw(&request)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))?;
+
+    Ok(match query.format.unwrap_or_default() {
+        RawFormat::Xml => (
+            [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+            raw.xml,
+        )
+            .into_response(),
+        RawFormat::Json => Json(ApiResponse::success(RawResponse {
+            country_code,
+            request: request.to_string(),
+            document: raw.document,
+        }))
+        .into_response(),
+    })
+}
+
+/// GET /api/v1/raw/load/{country}?start=..&end=..&format=json|xml
+/// The day-ahead load forecast document (A65)
+async fn raw_load(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<RawQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let params = QueryParams::total_load_forecast(zone.code, ForecastSource::DayAhead);
+    raw_document(&state, country_code, params, query).await
+}
+
+/// GET /api/v1/raw/generation/{country}?start=..&end=..&format=json|xml
+/// The day-ahead wind and solar forecast document (A69)
+async fn raw_generation(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<RawQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let params = QueryParams::generation_forecast(zone.code, ForecastSource::DayAhead);
+    raw_document(&state, country_code, params, query).await
+}
+
+pub(super) fn routes() -> [ApiRoute; 2] {
+    [
+        ApiRoute::get("/api/v1/raw/load/{country}", raw_load)
+            .usage("?start=RFC3339&end=RFC3339&format=json|xml")
+            .access(Access::Debug),
+        ApiRoute::get("/api/v1/raw/generation/{country}", raw_generation)
+            .usage("?start=RFC3339&end=RFC3339&format=json|xml")
+            .access(Access::Debug),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::entsoe::EntsoeClient;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn app(debug_endpoints: bool) -> Router {
+        let config = ServerConfig {
+            debug_endpoints,
+            api_tokens: vec!["secret".to_string()],
+            ..ServerConfig::default()
+        };
+        let client =
+            EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        router(AppState::new(Some(Arc::new(client)), config))
+    }
+
+    async fn get(app: &Router, uri: &str) -> (StatusCode, Option<String>, String) {
+        let request = Request::get(uri)
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_raw_endpoints() {
+        let uri = "/api/v1/raw/load/DE?start=2024-06-01T00:00:00Z";
+        assert_eq!(get(&app(false), uri).await.0, StatusCode::NOT_FOUND);
+
+        let app = app(true);
+        let (status, _, body) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains("test-token"));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let data = &body["data"];
+        assert_eq!(data["document"]["type"], "A65");
+        assert!(
+            data["request"]
+                .as_str()
+                .unwrap()
+                .contains("securityToken=***")
+        );
+
+        let uri = "/api/v1/raw/generation/DE?start=2024-06-01T00:00:00Z&format=xml";
+        let (status, content_type, body) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            content_type.as_deref(),
+            Some("application/xml; charset=utf-8")
+        );
+        assert!(body.contains("<type>A69</type>"));
+
+        // Behind the token check like every other API route
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
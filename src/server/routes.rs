@@ -0,0 +1,319 @@
+//! The route table both the router and the startup listing are built from, and the
+//! version endpoint
+
+use axum::handler::Handler;
+use axum::http::Method;
+use axum::response::Json;
+use axum::routing::{self, MethodRouter};
+use chrono::DateTime;
+use serde::Serialize;
+
+use super::dto::ApiResponse;
+use super::{
+    AppState, alerts, battery, charging, compare, control_areas, dashboard, grafana, grid, ha,
+    health, history, overview, plot, raw, sg_ready, surplus, websocket, zones,
+};
+
+/// Who may call a route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Access {
+    /// Health checks and metrics, never behind API tokens
+    Open,
+    /// Under /api/v1, behind the API tokens and the rate limit
+    Api,
+    /// Like `Api`, but only routed with `EDUCK_DEBUG_ENDPOINTS=true`
+    Debug,
+}
+
+pub(super) struct ApiRoute {
+    pub(super) method: Method,
+    pub(super) path: &'static str,
+    /// Query parameters or a note for the startup listing
+    pub(super) usage: &'static str,
+    pub(super) access: Access,
+    pub(super) handler: MethodRouter<AppState>,
+}
+
+impl ApiRoute {
+    pub(super) fn get<H, T>(path: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, AppState>,
+        T: 'static,
+    {
+        Self::new(Method::GET, path, routing::get(handler))
+    }
+
+    pub(super) fn post<H, T>(path: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, AppState>,
+        T: 'static,
+    {
+        Self::new(Method::POST, path, routing::post(handler))
+    }
+
+    fn new(method: Method, path: &'static str, handler: MethodRouter<AppState>) -> Self {
+        Self {
+            method,
+            path,
+            usage: "",
+            access: Access::Api,
+            handler,
+        }
+    }
+
+    pub(super) fn usage(mut self, usage: &'static str) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub(super) fn access(mut self, access: Access) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Line of the startup listing
+    fn listing(&self) -> String {
+        match self.usage {
+            "" => format!("  {} {}", self.method, self.path),
+            usage if usage.starts_with('?') => format!("  {} {}{}", self.method, self.path, usage),
+            usage => format!("  {} {} ({})", self.method, self.path, usage),
+        }
+    }
+}
+
+/// Every route of the server, in the order of the startup listing
+pub(super) fn table() -> Vec<ApiRoute> {
+    health::routes()
+        .into_iter()
+        .chain(dashboard::routes())
+        .chain([ApiRoute::get("/api/v1/version", get_version)])
+        .chain(zones::routes())
+        .chain(surplus::routes())
+        .chain(plot::routes())
+        .chain(history::routes())
+        .chain(grid::routes())
+        .chain(control_areas::routes())
+        .chain(compare::routes())
+        .chain(battery::routes())
+        .chain(charging::routes())
+        .chain(sg_ready::routes())
+        .chain(overview::routes())
+        .chain(ha::routes())
+        .chain(websocket::routes())
+        .chain(alerts::routes())
+        .chain(grafana::routes())
+        .chain(raw::routes())
+        .collect()
+}
+
+/// Lines of the startup listing of the routes `debug_endpoints` enables
+pub(super) fn listing(routes: &[ApiRoute], debug_endpoints: bool) -> Vec<String> {
+    routes
+        .iter()
+        .filter(|route| debug_endpoints || route.access != Access::Debug)
+        .map(ApiRoute::listing)
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+struct VersionResponse {
+    version: &'static str,
+    /// Commit built from, unknown outside a git checkout without `EDUCK_GIT_COMMIT`
+    git_commit: Option<&'static str>,
+    /// RFC3339
+    built_at: Option<String>,
+    /// Cargo features compiled in
+    features: Vec<&'static str>,
+}
+
+/// Cargo features educk was built with
+fn enabled_features() -> Vec<&'static str> {
+    [
+        ("blocking", cfg!(feature = "blocking")),
+        ("mqtt", cfg!(feature = "mqtt")),
+        ("opentelemetry", cfg!(feature = "opentelemetry")),
+        ("parquet", cfg!(feature = "parquet")),
+        ("sqlite", cfg!(feature = "sqlite")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(feature, _)| feature)
+    .collect()
+}
+
+/// GET /api/v1/version
+/// The running build
+async fn get_version() -> Json<ApiResponse<VersionResponse>> {
+    let built_at = option_env!("EDUCK_BUILD_EPOCH")
+        .and_then(|epoch| epoch.parse().ok())
+        .and_then(|epoch| DateTime::from_timestamp(epoch, 0))
+        .map(|built_at| built_at.to_rfc3339());
+    Json(ApiResponse::success(VersionResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: option_env!("EDUCK_GIT_COMMIT"),
+        built_at,
+        features: enabled_features(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::{Alert, Alerts};
+    use crate::config::ServerConfig;
+    use crate::server::router;
+    use crate::server::tests::{assert_golden, body_bytes, example_uri, get_request, snapshot_app};
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use serde_json::{Value, json};
+    use std::collections::{BTreeMap, HashSet};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    /// Every GET answer of [`snapshot_app`], rewritten by `UPDATE_GOLDEN=1`
+    const RESPONSES: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/api_v1_responses.golden.json"
+    );
+
+    /// Without API key, so data routes answer 503 without going upstream, and with the
+    /// alert [`concrete`] names
+    fn debug_app() -> Router {
+        let config = ServerConfig {
+            debug_endpoints: true,
+            ..ServerConfig::default()
+        };
+        let alerts = Alerts::new(vec![Alert::new("de", "DE", 0.0, Vec::new())]);
+        router(AppState::new(None, config).with_alerts(Arc::new(alerts)))
+    }
+
+    /// `path` with its parameters filled in
+    fn concrete(path: &str) -> String {
+        path.replace("{country}", "DE")
+            .replace("{from}", "DE")
+            .replace("{to}", "FR")
+            .replace("{id}", "de")
+    }
+
+    #[tokio::test]
+    async fn test_every_listed_route_is_routed() {
+        let app = debug_app();
+        let routes = table();
+        let mut seen = HashSet::new();
+        for route in &routes {
+            assert!(
+                seen.insert((route.method.clone(), route.path)),
+                "{} {} is listed twice",
+                route.method,
+                route.path
+            );
+            // Handlers may refuse the bare request, but only unrouted paths give 404
+            let request = Request::builder()
+                .method(route.method.clone())
+                .uri(concrete(route.path))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_ne!(
+                response.status(),
+                StatusCode::NOT_FOUND,
+                "{} is not routed",
+                route.path
+            );
+        }
+
+        let listing = listing(&routes, false);
+        assert_eq!(listing.len(), routes.len() - 2);
+        assert!(listing.contains(&"  GET /api/v1/version".to_string()));
+        assert!(listing.iter().all(|line| !line.contains("/raw/")));
+
+        let request = Request::get("/api/v1/unknown").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_version_endpoint() {
+        let request = Request::get("/api/v1/version").body(Body::empty()).unwrap();
+        let response = debug_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let data = &body["data"];
+        assert_eq!(data["version"], env!("CARGO_PKG_VERSION"));
+        assert!(DateTime::parse_from_rfc3339(data["built_at"].as_str().unwrap()).is_ok());
+        assert_eq!(
+            data["features"].as_array().unwrap().len(),
+            enabled_features().len()
+        );
+    }
+
+    /// Keys holding the wall clock time data was fetched at, not the time of the test clock
+    const FETCHED_AT: [&str; 2] = ["as_of", "last_successful_fetch"];
+
+    /// Mask the fetch times and the generated ids of the requests
+    fn mask_varying(value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    if FETCHED_AT.contains(&key.as_str()) && value.is_string() {
+                        *value = json!("fetch time");
+                    } else if key == "request_id" {
+                        *value = json!("request id");
+                    } else {
+                        mask_varying(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(mask_varying),
+            _ => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_route_answers_as_recorded() {
+        let app = snapshot_app();
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut responses = BTreeMap::new();
+        for route in table() {
+            if route.method != Method::GET || route.path == "/api/v1/ws/{country}" {
+                continue;
+            }
+            let uri = example_uri(route.path);
+            let response = app.clone().oneshot(get_request(&uri)).await.unwrap();
+            let status = response.status().as_u16();
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .map(|value| value.to_str().unwrap().to_string());
+            let bytes = body_bytes(response).await;
+            let mut body = match (
+                serde_json::from_slice::<Value>(&bytes),
+                String::from_utf8(bytes),
+            ) {
+                // The build is pinned by `test_version_endpoint`
+                (Ok(body), _) if route.path == "/api/v1/version" => json!(body["success"]),
+                (Ok(body), _) => body,
+                (Err(_), Ok(text)) => json!(text),
+                (Err(_), Err(e)) => json!(format!("{} bytes", e.into_bytes().len())),
+            };
+            mask_varying(&mut body);
+            responses.insert(
+                route.path,
+                json!({"uri": uri, "status": status, "content_type": content_type, "body": body}),
+            );
+        }
+
+        assert_golden(RESPONSES, &responses);
+    }
+}
@@ -0,0 +1,295 @@
+//! Versions of the response schema. Every answer states the version of its shape in
+//! `schema_version`; clients pin one by path (`/api/v1/...`) or by
+//! `Accept: application/vnd.educk.v1+json`, and versions that are not served are refused
+//! up front instead of answered in another shape.
+//!
+//! Only v1 is served. Its shape is pinned by the snapshot below, so a breaking change,
+//! e.g. enums instead of strings, has to land under a new version next to it.
+
+use axum::extract::Request;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use super::error::ApiError;
+
+/// Version of the shape of the responses of `/api/v1`
+pub(super) const SCHEMA_VERSION: u32 = 1;
+/// Versions with routes, oldest first
+const SERVED_VERSIONS: [u32; 1] = [SCHEMA_VERSION];
+/// Media type of version N is `application/vnd.educk.vN+json`
+const MEDIA_TYPE_PREFIX: &str = "application/vnd.educk.";
+const MEDIA_TYPE_SUFFIX: &str = "+json";
+/// Response header naming the schema version a request was answered in
+const SCHEMA_VERSION_HEADER: &str = "x-educk-schema-version";
+
+/// Schema version negotiated for a request, in its extensions for handlers that answer
+/// differently per version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ApiVersion(pub(super) u32);
+
+/// Version in a path like `/api/v2/...`
+fn path_version(path: &str) -> Option<u32> {
+    let rest = path.strip_prefix("/api/v")?;
+    let digits = rest.split('/').next()?;
+    digits.parse().ok()
+}
+
+/// Versions of the educk media types in `Accept`, `Err` with a media type of ours
+/// without a version number
+fn accepted_versions(headers: &HeaderMap) -> Result<Vec<u32>, String> {
+    let mut versions = Vec::new();
+    for media_type in headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|media_range| media_range.split(';').next().unwrap_or("").trim())
+    {
+        let lowercase = media_type.to_ascii_lowercase();
+        let Some(rest) = lowercase.strip_prefix(MEDIA_TYPE_PREFIX) else {
+            continue;
+        };
+        let version = rest
+            .strip_suffix(MEDIA_TYPE_SUFFIX)
+            .and_then(|version| version.strip_prefix('v'))
+            .and_then(|version| version.parse().ok())
+            .ok_or_else(|| format!("Unknown media type {}, served: {}", media_type, served()))?;
+        versions.push(version);
+    }
+    Ok(versions)
+}
+
+fn served() -> String {
+    SERVED_VERSIONS
+        .iter()
+        .map(|version| format!("v{}", version))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The version a request asks for: the one in its path, else the newest served one of
+/// its `Accept` header, else the current one
+fn negotiate(path: &str, headers: &HeaderMap) -> Result<u32, ApiError> {
+    let accepted = accepted_versions(headers)
+        .map_err(|message| ApiError::new(StatusCode::NOT_ACCEPTABLE, message))?;
+    let acceptable = |version: &u32| accepted.is_empty() || accepted.contains(version);
+
+    if let Some(version) = path_version(path) {
+        if !SERVED_VERSIONS.contains(&version) {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                format!("API v{} does not exist, served: {}", version, served()),
+            ));
+        }
+        if !acceptable(&version) {
+            return Err(ApiError::new(
+                StatusCode::NOT_ACCEPTABLE,
+                format!(
+                    "/api/v{} answers in schema version {} only",
+                    version, version
+                ),
+            ));
+        }
+        return Ok(version);
+    }
+    SERVED_VERSIONS
+        .iter()
+        .rev()
+        .copied()
+        .find(acceptable)
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_ACCEPTABLE,
+                format!("Schema versions served: {}", served()),
+            )
+        })
+}
+
+/// Refuse requests for versions that are not served and tell the handlers which one was
+/// negotiated
+pub(super) async fn negotiate_version(mut request: Request, next: Next) -> Response {
+    let version = match negotiate(request.uri().path(), request.headers()) {
+        Ok(version) => version,
+        Err(e) => return e.into_response(),
+    };
+    request.extensions_mut().insert(ApiVersion(version));
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(SCHEMA_VERSION_HEADER, HeaderValue::from(version));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::routes;
+    use crate::server::tests::{assert_golden, body_bytes, example_uri, get_request, snapshot_app};
+    use axum::http::Method;
+    use serde_json::{Value, json};
+    use std::collections::BTreeMap;
+    use tower::ServiceExt;
+
+    /// Snapshot of the v1 response shapes, rewritten by `UPDATE_GOLDEN=1`
+    const V1_SNAPSHOT: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/api_v1_shapes.golden.json"
+    );
+
+    /// Routes the snapshot leaves out: the WebSocket answers frames, not responses
+    const UNSNAPSHOTTED: [&str; 1] = ["/api/v1/ws/{country}"];
+
+    /// Routes whose data describe the build, e.g. its cargo features, of which only the
+    /// keys are pinned
+    const BUILD_DEPENDENT: [&str; 1] = ["/api/v1/version"];
+
+    /// Keys and JSON types of `value`, with arrays reduced to their first element
+    fn shape(value: &Value) -> Value {
+        match value {
+            Value::Null => json!("null"),
+            Value::Bool(_) => json!("boolean"),
+            Value::Number(_) => json!("number"),
+            Value::String(_) => json!("string"),
+            Value::Array(items) => Value::Array(items.first().map(shape).into_iter().collect()),
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), shape(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_v1_response_shapes_match_snapshot() {
+        let app = snapshot_app();
+        // Fetch once so the routes answering from cached data have some to show
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut shapes = BTreeMap::new();
+        for route in routes::table() {
+            if route.method != Method::GET || UNSNAPSHOTTED.contains(&route.path) {
+                continue;
+            }
+            let uri = example_uri(route.path);
+            let response = app.clone().oneshot(get_request(&uri)).await.unwrap();
+            let status = response.status().as_u16();
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .map(|value| value.to_str().unwrap().to_string());
+            let bytes = body_bytes(response).await;
+            let body = match serde_json::from_slice::<Value>(&bytes) {
+                Ok(body) if BUILD_DEPENDENT.contains(&route.path) => {
+                    let mut body_shape = shape(&body);
+                    for value in body_shape["data"].as_object_mut().unwrap().values_mut() {
+                        *value = json!("build dependent");
+                    }
+                    body_shape
+                }
+                Ok(body) => shape(&body),
+                // Plots, CSV, metrics and raw documents: only the status and content type
+                Err(_) => Value::Null,
+            };
+            shapes.insert(
+                route.path,
+                json!({"uri": uri, "status": status, "content_type": content_type, "body": body}),
+            );
+        }
+
+        // v1 has to keep its shape: breaking changes go into a new schema version, only
+        // additive ones are recorded with UPDATE_GOLDEN=1
+        assert_golden(V1_SNAPSHOT, &shapes);
+    }
+
+    #[tokio::test]
+    async fn test_schema_version_is_negotiated() {
+        let app = snapshot_app();
+        let request = |uri: &str, accept: Option<&str>| {
+            let mut request = get_request(uri);
+            if let Some(accept) = accept {
+                request
+                    .headers_mut()
+                    .insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+            }
+            app.clone().oneshot(request)
+        };
+
+        for accept in [
+            None,
+            Some("application/json"),
+            Some("application/vnd.educk.v1+json"),
+            Some("application/vnd.educk.v2+json, application/vnd.educk.v1+json;q=0.5"),
+        ] {
+            let response = request("/api/v1/countries", accept).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{:?}", accept);
+            assert_eq!(response.headers()[SCHEMA_VERSION_HEADER], "1");
+            let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["schema_version"], SCHEMA_VERSION);
+        }
+        // Errors are in the envelope of the version too
+        let response = request("/api/v1/renewable-surplus/XX/now", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["schema_version"], SCHEMA_VERSION);
+
+        for (uri, accept, status) in [
+            (
+                "/api/v1/countries",
+                "application/vnd.educk.v2+json",
+                StatusCode::NOT_ACCEPTABLE,
+            ),
+            (
+                "/api/v1/countries",
+                "application/vnd.educk.latest+json",
+                StatusCode::NOT_ACCEPTABLE,
+            ),
+            (
+                "/api/v2/countries",
+                "application/json",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                "/health",
+                "application/vnd.educk.v7+json",
+                StatusCode::NOT_ACCEPTABLE,
+            ),
+        ] {
+            let response = request(uri, Some(accept)).await.unwrap();
+            assert_eq!(response.status(), status, "{} {}", uri, accept);
+            let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(body["success"], false);
+            assert!(body["error"].as_str().unwrap().contains("v1"), "{}", body);
+        }
+        // Outside the API, without a version asked for, nothing changes
+        let response = request("/health", None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_versions_in_paths_and_media_types() {
+        assert_eq!(path_version("/api/v1/countries"), Some(1));
+        assert_eq!(path_version("/api/v12"), Some(12));
+        assert_eq!(path_version("/api/vx/countries"), None);
+        assert_eq!(path_version("/health"), None);
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::ACCEPT,
+            HeaderValue::from_static("text/html, Application/Vnd.Educk.V2+JSON;q=0.9"),
+        );
+        headers.append(
+            header::ACCEPT,
+            HeaderValue::from_static("application/vnd.educk.v1+json"),
+        );
+        assert_eq!(accepted_versions(&headers), Ok(vec![2, 1]));
+    }
+}
@@ -0,0 +1,236 @@
+//! SG Ready states for heat pumps and heating rods, derived from the surplus forecast
+//! with hysteresis so the signal does not flap
+
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use super::dto::{ApiResponse, ForecastInfo, conditional_json};
+use super::error::{ApiError, ValidQuery};
+use super::query::{fetch_window_series, query_window, requested_freshness, requested_zone};
+use super::routes::ApiRoute;
+use crate::entsoe::analysis::{
+    Freshness, SgReadyPoint, SgReadyState, SgThresholds, sg_ready_signal,
+};
+
+#[derive(Deserialize)]
+pub(super) struct SgReadyQuery {
+    /// Number of hours of forecast states (default: 24)
+    hours: Option<u32>,
+    /// Surplus in MW where state 2 begins, default: `EDUCK_SG_READY_THRESHOLDS`
+    normal_from: Option<f64>,
+    /// Where state 3 begins
+    recommended_from: Option<f64>,
+    /// Where state 4 begins
+    forced_from: Option<f64>,
+    /// MW, default: `EDUCK_SG_READY_HYSTERESIS`
+    hysteresis: Option<f64>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct SgStateResponse {
+    /// 1 to 4
+    state: u8,
+    /// `locked`, `normal`, `recommended` or `forced`
+    name: &'static str,
+}
+
+impl From<SgReadyState> for SgStateResponse {
+    fn from(state: SgReadyState) -> Self {
+        Self {
+            state: state.number(),
+            name: state.name(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct SgReadyPointResponse {
+    timestamp: String,
+    surplus_mw: f64,
+    #[serde(flatten)]
+    state: SgStateResponse,
+}
+
+impl From<&SgReadyPoint> for SgReadyPointResponse {
+    fn from(point: &SgReadyPoint) -> Self {
+        Self {
+            timestamp: point.timestamp.to_rfc3339(),
+            surplus_mw: point.surplus,
+            state: point.state.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct ThresholdsResponse {
+    normal_from_mw: f64,
+    recommended_from_mw: f64,
+    forced_from_mw: f64,
+    hysteresis_mw: f64,
+}
+
+#[derive(Serialize)]
+pub(super) struct SgReadyResponse {
+    country_code: String,
+    /// State now
+    current: SgReadyPointResponse,
+    thresholds: ThresholdsResponse,
+    /// From the current point on
+    series: Vec<SgReadyPointResponse>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+}
+
+/// GET /api/v1/sg-ready/:country?hours=24&recommended_from=0&hysteresis=1000
+/// Current SG Ready state and the states forecast for the next hours
+async fn get_sg_ready(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<SgReadyQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let defaults = state.config.sg_ready_thresholds;
+    let thresholds = SgThresholds {
+        normal_from: query.normal_from.unwrap_or(defaults.normal_from),
+        recommended_from: query.recommended_from.unwrap_or(defaults.recommended_from),
+        forced_from: query.forced_from.unwrap_or(defaults.forced_from),
+    };
+    thresholds.validate().map_err(ApiError::bad_request)?;
+    let hysteresis = query
+        .hysteresis
+        .unwrap_or(state.config.sg_ready_hysteresis_mw);
+    if !(hysteresis.is_finite() && hysteresis >= 0.0) {
+        return Err(ApiError::bad_request("`hysteresis` must not be negative"));
+    }
+
+    let now = state.now();
+    let hours = query.hours.unwrap_or(24);
+    let window = query_window(
+        Some(hours),
+        None,
+        None,
+        None,
+        now,
+        state.config.max_query_hours,
+    )?;
+    let series = fetch_window_series(
+        &state,
+        zone.code,
+        &window,
+        requested_freshness(query.freshness, query.use_intraday),
+    )
+    .await?;
+
+    // Earlier points of the fetched documents settle the hysteresis before now
+    let end = now + Duration::hours(hours as i64);
+    let points: Vec<_> = series
+        .points
+        .iter()
+        .filter(|point| point.timestamp <= end)
+        .cloned()
+        .collect();
+    let signal = sg_ready_signal(&points, &thresholds, hysteresis);
+    let current = signal
+        .iter()
+        .rposition(|point| point.timestamp <= now)
+        .unwrap_or(0);
+    let Some(current_point) = signal.get(current) else {
+        return Err(ApiError::no_data(
+            "No surplus points up to the end of the window",
+        ));
+    };
+
+    let response = SgReadyResponse {
+        country_code: country_code.to_ascii_uppercase(),
+        current: current_point.into(),
+        thresholds: ThresholdsResponse {
+            normal_from_mw: thresholds.normal_from,
+            recommended_from_mw: thresholds.recommended_from,
+            forced_from_mw: thresholds.forced_from,
+            hysteresis_mw: hysteresis,
+        },
+        series: signal[current..].iter().map(Into::into).collect(),
+        forecast: (&series).into(),
+    };
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [ApiRoute::get("/api/v1/sg-ready/{country}", get_sg_ready)
+        .usage("?hours=24&recommended_from=0&hysteresis=1000")]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::FixedClock;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state};
+    use axum::http::StatusCode;
+    use chrono::{TimeZone, Utc};
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_sg_ready_endpoint() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 5, 30, 0).unwrap();
+        let app = router(
+            test_state(Arc::new(MockTransport::forecasts())).with_clock(Arc::new(FixedClock(now))),
+        );
+        let get = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(get_request(uri)).await.unwrap();
+                let status = response.status();
+                let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+                (status, body)
+            }
+        };
+
+        // The mock surplus rises from -10 GW at midnight by 1 GW an hour
+        let (status, body) = get("/api/v1/sg-ready/de?hours=12&recommended_from=-2000").await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let data = &body["data"];
+        assert_eq!(data["current"]["timestamp"], "2024-06-01T05:00:00+00:00");
+        assert_eq!(data["current"]["state"], 2);
+        assert_eq!(data["current"]["name"], "normal");
+        assert_eq!(data["thresholds"]["recommended_from_mw"], -2000.0);
+        assert_eq!(data["thresholds"]["hysteresis_mw"], 1000.0);
+        let states: Vec<_> = data["series"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|point| point["state"].as_u64().unwrap())
+            .collect();
+        // 05:00 to 17:00: -2 GW is reached at 08:00, but passed by half the hysteresis
+        // only at 09:00; 10 GW is never reached
+        assert_eq!(states.len(), 13);
+        assert_eq!(states[..5], [2, 2, 2, 2, 3]);
+        assert!(states[4..].iter().all(|&state| state == 3));
+
+        for uri in [
+            "/api/v1/sg-ready/DE?recommended_from=20000",
+            "/api/v1/sg-ready/DE?hysteresis=-1",
+            "/api/v1/sg-ready/XX",
+        ] {
+            let (status, body) = get(uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+            assert_eq!(body["success"], false);
+        }
+    }
+}
@@ -0,0 +1,1859 @@
+//! Surplus forecasts of a zone: maxima, series, the current value, deficits, revisions,
+//! availability and the cached summary
+
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Json, Response};
+use chrono::Duration;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use super::dto::{
+    ApiResponse, CoverageResponse, ForecastInfo, IntervalResponse, MaxSurplusResponse,
+    PartialSeriesPoint, SeriesPoint, SourceSegmentResponse, SurplusModelResponse, SurplusPoint,
+    checked_coverage, conditional_json,
+};
+use super::error::{ApiError, NO_SURPLUS_POINTS, ValidQuery};
+use super::query::{
+    FreshnessQuery, TimeQuery, clamped_window, fetch_partial_window_series, fetch_window_series,
+    query_window, requested_day, requested_error_stats, requested_freshness, requested_local_zone,
+    requested_model, requested_zone, warm_series,
+};
+use super::routes::ApiRoute;
+use crate::entsoe::analysis::{
+    DailyHours, ForecastSide, Freshness, Interpolation, Interval, RenewableSurplus, SurplusDiff,
+    SurplusPoints, SurplusWindow, best_window, diff_series, find_deficit_windows, find_min_surplus,
+    max_surplus, value_at, window_robustness,
+};
+use crate::entsoe::areas::BiddingZone;
+use crate::entsoe::availability::DocumentAvailability;
+use crate::entsoe::csv_writer::CsvOptions;
+use crate::entsoe::localtime::LocalZone;
+use crate::entsoe::request::QueryParams;
+use crate::entsoe::window::{CalendarDay, TimeWindow};
+use crate::entsoe::{ForecastKind, PowerUnit, areas};
+use crate::storage::surplus_as_of;
+
+/// GET /api/v1/renewable-surplus/:country/night
+/// Find maximum renewable surplus during night hours (22:00-06:00)
+async fn get_night_surplus(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<FreshnessQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let model = requested_model(
+        query.baseload_mw.as_deref(),
+        query.export_mw,
+        query.efficiency,
+    )?;
+
+    let local = requested_local_zone(query.tz.as_deref(), zone)?;
+    let window = match query.window {
+        Some(day) => TimeWindow::calendar_day_from(state.now(), day, &local),
+        // Look ahead 48 hours to ensure we have night hours
+        None => TimeWindow::next_hours_from(state.now(), 48),
+    };
+    let mut series = fetch_window_series(
+        &state,
+        zone.code,
+        &window,
+        requested_freshness(query.freshness, query.use_intraday),
+    )
+    .await?;
+    let coverage = checked_coverage(&series, zone.code, window.start, window.end, query.strict)?;
+    model.apply(&mut series.points);
+
+    let reference = query
+        .window
+        .is_none()
+        .then(|| series.reference_time(state.now()));
+    let night_max = match reference {
+        Some(reference) => series
+            .points
+            .iter()
+            .within(reference, 48)
+            .night_hours(DailyHours::NIGHT, &local)
+            .max_surplus(),
+        None => series
+            .points
+            .iter()
+            .night_hours(DailyHours::NIGHT, &local)
+            .max_surplus(),
+    };
+
+    if let Some(max_surplus) = night_max {
+        let timestamp = max_surplus.timestamp;
+        let mut response = MaxSurplusResponse::from(max_surplus.clone())
+            .with_series(&series, timestamp)
+            .in_zone(&local, timestamp);
+        response.country_code = country_code.to_ascii_uppercase();
+        response.filter_applied = "Night hours (22:00-06:00)".to_string();
+        response.surplus_model = SurplusModelResponse::echo(&model);
+        response.coverage = Some(coverage);
+        response.reference_time = reference.map(|reference| reference.to_rfc3339());
+
+        Ok(conditional_json(
+            &headers,
+            &state.config,
+            ApiResponse::success(response),
+        ))
+    } else {
+        Err(ApiError::no_data("No night hours found in forecast period"))
+    }
+}
+
+/// GET /api/v1/renewable-surplus/:country/next-6h
+/// Find maximum renewable surplus within the next 6 hours
+async fn get_next_6h_surplus(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<FreshnessQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    get_next_hours_surplus(state, &headers, &country_code, 6, query).await
+}
+
+/// GET /api/v1/renewable-surplus/:country/next-24h
+/// Find maximum renewable surplus within the next 24 hours
+async fn get_next_24h_surplus(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<FreshnessQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    get_next_hours_surplus(state, &headers, &country_code, 24, query).await
+}
+
+/// GET /api/v1/renewable-surplus/:country/next?hours=N
+/// Find maximum renewable surplus within the next N hours (custom)
+async fn get_custom_hours_surplus(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<TimeQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let hours = query.hours.unwrap_or(24);
+    let query = FreshnessQuery {
+        window: query.window,
+        freshness: query.freshness,
+        use_intraday: query.use_intraday,
+        baseload_mw: query.baseload_mw,
+        export_mw: query.export_mw,
+        efficiency: query.efficiency,
+        strict: query.strict,
+        tz: query.tz,
+    };
+    get_next_hours_surplus(state, &headers, &country_code, hours, query).await
+}
+
+/// Helper function to get surplus for next N hours
+async fn get_next_hours_surplus(
+    state: AppState,
+    headers: &HeaderMap,
+    country_code: &str,
+    hours: u32,
+    query: FreshnessQuery,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(country_code)?;
+    let freshness = requested_freshness(query.freshness, query.use_intraday);
+    let model = requested_model(
+        query.baseload_mw.as_deref(),
+        query.export_mw,
+        query.efficiency,
+    )?;
+    let local = requested_local_zone(query.tz.as_deref(), zone)?;
+
+    let window = query_window(
+        Some(hours),
+        None,
+        None,
+        query.window.map(|day| (day, local)),
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let mut series = fetch_window_series(&state, zone.code, &window, freshness).await?;
+    let coverage_end = match window.is_relative() {
+        true => window.start + Duration::hours(hours as i64),
+        false => window.end,
+    };
+    let coverage = checked_coverage(&series, zone.code, window.start, coverage_end, query.strict)?;
+    model.apply(&mut series.points);
+
+    let now = state.now();
+    let reference = query.window.is_none().then(|| series.reference_time(now));
+    let (max_surplus, filter_applied) = match (query.window, reference) {
+        (Some(day), _) => (
+            series.points.iter().max_surplus(),
+            format!("{:?} in {}", day, local.name),
+        ),
+        (None, Some(reference)) if reference != now => (
+            series.points.iter().within(reference, hours).max_surplus(),
+            format!("Next {} hours from the start of the forecast", hours),
+        ),
+        (None, _) => (
+            series.points.iter().within(now, hours).max_surplus(),
+            format!("Next {} hours from now", hours),
+        ),
+    };
+
+    if let Some(max_surplus) = max_surplus {
+        let timestamp = max_surplus.timestamp;
+        let mut response = MaxSurplusResponse::from(max_surplus.clone())
+            .with_series(&series, timestamp)
+            .in_zone(&local, timestamp);
+        response.country_code = country_code.to_ascii_uppercase();
+        response.filter_applied = filter_applied;
+        response.surplus_model = SurplusModelResponse::echo(&model);
+        response.coverage = Some(coverage);
+        response.reference_time = reference.map(|reference| reference.to_rfc3339());
+
+        Ok(conditional_json(
+            headers,
+            &state.config,
+            ApiResponse::success(response),
+        ))
+    } else {
+        Err(ApiError::no_data(format!(
+            "No data found for next {} hours",
+            hours
+        )))
+    }
+}
+
+#[derive(Serialize)]
+struct SeriesResponse {
+    country_code: String,
+    period_start: String,
+    period_end: String,
+    /// Of the `timestamp_local` fields
+    timezone: &'static str,
+    /// Of the power values of `points`, whatever their `_mw` suffix
+    unit: PowerUnit,
+    points: Vec<SeriesPoint>,
+    sources: Vec<SourceSegmentResponse>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    surplus_model: Option<SurplusModelResponse>,
+    coverage: CoverageResponse,
+}
+
+/// Answer of `/series` with `partial=true`
+#[derive(Serialize)]
+struct PartialSeriesResponse {
+    country_code: String,
+    period_start: String,
+    period_end: String,
+    /// Of the `timestamp_local` fields
+    timezone: &'static str,
+    /// Of the power values of `points`, whatever their `_mw` suffix
+    unit: PowerUnit,
+    /// Forecast not published for the period, `null` if both are
+    missing: Option<ForecastSide>,
+    points: Vec<PartialSeriesPoint>,
+}
+
+/// Query of `/series`. Parameters of other endpoints are refused rather than ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeriesQuery {
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+    /// Must-run generation added to wind and solar: one value or 24 hourly (UTC) values,
+    /// comma separated
+    baseload_mw: Option<String>,
+    /// Assumed exports subtracted from the surplus
+    export_mw: Option<f64>,
+    /// Usable share of a positive surplus (0 < efficiency <= 1)
+    efficiency: Option<f64>,
+    /// IANA time zone of the `timestamp_local` fields (default: the zone's market time)
+    tz: Option<String>,
+    /// Fail with 404 unless the forecasts cover the whole requested window
+    #[serde(default)]
+    strict: bool,
+    /// Unit of the power values: `MW` (default), `GW` or `kW`
+    #[serde(default)]
+    unit: PowerUnit,
+    /// Keep the points of a forecast published without the other, the missing values
+    /// `null` (day-ahead forecasts)
+    #[serde(default)]
+    partial: bool,
+}
+
+/// GET /api/v1/renewable-surplus/:country/series?hours=N or ?start=..&end=..
+/// Get the full surplus series for the next N hours or an explicit interval; with
+/// `partial=true` also the points of one forecast when the other is missing
+async fn get_series(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<SeriesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, query.tz.as_deref(), zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+
+    let freshness = requested_freshness(query.freshness, query.use_intraday);
+    let model = requested_model(
+        query.baseload_mw.as_deref(),
+        query.export_mw,
+        query.efficiency,
+    )?;
+    let local = requested_local_zone(query.tz.as_deref(), zone)?;
+    if query.partial {
+        if query.strict {
+            return Err(ApiError::bad_request(
+                "`strict` and `partial` exclude each other",
+            ));
+        }
+        let series =
+            fetch_partial_window_series(&state, zone.code, &window, freshness, &model).await?;
+        let response = PartialSeriesResponse {
+            country_code,
+            period_start: window.start.to_rfc3339(),
+            period_end: window.end.to_rfc3339(),
+            timezone: local.name,
+            unit: query.unit,
+            missing: series.missing,
+            points: series
+                .points
+                .iter()
+                .map(|point| PartialSeriesPoint::in_zone(&point.in_power_unit(query.unit), &local))
+                .collect(),
+        };
+        return Ok(conditional_json(
+            &headers,
+            &state.config,
+            ApiResponse::success(response),
+        ));
+    }
+
+    let clamped = clamped_window(&state, zone.code, &window).await?;
+    let mut series = fetch_window_series(
+        &state,
+        zone.code,
+        clamped.as_ref().unwrap_or(&window),
+        freshness,
+    )
+    .await?;
+    let mut coverage =
+        checked_coverage(&series, zone.code, window.start, window.end, query.strict)?;
+    coverage.clamped_to = clamped.map(|clamped| {
+        (&Interval {
+            start: clamped.start,
+            end: clamped.end,
+        })
+            .into()
+    });
+    model.apply(&mut series.points);
+
+    if series.points.is_empty() {
+        return Err(ApiError::no_data(NO_SURPLUS_POINTS));
+    }
+
+    let response = SeriesResponse {
+        country_code,
+        period_start: window.start.to_rfc3339(),
+        period_end: window.end.to_rfc3339(),
+        timezone: local.name,
+        unit: query.unit,
+        points: series
+            .points
+            .iter()
+            .map(|point| SeriesPoint::in_zone(&point.in_power_unit(query.unit), &local))
+            .collect(),
+        sources: series.sources.iter().map(Into::into).collect(),
+        forecast: (&series).into(),
+        surplus_model: SurplusModelResponse::echo(&model),
+        coverage,
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+/// Length of the best window without `duration`
+const DEFAULT_WINDOW_HOURS: u32 = 3;
+
+#[derive(Serialize)]
+struct BestWindowResponse {
+    country_code: String,
+    start: String,
+    end: String,
+    duration_hours: u32,
+    mean_surplus_mw: f64,
+    /// Probability by the stored forecast errors that the surplus stays positive over
+    /// the window, with `band=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    robustness: Option<f64>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    surplus_model: Option<SurplusModelResponse>,
+}
+
+/// Query of `/best-window`. Parameters of other endpoints are refused rather than ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BestWindowQuery {
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+    /// Must-run generation added to wind and solar: one value or 24 hourly (UTC) values,
+    /// comma separated
+    baseload_mw: Option<String>,
+    /// Assumed exports subtracted from the surplus
+    export_mw: Option<f64>,
+    /// Usable share of a positive surplus (0 < efficiency <= 1)
+    efficiency: Option<f64>,
+    /// IANA time zone of the `timestamp_local` fields (default: the zone's market time)
+    tz: Option<String>,
+    /// Length of the window in hours (default: 3)
+    duration: Option<u32>,
+    /// Add the probability that the surplus stays positive by the stored forecast errors
+    #[serde(default)]
+    band: bool,
+}
+
+/// GET /api/v1/renewable-surplus/:country/best-window?duration=3&hours=24
+/// The stretch of `duration` hours with the highest mean surplus
+async fn get_best_window(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<BestWindowQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, query.tz.as_deref(), zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let duration_hours = query.duration.unwrap_or(DEFAULT_WINDOW_HOURS);
+    let length = Duration::hours(duration_hours as i64);
+    if duration_hours == 0 || length > window.end - window.start {
+        return Err(ApiError::bad_request(
+            "`duration` must be at least 1 hour and fit into the requested window",
+        ));
+    }
+
+    let freshness = requested_freshness(query.freshness, query.use_intraday);
+    let model = requested_model(
+        query.baseload_mw.as_deref(),
+        query.export_mw,
+        query.efficiency,
+    )?;
+    let mut series = fetch_window_series(&state, zone.code, &window, freshness).await?;
+    model.apply(&mut series.points);
+    let stats = requested_error_stats(&state, zone, query.band).await?;
+
+    let Some((start, mean)) = best_window(&series.points, length) else {
+        return Err(ApiError::no_data(format!(
+            "The forecasts cover no {} hour window",
+            duration_hours
+        )));
+    };
+    let response = BestWindowResponse {
+        country_code,
+        start: start.to_rfc3339(),
+        end: (start + length).to_rfc3339(),
+        duration_hours,
+        mean_surplus_mw: mean,
+        robustness: stats
+            .and_then(|stats| window_robustness(&series.points, start, length, &stats)),
+        forecast: (&series).into(),
+        surplus_model: SurplusModelResponse::echo(&model),
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+#[derive(Serialize)]
+struct DocumentAvailabilityResponse {
+    /// Code the document was requested under
+    code: &'static str,
+    start: String,
+    end: String,
+    published: String,
+}
+
+impl DocumentAvailabilityResponse {
+    fn new(code: &'static str, document: &DocumentAvailability) -> Self {
+        Self {
+            code,
+            start: document.interval.start.to_rfc3339(),
+            end: document.interval.end.to_rfc3339(),
+            published: document.published.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AvailabilityResponse {
+    country_code: String,
+    /// Day-ahead total load forecast (A65)
+    load: DocumentAvailabilityResponse,
+    /// Day-ahead wind and solar forecast (A69)
+    generation: DocumentAvailabilityResponse,
+    /// Where both are available, `null` if nowhere
+    surplus: Option<IntervalResponse>,
+}
+
+/// GET /api/v1/renewable-surplus/:country/availability
+/// How far the day-ahead forecasts reach, from yesterday to three days ahead
+async fn get_availability(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+) -> Result<Json<ApiResponse<AvailabilityResponse>>, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let availability = state
+        .client()?
+        .availability(zone.code)
+        .await
+        .map_err(|e| ApiError::upstream(e, StatusCode::BAD_GATEWAY))?;
+
+    Ok(Json(ApiResponse::success(AvailabilityResponse {
+        country_code,
+        load: DocumentAvailabilityResponse::new(areas::load_code(zone.code), &availability.load),
+        generation: DocumentAvailabilityResponse::new(
+            areas::generation_code(zone.code),
+            &availability.generation,
+        ),
+        surplus: availability.surplus().as_ref().map(Into::into),
+    })))
+}
+
+/// How far a current point may lie from the baseline point it is compared with
+const DIFF_TOLERANCE: Duration = Duration::minutes(30);
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    /// Age of the baseline forecast, e.g. `6h` (default) or `90m`
+    against: Option<String>,
+    /// Number of hours to look ahead (default: 24)
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+}
+
+#[derive(Serialize)]
+struct DiffPointResponse {
+    timestamp: String,
+    current_mw: f64,
+    baseline_mw: f64,
+    /// `current_mw - baseline_mw`
+    revision_mw: f64,
+}
+
+impl From<&SurplusDiff> for DiffPointResponse {
+    fn from(diff: &SurplusDiff) -> Self {
+        Self {
+            timestamp: diff.timestamp.to_rfc3339(),
+            current_mw: diff.current,
+            baseline_mw: diff.baseline,
+            revision_mw: diff.revision,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SurplusDiffResponse {
+    country_code: String,
+    period_start: String,
+    period_end: String,
+    /// The requested `against`
+    against: String,
+    /// When the newest document of the baseline was fetched
+    baseline_fetched_at: String,
+    /// Largest upward revision, `null` if nothing was revised up
+    max_upward_revision: Option<DiffPointResponse>,
+    /// Largest downward revision, `null` if nothing was revised down
+    max_downward_revision: Option<DiffPointResponse>,
+    points: Vec<DiffPointResponse>,
+}
+
+/// GET /api/v1/renewable-surplus/:country/diff?against=6h&hours=24
+/// The surplus forecast now against the forecast stored `against` ago, per timestamp
+async fn get_surplus_diff(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<DiffQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let against = query.against.unwrap_or_else(|| "6h".to_string());
+    let age = crate::cli::parse_duration(&against).map_err(|_| {
+        ApiError::bad_request("Invalid `against`: expected a duration like 6h or 90m")
+    })?;
+    let window = query_window(
+        query.hours,
+        None,
+        None,
+        requested_day(query.window, None, zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let Some(storage) = &state.storage else {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Forecast diffs need the history database (EDUCK_HISTORY_DB)",
+        ));
+    };
+
+    let as_of = window.start - age;
+    let baseline = surplus_as_of(storage.as_ref(), zone.code, window.start, window.end, as_of)
+        .await
+        .map_err(|e| {
+            eprintln!("Reading the forecast history failed: {}", e);
+            ApiError::from(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    let current = fetch_window_series(&state, zone.code, &window, Freshness::DayAhead).await?;
+
+    let diff = baseline
+        .as_ref()
+        .map(|baseline| diff_series(&current.points, &baseline.points, DIFF_TOLERANCE))
+        .unwrap_or_default();
+    let Some(baseline) = baseline.filter(|_| !diff.is_empty()) else {
+        return Err(ApiError::no_data(format!(
+            "No baseline available: no forecast of {} for this window was stored before {}",
+            country_code,
+            as_of.to_rfc3339()
+        )));
+    };
+
+    let by_revision = |a: &&SurplusDiff, b: &&SurplusDiff| a.revision.total_cmp(&b.revision);
+    let response = SurplusDiffResponse {
+        country_code,
+        period_start: window.start.to_rfc3339(),
+        period_end: window.end.to_rfc3339(),
+        against,
+        baseline_fetched_at: baseline.fetched_at.to_rfc3339(),
+        max_upward_revision: diff
+            .iter()
+            .filter(|d| d.revision > 0.0)
+            .max_by(by_revision)
+            .map(Into::into),
+        max_downward_revision: diff
+            .iter()
+            .filter(|d| d.revision < 0.0)
+            .min_by(by_revision)
+            .map(Into::into),
+        points: diff.iter().map(Into::into).collect(),
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ForecastCsvQuery {
+    /// `load` (default), `generation` or `total_generation`
+    kind: Option<ForecastKind>,
+    /// Number of hours to look ahead (default: 24), ignored when `start` or `window` is given
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Start of an explicit interval (RFC3339)
+    start: Option<String>,
+    /// End of an explicit interval (RFC3339, default: start + 24h)
+    end: Option<String>,
+}
+
+/// GET /api/v1/renewable-surplus/:country/forecast.csv?kind=load|generation|total_generation
+/// Day-ahead forecast points as CSV, streamed while the upstream document is parsed.
+/// Series of different production types are listed one after the other, not summed.
+async fn get_forecast_csv(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<ForecastCsvQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let window = query_window(
+        query.hours,
+        query.start.as_deref(),
+        query.end.as_deref(),
+        requested_day(query.window, None, zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+
+    let kind = query.kind.unwrap_or_default();
+    let mut points = Box::pin(
+        state.client()?.clone().fetch_points_stream(
+            QueryParams::new(kind.document_type())
+                .process_type("A01")
+                .param(kind.zone_parameter(), kind.zone_code(zone.code)),
+            window.start,
+            window.end,
+        ),
+    );
+
+    // Fail with a proper status while nothing has been sent yet
+    let first = match points.next().await {
+        Some(Err(e)) => return Err(ApiError::upstream(e, StatusCode::BAD_GATEWAY)),
+        None => {
+            return Err(ApiError::no_data(
+                "No forecast points in the requested period",
+            ));
+        }
+        first => first,
+    };
+
+    let options = CsvOptions::default();
+    let header = futures_util::stream::once(async move { options.encode_header() });
+    let rows = futures_util::stream::iter(first)
+        .chain(points)
+        .map(move |point| point.and_then(|point| options.encode_point(&point)));
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+        axum::body::Body::from_stream(header.chain(rows)),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct DeficitQuery {
+    /// Number of hours to look ahead (default: 48)
+    hours: Option<u32>,
+    /// Local calendar day to cover instead of the next hours: `today` or `tomorrow`
+    window: Option<CalendarDay>,
+    /// Surplus in MW a point must fall below (default: 0)
+    threshold: Option<f64>,
+    /// Shortest window reported, in minutes (default: 60)
+    min_duration_minutes: Option<u32>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+}
+
+#[derive(Serialize)]
+struct DeficitWindowResponse {
+    start: String,
+    end: String,
+    duration_minutes: i64,
+    mean_deficit_mw: f64,
+    worst: SurplusPoint,
+}
+
+impl From<&SurplusWindow> for DeficitWindowResponse {
+    fn from(window: &SurplusWindow) -> Self {
+        Self {
+            start: window.start.to_rfc3339(),
+            end: window.end.to_rfc3339(),
+            duration_minutes: window.duration().num_minutes(),
+            mean_deficit_mw: window.mean_surplus,
+            worst: (&window.min_point).into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DeficitResponse {
+    country_code: String,
+    threshold_mw: f64,
+    min_duration_minutes: u32,
+    /// Point with the largest deficit in the whole period
+    min_surplus: SurplusPoint,
+    windows: Vec<DeficitWindowResponse>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+}
+
+/// GET /api/v1/renewable-surplus/:country/deficits?hours=48&threshold=-20000
+/// Contiguous windows where the surplus stays below the threshold
+async fn get_deficits(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<DeficitQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+
+    let threshold = query.threshold.unwrap_or(0.0);
+    if !threshold.is_finite() || threshold > 0.0 {
+        return Err(ApiError::bad_request(
+            "`threshold` must be zero or negative",
+        ));
+    }
+    let min_duration_minutes = query.min_duration_minutes.unwrap_or(60);
+
+    let window = query_window(
+        Some(query.hours.unwrap_or(48)),
+        None,
+        None,
+        requested_day(query.window, None, zone)?,
+        state.now(),
+        state.config.max_query_hours,
+    )?;
+    let series = fetch_window_series(
+        &state,
+        zone.code,
+        &window,
+        requested_freshness(query.freshness, query.use_intraday),
+    )
+    .await?;
+
+    let Some(min_surplus) = find_min_surplus(&series.points) else {
+        return Err(ApiError::no_data("No data available"));
+    };
+
+    let windows = find_deficit_windows(
+        &series.points,
+        threshold,
+        Duration::minutes(min_duration_minutes as i64),
+    );
+
+    let response = DeficitResponse {
+        country_code,
+        threshold_mw: threshold,
+        min_duration_minutes,
+        min_surplus: (&min_surplus).into(),
+        windows: windows.iter().map(Into::into).collect(),
+        forecast: (&series).into(),
+    };
+
+    Ok(conditional_json(
+        &headers,
+        &state.config,
+        ApiResponse::success(response),
+    ))
+}
+
+#[derive(Deserialize)]
+struct NowQuery {
+    /// `linear` (default) or `step`
+    interpolation: Option<Interpolation>,
+    /// Forecasts to use: `dayahead` (default), `intraday` or `auto`
+    freshness: Option<Freshness>,
+    /// Shorthand for `freshness=auto`
+    #[serde(default)]
+    use_intraday: bool,
+}
+
+#[derive(Serialize)]
+struct NowResponse {
+    country_code: String,
+    timestamp: String,
+    generation_mw: f64,
+    load_mw: f64,
+    surplus_mw: f64,
+    total_generation_mw: Option<f64>,
+    /// Wind and solar as a percentage of load
+    renewable_penetration: f64,
+    /// Wind and solar as a percentage of total generation
+    renewable_share_of_generation: Option<f64>,
+    #[serde(flatten)]
+    forecast: ForecastInfo,
+}
+
+/// GET /api/v1/renewable-surplus/:country/now?interpolation=linear|step
+/// Surplus right now, interpolated between the surrounding forecast points
+async fn get_now_surplus(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    ValidQuery(query): ValidQuery<NowQuery>,
+) -> Result<Response, ApiError> {
+    let zone = requested_zone(&country_code)?;
+
+    let now = state.now();
+    let window = TimeWindow::next_hours_from(now, 2).extended_back(Duration::hours(1));
+    let series = fetch_window_series(
+        &state,
+        zone.code,
+        &window,
+        requested_freshness(query.freshness, query.use_intraday),
+    )
+    .await?;
+
+    let method = query.interpolation.unwrap_or_default();
+    let Some(current) = value_at(&series.points, now, method) else {
+        return Err(ApiError::no_data("No forecast covers the current time"));
+    };
+
+    Ok(Json(ApiResponse::success(NowResponse {
+        country_code,
+        timestamp: current.timestamp.to_rfc3339(),
+        generation_mw: current.generation,
+        load_mw: current.load,
+        surplus_mw: current.surplus,
+        total_generation_mw: current.total_generation,
+        renewable_penetration: current.renewable_penetration(),
+        renewable_share_of_generation: current.renewable_share_of_generation(),
+        forecast: (&series).into(),
+    }))
+    .into_response())
+}
+
+#[derive(Deserialize)]
+struct SurplusSummaryQuery {
+    /// Comma separated country codes to report (default: all)
+    countries: Option<String>,
+    /// Unit of the power values: `MW` (default), `GW` or `kW`
+    #[serde(default)]
+    unit: PowerUnit,
+}
+
+/// Headline values of one country from already fetched data
+#[derive(Serialize)]
+pub(super) struct CountrySurplusSummary {
+    pub(super) country_code: String,
+    /// When the data was fetched
+    pub(super) as_of: String,
+    /// `null` when the data does not cover the current time
+    pub(super) current_surplus_mw: Option<f64>,
+    /// Wind and solar as a percentage of load, now
+    pub(super) renewable_penetration: Option<f64>,
+    /// Largest surplus of the local calendar day of the country
+    pub(super) today_max_surplus_mw: Option<f64>,
+    pub(super) today_max_at: Option<String>,
+}
+
+/// Headline values of a country from the data the refresher or the document cache
+/// holds, `None` without any. Never requests anything upstream.
+pub(super) fn cached_summary(
+    state: &AppState,
+    country_code: String,
+    zone: &BiddingZone,
+    unit: PowerUnit,
+) -> Option<CountrySurplusSummary> {
+    let (series, as_of) = warm_series(state, &country_code, zone)?;
+
+    let now = state.now();
+    let current = value_at(&series.points, now, Interpolation::default());
+    let local = LocalZone::named(zone.timezone).unwrap_or(LocalZone::UTC);
+    let today = TimeWindow::calendar_day_from(now, CalendarDay::Today, &local);
+    let peak = max_surplus(
+        series
+            .points
+            .iter()
+            .filter(|point| today.start <= point.timestamp && point.timestamp < today.end),
+    );
+    Some(CountrySurplusSummary {
+        country_code,
+        as_of: as_of.to_rfc3339(),
+        current_surplus_mw: current
+            .as_ref()
+            .map(|current| unit.from_mw(current.surplus)),
+        renewable_penetration: current
+            .as_ref()
+            .map(RenewableSurplus::renewable_penetration),
+        today_max_surplus_mw: peak.map(|peak| unit.from_mw(peak.surplus)),
+        today_max_at: peak.map(|peak| peak.timestamp.to_rfc3339()),
+    })
+}
+
+#[derive(Serialize)]
+struct SurplusSummaryResponse {
+    /// Of the `_mw` values
+    unit: PowerUnit,
+    countries: Vec<CountrySurplusSummary>,
+    /// Requested countries without fetched data
+    unavailable: Vec<String>,
+}
+
+/// GET /api/v1/renewable-surplus/summary?countries=DE,FR
+/// Current and today's peak surplus of every country with prefetched or cached data.
+/// Never requests anything upstream; the other countries are listed as unavailable.
+async fn get_surplus_summary(
+    State(state): State<AppState>,
+    ValidQuery(query): ValidQuery<SurplusSummaryQuery>,
+) -> Result<Response, ApiError> {
+    let requested: Vec<String> = match query.countries.as_deref() {
+        Some(countries) => countries
+            .split(',')
+            .map(str::trim)
+            .filter(|country| !country.is_empty())
+            .map(str::to_ascii_uppercase)
+            .fold(Vec::new(), |mut requested, country| {
+                if !requested.contains(&country) {
+                    requested.push(country);
+                }
+                requested
+            }),
+        None => areas::list_countries()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    };
+
+    let mut countries = Vec::new();
+    let mut unavailable = Vec::new();
+    for country_code in requested {
+        let zone = requested_zone(&country_code)?;
+        match cached_summary(&state, country_code.clone(), zone, query.unit) {
+            Some(summary) => countries.push(summary),
+            None => unavailable.push(country_code),
+        }
+    }
+
+    Ok(Json(ApiResponse::success(SurplusSummaryResponse {
+        unit: query.unit,
+        countries,
+        unavailable,
+    }))
+    .into_response())
+}
+
+pub(super) fn routes() -> [ApiRoute; 12] {
+    [
+        ApiRoute::get("/api/v1/renewable-surplus/summary", get_surplus_summary)
+            .usage("?countries=DE,FR (cached data only)"),
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/night",
+            get_night_surplus,
+        ),
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/next-6h",
+            get_next_6h_surplus,
+        ),
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/next-24h",
+            get_next_24h_surplus,
+        ),
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/next",
+            get_custom_hours_surplus,
+        )
+        .usage("?hours=N&freshness=dayahead|intraday|auto"),
+        ApiRoute::get("/api/v1/renewable-surplus/{country}/series", get_series)
+            .usage("?hours=N|start=RFC3339&end=RFC3339|window=today|tomorrow&partial=true"),
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/best-window",
+            get_best_window,
+        )
+        .usage("?duration=3&hours=24&band=true"),
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/forecast.csv",
+            get_forecast_csv,
+        )
+        .usage("?kind=load&hours=N"),
+        ApiRoute::get("/api/v1/renewable-surplus/{country}/now", get_now_surplus)
+            .usage("?interpolation=linear|step"),
+        ApiRoute::get("/api/v1/renewable-surplus/{country}/deficits", get_deficits)
+            .usage("?hours=48&threshold=-20000"),
+        ApiRoute::get("/api/v1/renewable-surplus/{country}/diff", get_surplus_diff)
+            .usage("?against=6h&hours=24"),
+        ApiRoute::get(
+            "/api/v1/renewable-surplus/{country}/availability",
+            get_availability,
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::config::ServerConfig;
+    use crate::entsoe::EntsoeClient;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state, upstream_answering};
+    use chrono::{DateTime, DurationRound, TimeZone, Utc};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_maximum_reports_solar_and_wind() {
+        let midnight = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let app = router(
+            test_state(Arc::new(MockTransport::split_forecasts()))
+                .with_clock(Arc::new(FixedClock(midnight))),
+        );
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+        // Solar peaks at noon
+        assert_eq!(
+            data["timestamp"],
+            (midnight + Duration::hours(12)).to_rfc3339()
+        );
+        assert_eq!(data["solar_mw"], 12_000.0);
+        assert_eq!(data["wind_mw"], 10_000.0);
+        let solar_share = data["solar_share"].as_f64().unwrap();
+        assert!((solar_share - 54.545).abs() < 0.001, "{}", solar_share);
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/plot?hours=24&raw=true",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let page = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(page.contains(r#""customdata":[[0.0,10000.0]"#));
+        assert!(page.contains("Solar: %{customdata[0]} MW"));
+
+        // Forecasts without production types leave the split out
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let response = app
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-6h"))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert!(body["data"]["solar_mw"].is_null());
+        assert!(body["data"]["wind_share"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_series_reports_forecast_sources() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let uri = "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z";
+
+        for (query, generation, load) in [
+            ("freshness=dayahead", "day_ahead", "day_ahead"),
+            ("freshness=auto", "current", "intraday"),
+            ("use_intraday=true", "current", "intraday"),
+            (
+                "use_intraday=true&freshness=dayahead",
+                "day_ahead",
+                "day_ahead",
+            ),
+            ("use_intraday=false", "day_ahead", "day_ahead"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(get_request(&format!("{}&{}", uri, query)))
+                .await
+                .unwrap();
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+            let sources = body["data"]["sources"].as_array().unwrap();
+            assert_eq!(sources.len(), 1, "{}", query);
+            assert_eq!(sources[0]["start"], "2024-06-01T00:00:00+00:00");
+            assert_eq!(sources[0]["end"], "2024-06-01T23:00:00+00:00");
+            assert_eq!(sources[0]["generation"], generation, "{}", query);
+            assert_eq!(sources[0]["load"], load, "{}", query);
+        }
+
+        let response = app
+            .oneshot(get_request(&format!("{}&freshness=weekly", uri)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_partial_series_without_the_load_forecast() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts_without(
+            "A65",
+        ))));
+        let uri = "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z&end=2024-06-01T03:00:00Z";
+        let answer = |uri: String| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(get_request(&uri)).await.unwrap();
+                let status = response.status();
+                let body: serde_json::Value =
+                    serde_json::from_slice(&body_bytes(response).await).unwrap();
+                (status, body)
+            }
+        };
+
+        // Strict by default
+        let (status, _) = answer(uri.to_string()).await;
+        assert_ne!(status, StatusCode::OK);
+
+        let (status, body) = answer(format!("{}&partial=true&unit=GW", uri)).await;
+        assert_eq!(status, StatusCode::OK);
+        let data = &body["data"];
+        assert_eq!(data["missing"], "load");
+        assert_eq!(data["unit"], "GW");
+        let points = data["points"].as_array().unwrap();
+        assert_eq!(points.len(), 3);
+        assert_eq!(
+            points[1],
+            serde_json::json!({
+                "timestamp": "2024-06-01T01:00:00+00:00",
+                "timestamp_local": "2024-06-01T03:00:00+02:00",
+                "generation_mw": 41.0,
+                "load_mw": null,
+                "surplus_mw": null,
+            })
+        );
+
+        for query in ["partial=true&strict=true", "partial=true&freshness=auto"] {
+            let (status, _) = answer(format!("{}&{}", uri, query)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_series_with_both_forecasts() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let uri = "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z&end=2024-06-01T02:00:00Z&partial=true&export_mw=500";
+
+        let response = app.oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert!(body["data"]["missing"].is_null());
+        let point = &body["data"]["points"][0];
+        assert_eq!(point["load_mw"], 50_000.0);
+        assert_eq!(point["surplus_mw"], -10_500.0);
+    }
+
+    #[tokio::test]
+    async fn test_local_timestamps_across_dst_end() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        // Clocks in Germany go back from 03:00 to 02:00 on 2024-10-27
+        let uri = "/api/v1/renewable-surplus/DE/series?start=2024-10-26T23:00:00Z&end=2024-10-27T03:00:00Z";
+
+        let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"]["timezone"], "Europe/Berlin");
+        let local: Vec<&str> = body["data"]["points"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|point| point["timestamp_local"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            local,
+            [
+                "2024-10-27T01:00:00+02:00",
+                "2024-10-27T02:00:00+02:00",
+                "2024-10-27T02:00:00+01:00",
+                "2024-10-27T03:00:00+01:00",
+            ]
+        );
+
+        let response = app
+            .clone()
+            .oneshot(get_request(&format!("{}&tz=UTC", uri)))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(
+            body["data"]["points"][0]["timestamp_local"],
+            "2024-10-26T23:00:00+00:00"
+        );
+
+        let response = app
+            .clone()
+            .oneshot(get_request(&format!("{}&tz=Nowhere", uri)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/next-24h?tz=Europe/London",
+            ))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+        assert_eq!(data["timezone"], "Europe/London");
+        let instant = |field: &str| {
+            DateTime::parse_from_rfc3339(data[field].as_str().unwrap())
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert_eq!(instant("timestamp_local"), instant("timestamp"));
+
+        let response = app
+            .oneshot(get_request(&format!(
+                "{}&local=true",
+                uri.replace("/series", "/plot")
+            )))
+            .await
+            .unwrap();
+        let html = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(html.contains("Time (Europe/Berlin)"));
+        assert!(html.contains("2024-10-27 01:00 +02:00"));
+    }
+
+    #[tokio::test]
+    async fn test_series_applies_and_echoes_surplus_model() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let uri = "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z";
+        let data = |body: Vec<u8>| {
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            body["data"].clone()
+        };
+
+        let plain = app.clone().oneshot(get_request(uri)).await.unwrap();
+        let plain = data(body_bytes(plain).await);
+        let modelled = app
+            .oneshot(get_request(&format!(
+                "{}&baseload_mw=1000&export_mw=400",
+                uri
+            )))
+            .await
+            .unwrap();
+        let modelled = data(body_bytes(modelled).await);
+
+        assert!(plain.get("surplus_model").is_none());
+        assert_eq!(
+            modelled["surplus_model"],
+            serde_json::json!({ "baseload_mw": 1000.0, "export_mw": 400.0, "efficiency": 1.0 })
+        );
+        let surplus =
+            |data: &serde_json::Value, i: usize| data["points"][i]["surplus_mw"].as_f64().unwrap();
+        assert_eq!(surplus(&modelled, 0), surplus(&plain, 0) + 600.0);
+        assert_eq!(
+            modelled["points"][0]["generation_mw"],
+            plain["points"][0]["generation_mw"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_power_unit_option() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let body = |uri: String| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(get_request(&uri)).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+                serde_json::from_slice::<serde_json::Value>(&body_bytes(response).await).unwrap()
+            }
+        };
+        let series = "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z";
+
+        let plain = body(series.to_string()).await;
+        assert_eq!(plain["data"]["unit"], "MW");
+        let gigawatt = body(format!("{}&unit=GW", series)).await;
+        assert_eq!(gigawatt["data"]["unit"], "GW");
+        for field in ["generation_mw", "load_mw", "surplus_mw"] {
+            let mw = plain["data"]["points"][0][field].as_f64().unwrap();
+            let gw = gigawatt["data"]["points"][0][field].as_f64().unwrap();
+            assert_eq!(gw, PowerUnit::Gigawatt.from_mw(mw), "{}", field);
+        }
+
+        let plot = "/api/v1/renewable-surplus/DE/plot-json?start=2024-06-01T00:00:00Z&unit=kW";
+        let plot = body(plot.to_string()).await;
+        assert_eq!(plot["data"]["unit"], "kW");
+        assert_eq!(
+            plot["data"]["load"][0].as_f64().unwrap(),
+            plain["data"]["points"][0]["load_mw"].as_f64().unwrap() * 1_000.0
+        );
+
+        let sensor = body("/api/v1/ha/DE?unit=kw".to_string()).await;
+        assert_eq!(sensor["attributes"]["unit_of_measurement"], "kW");
+
+        let response = app
+            .oneshot(get_request(&format!("{}&unit=TW", series)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_series_is_clamped_to_the_availability() {
+        // Published up to 06:00 today
+        let app = router(test_state(Arc::new(MockTransport::truncated_forecasts(30))));
+        let yesterday = Utc::now().duration_trunc(Duration::days(1)).unwrap() - Duration::days(1);
+        let until = yesterday + Duration::hours(30);
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/availability"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+        assert_eq!(data["surplus"]["end"], until.to_rfc3339());
+        assert_eq!(data["load"]["start"], yesterday.to_rfc3339());
+        assert_eq!(data["generation"]["published"], "2024-06-01T12:00:00+00:00");
+
+        let uri = format!(
+            "/api/v1/renewable-surplus/DE/series?start={}&end={}",
+            (until - Duration::hours(10)).format("%Y-%m-%dT%H:%M:%SZ"),
+            (until + Duration::hours(4)).format("%Y-%m-%dT%H:%M:%SZ"),
+        );
+        let response = app.oneshot(get_request(&uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let coverage = &body["data"]["coverage"];
+        assert_eq!(coverage["clamped_to"]["end"], until.to_rfc3339());
+        assert_eq!(coverage["complete"], false);
+        assert_eq!(body["data"]["points"].as_array().unwrap().len(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_series_reports_truncated_coverage() {
+        // Upstream answers with the first 6 hours of the requested day only
+        let app = router(test_state(Arc::new(MockTransport::truncated_forecasts(6))));
+        let uri = "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z";
+
+        let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let coverage = &body["data"]["coverage"];
+        assert_eq!(coverage["complete"], false);
+        assert_eq!(
+            coverage["covered"],
+            serde_json::json!([{ "start": "2024-06-01T00:00:00+00:00", "end": "2024-06-01T06:00:00+00:00" }])
+        );
+        assert_eq!(
+            coverage["gaps"],
+            serde_json::json!([{ "start": "2024-06-01T06:00:00+00:00", "end": "2024-06-02T00:00:00+00:00" }])
+        );
+        assert_eq!(coverage["dropped_points"], 0);
+        assert_eq!(body["data"]["points"].as_array().unwrap().len(), 6);
+
+        let response = app
+            .clone()
+            .oneshot(get_request(&format!("{}&strict=true", uri)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let error = body["error"].as_str().unwrap();
+        assert!(
+            error.contains("cover 6.0 of the requested 24.0 hours"),
+            "{}",
+            error
+        );
+
+        // A window within the published hours is complete, strict or not
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z&end=2024-06-01T06:00:00Z&strict=true",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"]["coverage"]["complete"], true);
+    }
+
+    #[tokio::test]
+    async fn test_responses_include_forecast_metadata() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        for uri in [
+            "/api/v1/renewable-surplus/DE/series?start=2024-06-01T00:00:00Z",
+            "/api/v1/renewable-surplus/DE/plot-json?start=2024-06-01T00:00:00Z",
+            "/api/v1/renewable-surplus/DE/next-24h",
+        ] {
+            let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            let data = &body["data"];
+
+            assert_eq!(
+                data["forecast_created_at"], "2024-06-01T12:00:00+00:00",
+                "{}",
+                uri
+            );
+            assert_eq!(data["generation_forecast"]["revision_number"], 1, "{}", uri);
+            assert_eq!(data["cache_status"], "fresh", "{}", uri);
+            assert_eq!(
+                data["load_forecast"]["created_date_time"], "2024-06-01T12:00:00+00:00",
+                "{}",
+                uri
+            );
+        }
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/plot?start=2024-06-01T00:00:00Z",
+            ))
+            .await
+            .unwrap();
+        let html = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(html.contains("Forecast issued at 2024-06-01 12:00 UTC"));
+    }
+
+    #[tokio::test]
+    async fn test_now_endpoint_interpolates() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        for interpolation in ["linear", "step"] {
+            let uri = format!(
+                "/api/v1/renewable-surplus/DE/now?interpolation={}",
+                interpolation
+            );
+            let response = app.clone().oneshot(get_request(&uri)).await.unwrap();
+            let body: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+
+            assert_eq!(body["success"], true, "{}", interpolation);
+            // The mock load forecast is flat
+            assert_eq!(body["data"]["load_mw"], 50_000.0);
+            let generation = body["data"]["generation_mw"].as_f64().unwrap();
+            assert!((40_000.0..=63_000.0).contains(&generation));
+            // Penetration is relative to load, the share relative to total generation
+            assert_eq!(body["data"]["total_generation_mw"], 70_000.0);
+            let penetration = body["data"]["renewable_penetration"].as_f64().unwrap();
+            assert!((penetration - generation / 500.0).abs() < 1e-9);
+            let share = body["data"]["renewable_share_of_generation"]
+                .as_f64()
+                .unwrap();
+            assert!((share - generation / 700.0).abs() < 1e-9);
+        }
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/now?interpolation=cubic",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_forecast_csv_streams_points() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/forecast.csv?kind=generation&start=2024-06-01T00:00:00Z",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8"
+        );
+
+        let body = String::from_utf8(body_bytes(response).await).unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines[0], "timestamp,end,quantity,unit");
+        assert_eq!(
+            lines[1],
+            "2024-06-01T00:00:00+00:00,2024-06-01T01:00:00+00:00,40000,MAW"
+        );
+        assert_eq!(lines.len(), 1 + 24);
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/forecast.csv?kind=nuclear",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_forecast_csv_reports_upstream_errors() {
+        let app = router(test_state(upstream_answering(503)));
+
+        let response = app
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/forecast.csv"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_surplus_summary_is_served_from_the_cache() {
+        let transport = Arc::new(MockTransport::forecasts());
+        let client = EntsoeClient::with_transport("test-token", transport.clone())
+            .with_cache(std::time::Duration::from_secs(300));
+        let app = router(AppState::new(
+            Some(Arc::new(client)),
+            ServerConfig::default(),
+        ));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let fetched = transport.requests().len();
+
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/summary?countries=de,FR",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+
+        assert_eq!(transport.requests().len(), fetched);
+        assert_eq!(data["unavailable"], serde_json::json!(["FR"]));
+        let de = &data["countries"][0];
+        assert_eq!(de["country_code"], "DE");
+        assert!(DateTime::parse_from_rfc3339(de["as_of"].as_str().unwrap()).is_ok());
+        assert!(de["renewable_penetration"].is_number());
+        // Which hours of the mock fall on today depends on the time of the test
+        let current = de["current_surplus_mw"].as_f64().unwrap();
+        assert!(de["today_max_surplus_mw"].as_f64().unwrap() >= current);
+        assert!(DateTime::parse_from_rfc3339(de["today_max_at"].as_str().unwrap()).is_ok());
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/summary?countries=XX",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_surplus_diff_endpoint() {
+        use crate::entsoe::analysis::DocumentMeta;
+        use crate::storage::sqlite::SqliteStorage;
+        use crate::storage::{GENERATION, LOAD, Storage, StoredDocument};
+        use chrono::Timelike;
+
+        let storage = Arc::new(SqliteStorage::open_in_memory().unwrap());
+        let now = Utc::now();
+        let next_hour = now.duration_trunc(Duration::hours(1)).unwrap() + Duration::hours(1);
+        let document =
+            |doc_type: &str, hours_ago, revision_number, values: Vec<f64>| StoredDocument {
+                zone: "10Y1001A1001A83F".to_string(),
+                doc_type: doc_type.to_string(),
+                psr_type: String::new(),
+                meta: DocumentMeta {
+                    created_date_time: now - Duration::hours(hours_ago),
+                    revision_number,
+                },
+                fetched_at: now - Duration::hours(hours_ago),
+                points: values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, value)| (next_hour + Duration::hours(i as i64), value))
+                    .collect(),
+            };
+        // The mocked generation forecast of each hour, as stored 8 hours ago except for
+        // two hours that were revised since
+        let generation: Vec<f64> = (0..4)
+            .map(|i| 40_000.0 + (next_hour.hour() as f64 + i as f64) % 24.0 * 1_000.0)
+            .collect();
+        let mut baseline = generation.clone();
+        baseline[1] += 3_000.0;
+        baseline[2] -= 2_000.0;
+        storage
+            .store(&document(GENERATION, 8, 1, baseline))
+            .await
+            .unwrap();
+        storage
+            .store(&document(LOAD, 8, 1, vec![50_000.0; 4]))
+            .await
+            .unwrap();
+        // Fetched too recently to be the baseline of a 6 hour diff
+        storage
+            .store(&document(GENERATION, 1, 2, vec![0.0; 4]))
+            .await
+            .unwrap();
+
+        let app =
+            router(test_state(Arc::new(MockTransport::forecasts())).with_storage(storage.clone()));
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/diff?hours=6"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+
+        assert_eq!(data["against"], "6h");
+        let revisions: Vec<f64> = data["points"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|point| point["revision_mw"].as_f64().unwrap())
+            .collect();
+        assert_eq!(revisions, [0.0, -3_000.0, 2_000.0, 0.0]);
+        assert_eq!(data["max_downward_revision"]["revision_mw"], -3_000.0);
+        assert_eq!(
+            data["max_upward_revision"]["timestamp"],
+            (next_hour + Duration::hours(2)).to_rfc3339()
+        );
+        assert_eq!(data["points"][0]["baseline_mw"], generation[0] - 50_000.0);
+
+        // Nothing was stored 12 hours ago
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/diff?against=12h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["success"], false);
+        assert_eq!(body["code"], crate::server::error::NO_DATA);
+        assert!(
+            body["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("No baseline available")
+        );
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/diff?against=soon",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_deficits_endpoint() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        // Mock generation runs 40-63 GW against a flat 50 GW load
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/deficits?hours=48&threshold=-5000&min_duration_minutes=120",
+            ))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+
+        assert_eq!(data["min_surplus"]["surplus_mw"], -10_000.0);
+        let windows = data["windows"].as_array().unwrap();
+        assert!(!windows.is_empty());
+        for window in windows {
+            assert!(window["duration_minutes"].as_i64().unwrap() >= 120);
+            assert!(window["mean_deficit_mw"].as_f64().unwrap() < -5_000.0);
+            assert!(window["worst"]["surplus_mw"].as_f64().unwrap() < -5_000.0);
+        }
+
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/deficits?threshold=100",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_best_window_endpoint() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let uri = "/api/v1/renewable-surplus/DE/best-window?start=2024-06-01T00:00:00Z&end=2024-06-02T00:00:00Z";
+
+        let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let data = &body["data"];
+        // The surplus rises by 1 GW an hour from -10 GW at midnight
+        assert_eq!(data["start"], "2024-06-01T21:00:00+00:00");
+        assert_eq!(data["end"], "2024-06-02T00:00:00+00:00");
+        assert_eq!(data["duration_hours"], 3);
+        assert_eq!(data["mean_surplus_mw"], 12_000.0);
+        assert!(data.get("robustness").is_none());
+
+        for (query, status) in [
+            ("&duration=0", StatusCode::BAD_REQUEST),
+            ("&duration=25", StatusCode::BAD_REQUEST),
+            ("&band=true", StatusCode::SERVICE_UNAVAILABLE),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(get_request(&format!("{}{}", uri, query)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{}", query);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_best_window_robustness_from_stored_errors() {
+        use crate::server::tests::revised_storage;
+
+        let now = Utc.with_ymd_and_hms(2024, 6, 5, 0, 0, 0).unwrap();
+        let transport = Arc::new(MockTransport::forecasts_and_actuals());
+        let app = router(
+            test_state(transport.clone())
+                .with_storage(revised_storage().await)
+                .with_clock(Arc::new(FixedClock(now))),
+        );
+        let response = app
+            .clone()
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/DE/best-window?start=2024-06-01T00:00:00Z&end=2024-06-02T00:00:00Z&band=true",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        // 11 GW at 21:00 turn negative with the errors of -12 GW, half of those of the
+        // first stored forecasts against the actuals of the 30 days before 5 June
+        assert_eq!(body["data"]["robustness"], 0.5);
+        let actuals = || -> Vec<String> {
+            transport
+                .requests()
+                .into_iter()
+                .filter(|url| url.contains("processType=A16"))
+                .collect()
+        };
+        assert_eq!(actuals().len(), 2);
+        assert!(
+            actuals()
+                .iter()
+                .all(|url| url.contains("periodStart=202405060000&periodEnd=202406050000"))
+        );
+
+        // France has no stored forecasts and its actuals are not fetched
+        let response = app
+            .oneshot(get_request(
+                "/api/v1/renewable-surplus/FR/best-window?start=2024-06-01T00:00:00Z&band=true",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(actuals().len(), 2);
+    }
+}
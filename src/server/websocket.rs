@@ -0,0 +1,211 @@
+//! `GET /api/v1/ws/:country`: the series events of a country over a WebSocket
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::response::Response;
+use serde::Deserialize;
+use std::future;
+
+use super::AppState;
+use super::events::{self, StreamEvent};
+use super::query::unknown_country;
+use super::routes::ApiRoute;
+use crate::entsoe::areas::get_primary_zone;
+
+/// Close code for subscriptions to a country without a bidding zone
+const UNKNOWN_COUNTRY: u16 = 4404;
+
+/// Messages clients may send, tagged by `type`
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// Answered with `{"type":"pong"}`
+    Ping,
+    /// Switch to another country, answered with its snapshot
+    Subscribe { country: String },
+}
+
+/// GET /api/v1/ws/:country
+/// Sends a snapshot of the country's series, then an update per refresh
+async fn websocket(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| serve(socket, state, country_code))
+}
+
+async fn send(socket: &mut WebSocket, event: &StreamEvent) -> bool {
+    let text = serde_json::to_string(event).expect("stream events serialize");
+    socket.send(Message::Text(text.into())).await.is_ok()
+}
+
+/// Send the snapshot of `country_code`, or close the socket when it is unknown.
+/// Returns whether the connection is still open.
+async fn subscribe(socket: &mut WebSocket, state: &AppState, country_code: &str) -> bool {
+    if get_primary_zone(country_code).is_none() {
+        let close = CloseFrame {
+            code: UNKNOWN_COUNTRY,
+            reason: unknown_country(country_code).into(),
+        };
+        let _ = socket.send(Message::Close(Some(close))).await;
+        return false;
+    }
+
+    send(socket, &events::snapshot(state, country_code).await).await
+}
+
+async fn serve(mut socket: WebSocket, state: AppState, country_code: String) {
+    let mut country_code = country_code.to_ascii_uppercase();
+    let mut updates = events::updates(&state);
+    if !subscribe(&mut socket, &state, &country_code).await {
+        return;
+    }
+
+    loop {
+        let update = async {
+            match events::next_update(&mut updates, &country_code).await {
+                Some(event) => event,
+                None => future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    // Pings are answered by axum, binary frames are not part of the protocol
+                    Some(Ok(_)) => continue,
+                };
+
+                let open = match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(ClientMessage::Ping) => send(&mut socket, &StreamEvent::Pong).await,
+                    Ok(ClientMessage::Subscribe { country }) => {
+                        country_code = country.to_ascii_uppercase();
+                        subscribe(&mut socket, &state, &country_code).await
+                    }
+                    Err(e) => {
+                        let message = format!("Invalid message: {}", e);
+                        send(&mut socket, &StreamEvent::Error { message }).await
+                    }
+                };
+                if !open {
+                    break;
+                }
+            }
+            event = update => {
+                if !send(&mut socket, &event).await {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn routes() -> [ApiRoute; 1] {
+    [ApiRoute::get("/api/v1/ws/{country}", websocket).usage("WebSocket: snapshot, then updates")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::entsoe::EntsoeClient;
+    use crate::entsoe::testing::MockTransport;
+    use crate::refresher::Refresher;
+    use crate::server::router;
+    use futures_util::{SinkExt, StreamExt};
+    use std::sync::Arc;
+    use tokio_tungstenite::tungstenite;
+
+    #[test]
+    fn test_client_messages() {
+        assert_eq!(
+            serde_json::from_str::<ClientMessage>(r#"{"type":"ping"}"#).unwrap(),
+            ClientMessage::Ping
+        );
+        assert_eq!(
+            serde_json::from_str::<ClientMessage>(r#"{"type":"subscribe","country":"fr"}"#)
+                .unwrap(),
+            ClientMessage::Subscribe {
+                country: "fr".to_string()
+            }
+        );
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"type":"unsubscribe"}"#).is_err());
+    }
+
+    async fn next_json(
+        socket: &mut tokio_tungstenite::WebSocketStream<
+            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+        >,
+    ) -> serde_json::Value {
+        match socket.next().await.unwrap().unwrap() {
+            tungstenite::Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("expected a text message, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_ping_and_country_switch() {
+        let client = Arc::new(EntsoeClient::with_transport(
+            "test-token",
+            Arc::new(MockTransport::forecasts()),
+        ));
+        let refresher = Arc::new(Refresher::new(
+            client.clone(),
+            vec!["DE".to_string()],
+            std::time::Duration::from_secs(60),
+        ));
+        refresher.refresh_once().await;
+        let state = AppState::new(Some(client), ServerConfig::default()).with_refresher(refresher);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{address}/api/v1/ws/de"))
+                .await
+                .unwrap();
+
+        let snapshot = next_json(&mut socket).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["country_code"], "DE");
+        assert!(!snapshot["points"].as_array().unwrap().is_empty());
+
+        socket
+            .send(tungstenite::Message::text(r#"{"type":"ping"}"#))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_json(&mut socket).await,
+            serde_json::json!({ "type": "pong" })
+        );
+
+        // FR is not prefetched, its snapshot is fetched on demand
+        socket
+            .send(tungstenite::Message::text(
+                r#"{"type":"subscribe","country":"FR"}"#,
+            ))
+            .await
+            .unwrap();
+        let snapshot = next_json(&mut socket).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["country_code"], "FR");
+
+        socket
+            .send(tungstenite::Message::text(
+                r#"{"type":"subscribe","country":"XX"}"#,
+            ))
+            .await
+            .unwrap();
+        match socket.next().await.unwrap().unwrap() {
+            tungstenite::Message::Close(Some(frame)) => {
+                assert_eq!(u16::from(frame.code), UNKNOWN_COUNTRY);
+                assert_eq!(frame.reason.as_str(), "Unknown country `XX`");
+            }
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+    }
+}
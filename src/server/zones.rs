@@ -0,0 +1,253 @@
+//! Countries and bidding zones the API knows
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::AppState;
+use super::dto::ApiResponse;
+use super::error::{ApiError, ValidQuery};
+use super::query::requested_zone;
+use super::routes::ApiRoute;
+use crate::entsoe::areas::{self, BiddingZone, DocumentKind};
+use crate::entsoe::capabilities::{Capabilities, Capability};
+
+/// GET /api/v1/countries
+/// List all available countries
+async fn list_countries() -> Json<ApiResponse<Vec<String>>> {
+    let countries = areas::list_countries()
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    Json(ApiResponse::success(countries))
+}
+
+/// GET /api/v1/zones
+/// The whole zone catalog, grouped by country
+async fn list_zones() -> Json<ApiResponse<BTreeMap<&'static str, &'static [BiddingZone]>>> {
+    let catalog = areas::BIDDING_ZONES
+        .iter()
+        .map(|(country_code, zones)| (*country_code, zones.as_slice()))
+        .collect();
+
+    Json(ApiResponse::success(catalog))
+}
+
+/// GET /api/v1/zones/:country
+/// Get all bidding zones for a country
+async fn get_country_zones(
+    Path(country_code): Path<String>,
+) -> Result<Json<ApiResponse<&'static [BiddingZone]>>, StatusCode> {
+    match areas::get_zones_by_country(&country_code) {
+        Some(zones) => Ok(Json(ApiResponse::success(zones.as_slice()))),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[derive(Deserialize)]
+struct ZoneSearchQuery {
+    /// Matched against code, country code, name, alias and TSO
+    q: Option<String>,
+}
+
+/// GET /api/v1/zones/search?q=tennet
+/// Up to 20 zones matching the query, best matches first; an empty query matches nothing
+async fn search_zones(
+    ValidQuery(query): ValidQuery<ZoneSearchQuery>,
+) -> Json<ApiResponse<Vec<&'static BiddingZone>>> {
+    let zones = areas::search_zones(query.q.as_deref().unwrap_or_default());
+    Json(ApiResponse::success(zones))
+}
+
+/// Codes the forecasts of a zone are requested under
+#[derive(Serialize)]
+struct RequestedCodes {
+    /// `outBiddingZone_Domain` of the load forecasts
+    load: &'static str,
+    /// `in_Domain` of the generation forecasts
+    generation: &'static str,
+}
+
+#[derive(Serialize)]
+struct ZoneCapabilities {
+    #[serde(flatten)]
+    zone: &'static BiddingZone,
+    requested_as: RequestedCodes,
+    /// Every kind of document, `supported`, `unsupported` or `unknown` (tried when asked)
+    documents: Vec<Capability>,
+}
+
+/// GET /api/v1/capabilities/:country
+/// Which documents each zone of the country publishes, as seeded and as learned from
+/// fetches; data endpoints answer `unsupported_for_zone` for unsupported ones
+async fn get_capabilities(
+    State(state): State<AppState>,
+    Path(country_code): Path<String>,
+) -> Result<Json<ApiResponse<Vec<ZoneCapabilities>>>, ApiError> {
+    let zone = requested_zone(&country_code)?;
+    let seeded = Capabilities::default();
+    let capabilities = state
+        .entsoe_client
+        .as_ref()
+        .map_or(&seeded, |client| client.capabilities());
+    let zones = areas::get_zones_by_country(zone.country_code)
+        .into_iter()
+        .flatten()
+        .map(|zone| ZoneCapabilities {
+            zone,
+            requested_as: RequestedCodes {
+                load: areas::load_code(zone.code),
+                generation: areas::generation_code(zone.code),
+            },
+            documents: DocumentKind::ALL
+                .into_iter()
+                .map(|kind| capabilities.of(zone.code, kind))
+                .collect(),
+        })
+        .collect();
+    Ok(Json(ApiResponse::success(zones)))
+}
+
+pub(super) fn routes() -> [ApiRoute; 5] {
+    [
+        ApiRoute::get("/api/v1/countries", list_countries),
+        ApiRoute::get("/api/v1/zones", list_zones),
+        ApiRoute::get("/api/v1/zones/search", search_zones).usage("?q=..."),
+        ApiRoute::get("/api/v1/zones/{country}", get_country_zones),
+        ApiRoute::get("/api/v1/capabilities/{country}", get_capabilities),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::MockTransport;
+    use crate::server::router;
+    use crate::server::tests::{body_bytes, get_request, test_state};
+    use serde_json::json;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_zone_search() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/zones/search?q=tennet"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"][0]["code"], "10YDE-EON------1");
+        assert_eq!(body["data"][0]["alias"], "DE-TENNET");
+        assert_eq!(body["data"][0]["kind"], "control_area");
+
+        let response = app
+            .oneshot(get_request("/api/v1/zones/search"))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(body["data"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_of_a_country() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+        let capabilities = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(get_request(uri)).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+                let body: serde_json::Value =
+                    serde_json::from_slice(&body_bytes(response).await).unwrap();
+                body["data"].clone()
+            }
+        };
+
+        let data = capabilities("/api/v1/capabilities/de").await;
+        assert_eq!(data.as_array().unwrap().len(), 5);
+        assert_eq!(data[0]["alias"], "DE-LU");
+        assert_eq!(
+            data[0]["documents"][0],
+            json!({"kind": "load_forecast", "support": "supported", "learned": false})
+        );
+        let support = |zone: &serde_json::Value, kind: &str| {
+            zone["documents"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|document| document["kind"] == kind)
+                .map(|document| document["support"].clone())
+                .unwrap()
+        };
+        assert_eq!(support(&data[0], "intraday_prices"), "unsupported");
+        assert_eq!(support(&data[0], "generation_per_unit"), "unknown");
+        // TenneT is a control area, without prices of its own
+        assert_eq!(data[3]["tso"], "TenneT");
+        assert_eq!(support(&data[3], "day_ahead_prices"), "unsupported");
+
+        // Answered fetches are learned
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/renewable-surplus/DE/next-24h"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let data = capabilities("/api/v1/capabilities/DE").await;
+        assert_eq!(data[0]["documents"][0]["learned"], true);
+        assert_eq!(data[3]["documents"][0]["learned"], false);
+        assert_eq!(
+            data[0]["requested_as"],
+            json!({"load": "10Y1001A1001A83F", "generation": "10Y1001A1001A83F"})
+        );
+
+        // Denmark publishes its load under the country area
+        let data = capabilities("/api/v1/capabilities/DK").await;
+        assert_eq!(data[0]["load_code"], "10Y1001A1001A65H");
+        assert_eq!(
+            data[0]["requested_as"],
+            json!({"load": "10Y1001A1001A65H", "generation": "10Y1001A1001A796"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_zone_catalog_snapshot() {
+        let app = router(test_state(Arc::new(MockTransport::forecasts())));
+
+        let response = app
+            .clone()
+            .oneshot(get_request("/api/v1/zones/LU"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(
+            body["data"],
+            json!([{
+                "code": "10YLU-CEGEDEL-NQ",
+                "country_code": "LU",
+                "name": "Luxembourg",
+                "tso": null,
+                "alias": null,
+                "kind": "bidding_zone",
+                "timezone": "Europe/Luxembourg",
+            }])
+        );
+        let luxembourg = body["data"].clone();
+
+        let response = app.oneshot(get_request("/api/v1/zones")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let catalog = body["data"].as_object().unwrap();
+        assert_eq!(catalog.len(), areas::BIDDING_ZONES.len());
+        assert_eq!(catalog["LU"], luxembourg);
+        assert_eq!(catalog["DE"][1]["alias"], "DE-50HZ");
+        assert_eq!(catalog["DE"][1]["kind"], "control_area");
+        // Countries are listed in alphabetical order
+        assert_eq!(catalog.keys().next().map(String::as_str), Some("AL"));
+    }
+}
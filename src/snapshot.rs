@@ -0,0 +1,321 @@
+//! Offline demo mode: `educk snapshot` records the ENTSO-E responses behind the surplus
+//! series of some countries into one file, `educk serve --offline FILE` answers every
+//! upstream request from it instead of the network
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::entsoe::analysis::Freshness;
+use crate::entsoe::areas::get_primary_zone;
+use crate::entsoe::window::TimeWindow;
+use crate::entsoe::{EntsoeClient, EntsoeError, Transport, TransportResponse};
+
+/// Format version written by this build; files of any other version are rejected
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Query parameters that do not select a document, ignored when matching requests
+const IGNORED_PARAMS: [&str; 3] = ["securityToken", "periodStart", "periodEnd"];
+
+/// Answer to requests the snapshot has no response for, as ENTSO-E words it
+const NO_DATA: &str = "<Acknowledgement_MarketDocument><Reason><code>999</code><text>No matching data found in the offline snapshot</text></Reason></Acknowledgement_MarketDocument>";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotArgs {
+    pub countries: Vec<String>,
+    /// Look-ahead from the current hour
+    pub hours: u32,
+    pub out: PathBuf,
+}
+
+/// Contents of a snapshot file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// RFC3339
+    pub created_at: String,
+    /// Captured interval (RFC3339)
+    pub period_start: String,
+    pub period_end: String,
+    pub countries: Vec<String>,
+    pub responses: Vec<RecordedResponse>,
+}
+
+/// One upstream answer, matched to later requests by its query without token and period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub query: BTreeMap<String, String>,
+    pub status: u16,
+    pub body: String,
+}
+
+impl Snapshot {
+    /// Read a snapshot, rejecting files of another format version
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read snapshot {}: {}", path.display(), e))?;
+        let value: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("{} is not a snapshot: {}", path.display(), e))?;
+
+        match value.get("version").and_then(serde_json::Value::as_u64) {
+            Some(version) if version == SNAPSHOT_VERSION as u64 => {}
+            Some(version) => anyhow::bail!(
+                "{} is a version {} snapshot, this build reads version {}; capture it again with `educk snapshot`",
+                path.display(),
+                version,
+                SNAPSHOT_VERSION
+            ),
+            None => anyhow::bail!("{} is not a snapshot: no version field", path.display()),
+        }
+
+        serde_json::from_value(value)
+            .map_err(|e| anyhow::anyhow!("Invalid snapshot {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+/// Query parameters of `url` that select a document
+fn request_key(url: &str) -> BTreeMap<String, String> {
+    let Ok(url) = Url::parse(url) else {
+        return BTreeMap::new();
+    };
+    url.query_pairs()
+        .filter(|(name, _)| !IGNORED_PARAMS.contains(&name.as_ref()))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect()
+}
+
+/// Transport passing requests on to `inner` and keeping every response
+struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    responses: Mutex<Vec<RecordedResponse>>,
+}
+
+#[async_trait]
+impl Transport for RecordingTransport {
+    async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+        let response = self.inner.get(url).await?;
+        let query = request_key(url);
+
+        let mut responses = self.responses.lock().unwrap();
+        responses.retain(|recorded| recorded.query != query);
+        responses.push(RecordedResponse {
+            query,
+            status: response.status,
+            body: response.body.clone(),
+        });
+        Ok(response)
+    }
+}
+
+/// Transport answering from a snapshot, whatever period is requested
+pub struct OfflineTransport {
+    responses: HashMap<BTreeMap<String, String>, TransportResponse>,
+}
+
+impl OfflineTransport {
+    pub fn new(snapshot: &Snapshot) -> Self {
+        let responses = snapshot
+            .responses
+            .iter()
+            .map(|recorded| {
+                let response = TransportResponse {
+                    status: recorded.status,
+                    body: recorded.body.clone(),
+                    retry_after: None,
+                };
+                (recorded.query.clone(), response)
+            })
+            .collect();
+        Self { responses }
+    }
+}
+
+#[async_trait]
+impl Transport for OfflineTransport {
+    async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+        Ok(self
+            .responses
+            .get(&request_key(url))
+            .cloned()
+            .unwrap_or_else(|| TransportResponse {
+                status: 200,
+                body: NO_DATA.to_string(),
+                retry_after: None,
+            }))
+    }
+}
+
+/// Record what the surplus series of `countries` over `[start, end)` need, day-ahead
+/// and, where published, intraday
+pub async fn capture(
+    transport: Arc<dyn Transport>,
+    api_key: &str,
+    countries: &[String],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> anyhow::Result<Snapshot> {
+    let recorder = Arc::new(RecordingTransport {
+        inner: transport,
+        responses: Mutex::new(Vec::new()),
+    });
+    let client = EntsoeClient::with_transport(api_key, recorder.clone());
+    let (period_start, period_end) = TimeWindow::between(start, end).period();
+
+    for country in countries {
+        let zone = get_primary_zone(country)
+            .ok_or_else(|| anyhow::anyhow!("Unknown country code {:?}", country))?;
+        client
+            .get_surplus_series(zone.code, &period_start, &period_end, Freshness::DayAhead)
+            .await
+            .map_err(|e| anyhow::anyhow!("Capturing {} failed: {}", country, e))?;
+        if let Err(e) = client
+            .get_surplus_series(zone.code, &period_start, &period_end, Freshness::Intraday)
+            .await
+        {
+            eprintln!("No intraday forecast for {}: {}", country, e);
+        }
+    }
+
+    let mut responses = std::mem::take(&mut *recorder.responses.lock().unwrap());
+    responses.sort_by(|a, b| a.query.cmp(&b.query));
+    Ok(Snapshot {
+        version: SNAPSHOT_VERSION,
+        created_at: Utc::now().to_rfc3339(),
+        period_start: start.to_rfc3339(),
+        period_end: end.to_rfc3339(),
+        countries: countries.to_vec(),
+        responses,
+    })
+}
+
+/// `educk snapshot`: capture `args.hours` from the current hour into `args.out`
+pub async fn write_snapshot(
+    transport: Arc<dyn Transport>,
+    api_key: &str,
+    args: &SnapshotArgs,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let start = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+    let end = start + Duration::hours(args.hours as i64);
+
+    let snapshot = capture(transport, api_key, &args.countries, start, end).await?;
+    snapshot.save(&args.out)?;
+    println!(
+        "Captured {} responses for {} into {}",
+        snapshot.responses.len(),
+        args.countries.join(", "),
+        args.out.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::testing::MockTransport;
+    use chrono::TimeZone;
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    async fn mock_snapshot() -> Snapshot {
+        capture(
+            Arc::new(MockTransport::forecasts()),
+            "test-token",
+            &["DE".to_string()],
+            midnight(),
+            midnight() + Duration::hours(48),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("educk-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_request_key_ignores_token_and_period() {
+        let key = request_key(
+            "https://web-api.tp.entsoe.eu/api?securityToken=secret&documentType=A65&periodStart=202406010000&periodEnd=202406020000&outBiddingZone_Domain=10Y1001A1001A83F",
+        );
+        assert_eq!(
+            key.into_iter().collect::<Vec<_>>(),
+            [
+                ("documentType".to_string(), "A65".to_string()),
+                (
+                    "outBiddingZone_Domain".to_string(),
+                    "10Y1001A1001A83F".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline_transport_replays_captured_series() {
+        let snapshot = mock_snapshot().await;
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert!(!snapshot.responses.is_empty());
+        assert!(
+            snapshot
+                .responses
+                .iter()
+                .all(|recorded| !recorded.query.contains_key("securityToken"))
+        );
+
+        let live = EntsoeClient::with_transport("test-token", Arc::new(MockTransport::forecasts()));
+        let offline = EntsoeClient::with_transport("", Arc::new(OfflineTransport::new(&snapshot)));
+        let (start, end) = ("202406010000", "202406030000");
+        let expected = live
+            .get_surplus_series("10Y1001A1001A83F", start, end, Freshness::DayAhead)
+            .await
+            .unwrap();
+        let replayed = offline
+            .get_surplus_series("10Y1001A1001A83F", start, end, Freshness::DayAhead)
+            .await
+            .unwrap();
+        let surplus = |series: &crate::entsoe::analysis::SurplusSeries| -> Vec<f64> {
+            series.points.iter().map(|point| point.surplus).collect()
+        };
+        assert_eq!(surplus(&replayed), surplus(&expected));
+
+        // Countries that were not captured get upstream's "no data" answer
+        assert!(
+            offline
+                .get_surplus_series("10YFR-RTE------C", start, end, Freshness::DayAhead)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_round_trip_and_version_check() {
+        let snapshot = mock_snapshot().await;
+        let path = temp_path("snapshot.json");
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), snapshot);
+
+        let mut value: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        value["version"] = serde_json::json!(SNAPSHOT_VERSION + 1);
+        std::fs::write(&path, value.to_string()).unwrap();
+        let error = Snapshot::load(&path).unwrap_err().to_string();
+        assert!(error.contains("is a version 2 snapshot, this build reads version 1"));
+
+        std::fs::write(&path, "{\"countries\": []}").unwrap();
+        let error = Snapshot::load(&path).unwrap_err().to_string();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.contains("no version field"));
+    }
+}
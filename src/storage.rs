@@ -0,0 +1,339 @@
+//! Local history of fetched forecasts, written through from the background refresher
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
+
+use crate::entsoe::analysis::{
+    DocumentMeta, ForecastDrift, ForecastRevision, RenewableSurplus, SurplusSeries,
+    forecast_revision_drift,
+};
+use crate::entsoe::areas::get_primary_zone;
+use crate::entsoe::request::TimeRange;
+use crate::refresher::RefreshEvent;
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Document type of the stored generation series (solar and wind, A69)
+pub const GENERATION: &str = "A69";
+/// Document type of the stored load series (A65)
+pub const LOAD: &str = "A65";
+
+/// Points of one forecast document as stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredDocument {
+    pub zone: String,
+    pub doc_type: String,
+    /// Production type, empty for totals
+    pub psr_type: String,
+    pub meta: DocumentMeta,
+    pub fetched_at: DateTime<Utc>,
+    pub points: Vec<(DateTime<Utc>, f64)>,
+}
+
+impl StoredDocument {
+    /// Interval from the first to just past the last point; points start on whole minutes
+    pub fn interval(&self) -> Option<TimeRange> {
+        let first = self.points.iter().map(|(timestamp, _)| *timestamp).min()?;
+        let last = self.points.iter().map(|(timestamp, _)| *timestamp).max()?;
+        Some(TimeRange::new(first, last + Duration::minutes(1)))
+    }
+}
+
+/// A stored point together with the document it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredPoint {
+    pub timestamp: DateTime<Utc>,
+    pub value: f64,
+    pub meta: DocumentMeta,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Backend keeping the history of fetched documents
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Insert a document. Points of a newer document replace the current ones, which are
+    /// kept as superseded; storing the same revision again updates its points in place.
+    async fn store(&self, document: &StoredDocument) -> anyhow::Result<()>;
+
+    /// Current points of a series in `[start, end)`, each from the newest document
+    /// covering it
+    async fn points(
+        &self,
+        zone: &str,
+        doc_type: &str,
+        psr_type: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<StoredPoint>>;
+
+    /// Points of a series in `[start, end)` that a newer document replaced, by time and
+    /// oldest revision first
+    async fn superseded(
+        &self,
+        zone: &str,
+        doc_type: &str,
+        psr_type: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<StoredPoint>>;
+
+    /// Newest document among the current points of any production type in `interval`
+    async fn latest_revision(
+        &self,
+        zone: &str,
+        doc_type: &str,
+        interval: TimeRange,
+    ) -> anyhow::Result<Option<DocumentMeta>>;
+}
+
+/// Store `document` unless its revision is already the newest over its interval.
+/// Returns whether anything was written.
+pub async fn store_if_changed(
+    storage: &dyn Storage,
+    document: &StoredDocument,
+) -> anyhow::Result<bool> {
+    let Some(interval) = document.interval() else {
+        return Ok(false);
+    };
+    let latest = storage
+        .latest_revision(&document.zone, &document.doc_type, interval)
+        .await?;
+    if latest == Some(document.meta) {
+        return Ok(false);
+    }
+
+    storage.store(document).await?;
+    Ok(true)
+}
+
+/// Generation and load documents behind a refreshed surplus series. A series without
+/// document metadata has nothing to tell revisions apart by and is not stored.
+pub fn documents_of(
+    zone: &str,
+    series: &SurplusSeries,
+    fetched_at: DateTime<Utc>,
+) -> Vec<StoredDocument> {
+    let document =
+        |doc_type: &str, meta: DocumentMeta, value: fn(&RenewableSurplus) -> f64| StoredDocument {
+            zone: zone.to_string(),
+            doc_type: doc_type.to_string(),
+            psr_type: String::new(),
+            meta,
+            fetched_at,
+            points: series
+                .points
+                .iter()
+                .map(|point| (point.timestamp, value(point)))
+                .collect(),
+        };
+
+    let mut documents = Vec::new();
+    if let Some(meta) = series.generation_doc_meta {
+        documents.push(document(GENERATION, meta, |point| point.generation));
+    }
+    if let Some(meta) = series.load_doc_meta {
+        documents.push(document(LOAD, meta, |point| point.load));
+    }
+    documents
+}
+
+/// Stored surplus of `zone` in `[start, end)`, at the timestamps with both a
+/// generation and a load point
+pub async fn surplus_history(
+    storage: &dyn Storage,
+    zone: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> anyhow::Result<Vec<RenewableSurplus>> {
+    let generation = storage.points(zone, GENERATION, "", start, end).await?;
+    let load: BTreeMap<DateTime<Utc>, f64> = storage
+        .points(zone, LOAD, "", start, end)
+        .await?
+        .into_iter()
+        .map(|point| (point.timestamp, point.value))
+        .collect();
+
+    Ok(generation
+        .into_iter()
+        .filter_map(|generation| {
+            let load = *load.get(&generation.timestamp)?;
+            Some(RenewableSurplus {
+                timestamp: generation.timestamp,
+                generation: generation.value,
+                load,
+                surplus: generation.value - load,
+                total_generation: None,
+            })
+        })
+        .collect())
+}
+
+/// Stored surplus of `zone` in `[start, end)` as it was forecast at `as_of`
+#[derive(Debug, Clone)]
+pub struct SurplusAsOf {
+    /// When the newest of the used documents was fetched
+    pub fetched_at: DateTime<Utc>,
+    pub points: Vec<RenewableSurplus>,
+}
+
+/// Values of a stored series from the newest revision fetched by `as_of`, with the
+/// fetch time of that revision
+async fn series_as_of(
+    storage: &dyn Storage,
+    zone: &str,
+    doc_type: &str,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+    as_of: DateTime<Utc>,
+) -> anyhow::Result<BTreeMap<DateTime<Utc>, StoredPoint>> {
+    let current = storage.points(zone, doc_type, "", start, end).await?;
+    let superseded = storage.superseded(zone, doc_type, "", start, end).await?;
+
+    let mut known: BTreeMap<DateTime<Utc>, StoredPoint> = BTreeMap::new();
+    for point in superseded.into_iter().chain(current) {
+        if point.fetched_at > as_of {
+            continue;
+        }
+        match known.get(&point.timestamp) {
+            Some(newest) if newest.meta >= point.meta => {}
+            _ => {
+                known.insert(point.timestamp, point);
+            }
+        }
+    }
+    Ok(known)
+}
+
+/// Surplus of `zone` in `[start, end)` from the generation and load revisions that were
+/// fetched by `as_of`, `None` when nothing of the interval was stored by then
+pub async fn surplus_as_of(
+    storage: &dyn Storage,
+    zone: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    as_of: DateTime<Utc>,
+) -> anyhow::Result<Option<SurplusAsOf>> {
+    let generation = series_as_of(storage, zone, GENERATION, (start, end), as_of).await?;
+    let load = series_as_of(storage, zone, LOAD, (start, end), as_of).await?;
+
+    let mut fetched_at = None;
+    let mut points = Vec::new();
+    for (timestamp, generation) in &generation {
+        let Some(load) = load.get(timestamp) else {
+            continue;
+        };
+        fetched_at = fetched_at.max(Some(generation.fetched_at.max(load.fetched_at)));
+        points.push(RenewableSurplus {
+            timestamp: *timestamp,
+            generation: generation.value,
+            load: load.value,
+            surplus: generation.value - load.value,
+            total_generation: None,
+        });
+    }
+
+    Ok(fetched_at.map(|fetched_at| SurplusAsOf { fetched_at, points }))
+}
+
+/// Drift of the stored generation forecast of `zone` over the UTC day `date`, `None`
+/// when the day was stored in a single revision only
+pub async fn revision_drift(
+    storage: &dyn Storage,
+    zone: &str,
+    date: NaiveDate,
+) -> anyhow::Result<Option<ForecastDrift>> {
+    let start = date.and_time(chrono::NaiveTime::MIN).and_utc();
+    let end = start + Duration::days(1);
+    let current = storage.points(zone, GENERATION, "", start, end).await?;
+    let superseded = storage.superseded(zone, GENERATION, "", start, end).await?;
+
+    let revisions: Vec<ForecastRevision> = superseded
+        .into_iter()
+        .chain(current)
+        .map(|point| ForecastRevision {
+            timestamp: point.timestamp,
+            meta: point.meta,
+            value: point.value,
+        })
+        .collect();
+    Ok(forecast_revision_drift(&revisions))
+}
+
+/// First stored value of each timestamp of a series in `[start, end)`
+async fn first_revisions(
+    storage: &dyn Storage,
+    zone: &str,
+    doc_type: &str,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+) -> anyhow::Result<BTreeMap<DateTime<Utc>, f64>> {
+    let current = storage.points(zone, doc_type, "", start, end).await?;
+    let superseded = storage.superseded(zone, doc_type, "", start, end).await?;
+
+    let mut first: BTreeMap<DateTime<Utc>, StoredPoint> = BTreeMap::new();
+    for point in superseded.into_iter().chain(current) {
+        match first.get_mut(&point.timestamp) {
+            Some(earlier) if earlier.meta <= point.meta => {}
+            Some(later) => *later = point,
+            None => {
+                first.insert(point.timestamp, point);
+            }
+        }
+    }
+    Ok(first
+        .into_iter()
+        .map(|(timestamp, point)| (timestamp, point.value))
+        .collect())
+}
+
+/// Surplus of `zone` in `[start, end)` as first forecast, before any revision
+pub async fn first_forecast_surplus(
+    storage: &dyn Storage,
+    zone: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> anyhow::Result<BTreeMap<DateTime<Utc>, f64>> {
+    let generation = first_revisions(storage, zone, GENERATION, (start, end)).await?;
+    let load = first_revisions(storage, zone, LOAD, (start, end)).await?;
+
+    Ok(generation
+        .into_iter()
+        .filter_map(|(timestamp, generation)| Some((timestamp, generation - load.get(&timestamp)?)))
+        .collect())
+}
+
+/// Store every refresh until `shutdown` turns true
+pub async fn record_refreshes(
+    storage: Arc<dyn Storage>,
+    mut events: broadcast::Receiver<RefreshEvent>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = shutdown.changed() => break,
+        };
+
+        match event {
+            Ok(event) => {
+                let Some(zone) = get_primary_zone(&event.country_code) else {
+                    continue;
+                };
+                for document in documents_of(zone.code, &event.series, event.refreshed_at) {
+                    if let Err(e) = store_if_changed(storage.as_ref(), &document).await {
+                        eprintln!(
+                            "Storing the {} forecast of {} failed: {}",
+                            document.doc_type, event.country_code, e
+                        );
+                    }
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                eprintln!("History writer skipped {} refresh events", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
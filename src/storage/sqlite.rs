@@ -0,0 +1,551 @@
+//! [`Storage`] in a SQLite database
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, Transaction, params};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::{Storage, StoredDocument, StoredPoint};
+use crate::entsoe::analysis::DocumentMeta;
+use crate::entsoe::request::TimeRange;
+
+/// Schema changes in order; the database records how many ran in `user_version`
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE documents (
+        id INTEGER PRIMARY KEY,
+        zone TEXT NOT NULL,
+        doc_type TEXT NOT NULL,
+        psr_type TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        revision INTEGER NOT NULL,
+        fetched_at INTEGER NOT NULL,
+        UNIQUE (zone, doc_type, psr_type, created_at, revision)
+    );
+    CREATE TABLE points (
+        document_id INTEGER NOT NULL REFERENCES documents (id) ON DELETE CASCADE,
+        timestamp INTEGER NOT NULL,
+        value REAL NOT NULL,
+        PRIMARY KEY (document_id, timestamp)
+    );
+    CREATE INDEX points_by_timestamp ON points (timestamp);
+"#,
+    // One current point per timestamp, replaced revisions move to `superseded_points`
+    r#"
+    CREATE TABLE forecast_points (
+        zone TEXT NOT NULL,
+        doc_type TEXT NOT NULL,
+        psr_type TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        value REAL NOT NULL,
+        created_at INTEGER NOT NULL,
+        revision INTEGER NOT NULL,
+        fetched_at INTEGER NOT NULL,
+        PRIMARY KEY (zone, doc_type, psr_type, timestamp)
+    );
+    CREATE TABLE superseded_points (
+        zone TEXT NOT NULL,
+        doc_type TEXT NOT NULL,
+        psr_type TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        value REAL NOT NULL,
+        created_at INTEGER NOT NULL,
+        revision INTEGER NOT NULL,
+        fetched_at INTEGER NOT NULL,
+        PRIMARY KEY (zone, doc_type, psr_type, timestamp, created_at, revision)
+    );
+    CREATE TEMP VIEW ranked_points AS
+        SELECT d.zone, d.doc_type, d.psr_type, p.timestamp, p.value,
+               d.created_at, d.revision, d.fetched_at,
+               ROW_NUMBER() OVER (
+                   PARTITION BY d.zone, d.doc_type, d.psr_type, p.timestamp
+                   ORDER BY d.created_at DESC, d.revision DESC
+               ) AS rank
+        FROM points p JOIN documents d ON d.id = p.document_id;
+    INSERT INTO forecast_points
+        SELECT zone, doc_type, psr_type, timestamp, value, created_at, revision, fetched_at
+        FROM ranked_points WHERE rank = 1;
+    INSERT INTO superseded_points
+        SELECT zone, doc_type, psr_type, timestamp, value, created_at, revision, fetched_at
+        FROM ranked_points WHERE rank > 1;
+    DROP VIEW ranked_points;
+    DROP TABLE points;
+    DROP TABLE documents;
+"#,
+];
+
+/// Columns of a point row, in the order [`point`] reads them
+const POINT_COLUMNS: &str = "timestamp, value, created_at, revision, fetched_at";
+
+/// Database access is short and local, so callers block on the connection directly
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Open or create the database at `path` and bring its schema up to date
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut connection: Connection) -> anyhow::Result<Self> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn query_points(
+        &self,
+        table: &str,
+        order: &str,
+        key: (&str, &str, &str),
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<StoredPoint>> {
+        let connection = self.connection.lock().unwrap();
+        let mut query = connection.prepare(&format!(
+            "SELECT {POINT_COLUMNS} FROM {table}
+             WHERE zone = ?1 AND doc_type = ?2 AND psr_type = ?3
+               AND timestamp >= ?4 AND timestamp < ?5
+             ORDER BY {order}"
+        ))?;
+        let (zone, doc_type, psr_type) = key;
+        let points = query
+            .query_map(
+                params![zone, doc_type, psr_type, start.timestamp(), end.timestamp()],
+                point,
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(points)
+    }
+}
+
+/// Run the migrations the database has not seen yet, each in its own transaction
+fn migrate(connection: &mut Connection) -> anyhow::Result<()> {
+    let applied: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", version as i64 + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn timestamp(seconds: i64) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::from_timestamp(seconds, 0).ok_or(rusqlite::Error::IntegralValueOutOfRange(0, seconds))
+}
+
+fn point(row: &Row) -> rusqlite::Result<StoredPoint> {
+    Ok(StoredPoint {
+        timestamp: timestamp(row.get(0)?)?,
+        value: row.get(1)?,
+        meta: DocumentMeta {
+            created_date_time: timestamp(row.get(2)?)?,
+            revision_number: row.get(3)?,
+        },
+        fetched_at: timestamp(row.get(4)?)?,
+    })
+}
+
+/// Upsert one point of `document`, keeping whichever of the stored and new point is
+/// older as superseded
+fn store_point(
+    transaction: &Transaction,
+    document: &StoredDocument,
+    point_time: DateTime<Utc>,
+    value: f64,
+) -> rusqlite::Result<()> {
+    let key = params![
+        document.zone,
+        document.doc_type,
+        document.psr_type,
+        point_time.timestamp()
+    ];
+    let current = transaction
+        .query_row(
+            &format!(
+                "SELECT {POINT_COLUMNS} FROM forecast_points
+                 WHERE zone = ?1 AND doc_type = ?2 AND psr_type = ?3 AND timestamp = ?4"
+            ),
+            key,
+            point,
+        )
+        .optional()?;
+
+    let incoming = StoredPoint {
+        timestamp: point_time,
+        value,
+        meta: document.meta,
+        fetched_at: document.fetched_at,
+    };
+    let (current, superseded) = match current {
+        None => (Some(incoming), None),
+        Some(current) if current.meta == incoming.meta => (Some(incoming), None),
+        Some(current) if current.meta < incoming.meta => (Some(incoming), Some(current)),
+        // An older revision arriving late only adds to the history
+        Some(_) => (None, Some(incoming)),
+    };
+
+    if let Some(point) = superseded {
+        insert_point(transaction, "superseded_points", document, &point)?;
+    }
+    if let Some(point) = current {
+        insert_point(transaction, "forecast_points", document, &point)?;
+    }
+    Ok(())
+}
+
+fn insert_point(
+    transaction: &Transaction,
+    table: &str,
+    document: &StoredDocument,
+    point: &StoredPoint,
+) -> rusqlite::Result<()> {
+    transaction.execute(
+        &format!(
+            "INSERT OR REPLACE INTO {table}
+             (zone, doc_type, psr_type, timestamp, value, created_at, revision, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+        ),
+        params![
+            document.zone,
+            document.doc_type,
+            document.psr_type,
+            point.timestamp.timestamp(),
+            point.value,
+            point.meta.created_date_time.timestamp(),
+            point.meta.revision_number,
+            point.fetched_at.timestamp(),
+        ],
+    )?;
+    Ok(())
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn store(&self, document: &StoredDocument) -> anyhow::Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for (point_time, value) in &document.points {
+            store_point(&transaction, document, *point_time, *value)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    async fn points(
+        &self,
+        zone: &str,
+        doc_type: &str,
+        psr_type: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<StoredPoint>> {
+        self.query_points(
+            "forecast_points",
+            "timestamp",
+            (zone, doc_type, psr_type),
+            start,
+            end,
+        )
+    }
+
+    async fn superseded(
+        &self,
+        zone: &str,
+        doc_type: &str,
+        psr_type: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<StoredPoint>> {
+        self.query_points(
+            "superseded_points",
+            "timestamp, created_at, revision",
+            (zone, doc_type, psr_type),
+            start,
+            end,
+        )
+    }
+
+    async fn latest_revision(
+        &self,
+        zone: &str,
+        doc_type: &str,
+        interval: TimeRange,
+    ) -> anyhow::Result<Option<DocumentMeta>> {
+        let connection = self.connection.lock().unwrap();
+        let latest = connection
+            .query_row(
+                "SELECT created_at, revision FROM forecast_points
+                 WHERE zone = ?1 AND doc_type = ?2 AND timestamp >= ?3 AND timestamp < ?4
+                 ORDER BY created_at DESC, revision DESC LIMIT 1",
+                params![
+                    zone,
+                    doc_type,
+                    interval.start.timestamp(),
+                    interval.end.timestamp()
+                ],
+                |row| {
+                    Ok(DocumentMeta {
+                        created_date_time: timestamp(row.get(0)?)?,
+                        revision_number: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(latest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entsoe::analysis::{RenewableSurplus, SurplusSeries};
+    use crate::storage::{GENERATION, LOAD, documents_of, store_if_changed, surplus_history};
+    use chrono::{Duration, TimeZone};
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    fn document(created_hour: u32, revision: u32, values: &[f64]) -> StoredDocument {
+        StoredDocument {
+            zone: "10Y1001A1001A83F".to_string(),
+            doc_type: GENERATION.to_string(),
+            psr_type: String::new(),
+            meta: DocumentMeta {
+                created_date_time: Utc
+                    .with_ymd_and_hms(2024, 5, 31, created_hour, 0, 0)
+                    .unwrap(),
+                revision_number: revision,
+            },
+            fetched_at: midnight(),
+            points: values
+                .iter()
+                .enumerate()
+                .map(|(hour, &value)| (midnight() + Duration::hours(hour as i64), value))
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_newest_document_wins_per_timestamp() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        storage
+            .store(&document(12, 1, &[1.0, 1.0, 1.0]))
+            .await
+            .unwrap();
+        // A later forecast covering the first two hours only
+        storage.store(&document(18, 1, &[2.0, 2.0])).await.unwrap();
+        storage.store(&document(18, 2, &[3.0])).await.unwrap();
+
+        let points = storage
+            .points(
+                "10Y1001A1001A83F",
+                GENERATION,
+                "",
+                midnight(),
+                midnight() + Duration::days(1),
+            )
+            .await
+            .unwrap();
+
+        let values: Vec<_> = points
+            .iter()
+            .map(|p| (p.value, p.meta.revision_number))
+            .collect();
+        assert_eq!(values, [(3.0, 2), (2.0, 1), (1.0, 1)]);
+        assert!(
+            storage
+                .points(
+                    "10Y1001A1001A83F",
+                    LOAD,
+                    "",
+                    midnight(),
+                    midnight() + Duration::days(1)
+                )
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_storing_a_revision_again_updates_it() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        storage.store(&document(12, 1, &[1.0])).await.unwrap();
+        storage.store(&document(12, 1, &[5.0, 6.0])).await.unwrap();
+
+        let points = storage
+            .points(
+                "10Y1001A1001A83F",
+                GENERATION,
+                "",
+                midnight(),
+                midnight() + Duration::days(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            points.iter().map(|p| p.value).collect::<Vec<_>>(),
+            [5.0, 6.0]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_newer_revision_supersedes_stored_points() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let end = midnight() + Duration::days(1);
+        storage
+            .store(&document(12, 1, &[1.0, 2.0, 3.0]))
+            .await
+            .unwrap();
+        storage
+            .store(&document(12, 2, &[1.0, 5.0, 6.0]))
+            .await
+            .unwrap();
+        // Revision 1 fetched again after revision 2 changes neither view
+        storage
+            .store(&document(12, 1, &[1.0, 2.0, 3.0]))
+            .await
+            .unwrap();
+
+        let latest = storage
+            .points("10Y1001A1001A83F", GENERATION, "", midnight(), end)
+            .await
+            .unwrap();
+        let superseded = storage
+            .superseded("10Y1001A1001A83F", GENERATION, "", midnight(), end)
+            .await
+            .unwrap();
+
+        let values = |points: &[StoredPoint]| {
+            points
+                .iter()
+                .map(|p| (p.value, p.meta.revision_number))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(&latest), [(1.0, 2), (5.0, 2), (6.0, 2)]);
+        assert_eq!(values(&superseded), [(1.0, 1), (2.0, 1), (3.0, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_revision_is_not_written_again() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let first = document(12, 1, &[1.0, 2.0]);
+        assert_eq!(
+            storage
+                .latest_revision("10Y1001A1001A83F", GENERATION, first.interval().unwrap())
+                .await
+                .unwrap(),
+            None
+        );
+
+        assert!(store_if_changed(&storage, &first).await.unwrap());
+        assert!(!store_if_changed(&storage, &first).await.unwrap());
+        assert_eq!(
+            storage
+                .latest_revision("10Y1001A1001A83F", GENERATION, first.interval().unwrap())
+                .await
+                .unwrap(),
+            Some(first.meta)
+        );
+
+        let second = document(12, 2, &[1.0, 3.0]);
+        assert!(store_if_changed(&storage, &second).await.unwrap());
+        assert_eq!(
+            storage
+                .latest_revision("10Y1001A1001A83F", LOAD, second.interval().unwrap())
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_migration_keeps_points_of_document_schema() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO documents VALUES (1, 'Z', 'A69', '', 100, 1, 100), (2, 'Z', 'A69', '', 100, 2, 200);
+                 INSERT INTO points VALUES (1, 0, 1.0), (1, 60, 2.0), (2, 0, 3.0);",
+            )
+            .unwrap();
+
+        migrate(&mut connection).unwrap();
+
+        let rows = |table: &str| {
+            connection
+                .prepare(&format!(
+                    "SELECT timestamp, value, revision FROM {table} ORDER BY timestamp"
+                ))
+                .unwrap()
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<(i64, f64, u32)>>>()
+                .unwrap()
+        };
+        assert_eq!(rows("forecast_points"), [(0, 3.0, 2), (60, 2.0, 1)]);
+        assert_eq!(rows("superseded_points"), [(0, 1.0, 1)]);
+    }
+
+    #[test]
+    fn test_migrations_run_once() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        migrate(&mut connection).unwrap();
+
+        let version: i64 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version as usize, MIGRATIONS.len());
+    }
+
+    #[tokio::test]
+    async fn test_surplus_history_from_refreshed_series() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let meta = DocumentMeta {
+            created_date_time: midnight(),
+            revision_number: 1,
+        };
+        let series = SurplusSeries {
+            points: (0..3)
+                .map(|hour| RenewableSurplus {
+                    timestamp: midnight() + Duration::hours(hour),
+                    generation: 100.0 + hour as f64,
+                    load: 80.0,
+                    surplus: 20.0 + hour as f64,
+                    total_generation: None,
+                })
+                .collect(),
+            generation_doc_meta: Some(meta),
+            load_doc_meta: Some(meta),
+            ..SurplusSeries::default()
+        };
+        for document in documents_of("10Y1001A1001A83F", &series, midnight()) {
+            storage.store(&document).await.unwrap();
+        }
+
+        let history = surplus_history(
+            &storage,
+            "10Y1001A1001A83F",
+            midnight() + Duration::hours(1),
+            midnight() + Duration::days(1),
+        )
+        .await
+        .unwrap();
+
+        let surplus: Vec<_> = history.iter().map(|p| p.surplus).collect();
+        assert_eq!(surplus, [21.0, 22.0]);
+    }
+}
@@ -0,0 +1,266 @@
+//! `educk surplus --watch`: poll the surplus forecast and print what changed
+
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+
+use crate::entsoe::EntsoeClient;
+use crate::entsoe::analysis::{Freshness, SurplusSeries, best_window, max_surplus};
+use crate::entsoe::areas::get_primary_zone;
+use crate::entsoe::window::TimeWindow;
+use crate::notify::{Notification, NotifyChannel, notify_all};
+
+/// How far ahead every poll fetches
+const WATCH_HORIZON_HOURS: i64 = 48;
+
+/// Shortest accepted polling interval; forecasts are published at most every 15 minutes
+pub const MIN_WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchArgs {
+    pub country_code: String,
+    pub interval: std::time::Duration,
+    pub freshness: Freshness,
+    /// Length of the best window to track
+    pub window: Duration,
+    /// Where to notify when the max surplus improves by more than `notify_threshold_mw`
+    pub notify: Vec<NotifyChannel>,
+    pub notify_threshold_mw: f64,
+}
+
+/// The parts of a surplus series the watch reports on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub max_surplus: Option<(DateTime<Utc>, f64)>,
+    pub best_window: Option<(DateTime<Utc>, f64)>,
+    pub forecast_created_at: Option<DateTime<Utc>>,
+}
+
+impl Snapshot {
+    pub fn of(series: &SurplusSeries, window: Duration) -> Self {
+        let max_surplus = max_surplus(&series.points).map(|point| (point.timestamp, point.surplus));
+
+        Self {
+            max_surplus,
+            best_window: best_window(&series.points, window),
+            forecast_created_at: series.forecast_created_at(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    MaxSurplus {
+        previous: Option<(DateTime<Utc>, f64)>,
+        current: Option<(DateTime<Utc>, f64)>,
+    },
+    BestWindow {
+        previous: Option<(DateTime<Utc>, f64)>,
+        current: Option<(DateTime<Utc>, f64)>,
+    },
+    ForecastCreated(Option<DateTime<Utc>>),
+}
+
+fn describe(value: &Option<(DateTime<Utc>, f64)>) -> String {
+    match value {
+        Some((timestamp, surplus)) => format!("{:.0} MW at {}", surplus, timestamp.to_rfc3339()),
+        None => "none".to_string(),
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::MaxSurplus { previous, current } => write!(
+                f,
+                "max surplus {} -> {}",
+                describe(previous),
+                describe(current)
+            ),
+            Change::BestWindow { previous, current } => write!(
+                f,
+                "best window {} -> {}",
+                describe(previous),
+                describe(current)
+            ),
+            Change::ForecastCreated(Some(created)) => {
+                write!(f, "forecast created at {}", created.to_rfc3339())
+            }
+            Change::ForecastCreated(None) => write!(f, "forecast creation time unknown"),
+        }
+    }
+}
+
+/// Changes from `previous` to `current`; everything is new on the first poll
+pub fn changes(previous: Option<&Snapshot>, current: &Snapshot) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let previous_max = previous.and_then(|p| p.max_surplus);
+    if previous.is_none() || previous_max != current.max_surplus {
+        changes.push(Change::MaxSurplus {
+            previous: previous_max,
+            current: current.max_surplus,
+        });
+    }
+
+    let previous_window = previous.and_then(|p| p.best_window);
+    if previous.is_none() || previous_window != current.best_window {
+        changes.push(Change::BestWindow {
+            previous: previous_window,
+            current: current.best_window,
+        });
+    }
+
+    if previous.map(|p| p.forecast_created_at) != Some(current.forecast_created_at) {
+        changes.push(Change::ForecastCreated(current.forecast_created_at));
+    }
+
+    changes
+}
+
+/// Whether the max surplus grew by more than `threshold_mw` since the previous poll
+pub fn max_surplus_improved(
+    previous: Option<&Snapshot>,
+    current: &Snapshot,
+    threshold_mw: f64,
+) -> bool {
+    match (previous.and_then(|p| p.max_surplus), current.max_surplus) {
+        (Some((_, previous)), Some((_, current))) => current - previous > threshold_mw,
+        _ => false,
+    }
+}
+
+async fn poll(client: &EntsoeClient, zone: &str, args: &WatchArgs) -> anyhow::Result<Snapshot> {
+    let now = Utc::now();
+    let window = TimeWindow::next_hours_from(now, WATCH_HORIZON_HOURS);
+    let (period_start, period_end) = window.extended_back(Duration::hours(1)).period();
+    let mut series = client
+        .get_surplus_series(zone, &period_start, &period_end, args.freshness)
+        .await?;
+    // Only what is still ahead matters for the maximum and the best window
+    series.retain_between(now, window.end);
+
+    Ok(Snapshot::of(&series, args.window))
+}
+
+/// Poll every `args.interval` and print the changes until Ctrl-C.
+/// Failed polls are logged and retried on the next tick.
+pub async fn watch(client: &EntsoeClient, args: &WatchArgs) -> anyhow::Result<()> {
+    let zone = get_primary_zone(&args.country_code)
+        .ok_or_else(|| anyhow::anyhow!("Unknown country code {:?}", args.country_code))?;
+    let notifiers = args
+        .notify
+        .iter()
+        .map(NotifyChannel::notifier)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut ticker = tokio::time::interval(args.interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut previous: Option<Snapshot> = None;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        let snapshot = tokio::select! {
+            snapshot = poll(client, zone.code, args) => snapshot,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                eprintln!("[{}] Refresh failed: {}", Utc::now().to_rfc3339(), e);
+                continue;
+            }
+        };
+
+        let now = Utc::now().to_rfc3339();
+        for change in changes(previous.as_ref(), &snapshot) {
+            println!("[{}] {}", now, change);
+        }
+
+        if !notifiers.is_empty()
+            && max_surplus_improved(previous.as_ref(), &snapshot, args.notify_threshold_mw)
+        {
+            let notification = Notification::new(&args.country_code, snapshot.max_surplus);
+            notify_all(&notifiers, &notification).await;
+        }
+
+        previous = Some(snapshot);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, hour, 0, 0).unwrap()
+    }
+
+    fn snapshot(max: f64, window_hour: u32, created_hour: u32) -> Snapshot {
+        Snapshot {
+            max_surplus: Some((at(12), max)),
+            best_window: Some((at(window_hour), max / 2.0)),
+            forecast_created_at: Some(at(created_hour)),
+        }
+    }
+
+    #[test]
+    fn test_first_poll_reports_everything() {
+        let changes = changes(None, &snapshot(5_000.0, 11, 6));
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            changes[0].to_string(),
+            "max surplus none -> 5000 MW at 2024-06-01T12:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_only_changes_are_reported() {
+        let previous = snapshot(5_000.0, 11, 6);
+        assert!(changes(Some(&previous), &previous).is_empty());
+
+        let mut shifted = previous.clone();
+        shifted.best_window = Some((at(13), 2_500.0));
+        assert_eq!(
+            changes(Some(&previous), &shifted),
+            [Change::BestWindow {
+                previous: Some((at(11), 2_500.0)),
+                current: Some((at(13), 2_500.0)),
+            }]
+        );
+
+        let mut republished = previous.clone();
+        republished.forecast_created_at = Some(at(9));
+        assert_eq!(
+            changes(Some(&previous), &republished),
+            [Change::ForecastCreated(Some(at(9)))]
+        );
+    }
+
+    #[test]
+    fn test_max_surplus_improved() {
+        let previous = snapshot(5_000.0, 11, 6);
+        assert!(!max_surplus_improved(None, &previous, 0.0));
+        assert!(max_surplus_improved(
+            Some(&previous),
+            &snapshot(5_600.0, 11, 9),
+            500.0
+        ));
+        assert!(!max_surplus_improved(
+            Some(&previous),
+            &snapshot(5_400.0, 11, 9),
+            500.0
+        ));
+        assert!(!max_surplus_improved(
+            Some(&previous),
+            &snapshot(4_000.0, 11, 9),
+            500.0
+        ));
+    }
+}
@@ -0,0 +1,29 @@
+//! Helpers shared by the integration tests
+
+use serde::Serialize;
+use std::path::Path;
+
+/// Compare `actual`, as pretty JSON, with the golden file at `path`, or rewrite the file
+/// with `UPDATE_GOLDEN=1`. `educk::server` has the same for its unit tests.
+pub fn assert_golden(path: impl AsRef<Path>, actual: &impl Serialize) {
+    let path = path.as_ref();
+    let actual = serde_json::to_string_pretty(actual).unwrap() + "\n";
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|_| {
+        panic!(
+            "{} does not exist, create it with UPDATE_GOLDEN=1",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "{} differs; rerun with UPDATE_GOLDEN=1 if the change is intended\n--- expected\n{}\n+++ actual\n{}",
+        path.display(),
+        expected,
+        actual
+    );
+}
@@ -0,0 +1,345 @@
+//! The core flows end to end: parsing ENTSO-E responses, computing the surplus, exporting
+//! it and answering API requests from it. Every scenario runs the same under each feature
+//! combination and compares with one golden file under `tests/fixtures/core_flows`, so a
+//! feature changing a shared answer fails here; code behind a feature has `#[cfg]` tests
+//! at the end. After an intended change, `UPDATE_GOLDEN=1 cargo test --test core_flows`
+//! rewrites the golden files.
+
+mod common;
+
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use educk::config::ServerConfig;
+use educk::entsoe::analysis::{
+    RenewableSurplus, SurplusSeries, best_window, generation_series, load_aggregation, max_surplus,
+    merge_forecasts, surplus_series,
+};
+use educk::entsoe::{
+    EntsoeClient, EntsoeError, ForecastSource, GlMarketDocument, Transport, TransportResponse,
+    parse_response,
+};
+use educk::export::{OutputFormat, SurplusRecord, write_records};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const GERMANY: &str = "10Y1001A1001A83F";
+/// The local day of Germany the fixtures cover
+const DAY_START: &str = "2024-06-01T22:00:00Z";
+const DAY_END: &str = "2024-06-02T22:00:00Z";
+/// Keys of API answers that differ between requests
+const VARYING_KEYS: [&str; 3] = ["as_of", "last_successful_fetch", "request_id"];
+
+fn fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn parsed(name: &str) -> GlMarketDocument {
+    parse_response(fixture(name).as_bytes()).unwrap()
+}
+
+/// Compare `actual` with the golden file of `scenario`, or rewrite it with
+/// `UPDATE_GOLDEN=1`
+fn assert_golden(scenario: &str, actual: &Value) {
+    common::assert_golden(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/core_flows")
+            .join(format!("{}.golden.json", scenario)),
+        actual,
+    );
+}
+
+/// Answers day-ahead requests with the fixture of their document type and everything
+/// else as ENTSO-E does without data
+struct FixtureTransport;
+
+fn query_param(url: &str, name: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| value.to_string())
+    })
+}
+
+fn fixture_response(url: &str) -> TransportResponse {
+    let document = match query_param(url, "documentType").as_deref() {
+        Some("A44") => Some("a44_day_ahead_prices.xml"),
+        Some(_) if query_param(url, "processType").as_deref() != Some("A01") => None,
+        Some("A65") => Some("a65_load_forecast.xml"),
+        Some("A69") => Some("a69_wind_solar_forecast.xml"),
+        Some("A71") => Some("a71_generation_forecast.xml"),
+        _ => None,
+    };
+    TransportResponse {
+        status: 200,
+        body: fixture(document.unwrap_or("acknowledgement_no_data.xml")),
+        retry_after: None,
+    }
+}
+
+#[async_trait]
+impl Transport for FixtureTransport {
+    async fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+        Ok(fixture_response(url))
+    }
+}
+
+/// Surplus of the German day of the fixtures, as the server computes it
+fn fixture_surplus() -> SurplusSeries {
+    let (generation, load) = (
+        parsed("a69_wind_solar_forecast.xml"),
+        parsed("a65_load_forecast.xml"),
+    );
+    let generation = [(ForecastSource::DayAhead, &generation)];
+    let load = [(ForecastSource::DayAhead, &load)];
+    let (load_filter, _) = load_aggregation(&load, GERMANY);
+    surplus_series(
+        &merge_forecasts(&generation, &generation_series(GERMANY)).unwrap(),
+        &merge_forecasts(&load, &load_filter).unwrap(),
+    )
+    .unwrap()
+}
+
+fn point_json(point: &RenewableSurplus) -> Value {
+    json!({
+        "timestamp": point.timestamp.to_rfc3339(),
+        "generation_mw": point.generation,
+        "load_mw": point.load,
+        "surplus_mw": point.surplus,
+    })
+}
+
+/// Status and body of the answer to a GET of `uri`
+async fn get(uri: &str) -> (StatusCode, String) {
+    let client = EntsoeClient::with_transport("test-token", Arc::new(FixtureTransport));
+    let app = educk::server::app(Some(Arc::new(client)), ServerConfig::default());
+    let response = app
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// The JSON answer to a GET of `uri`, what differs between requests masked
+async fn get_json(uri: &str) -> Value {
+    let (status, body) = get(uri).await;
+    assert_eq!(status, StatusCode::OK, "{}: {}", uri, body);
+    let mut value: Value = serde_json::from_str(&body).unwrap();
+    mask_varying(&mut value);
+    value
+}
+
+fn mask_varying(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if VARYING_KEYS.contains(&key.as_str()) && !value.is_null() {
+                    *value = Value::String("<varying>".to_string());
+                } else {
+                    mask_varying(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(mask_varying),
+        _ => {}
+    }
+}
+
+#[test]
+fn test_surplus_of_parsed_fixtures() {
+    let series = fixture_surplus();
+    let (start, mean) = best_window(&series.points, chrono::Duration::hours(3)).unwrap();
+
+    assert_golden(
+        "surplus",
+        &json!({
+            "unit": series.unit,
+            "generation_created_at": series.generation_doc_meta.map(|meta| meta.created_date_time.to_rfc3339()),
+            "load_created_at": series.load_doc_meta.map(|meta| meta.created_date_time.to_rfc3339()),
+            "points": series.points.iter().map(point_json).collect::<Vec<_>>(),
+            "max": max_surplus(&series.points).map(point_json),
+            "best_3h_window": { "start": start.to_rfc3339(), "mean_surplus_mw": mean },
+        }),
+    );
+}
+
+#[test]
+fn test_export_as_json_lines_and_csv() {
+    let records = SurplusRecord::from_series(GERMANY, &fixture_surplus());
+    let dir = std::env::temp_dir().join(format!("educk-{}-core-flows", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut exported = serde_json::Map::new();
+    for (format, file) in [
+        (OutputFormat::JsonLines, "surplus.jsonl"),
+        (OutputFormat::Csv, "surplus.csv"),
+    ] {
+        let path = dir.join(file);
+        write_records(&path, format, &records).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = text.lines().take(3).map(Value::from).collect();
+        exported.insert(
+            file.to_string(),
+            json!({ "lines": text.lines().count(), "head": lines }),
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_golden("export", &Value::Object(exported));
+}
+
+#[tokio::test]
+async fn test_series_answer() {
+    let uri = format!(
+        "/api/v1/renewable-surplus/DE/series?start={}&end={}",
+        DAY_START, DAY_END
+    );
+    assert_golden("series_answer", &get_json(&uri).await);
+}
+
+#[tokio::test]
+async fn test_plot_json_and_best_window_answers() {
+    let range = format!("start={}&end={}", DAY_START, DAY_END);
+    assert_golden(
+        "plot_json_answer",
+        &get_json(&format!(
+            "/api/v1/renewable-surplus/DE/plot-json?{}&unit=GW",
+            range
+        ))
+        .await,
+    );
+    assert_golden(
+        "best_window_answer",
+        &get_json(&format!(
+            "/api/v1/renewable-surplus/DE/best-window?{}&duration=2",
+            range
+        ))
+        .await,
+    );
+}
+
+#[tokio::test]
+async fn test_forecast_csv_answer() {
+    let (status, body) = get(&format!(
+        "/api/v1/renewable-surplus/DE/forecast.csv?kind=load&start={}&end={}",
+        DAY_START, DAY_END
+    ))
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let lines: Vec<&str> = body.lines().collect();
+    assert_golden(
+        "forecast_csv_answer",
+        &json!({ "lines": lines.len(), "head": &lines[..lines.len().min(5)] }),
+    );
+}
+
+#[tokio::test]
+async fn test_errors_are_answered_as_json() {
+    let (status, body) = get("/api/v1/renewable-surplus/XX/series").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let mut body: Value = serde_json::from_str(&body).unwrap();
+    mask_varying(&mut body);
+    assert_golden("unknown_country_answer", &body);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_surplus_history_round_trip() {
+    use educk::storage::sqlite::SqliteStorage;
+    use educk::storage::{Storage, documents_of, surplus_history};
+
+    let series = fixture_surplus();
+    let storage = SqliteStorage::open_in_memory().unwrap();
+    let fetched_at = series.points[0].timestamp;
+    for document in documents_of(GERMANY, &series, fetched_at) {
+        storage.store(&document).await.unwrap();
+    }
+
+    let (start, end) = (
+        series.points[0].timestamp,
+        series.points.last().unwrap().timestamp + chrono::Duration::hours(1),
+    );
+    let stored = surplus_history(&storage, GERMANY, start, end)
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), series.points.len());
+    for (stored, point) in stored.iter().zip(&series.points) {
+        assert_eq!(point_json(stored), point_json(point));
+    }
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_export_as_parquet() {
+    let records = SurplusRecord::from_series(GERMANY, &fixture_surplus());
+    let path =
+        std::env::temp_dir().join(format!("educk-{}-core-flows.parquet", std::process::id()));
+    write_records(&path, OutputFormat::Parquet, &records).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(bytes.starts_with(b"PAR1") && bytes.ends_with(b"PAR1"));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_blocking_client_parses_like_the_async_one() {
+    use educk::blocking;
+
+    struct BlockingFixtures;
+    impl blocking::Transport for BlockingFixtures {
+        fn get(&self, url: &str) -> Result<TransportResponse, EntsoeError> {
+            Ok(fixture_response(url))
+        }
+    }
+
+    let client = blocking::EntsoeClient::with_transport("test-token", Arc::new(BlockingFixtures));
+    let document = client
+        .fetch_day_ahead_total_load_forecast(GERMANY, "202406012200", "202406022200")
+        .unwrap();
+    let values = |document: &GlMarketDocument| -> Vec<(String, f64)> {
+        document
+            .all_timestamped_points()
+            .unwrap()
+            .into_iter()
+            .map(|point| (point.timestamp.to_rfc3339(), point.quantity))
+            .collect()
+    };
+    assert_eq!(values(&document), values(&parsed("a65_load_forecast.xml")));
+}
+
+#[cfg(feature = "mqtt")]
+#[test]
+fn test_refresh_messages_of_the_fixture_surplus() {
+    use educk::mqtt::surplus_messages;
+    use educk::refresher::RefreshEvent;
+
+    let series = fixture_surplus();
+    let now = series.points[0].timestamp;
+    let event = RefreshEvent {
+        country_code: "DE".to_string(),
+        series: Arc::new(series),
+        refreshed_at: now,
+    };
+    let topics: Vec<String> = surplus_messages("educk", &event, now)
+        .into_iter()
+        .map(|message| message.topic)
+        .collect();
+    assert_eq!(
+        topics,
+        [
+            "educk/DE/surplus/current",
+            "educk/DE/surplus/next_max",
+            "educk/DE/surplus/series"
+        ]
+    );
+}
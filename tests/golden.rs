@@ -0,0 +1,139 @@
+//! Parses every ENTSO-E response under `tests/fixtures` and compares the resulting points
+//! with the `.golden.json` next to it. After an intended change of the parsed output,
+//! `UPDATE_GOLDEN=1 cargo test --test golden` rewrites the golden files.
+
+mod common;
+
+use educk::entsoe::prices::PriceMarketDocument;
+use educk::entsoe::{EntsoeError, GlMarketDocument, TimestampedPoint, parse_response};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+/// Root element a fixture is parsed as
+#[derive(Clone, Copy)]
+enum Document {
+    Gl,
+    Prices,
+}
+
+const FIXTURES: [(&str, Document); 9] = [
+    ("a65_load_forecast", Document::Gl),
+    ("a65_dst_spring_forward", Document::Gl),
+    ("a65_dst_spring_forward_pt15m", Document::Gl),
+    ("a65_dst_fall_back_pt15m", Document::Gl),
+    ("a69_wind_solar_forecast", Document::Gl),
+    ("a71_generation_forecast", Document::Gl),
+    ("a75_actual_generation", Document::Gl),
+    ("a44_day_ahead_prices", Document::Prices),
+    ("acknowledgement_no_data", Document::Gl),
+];
+
+fn fixture_path(name: &str, extension: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.{}", name, extension))
+}
+
+fn points_json(points: &[TimestampedPoint]) -> Value {
+    points
+        .iter()
+        .map(|point| {
+            json!({
+                "start": point.timestamp.to_rfc3339(),
+                "end": point.end().to_rfc3339(),
+                "position": point.position,
+                "quantity": point.quantity,
+                "unit": point.unit,
+            })
+        })
+        .collect()
+}
+
+fn error_json(error: &EntsoeError) -> Value {
+    match error {
+        EntsoeError::InvalidResponse(_) => json!({ "error": "invalid_response" }),
+        EntsoeError::DocumentParsing { source, .. } => {
+            json!({ "error": "document_parsing", "cause": source.to_string() })
+        }
+        other => json!({ "error": other.to_string() }),
+    }
+}
+
+fn gl_json(document: &GlMarketDocument) -> Result<Value, EntsoeError> {
+    let mut series = Vec::new();
+    for time_series in &document.time_series {
+        series.push(json!({
+            "key": time_series.key().to_string(),
+            "points": points_json(&time_series.timestamped_points()?),
+        }));
+    }
+    Ok(json!({ "type": document.doc_type, "series": series }))
+}
+
+fn prices_json(document: &PriceMarketDocument) -> Result<Value, EntsoeError> {
+    let prices = document.prices()?;
+    let points: Value = prices
+        .points
+        .iter()
+        .map(|point| {
+            json!({
+                "start": point.timestamp.to_rfc3339(),
+                "end": point.end().to_rfc3339(),
+                "price": point.amount,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "type": document.doc_type,
+        "currency": prices.currency,
+        "points": points,
+    }))
+}
+
+/// What the golden file of a fixture holds: its points, or the error parsing it fails with
+fn parsed(name: &str, document: Document) -> Value {
+    let bytes = std::fs::read(fixture_path(name, "xml")).unwrap();
+    let result = match document {
+        Document::Gl => parse_response::<GlMarketDocument>(&bytes).and_then(|doc| gl_json(&doc)),
+        Document::Prices => {
+            parse_response::<PriceMarketDocument>(&bytes).and_then(|doc| prices_json(&doc))
+        }
+    };
+    result.unwrap_or_else(|e| error_json(&e))
+}
+
+#[test]
+fn test_fixtures_match_golden_files() {
+    for (name, document) in FIXTURES {
+        common::assert_golden(fixture_path(name, "golden.json"), &parsed(name, document));
+    }
+}
+
+#[test]
+fn test_dst_day_has_23_hours() {
+    let bytes = std::fs::read(fixture_path("a65_dst_spring_forward", "xml")).unwrap();
+    let document: GlMarketDocument = parse_response(&bytes).unwrap();
+    let points = document.all_timestamped_points().unwrap();
+
+    // Upstream intervals are UTC, the local day from 00:00 CET to 00:00 CEST
+    assert_eq!(points.len(), 23);
+    assert_eq!(
+        points[0].timestamp.to_rfc3339(),
+        "2024-03-30T23:00:00+00:00"
+    );
+    assert_eq!(points[22].end().to_rfc3339(), "2024-03-31T22:00:00+00:00");
+}
+
+#[test]
+fn test_acknowledgement_is_rejected() {
+    let bytes = std::fs::read(fixture_path("acknowledgement_no_data", "xml")).unwrap();
+    match parse_response::<GlMarketDocument>(&bytes) {
+        Err(EntsoeError::InvalidResponse(body)) => {
+            assert!(body.contains("No matching data found"))
+        }
+        other => panic!(
+            "expected the acknowledgement to be rejected, got {:?}",
+            other
+        ),
+    }
+}